@@ -16,6 +16,20 @@ fn test_read_small_reg_with_deleted() {
     for _key in ParserIterator::new(&parser).iter() {}
 }
 
+fn test_get_parent_keys(cell_cache_capacity: usize) {
+    let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+        .cell_cache_capacity(cell_cache_capacity)
+        .build()
+        .unwrap();
+    let keys: Vec<_> = ParserIterator::new(&parser).iter().collect();
+    for mut key in keys {
+        // walk up to the root key (whose path is `\<root name>`)
+        while key.path.rfind('\\') > Some(0) {
+            key = parser.get_parent_key(&mut key).unwrap().unwrap();
+        }
+    }
+}
+
 pub fn bench(c: &mut Criterion) {
     let mut group1 = c.benchmark_group("read small reg");
     group1
@@ -26,6 +40,13 @@ pub fn bench(c: &mut Criterion) {
             b.iter(test_read_small_reg_with_deleted)
         });
     group1.finish();
+
+    let mut group2 = c.benchmark_group("get parent keys");
+    group2
+        .sample_size(100)
+        .bench_function("without cell cache", |b| b.iter(|| test_get_parent_keys(0)))
+        .bench_function("with cell cache", |b| b.iter(|| test_get_parent_keys(1024)));
+    group2.finish();
 }
 
 criterion_group!(benches, bench);
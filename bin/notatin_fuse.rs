@@ -0,0 +1,461 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use clap::{arg, Arg, Command};
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    Request,
+};
+use notatin::{
+    cell_key_value::CellKeyValue,
+    cli_util::*,
+    err::Error,
+    parser::{Parser, ParserIterator},
+    parser_builder::ParserBuilder,
+};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// The hive is a snapshot, so the kernel can cache entries for as long as it likes
+const TTL: Duration = Duration::from_secs(3600);
+const ROOT_INO: u64 = 1;
+
+enum Content {
+    Directory(Vec<u64>),
+    File(Vec<u8>),
+    /// A file that isn't built until it's first looked up or read
+    Value(ValueRef),
+}
+
+/// Where to find the value behind a file: the path of its key and the offset of its cell. Only values that a lookup
+/// finds again are read lazily; recovered values, and values with earlier versions, are built when mounting.
+struct ValueRef {
+    key_path: Arc<str>,
+    offset: usize,
+    /// The `<name>.json` sidecar rather than the raw data
+    sidecar: bool,
+}
+
+struct Node {
+    name: String,
+    parent: u64,
+    mtime: SystemTime,
+    content: Content,
+}
+
+/// The inodes of the filesystem, as built by `Tree::new`
+#[derive(Default)]
+struct Tree {
+    /// Inode `n` is `nodes[n - 1]`
+    nodes: Vec<Node>,
+    names: HashMap<(u64, String), u64>,
+}
+
+impl Tree {
+    fn new(parser: &Parser) -> Self {
+        let mut tree = Self::default();
+        let mut key_inos: HashMap<Arc<str>, u64> = HashMap::new();
+        for key in ParserIterator::new(parser).iter() {
+            let mtime = SystemTime::from(key.last_key_written_date_and_time());
+            let ino = if tree.nodes.is_empty() {
+                tree.nodes.push(Node {
+                    name: String::new(),
+                    parent: ROOT_INO,
+                    mtime,
+                    content: Content::Directory(Vec::new()),
+                });
+                ROOT_INO
+            } else {
+                // Keys whose parent wasn't returned (such as recovered deleted keys) go under the root
+                let parent = key
+                    .path
+                    .rfind('\\')
+                    .and_then(|index| key_inos.get(&key.path[..index]))
+                    .copied()
+                    .unwrap_or(ROOT_INO);
+                tree.add(parent, &key.key_name, mtime, Content::Directory(Vec::new()))
+            };
+            key_inos.insert(key.path.clone(), ino);
+
+            for value in key.value_iter() {
+                let value_mtime = value.uwp_timestamp().map_or(mtime, SystemTime::from);
+                let lazy = !key.cell_state.is_deleted()
+                    && !value.cell_state.is_deleted()
+                    && value.versions.is_empty();
+                let content = |sidecar: bool| {
+                    if lazy {
+                        Content::Value(ValueRef {
+                            key_path: key.path.clone(),
+                            offset: value.file_offset_absolute,
+                            sidecar,
+                        })
+                    } else {
+                        Content::File(file_data(&value, sidecar))
+                    }
+                };
+                let value_ino =
+                    tree.add(ino, &value.detail.value_name(), value_mtime, content(false));
+                let sidecar_name = format!("{}.json", tree.nodes[value_ino as usize - 1].name);
+                tree.add(ino, &sidecar_name, value_mtime, content(true));
+            }
+        }
+        tree
+    }
+
+    /// Adds a node under `parent`, escaping its name and making it unique within the directory. Returns its inode.
+    fn add(&mut self, parent: u64, name: &str, mtime: SystemTime, content: Content) -> u64 {
+        let base_name = escape_name(name);
+        let mut name = base_name.clone();
+        let mut suffix = 2;
+        while self.names.contains_key(&(parent, name.clone())) {
+            name = format!("{}~{}", base_name, suffix);
+            suffix += 1;
+        }
+        let ino = self.nodes.len() as u64 + 1;
+        self.names.insert((parent, name.clone()), ino);
+        if let Some(Content::Directory(children)) = self
+            .nodes
+            .get_mut(parent as usize - 1)
+            .map(|node| &mut node.content)
+        {
+            children.push(ino);
+        }
+        self.nodes.push(Node {
+            name,
+            parent,
+            mtime,
+            content,
+        });
+        ino
+    }
+
+    fn node(&self, ino: u64) -> Option<&Node> {
+        self.nodes.get(usize::try_from(ino.checked_sub(1)?).ok()?)
+    }
+}
+
+/// A read-only view of a hive: keys are directories, and each value is a file holding its raw data alongside a
+/// `<name>.json` sidecar with its metadata (type, offsets, state, decoded content). Only the tree of names is built
+/// when mounting; the files are read from the hive as they are used.
+struct RegistryFs {
+    parser: Parser,
+    tree: Tree,
+}
+
+impl RegistryFs {
+    fn new(parser: Parser) -> Self {
+        let tree = Tree::new(&parser);
+        Self { parser, tree }
+    }
+
+    fn node(&self, ino: u64) -> Option<&Node> {
+        self.tree.node(ino)
+    }
+
+    /// Like `node`, but a file that hasn't been built yet is read from the hive first. A value that can't be found again
+    /// is an empty file.
+    fn load(&mut self, ino: u64) -> Option<&Node> {
+        let index = usize::try_from(ino.checked_sub(1)?).ok()?;
+        let data = match &self.tree.nodes.get(index)?.content {
+            Content::Value(value_ref) => Some(
+                self.parser
+                    .get_key(&value_ref.key_path, true)
+                    .ok()
+                    .flatten()
+                    .and_then(|key| {
+                        key.value_iter()
+                            .find(|value| value.file_offset_absolute == value_ref.offset)
+                    })
+                    .map(|value| file_data(&value, value_ref.sidecar))
+                    .unwrap_or_default(),
+            ),
+            _ => None,
+        };
+        if let Some(data) = data {
+            self.tree.nodes[index].content = Content::File(data);
+        }
+        self.tree.nodes.get(index)
+    }
+
+    fn attr(ino: u64, node: &Node, req: &Request<'_>) -> FileAttr {
+        let (kind, perm, size, nlink) = match &node.content {
+            Content::Directory(_) => (FileType::Directory, 0o555, 0, 2),
+            Content::File(data) => (FileType::RegularFile, 0o444, data.len() as u64, 1),
+            // not reached: `load` builds the file first
+            Content::Value(_) => (FileType::RegularFile, 0o444, 0, 1),
+        };
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: node.mtime,
+            mtime: node.mtime,
+            ctime: node.mtime,
+            crtime: node.mtime,
+            kind,
+            perm,
+            nlink,
+            uid: req.uid(),
+            gid: req.gid(),
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+}
+
+/// Returns the contents of the file for `value`: its raw data, or its metadata as JSON for the sidecar
+fn file_data(value: &CellKeyValue, sidecar: bool) -> Vec<u8> {
+    if sidecar {
+        serde_json::to_vec_pretty(value).unwrap_or_default()
+    } else {
+        value.get_data().to_vec()
+    }
+}
+
+/// Registry names can hold characters that aren't allowed in file names, so `%`, `/`, and nulls are percent-encoded;
+/// the default value is named `(default)`
+fn escape_name(name: &str) -> String {
+    match name {
+        "" => "(default)".to_string(),
+        "." => "%2E".to_string(),
+        ".." => "%2E%2E".to_string(),
+        _ => name
+            .replace('%', "%25")
+            .replace('/', "%2F")
+            .replace('\0', "%00"),
+    }
+}
+
+impl Filesystem for RegistryFs {
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let ino = name
+            .to_str()
+            .and_then(|name| self.tree.names.get(&(parent, name.to_string())))
+            .copied();
+        match ino.and_then(|ino| self.load(ino).map(|node| (ino, node))) {
+            Some((ino, node)) => reply.entry(&TTL, &Self::attr(ino, node, req), 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.load(ino) {
+            Some(node) => reply.attr(&TTL, &Self::attr(ino, node, req)),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.load(ino).map(|node| &node.content) {
+            Some(Content::File(data)) => {
+                let start = usize::try_from(offset).unwrap_or_default().min(data.len());
+                let end = start.saturating_add(size as usize).min(data.len());
+                reply.data(&data[start..end])
+            }
+            Some(Content::Directory(_)) => reply.error(libc::EISDIR),
+            Some(Content::Value(_)) | None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let node = match self.node(ino) {
+            Some(node) => node,
+            None => return reply.error(libc::ENOENT),
+        };
+        let children = match &node.content {
+            Content::Directory(children) => children,
+            Content::File(_) | Content::Value(_) => return reply.error(libc::ENOTDIR),
+        };
+        let entries = vec![
+            (ino, FileType::Directory, "."),
+            (node.parent, FileType::Directory, ".."),
+        ]
+        .into_iter()
+        .chain(children.iter().filter_map(|child| {
+            self.node(*child).map(|child_node| {
+                let kind = match child_node.content {
+                    Content::Directory(_) => FileType::Directory,
+                    Content::File(_) | Content::Value(_) => FileType::RegularFile,
+                };
+                (*child, kind, child_node.name.as_str())
+            })
+        }));
+        for (index, (entry_ino, kind, name)) in entries
+            .enumerate()
+            .skip(usize::try_from(offset).unwrap_or_default())
+        {
+            // The offset passed back in is that of the next entry to return
+            if reply.add(entry_ino, index as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let matches = Command::new("Notatin Registry FUSE")
+        .version("1.0.1")
+        .about("Mounts a registry hive as a read-only filesystem")
+        .arg(
+            Arg::new("input")
+                .short('i')
+                .long("input")
+                .help("Registry file to mount")
+                .required(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::new("mountpoint")
+                .short('m')
+                .long("mountpoint")
+                .help("Directory to mount the hive on")
+                .required(true)
+                .number_of_values(1),
+        )
+        .arg(arg!(
+            -r --recover "Recover deleted and versioned keys and values"
+        ))
+        .arg(arg!(
+            -s --"skip-logs" "Skip transaction log files"
+        ))
+        .get_matches();
+
+    let input = PathBuf::from(matches.get_one::<String>("input").expect("Required value"));
+    let mountpoint = matches
+        .get_one::<String>("mountpoint")
+        .expect("Required value");
+    let recover = matches.get_flag("recover");
+    let skip_logs = matches.get_flag("skip-logs");
+
+    let logs = get_log_files(
+        skip_logs,
+        &input.file_name().unwrap().to_string_lossy(),
+        &input,
+    );
+    let mut parser_builder = ParserBuilder::from_path(input);
+    parser_builder.recover_deleted(recover);
+    for log in logs.unwrap_or_default() {
+        parser_builder.with_transaction_log(log);
+    }
+    let fs = RegistryFs::new(parser_builder.build()?);
+
+    println!("Mounted on {} (unmount to exit)", mountpoint);
+    fuser::mount2(
+        fs,
+        mountpoint,
+        &[MountOption::RO, MountOption::FSName("notatin".to_string())],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notatin::cell_key_value::CellKeyValueDataTypes;
+    use notatin::writer::{HiveWriter, WriterKey, WriterValue};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_escape_name() {
+        assert_eq!("(default)", escape_name(""));
+        assert_eq!("%2E", escape_name("."));
+        assert_eq!("%2E%2E", escape_name(".."));
+        assert_eq!("...", escape_name("..."));
+        assert_eq!("a%2Fb%25c%00", escape_name("a/b%c\0"));
+        assert_eq!("Control Panel", escape_name("Control Panel"));
+    }
+
+    #[test]
+    fn test_tree() {
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("a/b")
+            .set_value(WriterValue::new(
+                "",
+                CellKeyValueDataTypes::REG_BIN,
+                vec![1, 2, 3],
+            ))
+            .set_value(WriterValue::new(
+                "(default)",
+                CellKeyValueDataTypes::REG_DWORD,
+                vec![4, 0, 0, 0],
+            ));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        let parser = ParserBuilder::from_file(Cursor::new(hive)).build().unwrap();
+        let mut fs = RegistryFs::new(parser);
+
+        let key_ino = fs.tree.names[&(ROOT_INO, "a%2Fb".to_string())];
+        let mut names: Vec<&str> = match &fs.node(key_ino).unwrap().content {
+            Content::Directory(children) => children
+                .iter()
+                .map(|child| fs.node(*child).unwrap().name.as_str())
+                .collect(),
+            _ => panic!("a key should be a directory"),
+        };
+        names.sort_unstable();
+        // the default value and a value named after it are both kept
+        assert_eq!(
+            vec![
+                "(default)",
+                "(default).json",
+                "(default)~2",
+                "(default)~2.json"
+            ],
+            names
+        );
+
+        // value files aren't built until they are used
+        let data_ino = fs.tree.names[&(key_ino, "(default)".to_string())];
+        let sidecar_ino = fs.tree.names[&(key_ino, "(default).json".to_string())];
+        assert!(matches!(
+            fs.node(data_ino).unwrap().content,
+            Content::Value(_)
+        ));
+        match &fs.load(data_ino).unwrap().content {
+            Content::File(data) => assert_eq!(&vec![1, 2, 3], data),
+            _ => panic!("the value should be loaded"),
+        }
+        match &fs.load(sidecar_ino).unwrap().content {
+            Content::File(data) => {
+                let json: serde_json::Value = serde_json::from_slice(data).unwrap();
+                assert_eq!("REG_BIN", json["data_type"]);
+            }
+            _ => panic!("the sidecar should be loaded"),
+        }
+    }
+}
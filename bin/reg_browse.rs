@@ -0,0 +1,586 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use clap::{arg, Arg, Command};
+use notatin::{
+    cell::CellState,
+    cell_key_node::CellKeyNode,
+    cell_key_value::CellKeyValue,
+    cli_util::*,
+    err::Error,
+    parser::{Parser, ParserIterator},
+    parser_builder::ParserBuilder,
+    util::{format_date_time, hex_dump},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind},
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+        ExecutableCommand,
+    },
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame, Terminal,
+};
+use std::io::{self, stdout};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Focus {
+    Keys,
+    Values,
+}
+
+struct SearchEntry {
+    key_path: Arc<str>,
+    value_name: Option<String>,
+    recovered: bool,
+}
+
+struct App {
+    parser: Parser,
+    root: CellKeyNode,
+    /// The keys below the root down to the current key
+    stack: Vec<CellKeyNode>,
+    sub_keys: Vec<CellKeyNode>,
+    values: Vec<CellKeyValue>,
+    key_state: ListState,
+    value_state: ListState,
+    focus: Focus,
+    show_recovered: bool,
+    /// The search being typed, if any
+    search_input: Option<String>,
+    last_search: String,
+    /// Every key path and value name in the hive, built on the first search
+    search_index: Option<Vec<SearchEntry>>,
+    search_pos: usize,
+    status: String,
+}
+
+impl App {
+    fn new(mut parser: Parser) -> Result<Self, Error> {
+        let root = parser.get_root_key()?.ok_or_else(|| Error::Any {
+            detail: "The hive has no root key".to_string(),
+        })?;
+        let mut app = Self {
+            parser,
+            root,
+            stack: Vec::new(),
+            sub_keys: Vec::new(),
+            values: Vec::new(),
+            key_state: ListState::default(),
+            value_state: ListState::default(),
+            focus: Focus::Keys,
+            show_recovered: false,
+            search_input: None,
+            last_search: String::new(),
+            search_index: None,
+            search_pos: 0,
+            status: String::new(),
+        };
+        app.refresh();
+        Ok(app)
+    }
+
+    fn is_visible(&self, cell_state: CellState) -> bool {
+        self.show_recovered || cell_state == CellState::Allocated
+    }
+
+    fn current(&self) -> &CellKeyNode {
+        self.stack.last().unwrap_or(&self.root)
+    }
+
+    /// Reloads the sub keys and values of the current key
+    fn refresh(&mut self) {
+        let mut current = self.current().clone();
+        let sub_keys = current.read_sub_keys(&mut self.parser);
+        self.sub_keys = sub_keys
+            .into_iter()
+            .filter(|key| self.is_visible(key.cell_state))
+            .collect();
+        self.values = current
+            .value_iter()
+            .filter(|value| self.is_visible(value.cell_state))
+            .collect();
+        let first_key = if self.sub_keys.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        let first_value = if self.values.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        self.key_state.select(first_key);
+        self.value_state.select(first_value);
+    }
+
+    fn enter(&mut self) {
+        let selected = self
+            .key_state
+            .selected()
+            .and_then(|index| self.sub_keys.get(index))
+            .cloned();
+        if let Some(key) = selected {
+            self.stack.push(key);
+            self.refresh();
+        }
+    }
+
+    fn leave(&mut self) {
+        if let Some(left) = self.stack.pop().map(|key| key.key_name) {
+            self.refresh();
+            let index = self.sub_keys.iter().position(|key| key.key_name == left);
+            self.key_state.select(index.or(self.key_state.selected()));
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let (state, len) = match self.focus {
+            Focus::Keys => (&mut self.key_state, self.sub_keys.len()),
+            Focus::Values => (&mut self.value_state, self.values.len()),
+        };
+        if len > 0 {
+            let index = state.selected().unwrap_or_default() as isize + delta;
+            state.select(Some(index.clamp(0, len as isize - 1) as usize));
+        }
+    }
+
+    fn build_search_index(&self) -> Vec<SearchEntry> {
+        let mut index = Vec::new();
+        for key in ParserIterator::new(&self.parser).iter() {
+            for value in key.value_iter() {
+                index.push(SearchEntry {
+                    key_path: key.path.clone(),
+                    value_name: Some(value.get_pretty_name()),
+                    recovered: key.cell_state != CellState::Allocated
+                        || value.cell_state != CellState::Allocated,
+                });
+            }
+            index.push(SearchEntry {
+                recovered: key.cell_state != CellState::Allocated,
+                key_path: key.path,
+                value_name: None,
+            });
+        }
+        index
+    }
+
+    /// Jumps to the next key or value after the last match whose path or name contains `last_search` (ignoring case)
+    fn search_next(&mut self) {
+        if self.last_search.is_empty() {
+            return;
+        }
+        let index = match self.search_index.take() {
+            Some(index) => index,
+            None => self.build_search_index(),
+        };
+        let needle = self.last_search.to_lowercase();
+        let found = (0..index.len())
+            .map(|offset| (self.search_pos + offset) % index.len())
+            .find(|&i| {
+                let entry = &index[i];
+                (self.show_recovered || !entry.recovered)
+                    && match &entry.value_name {
+                        Some(value_name) => value_name.to_lowercase().contains(&needle),
+                        None => entry.key_path.to_lowercase().contains(&needle),
+                    }
+            });
+        match found {
+            Some(i) => {
+                self.search_pos = i + 1;
+                let SearchEntry {
+                    key_path,
+                    value_name,
+                    ..
+                } = &index[i];
+                self.status = match self.navigate_to(key_path) {
+                    Ok(true) => format!("Found {}", key_path),
+                    _ => format!("Found {}, but it can't be reached from the root", key_path),
+                };
+                if let Some(value_name) = value_name {
+                    let position = self
+                        .values
+                        .iter()
+                        .position(|value| &value.get_pretty_name() == value_name);
+                    self.value_state.select(position);
+                    self.focus = Focus::Values;
+                } else {
+                    self.focus = Focus::Keys;
+                }
+            }
+            None => self.status = format!("No match for \"{}\"", self.last_search),
+        }
+        self.search_index = Some(index);
+    }
+
+    /// Opens the key at `key_path`, walking down from the root. Returns false if part of the path wasn't found.
+    fn navigate_to(&mut self, key_path: &str) -> Result<bool, Error> {
+        let mut stack: Vec<CellKeyNode> = Vec::new();
+        let mut found = true;
+        for name in key_path.split('\\').filter(|name| !name.is_empty()).skip(1) {
+            let mut current = stack.last().unwrap_or(&self.root).clone();
+            match self.parser.get_sub_key(&mut current, name)? {
+                Some(key) => stack.push(key),
+                None => {
+                    found = false;
+                    break;
+                }
+            }
+        }
+        self.stack = stack;
+        self.refresh();
+        Ok(found)
+    }
+
+    /// Handles a key press. Returns false to quit.
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        if let Some(input) = &mut self.search_input {
+            match code {
+                KeyCode::Enter => {
+                    self.last_search = self.search_input.take().unwrap_or_default();
+                    self.search_pos = 0;
+                    self.search_next();
+                }
+                KeyCode::Esc => self.search_input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return true;
+        }
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-20),
+            KeyCode::PageDown => self.move_selection(20),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') if self.focus == Focus::Keys => {
+                self.enter()
+            }
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => self.leave(),
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::Keys => Focus::Values,
+                    Focus::Values => Focus::Keys,
+                }
+            }
+            KeyCode::Char('d') => {
+                self.show_recovered = !self.show_recovered;
+                self.status = format!(
+                    "Recovered items {}",
+                    if self.show_recovered {
+                        "shown"
+                    } else {
+                        "hidden"
+                    }
+                );
+                self.refresh();
+            }
+            KeyCode::Char('/') => self.search_input = Some(String::new()),
+            KeyCode::Char('n') => self.search_next(),
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Min(3),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(30),
+                Constraint::Percentage(25),
+                Constraint::Percentage(45),
+            ])
+            .split(rows[1]);
+
+        let current = self.current();
+        frame.render_widget(
+            Paragraph::new(format!(
+                "{}  (last written {})",
+                current.path,
+                format_date_time(current.last_key_written_date_and_time())
+            )),
+            rows[0],
+        );
+
+        let keys: Vec<ListItem> = self
+            .sub_keys
+            .iter()
+            .map(|key| ListItem::new(label(&key.key_name, key.cell_state)))
+            .collect();
+        frame.render_stateful_widget(
+            List::new(keys)
+                .block(pane("Keys", self.focus == Focus::Keys))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            columns[0],
+            &mut self.key_state,
+        );
+
+        let values: Vec<ListItem> = self
+            .values
+            .iter()
+            .map(|value| ListItem::new(label(&value.get_pretty_name(), value.cell_state)))
+            .collect();
+        frame.render_stateful_widget(
+            List::new(values)
+                .block(pane("Values", self.focus == Focus::Values))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            columns[1],
+            &mut self.value_state,
+        );
+
+        let detail = self
+            .value_state
+            .selected()
+            .and_then(|index| self.values.get(index))
+            .map(value_detail)
+            .unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(detail)
+                .block(pane("Data", false))
+                .wrap(Wrap { trim: false }),
+            columns[2],
+        );
+
+        let footer = match &self.search_input {
+            Some(input) => format!("Search: {}_", input),
+            None if !self.status.is_empty() => self.status.clone(),
+            None => "q: quit  enter/←/→: open/close key  tab: switch pane  /: search  n: next match  d: toggle recovered items".to_string(),
+        };
+        frame.render_widget(Paragraph::new(footer), rows[2]);
+    }
+}
+
+fn pane(title: &str, focused: bool) -> Block<'_> {
+    let block = Block::default().borders(Borders::ALL).title(title);
+    if focused {
+        block.border_style(Style::default().add_modifier(Modifier::BOLD))
+    } else {
+        block
+    }
+}
+
+fn label(name: &str, cell_state: CellState) -> String {
+    match cell_state {
+        CellState::Allocated => name.to_string(),
+        CellState::ModifiedTransactionLog => format!("{} [modified]", name),
+        _ => format!("{} [deleted]", name),
+    }
+}
+
+fn value_detail(value: &CellKeyValue) -> Vec<Line<'static>> {
+    let (content, _) = value.get_content();
+    let data = value.get_data();
+    let mut lines = vec![
+        Line::from(format!("Name:   {}", value.get_pretty_name())),
+        Line::from(format!("Type:   {:?}", value.data_type)),
+        Line::from(format!("State:  {:?}", value.cell_state)),
+        Line::from(format!("Offset: {:#x}", value.file_offset_absolute)),
+        Line::from(format!("Size:   {} bytes", data.len())),
+        Line::from(""),
+        Line::from(content.to_string()),
+        Line::from(""),
+    ];
+    lines.extend(hex_dump(data).into_iter().map(Line::from));
+    lines
+}
+
+/// Leaves raw mode and the alternate screen
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    stdout().execute(LeaveAlternateScreen)?;
+    Ok(())
+}
+
+fn run(app: &mut App) -> Result<(), Error> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.status.clear();
+                if !app.handle_key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let matches = Command::new("Notatin Registry Browser")
+        .version("1.0.1")
+        .arg(
+            Arg::new("input")
+                .short('i')
+                .long("input")
+                .help("Registry file to browse")
+                .required(true)
+                .number_of_values(1),
+        )
+        .arg(arg!(
+            -s --"skip-logs" "Skip transaction log files"
+        ))
+        .get_matches();
+
+    let input = PathBuf::from(matches.get_one::<String>("input").expect("Required value"));
+    let skip_logs = matches.get_flag("skip-logs");
+
+    let logs = get_log_files(
+        skip_logs,
+        &input.file_name().unwrap().to_string_lossy(),
+        &input,
+    );
+    let mut parser_builder = ParserBuilder::from_path(input);
+    // Recovered items are always read so they can be toggled on and off while browsing
+    parser_builder.recover_deleted(true);
+    for log in logs.unwrap_or_default() {
+        parser_builder.with_transaction_log(log);
+    }
+    let mut app = App::new(parser_builder.build()?)?;
+
+    // A panic would otherwise leave the terminal in raw mode, with the message hidden on the alternate screen
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
+
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    let result = run(&mut app);
+    restore_terminal()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notatin::cell_key_value::CellKeyValueDataTypes;
+    use notatin::cell_value::CellValue;
+    use notatin::writer::{HiveWriter, WriterKey, WriterValue};
+    use std::io::Cursor;
+
+    fn test_app() -> App {
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("Software\\Vendor")
+            .set_value(WriterValue::from_content(
+                "InstallPath",
+                CellKeyValueDataTypes::REG_SZ,
+                &CellValue::String("C:\\Vendor".to_string()),
+            ));
+        root.get_or_add_sub_key("System");
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        App::new(ParserBuilder::from_file(Cursor::new(hive)).build().unwrap()).unwrap()
+    }
+
+    fn sub_key_names(app: &App) -> Vec<&str> {
+        app.sub_keys
+            .iter()
+            .map(|key| key.key_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut app = test_app();
+        assert_eq!(vec!["Software", "System"], sub_key_names(&app));
+
+        // leaving the root does nothing
+        assert!(app.handle_key(KeyCode::Left));
+        assert_eq!("ROOT", app.current().key_name);
+
+        app.handle_key(KeyCode::Enter);
+        assert_eq!("Software", app.current().key_name);
+        assert_eq!(vec!["Vendor"], sub_key_names(&app));
+        app.handle_key(KeyCode::Enter);
+        assert_eq!("Vendor", app.current().key_name);
+        assert_eq!(1, app.values.len());
+
+        // going back up selects the key that was left
+        app.handle_key(KeyCode::Left);
+        app.handle_key(KeyCode::Left);
+        assert_eq!("ROOT", app.current().key_name);
+        assert_eq!(Some(0), app.key_state.selected());
+        app.handle_key(KeyCode::Down);
+        assert_eq!(Some(1), app.key_state.selected());
+        app.handle_key(KeyCode::Down);
+        assert_eq!(Some(1), app.key_state.selected());
+
+        assert!(!app.handle_key(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn test_search() {
+        let mut app = test_app();
+        for c in "/path".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        assert_eq!(Some("path"), app.search_input.as_deref());
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(None, app.search_input);
+        assert_eq!("Vendor", app.current().key_name);
+        assert_eq!(2, app.stack.len());
+        assert!(app.focus == Focus::Values);
+        assert_eq!(Some(0), app.value_state.selected());
+
+        app.last_search = "nothing like it".to_string();
+        app.search_next();
+        assert_eq!("No match for \"nothing like it\"", app.status);
+        // the index is kept for the next search
+        assert!(app.search_index.is_some());
+    }
+
+    #[test]
+    fn test_value_detail() {
+        let mut app = test_app();
+        assert!(app.navigate_to("\\ROOT\\Software\\Vendor").unwrap());
+        let lines: Vec<String> = value_detail(&app.values[0])
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect()
+            })
+            .collect();
+        assert_eq!("Name:   InstallPath", lines[0]);
+        assert_eq!("Type:   REG_SZ", lines[1]);
+        assert_eq!("C:\\Vendor", lines[6]);
+        assert_eq!(hex_dump(app.values[0].get_data()), lines[8..].to_vec());
+        assert_eq!("x [deleted]", label("x", CellState::DeletedPrimaryFile));
+        assert_eq!(
+            "x [modified]",
+            label("x", CellState::ModifiedTransactionLog)
+        );
+    }
+}
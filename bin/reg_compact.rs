@@ -0,0 +1,94 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use clap::{arg, Arg, Command};
+use notatin::{
+    cli_util::*,
+    compact::{self, HiveSpaceUsage},
+    err::Error,
+    parser_builder::ParserBuilder,
+};
+use std::path::PathBuf;
+
+fn main() -> Result<(), Error> {
+    let matches = Command::new("Notatin Registry Compact")
+        .version("1.0.1")
+        .arg(
+            Arg::new("input")
+                .short('i')
+                .long("input")
+                .help("Registry file to compact")
+                .required(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("Output file for the compacted hive")
+                .required(true)
+                .number_of_values(1),
+        )
+        .arg(arg!(
+            -s --"skip-logs" "Skip transaction log files"
+        ))
+        .get_matches();
+
+    let input = PathBuf::from(matches.get_one::<String>("input").expect("Required value"));
+    let output = matches.get_one::<String>("output").expect("Required value");
+    let skip_logs = matches.get_flag("skip-logs");
+
+    let logs = get_log_files(
+        skip_logs,
+        &input.file_name().unwrap().to_string_lossy(),
+        &input,
+    );
+    let mut parser_builder = ParserBuilder::from_path(input);
+    for log in logs.unwrap_or_default() {
+        parser_builder.with_transaction_log(log);
+    }
+    let mut parser = parser_builder.build()?;
+
+    let (hive, report) = compact::compact(&mut parser)?;
+    std::fs::write(output, hive)?;
+
+    print_usage("Original", report.original_file_size, &report.original);
+    print_usage("Compacted", report.compacted_file_size, &report.compacted);
+    let saved_percent = match report.original_file_size {
+        0 => 0.0,
+        size => report.bytes_saved() as f64 * 100.0 / size as f64,
+    };
+    println!(
+        "Saved {} bytes ({:.1}%)",
+        report.bytes_saved(),
+        saved_percent
+    );
+    Ok(())
+}
+
+fn print_usage(label: &str, file_size: usize, usage: &HiveSpaceUsage) {
+    println!(
+        "{}: {} bytes; {} hive bins ({} bytes); {} allocated cells ({} bytes); {} free cells ({} bytes)",
+        label,
+        file_size,
+        usage.hive_bins,
+        usage.hive_bins_size,
+        usage.allocated_cells,
+        usage.allocated_cells_size,
+        usage.free_cells,
+        usage.free_cells_size
+    );
+}
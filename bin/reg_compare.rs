@@ -14,7 +14,6 @@
  * limitations under the License.
  */
 
-use blake3::Hash;
 use clap::{arg, Arg, Command};
 use itertools::{EitherOrBoth, Itertools};
 use notatin::{
@@ -23,13 +22,14 @@ use notatin::{
     cli_util::*,
     err::Error,
     filter::{Filter, FilterBuilder},
+    hive_diff::HiveDiff,
     log::Logs,
-    parser::{Parser, ParserIterator},
+    parser::Parser,
     parser_builder::ParserBuilder,
+    progress,
     util::format_date_time,
 };
 use std::{
-    collections::HashMap,
     fmt::Debug,
     fs::File,
     io::{BufWriter, Write},
@@ -52,7 +52,7 @@ fn main() -> Result<(), Error> {
         .short('c')
         .long("compare")
         .help("Registry file or root folder to search for comparison")
-        .required(true)
+        .required_unless_present("pending")
         .number_of_values(1))
     .arg(Arg::new("output")
         .short('o')
@@ -70,12 +70,21 @@ fn main() -> Result<(), Error> {
     .arg(arg!(
         -s --"skip-logs" "Skip transaction log files"
     ))
+    .arg(arg!(
+        -p --pending "Compare the base hive as-is with its transaction log updated state (what the pending transactions would change)"
+    ).conflicts_with_all(["compare", "skip-logs"]))
     .get_matches();
 
+    let pending = matches.get_flag("pending");
     let base = matches.get_one::<String>("base").expect("Required value");
-    let compare = matches
-        .get_one::<String>("compare")
-        .expect("Required value");
+    // In pending mode the hive is compared with itself, with its transaction logs applied
+    let compare = if pending {
+        base
+    } else {
+        matches
+            .get_one::<String>("compare")
+            .expect("Required value")
+    };
 
     let output: &str = matches.get_one::<String>("output").expect("Required value");
 
@@ -101,6 +110,7 @@ fn main() -> Result<(), Error> {
             filter,
             use_diff_format,
             skip_logs,
+            pending,
         )
     } else {
         process_files(
@@ -110,6 +120,7 @@ fn main() -> Result<(), Error> {
             filter,
             use_diff_format,
             skip_logs,
+            pending,
         )
     }
 }
@@ -121,12 +132,13 @@ fn process_files<T>(
     filter: Option<Filter>,
     use_diff_format: bool,
     skip_logs: bool,
+    pending: bool,
 ) -> Result<(), Error>
 where
     T: AsRef<Path> + Debug,
 {
     let base_logs = get_log_files(
-        skip_logs,
+        skip_logs || pending,
         &base.file_name().unwrap().to_string_lossy(),
         &base,
     );
@@ -135,6 +147,10 @@ where
         &comparison.file_name().unwrap().to_string_lossy(),
         &comparison,
     );
+    if pending && comp_logs.as_ref().map_or(true, Vec::is_empty) {
+        println!("No transaction logs found for {:?}", comparison);
+        return Ok(());
+    }
 
     reg_compare(
         &outpath,
@@ -154,6 +170,7 @@ fn process_folders<T>(
     filter: Option<Filter>,
     use_diff_format: bool,
     skip_logs: bool,
+    pending: bool,
 ) -> Result<(), Error>
 where
     T: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>,
@@ -185,8 +202,11 @@ where
                         if comparison_path_to_find.is_file()
                             && file_has_size(&comparison_path_to_find)
                         {
-                            let base_logs = get_log_files(skip_logs, f, entry.path());
+                            let base_logs = get_log_files(skip_logs || pending, f, entry.path());
                             let comp_logs = get_log_files(skip_logs, f, &comparison_path_to_find);
+                            if pending && comp_logs.as_ref().map_or(true, Vec::is_empty) {
+                                continue;
+                            }
                             let outpath =
                                 get_outpath(primary_path_from_base, &outfolder, use_diff_format);
                             if let Err(e) = reg_compare(
@@ -254,96 +274,14 @@ where
 
     println!("Comparing {:?} and {:?}", base_primary, comparison_primary);
 
-    let mut original_map: HashMap<(String, Option<String>), Option<Hash>> = HashMap::new();
-
     let mut parser1 = get_parser(base_primary, base_logs)?;
-    let (k_total, _) = parser1.count_all_keys_and_values(filter.as_ref());
-    let mut k_added = 0;
-
-    let mut iter = ParserIterator::new(&parser1);
-    if let Some(f) = &filter {
-        iter.with_filter(f.clone());
-    }
-    for key in iter.iter() {
-        let path = &key.path;
-        original_map.insert((path.clone(), None), key.hash);
-        for value in key.value_iter() {
-            original_map.insert((path.clone(), Some(value.detail.value_name())), value.hash);
-        }
-        k_added += 1;
-        if k_added % 1000 == 0 {
-            update_parsed_keys(k_added, k_total);
-        }
-    }
-    update_parsed_keys(k_added, k_total);
-
-    let mut keys_added = Vec::new();
-    let mut keys_modified = Vec::new();
-    let mut keys_deleted = Vec::new();
-    let mut values_added = Vec::new();
-    let mut values_modified = Vec::new();
-    let mut values_deleted = Vec::new();
-
-    // For each item in comparison, see if it's in original_map
-    //   If missing, it's new
-    //   If present, compare the hash
-    //     If same, it's a match (ignore it)
-    //     If different, it's an update
-
     let parser2 = get_parser(comparison_primary, comparison_logs)?;
-    let (k_total, _) = parser2.count_all_keys_and_values(filter.as_ref());
-    let mut k_added = 0;
-    let mut iter = ParserIterator::new(&parser2);
-    if let Some(f) = filter {
-        iter.with_filter(f);
-    }
-    for key in iter.iter() {
-        let path = &key.path;
-
-        match original_map.remove(&(path.clone(), None)) {
-            Some(val) => {
-                if val != key.hash {
-                    let original_key = parser1.get_key(path, true);
-                    keys_modified.push((original_key.unwrap().unwrap(), key.clone()));
-                }
-            }
-            None => keys_added.push(key.clone()),
-        }
-
-        for value in key.value_iter() {
-            match original_map.remove(&(path.clone(), Some(value.detail.value_name().clone()))) {
-                Some(val) => {
-                    if val != value.hash {
-                        let original_key = parser1.get_key(&key.path, true).unwrap().unwrap();
-                        let original_value =
-                            original_key.get_value(&value.detail.value_name()).unwrap();
-                        values_modified.push((path.clone(), original_value, value));
-                    }
-                }
-                None => values_added.push((path.clone(), value)),
-            }
-        }
-        k_added += 1;
-        if k_added % 100 == 0 {
-            update_keys_compared(k_added, k_total);
-        }
-    }
-    update_keys_compared(k_added, k_total);
-
-    // Any items remaining in original_map were deleted (not present in file2)
-    for remaining in original_map {
-        match remaining.0 .1 {
-            None => {
-                let original_key = parser1.get_key(&remaining.0 .0, true).unwrap().unwrap();
-                keys_deleted.push(original_key)
-            }
-            Some(val) => {
-                let original_key = parser1.get_key(&remaining.0 .0, true).unwrap().unwrap();
-                let original_value = original_key.get_value(&val).unwrap();
-                values_deleted.push((original_key.path, original_value))
-            }
-        };
-    }
+    let diff = HiveDiff::compare(
+        &mut parser1,
+        &parser2,
+        filter.as_ref(),
+        &mut *progress::new(true),
+    )?;
 
     (if use_diff_format {
         write_diff
@@ -353,12 +291,12 @@ where
         &mut writer,
         &base_filenames,
         &comparison_filenames,
-        keys_deleted,
-        keys_added,
-        keys_modified,
-        values_deleted,
-        values_added,
-        values_modified,
+        diff.keys_deleted,
+        diff.keys_added,
+        diff.keys_modified,
+        diff.values_deleted,
+        diff.values_added,
+        diff.values_modified,
     )?;
 
     Ok(())
@@ -647,14 +585,6 @@ fn get_parser(primary: PathBuf, logs: Option<Vec<PathBuf>>) -> Result<Parser, Er
     parser_builder.build()
 }
 
-fn update_parsed_keys(k_added: usize, k_total: usize) {
-    println!("{}/{} keys parsed from base", k_added, k_total);
-}
-
-fn update_keys_compared(k_added: usize, k_total: usize) {
-    println!("{}/{} keys compared", k_added, k_total);
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -14,31 +14,59 @@
  * limitations under the License.
  */
 
-pub mod common_writer;
-pub mod json_writer;
-pub mod tsv_writer;
+pub mod profile_writer;
+pub mod progress_bar;
 pub mod xlsx_writer;
 
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::*;
+use std::sync::{Arc, Mutex};
 
+use chrono::FixedOffset;
 use clap::builder::{EnumValueParser, PossibleValue};
+use clap::error::ErrorKind;
 use clap::{arg, Arg, Command, ValueEnum};
 use notatin::{
+    cell_key_node::CellKeyNode,
     cli_util::*,
     err::Error,
+    export::{
+        common_writer::WriteCommon,
+        graph_writer::{GraphFormat, WriteGraph},
+        json_schema::{JsonSchema, SerializationOptions},
+        json_writer::WriteJson,
+        jsonl_writer::Compression,
+        manifest::Manifest,
+        tsv_writer::WriteTsv,
+    },
     filter::{Filter, FilterBuilder},
+    log::{ParseWarning, ParseWarnings},
+    name_anomalies::{AnomalyReport, AnomalyReportBuilder},
+    parser::{KeySort, Parser, ParserIterator},
     parser_builder::ParserBuilder,
-    progress,
+    path_mapping::PathMapping,
+    payload::Payload,
+    plugins::{
+        amcache::Amcache, autoruns::Autoruns, network::Network, profiles::UserProfiles, sam::Sam,
+        services::Services, shell_bags::ShellBags, user_assist::UserAssist,
+    },
+    progress::{self, Progress, ProgressHandler},
+    suspicious_name::SuspiciousName,
+    timestamp_format::{TimestampFormat, TimestampStyle},
+    transaction_log::TransactionLogInfo,
+    value_dedup::DedupReport,
 };
+use serde::Serialize;
 use walkdir::WalkDir;
 
-use common_writer::WriteCommon;
-use json_writer::WriteJson;
-use tsv_writer::WriteTsv;
+use profile_writer::WriteProfile;
+use progress_bar::ProgressBar;
 use xlsx_writer::WriteXlsx;
 
 fn main() -> Result<(), Error> {
-    let matches = Command::new("Notatin Registry Dump")
+    let mut command = Command::new("Notatin Registry Dump")
         .version("1.0.1")
         .arg(
             Arg::new("input")
@@ -72,29 +100,202 @@ fn main() -> Result<(), Error> {
         .arg(arg!(
             --"recovered-only" "Only export recovered items (applicable to tsv and xlsx output)"
         ))
+        .arg(arg!(
+            --"annotate-recovered" "Annotate recovered items with where they were recovered from (applicable to common output)"
+        ))
         .arg(arg!(
             --"full-field-info" "Get the offset and length for each key/value field (applicable to jsonl output)"
         ))
+        .arg(arg!(
+            --"include-slack" "Include the hex encoded slack of each key's and value's cells (applicable to jsonl output with the v1 schema)"
+        ))
+        .arg(
+            arg!(--schema [VERSION] "Layout of the records: v1 (the default) or v2, which has snake_case names, flattened warnings, plain JSON value data, and recovered and source annotations (applicable to jsonl output)")
+                .value_parser(EnumValueParser::<SchemaArg>::new())
+                .ignore_case(true),
+        )
+        .arg(
+            arg!(--include [FIELDS] "Optional fields to add to v2 records, comma separated")
+                .value_parser(EnumValueParser::<FieldSet>::new())
+                .value_delimiter(',')
+                .ignore_case(true),
+        )
+        .arg(
+            arg!(--exclude [FIELDS] "Optional fields to leave out of v2 records, comma separated")
+                .value_parser(EnumValueParser::<FieldSet>::new())
+                .value_delimiter(',')
+                .ignore_case(true),
+        )
         .arg(arg!(
             -s --"skip-logs" "Skip transaction log files"
         ))
+        .arg(arg!(
+            --"log-info" "Print the header and log entries (sequence numbers and dirty pages) of each transaction log, even with --skip-logs"
+        ))
         .arg(arg!(
             -f --filter [STRING] "Key path for filter (ex: 'ControlSet001\\Services')"
         ))
-        .get_matches();
+        .arg(
+            arg!(--profile [PROFILE] "Decode a well-known artifact into normalized records (output is jsonl)")
+                .value_parser(EnumValueParser::<Profile>::new())
+                .ignore_case(true),
+        )
+        .arg(
+            arg!(--compress [FORMAT] "Compress the output (applicable to jsonl, tsv, common, dot, and graphml output)")
+                .value_parser(EnumValueParser::<Compress>::new())
+                .ignore_case(true),
+        )
+        .arg(
+            arg!(--"timestamp-format" [FORMAT] "Format of timestamps (applicable to tsv, xlsx, dot, and graphml output)")
+                .value_parser(EnumValueParser::<TimestampArg>::new())
+                .ignore_case(true),
+        )
+        .arg(
+            arg!(--timezone [OFFSET] "UTC offset for ISO-8601 timestamps, ex: '+02:00' (applicable to tsv, xlsx, dot, and graphml output)")
+                .value_parser(|s: &str| {
+                    TimestampFormat::parse_timezone(s).ok_or("expected a UTC offset such as +02:00")
+                }),
+        )
+        .arg(
+            arg!(--"graph-depth" [LEVELS] "Leave out keys more than this many levels below the root key (applicable to dot and graphml output)")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(--sort [ORDER] "Write keys sorted by path or by last written timestamp rather than in hive order (applicable to jsonl, tsv, xlsx, and common output)")
+                .value_parser(EnumValueParser::<SortArg>::new())
+                .ignore_case(true),
+        )
+        .arg(arg!(
+            --"full-paths" "Write full key paths (ex: 'HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft') rather than paths within the hive. The key each hive is mounted at is told from its file name unless --mount-point is given"
+        ))
+        .arg(
+            arg!(--"mount-point" [KEY] "Key the hive is mounted at for --full-paths (ex: 'HKEY_USERS\\S-1-5-21-...')")
+                .requires("full-paths"),
+        )
+        .arg(
+            arg!(--"split-by-root" "Write one output file per top-level key, named after the key (not applicable to profiles)")
+                .conflicts_with_all(["filter", "profile"]),
+        )
+        .arg(arg!(
+            --"value-stats" "Add the entropy and file type (zlib, gzip, PNG, OLE, PE, etc.) of binary values (applicable to jsonl output)"
+        ))
+        .arg(arg!(
+            --"detect-payloads" "Flag values holding PE images (as is, embedded, or base64 encoded) or high entropy blobs"
+        ))
+        .arg(arg!(
+            --"detect-suspicious-names" "Flag keys whose names look made to hide them (empty, padded with whitespace, invisible or lookalike characters, ROT13 encoded); adds a column to tsv output"
+        ))
+        .arg(arg!(
+            --"guid-names" "Add the name of the class (registered in the hive's Classes tree) or known folder to keys and values named after its GUID (applicable to jsonl output)"
+        ))
+        .arg(arg!(
+            --"warnings-output" [FILE] "Write parse warnings (with severity, code, offset, and key path) to a jsonl file"
+        ))
+        .arg(arg!(
+            --"dedup-output" [FILE] "Write the data blobs (of at least 16 bytes) held by more than one value, with their paths, and the data sizes before and after deduplication to a jsonl file"
+        ))
+        .arg(arg!(
+            --"anomaly-output" [FILE] "Write key and value name statistics (lengths and characters) and the names that stand out (too long, control characters, rare characters) to a jsonl file"
+        ))
+        .arg(arg!(
+            --manifest "Write a manifest (input and output hashes, transaction logs applied, version, filter, counts, and warnings) next to each output, as <output>.manifest.json"
+        ));
+    let matches = command.get_matches_mut();
 
     let input = matches.get_one::<String>("input").expect("Required value");
     let output = matches.get_one::<String>("output").expect("Required value");
     let recurse = matches.get_flag("recurse");
     let recover = matches.get_flag("recover");
     let skip_logs = matches.get_flag("skip-logs");
+    let log_info = matches.get_flag("log-info");
     let recovered_only = matches.get_flag("recovered-only");
+    let annotate_recovered = matches.get_flag("annotate-recovered");
+    let split_by_root = matches.get_flag("split-by-root");
+    let full_paths = matches.get_flag("full-paths");
+    let mount_point = matches.get_one::<String>("mount-point").map(String::as_str);
     let get_full_field_info = matches.get_flag("full-field-info");
-    let output_type = *matches
-        .get_one::<OutputType>("TYPE")
-        .expect("Unrecognized value");
+    let include_slack = matches.get_flag("include-slack");
+    let schema = match matches.get_one::<SchemaArg>("schema") {
+        Some(SchemaArg::V2) => {
+            if include_slack {
+                command
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        "--include-slack isn't supported with --schema v2",
+                    )
+                    .exit();
+            }
+            let mut options = SerializationOptions::default();
+            for (arg, include) in [("include", true), ("exclude", false)].iter() {
+                for field in matches.get_many::<FieldSet>(arg).into_iter().flatten() {
+                    field.set(&mut options, *include);
+                }
+            }
+            JsonSchema::V2(options)
+        }
+        Some(SchemaArg::V1) | None => {
+            if let Some(arg) = ["include", "exclude"]
+                .iter()
+                .find(|arg| matches.contains_id(arg))
+            {
+                command
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!("--{} only applies to --schema v2", arg),
+                    )
+                    .exit();
+            }
+            JsonSchema::V1
+        }
+    };
+    let value_stats = matches.get_flag("value-stats");
+    let detect_payloads = matches.get_flag("detect-payloads");
+    let detect_suspicious_names = matches.get_flag("detect-suspicious-names");
+    let guid_names = matches.get_flag("guid-names");
+    let manifest = matches.get_flag("manifest");
+    let profile = matches.get_one::<Profile>("profile").copied();
+    let compression = match matches.get_one::<Compress>("compress") {
+        Some(Compress::Gzip) => Compression::Gzip,
+        Some(Compress::Zstd) => Compression::Zstd,
+        None => Compression::None,
+    };
+    let timestamp_format = TimestampFormat::new(
+        match matches.get_one::<TimestampArg>("timestamp-format") {
+            Some(TimestampArg::Epoch) => TimestampStyle::EpochSeconds,
+            Some(TimestampArg::Filetime) => TimestampStyle::FileTime,
+            Some(TimestampArg::Iso8601) | None => TimestampStyle::Iso8601,
+        },
+        matches.get_one::<FixedOffset>("timezone").copied(),
+    );
+    let key_sort = matches.get_one::<SortArg>("sort").map(|sort| match sort {
+        SortArg::Path => KeySort::Path,
+        SortArg::Timestamp => KeySort::Timestamp,
+    });
+    let output_type = match profile {
+        Some(_) => OutputType::Jsonl,
+        None => *matches
+            .get_one::<OutputType>("TYPE")
+            .expect("Unrecognized value"),
+    };
+    let graph_depth = matches.get_one::<usize>("graph-depth").copied();
+
+    let mut reports = Reports {
+        warnings: match matches.get_one::<String>("warnings-output") {
+            Some(f) => Some(BufWriter::new(File::create(f)?)),
+            None => None,
+        },
+        dedup: match matches.get_one::<String>("dedup-output") {
+            Some(f) => Some(BufWriter::new(File::create(f)?)),
+            None => None,
+        },
+        anomaly: match matches.get_one::<String>("anomaly-output") {
+            Some(f) => Some(BufWriter::new(File::create(f)?)),
+            None => None,
+        },
+    };
 
-    let filter = match matches.get_one::<String>("filter") {
+    let filter_path = matches.get_one::<String>("filter").map(String::as_str);
+    let filter = match filter_path {
         Some(f) => Some(
             FilterBuilder::new()
                 .add_key_path(f)
@@ -104,68 +305,118 @@ fn main() -> Result<(), Error> {
         None => None,
     };
 
+    let options = DumpOptions {
+        filter,
+        filter_path,
+        recover,
+        recovered_only,
+        annotate_recovered,
+        get_full_field_info,
+        include_slack,
+        schema,
+        value_stats,
+        detect_payloads,
+        detect_suspicious_names,
+        guid_names,
+        timestamp_format,
+        key_sort,
+        full_paths,
+        mount_point,
+        split_by_root,
+        skip_logs,
+        log_info,
+        output_type,
+        graph_depth,
+        profile,
+        compression,
+        manifest,
+    };
+
     if recurse {
         process_folder(
             &PathBuf::from(output),
             &PathBuf::from(input),
-            filter,
-            recover,
-            recovered_only,
-            get_full_field_info,
-            skip_logs,
-            output_type,
-        )
+            &options,
+            &mut reports,
+        )?;
     } else {
         process_file(
             &PathBuf::from(output),
             PathBuf::from(input),
-            filter,
-            recover,
-            recovered_only,
-            get_full_field_info,
-            skip_logs,
-            output_type,
-        )
+            &options,
+            &mut reports,
+        )?;
     }
+    reports.flush()
 }
 
-fn process_file(
-    outpath: &PathBuf,
-    input: PathBuf,
+/// The options of a run, shared by every hive it dumps
+struct DumpOptions<'a> {
     filter: Option<Filter>,
+    filter_path: Option<&'a str>,
     recover: bool,
     recovered_only: bool,
+    annotate_recovered: bool,
     get_full_field_info: bool,
+    include_slack: bool,
+    schema: JsonSchema,
+    value_stats: bool,
+    detect_payloads: bool,
+    detect_suspicious_names: bool,
+    guid_names: bool,
+    timestamp_format: TimestampFormat,
+    key_sort: Option<KeySort>,
+    full_paths: bool,
+    mount_point: Option<&'a str>,
+    split_by_root: bool,
     skip_logs: bool,
+    log_info: bool,
     output_type: OutputType,
+    graph_depth: Option<usize>,
+    profile: Option<Profile>,
+    compression: Compression,
+    manifest: bool,
+}
+
+/// The report files of a run, which every hive it dumps adds to (see `--warnings-output`, `--dedup-output`, and
+/// `--anomaly-output`)
+struct Reports {
+    warnings: Option<BufWriter<File>>,
+    dedup: Option<BufWriter<File>>,
+    anomaly: Option<BufWriter<File>>,
+}
+
+impl Reports {
+    fn flush(&mut self) -> Result<(), Error> {
+        for output in [&mut self.warnings, &mut self.dedup, &mut self.anomaly].iter_mut() {
+            if let Some(output) = output {
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn process_file(
+    outpath: &PathBuf,
+    input: PathBuf,
+    options: &DumpOptions,
+    reports: &mut Reports,
 ) -> Result<(), Error> {
     let logs = get_log_files(
-        skip_logs,
+        options.skip_logs,
         &input.file_name().unwrap().to_string_lossy(),
         &input,
     );
 
-    reg_dump(
-        input,
-        &PathBuf::from(outpath),
-        logs,
-        filter,
-        recover,
-        recovered_only,
-        get_full_field_info,
-        output_type,
-    )
+    reg_dump(input, &PathBuf::from(outpath), logs, options, reports)
 }
 
 fn process_folder(
     outfolder: &PathBuf,
     base: &PathBuf,
-    filter: Option<Filter>,
-    recover: bool,
-    recovered_only: bool,
-    get_full_field_info: bool,
-    skip_logs: bool,
-    output_type: OutputType,
+    options: &DumpOptions,
+    reports: &mut Reports,
 ) -> Result<(), Error> {
     let reg_files = vec![
         "sam",
@@ -174,6 +425,7 @@ fn process_folder(
         "system",
         "default",
         "amcache",
+        "amcache.hve",
         "ntuser.dat",
         "usrclass.dat",
     ];
@@ -189,17 +441,19 @@ fn process_folder(
                 match entry.path().strip_prefix(base) {
                     Err(e) => println!("{:?}", e),
                     Ok(primary_path_from_base) => {
-                        let logs = get_log_files(skip_logs, f, entry.path());
-                        let outpath = get_outpath(primary_path_from_base, outfolder, &output_type);
+                        let logs = get_log_files(options.skip_logs, f, entry.path());
+                        let outpath = get_outpath(
+                            primary_path_from_base,
+                            outfolder,
+                            &options.output_type,
+                            options.compression,
+                        );
                         let _ = reg_dump(
                             PathBuf::from(entry.path()),
                             &outpath,
                             logs,
-                            filter.clone(),
-                            recover,
-                            recovered_only,
-                            get_full_field_info,
-                            output_type,
+                            options,
+                            reports,
                         );
                     }
                 }
@@ -209,7 +463,12 @@ fn process_folder(
     Ok(())
 }
 
-fn get_outpath<T>(primary_path_from_base: &Path, outfolder: T, output_type: &OutputType) -> PathBuf
+fn get_outpath<T>(
+    primary_path_from_base: &Path,
+    outfolder: T,
+    output_type: &OutputType,
+    compression: Compression,
+) -> PathBuf
 where
     T: AsRef<Path> + std::convert::AsRef<std::ffi::OsStr>,
 {
@@ -218,54 +477,505 @@ where
     let mut output_path = Path::new(&outfolder).join(output_filename);
     match output_type {
         OutputType::Xlsx => output_path.set_extension("xlsx"),
-        OutputType::Tsv => output_path.set_extension("tsv"),
-        OutputType::Common => output_path.set_extension("txt"),
-        _ => output_path.set_extension("jsonl"),
+        OutputType::Tsv => output_path.set_extension(compressed_extension("tsv", compression)),
+        OutputType::Common => output_path.set_extension(compressed_extension("txt", compression)),
+        OutputType::Dot => output_path.set_extension(compressed_extension("dot", compression)),
+        OutputType::GraphMl => {
+            output_path.set_extension(compressed_extension("graphml", compression))
+        }
+        _ => output_path.set_extension(compressed_extension("jsonl", compression)),
     };
     output_path
 }
 
+fn write_keys(
+    parser: &Parser,
+    output: &Path,
+    filter: Option<Filter>,
+    options: &DumpOptions,
+    console: &mut Box<dyn progress::UpdateProgressTrait>,
+) -> Result<(), Error> {
+    let output_type = options.output_type;
+    let compression = options.compression;
+    if output_type == OutputType::Xlsx {
+        WriteXlsx::new(output, options.recovered_only)?
+            .timestamp_format(options.timestamp_format)
+            .write(parser, filter)
+    } else if output_type == OutputType::Tsv {
+        WriteTsv::new_compressed(output, options.recovered_only, compression)?
+            .timestamp_format(options.timestamp_format)
+            .write(parser, filter)
+    } else if output_type == OutputType::Common {
+        WriteCommon::new_compressed(output, compression)?
+            .annotate_recovered(options.annotate_recovered)
+            .write(parser, filter)
+    } else if output_type == OutputType::Dot || output_type == OutputType::GraphMl {
+        let format = if output_type == OutputType::Dot {
+            GraphFormat::Dot
+        } else {
+            GraphFormat::GraphMl
+        };
+        let mut writer = WriteGraph::new_compressed(output, format, compression)?;
+        if let Some(graph_depth) = options.graph_depth {
+            writer.max_depth(graph_depth);
+        }
+        writer.timestamp_format(options.timestamp_format);
+        writer.write(parser, filter)
+    } else if options.include_slack {
+        WriteJson::write_with_slack(output, parser, filter, console, compression)
+    } else {
+        WriteJson::write_schema(output, parser, filter, console, compression, options.schema)
+    }
+}
+
+/// The name of the `--split-by-root` file holding the root key, its values, and the recovered keys that hang from the
+/// root key (including the ones whose parent is unknown)
+const SPLIT_ROOT_NAME: &str = "(root)";
+
+/// Returns `output` with `_<name>` inserted before its extensions (ex: `out.jsonl.gz` becomes `out_Software.jsonl.gz`).
+/// `name` is one of `split_names`.
+fn get_split_outpath(output: &Path, name: &str) -> PathBuf {
+    let file_name = output
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (stem, extensions) = match file_name.find('.') {
+        Some(index) if index > 0 => file_name.split_at(index),
+        _ => (file_name.as_str(), ""),
+    };
+    output.with_file_name(format!("{}_{}{}", stem, name, extensions))
+}
+
+/// Returns the names to add to the `--split-by-root` output files of `key_names`: characters file names can't hold are
+/// replaced with '_', and names that then match one before them (ignoring case, as Windows does) or `SPLIT_ROOT_NAME`
+/// get a `~2`, `~3`... suffix
+fn split_names(key_names: &[String]) -> Vec<String> {
+    let mut used = HashSet::new();
+    used.insert(SPLIT_ROOT_NAME.to_lowercase());
+    key_names
+        .iter()
+        .map(|key_name| {
+            let base_name: String = key_name
+                .chars()
+                .map(|c| {
+                    if c.is_control() || "\\/:*?\"<>|".contains(c) {
+                        '_'
+                    } else {
+                        c
+                    }
+                })
+                .collect();
+            let mut name = base_name.clone();
+            let mut suffix = 2;
+            while !used.insert(name.to_lowercase()) {
+                name = format!("{}~{}", base_name, suffix);
+                suffix += 1;
+            }
+            name
+        })
+        .collect()
+}
+
+/// Returns `output` with `.manifest.json` appended (ex: `out.jsonl` becomes `out.jsonl.manifest.json`)
+fn get_manifest_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".manifest.json");
+    PathBuf::from(path)
+}
+
+fn print_log_info(log: &Path) {
+    let info = match TransactionLogInfo::from_path(log) {
+        Ok(info) => info,
+        Err(e) => {
+            println!("Unable to read transaction log {:?} ({})", log, e);
+            return;
+        }
+    };
+    println!(
+        "Transaction log {:?}: sequence numbers {}/{}, last written {}, hive bins data size {}, checksum {}",
+        log,
+        info.base_block.primary_sequence_number,
+        info.base_block.secondary_sequence_number,
+        info.base_block.last_modification_date_and_time,
+        info.base_block.hive_bins_data_size,
+        if info.base_block_checksum_is_valid {
+            "valid"
+        } else {
+            "invalid"
+        }
+    );
+    for entry in &info.entries {
+        println!(
+            "  Log entry at offset {}: sequence number {}, hive bins data size {}, {} dirty pages{}",
+            entry.file_offset_absolute,
+            entry.sequence_number,
+            entry.hive_bins_data_size,
+            entry.dirty_pages_count,
+            if entry.has_valid_hashes {
+                ""
+            } else {
+                " (invalid hashes; not applied)"
+            }
+        );
+        for page in &entry.dirty_pages {
+            println!("    Page at offset {}, {} bytes", page.offset, page.size);
+        }
+    }
+}
+
+fn compressed_extension(extension: &str, compression: Compression) -> String {
+    match compression.extension() {
+        Some(compressed) => format!("{}.{}", extension, compressed),
+        None => extension.to_string(),
+    }
+}
+
 fn reg_dump(
     input: PathBuf,
     output: &PathBuf,
     logs: Option<Vec<PathBuf>>,
-    filter: Option<Filter>,
-    recover: bool,
-    recovered_only: bool,
-    get_full_field_info: bool,
-    output_type: OutputType,
+    options: &DumpOptions,
+    reports: &mut Reports,
 ) -> Result<(), Error> {
-    let mut parser_builder = ParserBuilder::from_path(input);
-    parser_builder.update_console(true);
-    parser_builder.recover_deleted(recover);
-    parser_builder.get_full_field_info(get_full_field_info);
-    for log in logs.unwrap_or_default() {
-        parser_builder.with_transaction_log(log);
+    let hive = input.to_string_lossy().into_owned();
+    let logs = logs.unwrap_or_default();
+    if options.log_info {
+        let file_name = input
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        for log in get_log_files(false, &file_name, &input).unwrap_or_default() {
+            print_log_info(&log);
+        }
+    }
+    let progress_bar = Arc::new(ProgressBar::new());
+    let mut parser_builder = ParserBuilder::from_path(input.clone());
+    let handler = progress_bar.clone();
+    parser_builder.progress_handler(move |progress: &Progress| handler.on_progress(progress));
+    parser_builder.recover_deleted(options.recover);
+    parser_builder.get_full_field_info(options.get_full_field_info);
+    parser_builder.value_stats(options.value_stats);
+    parser_builder.detect_payloads(options.detect_payloads);
+    parser_builder.detect_suspicious_names(options.detect_suspicious_names);
+    parser_builder.guid_names(options.guid_names);
+    if let Some(key_sort) = options.key_sort {
+        parser_builder.sort_keys(key_sort);
+    }
+    if options.full_paths {
+        let file_name = input
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match options.mount_point
+            .map(PathMapping::new)
+            .or_else(|| PathMapping::from_file_name(&file_name))
+        {
+            Some(path_mapping) => {
+                parser_builder.full_paths(path_mapping);
+            }
+            None => println!(
+                "Unable to tell where {} is mounted (use --mount-point); writing paths within the hive",
+                hive
+            ),
+        }
+    }
+    for log in &logs {
+        parser_builder.with_transaction_log(log.clone());
     }
-    let parser = parser_builder.build()?;
+    let mut parser = parser_builder.build()?;
+    progress_bar.finish();
 
-    let mut console = progress::new(true);
-    console.write("Writing file")?;
+    // Progress is shown by `progress_bar`, so the writers don't print their own
+    let mut console = progress::new(false);
+    let mut outputs = Vec::new();
+    let findings =
+        Findings::new(&parser, options, reports).map(|findings| Arc::new(Mutex::new(findings)));
 
-    if output_type == OutputType::Xlsx {
-        WriteXlsx::new(output, recovered_only)?.write(&parser, filter)?;
-    } else if output_type == OutputType::Tsv {
-        WriteTsv::new(output, recovered_only)?.write(&parser, filter)?;
-    } else if output_type == OutputType::Common {
-        WriteCommon::new(output)?.write(&parser, filter)?;
+    let compression = options.compression;
+    if let Some(profile) = options.profile {
+        let progress_bar = &*progress_bar;
+        match profile {
+            Profile::Amcache => {
+                WriteProfile::write(output, &mut parser, Amcache {}, progress_bar, compression)?
+            }
+            Profile::UserAssist => WriteProfile::write(
+                output,
+                &mut parser,
+                UserAssist {},
+                progress_bar,
+                compression,
+            )?,
+            Profile::ShellBags => {
+                WriteProfile::write(output, &mut parser, ShellBags {}, progress_bar, compression)?
+            }
+            Profile::Network => {
+                WriteProfile::write(output, &mut parser, Network {}, progress_bar, compression)?
+            }
+            Profile::Services => {
+                WriteProfile::write(output, &mut parser, Services {}, progress_bar, compression)?
+            }
+            Profile::Autoruns => {
+                WriteProfile::write(output, &mut parser, Autoruns {}, progress_bar, compression)?
+            }
+            Profile::Sam => {
+                WriteProfile::write(output, &mut parser, Sam {}, progress_bar, compression)?
+            }
+            Profile::Profiles => WriteProfile::write(
+                output,
+                &mut parser,
+                UserProfiles {},
+                progress_bar,
+                compression,
+            )?,
+        }
+        progress_bar.finish();
+        if let Some(findings) = &findings {
+            // The profiles only read the keys they report on, so the checks need a traversal of their own
+            Findings::watch(findings, &mut parser);
+            progress_bar.set_total_keys(0);
+            for _ in ParserIterator::new(&parser).iter() {}
+            progress_bar.finish();
+        }
+    } else if options.split_by_root {
+        if let Some(findings) = &findings {
+            Findings::watch(findings, &mut parser);
+        }
+        if let Some(mut root) = parser.get_root_key()? {
+            let key_names: Vec<String> = root
+                .read_sub_keys(&mut parser)
+                .into_iter()
+                .map(|sub_key| sub_key.key_name)
+                .collect();
+            // The root key's own file: none of its sub keys match, but the recovered keys hanging from it are written
+            // along with it
+            let mut splits = vec![(
+                SPLIT_ROOT_NAME.to_string(),
+                FilterBuilder::new()
+                    .add_key_path(&root.path)
+                    .key_path_has_root(true)
+                    .build()?,
+            )];
+            for (key_name, name) in key_names.iter().zip(split_names(&key_names)) {
+                let filter = FilterBuilder::new()
+                    .add_literal_segment(key_name)
+                    .return_child_keys(true)
+                    .build()?;
+                splits.push((name, filter));
+            }
+            for (name, filter) in splits {
+                let split_output = get_split_outpath(output, &name);
+                progress_bar.set_total_keys(parser.count(Some(&filter))?.0);
+                write_keys(&parser, &split_output, Some(filter), options, &mut console)?;
+                progress_bar.finish();
+                println!("Finished writing {:?}", split_output);
+                outputs.push(split_output);
+            }
+        }
     } else {
-        WriteJson::write(output, &parser, filter, &mut console)?;
+        if let Some(findings) = &findings {
+            Findings::watch(findings, &mut parser);
+        }
+        progress_bar.set_total_keys(parser.count(options.filter.as_ref())?.0);
+        write_keys(
+            &parser,
+            output,
+            options.filter.clone(),
+            options,
+            &mut console,
+        )?;
+        progress_bar.finish();
+    }
+    parser.set_key_handler(None);
+    // Dropping the key handler dropped its reference to the findings
+    let mut findings =
+        findings.and_then(|findings| Arc::try_unwrap(findings).ok()?.into_inner().ok());
+    if !options.split_by_root {
+        println!("Finished writing {:?}", output);
+        outputs.push(output.clone());
+    }
+
+    if let Some(findings) = findings.as_ref().filter(|_| options.manifest) {
+        let mut manifest = Manifest::from_export(
+            &parser,
+            &input,
+            &logs,
+            options.filter_path,
+            (findings.key_count, findings.value_count),
+            &findings.warnings,
+        )?;
+        for output in &outputs {
+            manifest.add_output(output)?;
+        }
+        let manifest_path = get_manifest_path(output);
+        manifest.write(&manifest_path)?;
+        println!("Finished writing {:?}", manifest_path);
+    }
+
+    if let Some(findings) = &findings {
+        findings.print();
+    }
+
+    if let Some(warnings_output) = &mut reports.warnings {
+        let warnings = findings
+            .as_mut()
+            .map(|findings| std::mem::take(&mut findings.warnings))
+            .unwrap_or_default();
+        for warning in &warnings {
+            let warning = HiveWarning {
+                hive: &hive,
+                warning,
+            };
+            writeln!(warnings_output, "{}", serde_json::to_string(&warning)?)?;
+        }
+    }
+
+    if let Some(dedup_output) = &mut reports.dedup {
+        let report = HiveDedupReport {
+            hive: &hive,
+            report: &parser.value_dedup(16)?,
+        };
+        writeln!(dedup_output, "{}", serde_json::to_string(&report)?)?;
+    }
+
+    if let Some(anomaly_output) = &mut reports.anomaly {
+        let builder = findings
+            .as_mut()
+            .and_then(|findings| findings.anomalies.take());
+        let report = HiveAnomalyReport {
+            hive: &hive,
+            report: &builder.unwrap_or_default().build(),
+        };
+        writeln!(anomaly_output, "{}", serde_json::to_string(&report)?)?;
     }
-    console.write(&format!("\nFinished writing {:?}\n", output))?;
     Ok(())
 }
 
+/// What the checks and reports of a run (ex: `--detect-payloads`, `--anomaly-output`, `--manifest`) find in the keys the
+/// writers return, so they don't need a traversal of their own
+struct Findings {
+    detect_payloads: bool,
+    detect_suspicious_names: bool,
+    /// (key path, value name, payload)
+    payloads: Vec<(String, String, Payload)>,
+    /// (key path, reason)
+    suspicious_names: Vec<(String, SuspiciousName)>,
+    anomalies: Option<AnomalyReportBuilder>,
+    gather_warnings: bool,
+    /// Started from the hive's own warnings, for `--warnings-output` and the manifest
+    warnings: ParseWarnings,
+    key_count: usize,
+    value_count: usize,
+}
+
+impl Findings {
+    /// Returns None if none of the checks and reports are enabled in `options` and `reports`
+    fn new(parser: &Parser, options: &DumpOptions, reports: &Reports) -> Option<Self> {
+        let gather_warnings = options.manifest || reports.warnings.is_some();
+        if options.detect_payloads
+            || options.detect_suspicious_names
+            || reports.anomaly.is_some()
+            || gather_warnings
+        {
+            Some(Self {
+                detect_payloads: options.detect_payloads,
+                detect_suspicious_names: options.detect_suspicious_names,
+                payloads: Vec::new(),
+                suspicious_names: Vec::new(),
+                anomalies: reports
+                    .anomaly
+                    .as_ref()
+                    .map(|_| AnomalyReportBuilder::new()),
+                gather_warnings,
+                warnings: parser.hive_warnings(),
+                key_count: 0,
+                value_count: 0,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Makes `parser` send `findings` the keys its iterators return from now on
+    fn watch(findings: &Arc<Mutex<Self>>, parser: &mut Parser) {
+        let findings = findings.clone();
+        parser.set_key_handler(Some(Arc::new(move |key: &CellKeyNode| {
+            if let Ok(mut findings) = findings.lock() {
+                findings.add_key(key);
+            }
+        })));
+    }
+
+    fn add_key(&mut self, key: &CellKeyNode) {
+        if self.detect_payloads {
+            for value in key.value_iter() {
+                if let Some(payload) = value.detect_payload() {
+                    self.payloads
+                        .push((key.path.to_string(), value.get_pretty_name(), payload));
+                }
+            }
+        }
+        if self.detect_suspicious_names {
+            if let Some(suspicious_name) = key.suspicious_name() {
+                self.suspicious_names
+                    .push((key.path.to_string(), suspicious_name));
+            }
+        }
+        if let Some(anomalies) = &mut self.anomalies {
+            anomalies.add_key(key);
+        }
+        if self.gather_warnings {
+            self.warnings.add_key(key);
+        }
+        self.key_count += 1;
+        self.value_count += key.sub_values.len();
+    }
+
+    fn print(&self) {
+        for (key_path, value_name, payload) in &self.payloads {
+            println!("Payload in {}\\{}: {}", key_path, value_name, payload);
+        }
+        for (key_path, suspicious_name) in &self.suspicious_names {
+            println!(
+                "Suspicious key {}: {}",
+                key_path.escape_debug(),
+                suspicious_name
+            );
+        }
+    }
+}
+
+/// A parse warning tagged with the hive it came from, so warnings from a recursive run can share one file
+#[derive(Serialize)]
+struct HiveWarning<'a> {
+    hive: &'a str,
+    #[serde(flatten)]
+    warning: &'a ParseWarning,
+}
+
+/// A duplicate value data report tagged with the hive it came from, so reports from a recursive run can share one file
+#[derive(Serialize)]
+struct HiveDedupReport<'a> {
+    hive: &'a str,
+    #[serde(flatten)]
+    report: &'a DedupReport,
+}
+
+/// A name anomaly report tagged with the hive it came from, so reports from a recursive run can share one file
+#[derive(Serialize)]
+struct HiveAnomalyReport<'a> {
+    hive: &'a str,
+    #[serde(flatten)]
+    report: &'a AnomalyReport,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum OutputType {
     Jsonl,
     Common,
     Tsv,
     Xlsx,
+    Dot,
+    GraphMl,
 }
 
 impl ValueEnum for OutputType {
@@ -275,6 +985,8 @@ impl ValueEnum for OutputType {
             OutputType::Xlsx,
             OutputType::Tsv,
             OutputType::Common,
+            OutputType::Dot,
+            OutputType::GraphMl,
         ]
     }
 
@@ -284,6 +996,206 @@ impl ValueEnum for OutputType {
             OutputType::Xlsx => PossibleValue::new("xlsx"),
             OutputType::Tsv => PossibleValue::new("tsv"),
             OutputType::Common => PossibleValue::new("common"),
+            OutputType::Dot => PossibleValue::new("dot"),
+            OutputType::GraphMl => PossibleValue::new("graphml"),
         })
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Profile {
+    Amcache,
+    UserAssist,
+    ShellBags,
+    Network,
+    Services,
+    Autoruns,
+    Sam,
+    Profiles,
+}
+
+impl ValueEnum for Profile {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            Profile::Amcache,
+            Profile::UserAssist,
+            Profile::ShellBags,
+            Profile::Network,
+            Profile::Services,
+            Profile::Autoruns,
+            Profile::Sam,
+            Profile::Profiles,
+        ]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Profile::Amcache => PossibleValue::new("amcache"),
+            Profile::UserAssist => PossibleValue::new("userassist"),
+            Profile::ShellBags => PossibleValue::new("shellbags"),
+            Profile::Network => PossibleValue::new("network"),
+            Profile::Services => PossibleValue::new("services"),
+            Profile::Autoruns => PossibleValue::new("autoruns"),
+            Profile::Sam => PossibleValue::new("sam"),
+            Profile::Profiles => PossibleValue::new("profiles"),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compress {
+    Gzip,
+    Zstd,
+}
+
+impl ValueEnum for Compress {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Compress::Gzip, Compress::Zstd]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Compress::Gzip => PossibleValue::new("gzip"),
+            Compress::Zstd => PossibleValue::new("zstd"),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SchemaArg {
+    V1,
+    V2,
+}
+
+impl ValueEnum for SchemaArg {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[SchemaArg::V1, SchemaArg::V2]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            SchemaArg::V1 => PossibleValue::new("v1"),
+            SchemaArg::V2 => PossibleValue::new("v2"),
+        })
+    }
+}
+
+/// The optional fields of v2 records (see `SerializationOptions`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldSet {
+    RawData,
+    Warnings,
+    Offsets,
+    Security,
+    Versions,
+    Virtualization,
+}
+
+impl FieldSet {
+    fn set(&self, options: &mut SerializationOptions, include: bool) {
+        match self {
+            FieldSet::RawData => options.raw_data = include,
+            FieldSet::Warnings => options.warnings = include,
+            FieldSet::Offsets => options.offsets = include,
+            FieldSet::Security => options.security = include,
+            FieldSet::Versions => options.versions = include,
+            FieldSet::Virtualization => options.virtualization = include,
+        }
+    }
+}
+
+impl ValueEnum for FieldSet {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            FieldSet::RawData,
+            FieldSet::Warnings,
+            FieldSet::Offsets,
+            FieldSet::Security,
+            FieldSet::Versions,
+            FieldSet::Virtualization,
+        ]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            FieldSet::RawData => PossibleValue::new("raw-data"),
+            FieldSet::Warnings => PossibleValue::new("warnings"),
+            FieldSet::Offsets => PossibleValue::new("offsets"),
+            FieldSet::Security => PossibleValue::new("security"),
+            FieldSet::Versions => PossibleValue::new("versions"),
+            FieldSet::Virtualization => PossibleValue::new("virtualization"),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimestampArg {
+    Iso8601,
+    Epoch,
+    Filetime,
+}
+
+impl ValueEnum for TimestampArg {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            TimestampArg::Iso8601,
+            TimestampArg::Epoch,
+            TimestampArg::Filetime,
+        ]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            TimestampArg::Iso8601 => PossibleValue::new("iso8601"),
+            TimestampArg::Epoch => PossibleValue::new("epoch"),
+            TimestampArg::Filetime => PossibleValue::new("filetime"),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortArg {
+    Path,
+    Timestamp,
+}
+
+impl ValueEnum for SortArg {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[SortArg::Path, SortArg::Timestamp]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue> {
+        Some(match self {
+            SortArg::Path => PossibleValue::new("path"),
+            SortArg::Timestamp => PossibleValue::new("timestamp"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_names() {
+        let key_names: Vec<String> = ["Software", "a/b", "a:b", "SOFTWARE", "(Root)", "Setup"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "Software",
+                "a_b",
+                "a_b~2",
+                "SOFTWARE~2",
+                "(Root)~2",
+                "Setup"
+            ],
+            split_names(&key_names)
+        );
+        assert_eq!(
+            PathBuf::from("out/system_(root).jsonl.gz"),
+            get_split_outpath(Path::new("out/system.jsonl.gz"), SPLIT_ROOT_NAME)
+        );
+    }
+}
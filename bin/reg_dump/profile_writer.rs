@@ -16,33 +16,35 @@
 
 use notatin::{
     err::Error,
-    filter::Filter,
-    parser::{Parser, ParserIterator},
-    progress,
+    export::jsonl_writer::{Compression, JsonlWriter},
+    parser::Parser,
+    plugins::Plugin,
 };
 use std::fs::File;
-use std::io::{BufWriter, Write};
 use std::path::*;
 
-pub(crate) struct WriteJson {}
+use crate::progress_bar::ProgressBar;
 
-impl WriteJson {
-    pub(crate) fn write(
+pub(crate) struct WriteProfile {}
+
+impl WriteProfile {
+    pub(crate) fn write<P: Plugin>(
         out_path: impl AsRef<Path>,
-        parser: &Parser,
-        filter: Option<Filter>,
-        console: &mut Box<dyn progress::UpdateProgressTrait>,
+        parser: &mut Parser,
+        plugin: P,
+        progress_bar: &ProgressBar,
+        compression: Compression,
     ) -> Result<(), Error> {
-        let write_file = File::create(out_path)?;
-        let mut iter = ParserIterator::new(parser);
-        if let Some(filter) = filter {
-            iter.with_filter(filter);
-        }
-        let mut writer = BufWriter::new(write_file);
-        for (index, key) in iter.iter().enumerate() {
-            console.update_progress(index)?;
-            writeln!(&mut writer, "{}", serde_json::to_string(&key).unwrap())?;
+        let mut writer = JsonlWriter::new(File::create(out_path)?, compression)?;
+        let records = plugin.parse(parser)?;
+        for (index, record) in records.iter().enumerate() {
+            progress_bar.update(
+                "Writing records",
+                100.0 * (index + 1) as f64 / records.len() as f64,
+            );
+            writer.write(record)?;
         }
+        writer.finish()?;
         Ok(())
     }
 }
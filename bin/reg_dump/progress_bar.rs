@@ -0,0 +1,135 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use notatin::progress::{Progress, ProgressHandler, ProgressPhase};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A console progress bar with a percentage and estimated time remaining, one line per phase (log replay, recovery,
+/// traversal, writing). Traversal percentages are based on the key count set with `set_total_keys` when there is one,
+/// since the parser's own estimate advances unevenly for lopsided hives.
+pub(crate) struct ProgressBar {
+    total_keys: AtomicUsize,
+    line: Mutex<ProgressLine>,
+}
+
+#[derive(Default)]
+struct ProgressLine {
+    label: String,
+    phase_start: Option<Instant>,
+    last_draw: Option<Instant>,
+}
+
+impl ProgressBar {
+    const WIDTH: usize = 30;
+    const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub(crate) fn new() -> Self {
+        Self {
+            total_keys: AtomicUsize::new(0),
+            line: Mutex::new(ProgressLine::default()),
+        }
+    }
+
+    /// Sets the number of keys the next traversal will return (0 if unknown)
+    pub(crate) fn set_total_keys(&self, total_keys: usize) {
+        self.total_keys.store(total_keys, Ordering::Relaxed);
+    }
+
+    pub(crate) fn update(&self, label: &str, percentage: f64) {
+        let percentage = percentage.clamp(0.0, 100.0);
+        let mut line = self.line.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if line.label != label {
+            if line.last_draw.is_some() {
+                println!();
+            }
+            *line = ProgressLine {
+                label: label.to_string(),
+                phase_start: Some(now),
+                last_draw: None,
+            };
+        } else if percentage < 100.0
+            && matches!(line.last_draw, Some(last_draw) if now - last_draw < Self::REDRAW_INTERVAL)
+        {
+            return;
+        }
+        line.last_draw = Some(now);
+
+        let elapsed = now - line.phase_start.unwrap_or(now);
+        let eta = if percentage > 0.0 && percentage < 100.0 {
+            format!(
+                " ETA {}",
+                format_duration(elapsed.mul_f64((100.0 - percentage) / percentage))
+            )
+        } else {
+            format!(" in {}", format_duration(elapsed))
+        };
+        let filled = (percentage / 100.0 * Self::WIDTH as f64).round() as usize;
+        print!(
+            "\r{}: [{}{}] {:5.1}%{}    ",
+            label,
+            "#".repeat(filled),
+            " ".repeat(Self::WIDTH - filled),
+            percentage,
+            eta
+        );
+        io::stdout().flush().unwrap_or_default();
+    }
+
+    /// Ends the current line; the next update starts a new one
+    pub(crate) fn finish(&self) {
+        let mut line = self.line.lock().unwrap_or_else(|e| e.into_inner());
+        if line.last_draw.is_some() {
+            println!();
+        }
+        *line = ProgressLine::default();
+    }
+}
+
+impl ProgressHandler for ProgressBar {
+    fn on_progress(&self, progress: &Progress) {
+        match progress.phase {
+            ProgressPhase::RecoveringDeleted => {
+                self.update("Recovering deleted items", progress.percentage)
+            }
+            ProgressPhase::ApplyingTransactionLogs => {
+                self.update("Applying transaction logs", progress.percentage)
+            }
+            ProgressPhase::Traversing => {
+                let total_keys = self.total_keys.load(Ordering::Relaxed);
+                let percentage = if total_keys > 0 && progress.percentage < 100.0 {
+                    (100.0 * progress.keys_processed as f64 / total_keys as f64).min(99.9)
+                } else {
+                    progress.percentage
+                };
+                self.update("Traversing keys", percentage)
+            }
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
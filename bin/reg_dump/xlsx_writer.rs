@@ -15,14 +15,12 @@
  */
 
 use notatin::{
-    cell::{Cell, CellState},
-    cell_key_node::CellKeyNode,
-    cell_key_value::CellKeyValue,
-    cell_value::CellValue,
+    cell::CellState,
     err::Error,
     filter::Filter,
     parser::{Parser, ParserIterator},
-    progress, util,
+    record::{Record, RecordKind},
+    timestamp_format::TimestampFormat,
 };
 use std::{borrow::Cow, convert::TryFrom, path::*};
 use xlsxwriter::format::{FormatBorder, FormatColor, FormatUnderline};
@@ -31,7 +29,7 @@ use xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
 pub(crate) struct WriteXlsx {
     workbook: Workbook,
     recovered_only: bool,
-    console: Box<dyn progress::UpdateProgressTrait>,
+    timestamp_format: TimestampFormat,
 }
 
 impl WriteXlsx {
@@ -66,15 +64,27 @@ impl WriteXlsx {
         Ok(WriteXlsx {
             workbook: Workbook::new(&output.as_ref().to_string_lossy())?,
             recovered_only,
-            console: progress::new(true),
+            timestamp_format: TimestampFormat::default(),
         })
     }
 
+    /// Sets how timestamps are written (see `WriteTsv::timestamp_format`)
+    pub(crate) fn timestamp_format(&mut self, timestamp_format: TimestampFormat) -> &mut Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
     pub(crate) fn write(&mut self, parser: &Parser, filter: Option<Filter>) -> Result<(), Error> {
         let mut iter = ParserIterator::new(parser);
         if let Some(filter) = filter {
             iter.with_filter(filter);
         }
+        if let Some(sort) = parser.key_sort() {
+            iter.sort_keys(sort);
+        }
+        if let Some(path_mapping) = parser.path_mapping() {
+            iter.full_paths(path_mapping.clone());
+        }
 
         let mut reg_items_sheet =
             WorksheetState::new(self.workbook.add_worksheet(Some("Registry Items"))?);
@@ -125,10 +135,10 @@ impl WriteXlsx {
         reg_items_sheet.write_string(Self::COL_LOGS, "Logs")?;
         reg_items_sheet.sheet.freeze_panes(1, 0);
 
-        for (index, key) in iter.iter().enumerate() {
-            self.console.update_progress(index)?;
-            self.write_key(&mut reg_items_sheet, &mut overflow_sheet, &key, false)?;
+        for record in iter.as_records(self.timestamp_format) {
+            self.write_record(&mut reg_items_sheet, &mut overflow_sheet, &record)?;
         }
+        parser.check_cancelled()?;
 
         if let Some(logs) = parser.get_parse_logs().get() {
             let mut link_format = Format::new();
@@ -148,105 +158,24 @@ impl WriteXlsx {
         Ok(())
     }
 
-    fn write_key(
-        &self,
-        reg_items_sheet: &mut WorksheetState,
-        overflow_sheet: &mut WorksheetState,
-        cell_key_node: &CellKeyNode,
-        is_key_version: bool,
-    ) -> Result<(), Error> {
-        if !self.recovered_only || cell_key_node.has_or_is_recovered() {
-            reg_items_sheet.row += 1;
-            reg_items_sheet.set_shading(&cell_key_node.path, None, cell_key_node.cell_state);
-            let (row_format, link_format) = self.get_formatters(
-                cell_key_node.cell_state,
-                reg_items_sheet.shaded,
-                reg_items_sheet.upper_border,
-            );
-
-            reg_items_sheet.sheet.set_row(
-                reg_items_sheet.row,
-                Self::ROW_HEIGHT,
-                Some(&row_format),
-            )?;
-
-            let mut logs = cell_key_node.logs.clone();
-            reg_items_sheet.write_number(Self::COL_INDEX, reg_items_sheet.row.into())?;
-            Self::check_write_string(
-                reg_items_sheet,
-                overflow_sheet,
-                Self::COL_KEY_PATH,
-                &sanitize_for_xml_1_0(&cell_key_node.path),
-                &link_format,
-            )?;
-            reg_items_sheet.write_number(
-                Self::COL_SUBKEY_COUNT,
-                cell_key_node.cell_sub_key_offsets_absolute.len() as f64,
-            )?;
-            reg_items_sheet.write_string(
-                Self::COL_TIMESTAMP,
-                &util::format_date_time(cell_key_node.last_key_written_date_and_time()),
-            )?;
-            reg_items_sheet
-                .write_string(Self::COL_STATUS, &format!("{:?}", cell_key_node.cell_state))?;
-            if let Some(sequence_num) = cell_key_node.sequence_num {
-                reg_items_sheet.write_number(Self::COL_PREV_SEQ_NUM, sequence_num.into())?;
-            }
-            if let Some(sequence_num) = cell_key_node.updated_by_sequence_num {
-                reg_items_sheet.write_number(Self::COL_MOD_SEQ_NUM, sequence_num.into())?;
-            }
-            reg_items_sheet.write_string(
-                Self::COL_FLAGS,
-                &format!("{:?}", cell_key_node.key_node_flags(&mut logs)),
-            )?;
-            reg_items_sheet.write_string(
-                Self::COL_ACCESS_FLAGS,
-                &format!("{:?}", cell_key_node.access_flags(&mut logs)),
-            )?;
-            Self::check_write_string(
-                reg_items_sheet,
-                overflow_sheet,
-                Self::COL_LOGS,
-                &sanitize_for_xml_1_0(&cell_key_node.logs.to_string()),
-                &link_format,
-            )?;
-
-            for sub_key in &cell_key_node.versions {
-                self.write_key(reg_items_sheet, overflow_sheet, sub_key, true)?;
-            }
-        }
-
-        if !is_key_version {
-            // don't output values for modified keys; current/modified/deleted vals will be output via the current version of the key
-            for value in cell_key_node.value_iter() {
-                self.write_value(reg_items_sheet, overflow_sheet, cell_key_node, &value)?;
-
-                for sub_value in &value.versions {
-                    self.write_value(reg_items_sheet, overflow_sheet, cell_key_node, sub_value)?;
-                }
-            }
-        }
-        Ok(())
-    }
-
-    fn write_value(
+    fn write_record(
         &self,
         reg_items_sheet: &mut WorksheetState,
         overflow_sheet: &mut WorksheetState,
-        cell_key_node: &CellKeyNode,
-        value: &CellKeyValue,
+        record: &Record,
     ) -> Result<(), Error> {
-        if self.recovered_only && !value.has_or_is_recovered() {
+        if self.recovered_only && !record.recovered {
             return Ok(());
         }
+        let is_key = record.kind == RecordKind::Key;
         reg_items_sheet.row += 1;
         reg_items_sheet.set_shading(
-            &cell_key_node.path,
-            Some(&value.detail.value_name()),
-            value.cell_state,
+            &record.key_path,
+            Some(&record.name).filter(|_| !is_key),
+            record.state,
         );
         let (row_format, link_format) = self.get_formatters(
-            value.cell_state,
+            record.state,
             reg_items_sheet.shaded,
             reg_items_sheet.upper_border,
         );
@@ -259,44 +188,66 @@ impl WriteXlsx {
             reg_items_sheet,
             overflow_sheet,
             Self::COL_KEY_PATH,
-            &sanitize_for_xml_1_0(&cell_key_node.path),
+            &sanitize_for_xml_1_0(&record.key_path),
             &link_format,
         )?;
+        if let Some(sub_key_count) = record.sub_key_count {
+            reg_items_sheet.write_number(Self::COL_SUBKEY_COUNT, sub_key_count.into())?;
+        }
+        if is_key {
+            reg_items_sheet.write_string(
+                Self::COL_TIMESTAMP,
+                &self.timestamp_format.format(record.last_written),
+            )?;
+        } else {
+            Self::check_write_string(
+                reg_items_sheet,
+                overflow_sheet,
+                Self::COL_VALUE_NAME,
+                &sanitize_for_xml_1_0(&record.name),
+                &link_format,
+            )?;
+            Self::check_write_string(
+                reg_items_sheet,
+                overflow_sheet,
+                Self::COL_VALUE_DATA,
+                &sanitize_for_xml_1_0(&record.data),
+                &link_format,
+            )?;
+        }
+        reg_items_sheet.write_string(Self::COL_STATUS, &format!("{:?}", record.state))?;
+        if let Some(sequence_num) = record.sequence_num {
+            reg_items_sheet.write_number(Self::COL_PREV_SEQ_NUM, sequence_num.into())?;
+        }
+        if let Some(sequence_num) = record.updated_by_sequence_num {
+            reg_items_sheet.write_number(Self::COL_MOD_SEQ_NUM, sequence_num.into())?;
+        }
         Self::check_write_string(
             reg_items_sheet,
             overflow_sheet,
-            Self::COL_VALUE_NAME,
-            &sanitize_for_xml_1_0(&value.get_pretty_name()),
+            Self::COL_FLAGS,
+            &record.flags,
             &link_format,
         )?;
         Self::check_write_string(
             reg_items_sheet,
             overflow_sheet,
-            Self::COL_VALUE_DATA,
-            &sanitize_cell(&value.get_content().0),
+            Self::COL_ACCESS_FLAGS,
+            &record.access_flags,
             &link_format,
         )?;
-        reg_items_sheet.write_string(Self::COL_STATUS, &format!("{:?}", value.cell_state))?;
-        if let Some(sequence_num) = value.sequence_num {
-            reg_items_sheet.write_number(Self::COL_PREV_SEQ_NUM, sequence_num.into())?;
-        }
-        if let Some(sequence_num) = value.updated_by_sequence_num {
-            reg_items_sheet.write_number(Self::COL_MOD_SEQ_NUM, sequence_num.into())?;
-        }
-
         Self::check_write_string(
             reg_items_sheet,
             overflow_sheet,
             Self::COL_VALUE_TYPE,
-            &value.get_content().0.get_type(),
+            &record.data_type,
             &link_format,
         )?;
-
         Self::check_write_string(
             reg_items_sheet,
             overflow_sheet,
             Self::COL_LOGS,
-            &sanitize_for_xml_1_0(&value.logs.to_string()),
+            &sanitize_for_xml_1_0(&record.logs),
             &link_format,
         )?;
         Ok(())
@@ -438,13 +389,6 @@ fn sanitize_for_xml_1_0(s: &str) -> Cow<str> {
     }
 }
 
-fn sanitize_cell(v: &CellValue) -> Cow<str> {
-    match v {
-        CellValue::String(v) => sanitize_for_xml_1_0(v),
-        v => format!("{}", v).into(),
-    }
-}
-
 struct WorksheetState<'a> {
     sheet: Worksheet<'a>,
     row: u32,
@@ -0,0 +1,346 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use clap::{arg, Arg, ArgAction, Command};
+use notatin::{
+    cell_key_node::CellKeyNode,
+    cli_util::*,
+    err::Error,
+    parser::{Parser, ParserIterator},
+    parser_builder::ParserBuilder,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::PathBuf;
+use tiny_http::{Header, Method, Response, Server};
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+const DEFAULT_SEARCH_LIMIT: u64 = 100;
+/// Larger request bodies are cut off (and so fail to parse)
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(message: &str) -> Self {
+        Self {
+            code: INVALID_PARAMS,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(error: Error) -> Self {
+        Self {
+            code: SERVER_ERROR,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// The loaded hives by name. Parsers are kept for the life of the server so queries don't pay for parsing again.
+type Hives = BTreeMap<String, Parser>;
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message }
+        }),
+    }
+}
+
+fn handle_request(hives: &mut Hives, request: &Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let result = match request.get("method").and_then(Value::as_str) {
+        Some(method) => call(hives, method, request.get("params").unwrap_or(&Value::Null)),
+        None => Err(RpcError {
+            code: INVALID_REQUEST,
+            message: "The request has no method".to_string(),
+        }),
+    };
+    response(id, result)
+}
+
+fn call(hives: &mut Hives, method: &str, params: &Value) -> Result<Value, RpcError> {
+    if method == "hives" {
+        return Ok(json!(hives.keys().collect::<Vec<_>>()));
+    }
+    let parser = get_hive(hives, params)?;
+    match method {
+        "get_key" => {
+            let mut key = get_key(parser, params)?;
+            let sub_keys: Vec<String> = key
+                .read_sub_keys(parser)
+                .into_iter()
+                .map(|sub_key| sub_key.key_name)
+                .collect();
+            Ok(json!({ "key": key, "sub_keys": sub_keys }))
+        }
+        "get_value" => {
+            let key = get_key(parser, params)?;
+            let name = params.get("name").and_then(Value::as_str).unwrap_or("");
+            let value = key.get_value(name).ok_or_else(|| RpcError {
+                code: SERVER_ERROR,
+                message: format!("Value not found: {}", name),
+            })?;
+            Ok(json!(value))
+        }
+        "search" => search(parser, params),
+        "count" => {
+            let (keys, values) = parser.count(None)?;
+            Ok(json!({ "keys": keys, "values": values }))
+        }
+        _ => Err(RpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("Unknown method: {}", method),
+        }),
+    }
+}
+
+/// Returns the hive named by the `hive` param, which may be left out when only one hive is loaded
+fn get_hive<'a>(hives: &'a mut Hives, params: &Value) -> Result<&'a mut Parser, RpcError> {
+    match params.get("hive").and_then(Value::as_str) {
+        Some(name) => hives
+            .get_mut(name)
+            .ok_or_else(|| RpcError::invalid_params(&format!("Unknown hive: {}", name))),
+        None if hives.len() == 1 => Ok(hives.values_mut().next().expect("one hive is loaded")),
+        None => Err(RpcError::invalid_params(
+            "The hive param is required when more than one hive is loaded",
+        )),
+    }
+}
+
+/// Returns the key at the `path` param, relative to the root key (the root key if it's missing or empty)
+fn get_key(parser: &mut Parser, params: &Value) -> Result<CellKeyNode, RpcError> {
+    let path = params.get("path").and_then(Value::as_str).unwrap_or("");
+    let key = if path.is_empty() {
+        parser.get_root_key()?
+    } else {
+        parser.get_key(path, false)?
+    };
+    key.ok_or_else(|| RpcError {
+        code: SERVER_ERROR,
+        message: format!("Key not found: {}", path),
+    })
+}
+
+/// Returns the keys and values whose name contains the `query` param, ignoring case. Value data is searched as well if
+/// the `data` param is true. At most `limit` matches are returned.
+fn search(parser: &Parser, params: &Value) -> Result<Value, RpcError> {
+    let query = params
+        .get("query")
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::invalid_params("The query param is required"))?
+        .to_lowercase();
+    let search_data = params
+        .get("data")
+        .and_then(Value::as_bool)
+        .unwrap_or_default();
+    let limit = params
+        .get("limit")
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_SEARCH_LIMIT) as usize;
+
+    let mut matches = Vec::new();
+    for key in ParserIterator::new(parser).iter() {
+        if matches.len() >= limit {
+            break;
+        }
+        if key.key_name.to_lowercase().contains(&query) {
+            matches.push(json!({ "path": key.path }));
+        }
+        for value in key.value_iter() {
+            let name = value.get_pretty_name();
+            if name.to_lowercase().contains(&query)
+                || (search_data
+                    && value
+                        .get_content()
+                        .0
+                        .to_string()
+                        .to_lowercase()
+                        .contains(&query))
+            {
+                matches.push(json!({ "path": key.path, "value": name }));
+            }
+        }
+    }
+    matches.truncate(limit);
+    Ok(Value::Array(matches))
+}
+
+/// Handles a single request or a batch
+fn handle_body(hives: &mut Hives, body: &str) -> Value {
+    match serde_json::from_str::<Value>(body) {
+        Ok(Value::Array(requests)) => Value::Array(
+            requests
+                .iter()
+                .map(|request| handle_request(hives, request))
+                .collect(),
+        ),
+        Ok(request) => handle_request(hives, &request),
+        Err(e) => response(
+            Value::Null,
+            Err(RpcError {
+                code: PARSE_ERROR,
+                message: e.to_string(),
+            }),
+        ),
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let matches = Command::new("Notatin Registry Server")
+        .version("1.0.1")
+        .about("Answers JSON-RPC queries about registry files, keeping them parsed between queries")
+        .arg(
+            Arg::new("input")
+                .short('i')
+                .long("input")
+                .help("Registry file to load (may be repeated)")
+                .required(true)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("address")
+                .short('a')
+                .long("address")
+                .help("Address to listen on")
+                .default_value("127.0.0.1:8080")
+                .number_of_values(1),
+        )
+        .arg(arg!(
+            -r --recover "Recover deleted and versioned keys and values"
+        ))
+        .arg(arg!(
+            -s --"skip-logs" "Skip transaction log files"
+        ))
+        .get_matches();
+
+    let address = matches.get_one::<String>("address").expect("Has default");
+    let recover = matches.get_flag("recover");
+    let skip_logs = matches.get_flag("skip-logs");
+
+    let mut hives = Hives::new();
+    for input in matches.get_many::<String>("input").expect("Required value") {
+        let input = PathBuf::from(input);
+        let file_name = input.file_name().unwrap().to_string_lossy().to_string();
+        let logs = get_log_files(skip_logs, &file_name, &input);
+        let mut parser_builder = ParserBuilder::from_path(input);
+        // Queries come back to the same keys, so cache parsed cells
+        parser_builder
+            .recover_deleted(recover)
+            .cell_cache_capacity(4096);
+        for log in logs.unwrap_or_default() {
+            parser_builder.with_transaction_log(log);
+        }
+        let mut name = file_name.clone();
+        let mut suffix = 2;
+        while hives.contains_key(&name) {
+            name = format!("{}~{}", file_name, suffix);
+            suffix += 1;
+        }
+        println!("Loaded {}", name);
+        hives.insert(name, parser_builder.build()?);
+    }
+
+    let server = Server::http(address.as_str()).map_err(|e| Error::Any {
+        detail: format!("Unable to listen on {}: {}", address, e),
+    })?;
+    println!("Listening on http://{}", address);
+    let content_type =
+        Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("Valid header");
+    // A failed request or response only affects that client, so it's logged and the server carries on
+    for mut request in server.incoming_requests() {
+        if *request.method() != Method::Post {
+            if let Err(e) = request.respond(Response::empty(405)) {
+                eprintln!("Unable to respond: {}", e);
+            }
+            continue;
+        }
+        let mut body = String::new();
+        if let Err(e) = request
+            .as_reader()
+            .take(MAX_BODY_SIZE)
+            .read_to_string(&mut body)
+        {
+            eprintln!("Unable to read request: {}", e);
+            continue;
+        }
+        let result = handle_body(&mut hives, &body);
+        if let Err(e) = request
+            .respond(Response::from_string(result.to_string()).with_header(content_type.clone()))
+        {
+            eprintln!("Unable to respond: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle_body() {
+        let mut hives = Hives::new();
+        hives.insert(
+            "NTUSER.DAT".to_string(),
+            ParserBuilder::from_path("test_data/NTUSER.DAT")
+                .build()
+                .unwrap(),
+        );
+
+        let result = handle_body(
+            &mut hives,
+            r#"[{"jsonrpc": "2.0", "id": 1, "method": "hives"},
+                {"jsonrpc": "2.0", "id": 2, "method": "get_key", "params": {"path": "Control Panel\\Accessibility"}},
+                {"jsonrpc": "2.0", "id": 3, "method": "search", "params": {"query": "mousekeys", "limit": 1}},
+                {"jsonrpc": "2.0", "id": 4, "method": "get_key", "params": {"path": "Nope"}},
+                {"jsonrpc": "2.0", "id": 5, "method": "bogus"}]"#,
+        );
+        assert_eq!(json!(["NTUSER.DAT"]), result[0]["result"]);
+        assert!(result[1]["result"]["sub_keys"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("MouseKeys")));
+        let search = result[2]["result"].as_array().unwrap();
+        assert_eq!(1, search.len());
+        assert!(search[0]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("\\Control Panel\\Accessibility\\MouseKeys"));
+        assert_eq!(json!(SERVER_ERROR), result[3]["error"]["code"]);
+        assert_eq!(json!(METHOD_NOT_FOUND), result[4]["error"]["code"]);
+        assert_eq!(
+            json!(PARSE_ERROR),
+            handle_body(&mut hives, "{")["error"]["code"]
+        );
+    }
+}
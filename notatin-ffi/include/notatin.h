@@ -0,0 +1,254 @@
+#ifndef NOTATIN_H
+#define NOTATIN_H
+
+/* Generated by cbindgen from notatin-ffi/src. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ A registry key
+ */
+typedef struct NotatinKey NotatinKey;
+
+/*
+ Iterates over all the keys of a parser (see `notatin_parser_keys`)
+ */
+typedef struct NotatinKeyIterator NotatinKeyIterator;
+
+/*
+ An open registry hive
+ */
+typedef struct NotatinParser NotatinParser;
+
+/*
+ A registry value
+ */
+typedef struct NotatinValue NotatinValue;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Returns the message of the last error that occurred on the calling thread, or `NULL` if the last call succeeded.
+ The returned string must be released with `notatin_string_free`.
+ */
+char *notatin_last_error(void);
+
+/*
+ Releases a string returned by this library
+
+ # Safety
+
+ `s` must be null or a string returned by this library that hasn't already been released
+ */
+void notatin_string_free(char *s);
+
+/*
+ Returns the full path of the key, including the root key's name.
+ The string must be released with `notatin_string_free`.
+
+ # Safety
+
+ `key` must be a valid key
+ */
+char *notatin_key_path(const struct NotatinKey *key);
+
+/*
+ Returns the name of the key. The string must be released with `notatin_string_free`.
+
+ # Safety
+
+ `key` must be a valid key
+ */
+char *notatin_key_name(const struct NotatinKey *key);
+
+/*
+ Returns the last written timestamp of the key as a FILETIME (100 nanosecond intervals since 1601-01-01 UTC)
+
+ # Safety
+
+ `key` must be a valid key
+ */
+uint64_t notatin_key_last_written(const struct NotatinKey *key);
+
+/*
+ Returns true if the key was recovered from unallocated space or a transaction log
+
+ # Safety
+
+ `key` must be a valid key
+ */
+bool notatin_key_is_deleted(const struct NotatinKey *key);
+
+/*
+ Returns the number of values of the key
+
+ # Safety
+
+ `key` must be a valid key
+ */
+size_t notatin_key_value_count(const struct NotatinKey *key);
+
+/*
+ Returns the value at `index` (see `notatin_key_value_count`), or `NULL` if `index` is out of range.
+ The value must be released with `notatin_value_free`.
+
+ # Safety
+
+ `key` must be a valid key
+ */
+struct NotatinValue *notatin_key_value_at(const struct NotatinKey *key, size_t index);
+
+/*
+ Returns the value named `name` (case insensitive; use "" for the default value), or `NULL` if there is no such value.
+ The value must be released with `notatin_value_free`.
+
+ # Safety
+
+ `key` must be a valid key. `name` must be a nul terminated UTF-8 string.
+ */
+struct NotatinValue *notatin_key_get_value(const struct NotatinKey *key,
+                                           const char *name);
+
+/*
+ Releases a key
+
+ # Safety
+
+ `key` must be `NULL` or a key returned by this library that hasn't already been released
+ */
+void notatin_key_free(struct NotatinKey *key);
+
+/*
+ Opens the hive at `path`, applying the `log_count` transaction logs in `log_paths`.
+ Set `recover_deleted` to search for deleted and modified items.
+ Returns `NULL` on failure. The parser must be released with `notatin_parser_free`.
+
+ # Safety
+
+ `path` must be a nul terminated UTF-8 string. `log_paths` must point to `log_count` nul terminated UTF-8 strings,
+ or be `NULL` if `log_count` is 0.
+ */
+struct NotatinParser *notatin_parser_open(const char *path,
+                                          const char *const *log_paths,
+                                          size_t log_count,
+                                          bool recover_deleted);
+
+/*
+ Releases a parser. Any iterators created from the parser must be released first.
+
+ # Safety
+
+ `parser` must be `NULL` or a parser returned by `notatin_parser_open` that hasn't already been released
+ */
+void notatin_parser_free(struct NotatinParser *parser);
+
+/*
+ Returns the root key of the hive, or `NULL` if it can't be read.
+ The key must be released with `notatin_key_free`.
+
+ # Safety
+
+ `parser` must be a valid parser
+ */
+struct NotatinKey *notatin_parser_root_key(struct NotatinParser *parser);
+
+/*
+ Returns the key at `path` (relative to the root key, ex: `Control Panel\Accessibility`), or `NULL` if there is
+ no such key. `notatin_last_error` returns `NULL` if the key doesn't exist.
+ The key must be released with `notatin_key_free`.
+
+ # Safety
+
+ `parser` must be a valid parser. `path` must be a nul terminated UTF-8 string.
+ */
+struct NotatinKey *notatin_parser_get_key(struct NotatinParser *parser,
+                                          const char *path);
+
+/*
+ Returns an iterator over all the keys of the hive, in preorder. The iterator must be released with
+ `notatin_key_iterator_free` before the parser is released.
+
+ # Safety
+
+ `parser` must be a valid parser
+ */
+struct NotatinKeyIterator *notatin_parser_keys(const struct NotatinParser *parser);
+
+/*
+ Returns the next key, or `NULL` once all the keys have been returned.
+ The key must be released with `notatin_key_free`.
+
+ # Safety
+
+ `iterator` must be a valid iterator whose parser hasn't been released
+ */
+struct NotatinKey *notatin_key_iterator_next(struct NotatinKeyIterator *iterator);
+
+/*
+ Releases an iterator
+
+ # Safety
+
+ `iterator` must be `NULL` or an iterator returned by `notatin_parser_keys` that hasn't already been released
+ */
+void notatin_key_iterator_free(struct NotatinKeyIterator *iterator);
+
+/*
+ Returns the name of the value ("" for the default value). The string must be released with `notatin_string_free`.
+
+ # Safety
+
+ `value` must be a valid value
+ */
+char *notatin_value_name(const struct NotatinValue *value);
+
+/*
+ Returns the data type of the value as stored in the hive (ex: 1 for REG_SZ, 4 for REG_DWORD)
+
+ # Safety
+
+ `value` must be a valid value
+ */
+uint32_t notatin_value_data_type(const struct NotatinValue *value);
+
+/*
+ Returns the undecoded data of the value and stores its length in `len`.
+ The data is owned by `value` and is valid until `value` is released.
+
+ # Safety
+
+ `value` must be a valid value. `len` must point to writable memory.
+ */
+const uint8_t *notatin_value_data(const struct NotatinValue *value, size_t *len);
+
+/*
+ Returns the content of the value interpreted according to its data type, formatted as a string
+ (binary data is hex encoded and multi strings are formatted as a list, ex: `["a", "b"]`). Returns `NULL` if the value has no content.
+ The string must be released with `notatin_string_free`.
+
+ # Safety
+
+ `value` must be a valid value
+ */
+char *notatin_value_content(const struct NotatinValue *value);
+
+/*
+ Releases a value
+
+ # Safety
+
+ `value` must be `NULL` or a value returned by this library that hasn't already been released
+ */
+void notatin_value_free(struct NotatinValue *value);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NOTATIN_H */
@@ -0,0 +1,81 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn set_last_error(error: String) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+pub(crate) fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+/// Records that the argument `name` was null or invalid and returns `NULL`
+pub(crate) fn invalid_argument<T>(name: &str) -> *mut T {
+    set_last_error(format!("Invalid argument: {}", name));
+    std::ptr::null_mut()
+}
+
+/// Runs `f`, recording its error (or panic) as the last error of the calling thread.
+/// Returns None if `f` failed; the last error is cleared if it succeeded.
+/// Panics must not unwind into C, so they are reported as errors; a handle that was in use when a panic occurred
+/// should be released rather than reused.
+pub(crate) fn catch<T, E: ToString>(f: impl FnOnce() -> Result<T, E>) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(result)) => {
+            clear_last_error();
+            Some(result)
+        }
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            None
+        }
+        Err(_) => {
+            set_last_error("Panic while parsing".to_string());
+            None
+        }
+    }
+}
+
+/// Returns the message of the last error that occurred on the calling thread, or `NULL` if the last call succeeded.
+/// The returned string must be released with `notatin_string_free`.
+#[no_mangle]
+pub extern "C" fn notatin_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| match last.borrow().as_ref() {
+        Some(error) => crate::to_c_string(error),
+        None => std::ptr::null_mut(),
+    })
+}
+
+/// Releases a string returned by this library
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that hasn't already been released
+#[no_mangle]
+pub unsafe extern "C" fn notatin_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
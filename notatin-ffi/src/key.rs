@@ -0,0 +1,141 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::error::invalid_argument;
+use crate::value::NotatinValue;
+use notatin::cell_key_node::CellKeyNode;
+use std::os::raw::c_char;
+use std::ptr;
+
+/// A registry key
+pub struct NotatinKey {
+    pub(crate) inner: CellKeyNode,
+}
+
+impl NotatinKey {
+    pub(crate) fn into_raw(key: Option<CellKeyNode>) -> *mut Self {
+        match key {
+            Some(inner) => Box::into_raw(Box::new(Self { inner })),
+            None => ptr::null_mut(),
+        }
+    }
+}
+
+/// Returns the full path of the key, including the root key's name.
+/// The string must be released with `notatin_string_free`.
+///
+/// # Safety
+///
+/// `key` must be a valid key
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_path(key: *const NotatinKey) -> *mut c_char {
+    match key.as_ref() {
+        Some(key) => crate::to_c_string(&key.inner.path),
+        None => invalid_argument("key"),
+    }
+}
+
+/// Returns the name of the key. The string must be released with `notatin_string_free`.
+///
+/// # Safety
+///
+/// `key` must be a valid key
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_name(key: *const NotatinKey) -> *mut c_char {
+    match key.as_ref() {
+        Some(key) => crate::to_c_string(&key.inner.key_name),
+        None => invalid_argument("key"),
+    }
+}
+
+/// Returns the last written timestamp of the key as a FILETIME (100 nanosecond intervals since 1601-01-01 UTC)
+///
+/// # Safety
+///
+/// `key` must be a valid key
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_last_written(key: *const NotatinKey) -> u64 {
+    key.as_ref()
+        .map_or(0, |key| key.inner.detail.last_key_written_date_and_time())
+}
+
+/// Returns true if the key was recovered from unallocated space or a transaction log
+///
+/// # Safety
+///
+/// `key` must be a valid key
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_is_deleted(key: *const NotatinKey) -> bool {
+    key.as_ref()
+        .is_some_and(|key| key.inner.cell_state.is_deleted())
+}
+
+/// Returns the number of values of the key
+///
+/// # Safety
+///
+/// `key` must be a valid key
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_value_count(key: *const NotatinKey) -> usize {
+    key.as_ref().map_or(0, |key| key.inner.value_iter().count())
+}
+
+/// Returns the value at `index` (see `notatin_key_value_count`), or `NULL` if `index` is out of range.
+/// The value must be released with `notatin_value_free`.
+///
+/// # Safety
+///
+/// `key` must be a valid key
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_value_at(
+    key: *const NotatinKey,
+    index: usize,
+) -> *mut NotatinValue {
+    match key.as_ref() {
+        Some(key) => NotatinValue::into_raw(key.inner.next_value(index).map(|(value, _)| value)),
+        None => invalid_argument("key"),
+    }
+}
+
+/// Returns the value named `name` (case insensitive; use "" for the default value), or `NULL` if there is no such value.
+/// The value must be released with `notatin_value_free`.
+///
+/// # Safety
+///
+/// `key` must be a valid key. `name` must be a nul terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_get_value(
+    key: *const NotatinKey,
+    name: *const c_char,
+) -> *mut NotatinValue {
+    match (key.as_ref(), crate::from_c_string(name)) {
+        (Some(key), Some(name)) => NotatinValue::into_raw(key.inner.get_value(name)),
+        (None, _) => invalid_argument("key"),
+        (_, None) => invalid_argument("name"),
+    }
+}
+
+/// Releases a key
+///
+/// # Safety
+///
+/// `key` must be `NULL` or a key returned by this library that hasn't already been released
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_free(key: *mut NotatinKey) {
+    if !key.is_null() {
+        drop(Box::from_raw(key));
+    }
+}
@@ -0,0 +1,158 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! C API for notatin.
+//!
+//! Handles returned by the `notatin_*` functions are owned by the caller and must be released with the matching
+//! `*_free` function. Strings returned as `char *` are released with `notatin_string_free`.
+//! Functions that return a handle return `NULL` on failure; the reason is available from `notatin_last_error`.
+//! The header is `include/notatin.h`, generated with cbindgen (the build generates it again in `OUT_DIR`, and
+//! `test_header_is_current` checks that the two match).
+
+mod error;
+mod key;
+mod parser;
+mod value;
+
+pub use error::{notatin_last_error, notatin_string_free};
+pub use key::*;
+pub use parser::*;
+pub use value::*;
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+/// Returns a copy of `s` allocated for the caller. Interior nul characters are dropped.
+pub(crate) fn to_c_string(s: &str) -> *mut c_char {
+    let s = CString::new(s.replace('\0', "")).expect("Interior nul characters were removed");
+    s.into_raw()
+}
+
+/// Returns `s` as a `&str`, or None if `s` is null or isn't UTF-8
+///
+/// # Safety
+///
+/// `s` must be null or point to a nul terminated string that outlives the returned reference
+pub(crate) unsafe fn from_c_string<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        None
+    } else {
+        CStr::from_ptr(s).to_str().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    fn take_string(s: *mut c_char) -> String {
+        assert!(!s.is_null());
+        let result = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+        unsafe { notatin_string_free(s) };
+        result
+    }
+
+    fn open(path: &str) -> *mut NotatinParser {
+        let path = CString::new(path).unwrap();
+        unsafe { notatin_parser_open(path.as_ptr(), ptr::null(), 0, false) }
+    }
+
+    #[test]
+    fn test_header_is_current() {
+        let generated =
+            std::fs::read_to_string(std::path::Path::new(env!("OUT_DIR")).join("notatin.h"))
+                .unwrap();
+        assert_eq!(
+            generated,
+            include_str!("../include/notatin.h"),
+            "include/notatin.h is out of date; regenerate it with \
+            `cbindgen --config cbindgen.toml --output include/notatin.h`"
+        );
+    }
+
+    #[test]
+    fn test_parser_open_error() {
+        let parser = open("../test_data/does_not_exist");
+        assert!(parser.is_null());
+        assert!(take_string(notatin_last_error()).starts_with("An IO error has occurred"));
+
+        let parser = unsafe { notatin_parser_open(ptr::null(), ptr::null(), 0, false) };
+        assert!(parser.is_null());
+        assert_eq!("Invalid argument: path", take_string(notatin_last_error()));
+    }
+
+    #[test]
+    fn test_iterate_keys() {
+        let parser = open("../test_data/NTUSER.DAT");
+        assert!(!parser.is_null());
+        assert!(notatin_last_error().is_null());
+
+        let (mut keys, mut values) = (0, 0);
+        unsafe {
+            let iterator = notatin_parser_keys(parser);
+            loop {
+                let key = notatin_key_iterator_next(iterator);
+                if key.is_null() {
+                    break;
+                }
+                keys += 1;
+                values += notatin_key_value_count(key);
+                notatin_key_free(key);
+            }
+            notatin_key_iterator_free(iterator);
+            notatin_parser_free(parser);
+        }
+        assert_eq!((2853, 5523), (keys, values));
+    }
+
+    #[test]
+    fn test_get_key_and_values() {
+        let parser = open("../test_data/NTUSER.DAT");
+        let path = CString::new("Control Panel\\Accessibility\\MouseKeys").unwrap();
+        unsafe {
+            let key = notatin_parser_get_key(parser, path.as_ptr());
+            assert_eq!("MouseKeys", take_string(notatin_key_name(key)));
+            assert_eq!(
+                "\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\MouseKeys",
+                take_string(notatin_key_path(key))
+            );
+            assert_eq!(130_679_916_674_978_318, notatin_key_last_written(key));
+            assert!(!notatin_key_is_deleted(key));
+            assert_eq!(3, notatin_key_value_count(key));
+
+            let value = notatin_key_value_at(key, 0);
+            assert_eq!("Flags", take_string(notatin_value_name(value)));
+            notatin_value_free(value);
+            assert!(notatin_key_value_at(key, 3).is_null());
+
+            let name = CString::new("maximumspeed").unwrap();
+            let value = notatin_key_get_value(key, name.as_ptr());
+            assert_eq!("MaximumSpeed", take_string(notatin_value_name(value)));
+            assert_eq!(1, notatin_value_data_type(value));
+            assert_eq!("80", take_string(notatin_value_content(value)));
+            let mut len = 0;
+            let data = notatin_value_data(value, &mut len);
+            assert_eq!(b"8\x000\x00\x00\x00", std::slice::from_raw_parts(data, len));
+            notatin_value_free(value);
+            notatin_key_free(key);
+
+            let path = CString::new("Does not exist").unwrap();
+            assert!(notatin_parser_get_key(parser, path.as_ptr()).is_null());
+            assert!(notatin_last_error().is_null());
+            notatin_parser_free(parser);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::error::{catch, clear_last_error, invalid_argument};
+use crate::key::NotatinKey;
+use notatin::{
+    parser::{Parser, ParserIteratorContext},
+    parser_builder::ParserBuilder,
+};
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::ptr;
+
+/// An open registry hive
+pub struct NotatinParser {
+    inner: Parser,
+}
+
+/// Iterates over all the keys of a parser (see `notatin_parser_keys`)
+pub struct NotatinKeyIterator {
+    parser: *const NotatinParser,
+    context: ParserIteratorContext,
+}
+
+/// Opens the hive at `path`, applying the `log_count` transaction logs in `log_paths`.
+/// Set `recover_deleted` to search for deleted and modified items.
+/// Returns `NULL` on failure. The parser must be released with `notatin_parser_free`.
+///
+/// # Safety
+///
+/// `path` must be a nul terminated UTF-8 string. `log_paths` must point to `log_count` nul terminated UTF-8 strings,
+/// or be `NULL` if `log_count` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn notatin_parser_open(
+    path: *const c_char,
+    log_paths: *const *const c_char,
+    log_count: usize,
+    recover_deleted: bool,
+) -> *mut NotatinParser {
+    let path = match crate::from_c_string(path) {
+        Some(path) => PathBuf::from(path),
+        None => return invalid_argument("path"),
+    };
+    let mut builder = ParserBuilder::from_path(path);
+    if log_count > 0 {
+        if log_paths.is_null() {
+            return invalid_argument("log_paths");
+        }
+        for &log_path in std::slice::from_raw_parts(log_paths, log_count) {
+            match crate::from_c_string(log_path) {
+                Some(log_path) => {
+                    builder.with_transaction_log(PathBuf::from(log_path));
+                }
+                None => return invalid_argument("log_paths"),
+            }
+        }
+    }
+    builder.recover_deleted(recover_deleted);
+    match catch(|| builder.build()) {
+        Some(inner) => Box::into_raw(Box::new(NotatinParser { inner })),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Releases a parser. Any iterators created from the parser must be released first.
+///
+/// # Safety
+///
+/// `parser` must be `NULL` or a parser returned by `notatin_parser_open` that hasn't already been released
+#[no_mangle]
+pub unsafe extern "C" fn notatin_parser_free(parser: *mut NotatinParser) {
+    if !parser.is_null() {
+        drop(Box::from_raw(parser));
+    }
+}
+
+/// Returns the root key of the hive, or `NULL` if it can't be read.
+/// The key must be released with `notatin_key_free`.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser
+#[no_mangle]
+pub unsafe extern "C" fn notatin_parser_root_key(parser: *mut NotatinParser) -> *mut NotatinKey {
+    match parser.as_mut() {
+        Some(parser) => NotatinKey::into_raw(catch(|| parser.inner.get_root_key()).flatten()),
+        None => invalid_argument("parser"),
+    }
+}
+
+/// Returns the key at `path` (relative to the root key, ex: `Control Panel\Accessibility`), or `NULL` if there is
+/// no such key. `notatin_last_error` returns `NULL` if the key doesn't exist.
+/// The key must be released with `notatin_key_free`.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser. `path` must be a nul terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn notatin_parser_get_key(
+    parser: *mut NotatinParser,
+    path: *const c_char,
+) -> *mut NotatinKey {
+    match (parser.as_mut(), crate::from_c_string(path)) {
+        (Some(parser), Some(path)) => {
+            NotatinKey::into_raw(catch(|| parser.inner.get_key(path, false)).flatten())
+        }
+        (None, _) => invalid_argument("parser"),
+        (_, None) => invalid_argument("path"),
+    }
+}
+
+/// Returns an iterator over all the keys of the hive, in preorder. The iterator must be released with
+/// `notatin_key_iterator_free` before the parser is released.
+///
+/// # Safety
+///
+/// `parser` must be a valid parser
+#[no_mangle]
+pub unsafe extern "C" fn notatin_parser_keys(
+    parser: *const NotatinParser,
+) -> *mut NotatinKeyIterator {
+    match parser.as_ref() {
+        Some(p) => {
+            clear_last_error();
+            Box::into_raw(Box::new(NotatinKeyIterator {
+                parser,
+                context: ParserIteratorContext::from_parser(&p.inner, true, None),
+            }))
+        }
+        None => invalid_argument("parser"),
+    }
+}
+
+/// Returns the next key, or `NULL` once all the keys have been returned.
+/// The key must be released with `notatin_key_free`.
+///
+/// # Safety
+///
+/// `iterator` must be a valid iterator whose parser hasn't been released
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_iterator_next(
+    iterator: *mut NotatinKeyIterator,
+) -> *mut NotatinKey {
+    match iterator.as_mut() {
+        Some(iterator) => {
+            let parser = &(*iterator.parser).inner;
+            let context = &mut iterator.context;
+            NotatinKey::into_raw(
+                catch(|| Ok::<_, String>(parser.next_key_preorder(context))).flatten(),
+            )
+        }
+        None => invalid_argument("iterator"),
+    }
+}
+
+/// Releases an iterator
+///
+/// # Safety
+///
+/// `iterator` must be `NULL` or an iterator returned by `notatin_parser_keys` that hasn't already been released
+#[no_mangle]
+pub unsafe extern "C" fn notatin_key_iterator_free(iterator: *mut NotatinKeyIterator) {
+    if !iterator.is_null() {
+        drop(Box::from_raw(iterator));
+    }
+}
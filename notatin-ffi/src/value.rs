@@ -0,0 +1,115 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::error::invalid_argument;
+use notatin::{cell_key_value::CellKeyValue, cell_value::CellValue};
+use std::os::raw::c_char;
+use std::ptr;
+
+/// A registry value
+pub struct NotatinValue {
+    inner: CellKeyValue,
+    /// The undecoded data of the value; kept so `notatin_value_data` can lend it out
+    data: Vec<u8>,
+}
+
+impl NotatinValue {
+    pub(crate) fn into_raw(value: Option<CellKeyValue>) -> *mut Self {
+        match value {
+            Some(inner) => Box::into_raw(Box::new(Self {
+                data: inner.detail.value_bytes().unwrap_or_default(),
+                inner,
+            })),
+            None => ptr::null_mut(),
+        }
+    }
+}
+
+/// Returns the name of the value ("" for the default value). The string must be released with `notatin_string_free`.
+///
+/// # Safety
+///
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn notatin_value_name(value: *const NotatinValue) -> *mut c_char {
+    match value.as_ref() {
+        Some(value) => crate::to_c_string(&value.inner.detail.value_name()),
+        None => invalid_argument("value"),
+    }
+}
+
+/// Returns the data type of the value as stored in the hive (ex: 1 for REG_SZ, 4 for REG_DWORD)
+///
+/// # Safety
+///
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn notatin_value_data_type(value: *const NotatinValue) -> u32 {
+    value
+        .as_ref()
+        .map_or(0, |value| value.inner.detail.data_type_raw())
+}
+
+/// Returns the undecoded data of the value and stores its length in `len`.
+/// The data is owned by `value` and is valid until `value` is released.
+///
+/// # Safety
+///
+/// `value` must be a valid value. `len` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn notatin_value_data(
+    value: *const NotatinValue,
+    len: *mut usize,
+) -> *const u8 {
+    match (value.as_ref(), len.as_mut()) {
+        (Some(value), Some(len)) => {
+            *len = value.data.len();
+            value.data.as_ptr()
+        }
+        (None, _) => invalid_argument("value"),
+        (_, None) => invalid_argument("len"),
+    }
+}
+
+/// Returns the content of the value interpreted according to its data type, formatted as a string
+/// (binary data is hex encoded and multi strings are formatted as a list, ex: `["a", "b"]`). Returns `NULL` if the value has no content.
+/// The string must be released with `notatin_string_free`.
+///
+/// # Safety
+///
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn notatin_value_content(value: *const NotatinValue) -> *mut c_char {
+    match value.as_ref() {
+        Some(value) => match value.inner.get_content().0 {
+            CellValue::None | CellValue::Error => ptr::null_mut(),
+            content => crate::to_c_string(&content.to_string()),
+        },
+        None => invalid_argument("value"),
+    }
+}
+
+/// Releases a value
+///
+/// # Safety
+///
+/// `value` must be `NULL` or a value returned by this library that hasn't already been released
+#[no_mangle]
+pub unsafe extern "C" fn notatin_value_free(value: *mut NotatinValue) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
@@ -1,23 +1,36 @@
-from typing import Optional
+import datetime
+from typing import List, Optional, Union
 
 # classes
 class PyNotatinParser(object):
-    """ Create and return a new object. """
+    """ Create and return a new object. Transaction logs are applied from `log_files`;
+        set `recover_deleted` to search for deleted and modified items """
     @staticmethod # known case of __new__
-    def __new__(FileOrFileLike) -> PyNotatinParser: ...
+    def __new__(FileOrFileLike, log_files: List[FileOrFileLike] = [], recover_deleted: bool = False) -> PyNotatinParser: ...
+
+    """ Writes the keys and values matching `filter` to `path` using the native writers.
+        `format` is one of "jsonl", "tsv", or "common". `recovered_only` applies to tsv output """
+    def export(self, path: str, format: str = "jsonl", filter: Optional[PyNotatinFilter] = None, recovered_only: bool = False): ...
 
     """ Returns the key for the `path` parameter """
     def open(self, path: str) -> PyNotatinKey: ...
 
-    """ Returns an iterator that yields reg keys """
+    """ Returns an iterator that yields reg keys. The parser may be iterated any number of times """
     def reg_keys(self): ...
 
+    """ Returns an iterator that yields the reg values of the keys matching `filter`.
+        If `recovered` is true, only deleted and modified values (and their previous versions) are returned """
+    def reg_values(self, filter: Optional[PyNotatinFilter] = None, recovered: bool = False): ...
+
     """ Returns the root key """
     def root(self) -> PyNotatinKey: ...
 
     """ Returns the parent key for the `key` parameter """
     def get_parent(self, key: PyNotatinKey) -> PyNotatinKey: ...
 
+    """ Returns the decoded UserAssist entries (NTUSER.DAT) """
+    def user_assist(self) -> List[PyNotatinUserAssistEntry]: ...
+
 class PyNotatinKey(object):
     """ Returns an iterator that yields reg values """
     def values(self): ...
@@ -28,9 +41,35 @@ class PyNotatinKey(object):
     """ Returns an iterator that yields sub keys """
     def subkeys(self, parser: PyNotatinParser): ...
 
+    """ Returns an iterator that lazily reads and yields the direct sub keys """
+    def sub_keys(self): ...
+
     """ Returns the requested key, or None """
     def find_key(self, parser: PyNotatinParser, path: str) -> Optional[PyNotatinKey]: ...
 
+    """ Returns the security descriptors of the key """
+    def security_descriptors(self) -> List[dict]: ...
+
+    """ Returns the last written timestamp of the key """
+    @property
+    def last_key_written_date_and_time(self) -> datetime.datetime: ...
+
+    """ Returns the names of the key node flags that are set (ex: ["KEY_COMP_NAME"]) """
+    @property
+    def flags(self) -> List[str]: ...
+
+    """ Returns the names of the access flags that are set (ex: ["ACCESSED_AFTER_INIT"]) """
+    @property
+    def access_flags(self) -> List[str]: ...
+
+    """ Returns the state of the key (Allocated, DeletedPrimaryFile, etc.) """
+    @property
+    def cell_state(self) -> str: ...
+
+    """ Returns true if the key was recovered (deleted, or a previous version of a modified key) """
+    @property
+    def is_recovered(self) -> bool: ...
+
     """ Returns the name of the key """
     @property
     def name(self) -> str: ...
@@ -51,7 +90,26 @@ class PyNotatinKey(object):
     @property
     def number_of_key_values(self) -> int: ...
 
+    """ Returns the offset of the key's cell in the primary file """
+    @property
+    def file_offset_absolute(self) -> int: ...
+
+    """ Returns the sequence number of the transaction log entry the key was recovered from, if any """
+    @property
+    def sequence_num(self) -> Optional[int]: ...
+
+    """ Returns the warnings logged while parsing the key """
+    @property
+    def logs(self) -> List[str]: ...
+
+    """ Returns the attributes of the key as a dict """
+    def as_dict(self) -> dict: ...
+
 class PyNotatinValue(object):
+    """ Returns the path of the key the value belongs to """
+    @property
+    def key_path(self) -> str: ...
+
     """ Returns the value as bytes """
     @property
     def value(self) -> bytes: ...
@@ -68,9 +126,18 @@ class PyNotatinValue(object):
     @property
     def raw_data_type(self) -> int: ...
 
-    """ Returns the value as typed data """
+    """ Returns the undecoded value data as bytes """
     @property
-    def content(self) -> object: ...
+    def raw_data(self) -> bytes: ...
+
+    """ Returns the name of the data type (ex: "REG_SZ") """
+    @property
+    def data_type(self) -> str: ...
+
+    """ Returns the value as typed data: int for numeric types, str for string types,
+        list of str for REG_MULTI_SZ, and bytes for binary and unrecognized types """
+    @property
+    def content(self) -> Union[int, str, List[str], bytes, None]: ...
 
     """ Decodes the content using one of the supported decoders (see `PyNotatinDecodeFormat`) """
     def decode(self, format: PyNotatinDecodeFormat, offset: int) -> PyNotatinContent: ...
@@ -78,6 +145,21 @@ class PyNotatinValue(object):
     """ Returns an iterator that yields value versions, if `recover_deleted` was enabled """
     def versions(self): ...
 
+    """ Returns the offset of the value's cell in the primary file """
+    @property
+    def file_offset_absolute(self) -> int: ...
+
+    """ Returns the state of the value (Allocated, DeletedPrimaryFile, etc.) """
+    @property
+    def cell_state(self) -> str: ...
+
+    """ Returns the warnings logged while parsing the value """
+    @property
+    def logs(self) -> List[str]: ...
+
+    """ Returns the attributes of the value as a dict """
+    def as_dict(self) -> dict: ...
+
 class PyNotatinContent(object):
     """ Returns the decoded content """
     @property
@@ -113,3 +195,89 @@ class PyNotatinParserBuilder(object):
 
     """ Returns a PyNotatinParser """
     def build(self) -> PyNotatinParser: ...
+
+class PyNotatinFilter(object):
+    """ Create and return a new object. Segments are matched case-insensitively """
+    @staticmethod # known case of __new__
+    def __new__(key_path: Optional[str] = None, children: bool = False) -> PyNotatinFilter: ...
+
+    """ Appends each segment of `key_path` as a literal segment """
+    def add_key_path(self, key_path: str): ...
+
+    """ Appends a single literal segment """
+    def add_literal_segment(self, segment: str): ...
+
+    """ Appends a segment matched by the `regex` regular expression """
+    def add_regex_segment(self, regex: str): ...
+
+    """ Set to true if the key path starts with the root key name """
+    def key_path_has_root(self, key_path_has_root: bool): ...
+
+    """ Set to true to also return the descendants of the matching keys """
+    def return_child_keys(self, children: bool): ...
+
+class PyNotatinHiveDiff(object):
+    """ Compares the keys and values of `base` and `comparison` matching `filter` (mirrors reg_compare) """
+    @staticmethod # known case of __new__
+    def __new__(base: PyNotatinParser, comparison: PyNotatinParser, filter: Optional[PyNotatinFilter] = None) -> PyNotatinHiveDiff: ...
+
+    """ Returns the keys only present in `base` (dicts with path, last_key_written_date_and_time, flags, access_flags) """
+    @property
+    def keys_deleted(self) -> List[dict]: ...
+
+    """ Returns the keys only present in `comparison` """
+    @property
+    def keys_added(self) -> List[dict]: ...
+
+    """ Returns the modified keys (dicts with path, base, comparison) """
+    @property
+    def keys_modified(self) -> List[dict]: ...
+
+    """ Returns the values only present in `base` (dicts with key_path, name, data_type, content) """
+    @property
+    def values_deleted(self) -> List[dict]: ...
+
+    """ Returns the values only present in `comparison` """
+    @property
+    def values_added(self) -> List[dict]: ...
+
+    """ Returns the modified values (dicts with path, base, comparison) """
+    @property
+    def values_modified(self) -> List[dict]: ...
+
+    """ Returns the total number of deleted, added, and modified keys and values """
+    @property
+    def total_changes(self) -> int: ...
+
+class PyNotatinUserAssistEntry(object):
+    """ Returns the path of the UserAssist Count key """
+    @property
+    def key_path(self) -> str: ...
+
+    """ Returns the UserAssist GUID the entry belongs to """
+    @property
+    def guid(self) -> str: ...
+
+    """ Returns the decoded (ROT13) name """
+    @property
+    def name(self) -> str: ...
+
+    """ Returns the original value name """
+    @property
+    def value_name(self) -> str: ...
+
+    """ Returns the state of the value (Allocated, DeletedPrimaryFile, etc.) """
+    @property
+    def cell_state(self) -> str: ...
+
+    @property
+    def run_count(self) -> Optional[int]: ...
+
+    @property
+    def focus_count(self) -> Optional[int]: ...
+
+    @property
+    def focus_time_ms(self) -> Optional[int]: ...
+
+    @property
+    def last_executed(self) -> Optional[datetime.datetime]: ...
@@ -17,7 +17,10 @@
 
 pub mod err;
 pub mod py_notatin_content;
+pub mod py_notatin_filter;
+pub mod py_notatin_hive_diff;
 pub mod py_notatin_key;
 pub mod py_notatin_parser;
+pub mod py_notatin_plugins;
 pub mod py_notatin_value;
 pub mod util;
@@ -0,0 +1,84 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ *
+ */
+
+use crate::err::PyNotatinError;
+use notatin::filter::{Filter, FilterBuilder};
+use pyo3::prelude::*;
+
+#[pyclass]
+/// Restricts iteration to the keys matching a path made of literal and regex segments.
+/// Segments are matched case-insensitively, one per level of the key path.
+pub struct PyNotatinFilter {
+    inner: FilterBuilder,
+}
+
+#[pymethods]
+impl PyNotatinFilter {
+    #[new]
+    #[pyo3(signature = (key_path=None, children=false))]
+    fn new(key_path: Option<&str>, children: bool) -> Self {
+        let mut inner = FilterBuilder::new().return_child_keys(children);
+        if let Some(key_path) = key_path {
+            inner = inner.add_key_path(key_path);
+        }
+        PyNotatinFilter { inner }
+    }
+
+    /// Appends each segment of `key_path` as a literal segment
+    pub fn add_key_path(&mut self, key_path: &str) -> PyResult<()> {
+        self.update(|builder| builder.add_key_path(key_path));
+        Ok(())
+    }
+
+    /// Appends a single literal segment
+    pub fn add_literal_segment(&mut self, segment: &str) -> PyResult<()> {
+        self.update(|builder| builder.add_literal_segment(segment));
+        Ok(())
+    }
+
+    /// Appends a segment matched by the `regex` regular expression
+    pub fn add_regex_segment(&mut self, regex: &str) -> PyResult<()> {
+        self.update(|builder| builder.add_regex_segment(regex));
+        Ok(())
+    }
+
+    /// Set to true if the key path starts with the root key name
+    pub fn key_path_has_root(&mut self, key_path_has_root: bool) -> PyResult<()> {
+        self.update(|builder| builder.key_path_has_root(key_path_has_root));
+        Ok(())
+    }
+
+    /// Set to true to also return the descendants of the matching keys
+    pub fn return_child_keys(&mut self, children: bool) -> PyResult<()> {
+        self.update(|builder| builder.return_child_keys(children));
+        Ok(())
+    }
+}
+
+impl PyNotatinFilter {
+    fn update<F>(&mut self, func: F)
+    where
+        F: FnOnce(FilterBuilder) -> FilterBuilder,
+    {
+        self.inner = func(std::mem::take(&mut self.inner));
+    }
+
+    /// Returns the filter, or an error if any of the regex segments is invalid
+    pub(crate) fn build(&self) -> PyResult<Filter> {
+        Ok(self.inner.clone().build().map_err(PyNotatinError)?)
+    }
+}
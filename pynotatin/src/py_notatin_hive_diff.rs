@@ -0,0 +1,128 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ *
+ */
+
+use crate::err::PyNotatinError;
+use crate::py_notatin_filter::PyNotatinFilter;
+use crate::py_notatin_key::PyNotatinKey;
+use crate::py_notatin_parser::PyNotatinParser;
+use crate::py_notatin_value::PyNotatinValue;
+use notatin::{
+    cell_key_node::CellKeyNode, cell_key_value::CellKeyValue, hive_diff::HiveDiff, progress,
+};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+#[pyclass]
+/// Compares two parsers, mirroring `reg_compare`. Deleted, added, and modified keys and values are returned as dicts.
+pub struct PyNotatinHiveDiff {
+    #[pyo3(get)]
+    pub keys_deleted: Vec<PyObject>,
+    #[pyo3(get)]
+    pub keys_added: Vec<PyObject>,
+    #[pyo3(get)]
+    pub keys_modified: Vec<PyObject>,
+    #[pyo3(get)]
+    pub values_deleted: Vec<PyObject>,
+    #[pyo3(get)]
+    pub values_added: Vec<PyObject>,
+    #[pyo3(get)]
+    pub values_modified: Vec<PyObject>,
+    #[pyo3(get)]
+    pub total_changes: usize,
+}
+
+#[pymethods]
+impl PyNotatinHiveDiff {
+    #[new]
+    #[pyo3(signature = (base, comparison, filter=None))]
+    fn new(
+        py: Python,
+        base: &PyCell<PyNotatinParser>,
+        comparison: &PyCell<PyNotatinParser>,
+        filter: Option<PyRef<PyNotatinFilter>>,
+    ) -> PyResult<Self> {
+        let filter = filter.map(|filter| filter.build()).transpose()?;
+        let diff = HiveDiff::compare(
+            &mut base.try_borrow_mut()?.inner,
+            &comparison.try_borrow()?.inner,
+            filter.as_ref(),
+            &mut *progress::new(false),
+        )
+        .map_err(PyNotatinError)?;
+
+        Ok(PyNotatinHiveDiff {
+            total_changes: diff.total_changes(),
+            keys_deleted: Self::keys_to_dicts(py, &diff.keys_deleted)?,
+            keys_added: Self::keys_to_dicts(py, &diff.keys_added)?,
+            keys_modified: diff
+                .keys_modified
+                .iter()
+                .map(|(base, comparison)| {
+                    Self::modified_to_dict(
+                        py,
+                        &base.path,
+                        PyNotatinKey::key_to_dict(py, base)?,
+                        PyNotatinKey::key_to_dict(py, comparison)?,
+                    )
+                })
+                .collect::<PyResult<_>>()?,
+            values_deleted: Self::values_to_dicts(py, &diff.values_deleted)?,
+            values_added: Self::values_to_dicts(py, &diff.values_added)?,
+            values_modified: diff
+                .values_modified
+                .iter()
+                .map(|(key_path, base, comparison)| {
+                    Self::modified_to_dict(
+                        py,
+                        key_path,
+                        PyNotatinValue::value_to_dict(py, key_path, base)?,
+                        PyNotatinValue::value_to_dict(py, key_path, comparison)?,
+                    )
+                })
+                .collect::<PyResult<_>>()?,
+        })
+    }
+}
+
+impl PyNotatinHiveDiff {
+    /// Returns a dict holding both versions of a modified key or value
+    fn modified_to_dict(
+        py: Python,
+        path: &str,
+        base: PyObject,
+        comparison: PyObject,
+    ) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("path", path)?;
+        dict.set_item("base", base)?;
+        dict.set_item("comparison", comparison)?;
+        Ok(dict.to_object(py))
+    }
+
+    fn keys_to_dicts(py: Python, keys: &[CellKeyNode]) -> PyResult<Vec<PyObject>> {
+        keys.iter()
+            .map(|key| PyNotatinKey::key_to_dict(py, key))
+            .collect()
+    }
+
+    fn values_to_dicts(py: Python, values: &[(String, CellKeyValue)]) -> PyResult<Vec<PyObject>> {
+        values
+            .iter()
+            .map(|(key_path, value)| PyNotatinValue::value_to_dict(py, key_path, value))
+            .collect()
+    }
+}
@@ -17,16 +17,21 @@
 
 use pyo3::prelude::*;
 
+use crate::err::PyNotatinError;
 use crate::py_notatin_parser::{PyNotatinKeysIterator, PyNotatinParser};
 use crate::py_notatin_value::PyNotatinValue;
-use crate::util::date_to_pyobject;
-use notatin::{cell_key_node::CellKeyNode, cell_key_value::CellKeyValue};
-use pyo3::exceptions::PyNotImplementedError;
+use crate::util::{date_to_pyobject, hash_of, logs_to_pyobject};
+use notatin::{cell::CellState, cell_key_node::CellKeyNode, cell_key_value::CellKeyValue, log::Logs};
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyNotImplementedError, PyRuntimeError};
+use pyo3::types::PyDict;
 use pyo3::{Py, PyResult, Python};
 
 #[pyclass(subclass)]
 pub struct PyNotatinKey {
     pub(crate) inner: CellKeyNode,
+    /// The parser the key was read from; required to read sub keys and security descriptors
+    pub(crate) parser: Py<PyNotatinParser>,
     #[pyo3(get)]
     pub last_key_written_date_and_time: PyObject,
 }
@@ -48,32 +53,60 @@ impl PyNotatinKey {
     fn value(&mut self, name: &str) -> Option<Py<PyNotatinValue>> {
         match self.inner.get_value(name) {
             Some(value) => Python::with_gil(|py| {
-                PyNotatinValue::from_cell_key_value(py, value).ok()
+                PyNotatinValue::from_cell_key_value(py, value, &self.inner.path).ok()
             }),
             _ => None
         }
     }
 
-    /// sub_keys(self, parser, /)
+    /// subkeys(self, parser, /)
     /// --
     ///
     /// Returns an iterator that yields sub keys as python objects.
-    fn subkeys(&mut self, parser: &mut PyNotatinParser) -> PyResult<Py<PyNotatinSubKeysIterator>> {
+    fn subkeys(&mut self, parser: &PyCell<PyNotatinParser>) -> PyResult<Py<PyNotatinSubKeysIterator>> {
         self.sub_keys_iterator(parser)
     }
 
-    fn find_key(&mut self, parser: &mut PyNotatinParser, path: &str) -> Option<Py<PyNotatinKey>> {
-        match &mut parser.inner {
-            Some(parser) => match self.inner.get_sub_key_by_path(parser, path) {
-                Some(key) => Python::with_gil(|py| {
-                    PyNotatinKey::from_cell_key_node(py, key).ok()
-                }),
-                _ => None
+    /// sub_keys(self, /)
+    /// --
+    ///
+    /// Returns an iterator that lazily reads and yields the direct sub keys as python objects.
+    fn sub_keys(&self, py: Python) -> PyResult<Py<PyNotatinKeySubKeysIterator>> {
+        let mut inner = self.inner.clone();
+        inner.init_sub_key_iter();
+        Py::new(
+            py,
+            PyNotatinKeySubKeysIterator {
+                inner,
+                parser: self.parser.clone_ref(py),
             },
+        )
+    }
+
+    fn find_key(&mut self, parser: &PyCell<PyNotatinParser>, path: &str) -> Option<Py<PyNotatinKey>> {
+        let key = self.inner.get_sub_key_by_path(&mut parser.borrow_mut().inner, path);
+        match key {
+            Some(key) => Python::with_gil(|py| {
+                PyNotatinKey::from_cell_key_node(py, key, parser.into()).ok()
+            }),
             _ => None
         }
     }
 
+    /// security_descriptors(self, /)
+    /// --
+    ///
+    /// Returns the security descriptors of the key as a list of dicts.
+    fn security_descriptors(&mut self, py: Python) -> PyResult<PyObject> {
+        let security_descriptors = self
+            .inner
+            .get_security_descriptors(&mut self.parser.borrow_mut(py).inner)
+            .map_err(PyNotatinError)?;
+        let json = serde_json::to_string(&security_descriptors)
+            .map_err(|e| PyErr::new::<PyRuntimeError, _>(e.to_string()))?;
+        Ok(py.import("json")?.call_method1("loads", (json,))?.to_object(py))
+    }
+
     /// name(self, /)
     /// --
     ///
@@ -92,6 +125,15 @@ impl PyNotatinKey {
         self.inner.path.to_object(py)
     }
 
+    /// guid_name(self, /)
+    /// --
+    ///
+    /// Returns the name of the GUID the key is named after, if it's known and the parser was built with `guid_names`
+    #[getter]
+    pub fn guid_name(&self, py: Python) -> PyObject {
+        self.inner.guid_name.to_object(py)
+    }
+
     /// pretty_path(self, /)
     /// --
     ///
@@ -101,6 +143,15 @@ impl PyNotatinKey {
         self.inner.get_pretty_path().to_object(py)
     }
 
+    /// last_key_written_filetime(self, /)
+    /// --
+    ///
+    /// Returns the last written timestamp of the key as the FILETIME stored in the hive
+    #[getter]
+    pub fn last_key_written_filetime(&self, py: Python) -> PyObject {
+        self.inner.last_key_written_filetime().to_object(py)
+    }
+
     /// number_of_sub_keys(self, /)
     /// --
     ///
@@ -119,6 +170,108 @@ impl PyNotatinKey {
         self.inner.detail.number_of_key_values().to_object(py)
     }
 
+    /// flags(self, /)
+    /// --
+    ///
+    /// Returns the names of the key node flags that are set (ex: ["KEY_COMP_NAME"])
+    #[getter]
+    pub fn flags(&self, py: Python) -> PyObject {
+        let flags = self.inner.key_node_flags(&mut Logs::default());
+        flags
+            .iter_names()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+            .to_object(py)
+    }
+
+    /// access_flags(self, /)
+    /// --
+    ///
+    /// Returns the names of the access flags that are set (ex: ["ACCESSED_AFTER_INIT"])
+    #[getter]
+    pub fn access_flags(&self, py: Python) -> PyObject {
+        let flags = self.inner.access_flags(&mut Logs::default());
+        flags
+            .iter_names()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+            .to_object(py)
+    }
+
+    /// cell_state(self, /)
+    /// --
+    ///
+    /// Returns the state of the key (Allocated, DeletedPrimaryFile, etc.)
+    #[getter]
+    pub fn cell_state(&self, py: Python) -> PyObject {
+        format!("{:?}", self.inner.cell_state).to_object(py)
+    }
+
+    /// is_recovered(self, /)
+    /// --
+    ///
+    /// Returns true if the key was recovered (deleted, or a previous version of a modified key)
+    #[getter]
+    pub fn is_recovered(&self, py: Python) -> PyObject {
+        (self.inner.cell_state != CellState::Allocated).to_object(py)
+    }
+
+    /// file_offset_absolute(self, /)
+    /// --
+    ///
+    /// Returns the offset of the key's cell in the primary file
+    #[getter]
+    pub fn file_offset_absolute(&self, py: Python) -> PyObject {
+        self.inner.file_offset_absolute.to_object(py)
+    }
+
+    /// sequence_num(self, /)
+    /// --
+    ///
+    /// Returns the sequence number of the transaction log entry the key was recovered from, if any
+    #[getter]
+    pub fn sequence_num(&self, py: Python) -> PyObject {
+        self.inner.sequence_num.to_object(py)
+    }
+
+    /// logs(self, /)
+    /// --
+    ///
+    /// Returns the warnings logged while parsing the key
+    #[getter]
+    pub fn logs(&self, py: Python) -> PyObject {
+        logs_to_pyobject(py, &self.inner.logs)
+    }
+
+    /// as_dict(self, /)
+    /// --
+    ///
+    /// Returns the attributes of the key as a dict
+    pub fn as_dict(&self, py: Python) -> PyResult<PyObject> {
+        Self::key_to_dict(py, &self.inner)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PyNotatinKey(path={:?}, last_key_written_date_and_time={:?}, cell_state={:?})",
+            self.inner.path,
+            self.inner.last_key_written_date_and_time().to_rfc3339(),
+            self.inner.cell_state
+        )
+    }
+
+    fn __richcmp__(&self, other: PyRef<Self>, op: CompareOp, py: Python) -> PyObject {
+        match op {
+            CompareOp::Eq => (self.identity() == other.identity()).to_object(py),
+            CompareOp::Ne => (self.identity() != other.identity()).to_object(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        hash_of(self.identity())
+    }
+
     fn __iter__(mut slf: PyRefMut<Self>) -> PyResult<Py<PyNotatinValuesIterator>> {
         slf.values()
     }
@@ -132,6 +285,7 @@ impl PyNotatinKey {
     pub fn from_cell_key_node(
         py: Python,
         cell_key_node: CellKeyNode,
+        parser: Py<PyNotatinParser>,
     ) -> PyResult<Py<PyNotatinKey>> {
         Py::new(
             py,
@@ -140,10 +294,53 @@ impl PyNotatinKey {
                     &cell_key_node.last_key_written_date_and_time(),
                 )?,
                 inner: cell_key_node,
+                parser,
             },
         )
     }
 
+    /// Returns the attributes of `key` as a dict
+    pub(crate) fn key_to_dict(py: Python, key: &CellKeyNode) -> PyResult<PyObject> {
+        let mut logs = Logs::default();
+        let dict = PyDict::new(py);
+        dict.set_item("path", &*key.path)?;
+        dict.set_item("name", &key.key_name)?;
+        dict.set_item(
+            "last_key_written_date_and_time",
+            date_to_pyobject(&key.last_key_written_date_and_time())?,
+        )?;
+        dict.set_item("last_key_written_filetime", key.last_key_written_filetime())?;
+        let flags = key.key_node_flags(&mut logs);
+        dict.set_item(
+            "flags",
+            flags.iter_names().map(|(name, _)| name).collect::<Vec<_>>(),
+        )?;
+        let access_flags = key.access_flags(&mut logs);
+        dict.set_item(
+            "access_flags",
+            access_flags
+                .iter_names()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+        )?;
+        dict.set_item("cell_state", format!("{:?}", key.cell_state))?;
+        dict.set_item("file_offset_absolute", key.file_offset_absolute)?;
+        dict.set_item("sequence_num", key.sequence_num)?;
+        dict.set_item("number_of_sub_keys", key.detail.number_of_sub_keys())?;
+        dict.set_item("number_of_key_values", key.detail.number_of_key_values())?;
+        dict.set_item("logs", logs_to_pyobject(py, &key.logs))?;
+        Ok(dict.to_object(py))
+    }
+
+    /// Keys are equal if they were read from the same cell with the same state
+    fn identity(&self) -> (&str, usize, i8) {
+        (
+            &*self.inner.path,
+            self.inner.file_offset_absolute,
+            self.inner.cell_state as i8,
+        )
+    }
+
     fn reg_values_iterator(&mut self) -> PyResult<Py<PyNotatinValuesIterator>> {
         Python::with_gil(|py| {
             Py::new(
@@ -158,24 +355,20 @@ impl PyNotatinKey {
 
     fn sub_keys_iterator(
         &mut self,
-        parser: &mut PyNotatinParser,
+        parser: &PyCell<PyNotatinParser>,
     ) -> PyResult<Py<PyNotatinSubKeysIterator>> {
         Python::with_gil(|py| {
             self.inner.init_sub_key_iter();
-            match &mut parser.inner {
-                Some(parser) => {
-                    let sub_keys = self.inner.read_sub_keys(parser);
+            let sub_keys = self.inner.read_sub_keys(&mut parser.borrow_mut().inner);
 
-                    Py::new(py, PyNotatinSubKeysIterator { index: 0, sub_keys })
-                }
-                _ => Py::new(
-                    py,
-                    PyNotatinSubKeysIterator {
-                        index: 0,
-                        sub_keys: Vec::new(),
-                    },
-                ),
-            }
+            Py::new(
+                py,
+                PyNotatinSubKeysIterator {
+                    index: 0,
+                    sub_keys,
+                    parser: parser.into(),
+                },
+            )
         })
     }
 }
@@ -187,8 +380,14 @@ pub struct PyNotatinValuesIterator {
 }
 
 impl PyNotatinValuesIterator {
-    pub(crate) fn reg_value_to_pyobject(reg_value: CellKeyValue, py: Python) -> PyObject {
-        match PyNotatinValue::from_cell_key_value(py, reg_value).map(|entry| entry.to_object(py)) {
+    pub(crate) fn reg_value_to_pyobject(
+        reg_value: CellKeyValue,
+        key_path: &str,
+        py: Python,
+    ) -> PyObject {
+        match PyNotatinValue::from_cell_key_value(py, reg_value, key_path)
+            .map(|entry| entry.to_object(py))
+        {
             Ok(py_reg_value) => py_reg_value,
             Err(e) => e.to_object(py),
         }
@@ -199,7 +398,7 @@ impl PyNotatinValuesIterator {
             match self.inner.next_value(self.sub_values_iter_index) {
                 Some((value, sub_values_iter_index)) => {
                     self.sub_values_iter_index = sub_values_iter_index;
-                    Some(Self::reg_value_to_pyobject(value, py))
+                    Some(Self::reg_value_to_pyobject(value, &self.inner.path, py))
                 }
                 None => None,
             }
@@ -211,6 +410,7 @@ impl PyNotatinValuesIterator {
 pub struct PyNotatinSubKeysIterator {
     index: usize,
     sub_keys: Vec<CellKeyNode>,
+    parser: Py<PyNotatinParser>,
 }
 
 impl PyNotatinSubKeysIterator {
@@ -219,7 +419,11 @@ impl PyNotatinSubKeysIterator {
             match self.sub_keys.get(self.index) {
                 Some(key) => {
                     self.index += 1;
-                    Some(PyNotatinKeysIterator::reg_key_to_pyobject(key.clone(), py))
+                    Some(PyNotatinKeysIterator::reg_key_to_pyobject(
+                        key.clone(),
+                        self.parser.clone_ref(py),
+                        py,
+                    ))
                 }
                 None => None,
             }
@@ -227,6 +431,23 @@ impl PyNotatinSubKeysIterator {
     }
 }
 
+#[pyclass]
+pub struct PyNotatinKeySubKeysIterator {
+    inner: CellKeyNode,
+    parser: Py<PyNotatinParser>,
+}
+
+impl PyNotatinKeySubKeysIterator {
+    fn next(&mut self) -> Option<PyObject> {
+        Python::with_gil(|py| {
+            let sub_key = self.inner.next_sub_key(&mut self.parser.borrow_mut(py).inner);
+            sub_key.map(|key| {
+                PyNotatinKeysIterator::reg_key_to_pyobject(key, self.parser.clone_ref(py), py)
+            })
+        })
+    }
+}
+
 #[pymethods]
 impl PyNotatinValuesIterator {
     fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyNotatinValuesIterator>> {
@@ -238,6 +459,17 @@ impl PyNotatinValuesIterator {
     }
 }
 
+#[pymethods]
+impl PyNotatinKeySubKeysIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyNotatinKeySubKeysIterator>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        Ok(slf.next())
+    }
+}
+
 #[pymethods]
 impl PyNotatinSubKeysIterator {
     fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyNotatinSubKeysIterator>> {
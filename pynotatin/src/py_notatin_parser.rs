@@ -17,59 +17,143 @@
 
 use crate::err::PyNotatinError;
 use crate::py_notatin_content::PyNotatinContent;
-use crate::py_notatin_key::PyNotatinKey;
+use crate::py_notatin_filter::PyNotatinFilter;
+use crate::py_notatin_hive_diff::PyNotatinHiveDiff;
+use crate::py_notatin_key::{PyNotatinKey, PyNotatinValuesIterator};
+use crate::py_notatin_plugins::PyNotatinUserAssistEntry;
 use crate::py_notatin_value::{PyNotatinDecodeFormat, PyNotatinValue};
 use crate::util::{init_logging, FileOrFileLike};
 use ::notatin::{
+    cell::Cell,
     cell_key_node::CellKeyNode,
+    cell_key_value::CellKeyValue,
     err::Error,
+    export::{common_writer::WriteCommon, json_writer::WriteJson, tsv_writer::WriteTsv},
     parser::{Parser, ParserIteratorContext},
     parser_builder::ParserBuilder,
+    plugins::user_assist::UserAssist,
+    progress,
 };
-use pyo3::exceptions::{PyNotImplementedError, PyRuntimeError};
+use pyo3::exceptions::{PyNotImplementedError, PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
 
 #[pyclass(subclass)]
 /// Returns an instance of the parser.
 /// Works on both a path (string), or a file-like object.
+/// Transaction logs may be supplied with `log_files`; set `recover_deleted` to search for deleted and modified items.
+/// Set `guid_names` to name the keys and values named after a GUID with a known name (see their `guid_name`).
+/// The parser may be iterated any number of times, and its iterators may be used concurrently.
 pub struct PyNotatinParser {
-    pub inner: Option<Parser>,
+    pub inner: Parser,
 }
 
 #[pymethods]
 impl PyNotatinParser {
     #[new]
-    fn new(path_or_file_like: PyObject) -> PyResult<Self> {
-        let parser = ParserBuilder::from_file(FileOrFileLike::to_read_seek(&path_or_file_like)?)
-            .build()
-            .map_err(PyNotatinError)?;
-        Ok(PyNotatinParser {
-            inner: Some(parser),
-        })
+    #[pyo3(signature = (path_or_file_like, log_files=Vec::new(), recover_deleted=false, guid_names=false))]
+    fn new(
+        path_or_file_like: PyObject,
+        log_files: Vec<PyObject>,
+        recover_deleted: bool,
+        guid_names: bool,
+    ) -> PyResult<Self> {
+        Self::build(&path_or_file_like, &log_files, recover_deleted, guid_names)
     }
 
     /// Returns an iterator that yields reg keys as Python objects.
-    fn reg_keys(&mut self) -> PyResult<Py<PyNotatinKeysIterator>> {
-        self.reg_keys_iterator()
+    fn reg_keys(slf: &PyCell<Self>) -> PyResult<Py<PyNotatinKeysIterator>> {
+        Self::reg_keys_iterator(slf)
+    }
+
+    /// Returns an iterator that yields reg values as Python objects.
+    /// Only the values of the keys matching `filter` are returned. If `recovered` is true, only
+    /// deleted and modified values (along with their previous versions) are returned.
+    #[pyo3(signature = (filter=None, recovered=false))]
+    fn reg_values(
+        slf: &PyCell<Self>,
+        filter: Option<PyRef<PyNotatinFilter>>,
+        recovered: bool,
+    ) -> PyResult<Py<PyNotatinParserValuesIterator>> {
+        let filter = filter.map(|filter| filter.build()).transpose()?;
+        let iterator_context = ParserIteratorContext::from_parser(
+            &slf.borrow().inner,
+            true,
+            filter.map(|f| (f, false)),
+        );
+        Python::with_gil(|py| {
+            Py::new(
+                py,
+                PyNotatinParserValuesIterator {
+                    parser: slf.into(),
+                    iterator_context,
+                    recovered_only: recovered,
+                    key_path: Arc::from(""),
+                    values: VecDeque::new(),
+                },
+            )
+        })
+    }
+
+    /// Writes the keys and values matching `filter` to `path` using the native writers.
+    /// `format` is one of "jsonl", "tsv", or "common". `recovered_only` applies to tsv output.
+    #[pyo3(signature = (path, format="jsonl", filter=None, recovered_only=false))]
+    fn export(
+        &self,
+        path: &str,
+        format: &str,
+        filter: Option<PyRef<PyNotatinFilter>>,
+        recovered_only: bool,
+    ) -> PyResult<()> {
+        let filter = filter.map(|filter| filter.build()).transpose()?;
+        match format {
+            "jsonl" => WriteJson::write(path, &self.inner, filter, &mut progress::new(false)),
+            "tsv" => WriteTsv::new(path, recovered_only)
+                .and_then(|mut writer| writer.write(&self.inner, filter)),
+            "common" => {
+                WriteCommon::new(path).and_then(|mut writer| writer.write(&self.inner, filter))
+            }
+            _ => {
+                return Err(PyErr::new::<PyValueError, _>(format!(
+                    "Unsupported export format: {}",
+                    format
+                )))
+            }
+        }
+        .map_err(PyNotatinError)?;
+        Ok(())
     }
 
     /// Returns the key for the `path` parameter.
-    fn open(&mut self, path: &str) -> PyResult<Option<Py<PyNotatinKey>>> {
-        self.key_for(|parser| parser.get_key(path, false))
+    fn open(slf: &PyCell<Self>, path: &str) -> PyResult<Option<Py<PyNotatinKey>>> {
+        Self::key_for(slf, |parser| parser.get_key(path, false))
     }
 
     /// Returns the root key.
-    fn root(&mut self) -> PyResult<Option<Py<PyNotatinKey>>> {
-        self.key_for(|parser| parser.get_root_key())
+    fn root(slf: &PyCell<Self>) -> PyResult<Option<Py<PyNotatinKey>>> {
+        Self::key_for(slf, |parser| parser.get_root_key())
     }
 
     /// Returns the parent key for the `key` parameter.
-    fn get_parent(&mut self, key: &mut PyNotatinKey) -> PyResult<Option<Py<PyNotatinKey>>> {
-        self.key_for(|parser| parser.get_parent_key(&mut key.inner))
+    fn get_parent(
+        slf: &PyCell<Self>,
+        key: &mut PyNotatinKey,
+    ) -> PyResult<Option<Py<PyNotatinKey>>> {
+        Self::key_for(slf, |parser| parser.get_parent_key(&mut key.inner))
+    }
+
+    /// Returns the decoded UserAssist entries (NTUSER.DAT).
+    fn user_assist(&mut self, py: Python) -> PyResult<Vec<Py<PyNotatinUserAssistEntry>>> {
+        UserAssist::entries(&mut self.inner)
+            .map_err(PyNotatinError)?
+            .into_iter()
+            .map(|entry| PyNotatinUserAssistEntry::from_user_assist_entry(py, entry))
+            .collect()
     }
 
-    fn __iter__(mut slf: PyRefMut<Self>) -> PyResult<Py<PyNotatinKeysIterator>> {
-        slf.reg_keys()
+    fn __iter__(slf: &PyCell<Self>) -> PyResult<Py<PyNotatinKeysIterator>> {
+        Self::reg_keys_iterator(slf)
     }
 
 fn __next__(_slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
@@ -78,40 +162,49 @@ fn __next__(_slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
 }
 
 impl PyNotatinParser {
-    fn key_for<F>(&mut self, func: F) -> PyResult<Option<Py<PyNotatinKey>>>
+    /// Returns a parser for the primary file, applying any transaction logs
+    fn build(
+        primary_file: &PyObject,
+        transaction_logs: &[PyObject],
+        recover_deleted: bool,
+        guid_names: bool,
+    ) -> PyResult<Self> {
+        let mut builder = ParserBuilder::from_file(FileOrFileLike::to_read_seek(primary_file)?);
+        builder.recover_deleted(recover_deleted);
+        builder.guid_names(guid_names);
+        for transaction_log in transaction_logs {
+            builder.with_transaction_log(FileOrFileLike::to_read_seek(transaction_log)?);
+        }
+        Ok(PyNotatinParser {
+            inner: builder.build().map_err(PyNotatinError)?,
+        })
+    }
+
+    fn key_for<F>(slf: &PyCell<Self>, func: F) -> PyResult<Option<Py<PyNotatinKey>>>
     where
         F: FnOnce(&mut Parser) -> Result<Option<CellKeyNode>, Error>
     {
-        match &mut self.inner {
-            Some(parser) => match func(parser) {
-                Ok(key) => match key {
-                    Some(key) => Python::with_gil(|py| {
-                        Ok(PyNotatinKey::from_cell_key_node(py, key).ok())
-                    }),
-                    _ => Ok(None)
-                },
-                Err(e) => Err(PyErr::new::<PyRuntimeError, _>(e.to_string()))
+        let key = func(&mut slf.borrow_mut().inner);
+        match key {
+            Ok(key) => match key {
+                Some(key) => Python::with_gil(|py| {
+                    Ok(PyNotatinKey::from_cell_key_node(py, key, slf.into()).ok())
+                }),
+                _ => Ok(None)
             },
-            _ => Ok(None)
+            Err(e) => Err(PyErr::new::<PyRuntimeError, _>(e.to_string()))
         }
     }
 
-    /// Returns an iterator that yields reg keys as Python objects
-    fn reg_keys_iterator(&mut self) -> PyResult<Py<PyNotatinKeysIterator>> {
+    /// Returns an iterator that yields reg keys as Python objects.
+    /// The iterator shares the parser, so any number of iterators may be created.
+    fn reg_keys_iterator(slf: &PyCell<Self>) -> PyResult<Py<PyNotatinKeysIterator>> {
+        let iterator_context = ParserIteratorContext::from_parser(&slf.borrow().inner, true, None);
         Python::with_gil(|py| {
-            let inner = match self.inner.take() {
-                Some(inner) => inner,
-                None => {
-                    return Err(PyErr::new::<PyRuntimeError, _>(
-                        "PyNotatinParser can only be used once",
-                    ));
-                }
-            };
-            let iterator_context = ParserIteratorContext::from_parser(&inner, true, None);
             Py::new(
                 py,
                 PyNotatinKeysIterator {
-                    inner,
+                    parser: slf.into(),
                     iterator_context,
                 },
             )
@@ -123,6 +216,7 @@ impl PyNotatinParser {
 pub struct PyNotatinParserBuilder {
     pub primary_file: PyObject,
     pub recover_deleted: bool,
+    pub guid_names: bool,
     pub transaction_logs: Vec<PyObject>,
 }
 
@@ -133,6 +227,7 @@ impl PyNotatinParserBuilder {
         Ok(PyNotatinParserBuilder {
             primary_file: path_or_file_like,
             recover_deleted: false,
+            guid_names: false,
             transaction_logs: vec![],
         })
     }
@@ -142,33 +237,39 @@ impl PyNotatinParserBuilder {
         Ok(())
     }
 
+    pub fn guid_names(&mut self, guid_names: bool) -> PyResult<()> {
+        self.guid_names = guid_names;
+        Ok(())
+    }
+
     pub fn with_transaction_log(&mut self, log: PyObject) -> PyResult<()> {
         self.transaction_logs.push(log);
         Ok(())
     }
 
     pub fn build(&self) -> PyResult<PyNotatinParser> {
-        let mut builder =
-            ParserBuilder::from_file(FileOrFileLike::to_read_seek(&self.primary_file)?);
-        builder.recover_deleted(self.recover_deleted);
-        for transaction_log in &self.transaction_logs {
-            builder.with_transaction_log(FileOrFileLike::to_read_seek(transaction_log)?);
-        }
-        Ok(PyNotatinParser {
-            inner: Some(builder.build().map_err(PyNotatinError)?),
-        })
+        PyNotatinParser::build(
+            &self.primary_file,
+            &self.transaction_logs,
+            self.recover_deleted,
+            self.guid_names,
+        )
     }
 }
 
 #[pyclass]
 pub struct PyNotatinKeysIterator {
-    inner: Parser,
+    parser: Py<PyNotatinParser>,
     iterator_context: ParserIteratorContext,
 }
 
 impl PyNotatinKeysIterator {
-    pub(crate) fn reg_key_to_pyobject(reg_key: CellKeyNode, py: Python) -> PyObject {
-        match PyNotatinKey::from_cell_key_node(py, reg_key).map(|entry| entry.to_object(py)) {
+    pub(crate) fn reg_key_to_pyobject(
+        reg_key: CellKeyNode,
+        parser: Py<PyNotatinParser>,
+        py: Python,
+    ) -> PyObject {
+        match PyNotatinKey::from_cell_key_node(py, reg_key, parser).map(|entry| entry.to_object(py)) {
             Ok(py_reg_key) => py_reg_key,
             Err(e) => e.to_object(py),
         }
@@ -176,9 +277,13 @@ impl PyNotatinKeysIterator {
 
     fn next(&mut self) -> Option<PyObject> {
         Python::with_gil(|py| {
-            self.inner
-                .next_key_preorder(&mut self.iterator_context)
-                .map(|key| Self::reg_key_to_pyobject(key, py))
+            let parser = self.parser.borrow(py);
+            let mut key = parser.inner.next_key_preorder(&mut self.iterator_context)?;
+            if let Some(guid_names) = parser.inner.guid_names() {
+                guid_names.annotate(&mut key);
+            }
+            drop(parser);
+            Some(Self::reg_key_to_pyobject(key, self.parser.clone_ref(py), py))
         })
     }
 }
@@ -193,6 +298,58 @@ impl PyNotatinKeysIterator {
     }
 }
 
+#[pyclass]
+pub struct PyNotatinParserValuesIterator {
+    parser: Py<PyNotatinParser>,
+    iterator_context: ParserIteratorContext,
+    recovered_only: bool,
+    key_path: Arc<str>,
+    values: VecDeque<CellKeyValue>,
+}
+
+impl PyNotatinParserValuesIterator {
+    /// Queues the values of `key` (and their versions when only recovered items are requested)
+    fn queue_values(&mut self, key: &CellKeyNode) {
+        self.key_path = key.path.clone();
+        for value in key.value_iter() {
+            if !self.recovered_only {
+                self.values.push_back(value);
+            } else if value.has_or_is_recovered() {
+                let versions = value.versions.clone();
+                self.values.push_back(value);
+                self.values.extend(versions);
+            }
+        }
+    }
+
+    fn next(&mut self) -> Option<PyObject> {
+        Python::with_gil(|py| {
+            while self.values.is_empty() {
+                let parser = self.parser.borrow(py);
+                let mut key = parser.inner.next_key_preorder(&mut self.iterator_context)?;
+                if let Some(guid_names) = parser.inner.guid_names() {
+                    guid_names.annotate(&mut key);
+                }
+                drop(parser);
+                self.queue_values(&key);
+            }
+            self.values.pop_front().map(|value| {
+                PyNotatinValuesIterator::reg_value_to_pyobject(value, &self.key_path, py)
+            })
+        })
+    }
+}
+
+#[pymethods]
+impl PyNotatinParserValuesIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PyNotatinParserValuesIterator>> {
+        Ok(slf.into())
+    }
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        Ok(slf.next())
+    }
+}
+
 /// Parses a windows registry file.
 #[pymodule]
 fn notatin(py: Python, m: &PyModule) -> PyResult<()> {
@@ -204,6 +361,9 @@ fn notatin(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyNotatinValue>()?;
     m.add_class::<PyNotatinContent>()?;
     m.add_class::<PyNotatinDecodeFormat>()?;
+    m.add_class::<PyNotatinFilter>()?;
+    m.add_class::<PyNotatinHiveDiff>()?;
+    m.add_class::<PyNotatinUserAssistEntry>()?;
 
     Ok(())
 }
@@ -0,0 +1,69 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ *
+ */
+use crate::util::date_to_pyobject;
+use notatin::plugins::user_assist::UserAssistEntry;
+use pyo3::prelude::*;
+use pyo3::{Py, PyResult, Python};
+
+#[pyclass]
+/// A program execution entry decoded from an `Explorer\UserAssist\{GUID}\Count` value
+pub struct PyNotatinUserAssistEntry {
+    #[pyo3(get)]
+    pub key_path: String,
+    #[pyo3(get)]
+    pub guid: String,
+    #[pyo3(get)]
+    pub name: String,
+    #[pyo3(get)]
+    pub value_name: String,
+    #[pyo3(get)]
+    pub cell_state: String,
+    #[pyo3(get)]
+    pub run_count: Option<u32>,
+    #[pyo3(get)]
+    pub focus_count: Option<u32>,
+    #[pyo3(get)]
+    pub focus_time_ms: Option<u32>,
+    #[pyo3(get)]
+    pub last_executed: Option<PyObject>,
+}
+
+impl PyNotatinUserAssistEntry {
+    pub fn from_user_assist_entry(
+        py: Python,
+        entry: UserAssistEntry,
+    ) -> PyResult<Py<PyNotatinUserAssistEntry>> {
+        let last_executed = match &entry.last_executed {
+            Some(last_executed) => Some(date_to_pyobject(last_executed)?),
+            None => None,
+        };
+        Py::new(
+            py,
+            PyNotatinUserAssistEntry {
+                key_path: entry.key_path,
+                guid: entry.guid,
+                name: entry.name,
+                value_name: entry.value_name,
+                cell_state: format!("{:?}", entry.cell_state),
+                run_count: entry.run_count,
+                focus_count: entry.focus_count,
+                focus_time_ms: entry.focus_time_ms,
+                last_executed,
+            },
+        )
+    }
+}
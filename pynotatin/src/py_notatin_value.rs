@@ -17,18 +17,24 @@
 
 use crate::py_notatin_content::PyNotatinContent;
 use crate::py_notatin_key::PyNotatinValuesIterator;
+use crate::util::{hash_of, logs_to_pyobject};
 use pyo3::prelude::*;
 
 use notatin::{
     cell_key_value::CellKeyValue,
     cell_value::{CellValue, DecodableValue, DecodeFormat},
 };
+use pyo3::basic::CompareOp;
+use pyo3::types::PyDict;
 use pyo3::{Py, PyResult, Python};
 
 #[pyclass(subclass)]
 /// Returns an instance of a cell value.
 pub struct PyNotatinValue {
     inner: CellKeyValue,
+    #[pyo3(get)]
+    /// Path of the key the value belongs to
+    pub key_path: String,
 }
 
 #[pymethods]
@@ -40,6 +46,12 @@ impl PyNotatinValue {
             .to_object(py)
     }
 
+    #[getter]
+    /// Returns the undecoded value data as bytes
+    pub fn raw_data(&self, py: Python) -> PyObject {
+        self.value(py)
+    }
+
     #[getter]
     pub fn pretty_name(&self, py: Python) -> PyObject {
         self.inner.get_pretty_name().to_object(py)
@@ -50,6 +62,12 @@ impl PyNotatinValue {
         self.inner.detail.value_name().to_object(py)
     }
 
+    #[getter]
+    /// Returns the name of the GUID the value is named after, if it's known and the parser was built with `guid_names`
+    pub fn guid_name(&self, py: Python) -> PyObject {
+        self.inner.guid_name.to_object(py)
+    }
+
     #[getter]
     /// Returns the data type as an integer
     pub fn raw_data_type(&self, py: Python) -> PyObject {
@@ -57,7 +75,14 @@ impl PyNotatinValue {
     }
 
     #[getter]
-    /// Returns the value as typed data
+    /// Returns the name of the data type (ex: "REG_SZ")
+    pub fn data_type(&self, py: Python) -> PyObject {
+        format!("{:?}", self.inner.data_type).to_object(py)
+    }
+
+    #[getter]
+    /// Returns the value as typed data: int for numeric types, str for string types,
+    /// list of str for REG_MULTI_SZ, and bytes for binary and unrecognized types
     pub fn content(&self, py: Python) -> Option<PyObject> {
         let (content, _) = self.inner.get_content();
         Self::prepare_content(py, &content)
@@ -79,9 +104,54 @@ impl PyNotatinValue {
         )
     }
 
+    #[getter]
+    /// Returns the offset of the value's cell in the primary file
+    pub fn file_offset_absolute(&self, py: Python) -> PyObject {
+        self.inner.file_offset_absolute.to_object(py)
+    }
+
+    #[getter]
+    /// Returns the state of the value's cell (ex: "Allocated", "DeletedPrimaryFile")
+    pub fn cell_state(&self, py: Python) -> PyObject {
+        format!("{:?}", self.inner.cell_state).to_object(py)
+    }
+
+    #[getter]
+    /// Returns the warnings logged while parsing the value
+    pub fn logs(&self, py: Python) -> PyObject {
+        logs_to_pyobject(py, &self.inner.logs)
+    }
+
+    /// Returns the attributes of the value as a dict
+    pub fn as_dict(&self, py: Python) -> PyResult<PyObject> {
+        Self::value_to_dict(py, &self.key_path, &self.inner)
+    }
+
     fn versions(&mut self) -> PyResult<Py<PyNotatinValueVersionsIterator>> {
         self.versions_iterator()
     }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PyNotatinValue(key_path={:?}, name={:?}, data_type={:?}, cell_state={:?})",
+            self.key_path,
+            self.inner.detail.value_name(),
+            self.inner.data_type,
+            self.inner.cell_state
+        )
+    }
+
+    fn __richcmp__(&self, other: PyRef<Self>, op: CompareOp, py: Python) -> PyObject {
+        match op {
+            CompareOp::Eq => (self.identity() == other.identity()).to_object(py),
+            CompareOp::Ne => (self.identity() != other.identity()).to_object(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        hash_of(self.identity())
+    }
 }
 
 impl PyNotatinValue {
@@ -89,15 +159,46 @@ impl PyNotatinValue {
     pub fn from_cell_key_value(
         py: Python,
         cell_key_value: CellKeyValue,
+        key_path: &str,
     ) -> PyResult<Py<PyNotatinValue>> {
         Py::new(
             py,
             PyNotatinValue {
                 inner: cell_key_value,
+                key_path: key_path.to_string(),
             },
         )
     }
 
+    /// Returns the attributes of `value` as a dict
+    pub(crate) fn value_to_dict(
+        py: Python,
+        key_path: &str,
+        value: &CellKeyValue,
+    ) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("key_path", key_path)?;
+        dict.set_item("name", value.detail.value_name())?;
+        dict.set_item("pretty_name", value.get_pretty_name())?;
+        dict.set_item("data_type", format!("{:?}", value.data_type))?;
+        dict.set_item("content", Self::prepare_content(py, &value.get_content().0))?;
+        dict.set_item("cell_state", format!("{:?}", value.cell_state))?;
+        dict.set_item("file_offset_absolute", value.file_offset_absolute)?;
+        dict.set_item("sequence_num", value.sequence_num)?;
+        dict.set_item("logs", logs_to_pyobject(py, &value.logs))?;
+        Ok(dict.to_object(py))
+    }
+
+    /// Values are equal if they were read from the same cell with the same state
+    fn identity(&self) -> (&str, String, usize, i8) {
+        (
+            &self.key_path,
+            self.inner.detail.value_name(),
+            self.inner.file_offset_absolute,
+            self.inner.cell_state as i8,
+        )
+    }
+
     /// Returns typed data based upon the values's data_type
     pub(crate) fn prepare_content(py: Python, content: &CellValue) -> Option<PyObject> {
         match content {
@@ -106,6 +207,7 @@ impl PyNotatinValue {
             CellValue::U32(content) => Some(content.to_object(py)),
             CellValue::U64(content) => Some(content.to_object(py)),
             CellValue::I64(content) => Some(content.to_object(py)),
+            CellValue::DateTime(content) => Some(content.to_rfc3339().to_object(py)),
             CellValue::MultiString(content) => Some(content.to_object(py)),
             CellValue::Binary(content) => {
                 Some(pyo3::types::PyBytes::new(py, content).to_object(py))
@@ -121,6 +223,7 @@ impl PyNotatinValue {
                 PyNotatinValueVersionsIterator {
                     index: 0,
                     versions: self.inner.versions.clone(),
+                    key_path: self.key_path.clone(),
                 },
             )
         })
@@ -131,6 +234,7 @@ impl PyNotatinValue {
 pub struct PyNotatinValueVersionsIterator {
     index: usize,
     versions: Vec<CellKeyValue>,
+    key_path: String,
 }
 
 impl PyNotatinValueVersionsIterator {
@@ -141,6 +245,7 @@ impl PyNotatinValueVersionsIterator {
                     self.index += 1;
                     Some(PyNotatinValuesIterator::reg_value_to_pyobject(
                         value.clone(),
+                        &self.key_path,
                         py
                     ))
                 }
@@ -230,6 +335,7 @@ mod tests {
                     flags_raw: FieldLight { value: 1 },
                     padding: FieldLight { value: 0 },
                     value_bytes: FieldLight { value: None },
+                    data_slack: FieldLight { value: Vec::new() },
                     value_name: FieldLight {
                         value: "IE5_UA_Backup_Flag".to_string(),
                     },
@@ -242,11 +348,15 @@ mod tests {
                 cell_state: CellState::Allocated,
                 data_offsets_absolute: Vec::new(),
                 logs: Logs::default(),
+                value_name_raw: None,
+                value_stats: None,
+                guid_name: None,
                 versions: Vec::new(),
                 hash: None,
                 sequence_num: None,
                 updated_by_sequence_num: None,
             },
+            key_path: String::new(),
         };
         py_reg_value
             .inner
@@ -287,6 +397,7 @@ mod tests {
                         value_bytes: FieldLight {
                             value: Some(lznt1_buffer.clone()),
                         },
+                        data_slack: FieldLight { value: Vec::new() },
                         slack: FieldLight { value: vec![] },
                     })),
                     file_offset_absolute: 0,
@@ -295,11 +406,15 @@ mod tests {
                     cell_state: CellState::Allocated,
                     data_offsets_absolute: Vec::new(),
                     logs: Logs::default(),
+                    value_name_raw: None,
+                    value_stats: None,
+                    guid_name: None,
                     versions: Vec::new(),
                     hash: None,
                     sequence_num: None,
                     updated_by_sequence_num: None,
                 },
+                key_path: String::new(),
             };
 
             let decoded_value = py_notatin_value
@@ -16,10 +16,16 @@
  */
 
 use log::{Level, Log, Metadata, Record, SetLoggerError};
-use std::{cmp::Ordering, fs::File, io::BufReader};
+use std::{
+    cmp::Ordering,
+    collections::hash_map::DefaultHasher,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::BufReader,
+};
 
 use chrono::{DateTime, Datelike, Timelike, NaiveDateTime, Utc};
-use notatin::file_info::ReadSeek;
+use notatin::{log::Logs, ReadSeek};
 use pyo3::{PyObject, PyResult, Python, ToPyObject};
 use pyo3::types::PyDateTime;
 use pyo3_file::PyFileLikeObject;
@@ -115,6 +121,26 @@ pub fn date_to_pyobject(date: &DateTime<Utc>) -> PyResult<PyObject> {
     })
 }
 
+/// Returns the parse logs as a list of strings (`"<code> <text>"`)
+pub fn logs_to_pyobject(py: Python, logs: &Logs) -> PyObject {
+    let logs: Vec<String> = logs
+        .get()
+        .map(|logs| {
+            logs.iter()
+                .map(|log| format!("{:?} {}", log.code, log.text))
+                .collect()
+        })
+        .unwrap_or_default();
+    logs.to_object(py)
+}
+
+/// Returns a hash of `item`, for use by `__hash__`
+pub fn hash_of<T: Hash>(item: T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
 // Logging implementation from https://github.com/omerbenamram/pymft-rs
 /// A logger that prints all messages with a readable output format.
 struct PyLogger {
@@ -17,12 +17,13 @@
 
 import datetime
 import decimal
+import json
 
 import pytest
 
 from pathlib import Path
 
-from notatin import PyNotatinParser, PyNotatinParserBuilder, PyNotatinDecodeFormat
+from notatin import PyNotatinParser, PyNotatinParserBuilder, PyNotatinDecodeFormat, PyNotatinFilter, PyNotatinHiveDiff
 
 test_directory = Path(__file__).parents[2] / "test_data"
 
@@ -263,4 +264,203 @@ def test_value_decode(sample_parser3):
         value = key.value('ArcHistory')
         assert value.name == "ArcHistory"
         val = value.decode(PyNotatinDecodeFormat.utf16_multiple, 0).content
-        assert val == ['NAS_requested_data.7z', 'BlackHarrier_D7_i686_FDE_20141219.dd.7z', 'BlackHarrier_D7_amd64_20141217.7z', 'BlackHarrier_D7_amd64_FDE_20141217.7z', 'C:\\Users\\jmroberts\\Desktop\\USB_Research\\IEF.zip', 'Company_Report_10222013.vir.zip', 'LYNC.7z', 'viruses.zip', 'ALLDATA.txt.bz2']
\ No newline at end of file
+        assert val == ['NAS_requested_data.7z', 'BlackHarrier_D7_i686_FDE_20141219.dd.7z', 'BlackHarrier_D7_amd64_20141217.7z', 'BlackHarrier_D7_amd64_FDE_20141217.7z', 'C:\\Users\\jmroberts\\Desktop\\USB_Research\\IEF.zip', 'Company_Report_10222013.vir.zip', 'LYNC.7z', 'viruses.zip', 'ALLDATA.txt.bz2']
+def test_user_assist(sample_parser3):
+    with open(sample_parser3, "rb") as m:
+        parser = PyNotatinParser(m)
+        entries = parser.user_assist()
+        assert len(entries) == 338
+        entry = [e for e in entries if e.name == "Microsoft.Windows.GettingStarted"][0]
+        assert entry.value_name == "Zvpebfbsg.Jvaqbjf.TrggvatFgnegrq"
+        assert entry.guid == "{CEBFF5CD-ACE2-4F4F-9178-9926F41749EA}"
+        assert entry.run_count == 0
+        assert entry.last_executed == datetime.datetime(2013, 9, 17, 21, 26, 44, 788000)
+
+def test_reg_values(sample_parser):
+    with open(sample_parser, "rb") as m:
+        parser = PyNotatinParserBuilder(m).build()
+        assert sum(1 for _ in parser.reg_values()) == 5523
+
+        m.seek(0)
+        parser = PyNotatinParserBuilder(m).build()
+        values = list(parser.reg_values(PyNotatinFilter("Control Panel\\Accessibility\\MouseKeys")))
+        assert len(values) == 3
+        assert values[0].key_path == "\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\MouseKeys"
+        assert values[0].name == "Flags"
+        mouse_keys_path = values[0].key_path
+
+        m.seek(0)
+        parser = PyNotatinParserBuilder(m).build()
+        values = list(parser.reg_values(PyNotatinFilter("Control Panel\\Accessibility", children=True)))
+        assert len(values) == 34
+
+        m.seek(0)
+        parser = PyNotatinParserBuilder(m).build()
+        reg_filter = PyNotatinFilter()
+        reg_filter.add_literal_segment("Control Panel")
+        reg_filter.add_regex_segment("access.*")
+        reg_filter.add_regex_segment("mouse.*")
+        assert {value.key_path for value in parser.reg_values(reg_filter)} == {mouse_keys_path}
+
+        m.seek(0)
+        parser = PyNotatinParserBuilder(m).build()
+        reg_filter = PyNotatinFilter()
+        reg_filter.add_regex_segment("(")
+        with pytest.raises(RuntimeError):
+            parser.reg_values(reg_filter)
+
+def test_reg_values_recovered(sample_parser2):
+    with open(sample_parser2, "rb") as m:
+        builder = PyNotatinParserBuilder(m)
+        log1 = open(test_directory / "system.log1", "rb")
+        log2 = open(test_directory / "system.log2", "rb")
+        builder.with_transaction_log(log1)
+        builder.with_transaction_log(log2)
+        builder.recover_deleted(True)
+        parser = builder.build()
+        log1.close()
+        log2.close()
+        assert sum(1 for _ in parser.reg_values(recovered=True)) == 495
+
+def test_parser_with_logs(sample_parser2):
+    with open(sample_parser2, "rb") as m:
+        log1 = open(test_directory / "system.log1", "rb")
+        log2 = open(test_directory / "system.log2", "rb")
+        parser = PyNotatinParser(m, log_files=[log1, log2], recover_deleted=True)
+        log1.close()
+        log2.close()
+        assert sum(1 for _ in parser.reg_keys()) == 45618
+
+    parser = PyNotatinParser(str(sample_parser2), log_files=[str(test_directory / "system.log1"), str(test_directory / "system.log2")])
+    assert sum(1 for _ in parser.reg_keys()) == 45587
+
+def test_key_navigation(sample_parser):
+    with open(sample_parser, "rb") as m:
+        parser = PyNotatinParserBuilder(m).build()
+        root = parser.root()
+        assert root.flags == ["KEY_HIVE_ENTRY", "KEY_NO_DELETE", "KEY_COMP_NAME"]
+        assert sum(1 for _ in root.sub_keys()) == 10
+
+        key = parser.open("Control Panel\\Accessibility")
+        assert key.cell_state == "Allocated"
+        assert not key.is_recovered
+        assert key.flags == ["KEY_COMP_NAME"]
+        assert key.access_flags == ["ACCESSED_AFTER_INIT"]
+        sub_keys = list(key.sub_keys())
+        assert len(sub_keys) == 13
+        assert sub_keys[0].name == "AudioDescription"
+        assert sum(1 for _ in sub_keys[0].sub_keys()) == 0
+
+        mouse_keys = [sub_key for sub_key in sub_keys if sub_key.name == "MouseKeys"][0]
+        assert [value.name for value in mouse_keys.values()] == ["Flags", "MaximumSpeed", "TimeToMaximumSpeed"]
+        assert mouse_keys.value("MaximumSpeed").content == "80"
+
+        security_descriptors = key.security_descriptors()
+        assert len(security_descriptors) > 0
+        assert isinstance(security_descriptors[0], dict)
+
+def test_value_typed_content(sample_parser):
+    with open(sample_parser, "rb") as m:
+        parser = PyNotatinParserBuilder(m).build()
+        value = parser.open("AppEvents\\EventLabels\\Notification.Looping.Alarm").value("ExcludeFromCPL")
+        assert value.data_type == "REG_DWORD"
+        assert value.content == 1
+        assert value.raw_data == b"\x01\x00\x00\x00"
+
+        value = parser.open("Software\\Microsoft\\Internet Explorer\\Main").value("OperationalData")
+        assert value.data_type == "REG_QWORD"
+        assert value.content == 13
+        assert value.raw_data == b"\x0d\x00\x00\x00\x00\x00\x00\x00"
+
+        value = parser.open("AppEvents\\EventLabels\\.Default").value("")
+        assert value.data_type == "REG_SZ"
+        assert value.content == "Default Beep"
+        assert value.raw_data == "Default Beep\0".encode("utf-16-le")
+
+        value = parser.open("Control Panel\\International\\User Profile").value("Languages")
+        assert value.data_type == "REG_MULTI_SZ"
+        assert value.content == ["en-US"]
+
+        value = parser.open("Control Panel\\Appearance").value("SchemeLangID")
+        assert value.data_type == "REG_BIN"
+        assert value.content == b"\x09\x04"
+        assert value.raw_data == value.content
+
+def test_parser_reuse(sample_parser):
+    parser = PyNotatinParser(str(sample_parser))
+    assert sum(1 for _ in parser.reg_keys()) == 2853
+    assert sum(1 for _ in parser) == 2853
+
+    keys = parser.reg_keys()
+    values = parser.reg_values()
+    first = next(keys)
+    assert next(keys).path == parser.root().sub_keys().__next__().path
+    assert parser.open("Control Panel\\Accessibility").number_of_sub_keys == 13
+    assert sum(1 for _ in keys) == 2851
+    assert sum(1 for _ in values) == 5523
+    assert first.path == parser.root().path
+
+def test_hive_diff(sample_parser2):
+    base = PyNotatinParser(str(sample_parser2))
+    comparison = PyNotatinParser(str(sample_parser2), log_files=[str(test_directory / "system.log1"), str(test_directory / "system.log2")])
+    diff = PyNotatinHiveDiff(base, comparison, PyNotatinFilter("RegistryTest", children=True))
+    assert diff.total_changes == 4
+    assert [key["path"] for key in diff.keys_deleted] == ["\\ROOT\\RegistryTest\\New Sub Key"]
+    assert diff.keys_added == []
+    assert diff.keys_modified[0]["path"] == "\\ROOT\\RegistryTest"
+    assert diff.values_deleted[0]["name"] == "key1"
+    modified = diff.values_modified[0]
+    assert modified["base"]["content"] == "Multibyte character 𐐷"
+    assert modified["comparison"]["content"] == "Multibyte character 𐐷 - modified"
+
+    diff = PyNotatinHiveDiff(base, PyNotatinParser(str(sample_parser2)))
+    assert diff.total_changes == 0
+
+def test_export(sample_parser, tmp_path):
+    parser = PyNotatinParser(str(sample_parser))
+    reg_filter = PyNotatinFilter("Control Panel\\Accessibility\\MouseKeys")
+
+    jsonl = tmp_path / "out.jsonl"
+    parser.export(str(jsonl), filter=reg_filter)
+    keys = [json.loads(line) for line in jsonl.read_text().splitlines()]
+    assert [key["key_name"] for key in keys] == ["MouseKeys"]
+
+    tsv = tmp_path / "out.tsv"
+    parser.export(str(tsv), "tsv", reg_filter)
+    lines = tsv.read_text().splitlines()
+    assert lines[0].startswith("Index\tKey Path")
+    assert sum(1 for line in lines if "\\MouseKeys\t" in line) == 4
+
+    common = tmp_path / "out.csv"
+    parser.export(str(common), "common")
+    assert "## total_keys: 2853" in common.read_text().splitlines()
+
+    with pytest.raises(ValueError):
+        parser.export(str(tmp_path / "out.sqlite"), "sqlite")
+
+def test_key_and_value_dunders(sample_parser):
+    parser = PyNotatinParser(str(sample_parser))
+    key = parser.open("Control Panel\\Accessibility\\MouseKeys")
+    same_key = parser.open("Control Panel\\Accessibility\\MouseKeys")
+    assert key == same_key
+    assert key != parser.open("Control Panel\\Accessibility")
+    assert len({key, same_key}) == 1
+    assert repr(key).startswith("PyNotatinKey(path=\"\\\\CsiTool-CreateHive-")
+    assert key.file_offset_absolute > 0
+    assert key.logs == []
+    key_dict = key.as_dict()
+    assert key_dict["path"] == key.path
+    assert key_dict["cell_state"] == "Allocated"
+    assert key_dict["number_of_key_values"] == 3
+    assert key_dict["flags"] == key.flags
+
+    value = key.value("MaximumSpeed")
+    assert value == key.value("MaximumSpeed")
+    assert value != key.value("Flags")
+    assert value in set(key.values())
+    assert repr(value).endswith("\\\\MouseKeys\", name=\"MaximumSpeed\", data_type=REG_SZ, cell_state=Allocated)")
+    assert value.cell_state == "Allocated"
+    value_dict = value.as_dict()
+    assert value_dict["name"] == "MaximumSpeed"
+    assert value_dict["content"] == "80"
+    assert value_dict["file_offset_absolute"] == value.file_offset_absolute
@@ -0,0 +1,135 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Reads hives from zip triage collections (ex: KAPE or Velociraptor output) without extracting them to disk.
+//! Enabled with the `archive` feature.
+
+use crate::err::Error;
+use crate::file_info::ReadSeek;
+use std::io::Read;
+use zip::ZipArchive;
+
+/// Returns the contents of the archive member `member`, and of its `.LOG1` and `.LOG2` transaction logs if present.
+/// Member names are matched ignoring case and the path separator used (`\` or `/`).
+pub(crate) fn read_hive<R: ReadSeek>(
+    archive: R,
+    member: &str,
+) -> Result<(Vec<u8>, Vec<Vec<u8>>), Error> {
+    let mut archive = ZipArchive::new(archive)?;
+    let primary = match find_member(&archive, member) {
+        Some(name) => read_member(&mut archive, &name)?,
+        None => {
+            return Err(Error::Archive {
+                detail: format!("{} not found in archive", member),
+            })
+        }
+    };
+    let mut transaction_logs = Vec::new();
+    for extension in &[".LOG1", ".LOG2"] {
+        if let Some(name) = find_member(&archive, &(member.to_string() + extension)) {
+            transaction_logs.push(read_member(&mut archive, &name)?);
+        }
+    }
+    Ok((primary, transaction_logs))
+}
+
+fn find_member<R: ReadSeek>(archive: &ZipArchive<R>, member: &str) -> Option<String> {
+    let member = normalize_name(member);
+    archive
+        .file_names()
+        .find(|name| normalize_name(name) == member)
+        .map(str::to_string)
+}
+
+fn normalize_name(name: &str) -> String {
+    name.replace('\\', "/")
+        .trim_start_matches('/')
+        .to_ascii_lowercase()
+}
+
+/// The most space reserved up front for a member. The size in the archive isn't trusted, so larger members grow the buffer
+/// as they are read.
+const MAX_RESERVED_SIZE: u64 = 64 * 1024 * 1024;
+
+fn read_member<R: ReadSeek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Vec<u8>, Error> {
+    let mut member = archive.by_name(name)?;
+    let mut buffer = Vec::with_capacity(member.size().min(MAX_RESERVED_SIZE) as usize);
+    member.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser_builder::ParserBuilder;
+    use std::io::{Cursor, Write};
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    fn write_archive(members: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, path) in members {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(&std::fs::read(path).unwrap()).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_read_hive() {
+        let archive = write_archive(&[
+            ("C/Windows/System32/config/SYSTEM", "test_data/system"),
+            (
+                "C/Windows/System32/config/SYSTEM.LOG1",
+                "test_data/system.log1",
+            ),
+            (
+                "C/Windows/System32/config/SYSTEM.LOG2",
+                "test_data/system.log2",
+            ),
+            ("C/Users/Test/NTUSER.DAT", "test_data/NTUSER.DAT"),
+        ]);
+        let (primary, logs) =
+            read_hive(archive.clone(), "\\c\\windows\\system32\\config\\system").unwrap();
+        assert_eq!(std::fs::read("test_data/system").unwrap(), primary);
+        assert_eq!(2, logs.len());
+
+        let (_, logs) = read_hive(archive.clone(), "C/Users/Test/NTUSER.DAT").unwrap();
+        assert!(logs.is_empty());
+
+        assert_eq!(
+            Err(Error::Archive {
+                detail: "C/Windows/System32/config/SAM not found in archive".to_string()
+            }),
+            read_hive(archive, "C/Windows/System32/config/SAM")
+        );
+    }
+
+    #[test]
+    fn test_from_archive() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            write_archive(&[("C/Users/Test/NTUSER.DAT", "test_data/NTUSER.DAT")]).into_inner(),
+        )
+        .unwrap();
+        let parser = ParserBuilder::from_archive(file.path(), "C/Users/Test/NTUSER.DAT")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!((2853, 5523), parser.count_all_keys_and_values(None));
+    }
+}
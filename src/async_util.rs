@@ -0,0 +1,87 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Helpers for using the parser from async code. Parsing is CPU-bound, so these run it on tokio's blocking thread pool
+//! rather than on the executor (see also `ParserBuilder::from_async_read_seek` and `ParserBuilderFromFile::build_async`).
+//! Enabled with the `tokio` feature.
+
+use crate::err::Error;
+use crate::parser::Parser;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Runs `f` on tokio's blocking thread pool
+pub async fn spawn_blocking<T, F>(f: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await?
+}
+
+/// Runs `f` with `parser` on tokio's blocking thread pool, handing the parser back along with the result
+pub async fn with_parser<T, F>(mut parser: Parser, f: F) -> Result<(Parser, T), Error>
+where
+    F: FnOnce(&mut Parser) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    spawn_blocking(move || {
+        let result = f(&mut parser);
+        Ok((parser, result))
+    })
+    .await
+}
+
+pub(crate) async fn read_to_end<R: AsyncRead + Unpin>(mut source: R) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+    source.read_to_end(&mut buffer).await?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_build_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let primary = std::fs::read("test_data/system").unwrap();
+        let log1 = std::fs::read("test_data/system.log1").unwrap();
+        let log2 = std::fs::read("test_data/system.log2").unwrap();
+        let (_, count) = runtime
+            .block_on(async {
+                let mut builder = ParserBuilder::from_async_read_seek(&primary[..]).await?;
+                builder
+                    .with_async_transaction_log(&log1[..])
+                    .await?
+                    .with_async_transaction_log(&log2[..])
+                    .await?;
+                let parser = builder.build_async().await?;
+                with_parser(parser, |parser| parser.count(None)).await
+            })
+            .unwrap();
+
+        let expected = ParserBuilder::from_path("test_data/system")
+            .with_transaction_log("test_data/system.log1")
+            .with_transaction_log("test_data/system.log2")
+            .build()
+            .unwrap()
+            .count(None);
+        assert_eq!(expected, count);
+    }
+}
@@ -28,12 +28,11 @@ use nom::{
     IResult,
 };
 use num_traits::FromPrimitive;
-use serde::Serialize;
-use std::convert::TryInto;
+use serde::{Deserialize, Serialize};
 use std::mem;
 use winstructs::guid::Guid;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Primitive, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Primitive, Serialize)]
 #[repr(u32)]
 pub enum FileType {
     Primary = 0,
@@ -43,7 +42,7 @@ pub enum FileType {
 }
 impl_enum_from_value! { FileType }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Primitive, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Primitive, Serialize)]
 #[repr(u32)]
 pub enum FileFormat {
     DirectMemoryLoad = 1,
@@ -51,7 +50,7 @@ pub enum FileFormat {
 }
 impl_enum_from_value! { FileFormat }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BaseBlock {
     pub base: BaseBlockBase,
     pub ext: BaseBlockExtended,
@@ -65,18 +64,68 @@ impl BaseBlock {
 
         Ok((input, Self { base, ext }))
     }
+
+    pub fn capabilities(&self) -> HiveCapabilities {
+        HiveCapabilities::from_version(self.base.major_version, self.base.minor_version)
+    }
+}
+
+/// Format features a hive supports, based on the version in its base block
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HiveCapabilities {
+    pub major_version: u32,
+    pub minor_version: u32,
+    /// Sub key lists may be fast leaf (lf) lists (1.3+; older hives only use index leaf (li) lists)
+    pub fast_leaf_lists: bool,
+    /// Values larger than 16344 bytes are split up and referenced from a big data (db) record (1.4+); older hives store
+    /// them in a single cell
+    pub big_data: bool,
+    /// Sub key lists may be hash leaf (lh) lists (1.5+)
+    pub hash_leaf_lists: bool,
+    /// Key nodes may have layered key bit fields (1.6+, Windows 10); see `CellKeyNode::layered_key`
+    pub layered_keys: bool,
+}
+
+impl HiveCapabilities {
+    pub(crate) const LATEST_MINOR_VERSION: u32 = 6;
+
+    pub fn from_version(major_version: u32, minor_version: u32) -> Self {
+        Self {
+            major_version,
+            minor_version,
+            fast_leaf_lists: minor_version >= 3,
+            big_data: minor_version >= 4,
+            hash_leaf_lists: minor_version >= 5,
+            layered_keys: minor_version >= 6,
+        }
+    }
+
+    /// Returns true if this version of the format is one the parser knows about (1.2 through 1.6)
+    pub fn is_known_version(&self) -> bool {
+        self.major_version == 1 && (2..=Self::LATEST_MINOR_VERSION).contains(&self.minor_version)
+    }
+}
+
+impl Default for HiveCapabilities {
+    /// The capabilities of the latest version of the format, assumed until a base block is read
+    fn default() -> Self {
+        Self::from_version(1, Self::LATEST_MINOR_VERSION)
+    }
 }
 
 // Structure comments adapted from https://github.com/msuhanov/regf/blob/master/Windows%20registry%20file%20format%20specification.md#base-block
 
 /// Contains the data found in the header of both primary and log registry files
-#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BaseBlockBase {
     /// This number is incremented by 1 in the beginning of a write operation on the primary file.
     pub primary_sequence_number: u32,
     /// This number is incremented by 1 at the end of a write operation on the primary file. The primary sequence number and the secondary sequence number should be equal after a successful write operation.
     pub secondary_sequence_number: u32,
     pub last_modification_date_and_time: DateTime<Utc>,
+    /// `last_modification_date_and_time` as the FILETIME stored in the header
+    #[serde(default)]
+    pub last_modification_filetime: u64,
     pub major_version: u32,
     pub minor_version: u32,
     pub file_type: FileType,
@@ -88,7 +137,10 @@ pub struct BaseBlockBase {
     pub clustering_factor: u32,
     /// UTF-16LE string (contains a partial file path to the primary file, or a file name of the primary file).
     pub filename: String,
-    #[serde(serialize_with = "field_serializers::field_data_as_hex")]
+    #[serde(
+        serialize_with = "field_serializers::field_data_as_hex",
+        deserialize_with = "field_serializers::field_data_from_hex"
+    )]
     pub unk2: Vec<u8>,
     /// XOR-32 checksum of the previous 508 bytes
     pub checksum: u32,
@@ -125,6 +177,7 @@ impl BaseBlockBase {
                 last_modification_date_and_time: util::get_date_time_from_filetime(
                     last_modification_date_and_time,
                 ),
+                last_modification_filetime: last_modification_date_and_time,
                 major_version,
                 minor_version,
                 file_type: FileType::from_value(file_type_bytes, &mut logs),
@@ -140,20 +193,15 @@ impl BaseBlockBase {
         ))
     }
 
-    pub(crate) fn calculate_checksum(bytes: &[u8]) -> Result<u32, Error> {
+    /// Returns the XOR-32 checksum of the first 508 bytes of `bytes` (a base block), as stored in its `checksum` field
+    pub fn calculate_checksum(bytes: &[u8]) -> Result<u32, Error> {
         let mut index = 0;
         let mut xsum = 0;
 
-        let slice_to_u32 =
-            |s: &[u8]| -> [u8; 4] { s.try_into().expect("slice with incorrect length") };
-        let size_of_u32 = mem::size_of::<u32>();
-
         while index <= 0x01FB {
-            let slice = bytes
-                .get(index..index + size_of_u32)
+            xsum ^= util::read_u32_le(bytes, index)
                 .ok_or_else(|| Error::buffer("calculate_checksum"))?;
-            xsum ^= u32::from_le_bytes(slice_to_u32(slice));
-            index += size_of_u32;
+            index += mem::size_of::<u32>();
         }
         let ret = match xsum {
             0 => 1,
@@ -162,10 +210,36 @@ impl BaseBlockBase {
         };
         Ok(ret)
     }
+
+    /// Calculates the checksum of the base block at the start of `bytes` and stores it in its `checksum` field
+    pub(crate) fn update_checksum(bytes: &mut [u8]) -> Result<(), Error> {
+        let checksum = Self::calculate_checksum(bytes)?;
+        bytes
+            .get_mut(Self::CHECKSUM_OFFSET..Self::CHECKSUM_OFFSET + mem::size_of::<u32>())
+            .ok_or_else(|| Error::buffer("update_checksum"))?
+            .copy_from_slice(&checksum.to_le_bytes());
+        Ok(())
+    }
+
+    /// Returns true if the checksum stored in the base block at the start of `bytes` matches the calculated one
+    pub fn verify_checksum(bytes: &[u8]) -> Result<bool, Error> {
+        let stored = util::read_u32_le(bytes, Self::CHECKSUM_OFFSET)
+            .ok_or_else(|| Error::buffer("verify_checksum"))?;
+        Ok(stored == Self::calculate_checksum(bytes)?)
+    }
+}
+
+/// Where the parser's base block was read from
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum BaseBlockSource {
+    #[default]
+    PrimaryFile,
+    /// The primary file's base block was invalid, so the backup in a transaction log was used
+    TransactionLog,
 }
 
 /// Contains the additional data found in the header of a primary registry files
-#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BaseBlockExtended {
     pub reserved: FileBaseBlockReserved,
     pub boot_type: u32,
@@ -191,15 +265,24 @@ impl BaseBlockExtended {
 }
 
 // Relevant to win10+. See https://github.com/msuhanov/regf/blob/master/Windows%20registry%20file%20format%20specification.md#base-block for additional info in this area
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct FileBaseBlockReserved {
+    #[serde(deserialize_with = "field_serializers::field_guid_from_string")]
     pub rm_id: Guid,
+    #[serde(deserialize_with = "field_serializers::field_guid_from_string")]
     pub log_id: Guid,
     pub flags: FileBaseBlockReservedFlags,
+    #[serde(deserialize_with = "field_serializers::field_guid_from_string")]
     pub tm_id: Guid,
     pub signature: u32,
     pub last_reorganized_timestamp: DateTime<Utc>,
-    #[serde(serialize_with = "field_serializers::field_data_as_hex")]
+    /// `last_reorganized_timestamp` as the FILETIME stored in the header
+    #[serde(default)]
+    pub last_reorganized_filetime: u64,
+    #[serde(
+        serialize_with = "field_serializers::field_data_as_hex",
+        deserialize_with = "field_serializers::field_data_from_hex"
+    )]
     pub remaining: Vec<u8>,
     pub logs: Logs,
 }
@@ -214,6 +297,7 @@ impl PartialEq for FileBaseBlockReserved {
             && self.tm_id == other.tm_id
             && self.signature == other.signature
             && self.last_reorganized_timestamp == other.last_reorganized_timestamp
+            && self.last_reorganized_filetime == other.last_reorganized_filetime
             && self.remaining == other.remaining
     }
 }
@@ -241,6 +325,7 @@ impl FileBaseBlockReserved {
                 last_reorganized_timestamp: util::get_date_time_from_filetime(
                     last_reorganized_timestamp,
                 ),
+                last_reorganized_filetime: last_reorganized_timestamp,
                 remaining: remaining.to_vec(),
                 logs,
             },
@@ -248,7 +333,7 @@ impl FileBaseBlockReserved {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Primitive, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Primitive, Serialize)]
 #[repr(u32)]
 pub enum FileBaseBlockReservedFlags {
     None = 0,
@@ -320,6 +405,7 @@ mod tests {
                 last_modification_date_and_time: util::get_date_time_from_filetime(
                     129782121007374460,
                 ),
+                last_modification_filetime: 129782121007374460,
                 major_version: 1,
                 minor_version: 3,
                 file_type: FileType::Primary,
@@ -354,6 +440,29 @@ mod tests {
         assert_eq!(expected_error, ret);
     }
 
+    #[test]
+    fn test_base_block_json_round_trip() {
+        let buffer = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let (_, base_block) = BaseBlock::from_bytes(&buffer).unwrap();
+        let json = serde_json::to_string(&base_block).unwrap();
+        let read: BaseBlock = serde_json::from_str(&json).unwrap();
+        assert_eq!(base_block, read);
+        assert_eq!(base_block.base.logs, read.base.logs);
+        assert_eq!(json, serde_json::to_string(&read).unwrap());
+    }
+
+    #[test]
+    fn test_hive_capabilities() {
+        let capabilities = HiveCapabilities::from_version(1, 4);
+        assert!(capabilities.fast_leaf_lists && capabilities.big_data);
+        assert!(!capabilities.hash_leaf_lists && !capabilities.layered_keys);
+        assert!(capabilities.is_known_version());
+
+        assert!(HiveCapabilities::default().layered_keys);
+        assert!(!HiveCapabilities::from_version(1, 7).is_known_version());
+        assert!(!HiveCapabilities::from_version(2, 5).is_known_version());
+    }
+
     #[test]
     fn test_calculate_checksum() {
         let bytes = [
@@ -404,5 +513,14 @@ mod tests {
             Err(Error::buffer("calculate_checksum")),
             BaseBlockBase::calculate_checksum(&[])
         );
+        // the stored checksum (0xCC62CA21) doesn't match
+        assert_eq!(Ok(false), BaseBlockBase::verify_checksum(&bytes));
+        let mut bytes = bytes;
+        bytes[BaseBlockBase::CHECKSUM_OFFSET] = 0x20;
+        assert_eq!(Ok(true), BaseBlockBase::verify_checksum(&bytes));
+        assert_eq!(
+            Err(Error::buffer("verify_checksum")),
+            BaseBlockBase::verify_checksum(&bytes[..100])
+        );
     }
 }
@@ -14,10 +14,11 @@
  * limitations under the License.
  */
 
+use chrono::{DateTime, Utc};
 use nom::{branch::alt, bytes::complete::tag, combinator::map, IResult};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum CellState {
     DeletedTransactionLog = -3,
     DeletedPrimaryFile = -2,
@@ -79,6 +80,20 @@ impl CellType {
     }
 }
 
+/// An earlier state of a key or value recovered from the transaction logs (see `CellKeyNode::versions` and
+/// `CellKeyValue::versions`)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Version<'a, T> {
+    /// The sequence number of the hive when this state was current
+    pub sequence_num: Option<u32>,
+    /// The sequence number of the log entry that replaced this state
+    pub updated_by_sequence_num: Option<u32>,
+    /// For keys, the last written timestamp of this state. Values don't have a timestamp of their own, so for them it's
+    /// only set for UWP values (see `CellKeyValue::uwp_timestamp`)
+    pub timestamp: Option<DateTime<Utc>>,
+    pub item: &'a T,
+}
+
 pub trait Cell {
     fn get_file_offset_absolute(&self) -> usize;
     fn get_hash(&self) -> Option<blake3::Hash>;
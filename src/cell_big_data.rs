@@ -17,7 +17,8 @@
 use crate::cell_key_value::{CellKeyValue, CellKeyValueDataTypes};
 use crate::err::Error;
 use crate::file_info::FileInfo;
-use crate::log::Logs;
+use crate::log::{LogCode, Logs};
+use crate::util;
 use nom::{
     bytes::complete::tag,
     multi::count,
@@ -26,6 +27,9 @@ use nom::{
 };
 use serde::Serialize;
 
+/// The content of a big data value, the offsets of its segments, and the slack of the last segment
+type BigDataBytes = (Vec<u8>, Vec<usize>, Option<util::CellSlack>);
+
 /* List of data segments. Big data is used to reference data larger than 16344 bytes
 When the Minor version field of the base block is greater than 3, it has the following structure: */
 #[derive(Debug, Eq, PartialEq, Serialize)]
@@ -37,13 +41,16 @@ pub struct CellBigData {
 }
 
 impl CellBigData {
-    /// Returns a tuple of the full content buffer and the absolute data offsets
+    /// Returns a tuple of the full content buffer, the absolute data offsets, and the slack after the data in the last segment (see `util::read_cell_slack`).
+    /// If a segment can't be read, or the segments hold less than `data_size`, the data read up to that point is returned and the
+    /// shortfall is logged to `logs`; only a big data cell or segment list that can't be read is an error.
     pub(crate) fn get_big_data_bytes(
         file_info: &FileInfo,
         offset: usize,
         data_type: &CellKeyValueDataTypes,
         data_size: u32,
-    ) -> Result<(Vec<u8>, Vec<usize>), Error> {
+        logs: &mut Logs,
+    ) -> Result<BigDataBytes, Error> {
         let slice = file_info
             .buffer
             .get(offset..)
@@ -54,23 +61,70 @@ impl CellBigData {
 
         let mut big_data_buffer: Vec<u8> = Vec::new();
         let mut data_size_remaining = data_size;
+        let mut slack = None;
+        let mut truncated = false;
         for offset in data_offsets_absolute.iter() {
             if data_size_remaining > 0 {
-                let (input, size) = CellBigData::parse_big_data_size(file_info, *offset)?;
+                let (input, size) = match CellBigData::parse_big_data_size(file_info, *offset) {
+                    Ok(segment) => segment,
+                    Err(_) => {
+                        logs.add(
+                            LogCode::WarningBigDataContent,
+                            &format!(
+                                "Big data segment at relative offset {} is past the end of the file; read {} of {} bytes",
+                                offset,
+                                big_data_buffer.len(),
+                                data_size
+                            ),
+                        );
+                        truncated = true;
+                        break;
+                    }
+                };
                 let size_to_read = std::cmp::min(
                     size.unsigned_abs(),
                     std::cmp::min(data_size_remaining, CellKeyValue::BIG_DATA_SIZE_THRESHOLD),
-                );
-                let slice = input
-                    .get(..size_to_read as usize)
-                    .ok_or_else(|| Error::buffer("get_big_data_bytes"))?;
+                ) as usize;
+                // a segment cut short by the end of the file is read up to there
+                let slice = &input[..std::cmp::min(size_to_read, input.len())];
                 big_data_buffer.extend_from_slice(slice);
-                data_size_remaining -= size_to_read;
+                data_size_remaining -= slice.len() as u32;
+                if slice.len() < size_to_read {
+                    logs.add(
+                        LogCode::WarningBigDataContent,
+                        &format!(
+                            "Big data segment at relative offset {} exceeds the end of the file; read {} of {} bytes",
+                            offset,
+                            big_data_buffer.len(),
+                            data_size
+                        ),
+                    );
+                    truncated = true;
+                    break;
+                }
+                if data_size_remaining == 0 {
+                    slack = util::read_cell_slack(
+                        &file_info.buffer,
+                        file_info.hbin_offset_absolute + *offset as usize,
+                        size_to_read,
+                    );
+                }
             }
         }
+        if data_size_remaining > 0 && !truncated {
+            logs.add(
+                LogCode::WarningBigDataContent,
+                &format!(
+                    "Big data segments hold {} of {} bytes",
+                    big_data_buffer.len(),
+                    data_size
+                ),
+            );
+        }
         Ok((
             data_type.get_value_bytes(&big_data_buffer[..]),
             data_offsets_absolute.iter().map(|x| *x as usize).collect(),
+            slack,
         ))
     }
 
@@ -198,8 +252,14 @@ mod tests {
             ]
             .to_vec(),
         };
-        let res =
-            CellBigData::get_big_data_bytes(&file_info, 20, &CellKeyValueDataTypes::REG_DWORD, 4);
+        let mut logs = Logs::default();
+        let res = CellBigData::get_big_data_bytes(
+            &file_info,
+            20,
+            &CellKeyValueDataTypes::REG_DWORD,
+            4,
+            &mut logs,
+        );
         assert_eq!(Err(Error::buffer("get_big_data_bytes")), res);
     }
 }
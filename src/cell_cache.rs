@@ -0,0 +1,247 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell_key_node::CellKeyNode;
+use crate::cell_key_value::CellKeyValue;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Counters for one kind of cached cell
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct CellCacheCounters {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Number of cells currently in the cache
+    pub len: usize,
+}
+
+impl CellCacheCounters {
+    /// Returns the fraction of lookups that were served from the cache (0.0 if there haven't been any lookups)
+    pub fn hit_ratio(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+/// Statistics for the cell cache enabled by `ParserBuilder::cell_cache_capacity`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct CellCacheStats {
+    /// Maximum number of key nodes (and, separately, key values) the cache holds
+    pub capacity: usize,
+    pub keys: CellCacheCounters,
+    pub values: CellCacheCounters,
+}
+
+/// A least recently used map of parsed cells keyed by their absolute file offset
+#[derive(Clone, Debug)]
+struct Lru<T> {
+    capacity: usize,
+    entries: HashMap<usize, (T, u64)>,
+    // last use -> file offset; the first entry is the least recently used
+    recency: BTreeMap<u64, usize>,
+    tick: u64,
+    counters: CellCacheCounters,
+}
+
+impl<T: Clone> Lru<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            counters: CellCacheCounters::default(),
+        }
+    }
+
+    fn get(&mut self, offset: usize) -> Option<&T> {
+        if self.capacity == 0 {
+            return None;
+        }
+        match self.entries.get_mut(&offset) {
+            Some((cell, last_used)) => {
+                self.recency.remove(last_used);
+                self.tick += 1;
+                *last_used = self.tick;
+                self.recency.insert(self.tick, offset);
+                self.counters.hits += 1;
+                Some(cell)
+            }
+            None => {
+                self.counters.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, offset: usize, cell: &T) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.insert(offset, (cell.clone(), self.tick)) {
+            self.recency.remove(&last_used);
+        } else if self.entries.len() > self.capacity {
+            if let Some((_, evicted)) = self.recency.pop_first() {
+                self.entries.remove(&evicted);
+                self.counters.evictions += 1;
+            }
+        }
+        self.recency.insert(self.tick, offset);
+    }
+
+    /// Returns the cell at `offset` without counting a lookup or updating its recency
+    fn peek(&self, offset: usize) -> Option<&T> {
+        self.entries.get(&offset).map(|(cell, _)| cell)
+    }
+
+    fn counters(&self) -> CellCacheCounters {
+        CellCacheCounters {
+            len: self.entries.len(),
+            ..self.counters
+        }
+    }
+}
+
+/// Caches key nodes and key values as they are parsed so that keys which are read again (by repeated `get_key` calls,
+/// `get_parent_key` chains, or diffing) are cloned rather than re-parsed.
+/// Only cells read directly from the primary file's buffer are cached. Disabled (capacity 0) by default.
+#[derive(Clone, Debug)]
+pub(crate) struct CellCache {
+    keys: Lru<CellKeyNode>,
+    values: Lru<CellKeyValue>,
+}
+
+impl CellCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            keys: Lru::new(capacity),
+            values: Lru::new(capacity),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.keys.capacity > 0
+    }
+
+    /// Returns the key node parsed at `offset`. Copies of it need their path and sequence number updated.
+    pub(crate) fn get_key(&mut self, offset: usize) -> Option<&CellKeyNode> {
+        self.keys.get(offset)
+    }
+
+    pub(crate) fn peek_key(&self, offset: usize) -> Option<&CellKeyNode> {
+        self.keys.peek(offset)
+    }
+
+    pub(crate) fn insert_key(&mut self, key: &CellKeyNode) {
+        self.keys.insert(key.file_offset_absolute, key)
+    }
+
+    pub(crate) fn get_value(&mut self, offset: usize) -> Option<CellKeyValue> {
+        self.values.get(offset).cloned()
+    }
+
+    pub(crate) fn insert_value(&mut self, value: &CellKeyValue) {
+        self.values.insert(value.file_offset_absolute, value)
+    }
+
+    pub(crate) fn stats(&self) -> CellCacheStats {
+        CellCacheStats {
+            capacity: self.keys.capacity,
+            keys: self.keys.counters(),
+            values: self.values.counters(),
+        }
+    }
+}
+
+impl Default for CellCache {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_lru() {
+        let mut lru = Lru::new(2);
+        lru.insert(1, &"one");
+        lru.insert(2, &"two");
+        assert_eq!(Some(&"one"), lru.get(1));
+        lru.insert(3, &"three"); // evicts 2, the least recently used
+        assert_eq!(None, lru.get(2));
+        assert_eq!(Some(&"one"), lru.get(1));
+        assert_eq!(Some(&"three"), lru.get(3));
+        lru.insert(3, &"three again");
+        assert_eq!(
+            CellCacheCounters {
+                hits: 3,
+                misses: 1,
+                evictions: 1,
+                len: 2
+            },
+            lru.counters()
+        );
+        assert_eq!(0.75, lru.counters().hit_ratio());
+
+        let mut disabled = Lru::new(0);
+        disabled.insert(1, &"one");
+        assert_eq!(None, disabled.get(1));
+        assert_eq!(CellCacheCounters::default(), disabled.counters());
+    }
+
+    #[test]
+    fn test_cell_cache() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let mut cached_parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .cell_cache_capacity(64)
+            .build()
+            .unwrap();
+        assert_eq!(CellCacheStats::default(), parser.cell_cache_stats());
+
+        let path = "Control Panel\\Accessibility\\Keyboard Response";
+        let key = parser.get_key(path, false).unwrap().unwrap();
+        for _ in 0..2 {
+            let cached_key = cached_parser.get_key(path, false).unwrap().unwrap();
+            assert_eq!(key, cached_key);
+        }
+        let stats = cached_parser.cell_cache_stats();
+        assert_eq!(64, stats.capacity);
+        assert!(stats.keys.hits > 0);
+        // lookups also cache the siblings they pass over
+        assert!(stats.keys.len as u64 > stats.keys.misses);
+        assert_eq!(9, stats.values.hits);
+        assert_eq!(9, stats.values.misses);
+
+        // the parent is read through its offset rather than its path, and is found in the cache
+        let mut key = key;
+        let parent = parser.get_parent_key(&mut key).unwrap().unwrap();
+        let hits = cached_parser.cell_cache_stats().keys.hits;
+        assert_eq!(
+            parent,
+            cached_parser.get_parent_key(&mut key).unwrap().unwrap()
+        );
+        assert_eq!(hits + 1, cached_parser.cell_cache_stats().keys.hits);
+    }
+}
@@ -0,0 +1,168 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tracking of the cells read so far, to catch allocated cells that overlap (see
+//! `ParserBuilderFromPath::check_overlapping_cells`)
+
+use std::collections::BTreeMap;
+
+/// Whether key and value cells are checked to not overlap the cells read before them
+/// (see `ParserBuilderFromPath::check_overlapping_cells`)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OverlapCheck {
+    /// Cells aren't checked
+    Off,
+    /// Keys and values whose cell overlaps one read before are returned with a `LogCode::WarningOverlap` log
+    Flag,
+    /// Keys and values whose cell overlaps one read before are left out, and the key listing them gets a
+    /// `LogCode::WarningOverlap` log
+    Skip,
+}
+
+/// The extents of the cells read so far. The first cell read keeps the bytes it covers: a cell overlapping it isn't
+/// recorded, so the outcome only depends on the order cells are read in (sub key list and value list order).
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CellExtents {
+    // absolute file offset -> end of the cell
+    extents: BTreeMap<usize, usize>,
+}
+
+impl CellExtents {
+    /// Records the cell at `offset` and returns None, or returns the offset of a recorded cell that it overlaps.
+    /// Reading the same cell again isn't an overlap.
+    pub(crate) fn insert(&mut self, offset: usize, size: usize) -> Option<usize> {
+        if self.extents.contains_key(&offset) {
+            return None;
+        }
+        let end = offset.saturating_add(size);
+        if let Some((&previous, _)) = self
+            .extents
+            .range(..offset)
+            .next_back()
+            .filter(|&(_, &previous_end)| previous_end > offset)
+        {
+            return Some(previous);
+        }
+        if let Some((&next, _)) = self.extents.range(offset..end).next() {
+            return Some(next);
+        }
+        self.extents.insert(offset, end);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::LogCode;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_insert() {
+        let mut extents = CellExtents::default();
+        assert_eq!(None, extents.insert(0x1020, 0x50));
+        assert_eq!(None, extents.insert(0x1070, 0x10));
+        assert_eq!(None, extents.insert(0x1020, 0x50));
+        assert_eq!(Some(0x1020), extents.insert(0x1030, 0x10));
+        assert_eq!(Some(0x1070), extents.insert(0x1078, 0x10));
+        assert_eq!(Some(0x1020), extents.insert(0x1010, 0x18));
+        // overlapping cells aren't recorded
+        assert_eq!(None, extents.insert(0x1000, 0x20));
+        assert_eq!(None, extents.insert(0x1080, 0x08));
+    }
+
+    #[test]
+    fn test_check_overlapping_cells() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .check_overlapping_cells(OverlapCheck::Flag)
+            .build()
+            .unwrap();
+        assert!(!ParserIterator::new(&parser).iter().any(|key| {
+            key.logs.has_code(LogCode::WarningOverlap)
+                || key
+                    .value_iter()
+                    .any(|value| value.logs.has_code(LogCode::WarningOverlap))
+        }));
+
+        // Grow "Control Panel" over the cell of "Console", which is listed (and read) before it
+        let control_panel = parser.get_key("Control Panel", false).unwrap().unwrap();
+        let console = parser.get_key("Console", false).unwrap().unwrap();
+        let (offset, console_offset) = (
+            control_panel.file_offset_absolute,
+            console.file_offset_absolute,
+        );
+        assert_eq!(offset + control_panel.get_cell_size(), console_offset);
+        let mut hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let size = control_panel.detail.size() + console.detail.size();
+        hive[offset..offset + 4].copy_from_slice(&size.to_le_bytes());
+
+        let read_root = |check: Option<OverlapCheck>| {
+            let mut builder = ParserBuilder::from_file(Cursor::new(hive.clone()));
+            if let Some(check) = check {
+                builder.check_overlapping_cells(check);
+            }
+            let mut parser = builder.build().unwrap();
+            let mut root = parser.get_root_key().unwrap().unwrap();
+            let sub_keys = root.read_sub_keys(&mut parser);
+            let find = |name: &str| sub_keys.iter().find(|key| key.key_name == name).cloned();
+            (root, find("Control Panel"), find("Console"))
+        };
+
+        let (root, control_panel, console) = read_root(None);
+        assert!(!root.logs.has_code(LogCode::WarningOverlap));
+        assert!(!control_panel
+            .unwrap()
+            .logs
+            .has_code(LogCode::WarningOverlap));
+        assert!(!console.unwrap().logs.has_code(LogCode::WarningOverlap));
+
+        let (root, control_panel, console) = read_root(Some(OverlapCheck::Flag));
+        assert!(!root.logs.has_code(LogCode::WarningOverlap));
+        let control_panel = control_panel.unwrap();
+        assert!(control_panel.logs.has_code(LogCode::WarningOverlap));
+        assert!(control_panel.logs.get_string().contains(&format!(
+            "Cell of sub key at offset {} overlaps the cell at offset {}",
+            offset, console_offset
+        )));
+        assert!(!console.unwrap().logs.has_code(LogCode::WarningOverlap));
+
+        let (root, control_panel, console) = read_root(Some(OverlapCheck::Skip));
+        assert!(root.logs.has_code(LogCode::WarningOverlap));
+        assert!(control_panel.is_none());
+        assert!(console.is_some());
+
+        // Each lookup starts with no cells read, so the cells of an earlier lookup don't decide what's flagged
+        let mut builder = ParserBuilder::from_file(Cursor::new(hive));
+        builder.check_overlapping_cells(OverlapCheck::Flag);
+        let mut parser = builder.build().unwrap();
+        let mut root = parser.get_root_key().unwrap().unwrap();
+        root.read_sub_keys(&mut parser);
+        assert!(parser
+            .state
+            .cell_extents
+            .extents
+            .contains_key(&root.file_offset_absolute));
+        let console = parser.get_key("Console", false).unwrap().unwrap();
+        assert!(!console.logs.has_code(LogCode::WarningOverlap));
+        assert!(!parser
+            .state
+            .cell_extents
+            .extents
+            .contains_key(&root.file_offset_absolute));
+    }
+}
@@ -13,14 +13,15 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use crate::cell::{Cell, CellState};
+use crate::cell::{Cell, CellState, CellType, Version};
+use crate::cell_extents::OverlapCheck;
 use crate::cell_key_security;
 use crate::cell_key_value::CellKeyValue;
-use crate::err::Error;
+use crate::err::{Error, ErrorContext};
 use crate::field_offset_len::{FieldFull, FieldLight};
 use crate::field_serializers;
 use crate::file_info::FileInfo;
-use crate::filter::{Filter, FilterBuilder, FilterFlags};
+use crate::filter::{Filter, FilterFlags};
 use crate::impl_enum;
 use crate::impl_flags_from_bits;
 use crate::impl_serialize_for_bitflags;
@@ -32,9 +33,10 @@ use crate::parser::Parser;
 use crate::read_value_offset_length;
 use crate::state::State;
 use crate::sub_key_list_lf::SubKeyListLf;
-use crate::sub_key_list_lh::SubKeyListLh;
+use crate::sub_key_list_lh::{self, SubKeyListLh};
 use crate::sub_key_list_li::SubKeyListLi;
 use crate::sub_key_list_ri::SubKeyListRi;
+use crate::suspicious_name::{self, SuspiciousName};
 use crate::util;
 use bitflags::bitflags;
 use blake3::Hash;
@@ -46,7 +48,10 @@ use nom::{
     number::complete::{le_i32, le_u16, le_u32, le_u64},
     IResult,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::Arc;
 use winstructs::security::SecurityDescriptor;
 
 make_file_offset_structs!(
@@ -76,6 +81,114 @@ make_file_offset_structs!(
     }
 );
 
+/// How the path of a recovered (deleted) key was reconstructed from its chain of parent offsets
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum PathConfidence {
+    /// The parent key is allocated, so the path is the one the key had when it was deleted
+    Exact,
+    /// The path runs through one or more deleted parent keys which were recovered as well
+    Derived,
+    /// The chain of parents couldn't be followed to the root key; the path starts at the last parent found
+    Orphan,
+}
+
+/// How a layered key combines with the same key in the layers below it
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum LayerSemantics {
+    /// The key's values and sub keys are merged with those of the lower layers
+    None,
+    /// The key was deleted in this layer
+    IsTombstone,
+    /// The key hides the same key in the lower layers, but not its sub keys
+    IsSupersedeLocal,
+    /// The key hides the same key and its sub keys in the lower layers
+    IsSupersedeTree,
+}
+
+/// The layered key bit fields of a key node, which follow its access bits in hives that support layered keys (format 1.6+)
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LayeredKey {
+    /// The key's class name is inherited from the lower layers
+    pub inherit_class: bool,
+    pub layer_semantics: LayerSemantics,
+}
+
+impl LayeredKey {
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        Self {
+            inherit_class: bits & 0x01 != 0,
+            layer_semantics: match bits >> 6 {
+                0 => LayerSemantics::None,
+                1 => LayerSemantics::IsTombstone,
+                2 => LayerSemantics::IsSupersedeLocal,
+                _ => LayerSemantics::IsSupersedeTree,
+            },
+        }
+    }
+}
+
+/// A key node's flag bits, decoded (see `CellKeyNode::flags`). Flags are serialized by name; bits without a name are
+/// serialized in hex.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct KeyFlags {
+    #[serde(serialize_with = "field_serializers::flag_names")]
+    pub key_node: KeyNodeFlags,
+    #[serde(serialize_with = "field_serializers::flag_names")]
+    pub access: AccessFlags,
+    /// None if the hive's format doesn't support layered keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layered_key: Option<LayeredKey>,
+}
+
+/// A key's registry virtualization state, along with the other bits Windows keeps in the upper half of the largest sub
+/// key name size field (see `CellKeyNode::virtualization`). Keys left behind by UAC virtualization (ex: writes to
+/// `HKLM\SOFTWARE` by a program without administrator rights, redirected to the user's virtual store) show up here.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct Virtualization {
+    /// `KeyNodeFlags::KEY_VIRTUAL_SOURCE`: this key was virtualized at least once
+    pub virtual_source: bool,
+    /// `KeyNodeFlags::KEY_VIRTUAL_TARGET`: this key is a virtual copy
+    pub virtual_target: bool,
+    /// `KeyNodeFlags::KEY_VIRTUAL_STORE`: this key is a part of a virtual store path
+    pub virtual_store: bool,
+    /// This key is a `VirtualStore` key or below one (ex: `VirtualStore\MACHINE\SOFTWARE` in UsrClass.dat), whatever its flags
+    pub virtual_store_path: bool,
+    #[serde(serialize_with = "field_serializers::flag_names")]
+    pub control: VirtualizationControlFlags,
+    /// From either location the hive's version may use
+    #[serde(serialize_with = "field_serializers::flag_names")]
+    pub user: UserFlags,
+    /// Debug bits (bits 24-31 of the largest sub key name size field, Windows Vista+), which make the kernel break into
+    /// the debugger when the key is opened, created, etc.
+    pub debug: u8,
+    /// The largest sub key name size without the bits above
+    pub largest_sub_key_name_size: u16,
+}
+
+impl Virtualization {
+    /// Whether anything marks the key as touched by virtualization, or any user flag or debug bit is set
+    pub fn is_set(&self) -> bool {
+        self.virtual_source
+            || self.virtual_target
+            || self.virtual_store
+            || self.virtual_store_path
+            || !self.control.is_empty()
+            || !self.user.is_empty()
+            || self.debug != 0
+    }
+}
+
+/// Whether sub keys are checked to point back to the key that lists them (see `ParserBuilderFromPath::verify_parent_offsets`)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParentOffsetCheck {
+    /// Sub keys aren't checked
+    Off,
+    /// Sub keys whose parent offset is wrong are returned with a `LogCode::WarningParent` log
+    Flag,
+    /// Sub keys whose parent offset is wrong are left out, and the key listing them gets a `LogCode::WarningParent` log
+    Skip,
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
 pub(crate) enum FilterMatchState {
     None,
@@ -89,22 +202,36 @@ pub(crate) struct CellKeyNodeIteration {
     pub(crate) track_returned: u32,
     pub(crate) filter_state: Option<FilterMatchState>,
     pub(crate) sub_keys_iter_index: usize,
+    /// Number of levels below the key the traversal started from
+    pub(crate) depth: usize,
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CellKeyNode {
     pub file_offset_absolute: usize,
     pub detail: CellKeyNodeDetailEnum,
     pub key_name: String,
-    pub path: String,
+    /// Shared between clones of the key, and with the records and values the iterators return for it
+    pub path: Arc<str>,
     pub cell_state: CellState,
     pub sequence_num: Option<u32>,
     pub updated_by_sequence_num: Option<u32>,
-    pub(crate) sub_values: Vec<CellKeyValue>, // sub_values includes deleted values, if present
+    /// Includes deleted values, if present. Shared between clones of the key, so passing keys around doesn't copy value data.
+    pub(crate) sub_values: Arc<Vec<CellKeyValue>>,
     pub logs: Logs,
+    /// Set for keys recovered from free cells or slack space (see `PathConfidence`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_confidence: Option<PathConfidence>,
+    /// The name of the GUID the key is named after, if it's known. Only set on the keys returned by the iterators and
+    /// lookups (`get_key`, `read_sub_keys`, etc.) of a parser built with `ParserBuilder::guid_names`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guid_name: Option<String>,
 
     #[serde(skip)]
     pub cell_sub_key_offsets_absolute: Vec<u32>,
+    /// The key name's bytes, only kept when they can't be recreated from `key_name` (see `name_raw`)
+    #[serde(skip)]
+    pub key_name_raw: Option<Vec<u8>>,
 
     #[serde(skip)]
     pub hash: Option<Hash>,
@@ -127,37 +254,187 @@ pub(crate) struct CellKeyNodeReadOptions<'a> {
 
 impl CellKeyNode {
     const MIN_CELL_KEY_SIZE: usize = 72;
-    const SIGNATURE: &'static str = "nk";
+    /// Windows only uses a single level of ri lists, but nested ones are followed up to this depth
+    const MAX_SUB_KEY_LIST_DEPTH: usize = 8;
+    pub(crate) const SIGNATURE: &'static str = "nk";
+
+    // Offsets of the fields `from_bytes` reads, from the start of the cell's data (after the cell size). These are for
+    // code that reads or writes key nodes directly: `Parser::count`, `Parser::validate`, and `HiveWriter`.
+    pub(crate) const FLAGS_FIELD: usize = 2;
+    pub(crate) const PARENT_KEY_OFFSET_FIELD: usize = 16;
+    pub(crate) const NUMBER_OF_SUB_KEYS_FIELD: usize = 20;
+    pub(crate) const SUB_KEYS_LIST_OFFSET_FIELD: usize = 28;
+    pub(crate) const NUMBER_OF_KEY_VALUES_FIELD: usize = 36;
+    pub(crate) const KEY_VALUES_LIST_OFFSET_FIELD: usize = 40;
+    pub(crate) const SECURITY_KEY_OFFSET_FIELD: usize = 44;
+    pub(crate) const CLASS_NAME_OFFSET_FIELD: usize = 48;
+    pub(crate) const KEY_NAME_SIZE_FIELD: usize = 72;
+    pub(crate) const CLASS_NAME_SIZE_FIELD: usize = 74;
+    /// Size of the fields before the key name
+    pub(crate) const HEADER_SIZE: usize = 76;
 
     pub fn read_sub_keys(&mut self, parser: &mut Parser) -> Vec<Self> {
-        let (sub_keys, _) = self.read_sub_keys_internal(
+        let (mut sub_keys, _) = self.read_sub_keys_internal(
             &parser.file_info,
-            &mut parser.state,
+            parser.state.for_lookup(),
             &Filter::new(),
             None,
             true,
+            None,
         );
+        if let Some(guid_names) = &parser.guid_names {
+            sub_keys.iter_mut().for_each(|key| guid_names.annotate(key));
+        }
         sub_keys
     }
 
     pub fn get_sub_key_by_path(&mut self, parser: &mut Parser, sub_path: &str) -> Option<Self> {
-        if sub_path.is_empty() {
-            Some(self.clone())
-        } else {
-            let filter = FilterBuilder::new()
-                .add_key_path(&format!("{}\\{}", self.path, sub_path))
-                .key_path_has_root(true)
-                .build()
-                .unwrap_or_default();
-            self.get_sub_key_internal(&parser.file_info, &mut parser.state, &filter, None)
+        self.get_sub_key(sub_path, parser, false)
+    }
+
+    /// Returns the key at `sub_path`, relative to this key (an empty path returns a copy of this key).
+    /// Key names in `sub_path` are separated by backslashes; a backslash within a key name is written as two backslashes
+    /// (see `util::escape_key_name`). Names are compared ignoring ASCII case unless `case_sensitive` is true.
+    pub fn get_sub_key(
+        &self,
+        sub_path: &str,
+        parser: &mut Parser,
+        case_sensitive: bool,
+    ) -> Option<Self> {
+        let mut sub_key = self.find_sub_key_path(
+            &parser.file_info,
+            parser.state.for_lookup(),
+            sub_path,
+            case_sensitive,
+        );
+        if let (Some(guid_names), Some(sub_key)) = (&parser.guid_names, sub_key.as_mut()) {
+            guid_names.annotate(sub_key);
         }
+        sub_key
+    }
+
+    /// Like `get_sub_key`, but reads with `state` rather than with the parser's own state
+    pub(crate) fn find_sub_key_path(
+        &self,
+        file_info: &FileInfo,
+        state: &mut State,
+        sub_path: &str,
+        case_sensitive: bool,
+    ) -> Option<Self> {
+        let names = util::split_key_path(sub_path);
+        let (name, parent_names) = match names.split_last() {
+            Some(split) => split,
+            None => return Some(self.clone()),
+        };
+        // the keys along the way are only needed for their sub key lists, so their values aren't read
+        let mut parent: Option<Self> = None;
+        for parent_name in parent_names {
+            parent = Some(parent.as_ref().unwrap_or(self).find_sub_key_unread(
+                file_info,
+                state,
+                parent_name,
+                case_sensitive,
+            )?);
+        }
+        parent
+            .as_ref()
+            .unwrap_or(self)
+            .find_sub_key(file_info, state, name, case_sensitive)
+    }
+
+    /// Returns the sub key named `name` (which is not a path; any backslashes are part of the name)
+    pub(crate) fn find_sub_key(
+        &self,
+        file_info: &FileInfo,
+        state: &mut State,
+        name: &str,
+        case_sensitive: bool,
+    ) -> Option<Self> {
+        let sub_key = self.find_sub_key_unread(file_info, state, name, case_sensitive)?;
+        self.read_sub_key(file_info, state, &sub_key)
+    }
+
+    /// Reads `sub_key`, as returned by `find_sub_key_unread`, along with its values
+    pub(crate) fn read_sub_key(
+        &self,
+        file_info: &FileInfo,
+        state: &mut State,
+        sub_key: &Self,
+    ) -> Option<Self> {
+        let sub_key = Self::read(
+            file_info,
+            state,
+            CellKeyNodeReadOptions {
+                offset: sub_key.file_offset_absolute,
+                cur_path: &self.path,
+                filter: None,
+                self_is_filter_match_or_descendent: true,
+                sequence_num: None,
+                get_deleted_and_modified: false,
+            },
+        );
+        sub_key.ok().flatten().map(|mut sub_key| {
+            // mark the key as the result of the lookup (as a filter match would be)
+            sub_key.iteration_state.filter_state = Some(FilterMatchState::Exact);
+            sub_key
+        })
+    }
+
+    /// Returns the sub key named `name` as parsed from its cell, without its values
+    pub(crate) fn find_sub_key_unread(
+        &self,
+        file_info: &FileInfo,
+        state: &mut State,
+        name: &str,
+        case_sensitive: bool,
+    ) -> Option<Self> {
+        if self.cell_state != CellState::Allocated || self.detail.number_of_sub_keys() == 0 {
+            return None;
+        }
+        let is_match = |key_name: &str| {
+            if case_sensitive {
+                key_name == name
+            } else {
+                key_name.eq_ignore_ascii_case(name)
+            }
+        };
+        let offsets = Self::parse_sub_key_list(
+            file_info,
+            state,
+            self.detail.sub_keys_list_offset_relative(),
+        )
+        .ok()?;
+        for offset in offsets {
+            let offset = offset as usize;
+            if let Some(cached) = state.cell_cache.peek_key(offset) {
+                if is_match(&cached.key_name) {
+                    let mut sub_key = cached.clone();
+                    sub_key.path = format!("{}\\{}", self.path, sub_key.key_name).into();
+                    return Some(sub_key);
+                }
+                continue;
+            }
+            let parsed = file_info
+                .buffer
+                .get(offset..)
+                .map(|input| Self::from_bytes(state, input, offset, &self.path, None));
+            if let Some(Ok((_, sub_key))) = parsed {
+                if state.cell_cache.is_enabled() {
+                    state.cell_cache.insert_key(&sub_key);
+                }
+                if is_match(&sub_key.key_name) {
+                    return Some(sub_key);
+                }
+            }
+        }
+        None
     }
 
     pub fn get_sub_key_by_index(&mut self, parser: &mut Parser, index: usize) -> Option<Self> {
         if self.detail.number_of_sub_keys() > 0 {
             match Self::parse_sub_key_list(
                 &parser.file_info,
-                &mut parser.state,
+                parser.state.for_lookup(),
                 self.detail.sub_keys_list_offset_relative(),
             ) {
                 Ok(cell_sub_key_offsets_absolute) => {
@@ -184,7 +461,10 @@ impl CellKeyNode {
                                 ),
                             ),
                             Ok(sub_key) => {
-                                if let Some(sub_key) = sub_key {
+                                if let Some(mut sub_key) = sub_key {
+                                    if let Some(guid_names) = &parser.guid_names {
+                                        guid_names.annotate(&mut sub_key);
+                                    }
                                     return Some(sub_key);
                                 };
                             }
@@ -228,6 +508,12 @@ impl CellKeyNode {
         val.cloned()
     }
 
+    /// Moves the values out of the key, only copying them if they're shared with a clone of the key
+    pub(crate) fn take_values(&mut self) -> Vec<CellKeyValue> {
+        Arc::try_unwrap(std::mem::take(&mut self.sub_values))
+            .unwrap_or_else(|values| values.to_vec())
+    }
+
     pub fn value_iter(&self) -> CellKeyNodeValueIterator<'_> {
         CellKeyNodeValueIterator {
             inner: self,
@@ -255,14 +541,68 @@ impl CellKeyNode {
             &file_info.buffer[..],
             self.detail.security_key_offset_relative(),
             file_info.hbin_offset_absolute,
+            &mut self.logs,
+        )
+    }
+
+    /// Returns the raw (self-relative) security descriptor for the key
+    pub fn get_security_descriptor_bytes(&self, parser: &Parser) -> Result<Vec<u8>, Error> {
+        let file_info = parser.get_file_info();
+        cell_key_security::read_security_descriptor_bytes(
+            &file_info.buffer[..],
+            self.detail.security_key_offset_relative(),
+            file_info.hbin_offset_absolute,
         )
     }
 
+    /// Returns the key's class name, or None if it doesn't have one
+    pub fn get_class_name(&self, parser: &Parser) -> Option<String> {
+        let class_name_size = self.detail.class_name_size() as usize;
+        if class_name_size == 0 || self.detail.class_name_offset_relative() < 0 {
+            return None;
+        }
+        let file_info = parser.get_file_info();
+        // skip over the cell's size
+        let offset = self.detail.class_name_offset_relative() as usize
+            + file_info.hbin_offset_absolute
+            + std::mem::size_of::<i32>();
+        let slice = file_info.buffer.get(offset..offset + class_name_size)?;
+        let mut logs = Logs::default();
+        Some(util::from_utf16_le_string(
+            slice,
+            class_name_size,
+            &mut logs,
+            "get_class_name",
+        ))
+    }
+
     /// Returns path without root key
     pub fn get_pretty_path(&self) -> &str {
         &self.path[util::get_root_path_offset(&self.path)..]
     }
 
+    /// Returns the key name as stored in the hive, including any embedded nulls or invalid UTF-16
+    pub fn name_raw(&self) -> Cow<'_, [u8]> {
+        match &self.key_name_raw {
+            Some(raw) => Cow::Borrowed(raw),
+            None => Cow::Owned(util::name_to_bytes(
+                &self.key_name,
+                KeyNodeFlags::from_bits_truncate(self.detail.key_node_flag_bits())
+                    .contains(KeyNodeFlags::KEY_COMP_NAME),
+            )),
+        }
+    }
+
+    /// Returns the reason the key's name looks made to hide the key, if it does (see `suspicious_name::detect`)
+    pub fn suspicious_name(&self) -> Option<SuspiciousName> {
+        suspicious_name::detect(&self.key_name)
+    }
+
+    /// Returns the bytes between the end of the key's name and the end of its cell
+    pub fn slack(&self) -> &[u8] {
+        self.detail.slack_ref()
+    }
+
     pub fn key_node_flags(&self, logs: &mut Logs) -> KeyNodeFlags {
         KeyNodeFlags::from_bits_checked(self.detail.key_node_flag_bits(), logs)
     }
@@ -271,11 +611,111 @@ impl CellKeyNode {
         AccessFlags::from_bits_checked(self.detail.access_flag_bits(), logs)
     }
 
+    /// Returns the key's layered key bit fields, or None if the hive's format doesn't support layered keys
+    /// (see `HiveCapabilities::layered_keys`)
+    pub fn layered_key(&self, parser: &Parser) -> Option<LayeredKey> {
+        if parser.hive_capabilities().layered_keys {
+            Some(LayeredKey::from_bits(
+                (self.detail.access_flag_bits() >> 8) as u8,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the key's flag bits, decoded: the key node flags, the access bits, and the layered key bit fields (see
+    /// `virtualization` for the bits kept in the largest sub key name size field). Unlike `key_node_flags` and
+    /// `access_flags`, bits without a name are kept rather than logged and dropped.
+    pub fn flags(&self, parser: &Parser) -> KeyFlags {
+        self.decode_flags(self.layered_key(parser))
+    }
+
+    pub(crate) fn decode_flags(&self, layered_key: Option<LayeredKey>) -> KeyFlags {
+        KeyFlags {
+            key_node: KeyNodeFlags::from_bits_retain(self.detail.key_node_flag_bits()),
+            access: AccessFlags::from_bits_retain(self.detail.access_flag_bits() & 0xFF),
+            layered_key,
+        }
+    }
+
+    /// Returns the key's virtualization flags, user flags, and debug bits. Since Windows Vista, the upper half of the
+    /// largest sub key name size field holds these rather than part of the size.
+    pub fn virtualization(&self) -> Virtualization {
+        let key_node_flags = KeyNodeFlags::from_bits_retain(self.detail.key_node_flag_bits());
+        let largest_sub_key_name_size = self.detail.largest_sub_key_name_size();
+        Virtualization {
+            virtual_source: key_node_flags.contains(KeyNodeFlags::KEY_VIRTUAL_SOURCE),
+            virtual_target: key_node_flags.contains(KeyNodeFlags::KEY_VIRTUAL_TARGET),
+            virtual_store: key_node_flags.contains(KeyNodeFlags::KEY_VIRTUAL_STORE),
+            virtual_store_path: self
+                .path
+                .to_ascii_lowercase()
+                .split('\\')
+                .any(|name| name == "virtualstore"),
+            control: VirtualizationControlFlags::from_bits_retain(
+                (largest_sub_key_name_size >> 16) as u8 & 0xF,
+            ),
+            user: UserFlags::from_bits_retain(
+                ((key_node_flags.bits() >> 12) as u8 | (largest_sub_key_name_size >> 20) as u8)
+                    & 0xF,
+            ),
+            debug: (largest_sub_key_name_size >> 24) as u8,
+            largest_sub_key_name_size: largest_sub_key_name_size as u16,
+        }
+    }
+
+    /// Returns the earlier states of the key recovered from the transaction logs, newest first. These are only found when
+    /// parsing with `recover_deleted` and transaction logs.
+    pub fn versions(&self) -> Vec<Version<'_, Self>> {
+        let mut versions: Vec<_> = self
+            .versions
+            .iter()
+            .map(|key| Version {
+                sequence_num: key.sequence_num,
+                updated_by_sequence_num: key.updated_by_sequence_num,
+                timestamp: Some(key.last_key_written_date_and_time()),
+                item: key,
+            })
+            .collect();
+        versions.sort_by_key(|v| std::cmp::Reverse(v.sequence_num));
+        versions
+    }
+
     /// Returns the byte length of the cell (regardless of if it's allocated or free)
     pub fn last_key_written_date_and_time(&self) -> DateTime<Utc> {
         util::get_date_time_from_filetime(self.detail.last_key_written_date_and_time())
     }
 
+    /// Returns the last written timestamp as the FILETIME stored in the hive, for exact comparisons with other tools' output
+    pub fn last_key_written_filetime(&self) -> u64 {
+        self.detail.last_key_written_date_and_time()
+    }
+
+    /// Returns a digest of the key's name and its values' names, data types, and data (plus the key's last written
+    /// timestamp if `include_timestamps` is true), for checking whether keys in different hives are the same without
+    /// comparing them field by field. Values are hashed in name order and deleted values are skipped, so the digest doesn't
+    /// depend on the order of the value list or on `recover_deleted`. Sub keys aren't included; see `Parser::subtree_hash`.
+    pub fn content_hash(&self, include_timestamps: bool) -> Hash {
+        let mut hasher = blake3::Hasher::new();
+        util::hash_field(&mut hasher, self.key_name.as_bytes());
+        if include_timestamps {
+            hasher.update(&self.last_key_written_filetime().to_le_bytes());
+        }
+        let mut values: Vec<&CellKeyValue> = self
+            .sub_values
+            .iter()
+            .filter(|value| !value.cell_state.is_deleted())
+            .collect();
+        values.sort_by_cached_key(|value| value.detail.value_name());
+        hasher.update(&(values.len() as u64).to_le_bytes());
+        for value in values {
+            util::hash_field(&mut hasher, value.detail.value_name().as_bytes());
+            hasher.update(&value.detail.data_type_raw().to_le_bytes());
+            util::hash_field(&mut hasher, value.get_data());
+        }
+        hasher.finalize()
+    }
+
     pub(crate) fn is_free(&self) -> bool {
         self.detail.size() > 0
     }
@@ -303,14 +743,30 @@ impl CellKeyNode {
         slice: &[u8],
         options: CellKeyNodeReadOptions,
     ) -> Result<Option<Self>, Error> {
-        let (_, mut cell_key_node) = Self::from_bytes(
+        let (_, cell_key_node) = Self::from_bytes(
             state,
             slice,
             options.offset,
             options.cur_path,
             options.sequence_num,
-        )?;
+        )
+        .map_err(|e| Self::read_error_context(e, &options))?;
+        Self::read_remaining(file_info, state, cell_key_node, options)
+    }
+
+    fn read_error_context(error: impl Into<Error>, options: &CellKeyNodeReadOptions) -> Error {
+        error.into().with_context(
+            ErrorContext::new(options.offset, CellType::CellKey).with_key_path(options.cur_path),
+        )
+    }
 
+    /// Applies the filter to a freshly parsed key node and reads its values
+    fn read_remaining(
+        file_info: &FileInfo,
+        state: &mut State,
+        mut cell_key_node: Self,
+        options: CellKeyNodeReadOptions,
+    ) -> Result<Option<Self>, Error> {
         let filter_flags = match options.filter {
             Some(filter) => filter.check_cell(state, &cell_key_node),
             _ => FilterFlags::FILTER_ITERATE_KEYS,
@@ -356,15 +812,64 @@ impl CellKeyNode {
         state: &mut State,
         options: CellKeyNodeReadOptions,
     ) -> Result<Option<Self>, Error> {
-        Self::read_from_slice(
-            file_info,
-            state,
-            file_info
-                .buffer
-                .get(options.offset..)
-                .ok_or_else(|| Error::buffer("cell_key_node::read"))?,
-            options,
-        )
+        if !state.cell_cache.is_enabled() {
+            return Self::read_from_slice(
+                file_info,
+                state,
+                file_info
+                    .buffer
+                    .get(options.offset..)
+                    .ok_or_else(|| Error::buffer("cell_key_node::read"))?,
+                options,
+            );
+        }
+        let cached = state
+            .cell_cache
+            .get_key(options.offset)
+            .map(|cell_key_node| {
+                (
+                    format!("{}\\{}", options.cur_path, cell_key_node.key_name),
+                    options.filter.is_some() && cell_key_node.is_key_root(),
+                )
+            });
+        let cell_key_node = match cached {
+            Some((path, is_key_root)) => {
+                // Rule out keys that don't match the filter before paying for a copy
+                if let Some(filter) = options.filter {
+                    if filter
+                        .check_path(state, is_key_root, &path)
+                        .contains(FilterFlags::FILTER_NO_MATCH)
+                    {
+                        return Ok(None);
+                    }
+                }
+                let mut cell_key_node = state
+                    .cell_cache
+                    .peek_key(options.offset)
+                    .expect("the key was just found in the cache")
+                    .clone();
+                // The cached node may have been reached through a different path (or log sequence)
+                cell_key_node.path = path.into();
+                cell_key_node.sequence_num = options.sequence_num;
+                cell_key_node
+            }
+            None => {
+                let (_, cell_key_node) = Self::from_bytes(
+                    state,
+                    file_info
+                        .buffer
+                        .get(options.offset..)
+                        .ok_or_else(|| Error::buffer("cell_key_node::read"))?,
+                    options.offset,
+                    options.cur_path,
+                    options.sequence_num,
+                )
+                .map_err(|e| Self::read_error_context(e, &options))?;
+                state.cell_cache.insert_key(&cell_key_node);
+                cell_key_node
+            }
+        };
+        Self::read_remaining(file_info, state, cell_key_node, options)
     }
 
     pub(crate) fn read_sub_keys_internal(
@@ -374,6 +879,7 @@ impl CellKeyNode {
         filter: &Filter,
         sequence_num: Option<u32>,
         get_deleted_and_modified: bool,
+        max_sub_keys: Option<usize>,
     ) -> (Vec<Self>, bool) {
         if self.cell_state == CellState::Allocated {
             // Sanity check to prevent OOM with recovered data
@@ -388,15 +894,23 @@ impl CellKeyNode {
                 return (vec![], false);
             }
 
-            let mut children = Vec::with_capacity(self.detail.number_of_sub_keys() as usize);
+            if state.overlap_check != OverlapCheck::Off {
+                // Only does something for the root key; the others were recorded when read as sub keys
+                state
+                    .cell_extents
+                    .insert(self.file_offset_absolute, self.get_cell_size());
+            }
+            let mut children = Vec::new();
             let mut found_key = false;
             if self.detail.number_of_sub_keys() > 0 {
-                match Self::parse_sub_key_list(
+                match Self::parse_sub_key_list_items(
                     file_info,
                     state,
                     self.detail.sub_keys_list_offset_relative(),
+                    0,
+                    &mut HashSet::new(),
                 ) {
-                    Ok(cell_sub_key_offsets_absolute) => {
+                    Ok(sub_key_list_items) => {
                         let self_is_filter_match_or_descendent =
                             self.is_filter_match_or_descendent();
                         let sub_filter =
@@ -405,7 +919,47 @@ impl CellKeyNode {
                             } else {
                                 Some(filter)
                             };
-                        for val in cell_sub_key_offsets_absolute.iter() {
+                        // Sized by the list actually read rather than by the key's (untrusted) sub key count
+                        children.reserve(
+                            sub_key_list_items
+                                .len()
+                                .min(max_sub_keys.unwrap_or(usize::MAX)),
+                        );
+                        for (index, (val, name_hash)) in sub_key_list_items.iter().enumerate() {
+                            if max_sub_keys == Some(children.len()) {
+                                self.logs.add(
+                                    LogCode::WarningLimit,
+                                    &format!(
+                                        "Key limit reached; {} of {} sub keys not read",
+                                        sub_key_list_items.len() - index,
+                                        sub_key_list_items.len()
+                                    ),
+                                );
+                                break;
+                            }
+                            // Checked before the key is read so its cell is claimed before its values' cells
+                            let overlapping_offset =
+                                match util::read_u32_le(&file_info.buffer, *val as usize) {
+                                    Some(size) if state.overlap_check != OverlapCheck::Off => {
+                                        state.cell_extents.insert(
+                                            *val as usize,
+                                            (size as i32).unsigned_abs() as usize,
+                                        )
+                                    }
+                                    _ => None,
+                                };
+                            let overlap = overlapping_offset.map(|other_offset| {
+                                format!(
+                                    "{}: Cell of sub key at offset {} overlaps the cell at offset {}",
+                                    self.path, val, other_offset
+                                )
+                            });
+                            if let Some(message) = &overlap {
+                                if state.overlap_check == OverlapCheck::Skip {
+                                    self.logs.add(LogCode::WarningOverlap, message);
+                                    continue;
+                                }
+                            }
                             let ret = Self::read(
                                 file_info,
                                 state,
@@ -428,6 +982,39 @@ impl CellKeyNode {
                                 ),
                                 Ok(kn) => {
                                     if let Some(mut kn) = kn {
+                                        if let Some(name_hash) = name_hash {
+                                            let actual_hash =
+                                                sub_key_list_lh::name_hash(&kn.key_name);
+                                            if *name_hash != actual_hash {
+                                                kn.logs.add(
+                                                    LogCode::WarningNameHash,
+                                                    &format!(
+                                                        "Sub key list name hash {:#x} doesn't match the hash of the key name ({:#x})",
+                                                        name_hash, actual_hash
+                                                    ),
+                                                );
+                                            }
+                                        }
+                                        if state.parent_offset_check != ParentOffsetCheck::Off
+                                            && !self.is_parent_of(&kn, file_info)
+                                        {
+                                            let message = format!(
+                                                "Parent offset of sub key {} is {}; expected {}",
+                                                kn.path,
+                                                kn.detail.parent_key_offset_relative(),
+                                                self.file_offset_absolute
+                                                    - file_info.hbin_offset_absolute
+                                            );
+                                            if state.parent_offset_check == ParentOffsetCheck::Skip
+                                            {
+                                                self.logs.add(LogCode::WarningParent, &message);
+                                                continue;
+                                            }
+                                            kn.logs.add(LogCode::WarningParent, &message);
+                                        }
+                                        if let Some(message) = &overlap {
+                                            kn.logs.add(LogCode::WarningOverlap, message);
+                                        }
                                         if kn.iteration_state.filter_state.is_none() {
                                             if self_is_filter_match_or_descendent {
                                                 kn.iteration_state.filter_state =
@@ -446,7 +1033,10 @@ impl CellKeyNode {
                                 }
                             }
                         }
-                        self.cell_sub_key_offsets_absolute = cell_sub_key_offsets_absolute;
+                        self.cell_sub_key_offsets_absolute = sub_key_list_items
+                            .into_iter()
+                            .map(|(offset, _)| offset)
+                            .collect();
                     }
                     Err(_) => self.logs.add(
                         LogCode::WarningParse,
@@ -465,6 +1055,12 @@ impl CellKeyNode {
         }
     }
 
+    /// Returns true if the parent offset of `sub_key` points to this key
+    fn is_parent_of(&self, sub_key: &Self, file_info: &FileInfo) -> bool {
+        sub_key.detail.parent_key_offset_relative() as usize + file_info.hbin_offset_absolute
+            == self.file_offset_absolute
+    }
+
     pub(crate) fn parse_key_values(
         file_info: &FileInfo,
         key_values_count: u32,
@@ -488,6 +1084,36 @@ impl CellKeyNode {
         state: &mut State,
         list_offset_relative: u32,
     ) -> Result<Vec<u32>, Error> {
+        Ok(Self::parse_sub_key_list_items(
+            file_info,
+            state,
+            list_offset_relative,
+            0,
+            &mut HashSet::new(),
+        )?
+        .into_iter()
+        .map(|(offset, _)| offset)
+        .collect())
+    }
+
+    /// Returns the absolute offset of each sub key, along with the name hash stored for it in lh lists.
+    /// ri lists (lists of lists) are followed up to `MAX_SUB_KEY_LIST_DEPTH` levels deep. `visited` holds the offsets of
+    /// the lists read so far, so a list referenced more than once (a circular reference) is an error.
+    pub(crate) fn parse_sub_key_list_items(
+        file_info: &FileInfo,
+        state: &mut State,
+        list_offset_relative: u32,
+        depth: usize,
+        visited: &mut HashSet<u32>,
+    ) -> Result<Vec<(u32, Option<u32>)>, Error> {
+        if !visited.insert(list_offset_relative) {
+            return Err(Error::Any {
+                detail: format!(
+                    "parse_sub_key_list: sub key list at offset {} is referenced more than once (potential circular reference)",
+                    list_offset_relative as usize + file_info.hbin_offset_absolute
+                ),
+            });
+        }
         let file_offset_absolute = list_offset_relative as usize + file_info.hbin_offset_absolute;
         let slice = file_info
             .buffer
@@ -496,36 +1122,51 @@ impl CellKeyNode {
         // We either have an lf/lh/li list here (offsets to subkey lists), or an ri list (offsets to offsets...)
         // Look for the ri list first and follow the pointers
         match SubKeyListRi::from_bytes(slice) {
-            Ok((_, sub_key_list_ri)) => sub_key_list_ri.parse_offsets(file_info, state),
+            Ok((_, sub_key_list_ri)) => {
+                if depth < Self::MAX_SUB_KEY_LIST_DEPTH {
+                    sub_key_list_ri.parse_items(file_info, state, depth + 1, visited)
+                } else {
+                    Err(Error::Any {
+                        detail: format!(
+                            "parse_sub_key_list: ri lists nested more than {} levels deep at offset {}",
+                            Self::MAX_SUB_KEY_LIST_DEPTH,
+                            file_offset_absolute
+                        ),
+                    })
+                }
+            }
             Err(_) => {
                 let (_, cell_sub_key_list) = alt((
                     SubKeyListLf::from_bytes(),
                     SubKeyListLh::from_bytes(),
                     SubKeyListLi::from_bytes(),
-                ))(slice)?;
-                Ok(cell_sub_key_list.get_offset_list(file_info.hbin_offset_absolute as u32))
+                ))(slice)
+                .map_err(|e| {
+                    Error::from(e).with_context(ErrorContext::new(
+                        file_offset_absolute,
+                        CellType::read_cell_type(slice.get(4..).unwrap_or_default()),
+                    ))
+                })?;
+                Ok(cell_sub_key_list
+                    .get_offset_and_name_hash_list(file_info.hbin_offset_absolute as u32))
             }
         }
     }
 
-    pub(crate) fn lowercase(&self) -> String {
-        self.path.to_ascii_lowercase()
-    }
-
     pub(crate) fn is_key_root(&self) -> bool {
         let mut logs = Logs::default();
         self.key_node_flags(&mut logs)
             .contains(KeyNodeFlags::KEY_HIVE_ENTRY)
     }
 
-    fn from_bytes<'a>(
+    pub(crate) fn from_bytes<'a>(
         state: &mut State,
         input: &'a [u8],
         file_offset_absolute: usize,
         cur_path: &str,
         sequence_num: Option<u32>,
     ) -> IResult<&'a [u8], Self> {
-        let get_full_field_info = state.get_full_field_info;
+        let get_full_field_info = state.get_full_field_info && !state.values_only;
         let start_pos_ptr = input.as_ptr() as usize;
 
         init_value_enum! { CellKeyNodeDetail, detail_enum, get_full_field_info };
@@ -571,13 +1212,17 @@ impl CellKeyNode {
             let key_node_flags = KeyNodeFlags::from_bits_checked(key_node_flag_bits, &mut logs);
             //let access_flags = AccessFlags::from_bits_checked(access_flag_bits, &mut logs);
 
-            let key_name = util::string_from_bytes(
+            let (key_name, key_name_raw) = util::name_from_bytes(
                 key_node_flags.contains(KeyNodeFlags::KEY_COMP_NAME),
                 key_name_bytes,
-                key_name_size,
                 &mut logs,
                 "key_name_bytes",
             );
+            if state.detect_suspicious_names && !state.values_only {
+                if let Some(suspicious_name) = suspicious_name::detect(&key_name) {
+                    logs.add(LogCode::WarningSuspiciousName, &suspicious_name);
+                }
+            }
 
             let mut path = cur_path.to_owned();
             path.push('\\');
@@ -586,11 +1231,23 @@ impl CellKeyNode {
             let slack_offset = input.as_ptr() as usize - start_pos_ptr;
             let size_abs = size.unsigned_abs();
             let (input, slack_bytes) = util::parser_eat_remaining(input, size_abs, slack_offset)?;
-            detail_enum.set_slack_full(
-                &slack_bytes.to_vec(),
-                slack_offset,
-                slack_bytes.len() as u32,
-            );
+            if !state.values_only {
+                detail_enum.set_slack_full(
+                    &slack_bytes.to_vec(),
+                    slack_offset,
+                    slack_bytes.len() as u32,
+                );
+            }
+            let hash = if state.values_only {
+                None
+            } else {
+                Some(Self::hash(
+                    state,
+                    key_node_flag_bits,
+                    last_key_written_date_and_time,
+                    access_flag_bits,
+                ))
+            };
 
             let cell_key_node = Self {
                 detail: detail_enum,
@@ -598,20 +1255,18 @@ impl CellKeyNode {
                 //key_node_flags,
                 //access_flags,
                 key_name,
-                path,
+                path: path.into(),
                 cell_state: CellState::Allocated,
-                sub_values: Vec::new(),
+                sub_values: Arc::default(),
                 logs,
                 cell_sub_key_offsets_absolute: Vec::new(),
+                key_name_raw,
+                path_confidence: None,
+                guid_name: None,
                 iteration_state: CellKeyNodeIteration::default(),
                 versions: Vec::new(),
                 deleted_keys: Vec::new(),
-                hash: Some(Self::hash(
-                    state,
-                    key_node_flag_bits,
-                    last_key_written_date_and_time,
-                    access_flag_bits,
-                )),
+                hash,
                 sequence_num,
                 updated_by_sequence_num: None,
             };
@@ -620,29 +1275,6 @@ impl CellKeyNode {
         }
     }
 
-    fn get_sub_key_internal(
-        &mut self,
-        file_info: &FileInfo,
-        state: &mut State,
-        filter: &Filter,
-        sequence_num: Option<u32>,
-    ) -> Option<Self> {
-        let (children, found_key) =
-            self.read_sub_keys_internal(file_info, state, filter, sequence_num, false);
-        if found_key {
-            match children.get(0) {
-                Some(child) => return Some(child.clone()),
-                None => return None,
-            }
-        }
-        for mut child in children {
-            if let Some(key) = child.get_sub_key_internal(file_info, state, filter, sequence_num) {
-                return Some(key);
-            }
-        }
-        None
-    }
-
     fn read_values(
         &mut self,
         file_info: &FileInfo,
@@ -664,7 +1296,11 @@ impl CellKeyNode {
                 return Ok(());
             }
 
-            self.sub_values = Vec::with_capacity(self.detail.number_of_key_values() as usize);
+            self.sub_values = Arc::new(Vec::with_capacity(
+                self.detail.number_of_key_values() as usize
+            ));
+            let sub_values = Arc::make_mut(&mut self.sub_values);
+            let path = &self.path;
             let (_, key_values) = Self::parse_key_values(
                 file_info,
                 self.detail.number_of_key_values(),
@@ -685,18 +1321,53 @@ impl CellKeyNode {
                     return Ok(());
                 }
                 let offset = *val as usize + file_info.hbin_offset_absolute;
-                let (_, mut cell_key_value) = CellKeyValue::from_bytes(
-                    file_info
-                        .buffer
-                        .get(offset..)
-                        .ok_or_else(|| Error::buffer("read_values"))?,
-                    offset,
-                    sequence_num,
-                    state.get_full_field_info,
-                )?;
-
-                cell_key_value.read_value_bytes(file_info, state);
-                self.sub_values.push(cell_key_value);
+                let mut cell_key_value = match state.cell_cache.get_value(offset) {
+                    Some(mut cell_key_value) => {
+                        cell_key_value.sequence_num = sequence_num;
+                        cell_key_value
+                    }
+                    None => {
+                        let (_, mut cell_key_value) = CellKeyValue::from_bytes(
+                            file_info
+                                .buffer
+                                .get(offset..)
+                                .ok_or_else(|| Error::buffer("read_values"))?,
+                            offset,
+                            sequence_num,
+                            state.get_full_field_info,
+                        )
+                        .map_err(|e| {
+                            Error::from(e).with_context(
+                                ErrorContext::new(offset, CellType::CellValue).with_key_path(path),
+                            )
+                        })?;
+
+                        cell_key_value.read_value_bytes(file_info, state);
+                        if state.cell_cache.is_enabled() {
+                            state.cell_cache.insert_value(&cell_key_value);
+                        }
+                        cell_key_value
+                    }
+                };
+                if state.overlap_check != OverlapCheck::Off {
+                    if let Some(other_offset) = state
+                        .cell_extents
+                        .insert(offset, cell_key_value.get_cell_size())
+                    {
+                        let message = format!(
+                            "Cell of value {} at offset {} overlaps the cell at offset {}",
+                            cell_key_value.get_pretty_name(),
+                            offset,
+                            other_offset
+                        );
+                        if state.overlap_check == OverlapCheck::Skip {
+                            self.logs.add(LogCode::WarningOverlap, &message);
+                            continue;
+                        }
+                        cell_key_value.logs.add(LogCode::WarningOverlap, &message);
+                    }
+                }
+                sub_values.push(cell_key_value);
             }
         }
         Ok(())
@@ -716,7 +1387,7 @@ impl CellKeyNode {
     }
 
     fn update_modified_lists(&mut self, state: &State) {
-        let path = if self.is_key_root() { "" } else { &self.path };
+        let path = if self.is_key_root() { "" } else { &*self.path };
         if !self.cell_state.is_deleted_primary_file() {
             if let Some(deleted_keys) = state.deleted_keys.get(path) {
                 self.deleted_keys = deleted_keys.to_vec();
@@ -733,7 +1404,7 @@ impl CellKeyNode {
                     .sort_by(|a, b| b.sequence_num.cmp(&a.sequence_num));
             }
 
-            for val in &mut self.sub_values {
+            for val in Arc::make_mut(&mut self.sub_values).iter_mut() {
                 if let Some(modified_values) =
                     state.modified_values.get(path, &val.detail.value_name())
                 {
@@ -746,7 +1417,7 @@ impl CellKeyNode {
             if let Some(deleted_values) = state.deleted_values.get(path) {
                 let mut deleted_values = deleted_values.to_vec();
                 deleted_values.sort_by(|a, b| a.detail.value_name().cmp(&b.detail.value_name()));
-                self.sub_values.extend(deleted_values.to_vec());
+                Arc::make_mut(&mut self.sub_values).extend(deleted_values.to_vec());
             }
         }
     }
@@ -812,7 +1483,7 @@ impl Iterator for CellKeyNodeValueIterator<'_> {
 
 bitflags! {
     #[allow(non_camel_case_types)]
-    #[derive(Debug, Default, PartialEq)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
     pub struct AccessFlags: u32 {
         /// This key was accessed before a Windows registry was initialized with the NtInitializeRegistry() routine during the boot
         const ACCESSED_BEFORE_INIT = 0x00000001;
@@ -825,7 +1496,7 @@ impl_flags_from_bits! { AccessFlags, u32 }
 
 bitflags! {
     #[allow(non_camel_case_types)]
-    #[derive(Debug, Default, PartialEq)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
     pub struct KeyNodeFlags: u16 {
         /// Is volatile (not used, a key node on a disk isn't expected to have this flag set)
         const KEY_VOLATILE       = 0x0001;
@@ -847,13 +1518,110 @@ bitflags! {
         const KEY_VIRTUAL_TARGET = 0x0100;
         /// Is a part of a virtual store path
         const KEY_VIRTUAL_STORE  = 0x0200;
-        const KEY_UNKNOWN1       = 0x1000;
-        const KEY_UNKNOWN2       = 0x4000;
+        /// User flag `UserFlags::KEY_32BIT`, where hives written before Windows Vista keep the user flags
+        const KEY_USER_32BIT     = 0x1000;
+        /// User flag `UserFlags::KEY_REFLECTED`, where hives written before Windows Vista keep the user flags
+        const KEY_USER_REFLECTED = 0x2000;
+        /// User flag `UserFlags::KEY_NO_REFLECTION`, where hives written before Windows Vista keep the user flags
+        const KEY_USER_NO_REFLECTION = 0x4000;
+        /// User flag `UserFlags::KEY_USER_FLAG_8`, where hives written before Windows Vista keep the user flags
+        const KEY_USER_FLAG_8    = 0x8000;
     }
 }
-impl_serialize_for_bitflags! { KeyNodeFlags }
 impl_flags_from_bits! { KeyNodeFlags, u16 }
 
+impl KeyNodeFlags {
+    #[deprecated(note = "renamed to `KEY_USER_32BIT`")]
+    pub const KEY_UNKNOWN1: Self = Self::KEY_USER_32BIT;
+    #[deprecated(note = "renamed to `KEY_USER_NO_REFLECTION`")]
+    pub const KEY_UNKNOWN2: Self = Self::KEY_USER_NO_REFLECTION;
+
+    /// The user flags that had no name in the v1 JSON schema
+    const V1_UNNAMED: Self = Self::KEY_USER_REFLECTED.union(Self::KEY_USER_FLAG_8);
+
+    /// Like `from_bits_checked`, but the user flags that had no name in the v1 JSON schema are dropped and logged too
+    pub(crate) fn from_bits_checked_v1(flags: u16, logs: &mut Logs) -> Self {
+        let flags_mapped = Self::from_bits_truncate(flags).difference(Self::V1_UNNAMED);
+        if flags != flags_mapped.bits() {
+            logs.add(
+                LogCode::WarningUnrecognizedBitflag,
+                &format!(
+                    "{}::from_bits_checked: {:#X}",
+                    std::any::type_name::<Self>(),
+                    flags
+                ),
+            );
+        }
+        flags_mapped
+    }
+
+    /// Formats the flags as the v1 JSON schema does: like `Debug`, with the names `KEY_UNKNOWN1` and `KEY_UNKNOWN2` for
+    /// `KEY_USER_32BIT` and `KEY_USER_NO_REFLECTION`, and the other user flags as bits without a name
+    pub(crate) fn to_v1_string(self) -> String {
+        let named = self.difference(Self::V1_UNNAMED);
+        let mut parts: Vec<String> = named
+            .iter_names()
+            .map(|(name, _)| {
+                match name {
+                    "KEY_USER_32BIT" => "KEY_UNKNOWN1",
+                    "KEY_USER_NO_REFLECTION" => "KEY_UNKNOWN2",
+                    name => name,
+                }
+                .to_string()
+            })
+            .collect();
+        let unnamed = self.bits() & !named.intersection(Self::all()).bits();
+        if unnamed != 0 || parts.is_empty() {
+            parts.push(format!("{:#x}", unnamed));
+        }
+        format!("KeyNodeFlags({})", parts.join(" | "))
+    }
+}
+
+impl serde::ser::Serialize for KeyNodeFlags {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        serializer.serialize_str(&self.to_v1_string())
+    }
+}
+
+bitflags! {
+    /// The user flags of a key node, also known as Wow64 flags. Since Windows Vista these are bits 20-23 of the largest
+    /// sub key name size field; older hives keep them in the upper bits of the key node flags.
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+    pub struct UserFlags: u8 {
+        /// This key was created through the Wow64 subsystem, or shouldn't be used by a 64-bit program
+        const KEY_32BIT         = 0x1;
+        /// This key was created by the registry reflection process
+        const KEY_REFLECTED     = 0x2;
+        /// Registry reflection is disabled for this key
+        const KEY_NO_REFLECTION = 0x4;
+        /// In the old location: break into the debugger when this key is accessed (checked builds only). In the new
+        /// location: this key has a symlink through which the reflected keys are created.
+        const KEY_USER_FLAG_8   = 0x8;
+    }
+}
+impl_serialize_for_bitflags! { UserFlags }
+
+bitflags! {
+    /// The registry virtualization control flags of a key node (bits 16-19 of the largest sub key name size field,
+    /// Windows Vista+)
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+    pub struct VirtualizationControlFlags: u8 {
+        /// This key isn't virtualized
+        const REG_KEY_DONT_VIRTUALIZE  = 0x2;
+        /// Access denied errors on this key aren't hidden by virtualization
+        const REG_KEY_DONT_SILENT_FAIL = 0x4;
+        /// The virtualization control flags of this key are propagated to new sub keys
+        const REG_KEY_RECURSE_FLAG     = 0x8;
+    }
+}
+impl_serialize_for_bitflags! { VirtualizationControlFlags }
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -864,7 +1632,9 @@ mod tests {
     use crate::filter::FilterBuilder;
     use crate::parser::{ParserIterator, ParserIteratorContext};
     use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
     use nom::error::ErrorKind;
+    use std::convert::TryInto;
 
     #[test]
     fn test_get_sub_key_by_path() -> Result<(), Error> {
@@ -879,7 +1649,7 @@ mod tests {
             .unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\Control Panel\Accessibility",
-            sub_key.path
+            &*sub_key.path
         );
 
         let sub_key = key
@@ -887,7 +1657,7 @@ mod tests {
             .unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\Control Panel\Accessibility\AudioDescription",
-            sub_key.path
+            &*sub_key.path
         );
 
         let invalid_sub_key = key.get_sub_key_by_path(&mut parser, "Accessibility\\Nope");
@@ -898,8 +1668,358 @@ mod tests {
         let sub_key = key.get_sub_key_by_path(&mut parser, "").unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}",
-            sub_key.path
+            &*sub_key.path
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_sub_key() -> Result<(), Error> {
+        let mut software = WriterKey::new("Software");
+        software
+            .get_or_add_sub_key("Vendor")
+            .add_sub_key(WriterKey::new("a\\b"));
+        software.add_sub_key(WriterKey::new("MixedCase"));
+        let mut root = WriterKey::new("ROOT");
+        root.add_sub_key(software);
+        let hive = HiveWriter::new(root).to_bytes()?;
+        let mut parser = ParserBuilder::from_file(std::io::Cursor::new(hive)).build()?;
+
+        let escaped = format!("Software\\Vendor\\{}", util::escape_key_name("a\\b"));
+        let key = parser.get_key(&escaped, false)?.unwrap();
+        assert_eq!("a\\b", key.key_name);
+        assert_eq!(None, parser.get_key("Software\\Vendor\\a\\b", false)?);
+
+        // relative lookups, and the name passed to Parser::get_sub_key is taken as is
+        let software = parser.get_key("software", false)?.unwrap();
+        let mut vendor = software.get_sub_key("VENDOR", &mut parser, false).unwrap();
+        assert_eq!(
+            key,
+            software
+                .get_sub_key(
+                    &format!("vendor\\{}", util::escape_key_name("A\\B")),
+                    &mut parser,
+                    false
+                )
+                .unwrap()
+        );
+        assert_eq!(key, parser.get_sub_key(&mut vendor, "a\\b")?.unwrap());
+
+        assert!(parser
+            .get_key_with_options("Software\\MixedCase", false, true)?
+            .is_some());
+        assert_eq!(
+            None,
+            parser.get_key_with_options("Software\\mixedcase", false, true)?
+        );
+        assert!(software
+            .get_sub_key("mixedcase", &mut parser, false)
+            .is_some());
+        assert_eq!(None, software.get_sub_key("mixedcase", &mut parser, true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_with_embedded_null() -> Result<(), Error> {
+        let mut hidden = WriterKey::new("Run\0hidden");
+        hidden.set_value(WriterValue::new(
+            "\0值",
+            CellKeyValueDataTypes::REG_DWORD,
+            vec![1, 0, 0, 0],
+        ));
+        let mut root = WriterKey::new("ROOT");
+        root.add_sub_key(hidden);
+        let hive = HiveWriter::new(root).to_bytes()?;
+        let mut parser = ParserBuilder::from_file(std::io::Cursor::new(hive)).build()?;
+
+        let key = parser.get_key("Run\0hidden", false)?.unwrap();
+        assert_eq!("Run\0hidden", key.key_name);
+        assert_eq!(&b"Run\0hidden"[..], &key.name_raw()[..]);
+        assert_eq!(None, key.key_name_raw);
+        assert_eq!(
+            Some(LogCode::WarningName),
+            key.logs
+                .get()
+                .and_then(|logs| logs.first())
+                .map(|log| log.code)
+        );
+        assert_eq!("Run\\x00hidden", util::escape_string(&key.key_name));
+        assert!(serde_json::to_string(&key)?.contains("Run\\u0000hidden"));
+
+        let value = key.get_value("\0值").unwrap();
+        assert_eq!(vec![0, 0, 0x3C, 0x50], value.name_raw().into_owned());
+        assert_eq!(
+            Some(LogCode::WarningName),
+            value
+                .logs
+                .get()
+                .and_then(|logs| logs.first())
+                .map(|log| log.code)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sub_key_list_name_hash() -> Result<(), Error> {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT").build()?;
+        assert!(!ParserIterator::new(&parser)
+            .iter()
+            .any(|key| key.logs.has_code(LogCode::WarningNameHash)));
+
+        // More sub keys than fit in one lh list, so the root's list is an ri list of lh lists
+        let mut root = WriterKey::new("ROOT");
+        for i in 0..1100 {
+            root.add_sub_key(WriterKey::new(&format!("key{:04}", i)));
+        }
+        let mut hive = HiveWriter::new(root).to_bytes()?;
+        parser = ParserBuilder::from_file(std::io::Cursor::new(hive.clone())).build()?;
+        let root_key = parser.get_root_key()?.unwrap();
+        let sub_keys = root_key.clone().read_sub_keys(&mut parser);
+        assert_eq!(1100, sub_keys.len());
+        assert!(!sub_keys
+            .iter()
+            .any(|key| key.logs.has_code(LogCode::WarningNameHash)));
+
+        // Overwrite the name hash of the first item in the first lh list
+        let ri_offset = root_key.detail.sub_keys_list_offset_relative() as usize + 4096;
+        assert_eq!(b"ri", &hive[ri_offset + 4..ri_offset + 6]);
+        let lh_offset =
+            u32::from_le_bytes(hive[ri_offset + 8..ri_offset + 12].try_into()?) as usize + 4096;
+        assert_eq!(b"lh", &hive[lh_offset + 4..lh_offset + 6]);
+        hive[lh_offset + 12..lh_offset + 16].copy_from_slice(&0x1234u32.to_le_bytes());
+
+        parser = ParserBuilder::from_file(std::io::Cursor::new(hive)).build()?;
+        let sub_keys = parser.get_root_key()?.unwrap().read_sub_keys(&mut parser);
+        assert_eq!(1100, sub_keys.len());
+        let tampered: Vec<&str> = sub_keys
+            .iter()
+            .filter(|key| key.logs.has_code(LogCode::WarningNameHash))
+            .map(|key| key.key_name.as_str())
+            .collect();
+        assert_eq!(vec!["key0000"], tampered);
+        Ok(())
+    }
+
+    #[test]
+    fn test_circular_sub_key_lists() -> Result<(), Error> {
+        let patch = |hive: &mut Vec<u8>, offset: usize, value: u32| {
+            hive[offset..offset + 4].copy_from_slice(&value.to_le_bytes())
+        };
+
+        // Give ROOT\A\B the sub key list of ROOT, so A is its own descendent
+        let mut a = WriterKey::new("A");
+        a.add_sub_key(WriterKey::new("B"));
+        let mut root = WriterKey::new("ROOT");
+        root.add_sub_key(a);
+        let mut hive = HiveWriter::new(root).to_bytes()?;
+        let mut parser = ParserBuilder::from_file(std::io::Cursor::new(hive.clone())).build()?;
+        let root_key = parser.get_root_key()?.unwrap();
+        let b = parser.get_key("A\\B", false)?.unwrap();
+        let nk = b.file_offset_absolute + 4;
+        patch(&mut hive, nk + 20, 1);
+        patch(
+            &mut hive,
+            nk + 28,
+            root_key.detail.sub_keys_list_offset_relative(),
         );
+
+        parser = ParserBuilder::from_file(std::io::Cursor::new(hive)).build()?;
+        let keys: Vec<CellKeyNode> = ParserIterator::new(&parser).iter().collect();
+        assert_eq!(3, keys.len());
+        assert!(keys[2].logs.has_code(LogCode::WarningCycle));
+        let mut root_key = parser.get_root_key()?.unwrap();
+        assert!(WriterKey::from_parser_key(&mut parser, &mut root_key).is_err());
+
+        // An ri list which lists itself
+        let mut root = WriterKey::new("ROOT");
+        for i in 0..1100 {
+            root.add_sub_key(WriterKey::new(&format!("key{:04}", i)));
+        }
+        let mut hive = HiveWriter::new(root).to_bytes()?;
+        parser = ParserBuilder::from_file(std::io::Cursor::new(hive.clone())).build()?;
+        let ri_offset_relative = parser
+            .get_root_key()?
+            .unwrap()
+            .detail
+            .sub_keys_list_offset_relative();
+        let ri = ri_offset_relative as usize + 4096;
+        assert_eq!(b"ri", &hive[ri + 4..ri + 6]);
+        patch(&mut hive, ri + 12, ri_offset_relative);
+
+        parser = ParserBuilder::from_file(std::io::Cursor::new(hive)).build()?;
+        let mut root_key = parser.get_root_key()?.unwrap();
+        assert!(root_key.read_sub_keys(&mut parser).is_empty());
+        assert!(root_key.logs.has_code(LogCode::WarningParse));
+        Ok(())
+    }
+
+    #[test]
+    fn test_layered_key() -> Result<(), Error> {
+        let mut root = WriterKey::new("ROOT");
+        root.add_sub_key(WriterKey::new("layered"));
+        let mut hive = HiveWriter::new(root).to_bytes()?;
+        let mut parser = ParserBuilder::from_file(std::io::Cursor::new(hive.clone())).build()?;
+        let key = parser.get_key("layered", false)?.unwrap();
+        assert_eq!(None, key.layered_key(&parser));
+
+        // Mark the hive as version 1.6 and set the key's layered key bit fields after its access bits
+        hive[0x18..0x1C].copy_from_slice(&6u32.to_le_bytes());
+        crate::base_block::BaseBlockBase::update_checksum(&mut hive)?;
+        hive[key.file_offset_absolute + 17] = 0x81;
+
+        parser = ParserBuilder::from_file(std::io::Cursor::new(hive)).build()?;
+        assert!(parser.hive_capabilities().layered_keys);
+        let key = parser.get_key("layered", false)?.unwrap();
+        assert_eq!(
+            Some(LayeredKey {
+                inherit_class: true,
+                layer_semantics: LayerSemantics::IsSupersedeLocal
+            }),
+            key.layered_key(&parser)
+        );
+        let root_key = parser.get_root_key()?.unwrap();
+        assert_eq!(
+            Some(LayeredKey {
+                inherit_class: false,
+                layer_semantics: LayerSemantics::None
+            }),
+            root_key.layered_key(&parser)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_flags() -> Result<(), Error> {
+        let mut root = WriterKey::new("ROOT");
+        root.add_sub_key(WriterKey::new("flags"));
+        let mut hive = HiveWriter::new(root).to_bytes()?;
+        let mut parser = ParserBuilder::from_file(std::io::Cursor::new(hive.clone())).build()?;
+        let offset = parser
+            .get_key("flags", false)?
+            .unwrap()
+            .file_offset_absolute;
+
+        // A user flag in the old location and a bit without a name
+        hive[offset + 6..offset + 8].copy_from_slice(&0x1420u16.to_le_bytes());
+        parser = ParserBuilder::from_file(std::io::Cursor::new(hive)).build()?;
+        let key = parser.get_key("flags", false)?.unwrap();
+        let flags = key.flags(&parser);
+        assert_eq!(
+            KeyNodeFlags::KEY_COMP_NAME
+                | KeyNodeFlags::KEY_USER_32BIT
+                | KeyNodeFlags::from_bits_retain(0x0400),
+            flags.key_node
+        );
+        assert_eq!(key.access_flags(&mut Logs::default()), flags.access);
+        assert_eq!(None, flags.layered_key);
+
+        let json = serde_json::to_value(flags).unwrap();
+        assert_eq!(
+            serde_json::json!(["KEY_COMP_NAME", "KEY_USER_32BIT", "0x400"]),
+            json["key_node"]
+        );
+        assert!(json.get("layered_key").is_none());
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_flags_v1() {
+        assert_eq!(KeyNodeFlags::KEY_USER_32BIT, KeyNodeFlags::KEY_UNKNOWN1);
+        assert_eq!(
+            KeyNodeFlags::KEY_USER_NO_REFLECTION,
+            KeyNodeFlags::KEY_UNKNOWN2
+        );
+
+        // The v1 schema keeps the names from before the user flags had them
+        let flags = KeyNodeFlags::KEY_COMP_NAME
+            | KeyNodeFlags::KEY_USER_32BIT
+            | KeyNodeFlags::KEY_USER_NO_REFLECTION;
+        assert_eq!(
+            serde_json::json!("KeyNodeFlags(KEY_COMP_NAME | KEY_UNKNOWN1 | KEY_UNKNOWN2)"),
+            serde_json::to_value(flags).unwrap()
+        );
+        assert_eq!(
+            "KeyNodeFlags(KEY_COMP_NAME | 0x2400)",
+            (KeyNodeFlags::KEY_COMP_NAME
+                | KeyNodeFlags::KEY_USER_REFLECTED
+                | KeyNodeFlags::from_bits_retain(0x0400))
+            .to_v1_string()
+        );
+        assert_eq!("KeyNodeFlags(0x0)", KeyNodeFlags::empty().to_v1_string());
+
+        let mut logs = Logs::default();
+        let flags = KeyNodeFlags::from_bits_checked_v1(0x3020, &mut logs);
+        assert_eq!(
+            KeyNodeFlags::KEY_COMP_NAME | KeyNodeFlags::KEY_USER_32BIT,
+            flags
+        );
+        assert!(logs.has_code(LogCode::WarningUnrecognizedBitflag));
+        assert!(logs
+            .get_string()
+            .contains("KeyNodeFlags::from_bits_checked: 0x3020"));
+    }
+
+    #[test]
+    fn test_virtualization() -> Result<(), Error> {
+        let mut virtual_store = WriterKey::new("VirtualStore");
+        virtual_store.add_sub_key(WriterKey::new("MACHINE"));
+        let mut root = WriterKey::new("ROOT");
+        root.add_sub_key(virtual_store);
+        root.add_sub_key(WriterKey::new("plain"));
+        let mut hive = HiveWriter::new(root).to_bytes()?;
+        let mut parser = ParserBuilder::from_file(std::io::Cursor::new(hive.clone())).build()?;
+        let plain = parser.get_key("plain", false)?.unwrap();
+        assert!(!plain.virtualization().is_set());
+        let offset = plain.file_offset_absolute;
+
+        // KEY_VIRTUAL_SOURCE and a user flag in the old location, then user, virtualization control, and debug bits above
+        // a largest sub key name size of 12
+        hive[offset + 6..offset + 8].copy_from_slice(&0x10A0u16.to_le_bytes());
+        hive[offset + 56..offset + 60].copy_from_slice(&0x012A000Cu32.to_le_bytes());
+        parser = ParserBuilder::from_file(std::io::Cursor::new(hive)).build()?;
+        let virtualization = parser.get_key("plain", false)?.unwrap().virtualization();
+        assert!(virtualization.virtual_source && !virtualization.virtual_target);
+        assert!(!virtualization.virtual_store_path);
+        assert_eq!(
+            VirtualizationControlFlags::REG_KEY_DONT_VIRTUALIZE
+                | VirtualizationControlFlags::REG_KEY_RECURSE_FLAG,
+            virtualization.control
+        );
+        assert_eq!(
+            UserFlags::KEY_32BIT | UserFlags::KEY_REFLECTED,
+            virtualization.user
+        );
+        assert_eq!(1, virtualization.debug);
+        assert_eq!(12, virtualization.largest_sub_key_name_size);
+
+        let json = serde_json::to_value(virtualization).unwrap();
+        assert_eq!(
+            serde_json::json!(["KEY_32BIT", "KEY_REFLECTED"]),
+            json["user"]
+        );
+
+        let virtualization = parser
+            .get_key("VirtualStore\\MACHINE", false)?
+            .unwrap()
+            .virtualization();
+        assert!(virtualization.virtual_store_path && virtualization.is_set());
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone_shares_values() -> Result<(), Error> {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT").build()?;
+        let mut key = parser
+            .get_key("Control Panel\\Accessibility\\Keyboard Response", false)?
+            .unwrap();
+        let clone = key.clone();
+        assert!(Arc::ptr_eq(&key.sub_values, &clone.sub_values));
+
+        // taking the values from a key that shares them leaves the clone's values alone
+        let values = key.take_values();
+        assert_eq!(*clone.sub_values, values);
+        assert!(key.sub_values.is_empty());
         Ok(())
     }
 
@@ -915,12 +2035,12 @@ mod tests {
         let sub_key = key.get_sub_key_by_index(&mut parser, 0).unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\Control Panel\Accessibility\AudioDescription",
-            sub_key.path
+            &*sub_key.path
         );
         let sub_key = key.get_sub_key_by_index(&mut parser, 11).unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\Control Panel\Accessibility\TimeOut",
-            sub_key.path
+            &*sub_key.path
         );
 
         let invalid_sub_key = key.get_sub_key_by_index(&mut parser, 20);
@@ -940,12 +2060,12 @@ mod tests {
         let sub_key = key.next_sub_key(&mut parser).unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\Control Panel\Accessibility\AudioDescription",
-            sub_key.path
+            &*sub_key.path
         );
         let sub_key = key.next_sub_key(&mut parser).unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\Control Panel\Accessibility\Blind Access",
-            sub_key.path
+            &*sub_key.path
         );
         Ok(())
     }
@@ -986,6 +2106,7 @@ mod tests {
                 value_bytes: FieldLight {
                     value: Some(vec![49, 0, 48, 0, 48, 0, 48, 0, 0, 0]),
                 },
+                data_slack: FieldLight { value: vec![0, 0] },
                 slack: FieldLight {
                     value: vec![0, 0, 0],
                 },
@@ -994,6 +2115,9 @@ mod tests {
             flags: CellKeyValueFlags::VALUE_COMP_NAME_ASCII,
             data_offsets_absolute: vec![117708],
             logs: Logs::default(),
+            value_name_raw: None,
+            value_stats: None,
+            guid_name: None,
             versions: Vec::new(),
             cell_state: CellState::Allocated,
             hash: Some(hash_array.into()),
@@ -1067,11 +2191,19 @@ mod tests {
                     offset: 117656,
                     len: 10,
                 },
+                data_slack: FieldFull {
+                    value: vec![0, 0],
+                    offset: 117718,
+                    len: 2,
+                },
             })),
             data_type: CellKeyValueDataTypes::REG_SZ,
             flags: CellKeyValueFlags::VALUE_COMP_NAME_ASCII,
             data_offsets_absolute: vec![117708],
             logs: Logs::default(),
+            value_name_raw: None,
+            value_stats: None,
+            guid_name: None,
             versions: Vec::new(),
             cell_state: CellState::Allocated,
             hash: Some(hash_array.into()),
@@ -1082,6 +2214,60 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_versions() -> Result<(), Error> {
+        let filter = FilterBuilder::new()
+            .add_key_path("Control Panel\\Accessibility\\Keyboard Response")
+            .build()?;
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT").build()?;
+        let mut key = ParserIterator::new(&parser)
+            .with_filter(filter)
+            .iter()
+            .next()
+            .unwrap();
+        assert!(key.versions().is_empty());
+
+        let version = |key: &CellKeyNode, sequence_num: u32| {
+            let mut version = key.clone();
+            version.sequence_num = Some(sequence_num);
+            version.updated_by_sequence_num = Some(sequence_num + 1);
+            version.cell_state = CellState::ModifiedTransactionLog;
+            version
+        };
+        key.versions = vec![version(&key, 10), version(&key, 12), version(&key, 11)];
+        let versions = key.versions();
+        assert_eq!(
+            vec![
+                (Some(12), Some(13)),
+                (Some(11), Some(12)),
+                (Some(10), Some(11))
+            ],
+            versions
+                .iter()
+                .map(|v| (v.sequence_num, v.updated_by_sequence_num))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(key.last_key_written_date_and_time()),
+            versions[0].timestamp
+        );
+        assert_eq!(&key.versions[1], versions[0].item);
+
+        let mut value = key.get_value("delayBeforeAcceptance").unwrap();
+        assert!(value.versions().is_empty());
+        let mut prior_value = value.clone();
+        prior_value.sequence_num = Some(10);
+        prior_value.updated_by_sequence_num = Some(11);
+        value.versions = vec![prior_value];
+        let versions = value.versions();
+        assert_eq!(1, versions.len());
+        assert_eq!(Some(10), versions[0].sequence_num);
+        assert_eq!(Some(11), versions[0].updated_by_sequence_num);
+        assert_eq!(None, versions[0].timestamp);
+        assert_eq!(&value.versions[0], versions[0].item);
+        Ok(())
+    }
+
     #[test]
     fn test_parse_cell_key_node() {
         let buffer = [
@@ -1143,16 +2329,20 @@ mod tests {
             })),
             file_offset_absolute: 0,
             key_name: "CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}".to_string(),
-            path: String::from("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}"),
+            path: Arc::from("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}"),
             cell_state: CellState::Allocated,
-            sub_values: Vec::new(),
+            sub_values: Arc::default(),
             logs: Logs::default(),
             cell_sub_key_offsets_absolute: Vec::new(),
+            key_name_raw: None,
+            path_confidence: None,
+            guid_name: None,
             iteration_state: CellKeyNodeIteration {
                 to_return: 0,
                 track_returned: 0,
                 filter_state: None,
                 sub_keys_iter_index: 0,
+                depth: 0,
             },
             versions: Vec::new(),
             deleted_keys: Vec::new(),
@@ -1161,6 +2351,7 @@ mod tests {
             updated_by_sequence_num: None,
         };
         assert_eq!(expected_light_output, key_node);
+        assert_eq!(130685969864025753, key_node.last_key_written_filetime());
 
         state.get_full_field_info = true;
         let (_, key_node) =
@@ -1280,16 +2471,20 @@ mod tests {
             })),
             file_offset_absolute: 0,
             key_name: "CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}".to_string(),
-            path: String::from("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}"),
+            path: Arc::from("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}"),
             cell_state: CellState::Allocated,
-            sub_values: Vec::new(),
+            sub_values: Arc::default(),
             logs: Logs::default(),
             cell_sub_key_offsets_absolute: Vec::new(),
+            key_name_raw: None,
+            path_confidence: None,
+            guid_name: None,
             iteration_state: CellKeyNodeIteration {
                 to_return: 0,
                 track_returned: 0,
                 filter_state: None,
                 sub_keys_iter_index: 0,
+                depth: 0,
             },
             versions: Vec::new(),
             deleted_keys: Vec::new(),
@@ -1320,12 +2515,100 @@ mod tests {
         assert_eq!(expected_error, ret);
     }
 
+    #[test]
+    fn test_field_offsets() -> Result<(), Error> {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .get_full_field_info(true)
+            .build()?;
+        let key = parser.get_root_key()?.unwrap();
+        let detail = match &key.detail {
+            CellKeyNodeDetailEnum::Full(detail) => detail,
+            CellKeyNodeDetailEnum::Light(_) => panic!("Expected full field info"),
+        };
+        // `from_bytes` records offsets from the start of the cell, including the cell size
+        let field = |offset: usize| offset - std::mem::size_of::<i32>();
+        assert_eq!(
+            vec![
+                CellKeyNode::FLAGS_FIELD,
+                CellKeyNode::PARENT_KEY_OFFSET_FIELD,
+                CellKeyNode::NUMBER_OF_SUB_KEYS_FIELD,
+                CellKeyNode::SUB_KEYS_LIST_OFFSET_FIELD,
+                CellKeyNode::NUMBER_OF_KEY_VALUES_FIELD,
+                CellKeyNode::KEY_VALUES_LIST_OFFSET_FIELD,
+                CellKeyNode::SECURITY_KEY_OFFSET_FIELD,
+                CellKeyNode::CLASS_NAME_OFFSET_FIELD,
+                CellKeyNode::KEY_NAME_SIZE_FIELD,
+                CellKeyNode::CLASS_NAME_SIZE_FIELD,
+            ],
+            vec![
+                field(detail.key_node_flag_bits.offset),
+                field(detail.parent_key_offset_relative.offset),
+                field(detail.number_of_sub_keys.offset),
+                field(detail.sub_keys_list_offset_relative.offset),
+                field(detail.number_of_key_values.offset),
+                field(detail.key_values_list_offset_relative.offset),
+                field(detail.security_key_offset_relative.offset),
+                field(detail.class_name_offset_relative.offset),
+                field(detail.key_name_size.offset),
+                field(detail.class_name_size.offset),
+            ]
+        );
+        assert_eq!(
+            CellKeyNode::HEADER_SIZE,
+            field(detail.class_name_size.offset) + detail.class_name_size.len as usize
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_get_pretty_path() {
         let key_node = CellKeyNode {
-            path: String::from("\\Root\\folder1\\folder2"),
+            path: Arc::from("\\Root\\folder1\\folder2"),
             ..Default::default()
         };
         assert_eq!("folder1\\folder2", key_node.get_pretty_path());
     }
+
+    #[test]
+    fn test_verify_parent_offsets() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .verify_parent_offsets(ParentOffsetCheck::Flag)
+            .build()
+            .unwrap();
+        assert!(!ParserIterator::new(&parser)
+            .iter()
+            .any(|key| key.logs.has_code(LogCode::WarningParent)));
+
+        // Point "Control Panel" at a parent other than the root key
+        let key = parser.get_key("Control Panel", false).unwrap().unwrap();
+        let mut hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let nk = key.file_offset_absolute + 4;
+        hive[nk + 16..nk + 20].copy_from_slice(&0x1230u32.to_le_bytes());
+
+        let read_root = |check: Option<ParentOffsetCheck>| {
+            let mut builder = ParserBuilder::from_file(std::io::Cursor::new(hive.clone()));
+            if let Some(check) = check {
+                builder.verify_parent_offsets(check);
+            }
+            let mut parser = builder.build().unwrap();
+            let mut root = parser.get_root_key().unwrap().unwrap();
+            let sub_keys = root.read_sub_keys(&mut parser);
+            let control_panel = sub_keys
+                .into_iter()
+                .find(|key| key.key_name == "Control Panel");
+            (root, control_panel)
+        };
+
+        let (root, control_panel) = read_root(None);
+        assert!(!root.logs.has_code(LogCode::WarningParent));
+        assert!(!control_panel.unwrap().logs.has_code(LogCode::WarningParent));
+
+        let (root, control_panel) = read_root(Some(ParentOffsetCheck::Flag));
+        assert!(!root.logs.has_code(LogCode::WarningParent));
+        assert!(control_panel.unwrap().logs.has_code(LogCode::WarningParent));
+
+        let (root, control_panel) = read_root(Some(ParentOffsetCheck::Skip));
+        assert!(root.logs.has_code(LogCode::WarningParent));
+        assert!(control_panel.is_none());
+    }
 }
@@ -14,14 +14,16 @@
  * limitations under the License.
  */
 
-use crate::err::Error;
-use crate::log::Logs;
+use crate::cell::CellType;
+use crate::err::{Error, ErrorContext};
+use crate::log::{LogCode, Logs};
 use nom::{
     bytes::complete::{tag, take},
     number::complete::{le_i32, le_u16, le_u32},
     IResult,
 };
 use serde::Serialize;
+use std::collections::HashSet;
 use std::io::Cursor;
 use winstructs::security::SecurityDescriptor;
 
@@ -82,30 +84,64 @@ impl CellKeySecurity {
     }
 }
 
+/// Reads the security descriptors of the key security list starting at `security_key_offset`.
+/// Stops with a `LogCode::WarningCycle` log if the list loops back to a cell other than the one it started at.
 pub(crate) fn read_cell_key_security(
     buffer: &[u8],
     security_key_offset: u32,
     hbin_offset_absolute: usize,
+    logs: &mut Logs,
 ) -> Result<Vec<SecurityDescriptor>, Error> {
     let mut security_descriptors = Vec::new();
-    let mut offset: usize = security_key_offset as usize;
+    let mut visited = HashSet::new();
+    let mut offset = security_key_offset;
     loop {
+        visited.insert(offset);
         let slice = buffer
-            .get(offset + hbin_offset_absolute..)
+            .get(offset as usize + hbin_offset_absolute..)
             .ok_or_else(|| Error::buffer("read_cell_key_security"))?;
-        let (_, cell_key_security) = CellKeySecurity::from_bytes(slice)?;
+        let (_, cell_key_security) = CellKeySecurity::from_bytes(slice).map_err(|e| {
+            Error::from(e).with_context(ErrorContext::new(
+                offset as usize + hbin_offset_absolute,
+                CellType::CellSecurity,
+            ))
+        })?;
         security_descriptors.push(SecurityDescriptor::from_stream(&mut Cursor::new(
             cell_key_security.security_descriptor,
         ))?);
 
-        if cell_key_security.detail.flink == security_key_offset {
+        let flink = cell_key_security.detail.flink;
+        if flink == security_key_offset {
             break;
         }
-        offset = cell_key_security.detail.flink as usize;
+        if visited.contains(&flink) {
+            logs.add(
+                LogCode::WarningCycle,
+                &format!(
+                    "read_cell_key_security: flink {} of security key at {} points back into the list (circular reference)",
+                    flink, offset
+                ),
+            );
+            break;
+        }
+        offset = flink;
     }
     Ok(security_descriptors)
 }
 
+/// Returns the raw (self-relative) security descriptor of the key security cell at `security_key_offset`
+pub(crate) fn read_security_descriptor_bytes(
+    buffer: &[u8],
+    security_key_offset: u32,
+    hbin_offset_absolute: usize,
+) -> Result<Vec<u8>, Error> {
+    let slice = buffer
+        .get(security_key_offset as usize + hbin_offset_absolute..)
+        .ok_or_else(|| Error::buffer("read_security_descriptor_bytes"))?;
+    let (_, cell_key_security) = CellKeySecurity::from_bytes(slice)?;
+    Ok(cell_key_security.security_descriptor)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,4 +195,25 @@ mod tests {
 
         assert_eq!(expected_output, sec);
     }
+
+    #[test]
+    fn test_read_cell_key_security_cycle() {
+        let mut hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let root = 4096 + crate::util::read_u32_le(&hive, 0x24).unwrap() as usize;
+        let security_key_offset = crate::util::read_u32_le(&hive, root + 48).unwrap();
+        let mut logs = Logs::default();
+        let security_descriptors =
+            read_cell_key_security(&hive, security_key_offset, 4096, &mut logs).unwrap();
+        assert!(security_descriptors.len() > 2);
+        assert_eq!(None, logs.get());
+
+        // Point the second cell in the list at itself rather than back to the list header
+        let second = crate::util::read_u32_le(&hive, 4096 + security_key_offset as usize + 8)
+            .unwrap() as usize;
+        hive[4096 + second + 8..4096 + second + 12].copy_from_slice(&(second as u32).to_le_bytes());
+        let security_descriptors =
+            read_cell_key_security(&hive, security_key_offset, 4096, &mut logs).unwrap();
+        assert_eq!(2, security_descriptors.len());
+        assert_eq!(LogCode::WarningCycle, logs.get().unwrap()[0].code);
+    }
 }
@@ -13,24 +13,28 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use crate::cell::{Cell, CellState};
+use crate::cell::{Cell, CellState, Version};
 use crate::cell_big_data::CellBigData;
 use crate::cell_value::{CellValue, DecodableValue, DecodeFormat};
 use crate::err::Error;
 use crate::field_offset_len::{FieldFull, FieldLight};
 use crate::field_serializers;
 use crate::file_info::FileInfo;
+use crate::impl_deserialize_for_bitflags;
 use crate::impl_enum;
 use crate::impl_serialize_for_bitflags;
 use crate::init_value_enum;
 use crate::log::{LogCode, Logs};
 use crate::make_field_struct;
 use crate::make_file_offset_structs;
+use crate::payload::{self, Payload};
 use crate::read_value_offset_length;
 use crate::state::State;
 use crate::util;
+use crate::value_stats::ValueStats;
 use bitflags::bitflags;
 use blake3::Hash;
+use chrono::{DateTime, Utc};
 use enum_primitive_derive::Primitive;
 use nom::{
     bytes::complete::{tag, take},
@@ -38,10 +42,10 @@ use nom::{
     IResult,
 };
 use num_traits::FromPrimitive;
-use serde::{Serialize, Serializer};
-use std::{convert::TryInto, mem};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{borrow::Cow, convert::TryInto, mem};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Primitive, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Primitive, Serialize)]
 #[repr(u32)]
 #[allow(non_camel_case_types)]
 pub enum CellKeyValueDataTypes {
@@ -103,83 +107,126 @@ impl CellKeyValueDataTypes {
         CellValue::Binary(input_vec.to_vec())
     }
 
-    #[rustfmt::skip]
     pub(crate) fn get_value_content(
         &self,
-        input_vec: Option<&Vec<u8>>,
+        input: Option<&[u8]>,
         logs: &mut Logs,
     ) -> Result<CellValue, Error> {
-        match input_vec {
-            None => Ok(CellValue::None),
-            Some(input_vec) => {
-                let input = &input_vec[..];
-                let cv = match self {
-                    CellKeyValueDataTypes::REG_SZ
-                    | CellKeyValueDataTypes::REG_EXPAND_SZ
-                    | CellKeyValueDataTypes::REG_LINK => CellValue::String(
-                        util::from_utf16_le_string(input, input.len(), logs, "Get value content"),
-                    ),
-                    CellKeyValueDataTypes::REG_COMPOSITE_UINT8
-                    | CellKeyValueDataTypes::REG_COMPOSITE_BOOLEAN => {
-                        match input.get(0..mem::size_of::<u8>()) {
-                            Some(val) => CellValue::U32(u8::from_le_bytes(val.try_into()?) as u32),
-                            None => Self::handle_invalid_input(input_vec, logs),
-                        }
-                    }
-                    CellKeyValueDataTypes::REG_COMPOSITE_INT16 => {
-                        match input.get(0..mem::size_of::<i16>()) {
-                            Some(val) => CellValue::I32(i16::from_le_bytes(val.try_into()?) as i32),
-                            None => Self::handle_invalid_input(input_vec, logs),
-                        }
-                    }
-                    CellKeyValueDataTypes::REG_COMPOSITE_UINT16 => {
-                        match input.get(0..mem::size_of::<u16>()) {
-                            Some(val) => CellValue::U32(u16::from_le_bytes(val.try_into()?) as u32),
-                            None => Self::handle_invalid_input(input_vec, logs),
-                        }
-                    }
-                    CellKeyValueDataTypes::REG_DWORD
-                    | CellKeyValueDataTypes::REG_COMPOSITE_UINT32 => {
-                        match input.get(0..mem::size_of::<u32>()) {
-                            Some(val) => CellValue::U32(u32::from_le_bytes(val.try_into()?)),
-                            None => Self::handle_invalid_input(input_vec, logs),
-                        }
-                    }
-                    CellKeyValueDataTypes::REG_DWORD_BIG_ENDIAN => {
-                        match input.get(0..mem::size_of::<u32>()) {
-                            Some(val) => CellValue::U32(u32::from_be_bytes(val.try_into()?)),
-                            None => Self::handle_invalid_input(input_vec, logs),
-                        }
-                    }
-                    CellKeyValueDataTypes::REG_COMPOSITE_INT32 => {
-                        match input.get(0..mem::size_of::<i32>()) {
-                            Some(val) => CellValue::I32(i32::from_le_bytes(val.try_into()?)),
-                            None => Self::handle_invalid_input(input_vec, logs),
-                        }
-                    }
-                    CellKeyValueDataTypes::REG_COMPOSITE_INT64 => {
-                        match input.get(0..mem::size_of::<i64>()) {
-                            Some(val) => CellValue::I64(i64::from_le_bytes(val.try_into()?)),
-                            None => Self::handle_invalid_input(input_vec, logs),
-                        }
-                    }
-                    CellKeyValueDataTypes::REG_QWORD
-                    | CellKeyValueDataTypes::REG_COMPOSITE_UINT64
-                    | CellKeyValueDataTypes::REG_FILETIME => {
-                        match input.get(0..mem::size_of::<u64>()) {
-                            Some(val) => CellValue::U64(u64::from_le_bytes(val.try_into()?)),
-                            None => Self::handle_invalid_input(input_vec, logs),
-                        }
-                    }
-                    CellKeyValueDataTypes::REG_BIN => CellValue::Binary(input.to_vec()),
-                    CellKeyValueDataTypes::REG_MULTI_SZ => CellValue::MultiString(
-                        util::from_utf16_le_strings(input, input.len(), logs, "Get value content"),
-                    ),
-                    _ => CellValue::Binary(input.to_vec()),
-                };
-                Ok(cv)
+        Ok(match input {
+            None => CellValue::None,
+            Some(input) => match self.get_value_content_ref(input, logs)? {
+                CellValueRef::Binary(b) => CellValue::Binary(b.to_vec()),
+                CellValueRef::Value(v) => v,
+            },
+        })
+    }
+
+    /// Like `get_value_content`, but binary content borrows from `input` rather than being copied
+    #[rustfmt::skip]
+    pub(crate) fn get_value_content_ref<'a>(
+        &self,
+        input: &'a [u8],
+        logs: &mut Logs,
+    ) -> Result<CellValueRef<'a>, Error> {
+        let cv = match self {
+            CellKeyValueDataTypes::REG_SZ
+            | CellKeyValueDataTypes::REG_EXPAND_SZ
+            | CellKeyValueDataTypes::REG_LINK => CellValue::String(
+                util::from_utf16_le_string(input, input.len(), logs, "Get value content"),
+            ),
+            CellKeyValueDataTypes::REG_COMPOSITE_UINT8
+            | CellKeyValueDataTypes::REG_COMPOSITE_BOOLEAN => {
+                match input.get(0..mem::size_of::<u8>()) {
+                    Some(val) => CellValue::U32(u8::from_le_bytes(val.try_into()?) as u32),
+                    None => Self::handle_invalid_input(input, logs),
+                }
             }
-        }
+            CellKeyValueDataTypes::REG_COMPOSITE_INT16 => {
+                match input.get(0..mem::size_of::<i16>()) {
+                    Some(val) => CellValue::I32(i16::from_le_bytes(val.try_into()?) as i32),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_COMPOSITE_UINT16 => {
+                match input.get(0..mem::size_of::<u16>()) {
+                    Some(val) => CellValue::U32(u16::from_le_bytes(val.try_into()?) as u32),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_DWORD
+            | CellKeyValueDataTypes::REG_COMPOSITE_UINT32 => {
+                match input.get(0..mem::size_of::<u32>()) {
+                    Some(val) => CellValue::U32(u32::from_le_bytes(val.try_into()?)),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_DWORD_BIG_ENDIAN => {
+                match input.get(0..mem::size_of::<u32>()) {
+                    Some(val) => CellValue::U32(u32::from_be_bytes(val.try_into()?)),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_COMPOSITE_INT32 => {
+                match input.get(0..mem::size_of::<i32>()) {
+                    Some(val) => CellValue::I32(i32::from_le_bytes(val.try_into()?)),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_COMPOSITE_INT64 => {
+                match input.get(0..mem::size_of::<i64>()) {
+                    Some(val) => CellValue::I64(i64::from_le_bytes(val.try_into()?)),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_QWORD
+            | CellKeyValueDataTypes::REG_COMPOSITE_UINT64
+            | CellKeyValueDataTypes::REG_FILETIME => {
+                match input.get(0..mem::size_of::<u64>()) {
+                    Some(val) => CellValue::U64(u64::from_le_bytes(val.try_into()?)),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            // null-terminated, so the timestamp that follows UWP strings isn't included
+            CellKeyValueDataTypes::REG_COMPOSITE_UNICODE_STRING => CellValue::String(
+                util::from_utf16_le_string(input, input.len(), logs, "Get value content"),
+            ),
+            // a single UTF-16 code unit (`Char16`)
+            CellKeyValueDataTypes::REG_COMPOSITE_UNICODE_CHAR => {
+                match input.get(0..mem::size_of::<u16>()) {
+                    Some(val) => CellValue::String(util::from_utf16_le_string(
+                        val,
+                        val.len(),
+                        logs,
+                        "Get value content",
+                    )),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_COMPOSITE_TIME_SPAN => {
+                match input.get(0..mem::size_of::<i64>()) {
+                    Some(val) => CellValue::I64(i64::from_le_bytes(val.try_into()?)),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_COMPOSITE_DATE_TIME_OFFSET => {
+                match input.get(0..mem::size_of::<u64>()) {
+                    Some(val) => CellValue::DateTime(util::get_date_time_from_filetime(u64::from_le_bytes(val.try_into()?))),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_COMPOSITE_GUID => {
+                match input.get(0..16) {
+                    Some(val) => CellValue::String(util::get_guid_from_buffer(val, logs).to_string()),
+                    None => Self::handle_invalid_input(input, logs),
+                }
+            }
+            CellKeyValueDataTypes::REG_BIN => return Ok(CellValueRef::Binary(input)),
+            CellKeyValueDataTypes::REG_MULTI_SZ => CellValue::MultiString(
+                util::from_utf16_le_strings(input, input.len(), logs, "Get value content"),
+            ),
+            _ => return Ok(CellValueRef::Binary(input)),
+        };
+        Ok(CellValueRef::Value(cv))
     }
 
     pub(crate) fn get_data_type_len(&self) -> Option<usize> {
@@ -191,14 +238,37 @@ impl CellKeyValueDataTypes {
             | CellKeyValueDataTypes::REG_COMPOSITE_UINT32
             | CellKeyValueDataTypes::REG_DWORD_BIG_ENDIAN => Some(mem::size_of::<u32>()),
             CellKeyValueDataTypes::REG_COMPOSITE_INT32 => Some(mem::size_of::<i32>()),
-            CellKeyValueDataTypes::REG_COMPOSITE_INT64 => Some(mem::size_of::<i64>()),
+            CellKeyValueDataTypes::REG_COMPOSITE_UNICODE_CHAR => Some(mem::size_of::<u16>()),
+            CellKeyValueDataTypes::REG_COMPOSITE_INT64
+            | CellKeyValueDataTypes::REG_COMPOSITE_TIME_SPAN => Some(mem::size_of::<i64>()),
             CellKeyValueDataTypes::REG_QWORD
             | CellKeyValueDataTypes::REG_COMPOSITE_UINT64
+            | CellKeyValueDataTypes::REG_COMPOSITE_DATE_TIME_OFFSET
             | CellKeyValueDataTypes::REG_FILETIME => Some(mem::size_of::<u64>()),
             _ => None,
         }
     }
 
+    /// The inverse of `get_value_content`: returns `content` encoded as it is stored for this data type.
+    /// Returns None for `CellValue::None` and `CellValue::Error` since there are no bytes to recreate.
+    pub(crate) fn get_value_bytes_from_content(&self, content: &CellValue) -> Option<Vec<u8>> {
+        let bytes = match content {
+            CellValue::None | CellValue::Error => return None,
+            CellValue::Binary(b) => b.clone(),
+            CellValue::String(s) => util::to_utf16_le_string(s),
+            CellValue::MultiString(m) => util::to_utf16_le_strings(m),
+            CellValue::U32(v) => match self {
+                CellKeyValueDataTypes::REG_DWORD_BIG_ENDIAN => v.to_be_bytes().to_vec(),
+                _ => v.to_le_bytes().to_vec(),
+            },
+            CellValue::I32(v) => v.to_le_bytes().to_vec(),
+            CellValue::U64(v) => v.to_le_bytes().to_vec(),
+            CellValue::I64(v) => v.to_le_bytes().to_vec(),
+            CellValue::DateTime(v) => util::get_filetime_from_date_time(*v).to_le_bytes().to_vec(),
+        };
+        Some(self.get_value_bytes(&bytes))
+    }
+
     pub(crate) fn get_value_bytes(&self, input: &[u8]) -> Vec<u8> {
         match self.get_data_type_len() {
             Some(data_type_len) => input[0..std::cmp::min(data_type_len, input.len())].to_vec(), // ok as direct access (checking input.len())
@@ -215,6 +285,7 @@ bitflags! {
     }
 }
 impl_serialize_for_bitflags! {CellKeyValueFlags}
+impl_deserialize_for_bitflags! {CellKeyValueFlags}
 
 make_file_offset_structs!(
     CellKeyValueDetail {
@@ -231,6 +302,8 @@ make_file_offset_structs!(
         value_name: String; serde(serialize_with = "field_serializers::field_value_name_interpreted"),
         slack: Vec<u8>,
         value_bytes: Option<Vec<u8>>; serde(skip),
+        // bytes between the end of the value's data and the end of its data cell (for data stored outside of the value cell)
+        data_slack: Vec<u8>; serde(skip),
     }
 );
 
@@ -243,6 +316,13 @@ pub struct CellKeyValue {
     pub data_offsets_absolute: Vec<usize>,
     pub cell_state: CellState,
     pub logs: Logs,
+    /// The value name's bytes, only kept when they can't be recreated from the name (see `name_raw`)
+    pub value_name_raw: Option<Vec<u8>>,
+    /// Only set for binary data when parsing with `ParserBuilder::value_stats`
+    pub value_stats: Option<ValueStats>,
+    /// The name of the GUID the value is named after, if it's known. Only set on the values of keys returned by the
+    /// iterators and lookups of a parser built with `ParserBuilder::guid_names`
+    pub guid_name: Option<String>,
 
     pub versions: Vec<Self>,
     pub hash: Option<Hash>,
@@ -259,16 +339,30 @@ impl Serialize for CellKeyValue {
     }
 }
 
+/// The value's bytes aren't part of the serialized output, so they are recreated from the interpreted `value` (see `CellKeyValueForDeserialization`)
+impl<'de> Deserialize<'de> for CellKeyValue {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        CellKeyValueForDeserialization::deserialize(d).map(Self::from)
+    }
+}
+
 impl CellKeyValue {
+    /// UWP settings (settings.dat) data types are this plus the `Windows.Foundation.PropertyType` of the value
+    pub(crate) const UWP_DATA_TYPE_BASE: u32 = 100_000_000;
     pub(crate) const BIG_DATA_SIZE_THRESHOLD: u32 = 16344;
     const MIN_CELL_VALUE_SIZE: usize = 24;
+    /// Set in the data size of a value whose data is stored in its data offset field rather than in a cell
+    pub(crate) const DATA_IS_RESIDENT_MASK: u32 = 0x80000000;
     const SIGNATURE: &'static str = "vk";
 
     pub fn get_content(&self) -> (CellValue, Option<Logs>) {
         let mut warnings = Logs::default();
         let cell_value = self
             .data_type
-            .get_value_content(self.detail.value_bytes().as_ref(), &mut warnings)
+            .get_value_content(self.detail.value_bytes_ref().as_deref(), &mut warnings)
             .or_else(|err| -> Result<CellValue, Error> {
                 warnings.add(LogCode::WarningContent, &err);
                 Ok(CellValue::Error)
@@ -281,10 +375,141 @@ impl CellKeyValue {
         }
     }
 
+    /// Returns the value's data as stored in the hive (after any big data segments have been reassembled).
+    /// The data is borrowed from the value rather than copied.
+    pub fn get_data(&self) -> &[u8] {
+        self.detail.value_bytes_ref().as_deref().unwrap_or_default()
+    }
+
+    /// Returns the value name as stored in the hive, including any embedded nulls or invalid UTF-16
+    pub fn name_raw(&self) -> Cow<'_, [u8]> {
+        match &self.value_name_raw {
+            Some(raw) => Cow::Borrowed(raw),
+            None => Cow::Owned(util::name_to_bytes(
+                &self.detail.value_name(),
+                self.flags
+                    .contains(CellKeyValueFlags::VALUE_COMP_NAME_ASCII),
+            )),
+        }
+    }
+
     pub fn get_pretty_name(&self) -> String {
         util::get_pretty_name(&self.detail.value_name())
     }
 
+    /// Returns the reason the value's data doesn't look like its declared data type, if it doesn't:
+    /// a fixed size type with the wrong size, or string data that isn't UTF-16 (an odd length or a missing null terminator)
+    /// or that holds a PE image, as malware does to hide payloads in REG_SZ values.
+    /// Values whose data wasn't read (see `ParserBuilder::max_value_size`) aren't checked.
+    pub fn data_type_mismatch(&self) -> Option<String> {
+        const PE_SIGNATURE: &[u8] = b"MZ";
+        // device property types (ex: 0xFFFF0007 is DEVPROP_TYPE_UINT32) share the low bits of the registry types
+        if self.detail.data_type_raw() != self.data_type as u32 {
+            return None;
+        }
+        let data_size = (self.detail.data_size_raw() & !Self::DATA_IS_RESIDENT_MASK) as usize;
+        let data = self.detail.value_bytes_ref().as_deref()?;
+        match self.data_type {
+            CellKeyValueDataTypes::REG_DWORD
+            | CellKeyValueDataTypes::REG_DWORD_BIG_ENDIAN
+            | CellKeyValueDataTypes::REG_QWORD => {
+                let expected_size = self.data_type.get_data_type_len()?;
+                if data_size != expected_size {
+                    return Some(format!(
+                        "{:?} data is {} bytes rather than {}",
+                        self.data_type, data_size, expected_size
+                    ));
+                }
+            }
+            CellKeyValueDataTypes::REG_SZ
+            | CellKeyValueDataTypes::REG_EXPAND_SZ
+            | CellKeyValueDataTypes::REG_MULTI_SZ
+                if data.len() == data_size =>
+            {
+                if data.starts_with(PE_SIGNATURE) {
+                    return Some(format!("{:?} data starts with a PE header", self.data_type));
+                }
+                if data_size & 1 != 0 {
+                    return Some(format!(
+                        "{:?} data has an odd length ({} bytes)",
+                        self.data_type, data_size
+                    ));
+                }
+                if !data.is_empty() && !data.ends_with(&[0, 0]) {
+                    return Some(format!("{:?} data isn't null-terminated", self.data_type));
+                }
+            }
+            _ => (),
+        }
+        None
+    }
+
+    /// Like `data_type_mismatch`, but only for values parsed with `ParserBuilder::check_data_types`, which log the mismatch.
+    /// Used by the serialized forms, so the check doesn't add a field to the output of parsers that don't ask for it.
+    pub(crate) fn logged_data_type_mismatch(&self) -> Option<String> {
+        if self.logs.has_code(LogCode::WarningDataType) {
+            self.data_type_mismatch()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the first executable or other suspicious payload found in the value's data (see `payload::detect`)
+    pub fn detect_payload(&self) -> Option<Payload> {
+        payload::detect(self.detail.value_bytes_ref().as_deref()?)
+    }
+
+    /// Returns true if the value has one of the data types used by UWP apps' settings.dat hives
+    pub fn is_uwp_value(&self) -> bool {
+        (Self::UWP_DATA_TYPE_BASE..Self::UWP_DATA_TYPE_BASE + 0x1000)
+            .contains(&self.detail.data_type_raw())
+    }
+
+    /// Returns the modification timestamp UWP apps store after the data of each value in their settings.dat hives.
+    /// The timestamps of fixed size values (such as UWP integers) are only kept when parsing with `app_hive` enabled.
+    pub fn uwp_timestamp(&self) -> Option<DateTime<Utc>> {
+        if !self.is_uwp_value() {
+            return None;
+        }
+        let data = self.detail.value_bytes_ref().as_deref()?;
+        let content_len = self.data_type.get_data_type_len().unwrap_or_default();
+        let timestamp_offset = data.len().checked_sub(mem::size_of::<u64>())?;
+        if timestamp_offset < content_len {
+            return None;
+        }
+        Some(util::get_date_time_from_filetime(u64::from_le_bytes(
+            data[timestamp_offset..].try_into().ok()?,
+        )))
+    }
+
+    /// Returns the earlier states of the value recovered from the transaction logs, newest first. These are only found when
+    /// parsing with `recover_deleted` and transaction logs.
+    pub fn versions(&self) -> Vec<Version<'_, Self>> {
+        let mut versions: Vec<_> = self
+            .versions
+            .iter()
+            .map(|value| Version {
+                sequence_num: value.sequence_num,
+                updated_by_sequence_num: value.updated_by_sequence_num,
+                timestamp: value.uwp_timestamp(),
+                item: value,
+            })
+            .collect();
+        versions.sort_by_key(|v| std::cmp::Reverse(v.sequence_num));
+        versions
+    }
+
+    /// Returns the bytes between the end of the value's name and the end of its cell
+    pub fn slack(&self) -> &[u8] {
+        self.detail.slack_ref()
+    }
+
+    /// Returns the bytes between the end of the value's data and the end of the cell holding it (for big data, the last segment).
+    /// Values with resident data (4 bytes or less) don't have a data cell, so this is empty for them.
+    pub fn data_slack(&self) -> &[u8] {
+        self.detail.data_slack_ref()
+    }
+
     /// Returns a CellValue containing `self.detail.value_bytes` interpreted as `self.data_type`
     pub(crate) fn from_bytes(
         input_orig: &[u8],
@@ -332,13 +557,12 @@ impl CellKeyValue {
 
             let mut logs = Logs::default();
 
-            let value_name = if value_name_size == 0 {
-                String::new()
+            let (value_name, value_name_raw) = if value_name_size == 0 {
+                (String::new(), None)
             } else {
-                util::string_from_bytes(
+                util::name_from_bytes(
                     flags.contains(CellKeyValueFlags::VALUE_COMP_NAME_ASCII),
                     value_name_bytes,
-                    value_name_size,
                     &mut logs,
                     "value_name",
                 )
@@ -364,6 +588,9 @@ impl CellKeyValue {
                     cell_state: CellState::Allocated,
                     data_offsets_absolute: Vec::new(),
                     logs,
+                    value_name_raw,
+                    value_stats: None,
+                    guid_name: None,
                     versions: Vec::new(),
                     hash: None,
                     sequence_num,
@@ -375,15 +602,42 @@ impl CellKeyValue {
 
     /// Reads the value content and stores it in self.detail.value_bytes
     pub(crate) fn read_value_bytes(&mut self, file_info: &FileInfo, state: &mut State) {
-        let (value_bytes, data_offsets_absolute) = Self::read_value_bytes_direct(
-            self.file_offset_absolute,
-            self.detail.data_size_raw(),
-            self.detail.data_offset_relative(),
-            &self.data_type,
-            file_info,
-            &mut self.logs,
-        );
+        let data_size = self.detail.data_size_raw() & !Self::DATA_IS_RESIDENT_MASK;
+        // Read UWP values as binary in app hives so their data isn't cut short before the timestamp that follows it
+        let data_type = if state.app_hive && self.is_uwp_value() {
+            CellKeyValueDataTypes::REG_BIN
+        } else {
+            self.data_type
+        };
+        let (value_bytes, data_offsets_absolute, data_slack) = match state.limits.max_value_size {
+            Some(max_value_size) if data_size as usize > max_value_size => {
+                self.logs.add(
+                    LogCode::WarningLimit,
+                    &format!(
+                        "Value data size {} exceeds the limit of {} bytes; data not read",
+                        data_size, max_value_size
+                    ),
+                );
+                (Vec::new(), Vec::new(), None)
+            }
+            _ => Self::read_value_bytes_direct(
+                self.file_offset_absolute,
+                self.detail.data_size_raw(),
+                self.detail.data_offset_relative(),
+                &data_type,
+                file_info,
+                state.capabilities.big_data,
+                &mut self.logs,
+            ),
+        };
 
+        if let Some((data_slack_offset, data_slack)) = data_slack {
+            self.detail.set_data_slack_full(
+                &data_slack,
+                data_slack_offset,
+                data_slack.len() as u32,
+            );
+        }
         self.data_offsets_absolute.extend(data_offsets_absolute);
         self.hash = Some(CellKeyValue::hash(
             state,
@@ -391,12 +645,37 @@ impl CellKeyValue {
             self.detail.flags_raw(),
             &value_bytes,
         ));
+        // Move the bytes into place; the generated setter would copy them
         let value_bytes_len = value_bytes.len() as u32;
-        self.detail.set_value_bytes_full(
-            &Some(value_bytes),
-            self.file_offset_absolute,
-            value_bytes_len,
-        );
+        match &mut self.detail {
+            CellKeyValueDetailEnum::Light(detail) => {
+                detail.value_bytes = FieldLight::new(Some(value_bytes))
+            }
+            CellKeyValueDetailEnum::Full(detail) => {
+                detail.value_bytes = FieldFull::new_with_len(
+                    Some(value_bytes),
+                    self.file_offset_absolute,
+                    value_bytes_len,
+                )
+            }
+        }
+        if state.value_stats && ValueStats::applies_to(self.data_type) {
+            self.value_stats = self
+                .detail
+                .value_bytes_ref()
+                .as_deref()
+                .map(ValueStats::from_data);
+        }
+        if state.detect_payloads {
+            if let Some(payload) = self.detect_payload() {
+                self.logs.add(LogCode::WarningPayload, &payload);
+            }
+        }
+        if state.check_data_types {
+            if let Some(mismatch) = self.data_type_mismatch() {
+                self.logs.add(LogCode::WarningDataType, &mismatch);
+            }
+        }
     }
 
     /// Returns the byte length of the cell (regardless of if it's allocated or free)
@@ -417,54 +696,92 @@ impl CellKeyValue {
         Self::MIN_CELL_VALUE_SIZE <= size_abs && size_abs <= input.len()
     }
 
+    /// Returns the value's bytes, the offsets of the cells they were read from, and the absolute offset and bytes of the data's slack.
+    /// Big data records are only looked for if the hive's format has them (`HiveCapabilities::big_data`).
     fn read_value_bytes_direct(
         file_offset_absolute: usize,
         data_size_raw: u32,
         data_offset_relative: u32,
         data_type: &CellKeyValueDataTypes,
         file_info: &FileInfo,
+        has_big_data: bool,
         logs: &mut Logs,
-    ) -> (Vec<u8>, Vec<usize>) {
-        const DATA_IS_RESIDENT_MASK: u32 = 0x80000000;
+    ) -> (Vec<u8>, Vec<usize>, Option<util::CellSlack>) {
         let value_bytes;
         let mut data_offsets_absolute = Vec::new();
-        if data_size_raw & DATA_IS_RESIDENT_MASK == 0 {
+        let mut data_slack = None;
+        if data_size_raw & Self::DATA_IS_RESIDENT_MASK == 0 {
             let mut offset = data_offset_relative as usize + file_info.hbin_offset_absolute;
 
             if let Some(slice) = file_info.buffer.get(offset..) {
-                if CellKeyValue::BIG_DATA_SIZE_THRESHOLD < data_size_raw
+                if has_big_data
+                    && CellKeyValue::BIG_DATA_SIZE_THRESHOLD < data_size_raw
                     && CellBigData::is_big_data_block(slice)
                 {
-                    let (vb, offsets) = CellBigData::get_big_data_bytes(
+                    let (vb, offsets, slack) = CellBigData::get_big_data_bytes(
                         file_info,
                         offset,
                         data_type,
                         data_size_raw,
+                        logs,
                     )
-                    .or_else(|err| -> Result<(Vec<u8>, Vec<usize>), Error> {
+                    .or_else(|err| -> Result<(Vec<u8>, Vec<usize>, _), Error> {
                         logs.add(LogCode::WarningBigDataContent, &err);
-                        Ok((Vec::new(), Vec::new()))
+                        Ok((Vec::new(), Vec::new(), None))
                     })
                     .expect("Error handled in or_else");
                     value_bytes = vb;
                     data_offsets_absolute.extend(offsets);
+                    data_slack = slack;
                 } else {
-                    offset += mem::size_of::<i32>(); // skip over the size bytes
-                    data_offsets_absolute.push(offset);
-
-                    match file_info
-                        .buffer
-                        .get(offset..offset + data_size_raw as usize)
-                    {
-                        Some(slice) => value_bytes = data_type.get_value_bytes(slice),
-                        None => {
+                    let mut data_size = data_size_raw as usize;
+                    let cell_size = slice
+                        .get(..mem::size_of::<i32>())
+                        .and_then(|size| size.try_into().ok())
+                        .map(|size| i32::from_le_bytes(size).unsigned_abs() as usize);
+                    if let Some(cell_size) = cell_size {
+                        let cell_data_size = cell_size.saturating_sub(mem::size_of::<i32>());
+                        if data_size > cell_data_size {
+                            // Only possible in a corrupt hive; the bytes past the cell belong to other cells
                             logs.add(
-                                LogCode::WarningParse,
-                                &Error::buffer("read_value_bytes_direct: file_offset and length"),
+                                LogCode::WarningContent,
+                                &format!(
+                                    "Value data size {} exceeds its cell ({} bytes); read {} bytes",
+                                    data_size, cell_data_size, cell_data_size
+                                ),
                             );
-                            value_bytes = Vec::new();
+                            data_size = cell_data_size;
                         }
                     }
+
+                    offset += mem::size_of::<i32>(); // skip over the size bytes
+                    data_offsets_absolute.push(offset);
+
+                    let available = file_info.buffer.len().saturating_sub(offset);
+                    if data_size <= available {
+                        value_bytes = data_type
+                            .get_value_bytes(&file_info.buffer[offset..offset + data_size]);
+                        data_slack = util::read_cell_slack(
+                            &file_info.buffer,
+                            offset - mem::size_of::<i32>(),
+                            data_size,
+                        );
+                    } else if available > 0 {
+                        logs.add(
+                            LogCode::WarningContent,
+                            &format!(
+                                "Value data size {} exceeds the end of the file; read {} bytes",
+                                data_size, available
+                            ),
+                        );
+                        value_bytes = data_type.get_value_bytes(&file_info.buffer[offset..]);
+                    } else {
+                        logs.add(
+                            LogCode::WarningParse,
+                            &Error::buffer("read_value_bytes_direct: file_offset and length"),
+                        );
+                        value_bytes = Vec::new();
+                    }
                 }
             } else {
                 logs.add(
@@ -476,7 +793,7 @@ impl CellKeyValue {
         } else {
             const DATA_OFFSET_RELATIVE_OFFSET: usize = 12;
             data_offsets_absolute.push(file_offset_absolute + DATA_OFFSET_RELATIVE_OFFSET);
-            let data_size = data_size_raw ^ DATA_IS_RESIDENT_MASK;
+            let data_size = data_size_raw ^ Self::DATA_IS_RESIDENT_MASK;
             let resident_value = data_offset_relative.to_le_bytes();
 
             match resident_value.get(..data_size as usize) {
@@ -490,7 +807,7 @@ impl CellKeyValue {
                 }
             }
         }
-        (value_bytes, data_offsets_absolute)
+        (value_bytes, data_offsets_absolute, data_slack)
     }
 
     fn hash(state: &mut State, data_type_raw: u32, flags_raw: u16, value_bytes: &[u8]) -> Hash {
@@ -545,14 +862,30 @@ struct CellKeyValueForSerialization<'a> {
     updated_by_sequence_num: &'a Option<u32>,
     data_offsets_absolute: &'a Vec<usize>,
     state: &'a CellState,
-    value: CellValue,
+    value: CellValueRef<'a>,
     value_parse_warnings: Option<Logs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_type_mismatch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value_stats: &'a Option<ValueStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guid_name: &'a Option<String>,
     versions: &'a Vec<CellKeyValue>,
 }
 
 impl<'a> From<&'a CellKeyValue> for CellKeyValueForSerialization<'a> {
     fn from(other: &'a CellKeyValue) -> Self {
-        let (value, value_parse_warnings) = other.get_content();
+        let mut warnings = Logs::default();
+        let value = match other.detail.value_bytes_ref() {
+            None => CellValueRef::Value(CellValue::None),
+            Some(value_bytes) => other
+                .data_type
+                .get_value_content_ref(value_bytes, &mut warnings)
+                .unwrap_or_else(|err| {
+                    warnings.add(LogCode::WarningContent, &err);
+                    CellValueRef::Value(CellValue::Error)
+                }),
+        };
         Self {
             file_offset_absolute: other.file_offset_absolute,
             detail: &other.detail,
@@ -565,12 +898,86 @@ impl<'a> From<&'a CellKeyValue> for CellKeyValueForSerialization<'a> {
             updated_by_sequence_num: &other.updated_by_sequence_num,
             state: &other.cell_state,
             value,
-            value_parse_warnings,
+            value_parse_warnings: warnings.get_option(),
+            data_type_mismatch: other.logged_data_type_mismatch(),
+            value_stats: &other.value_stats,
+            guid_name: &other.guid_name,
             versions: &other.versions,
         }
     }
 }
 
+/// Value content where binary data is borrowed from the value's bytes (see `CellKeyValueDataTypes::get_value_content_ref`).
+/// Serializes exactly like the equivalent `CellValue`, without copying the data first.
+#[derive(Debug)]
+pub(crate) enum CellValueRef<'a> {
+    Binary(&'a [u8]),
+    Value(CellValue),
+}
+
+impl Serialize for CellValueRef<'_> {
+    fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct HexData<'a>(
+            #[serde(serialize_with = "field_serializers::field_data_as_hex")] &'a [u8],
+        );
+
+        match self {
+            Self::Binary(b) => s.serialize_newtype_variant("CellValue", 1, "Binary", &HexData(b)),
+            Self::Value(v) => v.serialize(s),
+        }
+    }
+}
+
+/// Owned counterpart of `CellKeyValueForSerialization`. Fields which are derived from others at serialization time
+/// (`value_name` and `value_parse_warnings`) are ignored.
+#[derive(Debug, Deserialize)]
+struct CellKeyValueForDeserialization {
+    file_offset_absolute: usize,
+    detail: CellKeyValueDetailEnum,
+    data_type: CellKeyValueDataTypes,
+    flags: CellKeyValueFlags,
+    cell_parse_warnings: Logs,
+    sequence_num: Option<u32>,
+    updated_by_sequence_num: Option<u32>,
+    data_offsets_absolute: Vec<usize>,
+    state: CellState,
+    value: CellValue,
+    #[serde(default)]
+    value_stats: Option<ValueStats>,
+    #[serde(default)]
+    guid_name: Option<String>,
+    versions: Vec<CellKeyValue>,
+}
+
+impl From<CellKeyValueForDeserialization> for CellKeyValue {
+    fn from(other: CellKeyValueForDeserialization) -> Self {
+        let mut detail = other.detail;
+        let value_bytes = other.data_type.get_value_bytes_from_content(&other.value);
+        let value_bytes_len = value_bytes.as_ref().map_or(0, Vec::len) as u32;
+        detail.set_value_bytes_full(&value_bytes, other.file_offset_absolute, value_bytes_len);
+        Self {
+            file_offset_absolute: other.file_offset_absolute,
+            detail,
+            data_type: other.data_type,
+            flags: other.flags,
+            data_offsets_absolute: other.data_offsets_absolute,
+            cell_state: other.state,
+            logs: other.cell_parse_warnings,
+            value_name_raw: None,
+            value_stats: other.value_stats,
+            guid_name: other.guid_name,
+            versions: other.versions,
+            hash: None,
+            sequence_num: other.sequence_num,
+            updated_by_sequence_num: other.updated_by_sequence_num,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -579,8 +986,11 @@ mod tests {
         CellKeyValueDataTypes, CellKeyValueDetailEnum, CellKeyValueDetailFull,
         CellKeyValueDetailLight, CellKeyValueFlags,
     };
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
     use std::fs::File;
-    use std::io::Read;
+    use std::io::{Cursor, Read};
 
     #[test]
     fn test_parse_cell_key_value() {
@@ -612,6 +1022,7 @@ mod tests {
                 flags_raw: FieldLight { value: 1 },
                 padding: FieldLight { value: 0 },
                 value_bytes: FieldLight { value: None },
+                data_slack: FieldLight { value: Vec::new() },
                 value_name: FieldLight {
                     value: "IE5_UA_Backup_Flag".to_string(),
                 },
@@ -624,6 +1035,9 @@ mod tests {
             cell_state: CellState::Allocated,
             data_offsets_absolute: Vec::new(),
             logs: Logs::default(),
+            value_name_raw: None,
+            value_stats: None,
+            guid_name: None,
             versions: Vec::new(),
             hash: None,
             sequence_num: None,
@@ -681,6 +1095,11 @@ mod tests {
                     offset: 0,
                     len: 0,
                 },
+                data_slack: FieldFull {
+                    value: Vec::new(),
+                    offset: 0,
+                    len: 0,
+                },
                 value_name: FieldFull {
                     value: "IE5_UA_Backup_Flag".to_string(),
                     offset: 24,
@@ -697,6 +1116,9 @@ mod tests {
             cell_state: CellState::Allocated,
             data_offsets_absolute: Vec::new(),
             logs: Logs::default(),
+            value_name_raw: None,
+            value_stats: None,
+            guid_name: None,
             versions: Vec::new(),
             hash: None,
             sequence_num: None,
@@ -738,6 +1160,7 @@ mod tests {
                 value_bytes: FieldLight {
                     value: Some(lznt1_buffer.clone()),
                 },
+                data_slack: FieldLight { value: Vec::new() },
                 slack: FieldLight { value: vec![] },
             })),
             file_offset_absolute: 0,
@@ -746,6 +1169,9 @@ mod tests {
             cell_state: CellState::Allocated,
             data_offsets_absolute: Vec::new(),
             logs: Logs::default(),
+            value_name_raw: None,
+            value_stats: None,
+            guid_name: None,
             versions: Vec::new(),
             hash: None,
             sequence_num: None,
@@ -860,6 +1286,109 @@ mod tests {
         assert_eq!(expected_output, decoded_value);
     }
 
+    #[test]
+    fn test_uwp_value_content() {
+        // settings.dat stores the value's modification timestamp after its data
+        let timestamp = 132_000_000_000_000_000u64.to_le_bytes();
+        let with_timestamp = |data: &[u8]| [data, &timestamp].concat();
+        let mut logs = Logs::default();
+        let content = |data_type_raw: u32, data: &[u8], logs: &mut Logs| {
+            CellKeyValueDataTypes::from_u32(data_type_raw & 0xFFF)
+                .unwrap()
+                .get_value_content(Some(data), logs)
+                .unwrap()
+        };
+
+        assert_eq!(
+            CellValue::String("settings".to_string()),
+            content(
+                0x5F5E10C,
+                &with_timestamp(&util::to_utf16_le_string("settings")),
+                &mut logs
+            )
+        );
+        assert_eq!(
+            CellValue::I32(-2),
+            content(
+                0x5F5E104,
+                &with_timestamp(&(-2i32).to_le_bytes()),
+                &mut logs
+            )
+        );
+        assert_eq!(
+            CellValue::U32(1),
+            content(0x5F5E10B, &with_timestamp(&[1]), &mut logs)
+        );
+        assert_eq!(
+            CellValue::String("A".to_string()),
+            content(0x5F5E10A, &with_timestamp(&[0x41, 0]), &mut logs)
+        );
+        assert_eq!(
+            CellValue::String("\u{e9}".to_string()),
+            content(0x5F5E10A, &with_timestamp(&[0xE9, 0]), &mut logs)
+        );
+        assert_eq!(
+            CellValue::I64(-600_000_000),
+            content(
+                0x5F5E10F,
+                &with_timestamp(&(-600_000_000i64).to_le_bytes()),
+                &mut logs
+            )
+        );
+        let date_time = content(0x5F5E10E, &with_timestamp(&timestamp), &mut logs);
+        assert_eq!(
+            CellValue::DateTime(util::get_date_time_from_filetime(132_000_000_000_000_000)),
+            date_time
+        );
+        assert_eq!("2019-04-17T18:40:00.0000000Z", date_time.to_string());
+        assert_eq!(
+            Some(timestamp.to_vec()),
+            CellKeyValueDataTypes::REG_COMPOSITE_DATE_TIME_OFFSET
+                .get_value_bytes_from_content(&date_time)
+        );
+        assert_eq!(
+            CellValue::String("00112233-4455-6677-8899-AABBCCDDEEFF".to_string()),
+            content(
+                0x5F5E110,
+                &with_timestamp(&[
+                    0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66, 0x88, 0x99, 0xAA, 0xBB, 0xCC,
+                    0xDD, 0xEE, 0xFF
+                ]),
+                &mut logs
+            )
+        );
+        assert!(logs.get().is_none());
+        assert_eq!(
+            CellValue::Binary(vec![0; 4]),
+            content(0x5F5E10E, &[0; 4], &mut logs)
+        );
+        assert!(logs.get().is_some());
+    }
+
+    #[test]
+    fn test_serialize_value_content() {
+        let parser = crate::parser_builder::ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let mut binary_values = 0;
+        for key in crate::parser::ParserIterator::new(&parser).iter() {
+            for value in key.value_iter() {
+                let (content, warnings) = value.get_content();
+                if let CellValue::Binary(b) = &content {
+                    assert_eq!(b[..], value.get_data()[..]);
+                    binary_values += 1;
+                }
+                let serialized = serde_json::to_value(&value).unwrap();
+                assert_eq!(serde_json::to_value(&content).unwrap(), serialized["value"]);
+                assert_eq!(
+                    serde_json::to_value(&warnings).unwrap(),
+                    serialized["value_parse_warnings"]
+                );
+            }
+        }
+        assert!(binary_values > 0);
+    }
+
     #[test]
     fn test_parse_big_data() {
         let mut file_info = FileInfo::from_path("test_data/system").unwrap();
@@ -894,4 +1423,392 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_slack() {
+        let mut file_info = FileInfo::from_path("test_data/system").unwrap();
+        file_info.hbin_offset_absolute = 4096;
+        let mut state = State::default();
+        let key_node = CellKeyNode::read(
+            &file_info,
+            &mut state,
+            CellKeyNodeReadOptions {
+                offset: 16155688,
+                cur_path: "",
+                filter: None,
+                self_is_filter_match_or_descendent: false,
+                sequence_num: None,
+                get_deleted_and_modified: false,
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(&[108, 0, 97, 0], key_node.slack());
+
+        let value = &key_node.sub_values[2];
+        assert_eq!("Multibyte character 𐐷", value.detail.value_name());
+        assert_eq!(&[112, 95, 244, 0], value.slack());
+        assert_eq!(&[245, 0, 112, 207, 244, 0], value.data_slack());
+
+        // for big data, the slack follows the data in the last segment
+        let value = &key_node.sub_values[1];
+        assert_eq!("Binary_81725".to_string(), value.detail.value_name());
+        assert!(!value.data_slack().is_empty());
+        assert!(value.data_slack().iter().all(|b| *b == 0));
+
+        let mut parser = crate::parser_builder::ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let key = parser
+            .get_key("AppEvents\\EventLabels\\Notification.Looping.Alarm", false)
+            .unwrap()
+            .unwrap();
+        // resident data doesn't have a data cell
+        let value = key.get_value("ExcludeFromCPL").unwrap();
+        assert_eq!(CellKeyValueDataTypes::REG_DWORD, value.data_type);
+        assert!(value.data_slack().is_empty());
+    }
+
+    /// Reads the values of the key holding the Binary_81725 big data value in test_data/system
+    fn read_big_data_key_values(file_info: &FileInfo) -> Vec<CellKeyValue> {
+        let mut state = State::default();
+        CellKeyNode::read(
+            file_info,
+            &mut state,
+            CellKeyNodeReadOptions {
+                offset: 16155688,
+                cur_path: "",
+                filter: None,
+                self_is_filter_match_or_descendent: false,
+                sequence_num: None,
+                get_deleted_and_modified: false,
+            },
+        )
+        .unwrap()
+        .unwrap()
+        .sub_values
+        .to_vec()
+    }
+
+    fn log_codes(value: &CellKeyValue) -> Vec<LogCode> {
+        value
+            .logs
+            .get()
+            .into_iter()
+            .flatten()
+            .map(|log| log.code)
+            .collect()
+    }
+
+    fn set_u32(buffer: &mut [u8], offset: usize, n: u32) {
+        buffer[offset..offset + 4].copy_from_slice(&n.to_le_bytes());
+    }
+
+    #[test]
+    fn test_truncated_data() {
+        const SEGMENT_SIZE: usize = CellKeyValue::BIG_DATA_SIZE_THRESHOLD as usize;
+        let mut file_info = FileInfo::from_path("test_data/system").unwrap();
+        file_info.hbin_offset_absolute = 4096;
+        let values = read_big_data_key_values(&file_info);
+
+        // each of the big data value's segments is in its own hive bin
+        let big_data = &values[1];
+        assert_eq!("Binary_81725", big_data.detail.value_name());
+        assert_eq!(81725, big_data.get_data().len());
+        assert!(log_codes(big_data).is_empty());
+        let segments: Vec<usize> = big_data
+            .data_offsets_absolute
+            .iter()
+            .map(|offset| file_info.hbin_offset_absolute + offset)
+            .collect();
+        assert_eq!(6, segments.len());
+
+        // the file ends in the middle of the third segment
+        let mut truncated = file_info.clone();
+        truncated.buffer.truncate(segments[2] + 4 + 1000);
+        let value = &read_big_data_key_values(&truncated)[1];
+        assert_eq!(SEGMENT_SIZE * 2 + 1000, value.get_data().len());
+        assert_eq!(vec![LogCode::WarningBigDataContent], log_codes(value));
+
+        // the fourth segment is past the end of the file
+        let mut corrupt = file_info.clone();
+        let segment_list_offset = u32::from_le_bytes(
+            corrupt.buffer[file_info.hbin_offset_absolute
+                + big_data.detail.data_offset_relative() as usize
+                + 8..][..4]
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        set_u32(
+            &mut corrupt.buffer,
+            file_info.hbin_offset_absolute + segment_list_offset + 4 + 3 * 4,
+            0x7FFF_FFF0,
+        );
+        let value = &read_big_data_key_values(&corrupt)[1];
+        assert_eq!(SEGMENT_SIZE * 3, value.get_data().len());
+        assert_eq!(vec![LogCode::WarningBigDataContent], log_codes(value));
+
+        // the recorded size is more than the segments hold
+        let mut corrupt = file_info.clone();
+        set_u32(
+            &mut corrupt.buffer,
+            big_data.file_offset_absolute + 8,
+            200_000,
+        );
+        let value = &read_big_data_key_values(&corrupt)[1];
+        assert_eq!(SEGMENT_SIZE * 6, value.get_data().len());
+        assert_eq!(vec![LogCode::WarningBigDataContent], log_codes(value));
+
+        // data in a single cell whose recorded size runs past its cell and the end of the file is cut at the end of
+        // the cell
+        let string = &values[2];
+        assert_eq!(CellKeyValueDataTypes::REG_SZ, string.data_type);
+        assert_eq!(1, string.data_offsets_absolute.len());
+        let data_offset = string.data_offsets_absolute[0];
+        let cell_size = i32::from_le_bytes(
+            file_info.buffer[data_offset - 4..data_offset]
+                .try_into()
+                .unwrap(),
+        )
+        .unsigned_abs() as usize;
+        let mut corrupt = file_info.clone();
+        set_u32(
+            &mut corrupt.buffer,
+            string.file_offset_absolute + 8,
+            0x1000_0000,
+        );
+        let value = &read_big_data_key_values(&corrupt)[2];
+        assert_eq!(cell_size - 4, value.get_data().len());
+        assert_eq!(vec![LogCode::WarningContent], log_codes(value));
+        assert!(value
+            .logs
+            .get_string()
+            .contains(&format!("read {} bytes", cell_size - 4)));
+    }
+
+    #[test]
+    fn test_app_hive() {
+        // UWP settings.dat values store their modification timestamp after the data
+        let filetime = 132_000_000_000_000_000u64;
+        let uwp_value = |name: &str, data_type_raw: u32, mut data: Vec<u8>| {
+            data.extend_from_slice(&filetime.to_le_bytes());
+            WriterValue {
+                name: name.to_string(),
+                data_type_raw,
+                data,
+            }
+        };
+        let mut root = WriterKey::new("{00000000-0000-0000-0000-000000000000}");
+        let local_state = root.get_or_add_sub_key("LocalState");
+        local_state.set_value(uwp_value("Count", 0x5F5E105, 7u32.to_le_bytes().to_vec()));
+        local_state.set_value(uwp_value(
+            "Name",
+            0x5F5E10C,
+            crate::util::to_utf16_le_string("settings"),
+        ));
+        let mut hive = HiveWriter::new(root).to_bytes().unwrap();
+        // app hives are often collected without having been flushed
+        hive[8..12].copy_from_slice(&2u32.to_le_bytes());
+        crate::base_block::BaseBlockBase::update_checksum(&mut hive).unwrap();
+
+        let read_local_state = |app_hive: bool| {
+            let mut builder = ParserBuilder::from_file(Cursor::new(hive.clone()));
+            builder.strict(true).app_hive(app_hive);
+            let mut parser = builder.build()?;
+            Ok::<_, Error>(parser.get_key("LocalState", false)?.unwrap())
+        };
+        assert!(read_local_state(false).is_err());
+
+        let key = read_local_state(true).unwrap();
+        let expected_timestamp = Some(crate::util::get_date_time_from_filetime(filetime));
+        let count = key.get_value("Count").unwrap();
+        assert!(count.is_uwp_value());
+        assert_eq!(CellKeyValueDataTypes::REG_COMPOSITE_UINT32, count.data_type);
+        assert_eq!(CellValue::U32(7), count.get_content().0);
+        assert_eq!(expected_timestamp, count.uwp_timestamp());
+
+        let name = key.get_value("Name").unwrap();
+        assert_eq!(
+            CellValue::String("settings".to_string()),
+            name.get_content().0
+        );
+        assert_eq!(expected_timestamp, name.uwp_timestamp());
+
+        // without app_hive the data of fixed size values is cut short before the timestamp
+        let mut parser = ParserBuilder::from_file(Cursor::new(hive.clone()))
+            .build()
+            .unwrap();
+        let key = parser.get_key("LocalState", false).unwrap().unwrap();
+        let count = key.get_value("Count").unwrap();
+        assert_eq!(CellValue::U32(7), count.get_content().0);
+        assert_eq!(None, count.uwp_timestamp());
+        assert_eq!(
+            expected_timestamp,
+            key.get_value("Name").unwrap().uwp_timestamp()
+        );
+    }
+
+    #[test]
+    fn test_settings_dat() {
+        // A UWP app's settings.dat: the root key is named for its mount point under \Registry\A\, the hive has no
+        // transaction logs and wasn't flushed, and each value stores its modification timestamp after its data
+        let read = |app_hive: bool| {
+            ParserBuilder::from_path("test_data/settings.dat")
+                .strict(true)
+                .app_hive(app_hive)
+                .build()
+        };
+        assert!(read(false).is_err());
+        let mut parser = read(true).unwrap();
+        let base_block = &parser.base_block.as_ref().unwrap().base;
+        assert_ne!(
+            base_block.primary_sequence_number,
+            base_block.secondary_sequence_number
+        );
+        assert_eq!(
+            (4, 8),
+            parser.count_all_keys_and_values(None),
+            "root, LocalState, LocalState\\Window, and RoamingState"
+        );
+
+        let key = parser.get_key("LocalState", false).unwrap().unwrap();
+        assert_eq!(
+            r"\{7E5D1C2B-3A49-4F86-9D0E-1B2C3D4E5F60}\LocalState",
+            &*key.path
+        );
+        let expected_timestamp = util::get_date_time_from_filetime(133_500_000_000_000_000);
+        assert!(
+            key.value_iter()
+                .all(|value| value.is_uwp_value()
+                    && value.uwp_timestamp() == Some(expected_timestamp))
+        );
+        let content = |name: &str| key.get_value(name).unwrap().get_content().0;
+        assert_eq!(CellValue::U32(1), content("FirstRunCompleted"));
+        assert_eq!(CellValue::U32(42), content("LaunchCount"));
+        assert_eq!(CellValue::String("Dark".to_string()), content("Theme"));
+        assert_eq!(
+            CellValue::String(",".to_string()),
+            content("DecimalSeparator")
+        );
+        assert_eq!(
+            CellValue::DateTime(expected_timestamp),
+            content("LastLaunched")
+        );
+        assert_eq!(
+            CellValue::String("6F1B2C3D-4E5F-4A6B-8C7D-9E0F1A2B3C4D".to_string()),
+            content("SessionId")
+        );
+        assert_eq!(
+            CellKeyValueDataTypes::REG_COMPOSITE_UNICODE_CHAR,
+            key.get_value("DecimalSeparator").unwrap().data_type
+        );
+
+        let window = parser
+            .get_key("LocalState\\Window", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            CellKeyValueDataTypes::REG_COMPOSITE_COMPOSITE_VALUE,
+            window.get_value("Placement").unwrap().data_type
+        );
+    }
+
+    #[test]
+    fn test_check_data_types() {
+        let mut root = WriterKey::new("ROOT");
+        let mut pe = b"MZ\x90\x00".to_vec();
+        pe.resize(64, 0);
+        root.set_value(WriterValue::new(
+            "Payload",
+            CellKeyValueDataTypes::REG_SZ,
+            pe,
+        ));
+        root.set_value(WriterValue::new(
+            "Odd",
+            CellKeyValueDataTypes::REG_SZ,
+            vec![b'a', 0, 0],
+        ));
+        root.set_value(WriterValue::new(
+            "Short",
+            CellKeyValueDataTypes::REG_DWORD,
+            vec![1, 0],
+        ));
+        root.set_value(WriterValue::new(
+            "Long",
+            CellKeyValueDataTypes::REG_QWORD,
+            vec![1; 12],
+        ));
+        root.set_value(WriterValue::new(
+            "Valid",
+            CellKeyValueDataTypes::REG_SZ,
+            crate::util::to_utf16_le_string("valid"),
+        ));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+
+        let mut builder = ParserBuilder::from_file(Cursor::new(hive.clone()));
+        builder.check_data_types(true);
+        let mut parser = builder.build().unwrap();
+        let root = parser.get_root_key().unwrap().unwrap();
+        let mismatches: Vec<_> = root
+            .value_iter()
+            .filter(|value| value.logs.has_code(LogCode::WarningDataType))
+            .map(|value| {
+                (
+                    value.detail.value_name(),
+                    value.data_type_mismatch().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "Payload".to_string(),
+                    "REG_SZ data starts with a PE header".to_string()
+                ),
+                (
+                    "Odd".to_string(),
+                    "REG_SZ data has an odd length (3 bytes)".to_string()
+                ),
+                (
+                    "Short".to_string(),
+                    "REG_DWORD data is 2 bytes rather than 4".to_string()
+                ),
+                (
+                    "Long".to_string(),
+                    "REG_QWORD data is 12 bytes rather than 8".to_string()
+                ),
+            ],
+            mismatches
+        );
+        let json = serde_json::to_value(root.get_value("Payload").unwrap()).unwrap();
+        assert_eq!(
+            "REG_SZ data starts with a PE header",
+            json["data_type_mismatch"]
+        );
+        let json = serde_json::to_value(root.get_value("Valid").unwrap()).unwrap();
+        assert!(json.get("data_type_mismatch").is_none());
+
+        // the mismatch is still available when the check is off, but isn't logged
+        let mut parser = ParserBuilder::from_file(Cursor::new(hive)).build().unwrap();
+        let root = parser.get_root_key().unwrap().unwrap();
+        assert!(!root
+            .value_iter()
+            .any(|value| value.logs.has_code(LogCode::WarningDataType)));
+        let short = root.get_value("Short").unwrap();
+        assert!(short.data_type_mismatch().is_some());
+        let json = serde_json::to_value(&short).unwrap();
+        assert!(json.get("data_type_mismatch").is_none());
+
+        // strings without a null terminator are common enough in real hives
+        let parser = ParserBuilder::from_path("test_data/system")
+            .check_data_types(true)
+            .build()
+            .unwrap();
+        let mismatches = ParserIterator::new(&parser)
+            .iter()
+            .flat_map(|key| key.value_iter().collect::<Vec<_>>())
+            .filter(|value| value.logs.has_code(LogCode::WarningDataType))
+            .count();
+        assert_eq!(32, mismatches);
+    }
 }
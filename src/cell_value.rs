@@ -14,16 +14,22 @@
  * limitations under the License.
  */
 
+use crate::environment;
 use crate::field_serializers;
 use crate::log::{LogCode, Logs};
 use crate::util;
-use serde::Serialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use strum_macros::IntoStaticStr;
 
-#[derive(Clone, Debug, Eq, IntoStaticStr, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, IntoStaticStr, PartialEq, Serialize)]
 pub enum CellValue {
     None,
-    #[serde(serialize_with = "field_serializers::field_data_as_hex")]
+    #[serde(
+        serialize_with = "field_serializers::field_data_as_hex",
+        deserialize_with = "field_serializers::field_data_from_hex"
+    )]
     Binary(Vec<u8>),
     String(String),
     MultiString(Vec<String>),
@@ -31,6 +37,7 @@ pub enum CellValue {
     I32(i32),
     U64(u64),
     I64(i64),
+    DateTime(DateTime<Utc>),
     Error,
 }
 
@@ -40,6 +47,18 @@ impl CellValue {
         let value_type: &str = self.into();
         value_type.to_string()
     }
+
+    /// Returns the value with each `%NAME%` in its strings replaced by the variable `NAME` in `env` (see
+    /// `environment::EnvironmentBuilder`). Unknown variables are left as they are, and other types are returned unchanged.
+    pub fn expand(&self, env: &HashMap<String, String>) -> CellValue {
+        match self {
+            Self::String(s) => Self::String(environment::expand_str(s, env)),
+            Self::MultiString(m) => {
+                Self::MultiString(m.iter().map(|s| environment::expand_str(s, env)).collect())
+            }
+            _ => self.clone(),
+        }
+    }
 }
 
 impl std::fmt::Display for CellValue {
@@ -52,6 +71,7 @@ impl std::fmt::Display for CellValue {
             Self::I32(v) => write!(f, "{}", v),
             Self::U64(v) => write!(f, "{}", v),
             Self::I64(v) => write!(f, "{}", v),
+            Self::DateTime(v) => write!(f, "{}", util::format_date_time(*v)),
             _ => write!(f, ""),
         }
     }
@@ -0,0 +1,160 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Rewrites hives without free cells and unreferenced hive bins, similar to the reorganization Windows performs.
+
+use crate::err::Error;
+use crate::hive_bin_header::HiveBinHeader;
+use crate::parser::Parser;
+use crate::util;
+use crate::writer::HiveWriter;
+use serde::Serialize;
+use std::convert::TryFrom;
+
+/// How the hive bins of a hive are used
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct HiveSpaceUsage {
+    pub hive_bins: usize,
+    /// Total size of the hive bins, including their headers
+    pub hive_bins_size: usize,
+    pub allocated_cells: usize,
+    pub allocated_cells_size: usize,
+    pub free_cells: usize,
+    pub free_cells_size: usize,
+}
+
+impl HiveSpaceUsage {
+    /// Walks the hive bins in `hive_bins` (the data following the base block). Stops at the first invalid hive bin or cell.
+    pub fn from_hive_bins(hive_bins: &[u8]) -> Self {
+        let mut usage = Self::default();
+        let mut hbin_offset = 0;
+        while hive_bins.get(hbin_offset..hbin_offset + 4) == Some(b"hbin") {
+            let hbin_size = match util::read_u32_le(hive_bins, hbin_offset + 8) {
+                Some(size) if size as usize >= HiveBinHeader::SIZE => size as usize,
+                _ => break,
+            };
+            let hbin_end = std::cmp::min(hbin_offset + hbin_size, hive_bins.len());
+            usage.hive_bins += 1;
+            usage.hive_bins_size += hbin_end - hbin_offset;
+
+            let mut cell_offset = hbin_offset + HiveBinHeader::SIZE;
+            while let Some(size) = util::read_u32_le(hive_bins, cell_offset) {
+                let size = size as i32;
+                let size_abs = size.unsigned_abs() as usize;
+                if size_abs == 0 || cell_offset + size_abs > hbin_end {
+                    break;
+                }
+                if size < 0 {
+                    usage.allocated_cells += 1;
+                    usage.allocated_cells_size += size_abs;
+                } else {
+                    usage.free_cells += 1;
+                    usage.free_cells_size += size_abs;
+                }
+                cell_offset += size_abs;
+            }
+            hbin_offset += hbin_size;
+        }
+        usage
+    }
+}
+
+/// Space usage before and after compaction
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct CompactReport {
+    pub original_file_size: usize,
+    pub original: HiveSpaceUsage,
+    pub compacted_file_size: usize,
+    pub compacted: HiveSpaceUsage,
+}
+
+impl CompactReport {
+    /// Returns the number of bytes saved (negative if the compacted hive is larger)
+    pub fn bytes_saved(&self) -> i64 {
+        i64::try_from(self.original_file_size).unwrap_or(i64::MAX)
+            - i64::try_from(self.compacted_file_size).unwrap_or(i64::MAX)
+    }
+}
+
+/// Returns a copy of the hive opened by `parser` that contains only the allocated, referenced cells of its keys and values.
+/// Deleted keys and values, free cells, and slack space are not carried over.
+pub fn compact(parser: &mut Parser) -> Result<(Vec<u8>, CompactReport), Error> {
+    let hbin_offset = parser.file_info.hbin_offset_absolute;
+    let hive_bins_data_size = parser
+        .base_block
+        .as_ref()
+        .map_or(0, |base_block| base_block.base.hive_bins_data_size as usize);
+    let original_hive_bins = parser
+        .file_info
+        .buffer
+        .get(hbin_offset..)
+        .ok_or_else(|| Error::buffer("compact: hive bins"))?;
+    let original_hive_bins =
+        &original_hive_bins[..std::cmp::min(hive_bins_data_size, original_hive_bins.len())];
+    let original = HiveSpaceUsage::from_hive_bins(original_hive_bins);
+    // Any data past the hive bins (ex: left over from a larger version of the hive) is dropped as well
+    let original_file_size = parser.file_info.buffer.len();
+
+    let compacted_hive = HiveWriter::from_parser(parser)?.to_bytes()?;
+    let compacted =
+        HiveSpaceUsage::from_hive_bins(compacted_hive.get(hbin_offset..).unwrap_or_default());
+    let report = CompactReport {
+        original_file_size,
+        original,
+        compacted_file_size: compacted_hive.len(),
+        compacted,
+    };
+    Ok((compacted_hive, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser_builder::ParserBuilder;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_compact() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let (hive, report) = compact(&mut parser).unwrap();
+        assert_eq!(hive.len(), report.compacted_file_size);
+        assert_eq!(
+            std::fs::metadata("test_data/NTUSER.DAT").unwrap().len() as usize,
+            report.original_file_size
+        );
+        assert_eq!(
+            parser.base_block.as_ref().unwrap().base.hive_bins_data_size as usize,
+            report.original.hive_bins_size
+        );
+        assert!(report.bytes_saved() > 0);
+        assert!(report.compacted.hive_bins_size < report.original.hive_bins_size);
+        assert!(report.compacted.allocated_cells_size <= report.original.allocated_cells_size);
+        assert_eq!(
+            report.compacted.hive_bins_size,
+            report.compacted.allocated_cells_size
+                + report.compacted.free_cells_size
+                + 32 * report.compacted.hive_bins
+        );
+
+        let compacted = ParserBuilder::from_file(Cursor::new(hive)).build().unwrap();
+        assert_eq!(
+            parser.count_all_keys_and_values(None),
+            compacted.count_all_keys_and_values(None)
+        );
+    }
+}
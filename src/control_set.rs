@@ -0,0 +1,101 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::Serialize;
+use std::borrow::Cow;
+
+/// The control set numbers recorded in the `Select` key of a SYSTEM hive (see `Parser::resolve_current_control_set`)
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ControlSets {
+    /// The control set `CurrentControlSet` refers to
+    pub current: u32,
+    /// The control set used for the next boot
+    pub default: Option<u32>,
+    /// The control set that was replaced after a failed boot, if any
+    pub failed: Option<u32>,
+    pub last_known_good: Option<u32>,
+}
+
+impl ControlSets {
+    /// The symbolic key name which only exists in a live registry
+    pub const CURRENT_CONTROL_SET: &'static str = "CurrentControlSet";
+
+    /// Returns the key name of control set `number` (ex: `ControlSet001`)
+    pub fn key_name(number: u32) -> String {
+        format!("ControlSet{:03}", number)
+    }
+
+    pub fn current_key_name(&self) -> String {
+        Self::key_name(self.current)
+    }
+
+    /// Returns true if `key_name` is the name of the current control set (ignoring ASCII case)
+    pub(crate) fn is_current(&self, key_name: &str) -> bool {
+        match key_name.get(.."ControlSet".len()) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("ControlSet") => {
+                let number = &key_name["ControlSet".len()..];
+                number.len() == 3 && number.parse::<u32>() == Ok(self.current)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `key_path` with each `CurrentControlSet` segment replaced by the name of the current control set
+    pub fn resolve_path<'a>(&self, key_path: &'a str) -> Cow<'a, str> {
+        let is_symbolic = |segment: &str| segment.eq_ignore_ascii_case(Self::CURRENT_CONTROL_SET);
+        if key_path.split('\\').any(is_symbolic) {
+            let current = self.current_key_name();
+            let segments: Vec<&str> = key_path
+                .split('\\')
+                .map(|segment| {
+                    if is_symbolic(segment) {
+                        current.as_str()
+                    } else {
+                        segment
+                    }
+                })
+                .collect();
+            Cow::Owned(segments.join("\\"))
+        } else {
+            Cow::Borrowed(key_path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_path() {
+        let control_sets = ControlSets {
+            current: 2,
+            ..Default::default()
+        };
+        assert_eq!("ControlSet002", control_sets.current_key_name());
+        assert_eq!(
+            "ControlSet002\\Services\\Dhcp",
+            control_sets.resolve_path("currentcontrolset\\Services\\Dhcp")
+        );
+        assert!(matches!(
+            control_sets.resolve_path("ControlSet001\\Services"),
+            Cow::Borrowed(_)
+        ));
+        assert!(control_sets.is_current("controlset002"));
+        assert!(!control_sets.is_current("ControlSet2"));
+        assert!(!control_sets.is_current("ControlSet001"));
+    }
+}
@@ -0,0 +1,217 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Expansion of environment variables in REG_EXPAND_SZ data (see `CellValue::expand`), so paths such as
+//! `%SystemRoot%\system32\svchost.exe` can be compared with each other and matched against IOCs.
+
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::plugins;
+use std::collections::HashMap;
+
+/// The variables of a Windows install on C:, as used by `EnvironmentBuilder::new`
+const DEFAULT_ENVIRONMENT: &[(&str, &str)] = &[
+    ("SystemDrive", "C:"),
+    ("SystemRoot", "C:\\Windows"),
+    ("windir", "C:\\Windows"),
+    ("ComSpec", "C:\\Windows\\system32\\cmd.exe"),
+    ("ProgramFiles", "C:\\Program Files"),
+    ("ProgramFiles(x86)", "C:\\Program Files (x86)"),
+    ("ProgramW6432", "C:\\Program Files"),
+    ("CommonProgramFiles", "C:\\Program Files\\Common Files"),
+    (
+        "CommonProgramFiles(x86)",
+        "C:\\Program Files (x86)\\Common Files",
+    ),
+    ("CommonProgramW6432", "C:\\Program Files\\Common Files"),
+    ("ProgramData", "C:\\ProgramData"),
+    ("ALLUSERSPROFILE", "C:\\ProgramData"),
+    ("PUBLIC", "C:\\Users\\Public"),
+];
+
+/// Builds the map of environment variables `CellValue::expand` takes. Variables are added in order, and each value
+/// added from a hive is expanded with the variables added before it (as Windows does when it builds a user's
+/// environment from the system's).
+#[derive(Clone, Debug)]
+pub struct EnvironmentBuilder {
+    vars: HashMap<String, String>,
+}
+
+impl Default for EnvironmentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EnvironmentBuilder {
+    /// Starts with the variables of a Windows install on C: (ex: `SystemRoot` is `C:\Windows`)
+    pub fn new() -> Self {
+        Self {
+            vars: DEFAULT_ENVIRONMENT
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Starts with no variables
+    pub fn empty() -> Self {
+        Self {
+            vars: HashMap::new(),
+        }
+    }
+
+    /// Adds (or replaces) a variable. `value` is used as is.
+    pub fn add_var(mut self, name: &str, value: &str) -> Self {
+        self.remove(name);
+        self.vars.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds the variables of a SYSTEM hive (the `Control\Session Manager\Environment` key of its current control set)
+    pub fn add_system_hive(self, parser: &mut Parser) -> Result<Self, Error> {
+        match plugins::get_current_control_set(parser)? {
+            Some(control_set) => self.add_key(
+                parser,
+                &format!("{}\\Control\\Session Manager\\Environment", control_set),
+            ),
+            None => Ok(self),
+        }
+    }
+
+    /// Adds the variables of an NTUSER.DAT hive (its `Environment` key)
+    pub fn add_user_hive(self, parser: &mut Parser) -> Result<Self, Error> {
+        self.add_key(parser, "Environment")
+    }
+
+    fn add_key(mut self, parser: &mut Parser, key_path: &str) -> Result<Self, Error> {
+        if let Some(key) = parser.get_key(key_path, false)? {
+            for value in key.value_iter() {
+                if let Some(data) = plugins::value_content_string(&value) {
+                    let data = expand_str(&data, &self.vars);
+                    self = self.add_var(&value.get_pretty_name(), &data);
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    fn remove(&mut self, name: &str) {
+        self.vars
+            .retain(|existing, _| !existing.eq_ignore_ascii_case(name));
+    }
+
+    pub fn build(self) -> HashMap<String, String> {
+        self.vars
+    }
+}
+
+/// Replaces each `%NAME%` in `s` with the value of the variable `NAME` in `env` (names are compared ignoring ASCII case,
+/// as Windows does). References to variables that aren't in `env` are left as they are.
+pub fn expand_str(s: &str, env: &HashMap<String, String>) -> String {
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('%') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after
+            .find('%')
+            .and_then(|end| Some((end, get_var(env, &after[..end])?)));
+        match value {
+            Some((end, value)) => {
+                expanded.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                // the closing '%' (if there is one) may open the next reference
+                expanded.push('%');
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+fn get_var<'a>(env: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    if name.is_empty() {
+        return None;
+    }
+    env.get(name)
+        .or_else(|| {
+            env.iter()
+                .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value)
+        })
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_value::CellValue;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_expand() {
+        let env = EnvironmentBuilder::new()
+            .add_var("USERPROFILE", "C:\\Users\\alice")
+            .build();
+        assert_eq!(
+            "C:\\Windows\\system32\\svchost.exe -k netsvcs",
+            expand_str("%SystemRoot%\\system32\\svchost.exe -k netsvcs", &env)
+        );
+        assert_eq!(
+            "C:\\Users\\alice\\AppData\\Local\\Temp",
+            expand_str("%userprofile%\\AppData\\Local\\Temp", &env)
+        );
+        assert_eq!("%NOPE%\\C:", expand_str("%NOPE%\\%SystemDrive%", &env));
+        assert_eq!("100% %% C:%", expand_str("100% %% %SystemDrive%%", &env));
+        assert_eq!(
+            CellValue::MultiString(vec!["C:\\ProgramData".to_string(), "C:".to_string()]),
+            CellValue::MultiString(vec![
+                "%ProgramData%".to_string(),
+                "%SYSTEMDRIVE%".to_string()
+            ])
+            .expand(&env)
+        );
+        assert_eq!(CellValue::U32(1), CellValue::U32(1).expand(&env));
+    }
+
+    #[test]
+    fn test_add_system_hive() {
+        let mut parser = ParserBuilder::from_path("test_data/system")
+            .build()
+            .unwrap();
+        let env = EnvironmentBuilder::new()
+            .add_system_hive(&mut parser)
+            .unwrap()
+            .build();
+        let key = parser
+            .get_key(
+                "CurrentControlSet\\Control\\Session Manager\\Environment",
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        assert!(key.value_iter().count() > 0);
+        for value in key.value_iter() {
+            let name = value.get_pretty_name();
+            assert!(get_var(&env, &name).is_some(), "{}", name);
+        }
+        assert!(!env.values().any(|value| value.contains("%SystemRoot%")));
+    }
+}
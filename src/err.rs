@@ -14,12 +14,19 @@
  * limitations under the License.
  */
 
+use crate::cell::CellType;
+use std::fmt;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Debug, Error, Eq, PartialEq)]
 pub enum Error {
     #[error("An error has occurred in the Nom library: {}", detail)]
-    Nom { detail: String },
+    Nom {
+        detail: String,
+        /// The nom parser that failed, if nom reported one
+        kind: Option<nom::error::ErrorKind>,
+    },
     #[error("An error has occurred in the Winstructs library: {}", detail)]
     Winstructs { detail: String },
     #[error("An error has occurred while converting: {}", detail)]
@@ -27,7 +34,10 @@ pub enum Error {
     #[error("An error has occurred in StripPrefix: {}", detail)]
     StripPrefix { detail: String },
     #[error("An IO error has occurred: {}", detail)]
-    Io { detail: String },
+    Io {
+        detail: String,
+        source: Option<IoError>,
+    },
     #[error("An error has occurred in the Xlsxwriter library: {}", detail)]
     XlsxWriter { detail: String },
     #[error("An error has occurred: {}", detail)]
@@ -36,6 +46,116 @@ pub enum Error {
     Buffer { detail: String },
     #[error("An error has occurred when converting: {}", detail)]
     Any { detail: String },
+    #[error("An error has occurred reading the archive: {}", detail)]
+    Archive { detail: String },
+    #[error("An error has occurred reading json: {}", detail)]
+    Json { detail: String },
+    #[error("An error has occurred reading the common export format: {}", detail)]
+    CommonExport { detail: String },
+    #[error("The hive is structurally inconsistent: {}", detail)]
+    Validation { detail: String },
+    #[error("The operation was cancelled: {}", detail)]
+    Cancelled { detail: String },
+    #[error("An error has occurred in the Yara library: {}", detail)]
+    Yara { detail: String },
+    #[error("An error has occurred running a blocking task: {}", detail)]
+    Task { detail: String },
+    /// Another error along with where in the hive it happened (see `Error::context`)
+    #[error("{} ({})", source, context)]
+    Context {
+        context: ErrorContext,
+        source: Box<Error>,
+    },
+}
+
+/// Broad classes of errors, so callers can decide how to react without matching every variant
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorCategory {
+    /// The hive (or transaction log) data is malformed
+    Parse,
+    /// Reading or writing a file failed
+    Io,
+    /// Reading or writing an export format failed
+    Export,
+    Validation,
+    Cancelled,
+    Other,
+}
+
+/// Where in the hive an error happened. Fields are `None` when they aren't known at the point the error was raised.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ErrorContext {
+    /// Absolute offset in the hive of the structure being read
+    pub file_offset: Option<usize>,
+    pub cell_type: Option<CellType>,
+    /// Path of the key being processed
+    pub key_path: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn new(file_offset: usize, cell_type: CellType) -> Self {
+        Self {
+            file_offset: Some(file_offset),
+            cell_type: Some(cell_type),
+            key_path: None,
+        }
+    }
+
+    pub fn with_key_path(mut self, key_path: &str) -> Self {
+        self.key_path = Some(key_path.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(file_offset) = self.file_offset {
+            parts.push(format!("offset: {:#x}", file_offset));
+        }
+        if let Some(cell_type) = self.cell_type {
+            parts.push(format!("cell type: {:?}", cell_type));
+        }
+        if let Some(key_path) = &self.key_path {
+            parts.push(format!("key path: {}", key_path));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// The `std::io::Error` behind an `Error::Io`, kept so it is available through `std::error::Error::source`.
+/// Two `IoError`s are equal if they have the same kind and message.
+#[derive(Clone, Debug)]
+pub struct IoError(Arc<std::io::Error>);
+
+impl IoError {
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.0.kind()
+    }
+
+    pub fn get_ref(&self) -> &std::io::Error {
+        &self.0
+    }
+}
+
+impl PartialEq for IoError {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.kind() == other.0.kind() && self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl Eq for IoError {}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for IoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
 }
 
 impl Error {
@@ -44,12 +164,81 @@ impl Error {
             detail: s.to_string(),
         }
     }
+
+    /// Attaches `context` to the error. If the error already has context, fields it is missing are filled in from
+    /// `context`; the innermost (most specific) information wins.
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Self::Context {
+                context: mut existing,
+                source,
+            } => {
+                existing.file_offset = existing.file_offset.or(context.file_offset);
+                existing.cell_type = existing.cell_type.or(context.cell_type);
+                existing.key_path = existing.key_path.or(context.key_path);
+                Self::Context {
+                    context: existing,
+                    source,
+                }
+            }
+            error => Self::Context {
+                context,
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// Returns where in the hive the error happened, if known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without any attached context
+    pub fn inner(&self) -> &Self {
+        match self {
+            Self::Context { source, .. } => source.inner(),
+            error => error,
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self.inner() {
+            Self::Nom { .. }
+            | Self::Winstructs { .. }
+            | Self::Conversion { .. }
+            | Self::TryFromInt { .. }
+            | Self::Buffer { .. } => ErrorCategory::Parse,
+            Self::Io { .. } | Self::Archive { .. } => ErrorCategory::Io,
+            Self::XlsxWriter { .. } | Self::Json { .. } | Self::CommonExport { .. } => {
+                ErrorCategory::Export
+            }
+            Self::Validation { .. } => ErrorCategory::Validation,
+            Self::Cancelled { .. } => ErrorCategory::Cancelled,
+            _ => ErrorCategory::Other,
+        }
+    }
+
+    /// Returns true if the error was caused by malformed hive data. These only affect the structure being read, so
+    /// the rest of the hive can still be parsed; I/O failures and cancellation are not recoverable.
+    pub fn is_recoverable(&self) -> bool {
+        self.category() == ErrorCategory::Parse
+    }
 }
 
 impl From<nom::Err<nom::error::Error<&[u8]>>> for Error {
-    fn from(_error: nom::Err<nom::error::Error<&[u8]>>) -> Self {
-        Error::Nom {
-            detail: "Nom parsing error".to_string(),
+    fn from(error: nom::Err<nom::error::Error<&[u8]>>) -> Self {
+        match error {
+            nom::Err::Error(error) | nom::Err::Failure(error) => Error::Nom {
+                detail: format!("Nom parsing error: {}", error.code.description()),
+                kind: Some(error.code),
+            },
+            nom::Err::Incomplete(needed) => Error::Nom {
+                detail: format!("Nom parsing error: incomplete input ({:?})", needed),
+                kind: None,
+            },
         }
     }
 }
@@ -82,6 +271,7 @@ impl From<std::io::Error> for Error {
     fn from(error: std::io::Error) -> Self {
         Error::Io {
             detail: format!("{:#?}", error.to_string()),
+            source: Some(IoError(Arc::new(error))),
         }
     }
 }
@@ -95,6 +285,48 @@ impl From<xlsxwriter::XlsxError> for Error {
     }
 }
 
+#[cfg(feature = "archive")]
+impl From<zip::result::ZipError> for Error {
+    fn from(error: zip::result::ZipError) -> Self {
+        Error::Archive {
+            detail: format!("{:#?}", error.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "yara")]
+impl From<yara::Error> for Error {
+    fn from(error: yara::Error) -> Self {
+        Error::Yara {
+            detail: format!("{:#?}", error.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<tokio::task::JoinError> for Error {
+    fn from(error: tokio::task::JoinError) -> Self {
+        if error.is_cancelled() {
+            Error::Cancelled {
+                detail: format!("{:#?}", error.to_string()),
+            }
+        } else {
+            Error::Task {
+                detail: format!("{:#?}", error.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "export")]
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Json {
+            detail: format!("{:#?}", error.to_string()),
+        }
+    }
+}
+
 impl From<std::num::TryFromIntError> for Error {
     fn from(error: std::num::TryFromIntError) -> Self {
         Error::TryFromInt {
@@ -102,3 +334,62 @@ impl From<std::num::TryFromIntError> for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn test_context() {
+        let error: Error = nom::Err::Error(nom::error::Error::new(
+            &b"vk"[..],
+            nom::error::ErrorKind::Tag,
+        ))
+        .into();
+        assert_eq!(
+            Error::Nom {
+                detail: "Nom parsing error: Tag".to_string(),
+                kind: Some(nom::error::ErrorKind::Tag)
+            },
+            error
+        );
+        assert_eq!(None, error.context());
+
+        let error = error
+            .with_context(ErrorContext::new(4128, CellType::CellKey))
+            .with_context(ErrorContext::new(32, CellType::CellOther).with_key_path("\\ROOT"));
+        assert_eq!(
+            Some(&ErrorContext {
+                file_offset: Some(4128),
+                cell_type: Some(CellType::CellKey),
+                key_path: Some("\\ROOT".to_string())
+            }),
+            error.context()
+        );
+        assert_eq!(
+            "An error has occurred in the Nom library: Nom parsing error: Tag (offset: 0x1020, cell type: CellKey, key path: \\ROOT)",
+            error.to_string()
+        );
+        assert!(matches!(error.inner(), Error::Nom { .. }));
+        assert_eq!(ErrorCategory::Parse, error.category());
+        assert!(error.is_recoverable());
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    fn test_io_source() {
+        let error: Error = std::io::Error::new(std::io::ErrorKind::NotFound, "missing hive").into();
+        assert_eq!(ErrorCategory::Io, error.category());
+        assert!(!error.is_recoverable());
+        let source = error.source().expect("the io error is the source");
+        assert_eq!("missing hive", source.to_string());
+        match error {
+            Error::Io {
+                source: Some(source),
+                ..
+            } => assert_eq!(std::io::ErrorKind::NotFound, source.kind()),
+            _ => panic!("expected an Io error"),
+        }
+    }
+}
@@ -0,0 +1,498 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{
+    cell::CellState,
+    cell_key_node::{CellKeyNode, CellKeyNodeDetailEnum, PathConfidence},
+    cell_key_value::{
+        CellKeyValue, CellKeyValueDataTypes, CellKeyValueDetailEnum, CellKeyValueFlags,
+    },
+    err::Error,
+    log::Logs,
+    util,
+};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use num_traits::FromPrimitive;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::*;
+use std::sync::Arc;
+
+/// The totals from the end of a common export file. They are only set once the reader has reached the end of the file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CommonTotals {
+    pub keys: u32,
+    pub values: u32,
+    pub unused_keys: u32,
+    pub unused_values: u32,
+    pub deleted_from_transaction_log_keys: u32,
+    pub deleted_from_transaction_log_values: u32,
+    pub modified_from_transaction_log_keys: u32,
+    pub modified_from_transaction_log_values: u32,
+}
+
+/// Reads keys and values in the registry common export format (as written by `WriteCommon`, Registry Explorer, and other tools)
+/// back into `CellKeyNode`s, so the output of different tools can be compared programmatically.
+///
+/// Each value is added to the key before it. Versions from the transaction logs (`M` records with the same path or name as the
+/// key or value before them) are added to that key's or value's `versions`. The format only has the key name of unused keys
+/// (its full path is restored from the annotations column written by `WriteCommon::annotate_recovered`, when present), and
+/// control characters escaped by the writer (ex: `\x00`) are not unescaped. Hashes aren't calculated.
+pub struct ReadCommon<R: Read> {
+    reader: BufReader<R>,
+    line_number: usize,
+    pending: Option<CellKeyNode>,
+    totals: CommonTotals,
+}
+
+impl ReadCommon<File> {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::from_reader(File::open(path)?))
+    }
+}
+
+impl<R: Read> ReadCommon<R> {
+    pub fn from_reader(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            line_number: 0,
+            pending: None,
+            totals: CommonTotals::default(),
+        }
+    }
+
+    pub fn totals(&self) -> &CommonTotals {
+        &self.totals
+    }
+
+    /// Returns the fields of the next key or value record, or None at the end of the file. Comment lines are skipped
+    /// (other than the totals, which are kept), and quoted fields may span lines.
+    fn next_record(&mut self) -> Result<Option<Vec<String>>, Error> {
+        loop {
+            let mut record = String::new();
+            if self.reader.read_line(&mut record)? == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+            if record.starts_with("##") {
+                self.read_total(&record);
+                continue;
+            }
+            let first_line = self.line_number;
+            loop {
+                if let Some(mut fields) = split_fields(&record) {
+                    if let Some(last) = fields.last_mut() {
+                        let len = last.trim_end_matches(['\r', '\n']).len();
+                        last.truncate(len);
+                    }
+                    if fields.len() == 1 && fields[0].trim().is_empty() {
+                        break;
+                    }
+                    return Ok(Some(fields));
+                }
+                if self.reader.read_line(&mut record)? == 0 {
+                    return Err(self.error(first_line, "unterminated quoted field"));
+                }
+                self.line_number += 1;
+            }
+        }
+    }
+
+    fn read_total(&mut self, line: &str) {
+        let (name, count) = match line[2..].split_once(':') {
+            Some((name, count)) => (name, count.trim()),
+            None => return,
+        };
+        let count = match count.parse() {
+            Ok(count) => count,
+            Err(_) => return,
+        };
+        let total = match name.trim() {
+            "total_keys" => &mut self.totals.keys,
+            "total_values" => &mut self.totals.values,
+            "total_unused_keys" => &mut self.totals.unused_keys,
+            "total_unused_values" => &mut self.totals.unused_values,
+            "total_deleted_from_transaction_log_keys" => {
+                &mut self.totals.deleted_from_transaction_log_keys
+            }
+            "total_deleted_from_transaction_log_values" => {
+                &mut self.totals.deleted_from_transaction_log_values
+            }
+            "total_modified_from_transaction_log_keys" => {
+                &mut self.totals.modified_from_transaction_log_keys
+            }
+            "total_modified_from_transaction_log_values" => {
+                &mut self.totals.modified_from_transaction_log_values
+            }
+            _ => return,
+        };
+        *total = count;
+    }
+
+    fn error(&self, line_number: usize, detail: &str) -> Error {
+        Error::CommonExport {
+            detail: format!("line {}: {}", line_number, detail),
+        }
+    }
+
+    fn field<'f>(&self, fields: &'f [String], index: usize) -> Result<&'f str, Error> {
+        fields
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| self.error(self.line_number, "too few fields"))
+    }
+
+    fn cell_state(&self, state: &str, annotations: &Annotations) -> Result<CellState, Error> {
+        match state {
+            "A" => Ok(CellState::Allocated),
+            "U" if annotations.source == Some("slack") => Ok(CellState::DeletedPrimaryFileSlack),
+            "U" => Ok(CellState::DeletedPrimaryFile),
+            "D" => Ok(CellState::DeletedTransactionLog),
+            "M" => Ok(CellState::ModifiedTransactionLog),
+            _ => Err(self.error(self.line_number, &format!("unknown state '{}'", state))),
+        }
+    }
+
+    fn read_key(&self, fields: &[String]) -> Result<CellKeyNode, Error> {
+        let annotations = Annotations::parse(fields.get(8).map_or("", String::as_str));
+        let cell_state = self.cell_state(self.field(fields, 1)?, &annotations)?;
+        let file_offset_absolute = self
+            .field(fields, 2)?
+            .parse()
+            .map_err(|_| self.error(self.line_number, "invalid offset"))?;
+        let key_path = self.field(fields, 3)?;
+        let timestamp = self.field(fields, 7)?;
+        let last_key_written = parse_date_time(timestamp).ok_or_else(|| {
+            self.error(
+                self.line_number,
+                &format!("invalid timestamp '{}'", timestamp),
+            )
+        })?;
+
+        let (key_name, path) = if cell_state.is_deleted_primary_file() {
+            (
+                key_path.to_string(),
+                annotations.path.unwrap_or(key_path).to_string(),
+            )
+        } else {
+            (
+                key_path.rsplit('\\').next().unwrap_or_default().to_string(),
+                format!("\\{}", key_path),
+            )
+        };
+        let mut detail = CellKeyNodeDetailEnum::default();
+        detail.set_last_key_written_date_and_time(
+            &util::get_filetime_from_date_time(last_key_written),
+            0,
+        );
+        Ok(CellKeyNode {
+            file_offset_absolute,
+            detail,
+            key_name,
+            path: path.into(),
+            cell_state,
+            sequence_num: annotations.prev_seq_num,
+            updated_by_sequence_num: annotations.mod_seq_num,
+            path_confidence: annotations.path_confidence,
+            ..Default::default()
+        })
+    }
+
+    fn read_value(&self, fields: &[String]) -> Result<CellKeyValue, Error> {
+        let annotations = Annotations::parse(fields.get(8).map_or("", String::as_str));
+        let cell_state = self.cell_state(self.field(fields, 1)?, &annotations)?;
+        let file_offset_absolute = self
+            .field(fields, 2)?
+            .parse()
+            .map_err(|_| self.error(self.line_number, "invalid offset"))?;
+        let value_name = match self.field(fields, 4)? {
+            "(default)" => "",
+            value_name => value_name,
+        };
+        let data_type_raw: u32 = self
+            .field(fields, 5)?
+            .parse()
+            .map_err(|_| self.error(self.line_number, "invalid data type"))?;
+        let data = util::from_hex_string(self.field(fields, 6)?)
+            .ok_or_else(|| self.error(self.line_number, "invalid value data"))?;
+
+        let mut detail = CellKeyValueDetailEnum::default();
+        detail.set_value_name(&value_name.to_string(), 0);
+        detail.set_value_name_size(&(value_name.len() as u16), 0);
+        detail.set_data_type_raw(&data_type_raw, 0);
+        detail.set_data_size_raw(&(data.len() as u32), 0);
+        detail.set_value_bytes(&Some(data), 0);
+        Ok(CellKeyValue {
+            file_offset_absolute,
+            detail,
+            data_type: CellKeyValueDataTypes::from_u32(data_type_raw)
+                .unwrap_or(CellKeyValueDataTypes::REG_UNKNOWN),
+            flags: CellKeyValueFlags::default(),
+            data_offsets_absolute: Vec::new(),
+            cell_state,
+            logs: Logs::default(),
+            value_name_raw: None,
+            value_stats: None,
+            guid_name: None,
+            versions: Vec::new(),
+            hash: None,
+            sequence_num: annotations.prev_seq_num,
+            updated_by_sequence_num: annotations.mod_seq_num,
+        })
+    }
+
+    fn read_next_key(&mut self) -> Result<Option<CellKeyNode>, Error> {
+        while let Some(fields) = self.next_record()? {
+            match fields[0].as_str() {
+                "key" => {
+                    let key = self.read_key(&fields)?;
+                    match &mut self.pending {
+                        Some(pending)
+                            if key.cell_state == CellState::ModifiedTransactionLog
+                                && key.path == pending.path =>
+                        {
+                            pending.versions.push(key)
+                        }
+                        pending => {
+                            if let Some(previous) = pending.replace(key) {
+                                return Ok(Some(previous));
+                            }
+                        }
+                    }
+                }
+                "value" => {
+                    let value = self.read_value(&fields)?;
+                    let pending = match &mut self.pending {
+                        Some(pending) => pending,
+                        None => {
+                            return Err(self.error(self.line_number, "value before the first key"))
+                        }
+                    };
+                    let sub_values = Arc::make_mut(&mut pending.sub_values);
+                    match sub_values.last_mut() {
+                        Some(previous)
+                            if value.cell_state == CellState::ModifiedTransactionLog
+                                && value.detail.value_name() == previous.detail.value_name() =>
+                        {
+                            previous.versions.push(value)
+                        }
+                        _ => sub_values.push(value),
+                    }
+                }
+                other => {
+                    return Err(self.error(
+                        self.line_number,
+                        &format!("unknown record type '{}'", other),
+                    ))
+                }
+            }
+        }
+        Ok(self.pending.take())
+    }
+}
+
+impl<R: Read> Iterator for ReadCommon<R> {
+    type Item = Result<CellKeyNode, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_next_key() {
+            Ok(key) => key.map(Ok),
+            Err(e) => {
+                self.pending = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// The annotations column written by `WriteCommon::annotate_recovered`
+#[derive(Default)]
+struct Annotations<'a> {
+    source: Option<&'a str>,
+    path: Option<&'a str>,
+    path_confidence: Option<PathConfidence>,
+    prev_seq_num: Option<u32>,
+    mod_seq_num: Option<u32>,
+}
+
+impl<'a> Annotations<'a> {
+    fn parse(annotations: &'a str) -> Self {
+        let mut parsed = Self::default();
+        for annotation in annotations.split(';') {
+            match annotation.split_once('=') {
+                Some(("source", source)) => parsed.source = Some(source),
+                Some(("path", path)) => parsed.path = Some(path),
+                Some(("path_confidence", confidence)) => {
+                    parsed.path_confidence = match confidence {
+                        "Exact" => Some(PathConfidence::Exact),
+                        "Derived" => Some(PathConfidence::Derived),
+                        "Orphan" => Some(PathConfidence::Orphan),
+                        _ => None,
+                    }
+                }
+                Some(("prev_seq_num", sequence_num)) => {
+                    parsed.prev_seq_num = sequence_num.parse().ok()
+                }
+                Some(("mod_seq_num", sequence_num)) => {
+                    parsed.mod_seq_num = sequence_num.parse().ok()
+                }
+                _ => (),
+            }
+        }
+        parsed
+    }
+}
+
+/// Splits a record into its fields, undoing the quoting of `util::escape_string`. Returns None if a quoted field isn't closed
+/// (because it continues on the next line).
+fn split_fields(record: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+/// Parses ISO-8601 timestamps (as written by `WriteCommon`), and timestamps without a timezone (assumed to be UTC)
+fn parse_date_time(timestamp: &str) -> Option<DateTime<Utc>> {
+    let timestamp = timestamp.trim();
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|date_time| date_time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(timestamp, format).ok())
+                .map(|date_time| Utc.from_utc_datetime(&date_time))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::common_writer::WriteCommon;
+    use crate::export::jsonl_writer::Compression;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_round_trip() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let mut output = Vec::new();
+        WriteCommon::from_writer(&mut output, Compression::None)
+            .unwrap()
+            .write(&parser, None)
+            .unwrap();
+        let mut reader = ReadCommon::from_reader(&output[..]);
+        let keys: Vec<CellKeyNode> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+
+        let expected: Vec<CellKeyNode> = ParserIterator::new(&parser).iter().collect();
+        assert_eq!(expected.len(), keys.len());
+        assert_eq!(keys.len() as u32, reader.totals().keys);
+        assert_eq!(
+            keys.iter()
+                .map(|key| key.value_iter().count())
+                .sum::<usize>() as u32,
+            reader.totals().values
+        );
+        for (expected, key) in expected.iter().zip(&keys) {
+            assert_eq!(expected.path, key.path);
+            assert_eq!(expected.key_name, key.key_name);
+            assert_eq!(expected.file_offset_absolute, key.file_offset_absolute);
+            assert_eq!(
+                expected.last_key_written_filetime(),
+                key.last_key_written_filetime()
+            );
+            assert_eq!(expected.value_iter().count(), key.value_iter().count());
+            for (expected, value) in expected.value_iter().zip(key.value_iter()) {
+                assert_eq!(expected.detail.value_name(), value.detail.value_name());
+                assert_eq!(expected.data_type, value.data_type);
+                assert_eq!(expected.get_data(), value.get_data());
+                assert_eq!(expected.get_content().0, value.get_content().0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_records() {
+        let export = "## Registry common export format\n\
+            key,A,4096,ROOT,,,,2012-04-06T15:52:25.1468083Z\n\
+            value,A,4200,ROOT,\"a, \"\"quoted\"\"\nname\",4,01 00 00 00,\n\
+            value,M,4300,ROOT,\"a, \"\"quoted\"\"\nname\",4,02 00 00 00,,source=transaction_log;prev_seq_num=7;mod_seq_num=9\n\
+            key,U,8192,Deleted,,,,2012-04-06 15:52:25.1468083,source=slack;path=\\ROOT\\Deleted;path_confidence=Exact\n\
+            value,U,8300,,(default),1,,\n\
+            ## total_keys: 1\n\
+            ## total_unused_keys: 1\n";
+        let mut reader = ReadCommon::from_reader(export.as_bytes());
+        let keys: Vec<CellKeyNode> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(2, keys.len());
+        assert_eq!(
+            CommonTotals {
+                keys: 1,
+                unused_keys: 1,
+                ..Default::default()
+            },
+            *reader.totals()
+        );
+
+        assert_eq!("\\ROOT", &*keys[0].path);
+        assert_eq!(129782011451468083, keys[0].last_key_written_filetime());
+        let value = keys[0].get_value("a, \"quoted\"\nname").unwrap();
+        assert_eq!(CellKeyValueDataTypes::REG_DWORD, value.data_type);
+        assert_eq!(&[1, 0, 0, 0][..], value.get_data());
+        assert_eq!(1, value.versions.len());
+        assert_eq!(
+            (Some(7), Some(9)),
+            (
+                value.versions[0].sequence_num,
+                value.versions[0].updated_by_sequence_num
+            )
+        );
+
+        assert_eq!("Deleted", keys[1].key_name);
+        assert_eq!("\\ROOT\\Deleted", &*keys[1].path);
+        assert_eq!(CellState::DeletedPrimaryFileSlack, keys[1].cell_state);
+        assert_eq!(Some(PathConfidence::Exact), keys[1].path_confidence);
+        assert_eq!(129782011451468083, keys[1].last_key_written_filetime());
+        assert_eq!("", keys[1].get_value("").unwrap().detail.value_name());
+
+        let err = ReadCommon::from_reader("value,A,0,ROOT,name,4,00,\n".as_bytes()).next();
+        assert!(matches!(err, Some(Err(Error::CommonExport { .. }))));
+        let err = ReadCommon::from_reader("key,A,0,\"ROOT,,,,\n".as_bytes()).next();
+        assert!(matches!(err, Some(Err(Error::CommonExport { .. }))));
+    }
+}
@@ -14,11 +14,12 @@
  * limitations under the License.
  */
 
-use notatin::{
+use crate::{
     cell::CellState,
-    cell_key_node::CellKeyNode,
+    cell_key_node::{CellKeyNode, PathConfidence},
     cell_key_value::CellKeyValue,
     err::Error,
+    export::jsonl_writer::{Compression, Sink},
     filter::Filter,
     parser::{Parser, ParserIterator},
     progress, util,
@@ -27,18 +28,43 @@ use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::*;
 
-pub(crate) struct WriteCommon {
-    writer: BufWriter<File>,
+pub struct WriteCommon<W: Write = File> {
+    writer: BufWriter<Sink<W>>,
+    annotate_recovered: bool,
 }
 
 impl WriteCommon {
-    pub(crate) fn new(output: impl AsRef<Path>) -> Result<Self, Error> {
-        let write_file = File::create(output)?;
-        let writer = BufWriter::new(write_file);
-        Ok(WriteCommon { writer })
+    pub fn new(output: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new_compressed(output, Compression::None)
     }
 
-    pub(crate) fn write(&mut self, parser: &Parser, filter: Option<Filter>) -> Result<(), Error> {
+    /// Like `new`, but the output is compressed with `compression`
+    pub fn new_compressed(
+        output: impl AsRef<Path>,
+        compression: Compression,
+    ) -> Result<Self, Error> {
+        Self::from_writer(File::create(output)?, compression)
+    }
+}
+
+impl<W: Write> WriteCommon<W> {
+    /// Writes to `writer` rather than to a file
+    pub fn from_writer(writer: W, compression: Compression) -> Result<Self, Error> {
+        Ok(WriteCommon {
+            writer: BufWriter::new(Sink::new(writer, compression)?),
+            annotate_recovered: false,
+        })
+    }
+
+    /// Adds an annotations column to recovered keys and values (and to versions from the transaction logs) with where they
+    /// were recovered from, the full path and path confidence of keys recovered from free cells, and their sequence numbers.
+    /// Defaults to false, which writes the original columns only.
+    pub fn annotate_recovered(&mut self, annotate_recovered: bool) -> &mut Self {
+        self.annotate_recovered = annotate_recovered;
+        self
+    }
+
+    pub fn write(&mut self, parser: &Parser, filter: Option<Filter>) -> Result<(), Error> {
         writeln!(
             &mut self.writer,
             "## Registry common export format\n\
@@ -66,6 +92,15 @@ impl WriteCommon {
             ## Before comparison with other common export implementations, the files should be sorted\n\
             ##"
         )?;
+        if self.annotate_recovered {
+            writeln!(
+                &mut self.writer,
+                "## Annotations\n\
+                ## Recovered keys and values, and versions from the transaction logs, have a ninth column of semicolon separated name=value pairs:\n\
+                ## source (free_cell, slack, or transaction_log), path and path_confidence (unused keys), prev_seq_num and mod_seq_num (transaction log items)\n\
+                ##"
+            )?;
+        }
         let mut keys = 0;
         let mut values = 0;
         let mut unused_keys = 0;
@@ -79,8 +114,14 @@ impl WriteCommon {
         if let Some(filter) = filter {
             iter.with_filter(filter);
         }
+        if let Some(sort) = parser.key_sort() {
+            iter.sort_keys(sort);
+        }
+        if let Some(path_mapping) = parser.path_mapping() {
+            iter.full_paths(path_mapping.clone());
+        }
 
-        let mut console = progress::new(true);
+        let mut console = progress::new(parser.update_console);
         for (index, key) in iter.iter().enumerate() {
             console.update_progress(index)?;
             self.write_key(
@@ -122,6 +163,7 @@ impl WriteCommon {
                 }
             }
         }
+        parser.check_cancelled()?;
         writeln!(&mut self.writer, "## total_keys: {}", keys)?;
         writeln!(&mut self.writer, "## total_values: {}", values)?;
         writeln!(&mut self.writer, "## total_unused_keys: {}", unused_keys)?;
@@ -150,7 +192,8 @@ impl WriteCommon {
             "## total_modified_from_transaction_log_values: {}",
             tx_log_modified_values
         )?;
-        Ok(())
+        self.writer.flush()?;
+        self.writer.get_mut().try_finish()
     }
 
     fn get_alloc_char(state: &CellState) -> &str {
@@ -177,18 +220,18 @@ impl WriteCommon {
             } // ## When including unused keys, only the recovered key name should be included, not the full path to the deleted key.
             CellState::Allocated => {
                 *keys += 1;
-                &key.path[1..]
+                key.path.strip_prefix('\\').unwrap_or(&key.path)
             } // drop the first slash to match EZ's formatting
             CellState::DeletedTransactionLog => {
                 *tx_log_deleted_keys += 1;
-                &key.path[1..]
+                key.path.strip_prefix('\\').unwrap_or(&key.path)
             } // drop the first slash to match EZ's formatting
             CellState::ModifiedTransactionLog => {
                 *tx_log_modified_keys += 1;
-                &key.path[1..]
+                key.path.strip_prefix('\\').unwrap_or(&key.path)
             } // drop the first slash to match EZ's formatting
         };
-        writeln!(
+        write!(
             self.writer,
             "key,{},{},{},,,,{}",
             Self::get_alloc_char(&key.cell_state),
@@ -196,7 +239,13 @@ impl WriteCommon {
             util::escape_string(key_path),
             util::format_date_time(key.last_key_written_date_and_time())
         )?;
-        Ok(())
+        self.write_annotations(
+            key.cell_state,
+            key.path_confidence
+                .map(|confidence| (&key.path[..], confidence)),
+            key.sequence_num,
+            key.updated_by_sequence_num,
+        )
     }
 
     fn write_value(
@@ -226,7 +275,7 @@ impl WriteCommon {
                 &key.key_name[..]
             }
         };
-        writeln!(
+        write!(
             self.writer,
             "value,{},{},{},{},{:?},{},",
             Self::get_alloc_char(&value.cell_state),
@@ -234,8 +283,105 @@ impl WriteCommon {
             util::escape_string(key_name),
             util::escape_string(&value.get_pretty_name()),
             value.data_type as u32,
-            util::to_hex_string(&value.detail.value_bytes().unwrap_or_default()[..])
+            util::to_hex_string(value.get_data())
+        )?;
+        self.write_annotations(
+            value.cell_state,
+            None,
+            value.sequence_num,
+            value.updated_by_sequence_num,
+        )
+    }
+
+    /// Ends the line, with the annotations column if `annotate_recovered` is set and the item isn't allocated
+    fn write_annotations(
+        &mut self,
+        cell_state: CellState,
+        recovered_path: Option<(&str, PathConfidence)>,
+        sequence_num: Option<u32>,
+        updated_by_sequence_num: Option<u32>,
+    ) -> Result<(), Error> {
+        if !self.annotate_recovered || cell_state == CellState::Allocated {
+            writeln!(self.writer)?;
+            return Ok(());
+        }
+        let source = match cell_state {
+            CellState::DeletedPrimaryFile => "free_cell",
+            CellState::DeletedPrimaryFileSlack => "slack",
+            _ => "transaction_log",
+        };
+        let mut annotations = vec![format!("source={}", source)];
+        if cell_state.is_deleted_primary_file() {
+            if let Some((path, confidence)) = recovered_path {
+                annotations.push(format!("path={}", path));
+                annotations.push(format!("path_confidence={:?}", confidence));
+            }
+        }
+        if let Some(sequence_num) = sequence_num {
+            annotations.push(format!("prev_seq_num={}", sequence_num));
+        }
+        if let Some(sequence_num) = updated_by_sequence_num {
+            annotations.push(format!("mod_seq_num={}", sequence_num));
+        }
+        writeln!(
+            self.writer,
+            ",{}",
+            util::escape_string(&annotations.join(";"))
         )?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser_builder::ParserBuilder;
+
+    fn write_common(annotate_recovered: bool) -> String {
+        let parser = ParserBuilder::from_path("test_data/system")
+            .recover_deleted(true)
+            .build()
+            .unwrap();
+        let mut output = Vec::new();
+        WriteCommon::from_writer(&mut output, Compression::None)
+            .unwrap()
+            .annotate_recovered(annotate_recovered)
+            .write(&parser, None)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_annotate_recovered() {
+        let plain = write_common(false);
+        let annotated = write_common(true);
+        let records = |output: &str| -> Vec<String> {
+            output
+                .lines()
+                .filter(|line| !line.starts_with("##"))
+                .map(str::to_string)
+                .collect()
+        };
+        let plain = records(&plain);
+        let annotated = records(&annotated);
+        assert_eq!(plain.len(), annotated.len());
+
+        let mut unused_keys = 0;
+        for (plain, annotated) in plain.iter().zip(&annotated) {
+            if plain.starts_with("key,A,") || plain.starts_with("value,A,") {
+                assert_eq!(plain, annotated);
+                continue;
+            }
+            let annotations = annotated
+                .strip_prefix(plain.as_str())
+                .and_then(|rest| rest.strip_prefix(','))
+                .unwrap();
+            assert!(annotations.contains("source="), "{}", annotated);
+            if plain.starts_with("key,U,") {
+                unused_keys += 1;
+                assert!(annotations.contains("path_confidence="), "{}", annotated);
+            }
+        }
+        assert_eq!(192, unused_keys);
+    }
+}
@@ -0,0 +1,317 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Graph exports of the key hierarchy (DOT for Graphviz, GraphML for Gephi and yEd): one node per key, with its name,
+//! path, last written timestamp, sub key and value counts, and depth as attributes, and an edge from each key to its
+//! sub keys.
+
+use crate::{
+    cell_key_node::CellKeyNode,
+    err::Error,
+    export::jsonl_writer::{Compression, Sink},
+    filter::Filter,
+    parser::{Parser, ParserIterator},
+    progress,
+    timestamp_format::TimestampFormat,
+    util,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::*;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+}
+
+pub struct WriteGraph<W: Write = File> {
+    format: GraphFormat,
+    max_depth: Option<usize>,
+    timestamp_format: TimestampFormat,
+    writer: BufWriter<Sink<W>>,
+}
+
+impl WriteGraph {
+    pub fn new(output: impl AsRef<Path>, format: GraphFormat) -> Result<Self, Error> {
+        Self::new_compressed(output, format, Compression::None)
+    }
+
+    /// Like `new`, but the output is compressed with `compression`
+    pub fn new_compressed(
+        output: impl AsRef<Path>,
+        format: GraphFormat,
+        compression: Compression,
+    ) -> Result<Self, Error> {
+        Self::from_writer(File::create(output)?, format, compression)
+    }
+}
+
+impl<W: Write> WriteGraph<W> {
+    /// Writes to `writer` rather than to a file
+    pub fn from_writer(
+        writer: W,
+        format: GraphFormat,
+        compression: Compression,
+    ) -> Result<Self, Error> {
+        Ok(WriteGraph {
+            format,
+            max_depth: None,
+            timestamp_format: TimestampFormat::default(),
+            writer: BufWriter::new(Sink::new(writer, compression)?),
+        })
+    }
+
+    /// Leaves out the keys more than `max_depth` levels below the root key (which is at depth 0); their sub keys aren't
+    /// read at all. Defaults to unlimited.
+    pub fn max_depth(&mut self, max_depth: usize) -> &mut Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Sets how the last written timestamps are written (see `WriteTsv::timestamp_format`)
+    pub fn timestamp_format(&mut self, timestamp_format: TimestampFormat) -> &mut Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// Writes the keys matching `filter`. A key whose parent isn't written (because of the filter) is a root of the graph.
+    pub fn write(&mut self, parser: &Parser, filter: Option<Filter>) -> Result<(), Error> {
+        let mut iter = ParserIterator::new(parser);
+        if let Some(filter) = filter {
+            iter.with_filter(filter);
+        }
+        if let Some(path_mapping) = parser.path_mapping() {
+            iter.full_paths(path_mapping.clone());
+        }
+        if let Some(max_depth) = self.max_depth {
+            iter.max_depth(max_depth);
+        }
+
+        match self.format {
+            GraphFormat::Dot => writeln!(self.writer, "digraph hive {{\n    node [shape=box];")?,
+            GraphFormat::GraphMl => {
+                writeln!(
+                    self.writer,
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                    <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+                )?;
+                for (name, attr_type) in [
+                    ("label", "string"),
+                    ("path", "string"),
+                    ("last_written", "string"),
+                    ("sub_keys", "int"),
+                    ("values", "int"),
+                    ("depth", "int"),
+                ]
+                .iter()
+                {
+                    writeln!(
+                        self.writer,
+                        "  <key id=\"{0}\" for=\"node\" attr.name=\"{0}\" attr.type=\"{1}\"/>",
+                        name, attr_type
+                    )?;
+                }
+                writeln!(
+                    self.writer,
+                    "  <graph id=\"hive\" edgedefault=\"directed\">"
+                )?;
+            }
+        }
+
+        // key path -> node id
+        let mut node_ids: HashMap<Arc<str>, usize> = HashMap::new();
+        let mut console = progress::new(parser.update_console);
+        for (index, key) in iter.iter().enumerate() {
+            console.update_progress(index)?;
+            let id = node_ids.len();
+            let last_written = self
+                .timestamp_format
+                .format(key.last_key_written_date_and_time());
+            self.write_node(id, &key, &last_written)?;
+            let parent_id = key
+                .path
+                .rfind('\\')
+                .and_then(|index| node_ids.get(&key.path[..index]))
+                .copied();
+            if let Some(parent_id) = parent_id {
+                self.write_edge(parent_id, id)?;
+            }
+            node_ids.insert(key.path, id);
+        }
+        parser.check_cancelled()?;
+
+        match self.format {
+            GraphFormat::Dot => writeln!(self.writer, "}}")?,
+            GraphFormat::GraphMl => writeln!(self.writer, "  </graph>\n</graphml>")?,
+        }
+        self.writer.flush()?;
+        self.writer.get_mut().try_finish()
+    }
+
+    fn write_node(
+        &mut self,
+        id: usize,
+        key: &CellKeyNode,
+        last_written: &str,
+    ) -> Result<(), Error> {
+        match self.format {
+            GraphFormat::Dot => writeln!(
+                self.writer,
+                "    n{} [label=\"{}\", path=\"{}\", last_written=\"{}\", sub_keys={}, values={}, depth={}];",
+                id,
+                dot_escape(&key.key_name),
+                dot_escape(&key.path),
+                dot_escape(last_written),
+                key.detail.number_of_sub_keys(),
+                key.detail.number_of_key_values(),
+                key.iteration_state.depth
+            )?,
+            GraphFormat::GraphMl => writeln!(
+                self.writer,
+                "    <node id=\"n{}\"><data key=\"label\">{}</data><data key=\"path\">{}</data>\
+                <data key=\"last_written\">{}</data><data key=\"sub_keys\">{}</data>\
+                <data key=\"values\">{}</data><data key=\"depth\">{}</data></node>",
+                id,
+                xml_escape(&key.key_name),
+                xml_escape(&key.path),
+                xml_escape(last_written),
+                key.detail.number_of_sub_keys(),
+                key.detail.number_of_key_values(),
+                key.iteration_state.depth
+            )?,
+        }
+        Ok(())
+    }
+
+    fn write_edge(&mut self, source: usize, target: usize) -> Result<(), Error> {
+        match self.format {
+            GraphFormat::Dot => writeln!(self.writer, "    n{} -> n{};", source, target)?,
+            GraphFormat::GraphMl => writeln!(
+                self.writer,
+                "    <edge source=\"n{}\" target=\"n{}\"/>",
+                source, target
+            )?,
+        }
+        Ok(())
+    }
+}
+
+/// Escapes `orig` for a quoted DOT string (key paths are full of backslashes)
+fn dot_escape(orig: &str) -> Cow<'_, str> {
+    let orig = util::escape_control_chars(orig);
+    if orig.contains(&['\\', '"', '\n', '\r'][..]) {
+        Cow::Owned(
+            orig.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', ""),
+        )
+    } else {
+        orig
+    }
+}
+
+/// Escapes `orig` for XML text and attribute values. Control characters, which XML 1.0 doesn't allow, are escaped as
+/// by `util::escape_control_chars`.
+fn xml_escape(orig: &str) -> Cow<'_, str> {
+    let orig = util::escape_control_chars(orig);
+    if orig.contains(&['&', '<', '>', '"', '\''][..]) {
+        Cow::Owned(
+            orig.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+                .replace('\'', "&apos;"),
+        )
+    } else {
+        orig
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterBuilder;
+    use crate::parser_builder::ParserBuilder;
+
+    fn write_graph(
+        format: GraphFormat,
+        filter: Option<Filter>,
+        max_depth: Option<usize>,
+    ) -> String {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let mut output = Vec::new();
+        let mut writer = WriteGraph::from_writer(&mut output, format, Compression::None).unwrap();
+        if let Some(max_depth) = max_depth {
+            writer.max_depth(max_depth);
+        }
+        writer.write(&parser, filter).unwrap();
+        drop(writer);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_write_dot() {
+        let output = write_graph(GraphFormat::Dot, None, Some(1));
+        assert!(output.starts_with("digraph hive {"));
+        assert!(output.trim_end().ends_with('}'));
+        let nodes = output
+            .lines()
+            .filter(|line| line.contains("[label="))
+            .count();
+        let edges = output.lines().filter(|line| line.contains(" -> ")).count();
+        // the root key and its 10 sub keys
+        assert_eq!((11, 10), (nodes, edges));
+        assert!(output.contains(
+            "n0 [label=\"CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\", \
+            path=\"\\\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\""
+        ));
+    }
+
+    #[test]
+    fn test_write_graphml() {
+        let filter = FilterBuilder::new()
+            .add_key_path("Control Panel")
+            .return_child_keys(true)
+            .build()
+            .unwrap();
+        let output = write_graph(GraphFormat::GraphMl, Some(filter), None);
+        assert!(output.starts_with("<?xml"));
+        assert!(output.trim_end().ends_with("</graphml>"));
+        let nodes = output.matches("<node ").count();
+        let edges = output.matches("<edge ").count();
+        assert!(nodes > 1);
+        // the filtered keys form a single tree
+        assert_eq!(nodes - 1, edges);
+        assert!(output.contains("<data key=\"label\">Control Panel</data>"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("\\\\Key \\\"1\\\"\\\\x00", dot_escape("\\Key \"1\"\0"));
+        assert_eq!(
+            "a &lt;b&gt; &amp; &quot;c&quot;",
+            xml_escape("a <b> & \"c\"")
+        );
+        assert_eq!("Control Panel", dot_escape("Control Panel"));
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{cell_key_node::CellKeyNode, err::Error};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines, Read};
+use std::path::*;
+
+/// Reads keys previously exported by `WriteJson` (one `CellKeyNode` per line) without needing the original hive
+pub struct ReadJson<R: Read> {
+    lines: Lines<BufReader<R>>,
+}
+
+impl ReadJson<File> {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::from_reader(File::open(path)?))
+    }
+}
+
+impl<R: Read> ReadJson<R> {
+    pub fn from_reader(reader: R) -> Self {
+        Self {
+            lines: BufReader::new(reader).lines(),
+        }
+    }
+}
+
+impl<R: Read> Iterator for ReadJson<R> {
+    type Item = Result<CellKeyNode, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if !line.trim().is_empty() {
+                return Some(serde_json::from_str(&line).map_err(Error::from));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_value::CellValue;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_round_trip() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .get_full_field_info(true)
+            .build()
+            .unwrap();
+        let key = parser
+            .get_key("Control Panel\\Accessibility\\MouseKeys", false)
+            .unwrap()
+            .unwrap();
+        let mut jsonl = serde_json::to_string(&key).unwrap();
+        jsonl += "\n\n";
+        jsonl += &serde_json::to_string(&key).unwrap();
+
+        let keys: Vec<CellKeyNode> = ReadJson::from_reader(jsonl.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(2, keys.len());
+        let read = &keys[0];
+        assert_eq!(key.path, read.path);
+        assert_eq!(key.detail, read.detail);
+        assert_eq!(
+            key.last_key_written_date_and_time(),
+            read.last_key_written_date_and_time()
+        );
+        assert_eq!(key.value_iter().count(), read.value_iter().count());
+        for (value, read_value) in key.value_iter().zip(read.value_iter()) {
+            assert_eq!(value.detail.value_name(), read_value.detail.value_name());
+            assert_eq!(value.data_type, read_value.data_type);
+            assert_eq!(value.flags, read_value.flags);
+            assert_eq!(value.get_content(), read_value.get_content());
+        }
+        assert_eq!(
+            Some(CellValue::String("80".to_string())),
+            read.get_value("MaximumSpeed").map(|v| v.get_content().0)
+        );
+        assert_eq!(
+            serde_json::to_string(&key).unwrap(),
+            serde_json::to_string(read).unwrap()
+        );
+
+        let err = ReadJson::from_reader("{\"not\": \"a key\"}".as_bytes()).next();
+        assert!(matches!(err, Some(Err(Error::Json { .. }))));
+    }
+}
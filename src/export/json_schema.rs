@@ -0,0 +1,400 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Versioned layouts for the records written by `WriteJson`. Version 1 is `CellKeyNode`'s own serialization, so its shape
+//! follows the structs; version 2 is defined here, separately from them, so it only changes along with its version number.
+
+use crate::{
+    cell::CellState,
+    cell_key_node::{CellKeyNode, KeyFlags, PathConfidence, Virtualization},
+    cell_key_value::{CellKeyValue, CellKeyValueDataTypes},
+    cell_value::CellValue,
+    log::{Log, Logs},
+    parser::Parser,
+    util,
+    value_stats::ValueStats,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+/// The layout of the records written by `WriteJson`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum JsonSchema {
+    /// The serialization of `CellKeyNode` (see the JSON schema section of the README), for existing consumers
+    #[default]
+    V1,
+    /// `KeyV2`: snake_case names throughout, logs flattened into `warnings` arrays, value content as plain JSON, and
+    /// `recovered` and `source` annotations on every key and value. The options pick which optional fields are written.
+    V2(SerializationOptions),
+}
+
+/// Which optional fields `KeyV2` and `ValueV2` write, to trade output size against completeness. The default writes
+/// everything except `raw_data`, `security_descriptor`, and `virtualization`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SerializationOptions {
+    /// `raw_data`: each value's data as space separated hex bytes, alongside the decoded `data`
+    pub raw_data: bool,
+    /// `warnings` on keys and values
+    pub warnings: bool,
+    /// `offset` on keys and values
+    pub offsets: bool,
+    /// `security_descriptor`: each key's self-relative security descriptor as space separated hex bytes
+    pub security: bool,
+    /// `versions` on keys and values (earlier states recovered from transaction logs)
+    pub versions: bool,
+    /// `virtualization` on keys: the virtualization flags, user flags, and debug bits (see `CellKeyNode::virtualization`)
+    pub virtualization: bool,
+}
+
+impl Default for SerializationOptions {
+    fn default() -> Self {
+        Self {
+            raw_data: false,
+            warnings: true,
+            offsets: true,
+            security: false,
+            versions: true,
+            virtualization: false,
+        }
+    }
+}
+
+/// Where a key or value was found
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordSource {
+    /// An allocated cell in the primary file
+    PrimaryFile,
+    /// A free cell in the primary file
+    PrimaryFileFreeCell,
+    /// Slack space in the primary file
+    PrimaryFileSlack,
+    /// A transaction log
+    TransactionLog,
+}
+
+/// The state of a key or value
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordState {
+    Allocated,
+    Deleted,
+    Modified,
+}
+
+fn state_and_source(cell_state: CellState) -> (RecordState, RecordSource) {
+    match cell_state {
+        CellState::Allocated => (RecordState::Allocated, RecordSource::PrimaryFile),
+        CellState::ModifiedTransactionLog => (RecordState::Modified, RecordSource::TransactionLog),
+        CellState::DeletedTransactionLog => (RecordState::Deleted, RecordSource::TransactionLog),
+        CellState::DeletedPrimaryFile => (RecordState::Deleted, RecordSource::PrimaryFileFreeCell),
+        CellState::DeletedPrimaryFileSlack => {
+            (RecordState::Deleted, RecordSource::PrimaryFileSlack)
+        }
+    }
+}
+
+fn flatten_logs(logs: &Logs) -> Vec<Log> {
+    logs.get().cloned().unwrap_or_default()
+}
+
+/// A key, as written with `JsonSchema::V2`. Fields that are an `Option` are left out when `SerializationOptions` turns
+/// them off.
+#[derive(Clone, Debug, Serialize)]
+pub struct KeyV2 {
+    /// Always 2
+    pub schema_version: u32,
+    pub path: String,
+    pub name: String,
+    /// See `CellKeyNode::guid_name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guid_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    pub last_written: DateTime<Utc>,
+    pub sub_key_count: u32,
+    pub value_count: usize,
+    pub state: RecordState,
+    /// True for deleted keys and keys modified by a transaction log
+    pub recovered: bool,
+    pub source: RecordSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_confidence: Option<PathConfidence>,
+    pub flags: KeyFlags,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virtualization: Option<Virtualization>,
+    pub sequence_num: Option<u32>,
+    pub updated_by_sequence_num: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_descriptor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<Log>>,
+    pub values: Vec<ValueV2>,
+    /// Earlier states of the key recovered from transaction logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub versions: Option<Vec<KeyV2>>,
+}
+
+impl KeyV2 {
+    /// Converts `key` with the optional fields chosen by `options`. `parser` is needed for the security descriptor.
+    pub fn with_options(key: &CellKeyNode, parser: &Parser, options: SerializationOptions) -> Self {
+        Self::new(key, Some(parser), options)
+    }
+
+    fn new(key: &CellKeyNode, parser: Option<&Parser>, options: SerializationOptions) -> Self {
+        let (state, source) = state_and_source(key.cell_state);
+        Self {
+            schema_version: 2,
+            path: key.path.to_string(),
+            name: key.key_name.clone(),
+            guid_name: key.guid_name.clone(),
+            offset: Some(key.file_offset_absolute).filter(|_| options.offsets),
+            last_written: key.last_key_written_date_and_time(),
+            sub_key_count: key.detail.number_of_sub_keys(),
+            value_count: key.sub_values.len(),
+            state,
+            recovered: state != RecordState::Allocated,
+            source,
+            path_confidence: key.path_confidence,
+            flags: key.decode_flags(parser.and_then(|parser| key.layered_key(parser))),
+            virtualization: Some(key.virtualization()).filter(|_| options.virtualization),
+            sequence_num: key.sequence_num,
+            updated_by_sequence_num: key.updated_by_sequence_num,
+            security_descriptor: match parser {
+                Some(parser) if options.security => key
+                    .get_security_descriptor_bytes(parser)
+                    .ok()
+                    .map(|bytes| util::to_hex_string(&bytes)),
+                _ => None,
+            },
+            warnings: Some(flatten_logs(&key.logs)).filter(|_| options.warnings),
+            values: key
+                .sub_values
+                .iter()
+                .map(|value| ValueV2::with_options(value, options))
+                .collect(),
+            versions: Some(&key.versions)
+                .filter(|_| options.versions)
+                .map(|versions| {
+                    versions
+                        .iter()
+                        .map(|version| Self::new(version, parser, options))
+                        .collect()
+                }),
+        }
+    }
+}
+
+/// Converts with the default `SerializationOptions`
+impl From<&CellKeyNode> for KeyV2 {
+    fn from(key: &CellKeyNode) -> Self {
+        Self::new(key, None, SerializationOptions::default())
+    }
+}
+
+/// A value, as written with `JsonSchema::V2`. Fields that are an `Option` (apart from `data_type_mismatch` and
+/// `value_stats`, which depend on the `ParserBuilder` options) are left out when `SerializationOptions` turns them off.
+#[derive(Clone, Debug, Serialize)]
+pub struct ValueV2 {
+    /// `(default)` for the default value
+    pub name: String,
+    /// See `CellKeyValue::guid_name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guid_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    pub data_type: CellKeyValueDataTypes,
+    /// How `data` was decoded: `none`, `binary` (as space separated hex bytes), `string`, `multi_string`, `u32`, `i32`,
+    /// `u64`, `i64`, `date_time`, or `error`
+    pub content_type: &'static str,
+    pub data: Value,
+    pub data_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_data: Option<String>,
+    /// See `CellKeyValue::data_type_mismatch`. Only written by parsers built with `ParserBuilder::check_data_types`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_type_mismatch: Option<String>,
+    pub state: RecordState,
+    /// True for deleted values and values modified by a transaction log
+    pub recovered: bool,
+    pub source: RecordSource,
+    pub sequence_num: Option<u32>,
+    pub updated_by_sequence_num: Option<u32>,
+    /// Logs from parsing the value's cell and from decoding its content
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<Log>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_stats: Option<ValueStats>,
+    /// Earlier states of the value recovered from transaction logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub versions: Option<Vec<ValueV2>>,
+}
+
+impl ValueV2 {
+    /// Converts `value` with the optional fields chosen by `options`
+    pub fn with_options(value: &CellKeyValue, options: SerializationOptions) -> Self {
+        let (state, source) = state_and_source(value.cell_state);
+        let (content, content_logs) = value.get_content();
+        let mut warnings = flatten_logs(&value.logs);
+        if let Some(content_logs) = content_logs {
+            warnings.extend(flatten_logs(&content_logs));
+        }
+        let (content_type, data) = match content {
+            CellValue::None => ("none", Value::Null),
+            CellValue::Binary(data) => ("binary", Value::from(util::to_hex_string(&data))),
+            CellValue::String(s) => ("string", Value::from(s)),
+            CellValue::MultiString(strings) => ("multi_string", Value::from(strings)),
+            CellValue::U32(n) => ("u32", Value::from(n)),
+            CellValue::I32(n) => ("i32", Value::from(n)),
+            CellValue::U64(n) => ("u64", Value::from(n)),
+            CellValue::I64(n) => ("i64", Value::from(n)),
+            CellValue::DateTime(date_time) => (
+                "date_time",
+                Value::from(date_time.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            ),
+            CellValue::Error => ("error", Value::Null),
+        };
+        let raw_data = value.get_data();
+        Self {
+            name: value.get_pretty_name(),
+            guid_name: value.guid_name.clone(),
+            offset: Some(value.file_offset_absolute).filter(|_| options.offsets),
+            data_type: value.data_type,
+            content_type,
+            data,
+            data_size: raw_data.len(),
+            raw_data: Some(raw_data)
+                .filter(|_| options.raw_data)
+                .map(util::to_hex_string),
+            data_type_mismatch: value.logged_data_type_mismatch(),
+            state,
+            recovered: state != RecordState::Allocated,
+            source,
+            sequence_num: value.sequence_num,
+            updated_by_sequence_num: value.updated_by_sequence_num,
+            warnings: Some(warnings).filter(|_| options.warnings),
+            value_stats: value.value_stats,
+            versions: Some(&value.versions)
+                .filter(|_| options.versions)
+                .map(|versions| {
+                    versions
+                        .iter()
+                        .map(|version| Self::with_options(version, options))
+                        .collect()
+                }),
+        }
+    }
+}
+
+/// Converts with the default `SerializationOptions`
+impl From<&CellKeyValue> for ValueV2 {
+    fn from(value: &CellKeyValue) -> Self {
+        Self::with_options(value, SerializationOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_key_v2() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let key = parser
+            .get_key("Control Panel\\Accessibility\\MouseKeys", false)
+            .unwrap()
+            .unwrap();
+        let json = serde_json::to_value(KeyV2::from(&key)).unwrap();
+        assert_eq!(2, json["schema_version"]);
+        assert_eq!("MouseKeys", json["name"]);
+        assert_eq!("allocated", json["state"]);
+        assert_eq!("primary_file", json["source"]);
+        assert_eq!(false, json["recovered"]);
+        assert!(json["warnings"].is_array());
+        assert!(json.get("logs").is_none());
+        assert!(json["flags"]["key_node"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("KEY_COMP_NAME")));
+        assert!(json["flags"].get("layered_key").is_none());
+
+        let values = json["values"].as_array().unwrap();
+        assert_eq!(key.value_iter().count(), values.len());
+        for (value, json) in key.value_iter().zip(values) {
+            assert_eq!(value.get_pretty_name(), json["name"]);
+            assert!(json["warnings"].is_array());
+            if let (CellValue::String(s), _) = value.get_content() {
+                assert_eq!("string", json["content_type"]);
+                assert_eq!(s, json["data"]);
+            }
+            assert!(json.get("raw_data").is_none());
+        }
+        assert!(json.get("security_descriptor").is_none());
+        assert!(json.get("virtualization").is_none());
+
+        let options = SerializationOptions {
+            raw_data: true,
+            warnings: false,
+            offsets: false,
+            security: true,
+            versions: false,
+            virtualization: true,
+        };
+        let json = serde_json::to_value(KeyV2::with_options(&key, &parser, options)).unwrap();
+        for field in &["offset", "warnings", "versions"] {
+            assert!(json.get(field).is_none());
+        }
+        assert_eq!(json!(false), json["virtualization"]["virtual_store_path"]);
+        assert!(json["security_descriptor"]
+            .as_str()
+            .unwrap()
+            .starts_with("01 00"));
+        for (value, json) in key.value_iter().zip(json["values"].as_array().unwrap()) {
+            assert_eq!(util::to_hex_string(value.get_data()), json["raw_data"]);
+            assert!(json.get("offset").is_none());
+        }
+    }
+
+    #[test]
+    fn test_value_v2_data_type_mismatch() {
+        let mut root = WriterKey::new("ROOT");
+        root.set_value(WriterValue::new(
+            "Short",
+            CellKeyValueDataTypes::REG_DWORD,
+            vec![1, 0],
+        ));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        let read_value = |check_data_types: bool| {
+            let mut builder = ParserBuilder::from_file(Cursor::new(hive.clone()));
+            builder.check_data_types(check_data_types);
+            let mut parser = builder.build().unwrap();
+            let root = parser.get_root_key().unwrap().unwrap();
+            serde_json::to_value(ValueV2::from(&root.get_value("Short").unwrap())).unwrap()
+        };
+        assert_eq!(
+            "REG_DWORD data is 2 bytes rather than 4",
+            read_value(true)["data_type_mismatch"]
+        );
+        assert!(read_value(false).get("data_type_mismatch").is_none());
+    }
+}
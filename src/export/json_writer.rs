@@ -0,0 +1,162 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{
+    cell_key_node::CellKeyNode,
+    err::Error,
+    export::json_schema::{JsonSchema, KeyV2},
+    export::jsonl_writer::{Compression, JsonlWriter},
+    filter::Filter,
+    parser::{Parser, ParserIterator},
+    progress, util,
+};
+use serde::Serialize;
+use std::fs::File;
+use std::path::*;
+
+pub struct WriteJson {}
+
+impl WriteJson {
+    pub fn write(
+        out_path: impl AsRef<Path>,
+        parser: &Parser,
+        filter: Option<Filter>,
+        console: &mut Box<dyn progress::UpdateProgressTrait>,
+    ) -> Result<(), Error> {
+        Self::write_compressed(out_path, parser, filter, console, Compression::None)
+    }
+
+    /// Like `write`, but the output is compressed with `compression`
+    pub fn write_compressed(
+        out_path: impl AsRef<Path>,
+        parser: &Parser,
+        filter: Option<Filter>,
+        console: &mut Box<dyn progress::UpdateProgressTrait>,
+        compression: Compression,
+    ) -> Result<(), Error> {
+        Self::write_keys(
+            out_path,
+            parser,
+            filter,
+            console,
+            compression,
+            |writer, key| writer.write(key),
+        )
+    }
+
+    /// Like `write_compressed`, but each key is written in the layout of `schema`
+    pub fn write_schema(
+        out_path: impl AsRef<Path>,
+        parser: &Parser,
+        filter: Option<Filter>,
+        console: &mut Box<dyn progress::UpdateProgressTrait>,
+        compression: Compression,
+        schema: JsonSchema,
+    ) -> Result<(), Error> {
+        match schema {
+            JsonSchema::V1 => {
+                Self::write_compressed(out_path, parser, filter, console, compression)
+            }
+            JsonSchema::V2(options) => Self::write_keys(
+                out_path,
+                parser,
+                filter,
+                console,
+                compression,
+                |writer, key| writer.write(&KeyV2::with_options(key, parser, options)),
+            ),
+        }
+    }
+
+    /// Like `write_compressed`, but each key also includes the hex encoded slack of its cell, and of its values' cells and data cells.
+    /// Data hidden in slack isn't visible to the registry APIs.
+    pub fn write_with_slack(
+        out_path: impl AsRef<Path>,
+        parser: &Parser,
+        filter: Option<Filter>,
+        console: &mut Box<dyn progress::UpdateProgressTrait>,
+        compression: Compression,
+    ) -> Result<(), Error> {
+        Self::write_keys(
+            out_path,
+            parser,
+            filter,
+            console,
+            compression,
+            |writer, key| writer.write(&KeyWithSlack::from(key)),
+        )
+    }
+
+    fn write_keys(
+        out_path: impl AsRef<Path>,
+        parser: &Parser,
+        filter: Option<Filter>,
+        console: &mut Box<dyn progress::UpdateProgressTrait>,
+        compression: Compression,
+        write_key: impl Fn(&mut JsonlWriter<File>, &CellKeyNode) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut writer = JsonlWriter::new(File::create(out_path)?, compression)?;
+        let mut iter = ParserIterator::new(parser);
+        if let Some(filter) = filter {
+            iter.with_filter(filter);
+        }
+        if let Some(sort) = parser.key_sort() {
+            iter.sort_keys(sort);
+        }
+        if let Some(path_mapping) = parser.path_mapping() {
+            iter.full_paths(path_mapping.clone());
+        }
+        for (index, key) in iter.iter().enumerate() {
+            console.update_progress(index)?;
+            write_key(&mut writer, &key)?;
+        }
+        parser.check_cancelled()?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct KeyWithSlack<'a> {
+    #[serde(flatten)]
+    key: &'a CellKeyNode,
+    slack: String,
+    value_slack: Vec<ValueSlack>,
+}
+
+impl<'a> From<&'a CellKeyNode> for KeyWithSlack<'a> {
+    fn from(key: &'a CellKeyNode) -> Self {
+        Self {
+            key,
+            slack: util::to_hex_string(key.slack()),
+            value_slack: key
+                .value_iter()
+                .map(|value| ValueSlack {
+                    value_name: value.get_pretty_name(),
+                    slack: util::to_hex_string(value.slack()),
+                    data_slack: util::to_hex_string(value.data_slack()),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ValueSlack {
+    value_name: String,
+    slack: String,
+    data_slack: String,
+}
@@ -0,0 +1,253 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::err::Error;
+use serde::Serialize;
+use std::io::Write;
+
+/// Compression applied by `JsonlWriter` and the tsv and common writers. Gzip and zstd require the `compress` feature.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Compression {
+    #[default]
+    None,
+    #[cfg(feature = "compress")]
+    Gzip,
+    #[cfg(feature = "compress")]
+    Zstd,
+}
+
+impl Compression {
+    /// The extension conventionally appended to a file compressed this way (ex: `gz` for `out.jsonl.gz`)
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            #[cfg(feature = "compress")]
+            Compression::Gzip => Some("gz"),
+            #[cfg(feature = "compress")]
+            Compression::Zstd => Some("zst"),
+        }
+    }
+}
+
+/// The underlying writer of the export writers, compressing what is written to it if requested
+pub(crate) enum Sink<W: Write> {
+    Plain(W),
+    #[cfg(feature = "compress")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "compress")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Sink<W> {
+    pub(crate) fn new(writer: W, compression: Compression) -> Result<Self, Error> {
+        Ok(match compression {
+            Compression::None => Sink::Plain(writer),
+            #[cfg(feature = "compress")]
+            Compression::Gzip => Sink::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "compress")]
+            Compression::Zstd => {
+                Sink::Zstd(zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?)
+            }
+        })
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Sink::Plain(w) => w,
+            #[cfg(feature = "compress")]
+            Sink::Gzip(w) => w,
+            #[cfg(feature = "compress")]
+            Sink::Zstd(w) => w,
+        }
+    }
+
+    fn finish(self) -> Result<W, Error> {
+        Ok(match self {
+            Sink::Plain(mut w) => {
+                w.flush()?;
+                w
+            }
+            #[cfg(feature = "compress")]
+            Sink::Gzip(w) => w.finish()?,
+            #[cfg(feature = "compress")]
+            Sink::Zstd(w) => w.finish()?,
+        })
+    }
+
+    /// Completes the compressed stream without giving up the writer (for writers that are only borrowed when done).
+    /// Nothing should be written afterwards.
+    pub(crate) fn try_finish(&mut self) -> Result<(), Error> {
+        match self {
+            Sink::Plain(w) => w.flush()?,
+            #[cfg(feature = "compress")]
+            Sink::Gzip(w) => w.try_finish()?,
+            #[cfg(feature = "compress")]
+            Sink::Zstd(w) => {
+                w.do_finish()?;
+                w.get_mut().flush()?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Sink<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+/// Writes one JSON record per line. Records are serialized straight into a reused buffer (rather than into a new `String`
+/// per record) which is handed to the underlying writer, compressed if requested, in batches of about `FLUSH_THRESHOLD` bytes.
+///
+/// Call `finish` when done: it writes the last batch and completes the compressed stream. Dropping the writer instead
+/// loses any records that are still buffered.
+pub struct JsonlWriter<W: Write> {
+    sink: Sink<W>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub const FLUSH_THRESHOLD: usize = 1 << 20;
+
+    pub fn new(writer: W, compression: Compression) -> Result<Self, Error> {
+        Ok(Self {
+            sink: Sink::new(writer, compression)?,
+            buffer: Vec::with_capacity(Self::FLUSH_THRESHOLD + Self::FLUSH_THRESHOLD / 4),
+        })
+    }
+
+    pub fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.buffer, record)?;
+        self.buffer.push(b'\n');
+        if self.buffer.len() >= Self::FLUSH_THRESHOLD {
+            self.flush_buffer()?;
+        }
+        Ok(())
+    }
+
+    /// Writes any buffered records and completes the compressed stream. Returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.flush_buffer()?;
+        self.sink.finish()
+    }
+
+    fn flush_buffer(&mut self) -> Result<(), Error> {
+        self.sink.writer().write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Record {
+        index: usize,
+        name: String,
+    }
+
+    fn records() -> Vec<Record> {
+        (0..50_000)
+            .map(|index| Record {
+                index,
+                name: format!("key {}", index),
+            })
+            .collect()
+    }
+
+    fn expected() -> String {
+        records()
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap() + "\n")
+            .collect()
+    }
+
+    fn write(compression: Compression) -> Vec<u8> {
+        let mut writer = JsonlWriter::new(Vec::new(), compression).unwrap();
+        for record in records() {
+            writer.write(&record).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_jsonl_writer() {
+        let output = write(Compression::None);
+        assert!(output.len() > JsonlWriter::<Vec<u8>>::FLUSH_THRESHOLD);
+        assert_eq!(expected(), String::from_utf8(output).unwrap());
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn test_jsonl_writer_compressed() {
+        use std::io::Read;
+
+        let expected = expected();
+
+        let gzip = write(Compression::Gzip);
+        assert!(gzip.len() < expected.len());
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(&gzip[..])
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(expected, decompressed);
+
+        let zstd = write(Compression::Zstd);
+        assert!(zstd.len() < expected.len());
+        assert_eq!(
+            expected.as_bytes(),
+            &zstd::decode_all(&zstd[..]).unwrap()[..]
+        );
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn test_sink_try_finish() {
+        let expected = expected();
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let mut sink = Sink::new(Vec::new(), compression).unwrap();
+            sink.write_all(expected.as_bytes()).unwrap();
+            sink.try_finish().unwrap();
+            let compressed = match sink {
+                Sink::Gzip(w) => w.get_ref().clone(),
+                Sink::Zstd(w) => w.get_ref().clone(),
+                Sink::Plain(_) => unreachable!(),
+            };
+            let decompressed = match compression {
+                Compression::Gzip => {
+                    use std::io::Read;
+                    let mut decompressed = Vec::new();
+                    flate2::read::GzDecoder::new(&compressed[..])
+                        .read_to_end(&mut decompressed)
+                        .unwrap();
+                    decompressed
+                }
+                _ => zstd::decode_all(&compressed[..]).unwrap(),
+            };
+            assert_eq!(expected.as_bytes(), &decompressed[..]);
+        }
+    }
+}
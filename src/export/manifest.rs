@@ -0,0 +1,293 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::err::Error;
+use crate::filter::Filter;
+use crate::log::{ParseWarnings, Severity};
+use crate::parser::Parser;
+use crate::transaction_log::TransactionLogInfo;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+/// A record of how an export was made, for chain of custody and for reproducing it later: the hashes of the files that were
+/// read and written, the transaction logs that were applied, the tool version and settings, and summaries of what was
+/// exported and of the parse warnings. Works alongside any of the writers; build it once the export is written.
+#[derive(Clone, Debug, Serialize)]
+pub struct Manifest {
+    pub tool: &'static str,
+    pub version: &'static str,
+    pub created: DateTime<Utc>,
+    pub input: ManifestFile,
+    pub transaction_logs: Vec<ManifestTransactionLog>,
+    pub recover_deleted: bool,
+    /// As given by the caller (ex: the key path the filter was built from)
+    pub filter: Option<String>,
+    pub key_count: usize,
+    pub value_count: usize,
+    pub outputs: Vec<ManifestFile>,
+    pub warnings: WarningsSummary,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ManifestFile {
+    pub path: String,
+    pub size: u64,
+    /// Lowercase hex
+    pub sha256: String,
+}
+
+impl ManifestFile {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut file = File::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        let mut buffer = [0; 0x10000];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+        Ok(Self {
+            path: path.to_string_lossy().into_owned(),
+            size,
+            sha256: hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ManifestTransactionLog {
+    #[serde(flatten)]
+    pub file: ManifestFile,
+    pub primary_sequence_number: Option<u32>,
+    /// Whether any of the log's entries were applied to the hive
+    pub applied: bool,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct WarningsSummary {
+    pub total: usize,
+    pub by_severity: BTreeMap<Severity, usize>,
+    pub by_code: BTreeMap<String, usize>,
+}
+
+impl Manifest {
+    /// Describes an export of `parser`, which was built from `input` and `transaction_logs`. The key and value counts are
+    /// those `filter` returns; `filter_description` is recorded as the filter.
+    pub fn new(
+        parser: &Parser,
+        input: impl AsRef<Path>,
+        transaction_logs: &[impl AsRef<Path>],
+        filter: Option<&Filter>,
+        filter_description: Option<&str>,
+    ) -> Result<Self, Error> {
+        let (key_count, value_count) = parser.count(filter)?;
+        Self::from_export(
+            parser,
+            input,
+            transaction_logs,
+            filter_description,
+            (key_count, value_count),
+            &parser.parse_warnings(),
+        )
+    }
+
+    /// Like `new`, but with the key and value counts and the warnings gathered while the export was written (ex: by a
+    /// `KeyHandler` adding each key to `Parser::hive_warnings` with `ParseWarnings::add_key`), so the hive isn't traversed
+    /// again
+    pub fn from_export(
+        parser: &Parser,
+        input: impl AsRef<Path>,
+        transaction_logs: &[impl AsRef<Path>],
+        filter_description: Option<&str>,
+        (key_count, value_count): (usize, usize),
+        warnings: &ParseWarnings,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            tool: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            created: Utc::now(),
+            input: ManifestFile::from_path(input)?,
+            transaction_logs: transaction_logs
+                .iter()
+                .map(|log| Self::transaction_log(parser, log.as_ref()))
+                .collect::<Result<_, _>>()?,
+            recover_deleted: parser.recover_deleted,
+            filter: filter_description.map(str::to_string),
+            key_count,
+            value_count,
+            outputs: Vec::new(),
+            warnings: Self::warnings_summary(warnings),
+        })
+    }
+
+    fn transaction_log(parser: &Parser, path: &Path) -> Result<ManifestTransactionLog, Error> {
+        let file = ManifestFile::from_path(path)?;
+        // A log that can't be parsed wasn't applied, but is still recorded
+        let primary_sequence_number = TransactionLogInfo::from_path(path)
+            .ok()
+            .map(|info| info.base_block.primary_sequence_number);
+        let applied = primary_sequence_number.is_some_and(|primary_sequence_number| {
+            parser
+                .applied_transaction_logs()
+                .iter()
+                .any(|applied| applied.primary_sequence_number == primary_sequence_number)
+        });
+        Ok(ManifestTransactionLog {
+            file,
+            primary_sequence_number,
+            applied,
+        })
+    }
+
+    fn warnings_summary(warnings: &ParseWarnings) -> WarningsSummary {
+        let mut summary = WarningsSummary::default();
+        for warning in warnings {
+            summary.total += 1;
+            *summary.by_severity.entry(warning.severity).or_default() += 1;
+            *summary
+                .by_code
+                .entry(format!("{:?}", warning.code))
+                .or_default() += 1;
+        }
+        summary
+    }
+
+    /// Records an output file, with its hash. Call this once the file is completely written.
+    pub fn add_output(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, Error> {
+        self.outputs.push(ManifestFile::from_path(path)?);
+        Ok(self)
+    }
+
+    /// Writes the manifest to `path` as pretty printed json
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base_block::BaseBlockBase;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn test_manifest() {
+        let logs = ["test_data/system.log1", "test_data/system.log2"];
+        let parser = ParserBuilder::from_path("test_data/system")
+            .with_transaction_log(logs[0])
+            .with_transaction_log(logs[1])
+            .build()
+            .unwrap();
+        let mut manifest = Manifest::new(&parser, "test_data/system", &logs, None, None).unwrap();
+        manifest.add_output("test_data/system.log1").unwrap();
+
+        let (keys, values) = parser.count(None).unwrap();
+        assert_eq!((keys, values), (manifest.key_count, manifest.value_count));
+        assert_eq!(
+            std::fs::metadata("test_data/system").unwrap().len(),
+            manifest.input.size
+        );
+        assert_eq!(
+            "1510398cdd8d544ca3f9b587c9ca5486cb77fa90a8efe514d5f3e04dccb29df7",
+            manifest.input.sha256
+        );
+        assert_eq!(2, manifest.transaction_logs.len());
+        assert!(manifest.transaction_logs.iter().all(|log| log.applied));
+        assert_eq!(manifest.transaction_logs[0].file, manifest.outputs[0]);
+        assert_eq!(parser.parse_warnings().len(), manifest.warnings.total);
+
+        let mut warnings = parser.hive_warnings();
+        let (mut keys, mut values) = (0, 0);
+        for key in ParserIterator::new(&parser).iter() {
+            warnings.add_key(&key);
+            keys += 1;
+            values += key.sub_values.len();
+        }
+        let from_export = Manifest::from_export(
+            &parser,
+            "test_data/system",
+            &logs,
+            None,
+            (keys, values),
+            &warnings,
+        )
+        .unwrap();
+        assert_eq!(
+            (manifest.key_count, manifest.value_count),
+            (from_export.key_count, from_export.value_count)
+        );
+        assert_eq!(manifest.warnings, from_export.warnings);
+
+        let json = serde_json::to_value(&manifest).unwrap();
+        assert_eq!("notatin", json["tool"]);
+        assert_eq!(
+            json!(manifest.transaction_logs[0].file.sha256),
+            json["transaction_logs"][0]["sha256"]
+        );
+    }
+
+    #[test]
+    fn test_manifest_unapplied_logs() {
+        // The primary file has already been updated past the entries of the first log
+        let logs = ["test_data/system.log1", "test_data/system.log2"];
+        let mut hive = std::fs::read("test_data/system").unwrap();
+        hive[4..8].copy_from_slice(&4068u32.to_le_bytes());
+        hive[8..12].copy_from_slice(&4067u32.to_le_bytes());
+        BaseBlockBase::update_checksum(&mut hive).unwrap();
+        let mut builder = ParserBuilder::from_file(std::io::Cursor::new(hive));
+        for log in &logs {
+            builder.with_transaction_log(std::fs::File::open(log).unwrap());
+        }
+        let parser = builder.build().unwrap();
+        let manifest = Manifest::new(&parser, "test_data/system", &logs, None, None).unwrap();
+        assert_eq!(
+            vec![(Some(4064), false), (Some(4067), true)],
+            manifest
+                .transaction_logs
+                .iter()
+                .map(|log| (log.primary_sequence_number, log.applied))
+                .collect::<Vec<_>>()
+        );
+
+        // A log of another hive, which the parser wasn't given
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let manifest =
+            Manifest::new(&parser, "test_data/NTUSER.DAT", &logs[..1], None, None).unwrap();
+        assert!(!manifest.transaction_logs[0].applied);
+    }
+}
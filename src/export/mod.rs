@@ -0,0 +1,30 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Writers for exporting the keys and values of a hive (used by `reg_dump`), and readers to load exported jsonl and common
+//! export format files back into typed structures.
+//!
+//! Progress is written to the console if the parser was built with `update_console`.
+
+pub mod common_reader;
+pub mod common_writer;
+pub mod graph_writer;
+pub mod json_reader;
+pub mod json_schema;
+pub mod json_writer;
+pub mod jsonl_writer;
+pub mod manifest;
+pub mod tsv_writer;
@@ -0,0 +1,139 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{
+    err::Error,
+    export::jsonl_writer::{Compression, Sink},
+    filter::Filter,
+    parser::{Parser, ParserIterator},
+    progress,
+    record::{Record, RecordKind},
+    timestamp_format::TimestampFormat,
+    util,
+};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::*;
+
+pub struct WriteTsv {
+    index: usize,
+    recovered_only: bool,
+    timestamp_format: TimestampFormat,
+    /// Adds the Suspicious Name column; set from `ParserBuilder::detect_suspicious_names`
+    suspicious_names: bool,
+    writer: BufWriter<Sink<File>>,
+}
+
+impl WriteTsv {
+    pub fn new(output: impl AsRef<Path>, recovered_only: bool) -> Result<Self, Error> {
+        Self::new_compressed(output, recovered_only, Compression::None)
+    }
+
+    /// Like `new`, but the output is compressed with `compression`
+    pub fn new_compressed(
+        output: impl AsRef<Path>,
+        recovered_only: bool,
+        compression: Compression,
+    ) -> Result<Self, Error> {
+        let write_file = File::create(output)?;
+        let writer = BufWriter::new(Sink::new(write_file, compression)?);
+        Ok(WriteTsv {
+            index: 0,
+            recovered_only,
+            timestamp_format: TimestampFormat::default(),
+            suspicious_names: false,
+            writer,
+        })
+    }
+
+    /// Sets how timestamps are written (ISO-8601, epoch seconds, or FILETIME), and the fixed UTC offset ISO-8601
+    /// timestamps are written at. Defaults to ISO-8601 in UTC.
+    pub fn timestamp_format(&mut self, timestamp_format: TimestampFormat) -> &mut Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    pub fn write(&mut self, parser: &Parser, filter: Option<Filter>) -> Result<(), Error> {
+        self.suspicious_names = parser.state.detect_suspicious_names;
+        let mut iter = ParserIterator::new(parser);
+        if let Some(filter) = filter {
+            iter.with_filter(filter);
+        }
+        if let Some(sort) = parser.key_sort() {
+            iter.sort_keys(sort);
+        }
+        if let Some(path_mapping) = parser.path_mapping() {
+            iter.full_paths(path_mapping.clone());
+        }
+
+        let mut console = progress::new(parser.update_console);
+        write!(self.writer,"Index\tKey Path\tSubkey Count\tValue Name\tValue Data\tTimestamp\tStatus\tPrevious Seq Num\tModifying Seq Num\tFlags\tAccess Flags\tValue Type\tLogs")?;
+        if self.suspicious_names {
+            write!(self.writer, "\tSuspicious Name")?;
+        }
+        writeln!(self.writer)?;
+        for (index, record) in iter.as_records(self.timestamp_format).enumerate() {
+            console.update_progress(index)?;
+            self.write_record_tsv(&record)?;
+        }
+        parser.check_cancelled()?;
+        writeln!(self.writer, "\nLogs\n-----------")?;
+        parser.get_parse_logs().write(&mut self.writer)?;
+        self.writer.flush()?;
+        self.writer.get_mut().try_finish()
+    }
+
+    fn write_record_tsv(&mut self, record: &Record) -> Result<(), Error> {
+        if self.recovered_only && !record.recovered {
+            return Ok(());
+        }
+        let is_key = record.kind == RecordKind::Key;
+        self.index += 1;
+        write!(
+            self.writer,
+            "{index}\t{key_path}\t{subkey_count}\t{value_name}\t{value_data}\t{timestamp}\t{status:?}\t{prev_seq_num}\t{mod_seq_num}\t{flags}\t{access_flags}\t{value_type}\t{logs}",
+            index = self.index,
+            key_path = util::escape_string(&record.key_path),
+            subkey_count = record.sub_key_count.map(|count| count.to_string()).unwrap_or_default(),
+            value_name = if is_key { "".into() } else { util::escape_string(&record.name) },
+            value_data = util::escape_string(&record.data),
+            timestamp = if is_key { self.timestamp_format.format(record.last_written) } else { String::new() },
+            status = record.state,
+            prev_seq_num = Self::get_sequence_num_string(record.sequence_num),
+            mod_seq_num = Self::get_sequence_num_string(record.updated_by_sequence_num),
+            flags = record.flags,
+            access_flags = record.access_flags,
+            value_type = record.data_type,
+            logs = util::escape_string(&record.logs)
+        )?;
+        if self.suspicious_names {
+            write!(
+                self.writer,
+                "\t{}",
+                util::escape_string(&record.suspicious_name)
+            )?;
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn get_sequence_num_string(seq_num: Option<u32>) -> String {
+        match seq_num {
+            Some(seq_num) => format!("{}", seq_num),
+            _ => String::new(),
+        }
+    }
+}
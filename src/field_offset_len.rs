@@ -14,7 +14,7 @@
  * limitations under the License.
  */
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 pub(crate) trait FieldTrait<T: Default + 'static> {
     fn value(&self) -> T;
@@ -29,7 +29,7 @@ pub(crate) trait FieldTrait<T: Default + 'static> {
     fn get_field_full(&self) -> Option<&FieldFull<T>>;
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct FieldFull<T: Default> {
     pub value: T,
     pub offset: usize,
@@ -79,7 +79,7 @@ impl<T: Default> Default for FieldFull<T> {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct FieldLight<T: Default> {
     pub value: T,
 }
@@ -115,7 +115,7 @@ mod macros {
     #[macro_export]
     macro_rules! make_field_struct {
         ( @$field_type:ident, $name:ident { } -> ($($result:tt)*) ) => (
-            #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+            #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
             pub struct $name {
                 $($result)*
             }
@@ -167,6 +167,15 @@ mod macros {
                 }
 
                 paste::item! {
+                    /// Borrows the field's value rather than cloning it
+                    #[allow(dead_code)]
+                    pub fn [< $field _ref >] (&self) -> &$type {
+                        match self {
+                            Self::Light(detail) => &detail.$field.value,
+                            Self::Full(detail) => &detail.$field.value,
+                        }
+                    }
+
                     // Would love if there was some way to tell if $type was a primitive or not, and therefore whether we should generate
                     // set_field vs. set_field_full.
                     #[allow(dead_code)]
@@ -203,7 +212,7 @@ mod macros {
     ///     {class_name_prefix}Light: A struct which contains FieldLight objects for each field (value only)
     ///     {class_name_prefix}Full: A struct which contains FieldFull objects for each field (value, offset, and length)
     ///     {class_name_prefix}Enum: An enum with variants for the above two structs
-    /// Accessor and setter functions are created on {class_name_prefix}Enum for each field.
+    /// Accessor (cloning and borrowing) and setter functions are created on {class_name_prefix}Enum for each field.
     /// A `default()` function is created for {class_name_prefix}Enum which creates a default {class_name_prefix}Light variant
     #[macro_export]
     macro_rules! make_file_offset_structs {
@@ -216,7 +225,7 @@ mod macros {
                 make_field_struct! ( FieldLight, [<$class_name_prefix Light>] { $($tail)* } );
                 make_field_struct! ( FieldFull, [<$class_name_prefix Full>] { $($tail)* } );
 
-                #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+                #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
                 pub enum [<$class_name_prefix Enum>]  {
                     Light(Box<[<$class_name_prefix Light>]>),
                     Full(Box<[<$class_name_prefix Full>]>),
@@ -18,7 +18,11 @@ use crate::cell_key_node::{AccessFlags, KeyNodeFlags};
 use crate::field_offset_len::FieldTrait;
 use crate::log::Logs;
 use crate::util;
+use bitflags::{Bits, Flags};
+use serde::de::{self, Deserialize, Deserializer};
 use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt::UpperHex;
+use winstructs::guid::Guid;
 
 pub(crate) fn field_data_as_hex<S: Serializer>(
     x: &[u8],
@@ -27,6 +31,35 @@ pub(crate) fn field_data_as_hex<S: Serializer>(
     s.serialize_str(&util::to_hex_string(x))
 }
 
+pub(crate) fn field_data_from_hex<'de, D: Deserializer<'de>>(
+    d: D,
+) -> std::result::Result<Vec<u8>, D::Error> {
+    let s = String::deserialize(d)?;
+    util::from_hex_string(&s).ok_or_else(|| de::Error::custom(format!("invalid hex data: {}", s)))
+}
+
+pub(crate) fn field_guid_from_string<'de, D: Deserializer<'de>>(
+    d: D,
+) -> std::result::Result<Guid, D::Error> {
+    let s = String::deserialize(d)?;
+    util::get_guid_from_string(&s).ok_or_else(|| de::Error::custom(format!("invalid guid: {}", s)))
+}
+
+/// Serializes bitflags as a list of the names of the flags that are set, followed by any remaining bits in hex
+pub(crate) fn flag_names<F, S>(x: &F, s: S) -> std::result::Result<S::Ok, S::Error>
+where
+    F: Flags,
+    F::Bits: UpperHex,
+    S: Serializer,
+{
+    let mut names: Vec<String> = x.iter_names().map(|(name, _)| name.to_string()).collect();
+    let unknown = x.bits() & !F::all().bits();
+    if unknown != F::Bits::EMPTY {
+        names.push(format!("{:#X}", unknown));
+    }
+    s.collect_seq(names)
+}
+
 pub(crate) fn field_last_key_written_date_and_time_interpreted<S: Serializer>(
     x: &dyn FieldTrait<u64>,
     s: S,
@@ -45,10 +78,10 @@ pub(crate) fn field_key_node_flag_bits_interpreted<S: Serializer>(
     s: S,
 ) -> std::result::Result<S::Ok, S::Error> {
     let mut logs = Logs::default();
-    let flags = KeyNodeFlags::from_bits_checked(x.value(), &mut logs);
+    let flags = KeyNodeFlags::from_bits_checked_v1(x.value(), &mut logs);
     let mut ser = s.serialize_struct("key_node_flag_bits", get_field_count_with_logs(x, &logs))?;
     serialize_base_field(x, &mut ser)?;
-    ser.serialize_field("interpreted", &format!("{:?}", flags))?;
+    ser.serialize_field("interpreted", &flags.to_v1_string())?;
     if logs.has_logs() {
         ser.serialize_field("logs", &logs.get_string())?;
     }
@@ -15,7 +15,8 @@
  */
 
 use crate::err::Error;
-use std::io::{self, Read, Seek};
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::Path;
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -51,6 +52,7 @@ impl FileInfo {
     }
 }
 
+/// An input source for a primary file or transaction log. Implemented for every `Read + Seek` type.
 pub trait ReadSeek: Read + Seek {
     fn tell(&mut self) -> io::Result<u64> {
         self.stream_position()
@@ -58,3 +60,94 @@ pub trait ReadSeek: Read + Seek {
 }
 
 impl<T: Read + Seek> ReadSeek for T {}
+
+/// Restricts a reader to the `len` bytes starting at `start`, so a hive can be read in place from within a larger file
+/// (ex: a raw disk image or a volume shadow copy). Offsets are relative to `start`.
+pub struct ReadSeekRange<R: ReadSeek> {
+    inner: R,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<R: ReadSeek> ReadSeekRange<R> {
+    pub fn new(mut inner: R, start: u64, len: u64) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            inner,
+            start,
+            len,
+            pos: 0,
+        })
+    }
+}
+
+impl<R: ReadSeek> Read for ReadSeekRange<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        let max = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..max])?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: ReadSeek> Seek for ReadSeekRange<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let invalid_seek = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        };
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => add_offset(self.len, offset),
+            SeekFrom::Current(offset) => add_offset(self.pos, offset),
+        }
+        .ok_or_else(invalid_seek)?;
+        let inner_pos = self.start.checked_add(new_pos).ok_or_else(invalid_seek)?;
+        self.inner.seek(SeekFrom::Start(inner_pos))?;
+        self.pos = new_pos;
+        Ok(new_pos)
+    }
+}
+
+fn add_offset(base: u64, offset: i64) -> Option<u64> {
+    if offset < 0 {
+        base.checked_sub(offset.unsigned_abs())
+    } else {
+        base.checked_add(offset as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_seek_range() {
+        let mut range = ReadSeekRange::new(Cursor::new(b"0123456789".to_vec()), 2, 5).unwrap();
+        let mut buffer = Vec::new();
+        range.read_to_end(&mut buffer).unwrap();
+        assert_eq!(b"23456", &buffer[..]);
+
+        assert_eq!(1, range.seek(SeekFrom::End(-4)).unwrap());
+        let mut buffer = [0; 2];
+        range.read_exact(&mut buffer).unwrap();
+        assert_eq!(b"34", &buffer);
+        assert_eq!(3, range.tell().unwrap());
+        assert!(range.seek(SeekFrom::Current(-4)).is_err());
+        assert_eq!(
+            io::ErrorKind::InvalidInput,
+            range.seek(SeekFrom::Start(u64::MAX)).unwrap_err().kind()
+        );
+
+        range.seek(SeekFrom::Start(0)).unwrap();
+        let file_info = FileInfo::from_read_seek(range).unwrap();
+        assert_eq!(b"23456", &file_info.buffer[..]);
+    }
+}
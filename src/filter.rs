@@ -15,6 +15,7 @@
  */
 
 use crate::cell_key_node::CellKeyNode;
+use crate::control_set::ControlSets;
 use crate::err::Error;
 use crate::impl_serialize_for_bitflags;
 use crate::state::State;
@@ -46,19 +47,38 @@ impl Filter {
     }
 
     pub(crate) fn match_cell(&self, state: &mut State, cell: &CellKeyNode) -> FilterFlags {
-        if cell.is_key_root() {
+        self.match_path(state, cell.is_key_root(), &cell.path)
+    }
+
+    /// Checks the path of a key without needing the key itself (`is_key_root` indicates if `path` belongs to the root key)
+    pub(crate) fn check_path(
+        &self,
+        state: &mut State,
+        is_key_root: bool,
+        path: &str,
+    ) -> FilterFlags {
+        if self.is_valid() {
+            self.match_path(state, is_key_root, path)
+        } else {
+            FilterFlags::FILTER_ITERATE_KEYS
+        }
+    }
+
+    fn match_path(&self, state: &mut State, is_key_root: bool, path: &str) -> FilterFlags {
+        if is_key_root {
             if let Some(reg_query) = &self.reg_query {
                 if !reg_query.key_path_has_root {
                     return FilterFlags::FILTER_ITERATE_KEYS;
                 }
             }
         }
-        self.match_key(state, cell.lowercase())
+        self.match_key(state, path.to_ascii_lowercase())
     }
 
     fn match_key(&self, state: &mut State, key_path: String) -> FilterFlags {
         if let Some(reg_query) = &self.reg_query {
-            reg_query.check_key_match(&key_path, state.get_root_path_offset(&key_path))
+            let root_key_name_offset = state.get_root_path_offset(&key_path);
+            reg_query.check_key_match(&key_path, root_key_name_offset, state.control_sets.as_ref())
         } else {
             FilterFlags::FILTER_ITERATE_KEYS
         }
@@ -160,7 +180,13 @@ pub struct RegQuery {
 }
 
 impl RegQuery {
-    fn check_key_match(&self, key_path: &str, mut root_key_name_offset: usize) -> FilterFlags {
+    /// A `CurrentControlSet` segment of the filter matches the current control set in `control_sets`, if set
+    fn check_key_match(
+        &self,
+        key_path: &str,
+        mut root_key_name_offset: usize,
+        control_sets: Option<&ControlSets>,
+    ) -> FilterFlags {
         if self.key_path_has_root {
             root_key_name_offset = 0;
         }
@@ -172,7 +198,15 @@ impl RegQuery {
             match filter_path_segment {
                 Some(fps) => match fps {
                     RegQueryComponent::ComponentString(s) => {
-                        if s != &key_path_segment.to_ascii_lowercase() {
+                        let is_match = match control_sets {
+                            Some(control_sets)
+                                if s.eq_ignore_ascii_case(ControlSets::CURRENT_CONTROL_SET) =>
+                            {
+                                control_sets.is_current(key_path_segment)
+                            }
+                            _ => s == &key_path_segment.to_ascii_lowercase(),
+                        };
+                        if !is_match {
                             return FilterFlags::FILTER_NO_MATCH;
                         } else {
                             filter_path_segment = filter_iterator.next();
@@ -221,7 +255,7 @@ mod tests {
             .return_child_keys(true)
             .build()?;
         let mut key_node = cell_key_node::CellKeyNode {
-            path: String::from("HighContrast"),
+            path: "HighContrast".into(),
             ..Default::default()
         };
         assert_eq!(
@@ -230,14 +264,14 @@ mod tests {
             "check_cell: Same case key match failed"
         );
 
-        key_node.path = String::from("Highcontrast");
+        key_node.path = "Highcontrast".into();
         assert_eq!(
             FilterFlags::FILTER_ITERATE_KEYS | FilterFlags::FILTER_KEY_MATCH,
             filter.check_cell(&mut state, &key_node),
             "check_cell: Different case key match failed"
         );
 
-        key_node.path = String::from("badVal");
+        key_node.path = "badVal".into();
         assert_eq!(
             FilterFlags::FILTER_NO_MATCH,
             filter.check_cell(&mut state, &key_node),
@@ -0,0 +1,118 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Entry points for the cargo-fuzz targets in `fuzz/`. Each one feeds arbitrary bytes to one of the crate's internal
+//! parsers, treating the input as a hive whose hive bins start at offset 0. Malformed input must come back as an
+//! `Error` (or a log), never a panic. Enabled with the `fuzzing` feature.
+
+use crate::base_block::BaseBlock;
+use crate::cell_key_node::{CellKeyNode, CellKeyNodeReadOptions};
+use crate::cell_key_security;
+use crate::cell_key_value::CellKeyValue;
+use crate::err::Error;
+use crate::file_info::FileInfo;
+use crate::filter::Filter;
+use crate::log::Logs;
+use crate::parser::ParserIterator;
+use crate::parser_builder::ParserBuilder;
+use crate::state::State;
+use crate::transaction_log::TransactionLog;
+use std::collections::HashSet;
+use std::io::Cursor;
+
+fn file_info(data: &[u8]) -> FileInfo {
+    FileInfo {
+        hbin_offset_absolute: 0,
+        buffer: data.to_vec(),
+    }
+}
+
+/// Parses a base block (the registry file header)
+pub fn base_block(data: &[u8]) -> Result<(), Error> {
+    let (_, base_block) = BaseBlock::from_bytes(data)?;
+    base_block.capabilities();
+    Ok(())
+}
+
+/// Reads a key node at offset 0, along with its values and sub keys
+pub fn key_node(data: &[u8]) -> Result<(), Error> {
+    let file_info = file_info(data);
+    let mut state = State::default();
+    let filter = Filter::new();
+    let cell_key_node = CellKeyNode::read(
+        &file_info,
+        &mut state,
+        CellKeyNodeReadOptions {
+            offset: 0,
+            cur_path: "",
+            filter: None,
+            self_is_filter_match_or_descendent: false,
+            sequence_num: None,
+            get_deleted_and_modified: false,
+        },
+    )?;
+    if let Some(mut cell_key_node) = cell_key_node {
+        cell_key_node.read_sub_keys_internal(&file_info, &mut state, &filter, None, false, None);
+    }
+    Ok(())
+}
+
+/// Reads a key value at offset 0 and converts its data
+pub fn key_value(data: &[u8]) -> Result<(), Error> {
+    let file_info = file_info(data);
+    let mut state = State::default();
+    let (_, mut cell_key_value) = CellKeyValue::from_bytes(data, 0, None, true)?;
+    cell_key_value.read_value_bytes(&file_info, &mut state);
+    cell_key_value.get_content();
+    Ok(())
+}
+
+/// Reads the sub key list (lf, lh, li, or ri) at offset 0
+pub fn sub_key_list(data: &[u8]) -> Result<(), Error> {
+    CellKeyNode::parse_sub_key_list_items(
+        &file_info(data),
+        &mut State::default(),
+        0,
+        0,
+        &mut HashSet::new(),
+    )?;
+    Ok(())
+}
+
+/// Reads the key security list starting at offset 0
+pub fn key_security(data: &[u8]) -> Result<(), Error> {
+    cell_key_security::read_cell_key_security(data, 0, 0, &mut Logs::default())?;
+    Ok(())
+}
+
+/// Parses a transaction log
+pub fn transaction_log(data: &[u8]) -> Result<(), Error> {
+    TransactionLog::from_bytes(data)?;
+    Ok(())
+}
+
+/// Builds a parser for a whole hive, recovering deleted items, and iterates over it
+pub fn hive(data: &[u8]) -> Result<(), Error> {
+    let mut builder = ParserBuilder::from_file(Cursor::new(data.to_vec()));
+    builder.recover_deleted(true);
+    let parser = builder.build()?;
+    for key in ParserIterator::new(&parser).iter() {
+        for value in key.value_iter() {
+            value.get_content();
+        }
+    }
+    Ok(())
+}
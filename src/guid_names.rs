@@ -0,0 +1,255 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Names for the GUIDs keys and values are often named after (COM classes, known folders, shell folders), so exports of
+//! shellbag and COM related keys can be read without looking each one up (see `ParserBuilderFromPath::guid_names`).
+
+use crate::cell_key_node::CellKeyNode;
+use crate::cell_key_value::CellKeyValue;
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::plugins;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Returns the name of a known folder (`FOLDERID_*`) or common shell folder. `guid` is in uppercase, without braces.
+pub(crate) fn known_folder_name(guid: &str) -> Option<&'static str> {
+    match guid {
+        "008CA0B1-55B4-4C56-B8A8-4DE4B299D3BE" => Some("Account Pictures"),
+        "0139D44E-6AFE-49F2-8690-3DAFCAE6FFB8" => Some("Common Programs"),
+        "031E4825-7B94-4DC3-B131-E946B44C8DD5" => Some("Libraries"),
+        "0762D272-C50A-4BB0-A382-697DCD729B80" => Some("User Profiles"),
+        "0AC0837C-BBF8-452A-850D-79D08E667CA7" => Some("Computer"),
+        "18989B1D-99B5-455B-841C-AB7C74E4DDFC" => Some("Videos"),
+        "1777F761-68AD-4D8A-87BD-30B759FA33DD" => Some("Favorites"),
+        "1AC14E77-02E7-4E5D-B744-2EB1AE5198B7" => Some("System"),
+        "1B3EA5DC-B587-4786-B4EF-BD1DC332AEAE" => Some("Libraries"),
+        "208D2C60-3AEA-1069-A2D7-08002B30309D" => Some("My Network Places"),
+        "20D04FE0-3AEA-1069-A2D8-08002B30309D" => Some("My Computer"),
+        "21EC2020-3AEA-1069-A2DD-08002B30309D" => Some("Control Panel"),
+        "2400183A-6185-49FB-A2D8-4A392A602BA3" => Some("Public Videos"),
+        "26EE0668-A00A-44D7-9371-BEB064C98683" => Some("Control Panel"),
+        "2B0F765D-C0E9-4171-908E-08A611B84FF6" => Some("Cookies"),
+        "31C0DD25-9439-4F12-BF41-7FF4EDA38722" => Some("3D Objects"),
+        "3214FAB5-9757-4298-BB61-92A9DEAA44FF" => Some("Public Music"),
+        "33E28130-4E1E-4676-835A-98395C3BC3BB" => Some("Pictures"),
+        "352481E8-33BE-4251-BA85-6007CAEDCF9D" => Some("Temporary Internet Files"),
+        "374DE290-123F-4565-9164-39C4925E467B" => Some("Downloads"),
+        "3D644C9B-1FB8-4F30-9B45-F670235F79C0" => Some("Public Downloads"),
+        "3EB685DB-65F9-4CF6-A03A-E3EF65729F3D" => Some("Roaming AppData"),
+        "450D8FBA-AD25-11D0-98A8-0800361B1103" => Some("My Documents"),
+        "4BD8D571-6D19-48D3-BE97-422220080E43" => Some("Music"),
+        "4C5C32FF-BB9D-43B0-B5B4-2D72E54EAAA4" => Some("Saved Games"),
+        "52A4F021-7B75-48A9-9F6B-4B87A210BC8F" => Some("Quick Launch"),
+        "5399E694-6CE5-4D6C-8FCE-1D8870FDCBA0" => Some("Control Panel"),
+        "56784854-C6CB-462B-8169-88E350ACB882" => Some("Contacts"),
+        "59031A47-3F72-44A7-89C5-5595FE6B30EE" => Some("Users Files"),
+        "5E6C858F-0E22-4760-9AFE-EA3317B67173" => Some("User Profile"),
+        "625B53C3-AB48-4EC1-BA1F-A1EF4146FC19" => Some("Start Menu"),
+        "62AB5D82-FDC1-4DC3-A9DD-070D1D495D97" => Some("ProgramData"),
+        "6365D5A7-0F0D-45E5-87F6-0DA56B6A4F7D" => Some("Common Files (x64)"),
+        "645FF040-5081-101B-9F08-00AA002F954E" => Some("Recycle Bin"),
+        "679F85CB-0220-4080-B29B-5540CC05AAB6" => Some("Quick Access"),
+        "6D809377-6AF0-444B-8957-A3773F02200E" => Some("Program Files (x64)"),
+        "724EF170-A42D-4FEF-9F26-B60E846FBA4F" => Some("Administrative Tools"),
+        "7C5A40EF-A0FB-4BFC-874A-C0F2E0B9FA8E" => Some("Program Files (x86)"),
+        "82A5EA35-D9CD-47C5-9629-E15D2F714E6E" => Some("Common Startup"),
+        "82A74AEB-AEB4-465C-A014-D097EE346D63" => Some("Control Panel"),
+        "8983036C-27C0-404B-8F08-102D10DCFD74" => Some("SendTo"),
+        "905E63B6-C1BF-494E-B29C-65B732D3D21A" => Some("Program Files"),
+        "9274BD8D-CFD1-41C3-B35E-B13F55A758F4" => Some("Printer Shortcuts"),
+        "A4115719-D62E-491D-AA7C-E74B8BE3B067" => Some("Common Start Menu"),
+        "A520A1A4-1780-4FF6-BD18-167343C5AF16" => Some("LocalLow AppData"),
+        "A52BBA46-E9E1-435F-B3D9-28DAA648C0F6" => Some("OneDrive"),
+        "A63293E8-664E-48DB-A079-DF759E0509F7" => Some("Templates"),
+        "A77F5D77-2E2B-44C3-A6A2-ABA601054A51" => Some("Programs"),
+        "AB5FB87B-7CE2-4F83-915D-550846C9537B" => Some("Camera Roll"),
+        "AE50C081-EBD2-438A-8655-8A092E34987A" => Some("Recent Items"),
+        "B4BFCC3A-DB2C-424C-B029-7FE99A87C641" => Some("Desktop"),
+        "B6EBFB86-6907-413C-9AF7-4FC2ABF07CC5" => Some("Public Pictures"),
+        "B7534046-3ECB-4C18-BE4E-64CD4CB7D6AC" => Some("Recycle Bin"),
+        "B7BEDE81-DF94-4682-A7D8-57A52620B86F" => Some("Screenshots"),
+        "B94237E7-57AC-4347-9151-B08C6C32D1F7" => Some("Common Templates"),
+        "B97D20BB-F46A-4C97-BA10-5E3608430854" => Some("Startup"),
+        "BFB9D5E0-C6A9-404C-B2B2-AE6DB6AF4968" => Some("Links"),
+        "C4AA340D-F20F-4863-AFEF-F87EF2E6BA25" => Some("Public Desktop"),
+        "C5ABBF53-E17F-4121-8900-86626FC2C973" => Some("Network Shortcuts"),
+        "D0384E7D-BAC3-4797-8F14-CBA229B392B5" => Some("Common Administrative Tools"),
+        "D20BEEC4-5CA8-4905-AE3B-BF251EA09B53" => Some("Network"),
+        "D65231B0-B2F1-4857-A4CE-A8E7C6EA7D27" => Some("System (x86)"),
+        "D9DC8A3B-B784-432E-A781-5A1130A75963" => Some("History"),
+        "DE974D24-D9C6-4D3E-BF91-F4455120B917" => Some("Common Files (x86)"),
+        "DFDF76A2-C82A-4D63-906A-5644AC457385" => Some("Public"),
+        "ED4824AF-DCE4-45A8-81E2-FC7965083634" => Some("Public Documents"),
+        "F02C1A0D-BE21-4350-88B0-7367FC96EF3C" => Some("Network"),
+        "F1B32785-6FBA-4FCF-9D55-7B8E7F157091" => Some("Local AppData"),
+        "F38BF404-1D43-42F2-9305-67DE0B28FC23" => Some("Windows"),
+        "F3CE0F7C-4901-4ACC-8648-D5D44B04EF8F" => Some("Users Files"),
+        "F7F1ED05-9F6D-47A2-AAAE-29D317C6F066" => Some("Common Files"),
+        "FD228CB7-AE11-4AE3-864C-16F3910AB8FE" => Some("Fonts"),
+        "FDD39AD0-238F-46AF-ADB4-6C85480369C7" => Some("Documents"),
+        _ => None,
+    }
+}
+
+/// Resolves GUIDs to names: CLSIDs from the `CLSID` key of a hive's Classes tree, then the built-in table of known folders
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GuidNames {
+    // uppercase GUID, without braces, to the default value of the class's key
+    clsids: HashMap<String, String>,
+}
+
+impl GuidNames {
+    /// Where the `CLSID` key is in SOFTWARE, NTUSER.DAT, and UsrClass.dat hives
+    const CLSID_PATHS: &'static [&'static str] =
+        &["Classes\\CLSID", "Software\\Classes\\CLSID", "CLSID"];
+
+    /// Reads the names of the classes registered in `parser`'s hive. Hives without a Classes tree only get the
+    /// built-in names.
+    pub fn from_parser(parser: &mut Parser) -> Result<Self, Error> {
+        let mut guid_names = Self::default();
+        for path in Self::CLSID_PATHS {
+            for key in plugins::get_sub_keys(parser, path)? {
+                if let (Some(guid), Some(name)) = (
+                    normalize_guid(&key.key_name),
+                    plugins::get_value_string(&key, ""),
+                ) {
+                    if !name.is_empty() {
+                        guid_names.clsids.entry(guid).or_insert(name);
+                    }
+                }
+            }
+        }
+        Ok(guid_names)
+    }
+
+    /// Returns the name of `guid`, which may be in braces (ex: `{20D04FE0-3AEA-1069-A2D8-08002B30309D}`). Returns None if
+    /// `guid` isn't a GUID or its name isn't known.
+    pub fn name(&self, guid: &str) -> Option<&str> {
+        let guid = normalize_guid(guid)?;
+        self.clsids
+            .get(&guid)
+            .map(String::as_str)
+            .or_else(|| known_folder_name(&guid))
+    }
+
+    /// Sets `guid_name` on `key`, its values, and their earlier versions, when they're named after a GUID with a known name
+    pub fn annotate(&self, key: &mut CellKeyNode) {
+        key.guid_name = self.name(&key.key_name).map(str::to_string);
+        if key
+            .sub_values
+            .iter()
+            .any(|value| self.name(&value.detail.value_name()).is_some())
+        {
+            for value in Arc::make_mut(&mut key.sub_values) {
+                self.annotate_value(value);
+            }
+        }
+        for version in &mut key.versions {
+            self.annotate(version);
+        }
+    }
+
+    fn annotate_value(&self, value: &mut CellKeyValue) {
+        value.guid_name = self.name(&value.detail.value_name()).map(str::to_string);
+        for version in &mut value.versions {
+            self.annotate_value(version);
+        }
+    }
+}
+
+/// Returns `s` in uppercase without braces if it's a GUID (ex: `{374de290-123f-4565-9164-39c4925e467b}`)
+fn normalize_guid(s: &str) -> Option<String> {
+    let guid = s
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .unwrap_or(s);
+    let is_guid = guid.len() == 36
+        && guid.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        });
+    if is_guid {
+        Some(guid.to_ascii_uppercase())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_guid_names() {
+        let mut guid_names = GuidNames::default();
+        guid_names.clsids.insert(
+            "0002DF01-0000-0000-C000-000000000046".to_string(),
+            "Internet Explorer(Ver 1.0)".to_string(),
+        );
+        assert_eq!(
+            Some("Internet Explorer(Ver 1.0)"),
+            guid_names.name("{0002df01-0000-0000-c000-000000000046}")
+        );
+        assert_eq!(
+            Some("Downloads"),
+            guid_names.name("374DE290-123F-4565-9164-39C4925E467B")
+        );
+        assert_eq!(
+            None,
+            guid_names.name("{00000000-0000-0000-0000-000000000000}")
+        );
+        assert_eq!(
+            None,
+            guid_names.name("{374DE290-123F-4565-9164-39C4925E467B")
+        );
+        assert_eq!(None, guid_names.name("Downloads"));
+    }
+
+    #[test]
+    fn test_annotate() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .guid_names(true)
+            .build()
+            .unwrap();
+        let guid_names = parser.guid_names().unwrap();
+        let mut downloads = 0;
+        for key in ParserIterator::new(&parser).iter() {
+            assert_eq!(
+                guid_names.name(&key.key_name).map(str::to_string),
+                key.guid_name
+            );
+            for value in key.value_iter() {
+                assert_eq!(
+                    guid_names
+                        .name(&value.get_pretty_name())
+                        .map(str::to_string),
+                    value.guid_name
+                );
+                if value
+                    .get_pretty_name()
+                    .eq_ignore_ascii_case("{374DE290-123F-4565-9164-39C4925E467B}")
+                {
+                    assert_eq!(Some("Downloads"), value.guid_name.as_deref());
+                    downloads += 1;
+                }
+            }
+        }
+        assert!(downloads > 0);
+    }
+}
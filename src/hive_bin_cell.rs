@@ -19,6 +19,14 @@ use core::fmt::Debug;
 pub trait CellSubKeyList {
     fn size(&self) -> u32;
     fn get_offset_list(&self, hbin_offset_absolute: u32) -> Vec<u32>;
+
+    /// Like `get_offset_list`, but each offset is paired with the name hash stored for it by list types which have them (lh)
+    fn get_offset_and_name_hash_list(&self, hbin_offset_absolute: u32) -> Vec<(u32, Option<u32>)> {
+        self.get_offset_list(hbin_offset_absolute)
+            .into_iter()
+            .map(|offset| (offset, None))
+            .collect()
+    }
 }
 
 impl Debug for dyn CellSubKeyList {
@@ -6,9 +6,9 @@ use nom::{
     number::complete::{le_u32, le_u64},
     IResult,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct HiveBinHeader {
     /// The absolute offset of the hive bin, calculated at parse time
     pub file_offset_absolute: usize,
@@ -27,6 +27,11 @@ pub struct HiveBinHeader {
 }
 
 impl HiveBinHeader {
+    /// Size of the header at the start of each hive bin; the hive bin's cells follow it
+    pub(crate) const SIZE: usize = 32;
+    /// Hive bin sizes are multiples of this
+    pub(crate) const ALIGNMENT: usize = 4096;
+
     pub(crate) fn from_bytes<'a>(file_info: &FileInfo, input: &'a [u8]) -> IResult<&'a [u8], Self> {
         let file_offset_absolute = file_info.get_file_offset(input);
         let (input, _signature) = tag("hbin")(input)?;
@@ -51,9 +56,115 @@ impl HiveBinHeader {
     }
 }
 
+/// A cell within a hive bin
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct HiveBinCell {
+    pub file_offset_absolute: usize,
+    /// Size of the cell in bytes, including its size field
+    pub size: u32,
+    pub is_allocated: bool,
+}
+
+impl HiveBinCell {
+    /// Cell sizes are multiples of this, so it's also the size of the smallest cell
+    pub(crate) const ALIGNMENT: usize = 8;
+}
+
+/// A hive bin's header and the layout of its cells, as returned by `Parser::hive_bins`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct HiveBin {
+    pub header: HiveBinHeader,
+    /// The hive bin's cells in file order. If a cell's size is invalid, it and the rest of the hive bin are left out.
+    pub cells: Vec<HiveBinCell>,
+}
+
+impl HiveBin {
+    /// Total size of the allocated cells, in bytes
+    pub fn allocated_size(&self) -> u64 {
+        self.cells_size(true)
+    }
+
+    /// Total size of the free cells, in bytes
+    pub fn free_size(&self) -> u64 {
+        self.cells_size(false)
+    }
+
+    fn cells_size(&self, is_allocated: bool) -> u64 {
+        self.cells
+            .iter()
+            .filter(|cell| cell.is_allocated == is_allocated)
+            .map(|cell| cell.size as u64)
+            .sum()
+    }
+}
+
+/// Iterator over the hive bins of a hive (see `Parser::hive_bins`)
+pub struct HiveBins<'a> {
+    file_info: &'a FileInfo,
+    /// Absolute offset of the next hive bin
+    offset: usize,
+    /// Absolute offset of the end of the hive bins
+    end: usize,
+}
+
+impl<'a> HiveBins<'a> {
+    pub(crate) fn new(file_info: &'a FileInfo, hive_bins_data_size: usize) -> Self {
+        let offset = file_info.hbin_offset_absolute;
+        Self {
+            file_info,
+            offset,
+            end: std::cmp::min(offset + hive_bins_data_size, file_info.buffer.len()),
+        }
+    }
+
+    fn read_cells(&self, hive_bin_end: usize) -> Vec<HiveBinCell> {
+        let mut cells = Vec::new();
+        let mut offset = self.offset + HiveBinHeader::SIZE;
+        while let Some(size) = util::read_u32_le(&self.file_info.buffer, offset) {
+            let size = size as i32;
+            let size_abs = size.unsigned_abs() as usize;
+            if size_abs < HiveBinCell::ALIGNMENT || offset + size_abs > hive_bin_end {
+                break;
+            }
+            cells.push(HiveBinCell {
+                file_offset_absolute: offset,
+                size: size_abs as u32,
+                is_allocated: size < 0,
+            });
+            offset += size_abs;
+            if offset >= hive_bin_end {
+                break;
+            }
+        }
+        cells
+    }
+}
+
+impl Iterator for HiveBins<'_> {
+    type Item = HiveBin;
+
+    /// Returns None once a hive bin's header is missing or its size is invalid (see `Parser::validate` for details)
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, header) = self
+            .file_info
+            .buffer
+            .get(self.offset..self.end)
+            .and_then(|input| HiveBinHeader::from_bytes(self.file_info, input).ok())?;
+        let size = header.size as usize;
+        if size < HiveBinHeader::SIZE || self.offset + size > self.end {
+            self.offset = self.end;
+            return None;
+        }
+        let cells = self.read_cells(self.offset + size);
+        self.offset += size;
+        Some(HiveBin { header, cells })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser_builder::ParserBuilder;
 
     #[test]
     fn test_parse_hive_bin_header() {
@@ -85,4 +196,45 @@ mod tests {
 
         assert_eq!(expected, ret);
     }
+
+    #[test]
+    fn test_hive_bins() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let hive_bins: Vec<HiveBin> = parser.hive_bins().collect();
+        let (_, hive_bins_data_size) = parser.get_base_block_info();
+        assert_eq!(
+            hive_bins_data_size as u64,
+            hive_bins
+                .iter()
+                .map(|hbin| hbin.header.size as u64)
+                .sum::<u64>()
+        );
+
+        let first = &hive_bins[0];
+        assert_eq!(4096, first.header.file_offset_absolute);
+        assert_eq!(0, first.header.offset_from_first_hbin);
+        assert_eq!(
+            first.header.file_offset_absolute + 32,
+            first.cells[0].file_offset_absolute
+        );
+        for hbin in &hive_bins {
+            assert_eq!(
+                hbin.header.size as u64 - 32,
+                hbin.allocated_size() + hbin.free_size()
+            );
+            assert_eq!(
+                hbin.header.file_offset_absolute,
+                parser.get_file_info().hbin_offset_absolute
+                    + hbin.header.offset_from_first_hbin as usize
+            );
+        }
+        let allocated_cells = hive_bins
+            .iter()
+            .flat_map(|hbin| hbin.cells.iter())
+            .filter(|cell| cell.is_allocated)
+            .count();
+        assert!(allocated_cells > 2853 + 5523);
+    }
 }
@@ -0,0 +1,236 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell_key_node::CellKeyNode;
+use crate::cell_key_value::CellKeyValue;
+use crate::err::Error;
+use crate::filter::Filter;
+use crate::parser::{Parser, ParserIterator};
+use crate::progress::UpdateProgressTrait;
+use blake3::Hash;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The keys and values that differ between a base hive and a comparison hive.
+/// Keys and values are matched by path (and value name); they are modified if their hashes differ.
+#[derive(Clone, Debug, Default)]
+pub struct HiveDiff {
+    pub keys_deleted: Vec<CellKeyNode>,
+    pub keys_added: Vec<CellKeyNode>,
+    /// (base key, comparison key)
+    pub keys_modified: Vec<(CellKeyNode, CellKeyNode)>,
+    /// (key path, base value)
+    pub values_deleted: Vec<(String, CellKeyValue)>,
+    /// (key path, comparison value)
+    pub values_added: Vec<(String, CellKeyValue)>,
+    /// (key path, base value, comparison value)
+    pub values_modified: Vec<(String, CellKeyValue, CellKeyValue)>,
+}
+
+impl HiveDiff {
+    /// Compares the keys and values of `base` and `comparison` matching `filter`.
+    /// Progress messages are written to `console`.
+    pub fn compare(
+        base: &mut Parser,
+        comparison: &Parser,
+        filter: Option<&Filter>,
+        console: &mut dyn UpdateProgressTrait,
+    ) -> Result<Self, Error> {
+        let mut original_map: HashMap<(Arc<str>, Option<String>), Option<Hash>> = HashMap::new();
+
+        let (k_total, _) = base.count_all_keys_and_values(filter);
+        let mut k_added = 0;
+        let mut iter = ParserIterator::new(base);
+        if let Some(f) = filter {
+            iter.with_filter(f.clone());
+        }
+        for key in iter.iter() {
+            let path = &key.path;
+            original_map.insert((path.clone(), None), key.hash);
+            for value in key.value_iter() {
+                original_map.insert((path.clone(), Some(value.detail.value_name())), value.hash);
+            }
+            k_added += 1;
+            if k_added % 1000 == 0 {
+                Self::update_parsed_keys(console, k_added, k_total)?;
+            }
+        }
+        Self::update_parsed_keys(console, k_added, k_total)?;
+
+        let mut diff = Self::default();
+
+        // For each item in comparison, see if it's in original_map
+        //   If missing, it's new
+        //   If present, compare the hash
+        //     If same, it's a match (ignore it)
+        //     If different, it's an update
+        let (k_total, _) = comparison.count_all_keys_and_values(filter);
+        let mut k_added = 0;
+        let mut iter = ParserIterator::new(comparison);
+        if let Some(f) = filter {
+            iter.with_filter(f.clone());
+        }
+        for key in iter.iter() {
+            let path = &key.path;
+
+            match original_map.remove(&(path.clone(), None)) {
+                Some(val) => {
+                    if val != key.hash {
+                        if let Some(original_key) = base.get_key(path, true)? {
+                            diff.keys_modified.push((original_key, key.clone()));
+                        }
+                    }
+                }
+                None => diff.keys_added.push(key.clone()),
+            }
+
+            for value in key.value_iter() {
+                let value_name = value.detail.value_name();
+                match original_map.remove(&(path.clone(), Some(value_name.clone()))) {
+                    Some(val) => {
+                        if val != value.hash {
+                            if let Some(original_value) = base
+                                .get_key(path, true)?
+                                .and_then(|original_key| original_key.get_value(&value_name))
+                            {
+                                diff.values_modified.push((
+                                    path.to_string(),
+                                    original_value,
+                                    value,
+                                ));
+                            }
+                        }
+                    }
+                    None => diff.values_added.push((path.to_string(), value)),
+                }
+            }
+            k_added += 1;
+            if k_added % 100 == 0 {
+                Self::update_keys_compared(console, k_added, k_total)?;
+            }
+        }
+        Self::update_keys_compared(console, k_added, k_total)?;
+
+        // Any items remaining in original_map were deleted (not present in comparison)
+        for ((path, value_name), _) in original_map {
+            if let Some(original_key) = base.get_key(&path, true)? {
+                match value_name {
+                    None => diff.keys_deleted.push(original_key),
+                    Some(value_name) => {
+                        if let Some(original_value) = original_key.get_value(&value_name) {
+                            diff.values_deleted
+                                .push((original_key.path.to_string(), original_value))
+                        }
+                    }
+                }
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Returns the number of keys and values that were deleted, added, or modified
+    pub fn total_changes(&self) -> usize {
+        self.keys_deleted.len()
+            + self.keys_added.len()
+            + self.keys_modified.len()
+            + self.values_deleted.len()
+            + self.values_added.len()
+            + self.values_modified.len()
+    }
+
+    fn update_parsed_keys(
+        console: &mut dyn UpdateProgressTrait,
+        k_added: usize,
+        k_total: usize,
+    ) -> Result<(), Error> {
+        console.write(&format!("{}/{} keys parsed from base\n", k_added, k_total))
+    }
+
+    fn update_keys_compared(
+        console: &mut dyn UpdateProgressTrait,
+        k_added: usize,
+        k_total: usize,
+    ) -> Result<(), Error> {
+        console.write(&format!("{}/{} keys compared\n", k_added, k_total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_value::CellValue;
+    use crate::filter::FilterBuilder;
+    use crate::parser_builder::ParserBuilder;
+    use crate::progress;
+
+    #[test]
+    fn test_compare_same_hive() {
+        let mut base = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let comparison = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let diff =
+            HiveDiff::compare(&mut base, &comparison, None, &mut *progress::new(false)).unwrap();
+        assert_eq!(0, diff.total_changes());
+    }
+
+    #[test]
+    fn test_compare_with_transaction_logs() {
+        let mut base = ParserBuilder::from_path("test_data/system")
+            .build()
+            .unwrap();
+        let comparison = ParserBuilder::from_path("test_data/system")
+            .with_transaction_log("test_data/system.log1")
+            .with_transaction_log("test_data/system.log2")
+            .build()
+            .unwrap();
+        let filter = FilterBuilder::new()
+            .add_key_path("RegistryTest")
+            .return_child_keys(true)
+            .build()
+            .unwrap();
+        let diff = HiveDiff::compare(
+            &mut base,
+            &comparison,
+            Some(&filter),
+            &mut *progress::new(false),
+        )
+        .unwrap();
+        assert_eq!(4, diff.total_changes());
+        assert_eq!(1, diff.keys_deleted.len());
+        assert_eq!(
+            "\\ROOT\\RegistryTest\\New Sub Key",
+            &*diff.keys_deleted[0].path
+        );
+        assert_eq!(1, diff.keys_modified.len());
+        assert_eq!("\\ROOT\\RegistryTest", &*diff.keys_modified[0].1.path);
+        assert_eq!(1, diff.values_deleted.len());
+        assert_eq!("key1", diff.values_deleted[0].1.detail.value_name());
+        assert_eq!(1, diff.values_modified.len());
+        let (path, base_value, comparison_value) = &diff.values_modified[0];
+        assert_eq!("\\ROOT\\RegistryTest", path);
+        assert_eq!(
+            CellValue::String("Multibyte character 𐐷".to_string()),
+            base_value.get_content().0
+        );
+        assert_eq!(
+            CellValue::String("Multibyte character 𐐷 - modified".to_string()),
+            comparison_value.get_content().0
+        );
+    }
+}
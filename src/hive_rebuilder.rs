@@ -0,0 +1,403 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Editing of hives (requires the `rebuild` feature). The source hive is never modified; edits are saved as a new
+//! primary file with updated sequence numbers and checksum.
+//!
+//! `HiveRebuilder` loads the allocated keys and values of a hive and applies structural edits (adding and deleting keys
+//! and values) in memory. The saved hive is rebuilt from scratch by `writer::HiveWriter`, so cell offsets change and
+//! deleted keys, deleted values, slack space, and cells that couldn't be parsed aren't carried over.
+//!
+//! `HivePatcher` instead patches bytes in place in a copy of the hive, for repairing corrupted cells: everything it
+//! doesn't touch, including deleted cells and slack, is kept as is.
+//!
+//! ```no_run
+//! use notatin::cell_key_value::CellKeyValueDataTypes;
+//! use notatin::hive_rebuilder::HiveRebuilder;
+//! use notatin::parser_builder::ParserBuilder;
+//! use notatin::writer::WriterValue;
+//!
+//! let mut parser = ParserBuilder::from_path("NTUSER.DAT").build().unwrap();
+//! let mut hive = HiveRebuilder::from_parser(&mut parser).unwrap();
+//! hive.create_key("Software\\Microsoft\\Windows\\CurrentVersion\\Run");
+//! hive.set_value(
+//!     "Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+//!     WriterValue::new("Updater", CellKeyValueDataTypes::REG_SZ, "C:\\evil.exe".encode_utf16().flat_map(u16::to_le_bytes).collect()),
+//! ).unwrap();
+//! hive.save("NTUSER.modified.DAT").unwrap();
+//! ```
+
+use crate::base_block::BaseBlockBase;
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::parser_builder::ParserBuilder;
+use crate::util;
+use crate::writer::{HiveWriter, WriterKey, WriterValue};
+use chrono::Utc;
+use std::fs::OpenOptions;
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+/// Edits the keys and values of a hive, then writes them out as a new hive (see the module documentation)
+#[derive(Clone, Debug)]
+pub struct HiveRebuilder {
+    writer: HiveWriter,
+}
+
+impl HiveRebuilder {
+    /// Loads the allocated keys and values of the hive opened by `parser`
+    pub fn from_parser(parser: &mut Parser) -> Result<Self, Error> {
+        Ok(Self {
+            writer: HiveWriter::from_parser(parser)?,
+        })
+    }
+
+    pub fn root(&self) -> &WriterKey {
+        &self.writer.root
+    }
+
+    /// Returns the key at `key_path` (not including the root key's name)
+    pub fn get_key(&self, key_path: &str) -> Option<&WriterKey> {
+        self.writer.root.get_sub_key(key_path)
+    }
+
+    /// Returns the key at `key_path` (not including the root key's name), adding any missing keys along the way
+    pub fn create_key(&mut self, key_path: &str) -> &mut WriterKey {
+        if self.writer.root.get_sub_key(key_path).is_none() {
+            // The deepest existing key is the one gaining a sub key
+            let names: Vec<&str> = key_path
+                .split('\\')
+                .filter(|name| !name.is_empty())
+                .collect();
+            let existing_path = (0..names.len())
+                .rev()
+                .map(|len| names[..len].join("\\"))
+                .find(|path| self.writer.root.get_sub_key(path).is_some())
+                .unwrap_or_default();
+            if let Some(existing) = self.writer.root.get_sub_key_mut(&existing_path) {
+                Self::touch(existing);
+            }
+        }
+        self.writer.root.get_or_add_sub_key(key_path)
+    }
+
+    /// Deletes the key at `key_path` and its descendents. Returns the deleted key.
+    pub fn delete_key(&mut self, key_path: &str) -> Result<WriterKey, Error> {
+        let (parent_path, name) = Self::split_path(key_path);
+        if name.is_empty() {
+            return Err(Error::Any {
+                detail: "delete_key: the root key can't be deleted".to_string(),
+            });
+        }
+        let parent = self.get_key_mut(parent_path)?;
+        let deleted = parent
+            .remove_sub_key(name)
+            .ok_or_else(|| Self::not_found(key_path))?;
+        Self::touch(parent);
+        Ok(deleted)
+    }
+
+    /// Sets `value` in the existing key at `key_path`, replacing any value with the same name
+    pub fn set_value(&mut self, key_path: &str, value: WriterValue) -> Result<(), Error> {
+        Self::touch(self.get_key_mut(key_path)?.set_value(value));
+        Ok(())
+    }
+
+    /// Deletes the value named `name` from the key at `key_path`. Returns the deleted value.
+    pub fn delete_value(&mut self, key_path: &str, name: &str) -> Result<WriterValue, Error> {
+        let key = self.get_key_mut(key_path)?;
+        let deleted = key.remove_value(name).ok_or_else(|| Error::Any {
+            detail: format!("delete_value: value {} not found in {}", name, key_path),
+        })?;
+        Self::touch(key);
+        Ok(deleted)
+    }
+
+    /// Returns the contents of the edited hive. Its sequence numbers are one past the source hive's primary sequence number.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut writer = self.writer.clone();
+        writer.sequence_number = writer.sequence_number.wrapping_add(1);
+        writer.last_modification_date_and_time = Utc::now();
+        writer.to_bytes()
+    }
+
+    /// Writes the edited hive to `out_path`. Fails if `out_path` already exists, so the source hive can't be overwritten.
+    pub fn save(&self, out_path: impl AsRef<Path>) -> Result<(), Error> {
+        save_new(out_path, &self.to_bytes()?)
+    }
+
+    /// Returns a parser for the edited hive
+    pub fn to_parser(&self) -> Result<Parser, Error> {
+        ParserBuilder::from_file(Cursor::new(self.to_bytes()?)).build()
+    }
+
+    fn get_key_mut(&mut self, key_path: &str) -> Result<&mut WriterKey, Error> {
+        self.writer
+            .root
+            .get_sub_key_mut(key_path)
+            .ok_or_else(|| Self::not_found(key_path))
+    }
+
+    /// Returns the parent path and name of the key at `key_path`
+    fn split_path(key_path: &str) -> (&str, &str) {
+        let key_path = key_path.trim_matches('\\');
+        match key_path.rfind('\\') {
+            Some(index) => (&key_path[..index], &key_path[index + 1..]),
+            None => ("", key_path),
+        }
+    }
+
+    /// Updates the last written time, as Windows does when a key's values or sub keys change
+    fn touch(key: &mut WriterKey) {
+        key.last_key_written_date_and_time = Utc::now();
+    }
+
+    fn not_found(key_path: &str) -> Error {
+        Error::Any {
+            detail: format!("key {} not found", key_path),
+        }
+    }
+}
+
+/// Patches bytes in place in a copy of a hive, such as a corrupted cell's signature, size, or offsets (see the module
+/// documentation). Offsets are absolute file offsets, as in `CellKeyNode::file_offset_absolute`.
+#[derive(Clone, Debug)]
+pub struct HivePatcher {
+    bytes: Vec<u8>,
+    hbin_offset_absolute: usize,
+}
+
+impl HivePatcher {
+    /// The primary sequence number is followed by the secondary sequence number and the last written time
+    const PRIMARY_SEQUENCE_NUMBER_OFFSET: usize = 4;
+
+    /// Copies the hive opened by `parser`, with any transaction logs already applied
+    pub fn from_parser(parser: &Parser) -> Self {
+        Self {
+            bytes: parser.file_info.buffer.clone(),
+            hbin_offset_absolute: parser.file_info.hbin_offset_absolute,
+        }
+    }
+
+    /// Returns the current bytes at `offset`
+    pub fn read_bytes(&self, offset: usize, len: usize) -> Result<&[u8], Error> {
+        self.bytes
+            .get(offset..offset.saturating_add(len))
+            .ok_or_else(|| Error::buffer("HivePatcher::read_bytes"))
+    }
+
+    /// Overwrites the bytes at `offset` with `bytes`. Only the hive bins can be patched; the base block is updated by
+    /// `to_bytes`.
+    pub fn write_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Error> {
+        if offset < self.hbin_offset_absolute {
+            return Err(Error::Any {
+                detail: format!(
+                    "write_bytes: offset {} is in the base block, before the hive bins at {}",
+                    offset, self.hbin_offset_absolute
+                ),
+            });
+        }
+        self.bytes
+            .get_mut(offset..offset.saturating_add(bytes.len()))
+            .ok_or_else(|| Error::buffer("HivePatcher::write_bytes"))?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Sets the size of the cell at `offset`: allocated cells are stored with a negative size, free cells with a positive one
+    pub fn set_cell_size(
+        &mut self,
+        offset: usize,
+        size: u32,
+        allocated: bool,
+    ) -> Result<(), Error> {
+        let size = size as i32;
+        let size = if allocated { -size } else { size };
+        self.write_bytes(offset, &size.to_le_bytes())
+    }
+
+    /// Returns the contents of the patched hive. Its sequence numbers are one past the source hive's primary sequence
+    /// number, and its last written time is now.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = self.bytes.clone();
+        let sequence_number = util::read_u32_le(&bytes, Self::PRIMARY_SEQUENCE_NUMBER_OFFSET)
+            .ok_or_else(|| Error::buffer("HivePatcher::to_bytes"))?
+            .wrapping_add(1);
+        let mut header = sequence_number.to_le_bytes().to_vec();
+        header.extend_from_slice(&sequence_number.to_le_bytes());
+        header.extend_from_slice(&util::get_filetime_from_date_time(Utc::now()).to_le_bytes());
+        bytes
+            .get_mut(
+                Self::PRIMARY_SEQUENCE_NUMBER_OFFSET
+                    ..Self::PRIMARY_SEQUENCE_NUMBER_OFFSET + header.len(),
+            )
+            .ok_or_else(|| Error::buffer("HivePatcher::to_bytes"))?
+            .copy_from_slice(&header);
+        BaseBlockBase::update_checksum(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Writes the patched hive to `out_path`. Fails if `out_path` already exists, so the source hive can't be overwritten.
+    pub fn save(&self, out_path: impl AsRef<Path>) -> Result<(), Error> {
+        save_new(out_path, &self.to_bytes()?)
+    }
+
+    /// Returns a parser for the patched hive
+    pub fn to_parser(&self) -> Result<Parser, Error> {
+        ParserBuilder::from_file(Cursor::new(self.to_bytes()?)).build()
+    }
+}
+
+fn save_new(out_path: impl AsRef<Path>, bytes: &[u8]) -> Result<(), Error> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(out_path)?
+        .write_all(bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::cell_value::CellValue;
+
+    const MOUSE_KEYS: &str = "Control Panel\\Accessibility\\MouseKeys";
+
+    #[test]
+    fn test_edit_hive() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let mut hive = HiveRebuilder::from_parser(&mut parser).unwrap();
+
+        let run = "Software\\Microsoft\\Windows\\CurrentVersion\\Run\\Notatin";
+        hive.create_key(run);
+        hive.set_value(
+            run,
+            WriterValue::from_content(
+                "Updater",
+                CellKeyValueDataTypes::REG_SZ,
+                &CellValue::String("C:\\updater.exe".to_string()),
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            "MaximumSpeed",
+            hive.delete_value(MOUSE_KEYS, "maximumspeed").unwrap().name
+        );
+        assert!(hive.delete_value(MOUSE_KEYS, "MaximumSpeed").is_err());
+        hive.delete_key("Control Panel\\Accessibility\\On").unwrap();
+        assert!(hive.delete_key("Does not exist").is_err());
+        assert!(hive.delete_key("").is_err());
+        assert!(hive
+            .set_value("Does not exist", WriterValue::default())
+            .is_err());
+
+        let mut edited = hive.to_parser().unwrap();
+        let base_block = &edited.base_block.as_ref().unwrap().base;
+        let original_base_block = &parser.base_block.as_ref().unwrap().base;
+        assert_eq!(
+            original_base_block.primary_sequence_number + 1,
+            base_block.primary_sequence_number
+        );
+        assert_eq!(
+            base_block.primary_sequence_number,
+            base_block.secondary_sequence_number
+        );
+        assert_eq!((2853, 5521), edited.count_all_keys_and_values(None));
+
+        let key = edited.get_key(run, false).unwrap().unwrap();
+        assert_eq!(
+            CellValue::String("C:\\updater.exe".to_string()),
+            key.get_value("Updater").unwrap().get_content().0
+        );
+        let key = edited.get_key(MOUSE_KEYS, false).unwrap().unwrap();
+        assert!(key.get_value("MaximumSpeed").is_none());
+        assert!(edited
+            .get_key("Control Panel\\Accessibility\\On", false)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_save_does_not_overwrite() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"original").unwrap();
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let hive = HiveRebuilder::from_parser(&mut parser).unwrap();
+        assert!(hive.save(file.path()).is_err());
+        assert!(HivePatcher::from_parser(&parser).save(file.path()).is_err());
+        assert_eq!(b"original".to_vec(), std::fs::read(file.path()).unwrap());
+    }
+
+    #[test]
+    fn test_patch_hive() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let key = parser.get_key(MOUSE_KEYS, false).unwrap().unwrap();
+        let offset = key.file_offset_absolute;
+
+        // Break the key's signature, so it and its descendents can't be read
+        let mut hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        hive[offset + 4..offset + 6].copy_from_slice(b"xx");
+        let mut corrupted = ParserBuilder::from_file(Cursor::new(hive)).build().unwrap();
+        assert!(corrupted.get_key(MOUSE_KEYS, false).unwrap().is_none());
+
+        let mut patcher = HivePatcher::from_parser(&corrupted);
+        assert_eq!(b"xx", patcher.read_bytes(offset + 4, 2).unwrap());
+        patcher.write_bytes(offset + 4, b"nk").unwrap();
+        assert!(patcher.write_bytes(0, b"regf").is_err());
+        assert!(patcher.write_bytes(usize::MAX - 1, b"nk").is_err());
+        let bytes = patcher.to_bytes().unwrap();
+        assert!(BaseBlockBase::verify_checksum(&bytes).unwrap());
+
+        let mut patched = patcher.to_parser().unwrap();
+        let base_block = &patched.base_block.as_ref().unwrap().base;
+        let original_base_block = &parser.base_block.as_ref().unwrap().base;
+        assert_eq!(
+            original_base_block.primary_sequence_number + 1,
+            base_block.primary_sequence_number
+        );
+        assert_eq!(
+            base_block.primary_sequence_number,
+            base_block.secondary_sequence_number
+        );
+        assert_eq!(
+            parser.count_all_keys_and_values(None),
+            patched.count_all_keys_and_values(None)
+        );
+        let patched_key = patched.get_key(MOUSE_KEYS, false).unwrap().unwrap();
+        assert_eq!(offset, patched_key.file_offset_absolute);
+        assert_eq!(key.value_iter().count(), patched_key.value_iter().count());
+
+        let size = key.get_cell_size() as u32;
+        patcher.set_cell_size(offset, size, false).unwrap();
+        assert_eq!(
+            &(size as i32).to_le_bytes(),
+            patcher.read_bytes(offset, 4).unwrap()
+        );
+        patcher.set_cell_size(offset, size, true).unwrap();
+        assert_eq!(
+            &(-(size as i32)).to_le_bytes(),
+            patcher.read_bytes(offset, 4).unwrap()
+        );
+    }
+}
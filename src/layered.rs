@@ -0,0 +1,255 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Merges differencing hives, such as the ones Windows containers and App-V layer over a base hive, into a composite view.
+//!
+//! Each key of a differencing hive is combined with the key at the same path below it according to its layered key bit
+//! fields (see `CellKeyNode::layered_key`): tombstones delete the key, supersede-tree keys replace the key and its sub keys,
+//! supersede-local keys replace the key but keep its sub keys, and other keys are merged value by value.
+//!
+//! ```no_run
+//! use notatin::layered;
+//! use notatin::parser_builder::ParserBuilder;
+//!
+//! let mut base = ParserBuilder::from_path("SOFTWARE").build().unwrap();
+//! let mut differencing = ParserBuilder::from_path("SOFTWARE_LAYER").build().unwrap();
+//! layered::merge(&mut base, &mut differencing)
+//!     .unwrap()
+//!     .write("SOFTWARE_MERGED")
+//!     .unwrap();
+//! ```
+
+use crate::cell_key_node::{CellKeyNode, LayerSemantics};
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::writer::{HiveWriter, WriterKey};
+use std::collections::HashSet;
+
+/// Returns the keys of `base` with the keys of `differencing` layered over them, ready to be queried (through
+/// `HiveWriter::root`) or written out. Keys of a differencing hive whose format doesn't support layered keys are all merged.
+pub fn merge(base: &mut Parser, differencing: &mut Parser) -> Result<HiveWriter, Error> {
+    let mut writer = HiveWriter::from_parser(base)?;
+    merge_into(&mut writer.root, differencing)?;
+    Ok(writer)
+}
+
+/// Layers the keys of `differencing` over `merged` (the root of a key tree, such as the result of a previous `merge`),
+/// so several layers can be stacked
+pub fn merge_into(merged: &mut WriterKey, differencing: &mut Parser) -> Result<(), Error> {
+    let mut root = differencing
+        .get_root_key()?
+        .ok_or_else(|| Error::buffer("layered::merge_into: no root key"))?;
+    for value in WriterKey::from_parser_key_without_sub_keys(differencing, &root).values {
+        merged.set_value(value);
+    }
+    merge_sub_keys(merged, differencing, &mut root, &mut HashSet::new())
+}
+
+/// `ancestors` holds the offsets of the keys being merged above `key`, so a circular reference in the differencing hive
+/// is an error rather than endless recursion
+fn merge_sub_keys(
+    merged: &mut WriterKey,
+    differencing: &mut Parser,
+    key: &mut CellKeyNode,
+    ancestors: &mut HashSet<usize>,
+) -> Result<(), Error> {
+    if !ancestors.insert(key.file_offset_absolute) {
+        return Err(Error::Any {
+            detail: format!(
+                "{}: key at offset {} is its own ancestor (circular reference)",
+                key.path, key.file_offset_absolute
+            ),
+        });
+    }
+    for mut sub_key in key.read_sub_keys(differencing) {
+        let layered_key = sub_key.layered_key(differencing);
+        let layer_semantics = layered_key.map_or(LayerSemantics::None, |l| l.layer_semantics);
+        let inherit_class = matches!(layered_key, Some(l) if l.inherit_class);
+        let merged_sub_key = match layer_semantics {
+            LayerSemantics::IsTombstone => {
+                merged.remove_sub_key(&sub_key.key_name);
+                continue;
+            }
+            LayerSemantics::IsSupersedeTree => {
+                let lower_class_name = merged
+                    .remove_sub_key(&sub_key.key_name)
+                    .and_then(|lower| lower.class_name);
+                let merged_sub_key = merged.add_sub_key(
+                    WriterKey::from_parser_key_without_sub_keys(differencing, &sub_key),
+                );
+                if inherit_class {
+                    merged_sub_key.class_name = lower_class_name;
+                }
+                merged_sub_key
+            }
+            LayerSemantics::IsSupersedeLocal | LayerSemantics::None => {
+                let upper = WriterKey::from_parser_key_without_sub_keys(differencing, &sub_key);
+                match merged.get_sub_key_mut(&sub_key.key_name) {
+                    Some(lower) => {
+                        if layer_semantics == LayerSemantics::IsSupersedeLocal {
+                            lower.values.clear();
+                        }
+                        for value in upper.values {
+                            lower.set_value(value);
+                        }
+                        lower.last_key_written_date_and_time = upper.last_key_written_date_and_time;
+                        lower.flags = upper.flags;
+                        if !inherit_class {
+                            lower.class_name = upper.class_name;
+                        }
+                        if upper.security_descriptor.is_some() {
+                            lower.security_descriptor = upper.security_descriptor;
+                        }
+                    }
+                    None => {
+                        merged.add_sub_key(upper);
+                    }
+                }
+                merged
+                    .get_sub_key_mut(&sub_key.key_name)
+                    .expect("the sub key was just merged or added")
+            }
+        };
+        merge_sub_keys(merged_sub_key, differencing, &mut sub_key, ancestors)?;
+    }
+    ancestors.remove(&key.file_offset_absolute);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base_block::BaseBlockBase;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::WriterValue;
+    use std::io::Cursor;
+
+    fn dword(name: &str, value: u32) -> WriterValue {
+        WriterValue::new(
+            name,
+            CellKeyValueDataTypes::REG_DWORD,
+            value.to_le_bytes().to_vec(),
+        )
+    }
+
+    fn sub_key_names(key: &WriterKey) -> Vec<&str> {
+        let mut names: Vec<&str> = key.sub_keys.iter().map(|key| key.name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Writes `root` as a version 1.6 hive, setting the layered key bit fields of the keys at `layered_keys`
+    fn differencing_hive(root: WriterKey, layered_keys: &[(&str, u8)]) -> Parser {
+        let mut hive = HiveWriter::new(root).to_bytes().unwrap();
+        hive[0x18..0x1C].copy_from_slice(&6u32.to_le_bytes());
+        BaseBlockBase::update_checksum(&mut hive).unwrap();
+        let mut parser = ParserBuilder::from_file(Cursor::new(hive.clone()))
+            .build()
+            .unwrap();
+        for (path, bits) in layered_keys {
+            let key = parser.get_key(path, false).unwrap().unwrap();
+            hive[key.file_offset_absolute + 17] = *bits;
+        }
+        ParserBuilder::from_file(Cursor::new(hive)).build().unwrap()
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("Merged\\Lower")
+            .set_value(dword("kept", 1));
+        root.get_or_add_sub_key("Merged").set_value(dword("a", 1));
+        root.get_or_add_sub_key("Merged").set_value(dword("b", 1));
+        root.get_or_add_sub_key("Tree\\Lower");
+        root.get_or_add_sub_key("Local\\Lower");
+        root.get_or_add_sub_key("Local").set_value(dword("a", 1));
+        root.get_or_add_sub_key("Tombstone\\Lower");
+        let mut base =
+            ParserBuilder::from_file(Cursor::new(HiveWriter::new(root).to_bytes().unwrap()))
+                .build()
+                .unwrap();
+
+        let mut root = WriterKey::new("ROOT");
+        root.set_value(dword("root", 2));
+        root.get_or_add_sub_key("Merged\\Upper");
+        root.get_or_add_sub_key("Merged").set_value(dword("b", 2));
+        root.get_or_add_sub_key("Tree\\Upper");
+        root.get_or_add_sub_key("Local\\Upper");
+        root.get_or_add_sub_key("Local").set_value(dword("c", 2));
+        root.get_or_add_sub_key("Tombstone");
+        root.get_or_add_sub_key("Added").set_value(dword("a", 2));
+        let mut differencing = differencing_hive(
+            root,
+            &[("Tombstone", 0x40), ("Local", 0x80), ("Tree", 0xC0)],
+        );
+
+        let merged = merge(&mut base, &mut differencing).unwrap().root;
+        assert_eq!(Some(&dword("root", 2)), merged.get_value("root"));
+        assert_eq!(
+            vec!["Added", "Local", "Merged", "Tree"],
+            sub_key_names(&merged)
+        );
+
+        let merged_key = merged.get_sub_key("Merged").unwrap();
+        assert_eq!(vec![dword("a", 1), dword("b", 2)], merged_key.values);
+        assert_eq!(vec!["Lower", "Upper"], sub_key_names(merged_key));
+        assert_eq!(
+            Some(&dword("kept", 1)),
+            merged
+                .get_sub_key("Merged\\Lower")
+                .unwrap()
+                .get_value("kept")
+        );
+
+        assert_eq!(
+            vec!["Upper"],
+            sub_key_names(merged.get_sub_key("Tree").unwrap())
+        );
+
+        let local = merged.get_sub_key("Local").unwrap();
+        assert_eq!(vec![dword("c", 2)], local.values);
+        assert_eq!(vec!["Lower", "Upper"], sub_key_names(local));
+
+        assert_eq!(
+            vec![dword("a", 2)],
+            merged.get_sub_key("Added").unwrap().values
+        );
+    }
+
+    #[test]
+    fn test_merge_without_layered_keys() {
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("Key").set_value(dword("a", 1));
+        let mut base = ParserBuilder::from_file(Cursor::new(
+            HiveWriter::new(root.clone()).to_bytes().unwrap(),
+        ))
+        .build()
+        .unwrap();
+        root.get_or_add_sub_key("Key").set_value(dword("a", 2));
+        let mut differencing =
+            ParserBuilder::from_file(Cursor::new(HiveWriter::new(root).to_bytes().unwrap()))
+                .build()
+                .unwrap();
+
+        // A 1.5 hive doesn't have layered keys, so its keys are merged with the base hive's
+        let merged = merge(&mut base, &mut differencing).unwrap().root;
+        assert_eq!(
+            vec![dword("a", 2)],
+            merged.get_sub_key("Key").unwrap().values
+        );
+    }
+}
@@ -17,31 +17,71 @@
 pub mod field_offset_len;
 pub(crate) mod macros;
 
+#[cfg(feature = "archive")]
+pub(crate) mod archive;
+
+#[cfg(feature = "tokio")]
+pub mod async_util;
+
 pub mod base_block;
 pub mod cell;
 pub mod cell_big_data;
+pub mod cell_cache;
+pub mod cell_extents;
 pub mod cell_key_node;
 pub mod cell_key_security;
 pub mod cell_key_value;
 pub mod cell_value;
 pub mod cli_util;
+pub mod compact;
+pub mod control_set;
+pub mod environment;
 pub mod err;
+#[cfg(feature = "export")]
+pub mod export;
 pub mod field_serializers;
 pub mod file_info;
 pub mod filter;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub mod guid_names;
 pub mod hive_bin_cell;
 pub mod hive_bin_header;
+pub mod hive_diff;
+#[cfg(feature = "rebuild")]
+pub mod hive_rebuilder;
+pub mod layered;
 pub mod log;
 pub mod marvin32;
+pub mod name_anomalies;
 pub mod parser;
 pub mod parser_builder;
 pub mod parser_recover_deleted;
+pub mod path_mapping;
+pub mod payload;
+pub mod plugins;
 pub mod progress;
+pub mod record;
 pub mod reg_item_map;
+pub mod sanitize;
+pub mod shell_items;
 pub mod state;
 pub mod sub_key_list_lf;
 pub mod sub_key_list_lh;
 pub mod sub_key_list_li;
 pub mod sub_key_list_ri;
+pub mod subtree_stats;
+pub mod suspicious_name;
+pub mod timestamp_format;
 pub mod transaction_log;
 pub mod util;
+pub mod validate;
+pub mod value_dedup;
+pub mod value_stats;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod writer;
+#[cfg(feature = "yara")]
+pub mod yara_scan;
+
+pub use file_info::{ReadSeek, ReadSeekRange};
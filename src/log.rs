@@ -14,12 +14,13 @@
  * limitations under the License.
  */
 
+use crate::cell_key_node::CellKeyNode;
 use crate::err::Error;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::io::{BufWriter, Write};
+use std::io::Write;
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Logs {
     logs: Option<Vec<Log>>,
 }
@@ -48,6 +49,11 @@ impl Logs {
                 .is_empty()
     }
 
+    /// Returns true if any of the logs has `code`
+    pub fn has_code(&self, code: LogCode) -> bool {
+        self.logs.iter().flatten().any(|log| log.code == code)
+    }
+
     pub fn get(&self) -> Option<&Vec<Log>> {
         self.logs.as_ref()
     }
@@ -75,7 +81,7 @@ impl Logs {
         }
     }
 
-    pub fn write<W: Write>(&self, writer: &mut BufWriter<std::fs::File>) -> Result<(), Error> {
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
         if let Some(logs) = &self.logs {
             for log in logs {
                 writeln!(writer, "{:?} {}", log.code, log.text)?;
@@ -101,7 +107,7 @@ impl fmt::Display for Logs {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum LogCode {
     WarningOther,
     WarningNom,
@@ -114,11 +120,216 @@ pub enum LogCode {
     WarningBaseBlock,
     WarningParse,
     WarningRecovery,
+    WarningName,
+    WarningNameHash,
+    WarningParent,
+    WarningLimit,
+    WarningCycle,
+    WarningDataType,
+    WarningPayload,
+    WarningSuspiciousName,
+    WarningOverlap,
     Info,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+impl LogCode {
+    pub fn severity(&self) -> Severity {
+        match self {
+            LogCode::Info => Severity::Info,
+            // Data was skipped because it couldn't be read
+            LogCode::WarningNom
+            | LogCode::WarningParse
+            | LogCode::WarningBaseBlock
+            | LogCode::WarningBigDataContent => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Log {
     pub code: LogCode,
     pub text: String,
 }
+
+/// A log entry along with where it was found
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ParseWarning {
+    pub severity: Severity,
+    pub code: LogCode,
+    /// Absolute file offset of the key or value the entry belongs to; None for entries about the hive as a whole
+    pub offset: Option<usize>,
+    pub key_path: Option<String>,
+    pub value_name: Option<String>,
+    pub text: String,
+}
+
+/// The log entries of a hive and all of its keys and values, collected in one place (see `Parser::parse_warnings`)
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ParseWarnings {
+    warnings: Vec<ParseWarning>,
+}
+
+impl ParseWarnings {
+    pub(crate) fn add_logs(
+        &mut self,
+        logs: &Logs,
+        offset: Option<usize>,
+        key_path: Option<&str>,
+        value_name: Option<&str>,
+    ) {
+        for log in logs.get().into_iter().flatten() {
+            self.warnings.push(ParseWarning {
+                severity: log.code.severity(),
+                code: log.code,
+                offset,
+                key_path: key_path.map(str::to_string),
+                value_name: value_name.map(str::to_string),
+                text: log.text.clone(),
+            });
+        }
+    }
+
+    /// Adds the log entries of `key` and of its values (including those from decoding value content)
+    pub fn add_key(&mut self, key: &CellKeyNode) {
+        self.add_logs(
+            &key.logs,
+            Some(key.file_offset_absolute),
+            Some(&key.path),
+            None,
+        );
+        for value in key.value_iter() {
+            let value_name = value.detail.value_name();
+            let offset = Some(value.file_offset_absolute);
+            self.add_logs(&value.logs, offset, Some(&key.path), Some(&value_name));
+            if let (_, Some(content_logs)) = value.get_content() {
+                self.add_logs(&content_logs, offset, Some(&key.path), Some(&value_name));
+            }
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, ParseWarning> {
+        self.warnings.iter()
+    }
+
+    /// Returns the entries with a severity of at least `severity`
+    pub fn at_least(&self, severity: Severity) -> impl Iterator<Item = &ParseWarning> {
+        self.warnings
+            .iter()
+            .filter(move |warning| warning.severity >= severity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Writes one entry per line as json
+    #[cfg(feature = "export")]
+    pub fn write_jsonl<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        for warning in &self.warnings {
+            writeln!(writer, "{}", serde_json::to_string(warning)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl IntoIterator for ParseWarnings {
+    type Item = ParseWarning;
+    type IntoIter = std::vec::IntoIter<ParseWarning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.warnings.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ParseWarnings {
+    type Item = &'a ParseWarning;
+    type IntoIter = std::slice::Iter<'a, ParseWarning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.warnings.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_warnings() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        assert!(parser.parse_warnings().is_empty());
+
+        // Retyping a REG_DWORD as a REG_QWORD leaves too few bytes to decode
+        let (key_path, value_name, value_offset) = ParserIterator::new(&parser)
+            .iter()
+            .find_map(|key| {
+                key.value_iter()
+                    .find(|value| value.data_type == CellKeyValueDataTypes::REG_DWORD)
+                    .map(|value| {
+                        (
+                            key.path.to_string(),
+                            value.detail.value_name(),
+                            value.file_offset_absolute,
+                        )
+                    })
+            })
+            .unwrap();
+        let mut hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let data_type = value_offset + 16;
+        hive[data_type..data_type + 4]
+            .copy_from_slice(&(CellKeyValueDataTypes::REG_QWORD as u32).to_le_bytes());
+
+        let parser = ParserBuilder::from_file(Cursor::new(hive)).build().unwrap();
+        let warnings = parser.parse_warnings();
+        assert_eq!(1, warnings.len());
+        let warning = warnings.iter().next().unwrap();
+        assert_eq!(
+            (
+                Severity::Warning,
+                LogCode::WarningConversion,
+                Some(value_offset),
+                Some(key_path),
+                Some(value_name)
+            ),
+            (
+                warning.severity,
+                warning.code,
+                warning.offset,
+                warning.key_path.clone(),
+                warning.value_name.clone()
+            )
+        );
+        assert_eq!(1, warnings.at_least(Severity::Warning).count());
+        assert_eq!(0, warnings.at_least(Severity::Error).count());
+
+        #[cfg(feature = "export")]
+        {
+            let mut jsonl = Vec::new();
+            warnings.write_jsonl(&mut jsonl).unwrap();
+            let line = String::from_utf8(jsonl).unwrap();
+            assert_eq!(1, line.lines().count());
+            assert_eq!(
+                *warning,
+                serde_json::from_str::<ParseWarning>(line.trim_end()).unwrap()
+            );
+        }
+    }
+}
@@ -29,6 +29,26 @@ macro_rules! impl_serialize_for_bitflags {
     };
 }
 
+/// The inverse of `impl_serialize_for_bitflags`: parses the `Debug` representation of the flags (ex: `Flags(A | B)`)
+#[macro_export]
+macro_rules! impl_deserialize_for_bitflags {
+    ($flags: ident) => {
+        impl<'de> serde::de::Deserialize<'de> for $flags {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: serde::de::Deserializer<'de>,
+            {
+                let s = String::deserialize(deserializer)?;
+                let inner = s
+                    .strip_prefix(concat!(stringify!($flags), "("))
+                    .and_then(|s| s.strip_suffix(')'))
+                    .unwrap_or(&s);
+                bitflags::parser::from_str(inner).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! impl_flags_from_bits {
     ($bitflag_type: ident, $var_type: ident) => {
@@ -120,4 +140,27 @@ mod tests {
             }
         ]), logs.get(), "Unmapped bits from_bits_checked conversion - logs should contain a warning");
     }
+
+    #[test]
+    fn test_deserialize_for_bitflags() {
+        bitflags! {
+            #[derive(Debug, PartialEq)]
+            pub struct TestFlags: u16 {
+                const TEST_1 = 0x0001;
+                const TEST_2 = 0x0002;
+            }
+        }
+        impl_serialize_for_bitflags! { TestFlags }
+        impl_deserialize_for_bitflags! { TestFlags }
+
+        for flags in [
+            TestFlags::TEST_1 | TestFlags::TEST_2,
+            TestFlags::empty(),
+            TestFlags::from_bits_retain(0x0011),
+        ] {
+            let json = serde_json::to_string(&flags).unwrap();
+            assert_eq!(flags, serde_json::from_str(&json).unwrap(), "{}", json);
+        }
+        assert!(serde_json::from_str::<TestFlags>("\"TestFlags(TEST_3)\"").is_err());
+    }
 }
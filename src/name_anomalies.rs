@@ -0,0 +1,404 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Length and character statistics of a hive's key and value names, with the names that stand out (see
+//! `Parser::anomaly_report`). Names far longer than Windows allows (such as key names of thousands of characters) and
+//! names with unusual characters are used to break other tools, or to hide from them.
+
+use crate::cell_key_node::CellKeyNode;
+use serde::Serialize;
+
+/// The longest key name Windows creates, in UTF-16 code units
+pub const MAX_KEY_NAME_LENGTH: usize = 255;
+/// The longest value name Windows creates, in UTF-16 code units
+pub const MAX_VALUE_NAME_LENGTH: usize = 16_383;
+/// Names shorter than this aren't reported as length outliers, however short the hive's other names are
+pub const MIN_OUTLIER_LENGTH: usize = 64;
+/// How many standard deviations above the mean length of the hive's names a name has to be to be a length outlier
+pub const OUTLIER_STD_DEVS: f64 = 4.0;
+/// Names with characters outside ASCII are reported when less than this share of the hive's names (of the same kind)
+/// have them
+pub const RARE_CHARACTER_SHARE: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum NameKind {
+    Key,
+    Value,
+}
+
+/// The characters of a set of names, counted by class
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct CharacterCounts {
+    /// ASCII letters
+    pub letters: u64,
+    /// ASCII digits
+    pub digits: u64,
+    /// ASCII spaces
+    pub spaces: u64,
+    /// ASCII punctuation and symbols
+    pub punctuation: u64,
+    /// Control characters, including nulls
+    pub control: u64,
+    /// Everything else
+    pub non_ascii: u64,
+}
+
+impl CharacterCounts {
+    fn add(&mut self, c: char) {
+        if c.is_ascii_alphabetic() {
+            self.letters += 1;
+        } else if c.is_ascii_digit() {
+            self.digits += 1;
+        } else if c == ' ' {
+            self.spaces += 1;
+        } else if c.is_ascii_punctuation() {
+            self.punctuation += 1;
+        } else if c.is_control() {
+            self.control += 1;
+        } else {
+            self.non_ascii += 1;
+        }
+    }
+}
+
+/// Statistics on one kind of name. Lengths are in UTF-16 code units, as Windows counts them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NameStats {
+    pub count: usize,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub mean_length: f64,
+    pub std_dev_length: f64,
+    /// Names of zero length (for values, the default value)
+    pub empty_count: usize,
+    /// Names with at least one control character
+    pub control_count: usize,
+    /// Names with at least one character outside ASCII
+    pub non_ascii_count: usize,
+    pub characters: CharacterCounts,
+}
+
+/// Why a name stands out
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum AnomalyReason {
+    /// Longer than Windows allows (see `MAX_KEY_NAME_LENGTH` and `MAX_VALUE_NAME_LENGTH`)
+    TooLong,
+    /// Far longer than the hive's other names of the same kind (see `MIN_OUTLIER_LENGTH` and `OUTLIER_STD_DEVS`)
+    LengthOutlier,
+    /// Holds control characters
+    ControlCharacters,
+    /// Holds characters outside ASCII, which few of the hive's other names of the same kind do (see `RARE_CHARACTER_SHARE`)
+    RareCharacters,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct NameAnomaly {
+    pub kind: NameKind,
+    /// Path of the key, or of the value's key
+    pub key_path: String,
+    /// Name of the value, for value names
+    pub value_name: Option<String>,
+    /// In UTF-16 code units
+    pub length: usize,
+    pub reasons: Vec<AnomalyReason>,
+}
+
+/// Name statistics of a hive, as returned by `Parser::anomaly_report`
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct AnomalyReport {
+    pub key_names: NameStats,
+    pub value_names: NameStats,
+    /// The names that stand out, in the order they were found
+    pub anomalies: Vec<NameAnomaly>,
+}
+
+impl AnomalyReport {
+    pub(crate) fn new(keys: impl Iterator<Item = CellKeyNode>) -> Self {
+        let mut builder = AnomalyReportBuilder::new();
+        for key in keys {
+            builder.add_key(&key);
+        }
+        builder.build()
+    }
+}
+
+/// Builds an `AnomalyReport` from keys added one at a time, such as the keys an export writes (see `Parser::set_key_handler`)
+#[derive(Default)]
+pub struct AnomalyReportBuilder {
+    key_names: NameStatsBuilder,
+    value_names: NameStatsBuilder,
+    // which names stand out depends on the statistics, so the ones that might are kept until all names are counted
+    candidates: Vec<(NameKind, String, Option<String>, NameInfo)>,
+}
+
+impl AnomalyReportBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_key(&mut self, key: &CellKeyNode) {
+        let name = self.key_names.add(&key.key_name);
+        if name.is_candidate() {
+            self.candidates
+                .push((NameKind::Key, key.path.to_string(), None, name));
+        }
+        for value in key.value_iter() {
+            let value_name = value.detail.value_name();
+            let name = self.value_names.add(&value_name);
+            if name.is_candidate() {
+                self.candidates.push((
+                    NameKind::Value,
+                    key.path.to_string(),
+                    Some(value_name),
+                    name,
+                ));
+            }
+        }
+    }
+
+    pub fn build(self) -> AnomalyReport {
+        let key_names = self.key_names.build();
+        let value_names = self.value_names.build();
+        let anomalies = self
+            .candidates
+            .into_iter()
+            .filter_map(|(kind, key_path, value_name, name)| {
+                let reasons = match kind {
+                    NameKind::Key => name.reasons(&key_names, MAX_KEY_NAME_LENGTH),
+                    NameKind::Value => name.reasons(&value_names, MAX_VALUE_NAME_LENGTH),
+                };
+                if reasons.is_empty() {
+                    None
+                } else {
+                    Some(NameAnomaly {
+                        kind,
+                        key_path,
+                        value_name,
+                        length: name.length,
+                        reasons,
+                    })
+                }
+            })
+            .collect();
+        AnomalyReport {
+            key_names,
+            value_names,
+            anomalies,
+        }
+    }
+}
+
+/// What's known about a name before the statistics of all names are
+struct NameInfo {
+    length: usize,
+    has_control: bool,
+    has_non_ascii: bool,
+}
+
+impl NameInfo {
+    fn is_candidate(&self) -> bool {
+        self.length >= MIN_OUTLIER_LENGTH || self.has_control || self.has_non_ascii
+    }
+
+    fn reasons(&self, stats: &NameStats, max_length: usize) -> Vec<AnomalyReason> {
+        let mut reasons = Vec::new();
+        if self.length > max_length {
+            reasons.push(AnomalyReason::TooLong);
+        }
+        if self.length >= MIN_OUTLIER_LENGTH
+            && self.length as f64 > stats.mean_length + OUTLIER_STD_DEVS * stats.std_dev_length
+        {
+            reasons.push(AnomalyReason::LengthOutlier);
+        }
+        if self.has_control {
+            reasons.push(AnomalyReason::ControlCharacters);
+        }
+        if self.has_non_ascii
+            && (stats.non_ascii_count as f64) < RARE_CHARACTER_SHARE * stats.count as f64
+        {
+            reasons.push(AnomalyReason::RareCharacters);
+        }
+        reasons
+    }
+}
+
+#[derive(Default)]
+struct NameStatsBuilder {
+    stats: NameStats,
+    length_sum: f64,
+    length_sum_squares: f64,
+}
+
+impl NameStatsBuilder {
+    fn add(&mut self, name: &str) -> NameInfo {
+        let stats = &mut self.stats;
+        let length = name.encode_utf16().count();
+        if stats.count == 0 || length < stats.min_length {
+            stats.min_length = length;
+        }
+        stats.max_length = std::cmp::max(stats.max_length, length);
+        stats.count += 1;
+        self.length_sum += length as f64;
+        self.length_sum_squares += (length * length) as f64;
+
+        let mut info = NameInfo {
+            length,
+            has_control: false,
+            has_non_ascii: false,
+        };
+        for c in name.chars() {
+            stats.characters.add(c);
+            info.has_control |= c.is_control();
+            info.has_non_ascii |= !c.is_ascii();
+        }
+        if length == 0 {
+            stats.empty_count += 1;
+        }
+        if info.has_control {
+            stats.control_count += 1;
+        }
+        if info.has_non_ascii {
+            stats.non_ascii_count += 1;
+        }
+        info
+    }
+
+    fn build(mut self) -> NameStats {
+        if self.stats.count > 0 {
+            let count = self.stats.count as f64;
+            let mean = self.length_sum / count;
+            self.stats.mean_length = mean;
+            // population standard deviation; rounding can make the variance slightly negative
+            self.stats.std_dev_length = (self.length_sum_squares / count - mean * mean)
+                .max(0.0)
+                .sqrt();
+        }
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+
+    #[test]
+    fn test_anomaly_report() {
+        let mut root = WriterKey::new("ROOT");
+        for i in 0..200 {
+            root.get_or_add_sub_key(&format!("Software\\Key{:03}", i))
+                .set_value(WriterValue::new(
+                    "Value",
+                    CellKeyValueDataTypes::REG_DWORD,
+                    vec![0; 4],
+                ));
+        }
+        let long_name = "A".repeat(300);
+        root.get_or_add_sub_key(&format!("Software\\{}", long_name));
+        root.get_or_add_sub_key("Software\\Caf\u{e9}");
+        root.get_or_add_sub_key("Software\\Key\u{1}")
+            .set_value(WriterValue::new(
+                &"V".repeat(MAX_VALUE_NAME_LENGTH + 1),
+                CellKeyValueDataTypes::REG_DWORD,
+                vec![0; 4],
+            ));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        let parser = ParserBuilder::from_file(std::io::Cursor::new(hive))
+            .build()
+            .unwrap();
+
+        let report = parser.anomaly_report().unwrap();
+        let mut builder = AnomalyReportBuilder::new();
+        for key in ParserIterator::new(&parser).iter() {
+            builder.add_key(&key);
+        }
+        assert_eq!(report, builder.build());
+        // the root key, Software, and the sub keys
+        assert_eq!(205, report.key_names.count);
+        assert_eq!(300, report.key_names.max_length);
+        assert_eq!(1, report.key_names.control_count);
+        assert_eq!(1, report.key_names.non_ascii_count);
+        assert_eq!(1, report.key_names.characters.control);
+        assert_eq!(201, report.value_names.count);
+        assert_eq!(5, report.value_names.min_length);
+        assert_eq!(MAX_VALUE_NAME_LENGTH + 1, report.value_names.max_length);
+
+        let mut anomalies: Vec<_> = report
+            .anomalies
+            .iter()
+            .map(|anomaly| {
+                (
+                    anomaly.kind,
+                    anomaly.key_path.rsplit('\\').next().unwrap().to_string(),
+                    anomaly.length,
+                    anomaly.reasons.clone(),
+                )
+            })
+            .collect();
+        anomalies
+            .sort_by_key(|(kind, key_name, _, _)| (key_name.clone(), *kind == NameKind::Value));
+        assert_eq!(
+            vec![
+                (
+                    NameKind::Key,
+                    long_name,
+                    300,
+                    vec![AnomalyReason::TooLong, AnomalyReason::LengthOutlier]
+                ),
+                (
+                    NameKind::Key,
+                    "Caf\u{e9}".to_string(),
+                    4,
+                    vec![AnomalyReason::RareCharacters]
+                ),
+                (
+                    NameKind::Key,
+                    "Key\u{1}".to_string(),
+                    4,
+                    vec![AnomalyReason::ControlCharacters]
+                ),
+                (
+                    NameKind::Value,
+                    "Key\u{1}".to_string(),
+                    MAX_VALUE_NAME_LENGTH + 1,
+                    vec![AnomalyReason::TooLong, AnomalyReason::LengthOutlier]
+                ),
+            ],
+            anomalies
+        );
+    }
+
+    #[test]
+    fn test_anomaly_report_ntuser() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let report = parser.anomaly_report().unwrap();
+        let (keys, values) = parser.count_all_keys_and_values(None);
+        assert_eq!(keys, report.key_names.count);
+        assert_eq!(values, report.value_names.count);
+        assert!(report.key_names.max_length <= MAX_KEY_NAME_LENGTH);
+        assert!(report.key_names.mean_length > 0.0);
+        assert!(report.key_names.std_dev_length > 0.0);
+        assert!(!report
+            .anomalies
+            .iter()
+            .any(|anomaly| anomaly.reasons.contains(&AnomalyReason::TooLong)));
+    }
+}
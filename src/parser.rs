@@ -14,18 +14,44 @@
  * limitations under the License.
  */
 
-use crate::base_block::{BaseBlock, BaseBlockBase, FileType};
-use crate::cell_key_node::{CellKeyNode, CellKeyNodeReadOptions, FilterMatchState};
+use crate::base_block::{BaseBlock, BaseBlockBase, BaseBlockSource, FileType, HiveCapabilities};
+use crate::cell_cache::{CellCache, CellCacheStats};
+use crate::cell_key_node::{CellKeyNode, CellKeyNodeReadOptions, FilterMatchState, KeyNodeFlags};
+use crate::cell_key_value::CellKeyValue;
+use crate::cell_value::CellValue;
+use crate::control_set::ControlSets;
 use crate::err::Error;
 use crate::file_info::FileInfo;
-use crate::filter::{Filter, FilterBuilder};
-use crate::hive_bin_header::HiveBinHeader;
-use crate::log::{LogCode, Logs};
+use crate::filter::{Filter, FilterBuilder, FilterFlags};
+use crate::guid_names::GuidNames;
+use crate::hive_bin_header::{HiveBinHeader, HiveBins};
+use crate::log::{LogCode, Logs, ParseWarnings};
+use crate::name_anomalies::AnomalyReport;
 use crate::parser_recover_deleted::ParserRecoverDeleted;
-use crate::progress;
+use crate::path_mapping::PathMapping;
+use crate::payload::Payload;
+use crate::plugins;
+use crate::progress::{self, KeyHandler, ProgressPhase, ProgressReporter};
+use crate::record::Record;
 use crate::state::State;
-use crate::transaction_log::TransactionLog;
-use std::collections::BTreeSet;
+use crate::subtree_stats::SubtreeStats;
+use crate::suspicious_name::SuspiciousName;
+use crate::timestamp_format::TimestampFormat;
+use crate::transaction_log::{
+    AppliedTransactionLog, LogEntryHashPolicy, ReplayScope, TransactionLog,
+};
+use crate::util;
+use crate::validate::{AnomalyType, ValidationReport, Validator};
+use crate::value_dedup::DedupReport;
+use crate::writer::HiveWriter;
+#[cfg(feature = "yara")]
+use crate::yara_scan::{self, YaraMatch};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
 
 /* Structures based upon:
     https://github.com/libyal/libregf/blob/main/documentation/Windows%20NT%20Registry%20File%20(REGF)%20format.asciidoc
@@ -47,24 +73,53 @@ pub struct Parser {
     pub(crate) file_info: FileInfo,
     pub(crate) state: State,
     pub(crate) base_block: Option<BaseBlock>,
+    pub(crate) base_block_source: BaseBlockSource,
     pub(crate) hive_bin_header: Option<HiveBinHeader>,
     pub(crate) cell_key_node_root: Option<CellKeyNode>,
+    pub(crate) applied_transaction_logs: Vec<AppliedTransactionLog>,
     pub(crate) recover_deleted: bool,
     pub(crate) update_console: bool,
+    pub(crate) key_sort: Option<KeySort>,
+    pub(crate) path_mapping: Option<PathMapping>,
+    pub(crate) guid_names: Option<GuidNames>,
+    pub(crate) progress: ProgressReporter,
 }
 
 impl Parser {
     pub(crate) fn init(
         &mut self,
         recover_deleted: bool,
-        parsed_transaction_logs: Vec<TransactionLog>,
+        recover_base_block: bool,
+        replay_scope: Option<&ReplayScope>,
+        log_entry_hash_policy: LogEntryHashPolicy,
+        mut parsed_transaction_logs: Vec<TransactionLog>,
     ) -> Result<(), Error> {
-        let (is_supported_format, has_bad_checksum) = self.init_base_block()?;
+        let (is_supported_format, has_bad_checksum) = match self.init_base_block() {
+            Err(e) if recover_base_block && !parsed_transaction_logs.is_empty() => {
+                self.state.info.add(
+                    LogCode::WarningBaseBlock,
+                    &format!(
+                        "Hive requires recovery: unable to parse the base block ({})",
+                        e
+                    ),
+                );
+                if !self.recover_base_block(&mut parsed_transaction_logs)? {
+                    return Err(e);
+                }
+                self.init_base_block()?
+            }
+            result => result?,
+        };
         if is_supported_format {
             if recover_deleted {
                 self.init_recover_deleted()?;
             }
-            self.apply_transaction_logs(has_bad_checksum, parsed_transaction_logs)?;
+            self.apply_transaction_logs(
+                has_bad_checksum,
+                replay_scope,
+                log_entry_hash_policy,
+                parsed_transaction_logs,
+            )?;
             self.init_root()?;
         }
         Ok(())
@@ -99,6 +154,7 @@ impl Parser {
         let (input, base_block) = BaseBlock::from_bytes(&self.file_info.buffer)?;
         self.file_info.hbin_offset_absolute =
             input.as_ptr() as usize - self.file_info.buffer.as_ptr() as usize;
+        self.state.capabilities = base_block.capabilities();
         self.base_block = Some(base_block);
         self.check_base_block()
     }
@@ -121,8 +177,16 @@ impl Parser {
                     )
                 );
             }
-            let checksum = BaseBlockBase::calculate_checksum(&self.file_info.buffer[..0x200])?;
-            if checksum != base_block.checksum {
+            if !self.state.capabilities.is_known_version() {
+                self.state.info.add(
+                    LogCode::WarningBaseBlock,
+                    &format!(
+                        "Unrecognized hive format version {}.{}",
+                        base_block.major_version, base_block.minor_version
+                    ),
+                );
+            }
+            if !BaseBlockBase::verify_checksum(&self.file_info.buffer)? {
                 self.state.info.add(
                     LogCode::WarningBaseBlock,
                     &"Hive requires recovery: base block checksum is wrong.",
@@ -162,11 +226,24 @@ impl Parser {
             file_info: &self.file_info,
             state: &mut self.state,
         };
+        let mut hive_bins_read = 0;
         while file_offset_absolute < hive_bins_size as usize {
+            self.progress.check_cancelled()?;
+            if hive_bins_read % ProgressReporter::RECOVERY_INTERVAL == 0 {
+                self.progress.report(
+                    ProgressPhase::RecoveringDeleted,
+                    100.0 * file_offset_absolute as f64 / hive_bins_size as f64,
+                    0,
+                );
+            }
+            hive_bins_read += 1;
             let file_offset_absolute_ret =
                 parser_recover_deleted.find_free_keys_and_values(file_offset_absolute)?;
             file_offset_absolute = file_offset_absolute_ret;
         }
+        parser_recover_deleted.reconstruct_paths();
+        self.progress
+            .report(ProgressPhase::RecoveringDeleted, 100.0, 0);
         Ok(true)
     }
 
@@ -196,31 +273,50 @@ impl Parser {
         &mut self,
         parsed_transaction_logs: &mut Vec<TransactionLog>,
     ) -> Result<(), Error> {
+        if self.recover_base_block(parsed_transaction_logs)? {
+            self.init_base_block()?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the primary file's base block with the backup in the newest transaction log whose base block has a valid
+    /// checksum. Returns false, leaving the base block alone, if there is no such log.
+    fn recover_base_block(
+        &mut self,
+        parsed_transaction_logs: &mut Vec<TransactionLog>,
+    ) -> Result<bool, Error> {
+        // logs come in here sorted oldest to newest
+        let index = match parsed_transaction_logs.iter().rposition(|log| {
+            BaseBlockBase::verify_checksum(&log.base_block_bytes).unwrap_or_default()
+        }) {
+            Some(index) => index,
+            None => {
+                self.state.info.add(
+                    LogCode::WarningBaseBlock,
+                    &"Unable to recover the base block: no transaction log has a valid base block",
+                );
+                return Ok(false);
+            }
+        };
         self.state
             .info
             .add(LogCode::WarningBaseBlock, &"Applying recovered base block");
 
-        // logs come in here sorted oldest to newest
-        let newest_log = parsed_transaction_logs
-            .last()
-            .expect("shouldn't be here unless we have logs");
         let slice = self
             .file_info
             .buffer
             .get_mut(..BaseBlockBase::BASE_BLOCK_LEN)
-            .ok_or_else(|| Error::buffer("handle_bad_checksum"))?;
-        slice.copy_from_slice(&newest_log.base_block_bytes);
+            .ok_or_else(|| Error::buffer("recover_base_block"))?;
+        slice.copy_from_slice(&parsed_transaction_logs[index].base_block_bytes);
+        self.base_block_source = BaseBlockSource::TransactionLog;
 
         // Per https://github.com/msuhanov/regf/blob/master/Windows%20registry%20file%20format%20specification.md#new-format-1:
         //  "If a primary file contains an invalid base block, only the transaction log file with latest log entries is used in the recovery."
         debug_assert!(parsed_transaction_logs.len() <= 2); // Per Microsoft there will max two logs at this point.
-                                                           // if there are two logs, remove the first (older) one
-        if parsed_transaction_logs.len() > 1 {
-            parsed_transaction_logs.remove(0);
-        }
-
-        self.init_base_block()?;
-        Ok(())
+                                                           // keep only the log the base block came from
+        parsed_transaction_logs.truncate(index + 1);
+        parsed_transaction_logs.drain(..index);
+        Ok(true)
     }
 
     fn update_header_after_transaction_logs(
@@ -241,7 +337,7 @@ impl Parser {
             .copy_from_slice(&new_sequence_number_bytes);
 
         // Update the checksum
-        let new_checksum = BaseBlockBase::calculate_checksum(&self.file_info.buffer[..0x200])?;
+        let new_checksum = BaseBlockBase::calculate_checksum(&self.file_info.buffer)?;
         self.file_info.buffer[BaseBlockBase::CHECKSUM_OFFSET
             ..BaseBlockBase::CHECKSUM_OFFSET + std::mem::size_of_val(&new_checksum)]
             .copy_from_slice(&new_checksum.to_le_bytes());
@@ -258,11 +354,14 @@ impl Parser {
     fn apply_transaction_logs(
         &mut self,
         has_bad_checksum: bool,
+        replay_scope: Option<&ReplayScope>,
+        log_entry_hash_policy: LogEntryHashPolicy,
         mut parsed_transaction_logs: Vec<TransactionLog>,
     ) -> Result<(), Error> {
         if !parsed_transaction_logs.is_empty()
             && self.prepare_transaction_logs(has_bad_checksum, &mut parsed_transaction_logs)?
         {
+            let replay_ranges = self.get_replay_ranges(replay_scope)?;
             let mut new_sequence_number = 0;
             let mut original_items = TransactionLog::get_reg_items(self, 0)?;
 
@@ -286,10 +385,22 @@ impl Parser {
                             index + 1,
                             parsed_transaction_logs.len()
                         ))?;
-                        let (new_seq_num_ret, prior_reg_items) =
-                            log.update_parser(self, original_items)?;
+                        let (new_seq_num_ret, prior_reg_items) = log.update_parser(
+                            self,
+                            original_items,
+                            (index, parsed_transaction_logs.len()),
+                            replay_ranges.as_deref(),
+                            log_entry_hash_policy,
+                        )?;
                         original_items = prior_reg_items;
                         new_sequence_number = new_seq_num_ret;
+                        // A log none of whose entries were replayed wasn't applied
+                        if new_seq_num_ret != 0 {
+                            self.applied_transaction_logs.push(AppliedTransactionLog {
+                                primary_sequence_number: log.base_block.primary_sequence_number,
+                                last_sequence_number: new_sequence_number,
+                            });
+                        }
                     } else {
                         self.state.info.add(
                             LogCode::WarningTransactionLog,
@@ -303,11 +414,68 @@ impl Parser {
                     );
                 }
             }
+            self.progress
+                .report(ProgressPhase::ApplyingTransactionLogs, 100.0, 0);
             self.update_header_after_transaction_logs(new_sequence_number)?;
         }
         Ok(())
     }
 
+    /// Returns the ranges (relative from the start of the hive bins data) a log entry must touch to be applied, or `None` to apply every entry.
+    /// For `ReplayScope::KeySubtree` these are the cells of the matching keys in the primary file: the key and value cells,
+    /// the start of their lists, and the start of each value's data.
+    fn get_replay_ranges(
+        &mut self,
+        replay_scope: Option<&ReplayScope>,
+    ) -> Result<Option<Vec<Range<usize>>>, Error> {
+        match replay_scope {
+            None => Ok(None),
+            Some(ReplayScope::OffsetRange(range)) => {
+                let range = range.start as usize..range.end as usize;
+                Ok(Some(vec![range]))
+            }
+            Some(ReplayScope::KeySubtree(key_path)) => {
+                let filter = FilterBuilder::new()
+                    .add_key_path(key_path)
+                    .return_child_keys(true)
+                    .build()?;
+                self.init_root()?;
+                let hbin_offset = self.file_info.hbin_offset_absolute;
+                let relative = |offset_absolute: usize, len: usize| {
+                    let start = offset_absolute.saturating_sub(hbin_offset);
+                    start..start + len.max(1)
+                };
+                let mut ranges = Vec::new();
+                let mut iter = ParserIterator::new(self);
+                iter.with_filter(filter).without_progress();
+                for key in iter.iter() {
+                    ranges.push(relative(
+                        key.file_offset_absolute,
+                        key.detail.size().unsigned_abs() as usize,
+                    ));
+                    if key.detail.number_of_sub_keys() > 0 {
+                        let offset = key.detail.sub_keys_list_offset_relative() as usize;
+                        ranges.push(offset..offset + 1);
+                    }
+                    if key.detail.number_of_key_values() > 0 {
+                        let offset = key.detail.key_values_list_offset_relative() as usize;
+                        ranges.push(offset..offset + 1);
+                    }
+                    for value in key.sub_values.iter() {
+                        ranges.push(relative(
+                            value.file_offset_absolute,
+                            value.detail.size().unsigned_abs() as usize,
+                        ));
+                        for data_offset in &value.data_offsets_absolute {
+                            ranges.push(relative(*data_offset, 1));
+                        }
+                    }
+                }
+                Ok(Some(ranges))
+            }
+        }
+    }
+
     pub(crate) fn get_base_block_info(&self) -> (u32, u32) {
         let base_block = &self
             .base_block
@@ -320,6 +488,95 @@ impl Parser {
         )
     }
 
+    /// Checks the hive bins and every cell reachable from the root key for structural anomalies (signatures, sizes,
+    /// parent/child consistency, sub key and value counts, and security cell reference counts). Anomalies are reported
+    /// rather than treated as errors; the report is empty for a hive that isn't a supported format.
+    pub fn validate(&self) -> ValidationReport {
+        match &self.base_block {
+            Some(base_block) if self.is_supported_file_type() => {
+                let base = &base_block.base;
+                let hbin_offset = self.file_info.hbin_offset_absolute;
+                let hive_bins = self.file_info.buffer.get(hbin_offset..).unwrap_or_default();
+                let hive_bins =
+                    &hive_bins[..std::cmp::min(base.hive_bins_data_size as usize, hive_bins.len())];
+                Validator::new(hive_bins, hbin_offset, base.minor_version)
+                    .validate(base.root_cell_offset_relative as u32)
+            }
+            _ => ValidationReport::default(),
+        }
+    }
+
+    /// Returns the parsed base block (the hive's header), including its reserved regions which aren't interpreted
+    pub fn base_block(&self) -> Option<&BaseBlock> {
+        self.base_block.as_ref()
+    }
+
+    /// Returns the format features the hive supports according to the version in its base block
+    pub fn hive_capabilities(&self) -> HiveCapabilities {
+        self.state.capabilities
+    }
+
+    /// Returns an iterator over the hive bins, with each one's header and the offset, size, and allocation state of its cells.
+    /// Iteration stops at the first hive bin with a missing header or an invalid size; nothing is returned for a hive that isn't a supported format.
+    pub fn hive_bins(&self) -> HiveBins<'_> {
+        let hive_bins_data_size = match &self.base_block {
+            Some(base_block) if self.is_supported_file_type() => {
+                base_block.base.hive_bins_data_size as usize
+            }
+            _ => 0,
+        };
+        HiveBins::new(&self.file_info, hive_bins_data_size)
+    }
+
+    /// Returns an error listing the structural inconsistencies of the hive, if it has any (see `ParserBuilder::strict`)
+    pub(crate) fn check_strict(&self) -> Result<(), Error> {
+        const MAX_LISTED: usize = 10;
+        let mut problems = Vec::new();
+        if self.is_supported_file_type() {
+            let base_block = &self
+                .base_block
+                .as_ref()
+                .expect("Shouldn't be here unless we've parsed the base block")
+                .base;
+            if base_block.primary_sequence_number != base_block.secondary_sequence_number
+                && !self.state.app_hive
+            {
+                problems.push(
+                    "Hive requires recovery: primary and secondary sequence numbers do not match"
+                        .to_string(),
+                );
+            }
+            if BaseBlockBase::calculate_checksum(&self.file_info.buffer)? != base_block.checksum {
+                problems.push("Hive requires recovery: base block checksum is wrong".to_string());
+            }
+            // Allocated cells that nothing references are left behind by normal use, so they aren't a problem
+            problems.extend(
+                self.validate()
+                    .anomalies
+                    .iter()
+                    .filter(|anomaly| anomaly.anomaly_type != AnomalyType::UnreferencedCell)
+                    .map(|anomaly| {
+                        format!(
+                            "{:?} at {:#x} {}: {}",
+                            anomaly.anomaly_type, anomaly.offset, anomaly.key_path, anomaly.detail
+                        )
+                    }),
+            );
+        } else {
+            problems.push("Unsupported registry file type".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            let count = problems.len();
+            problems.truncate(MAX_LISTED);
+            Err(Error::Validation {
+                detail: format!("{} problem(s) found: {}", count, problems.join("; ")),
+            })
+        }
+    }
+
     /// Counts all subkeys and values
     pub fn count_all_keys_and_values(&self, filter: Option<&Filter>) -> (usize, usize) {
         let mut keys = 0;
@@ -335,6 +592,186 @@ impl Parser {
         (keys, values)
     }
 
+    /// Returns the same counts as `count_all_keys_and_values` without reading keys and values: key names are only decoded
+    /// where `filter` needs a key's path, and values are counted from each key's value count rather than read. Suited to
+    /// sizing a filter's result set (or a progress total) before a full export. Keys and values recovered with
+    /// `recover_deleted` aren't counted, and the limits (such as `max_keys`) aren't applied.
+    pub fn count(&self, filter: Option<&Filter>) -> Result<(usize, usize), Error> {
+        let root_offset = match &self.base_block {
+            Some(base_block) if self.is_supported_file_type() => {
+                base_block.base.root_cell_offset_relative as usize
+                    + self.file_info.hbin_offset_absolute
+            }
+            _ => return Ok((0, 0)),
+        };
+        let filter = filter.filter(|filter| filter.is_valid());
+        let mut state = State {
+            control_sets: self.state.control_sets,
+            ..State::default()
+        };
+        let buffer = &self.file_info.buffer;
+        let (mut keys, mut values) = (0, 0);
+        let mut visited = HashSet::new();
+        // (offset, parent path, whether the parent is a filter match or a descendent of one)
+        let mut stack = vec![(root_offset, String::new(), false)];
+        while let Some((offset, parent_path, parent_in_scope)) = stack.pop() {
+            if !visited.insert(offset) {
+                continue;
+            }
+            // the key's fields are read in place (see `CellKeyNode::HEADER_SIZE`), after the cell size
+            let nk_offset = offset + std::mem::size_of::<i32>();
+            let nk = match buffer.get(nk_offset..nk_offset + CellKeyNode::HEADER_SIZE) {
+                Some(nk)
+                    if nk.starts_with(CellKeyNode::SIGNATURE.as_bytes())
+                        && (util::read_u32_le(buffer, offset).unwrap_or_default() as i32) < 0 =>
+                {
+                    nk
+                }
+                _ => continue,
+            };
+            let read_u32 = |field_offset| util::read_u32_le(nk, field_offset).unwrap_or_default();
+            let (in_scope, path) = match filter {
+                // every descendent of a match is returned, so their paths aren't needed
+                Some(filter) if !(parent_in_scope && filter.return_sub_keys()) => {
+                    let name_size = util::read_u16_le(nk, CellKeyNode::KEY_NAME_SIZE_FIELD)
+                        .unwrap_or_default() as usize;
+                    let name_offset = nk_offset + CellKeyNode::HEADER_SIZE;
+                    let name_bytes = buffer
+                        .get(name_offset..name_offset + name_size)
+                        .unwrap_or_default();
+                    let flags = KeyNodeFlags::from_bits_truncate(
+                        util::read_u16_le(nk, CellKeyNode::FLAGS_FIELD).unwrap_or_default(),
+                    );
+                    let (name, _) = util::name_from_bytes(
+                        flags.contains(KeyNodeFlags::KEY_COMP_NAME),
+                        name_bytes,
+                        &mut Logs::default(),
+                        "key_name_bytes",
+                    );
+                    let path = format!("{}\\{}", parent_path, name);
+                    let filter_flags = filter.check_path(&mut state, offset == root_offset, &path);
+                    if filter_flags.contains(FilterFlags::FILTER_NO_MATCH) {
+                        continue;
+                    }
+                    (
+                        parent_in_scope || filter_flags.contains(FilterFlags::FILTER_KEY_MATCH),
+                        path,
+                    )
+                }
+                _ => (true, String::new()),
+            };
+            if in_scope {
+                keys += 1;
+                values += read_u32(CellKeyNode::NUMBER_OF_KEY_VALUES_FIELD) as usize;
+            }
+            if read_u32(CellKeyNode::NUMBER_OF_SUB_KEYS_FIELD) > 0 {
+                if let Ok(sub_keys) = CellKeyNode::parse_sub_key_list_items(
+                    &self.file_info,
+                    &mut state,
+                    read_u32(CellKeyNode::SUB_KEYS_LIST_OFFSET_FIELD),
+                    0,
+                    &mut HashSet::new(),
+                ) {
+                    for (sub_key_offset, _) in sub_keys {
+                        stack.push((sub_key_offset as usize, path.clone(), in_scope));
+                    }
+                }
+            }
+        }
+        Ok((keys, values))
+    }
+
+    /// Returns the key and value counts, total data size, depth, and most recent last written timestamp of the key at
+    /// `key_path` (not including the root key's name; an empty path is the entire hive) and its descendents, in one pass.
+    /// Returns None if the key doesn't exist.
+    pub fn subtree_stats(&self, key_path: &str) -> Result<Option<SubtreeStats>, Error> {
+        let key_path = key_path.trim_matches('\\');
+        let mut iter = ParserIterator::new(self);
+        if !key_path.is_empty() {
+            iter.with_filter(
+                FilterBuilder::new()
+                    .add_key_path(key_path)
+                    .return_child_keys(true)
+                    .build()?,
+            );
+        }
+        let mut stats: Option<SubtreeStats> = None;
+        let mut top_depth = 0;
+        for key in iter.iter() {
+            let stats = stats.get_or_insert_with(|| {
+                // the first key returned is the top of the subtree
+                top_depth = SubtreeStats::depth(&key.path);
+                SubtreeStats::default()
+            });
+            stats.add_key(&key, top_depth);
+        }
+        self.check_cancelled()?;
+        Ok(stats)
+    }
+
+    /// Returns a digest of the key at `key_path` (not including the root key's name; an empty path is the entire hive) and
+    /// its descendents, made from each key's path below `key_path` and its `CellKeyNode::content_hash`. Keys are hashed in
+    /// path order and deleted keys are skipped, so equal digests mean the subtrees hold the same keys and values. Returns
+    /// None if the key doesn't exist.
+    pub fn subtree_hash(
+        &self,
+        key_path: &str,
+        include_timestamps: bool,
+    ) -> Result<Option<blake3::Hash>, Error> {
+        let key_path = key_path.trim_matches('\\');
+        let mut iter = ParserIterator::new(self);
+        if !key_path.is_empty() {
+            iter.with_filter(
+                FilterBuilder::new()
+                    .add_key_path(key_path)
+                    .return_child_keys(true)
+                    .build()?,
+            );
+        }
+        let mut top_path_len = None;
+        let mut keys = Vec::new();
+        for key in iter.iter() {
+            // the first key returned is the top of the subtree
+            let top_path_len = *top_path_len.get_or_insert(key.path.len());
+            if key.cell_state.is_deleted() {
+                continue;
+            }
+            keys.push((
+                key.path.get(top_path_len..).unwrap_or_default().to_string(),
+                key.content_hash(include_timestamps),
+            ));
+        }
+        self.check_cancelled()?;
+        if top_path_len.is_none() {
+            return Ok(None);
+        }
+        keys.sort_by(|a, b| (&a.0, a.1.as_bytes()).cmp(&(&b.0, b.1.as_bytes())));
+        let mut hasher = blake3::Hasher::new();
+        for (path, hash) in keys {
+            util::hash_field(&mut hasher, path.as_bytes());
+            hasher.update(hash.as_bytes());
+        }
+        Ok(Some(hasher.finalize()))
+    }
+
+    /// Hashes the data of every value and returns the blobs held by more than one value, with where they are, along with
+    /// the total and deduplicated data sizes. Only data of at least `min_size` bytes is listed as a duplicate (small
+    /// data such as DWORDs is repeated everywhere), though all of it counts towards the sizes.
+    pub fn value_dedup(&self, min_size: usize) -> Result<DedupReport, Error> {
+        let report = DedupReport::new(ParserIterator::new(self).values(), min_size);
+        self.check_cancelled()?;
+        Ok(report)
+    }
+
+    /// Returns the length and character statistics of the key names and of the value names, along with the names that
+    /// stand out: longer than Windows allows, far longer than the hive's other names, holding control characters, or
+    /// holding characters that are rare in the hive (see `name_anomalies`).
+    pub fn anomaly_report(&self) -> Result<AnomalyReport, Error> {
+        let report = AnomalyReport::new(ParserIterator::new(self).iter());
+        self.check_cancelled()?;
+        Ok(report)
+    }
+
     /// Counts all subkeys and values
     pub(crate) fn _count_all_keys_and_values_with_modified(
         &mut self,
@@ -386,6 +823,86 @@ impl Parser {
         &self.state.info
     }
 
+    /// Returns where the base block was read from: the primary file, or the backup in a transaction log if the primary
+    /// file's base block had a bad checksum (or couldn't be parsed, see `ParserBuilderFromPath::recover_base_block`)
+    pub fn base_block_source(&self) -> BaseBlockSource {
+        self.base_block_source
+    }
+
+    /// Returns the transaction logs that were applied while building the parser, in the order they were applied
+    pub fn applied_transaction_logs(&self) -> &[AppliedTransactionLog] {
+        &self.applied_transaction_logs
+    }
+
+    /// Returns the order export writers write keys in, if one was set (see `ParserBuilderFromPath::sort_keys`)
+    pub fn key_sort(&self) -> Option<KeySort> {
+        self.key_sort
+    }
+
+    /// Returns the mapping export writers write full key paths with, if one was set (see `ParserBuilderFromPath::full_paths`)
+    pub fn path_mapping(&self) -> Option<&PathMapping> {
+        self.path_mapping.as_ref()
+    }
+
+    /// Returns the names iterators annotate keys and values named after a GUID with, if enabled
+    /// (see `ParserBuilderFromPath::guid_names`)
+    pub fn guid_names(&self) -> Option<&GuidNames> {
+        self.guid_names.as_ref()
+    }
+
+    /// Collects the log entries of the hive (base block, transaction logs, recovery) and of every key and value
+    /// (including those from decoding value content), with the offset and path of the item each entry belongs to.
+    /// This iterates the entire hive.
+    pub fn parse_warnings(&self) -> ParseWarnings {
+        let mut warnings = self.hive_warnings();
+        for key in ParserIterator::new(self).iter() {
+            warnings.add_key(&key);
+        }
+        warnings
+    }
+
+    /// Collects the log entries of the hive (base block, transaction logs, recovery) without those of its keys and values,
+    /// which can be added with `ParseWarnings::add_key` as they're read (ex: while an export is written, see
+    /// `set_key_handler`) rather than in a traversal of their own
+    pub fn hive_warnings(&self) -> ParseWarnings {
+        let mut warnings = ParseWarnings::default();
+        if let Some(base_block) = &self.base_block {
+            warnings.add_logs(&base_block.base.logs, Some(0), None, None);
+        }
+        warnings.add_logs(&self.state.info, None, None, None);
+        warnings
+    }
+
+    /// Sends each key the parser's iterators return to `handler` from now on (see `KeyHandler`), or stops sending them if
+    /// `handler` is None
+    pub fn set_key_handler(&mut self, handler: Option<Arc<dyn KeyHandler>>) {
+        self.progress.key_handler = handler;
+    }
+
+    /// Returns `Error::Cancelled` if the parser's cancellation token has been triggered. Iterators simply stop returning keys
+    /// once cancelled, so call this after iterating to tell a cancelled traversal from a complete one.
+    pub fn check_cancelled(&self) -> Result<(), Error> {
+        self.progress.check_cancelled()
+    }
+
+    /// Returns the hit, miss, and eviction counts of the cell cache (see `ParserBuilder::cell_cache_capacity`).
+    /// Only lookups made through this parser are counted; iterators work on their own copy of the cache.
+    pub fn cell_cache_stats(&self) -> CellCacheStats {
+        self.state.cell_cache.stats()
+    }
+
+    /// Returns a reader for looking up keys and values through a shared reference to the parser. `Parser` is `Send` and
+    /// `Sync`, so it can be put behind an `Arc` and queried from several threads at once, each with its own reader,
+    /// rather than behind a `Mutex`. A reader has its own cell cache (with the parser's capacity) and control set.
+    pub fn reader(&self) -> ParserReader<'_> {
+        let mut state = self.state.for_traversal();
+        state.cell_cache = CellCache::new(self.state.cell_cache.stats().capacity);
+        ParserReader {
+            parser: self,
+            state,
+        }
+    }
+
     pub fn next_key_postorder(
         &self,
         iter_context: &mut ParserIteratorContext,
@@ -406,22 +923,18 @@ impl Parser {
             }
 
             if let Some(mut node) = iter_context.pop_stack_to_traverse() {
-                if node.detail.number_of_sub_keys() > 0 {
-                    let (children, _) = node.read_sub_keys_internal(
-                        &self.file_info,
-                        &mut iter_context.state,
-                        &iter_context.filter,
-                        None,
-                        iter_context.get_modified_items,
-                    );
-                    node.iteration_state.to_return = children.len() as u32;
-                    for c in children.into_iter().rev() {
-                        let _ = iter_context.push_check_stack_to_traverse(c); // Come back to this. We should log if we get an error, but we need to rework things so self is mut, or pass in the logs directly.
+                for c in self
+                    .read_sub_keys_within_limits(iter_context, &mut node)
+                    .into_iter()
+                    .rev()
+                {
+                    if let Err(err) = iter_context.push_check_stack_to_traverse(c) {
+                        node.logs.add(LogCode::WarningCycle, &err);
                     }
                 }
                 for d in node.deleted_keys.iter_mut() {
                     d.iteration_state.filter_state = node.iteration_state.filter_state;
-                    iter_context.stack_to_traverse.push(d.clone()); // just push directly; don't call push_check_stack_to_traverse because we don't follow deleted keys. (Also, log errors todo ^^.)
+                    iter_context.stack_to_traverse.push(d.clone()); // just push directly; don't call push_check_stack_to_traverse because we don't follow deleted keys.
                 }
                 if !iter_context.stack_to_return.is_empty() {
                     let last = iter_context
@@ -449,22 +962,86 @@ impl Parser {
         None
     }
 
+    /// Reads the sub keys of `node` for a traversal, stopping at the depth and key count limits (see `Limits`)
+    fn read_sub_keys_within_limits(
+        &self,
+        iter_context: &mut ParserIteratorContext,
+        node: &mut CellKeyNode,
+    ) -> Vec<CellKeyNode> {
+        if node.detail.number_of_sub_keys() == 0 {
+            return Vec::new();
+        }
+        let limits = iter_context.state.limits;
+        if let Some(max_depth) = limits.max_depth {
+            if node.iteration_state.depth >= max_depth {
+                node.logs.add(
+                    LogCode::WarningLimit,
+                    &format!("Depth limit of {} reached; sub keys not read", max_depth),
+                );
+                return Vec::new();
+            }
+        }
+        // Sub keys stop being read once the key limit is reached
+        let max_sub_keys = limits
+            .max_keys
+            .map(|max_keys| max_keys.saturating_sub(iter_context.keys_found));
+        let (mut children, _) = node.read_sub_keys_internal(
+            &self.file_info,
+            &mut iter_context.state,
+            &iter_context.filter,
+            None,
+            iter_context.get_modified_items,
+            max_sub_keys,
+        );
+        iter_context.keys_found += children.len();
+        for c in children.iter_mut() {
+            c.iteration_state.depth = node.iteration_state.depth + 1;
+        }
+        node.iteration_state.to_return = children.len() as u32;
+        children
+    }
+
+    /// Returns the keys level by level: the root, then its sub keys, then theirs, and so on
+    pub fn next_key_breadth_first(
+        &self,
+        iter_context: &mut ParserIteratorContext,
+    ) -> Option<CellKeyNode> {
+        // the traversal starts from the root key on stack_to_traverse
+        iter_context
+            .queue_to_traverse
+            .extend(iter_context.stack_to_traverse.drain(..));
+        while let Some(mut node) = iter_context.queue_to_traverse.pop_front() {
+            for c in self.read_sub_keys_within_limits(iter_context, &mut node) {
+                if let Err(err) = iter_context.push_check_queue_to_traverse(c) {
+                    node.logs.add(LogCode::WarningCycle, &err);
+                }
+            }
+            for d in node.deleted_keys.iter_mut() {
+                d.iteration_state.filter_state = node.iteration_state.filter_state;
+                iter_context.queue_to_traverse.push_back(d.clone()); // we don't follow deleted keys (see next_key_preorder)
+            }
+            if iter_context.filter_include_ancestors
+                || !iter_context.filter.is_valid()
+                || node.is_filter_match_or_descendent()
+            {
+                return Some(node);
+            }
+        }
+        None
+    }
+
     pub fn next_key_preorder(
         &self,
         iter_context: &mut ParserIteratorContext,
     ) -> Option<CellKeyNode> {
         while let Some(mut node) = iter_context.pop_stack_to_traverse() {
-            if node.detail.number_of_sub_keys() > 0 {
-                let (children, _) = node.read_sub_keys_internal(
-                    &self.file_info,
-                    &mut iter_context.state,
-                    &iter_context.filter,
-                    None,
-                    iter_context.get_modified_items,
-                );
-                node.iteration_state.to_return = children.len() as u32;
-                for c in children.into_iter().rev() {
-                    let _ = iter_context.push_check_stack_to_traverse(c);
+            for c in self
+                .read_sub_keys_within_limits(iter_context, &mut node)
+                .into_iter()
+                .rev()
+            {
+                if let Err(err) = iter_context.push_check_stack_to_traverse(c) {
+                    node.logs.add(LogCode::WarningCycle, &err);
                 }
             }
             for d in node.deleted_keys.iter_mut() {
@@ -485,77 +1062,180 @@ impl Parser {
 // Direct key accessor methods - used by PyNotatin
 impl Parser {
     pub fn get_root_key(&mut self) -> Result<Option<CellKeyNode>, Error> {
-        match &self.base_block {
-            Some(bb) => {
-                let root = CellKeyNode::read(
-                    &self.file_info,
-                    &mut self.state,
-                    CellKeyNodeReadOptions {
-                        offset: bb.base.root_cell_offset_relative as usize
-                            + self.file_info.hbin_offset_absolute,
-                        cur_path: "",
-                        filter: None,
-                        self_is_filter_match_or_descendent: true,
-                        sequence_num: None,
-                        get_deleted_and_modified: true,
-                    },
-                )?;
-                Ok(root)
-            }
-            _ => Ok(None),
-        }
+        annotate(
+            self.guid_names.as_ref(),
+            read_root_key(
+                &self.file_info,
+                self.state.for_lookup(),
+                self.base_block.as_ref(),
+            ),
+        )
     }
 
+    /// Returns the sub key of `cell_key_node` named `name`. `name` is a single key name, not a path,
+    /// so it may contain backslashes. Names are compared ignoring ASCII case.
     pub fn get_sub_key(
         &mut self,
         cell_key_node: &mut CellKeyNode,
         name: &str,
     ) -> Result<Option<CellKeyNode>, Error> {
-        let key_path_sans_root =
-            &cell_key_node.path[self.state.get_root_path_offset(&cell_key_node.path)..];
-        let filter = FilterBuilder::new()
-            .add_key_path(&(key_path_sans_root.to_string() + "\\" + name))
-            .build()?;
-        cell_key_node
-            .read_sub_keys_internal(&self.file_info, &mut self.state, &filter, None, false)
-            .0
-            .get(0)
-            .map_or_else(|| Ok(None), |k| Ok(Some(k.clone())))
+        annotate(
+            self.guid_names.as_ref(),
+            Ok(cell_key_node.find_sub_key(&self.file_info, self.state.for_lookup(), name, false)),
+        )
     }
 
     pub fn get_key(
         &mut self,
-        mut key_path: &str,
+        key_path: &str,
         key_path_has_root: bool,
     ) -> Result<Option<CellKeyNode>, Error> {
-        match self.get_root_key() {
-            Ok(root) => {
-                if let Some(mut root) = root {
-                    // if key_path starts with '\\', strip it
-                    key_path = key_path.trim_start_matches('\\');
-                    // if key_path_has_root, strip that before searching
-                    if key_path_has_root {
-                        if let Some(slash_offset) = key_path.find('\\') {
-                            key_path = &key_path[slash_offset + 1..];
-                        } else {
-                            key_path = ""; // key_path _is_ root
-                        }
+        self.get_key_with_options(key_path, key_path_has_root, false)
+    }
+
+    /// Returns the key at `key_path`. A backslash within a key name is written as two backslashes (see `util::escape_key_name`).
+    /// Names are compared ignoring ASCII case unless `case_sensitive` is true.
+    pub fn get_key_with_options(
+        &mut self,
+        key_path: &str,
+        key_path_has_root: bool,
+        case_sensitive: bool,
+    ) -> Result<Option<CellKeyNode>, Error> {
+        annotate(
+            self.guid_names.as_ref(),
+            find_key(
+                &self.file_info,
+                self.state.for_lookup(),
+                self.base_block.as_ref(),
+                key_path,
+                key_path_has_root,
+                case_sensitive,
+            ),
+        )
+    }
+
+    /// Returns the decoded content of the value named `value_name` in the key at `key_path`, or None if there's no such key
+    /// or value. Like `get_key`, names are compared ignoring ASCII case; `""` is the key's default value.
+    /// Warnings from decoding the content are dropped (use `CellKeyValue::get_content` to get them).
+    pub fn get_value(
+        &mut self,
+        key_path: &str,
+        value_name: &str,
+    ) -> Result<Option<CellValue>, Error> {
+        Ok(self
+            .get_key(key_path, false)?
+            .and_then(|key| key.get_value(value_name))
+            .map(|value| value.get_content().0))
+    }
+
+    /// Looks up many values in one pass (ex: the Run keys and services of an autoruns sweep) and returns the decoded content
+    /// of each one found, keyed by its `(key_path, value_name)` lookup; lookups whose key or value is missing are left out.
+    /// Keys are found along the tree of the lookups' paths, so a prefix shared by several lookups
+    /// (ex: `Microsoft\Windows\CurrentVersion`) is only walked once, and each key's values are only read once.
+    /// Names are compared as they are by `get_value`.
+    pub fn get_values_bulk<'a>(
+        &mut self,
+        lookups: &[(&'a str, &'a str)],
+    ) -> Result<HashMap<(&'a str, &'a str), CellValue>, Error> {
+        let mut results = HashMap::new();
+        let root = match self.get_root_key()? {
+            Some(root) => root,
+            None => return Ok(results),
+        };
+        // sorted by path, so keys next to each other in the hive are looked up one after another
+        let mut by_key: BTreeMap<Vec<String>, Vec<(&'a str, &'a str)>> = BTreeMap::new();
+        for &(key_path, value_name) in lookups {
+            let resolved = match self.state.control_sets {
+                Some(control_sets) => control_sets.resolve_path(key_path),
+                None => Cow::Borrowed(key_path),
+            };
+            let names = util::split_key_path(resolved.trim_start_matches('\\'))
+                .into_iter()
+                .map(|name| name.to_ascii_lowercase())
+                .collect();
+            by_key
+                .entry(names)
+                .or_default()
+                .push((key_path, value_name));
+        }
+
+        let mut found = HashMap::new();
+        for (names, lookups) in by_key {
+            let key = match names.split_last() {
+                Some((_, parent_names)) => self
+                    .find_key_unread(&root, parent_names, &mut found)
+                    .zip(self.find_key_unread(&root, &names, &mut found))
+                    .and_then(|(parent, key)| {
+                        parent.read_sub_key(&self.file_info, &mut self.state, &key)
+                    }),
+                None => Some(root.clone()),
+            };
+            if let Some(key) = key {
+                for (key_path, value_name) in lookups {
+                    if let Some(value) = key.get_value(value_name) {
+                        results.insert((key_path, value_name), value.get_content().0);
                     }
-                    let key = root.get_sub_key_by_path(self, key_path);
-                    Ok(key)
-                } else {
-                    Ok(None)
                 }
             }
-            _ => Ok(None),
         }
+        Ok(results)
+    }
+
+    /// Returns the key at the path made of `names` (lowercase) without its values, remembering each key along the way in
+    /// `found` (None for keys that don't exist) so later lookups sharing a prefix don't walk it again
+    fn find_key_unread(
+        &mut self,
+        root: &CellKeyNode,
+        names: &[String],
+        found: &mut HashMap<Vec<String>, Option<CellKeyNode>>,
+    ) -> Option<CellKeyNode> {
+        let (name, parent_names) = match names.split_last() {
+            Some(split) => split,
+            None => return Some(root.clone()),
+        };
+        if let Some(key) = found.get(names) {
+            return key.clone();
+        }
+        let key = self
+            .find_key_unread(root, parent_names, found)
+            .and_then(|parent| {
+                parent.find_sub_key_unread(&self.file_info, &mut self.state, name, false)
+            });
+        found.insert(names.to_vec(), key.clone());
+        key
+    }
+
+    /// Reads the `Select` key of a SYSTEM hive to find which ControlSetNNN key is the current control set (along with the
+    /// default, failed, and last known good ones). Returns None if the hive doesn't have a `Select\Current` value.
+    ///
+    /// Afterwards, a `CurrentControlSet` segment in a `get_key` path or in a filter refers to the current control set,
+    /// as it would in a live registry.
+    pub fn resolve_current_control_set(&mut self) -> Result<Option<ControlSets>, Error> {
+        let select = match self.get_key("Select", false)? {
+            Some(select) => select,
+            None => return Ok(None),
+        };
+        // a value of 0 means there is no such control set
+        let get_number = |name| {
+            plugins::get_value_u64(&select, name)
+                .and_then(|number| u32::try_from(number).ok())
+                .filter(|number| *number != 0)
+        };
+        let control_sets = get_number("Current").map(|current| ControlSets {
+            current,
+            default: get_number("Default"),
+            failed: get_number("Failed"),
+            last_known_good: get_number("LastKnownGood"),
+        });
+        self.state.control_sets = control_sets;
+        Ok(control_sets)
     }
 
     pub fn get_parent_key(
         &mut self,
         cell_key_node: &mut CellKeyNode,
     ) -> Result<Option<CellKeyNode>, Error> {
-        let mut parent_path = cell_key_node.path.clone();
+        let mut parent_path = cell_key_node.path.to_string();
         if let Some(last_slash_offset) = parent_path.rfind('\\') {
             parent_path.truncate(last_slash_offset);
         }
@@ -564,7 +1244,7 @@ impl Parser {
         }
         let parent = CellKeyNode::read(
             &self.file_info,
-            &mut self.state,
+            self.state.for_lookup(),
             CellKeyNodeReadOptions {
                 offset: cell_key_node.detail.parent_key_offset_relative() as usize
                     + self.file_info.hbin_offset_absolute,
@@ -574,8 +1254,21 @@ impl Parser {
                 sequence_num: None,
                 get_deleted_and_modified: true,
             },
-        )?;
-        Ok(parent)
+        );
+        annotate(self.guid_names.as_ref(), parent)
+    }
+
+    /// Writes the key at `key_path` (not including the root key's name) and its allocated descendents, values,
+    /// class names, and security descriptors to `output` as a new hive. The key becomes the root key of the new hive.
+    pub fn export_subtree(
+        &mut self,
+        key_path: &str,
+        output: impl AsRef<Path>,
+    ) -> Result<(), Error> {
+        let mut key = self.get_key(key_path, false)?.ok_or_else(|| Error::Any {
+            detail: format!("export_subtree: key {} not found", key_path),
+        })?;
+        HiveWriter::from_parser_key(self, &mut key)?.write(output)
     }
 }
 
@@ -586,8 +1279,17 @@ pub struct ParserIteratorContext {
     stack_to_traverse: Vec<CellKeyNode>,
     stack_file_offsets: BTreeSet<usize>,
     stack_to_return: Vec<CellKeyNode>,
+    queue_to_traverse: VecDeque<CellKeyNode>,
     get_modified_items: bool,
     filter_include_ancestors: bool,
+    keys_processed: usize,
+    // The number of keys read so far (the root and the sub keys pushed to be traversed), checked against `Limits::max_keys`
+    keys_found: usize,
+    // Used to estimate the traversal percentage: the number of the root key's children pushed onto the stack,
+    // and the lowest the stack has been since (anything below that hasn't been started yet)
+    top_level_total: Option<usize>,
+    top_level_floor: usize,
+    breadth_first_percentage: f64,
 }
 
 impl ParserIteratorContext {
@@ -602,24 +1304,41 @@ impl ParserIteratorContext {
         let mut stack_file_offsets = BTreeSet::new();
         stack_file_offsets.insert(root.file_offset_absolute);
         ParserIteratorContext {
-            state: parser.state.clone(),
+            state: parser.state.for_traversal(),
             filter,
             stack_to_traverse: vec![root],
             stack_file_offsets,
             stack_to_return: vec![],
+            queue_to_traverse: VecDeque::new(),
             get_modified_items,
             filter_include_ancestors,
+            keys_processed: 0,
+            keys_found: 1,
+            top_level_total: None,
+            top_level_floor: 0,
+            breadth_first_percentage: 0.0,
         }
     }
 
     fn push_check_stack_to_traverse(&mut self, node_to_add: CellKeyNode) -> Result<(), Error> {
+        self.check_file_offset(&node_to_add)?;
+        self.stack_to_traverse.push(node_to_add);
+        Ok(())
+    }
+
+    fn push_check_queue_to_traverse(&mut self, node_to_add: CellKeyNode) -> Result<(), Error> {
+        self.check_file_offset(&node_to_add)?;
+        self.queue_to_traverse.push_back(node_to_add);
+        Ok(())
+    }
+
+    fn check_file_offset(&mut self, node_to_add: &CellKeyNode) -> Result<(), Error> {
         // Make sure the offset of what we're about to add is not the same as the offset of the current node, or of another node we are going to process.
         // Otherwise we could have a circular reference (this should only happen in recovery mode)
         if self
             .stack_file_offsets
             .insert(node_to_add.file_offset_absolute)
         {
-            self.stack_to_traverse.push(node_to_add);
             Ok(())
         } else {
             Err(Error::Any {
@@ -631,36 +1350,300 @@ impl ParserIteratorContext {
     fn pop_stack_to_traverse(&mut self) -> Option<CellKeyNode> {
         self.stack_to_traverse.pop()
     }
+
+    /// Counts a returned key and returns the estimated percentage of the traversal completed
+    fn track_progress(&mut self) -> f64 {
+        self.keys_processed += 1;
+        if !self.queue_to_traverse.is_empty() {
+            // Breadth first: the share of the keys found so far that have been returned. Keys are found faster than
+            // they are returned near the top of the tree, so don't let the estimate go backwards.
+            let found = self.keys_processed + self.queue_to_traverse.len();
+            self.breadth_first_percentage = self
+                .breadth_first_percentage
+                .max(100.0 * self.keys_processed as f64 / found as f64);
+            return self.breadth_first_percentage;
+        }
+        let remaining = self.stack_to_traverse.len();
+        if self.top_level_total.is_none() {
+            self.top_level_total = Some(remaining);
+            self.top_level_floor = remaining;
+        }
+        let total = self.top_level_total.unwrap_or_default();
+        self.top_level_floor = self.top_level_floor.min(remaining);
+        if total == 0 {
+            100.0
+        } else {
+            100.0 * (total - self.top_level_floor) as f64 / total as f64
+        }
+    }
 }
 
-#[derive(Clone)]
-pub struct ParserIterator<'a> {
-    parser: &'a Parser,
-    postorder_iteration: bool,
-    context: ParserIteratorContext,
+/// The order in which `ParserIterator` returns keys
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TraversalOrder {
+    /// Parents before their children (depth first)
+    #[default]
+    PreOrder,
+    /// Children before their parents (depth first); useful to aggregate statistics for each subtree
+    PostOrder,
+    /// Level by level, starting with the root key
+    BreadthFirst,
 }
 
-impl Iterator for ParserIterator<'_> {
-    type Item = CellKeyNode;
+/// A stable order for `ParserIterator` to return keys in, regardless of where they are stored in the hive
+/// (see `ParserIterator::sort_keys`)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeySort {
+    /// By path, comparing key names ignoring case; parents come before their children
+    Path,
+    /// By last written timestamp (oldest first), then by path
+    Timestamp,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.postorder_iteration {
-            self.parser.next_key_postorder(&mut self.context)
-        } else {
-            self.parser.next_key_preorder(&mut self.context)
+impl KeySort {
+    pub(crate) fn sort(&self, keys: &mut [CellKeyNode]) {
+        fn path_segments(key: &CellKeyNode) -> Vec<String> {
+            key.path.split('\\').map(str::to_lowercase).collect()
+        }
+        match self {
+            KeySort::Path => keys.sort_by_cached_key(|key| (path_segments(key), key.path.clone())),
+            KeySort::Timestamp => keys.sort_by_cached_key(|key| {
+                (
+                    key.last_key_written_filetime(),
+                    path_segments(key),
+                    key.path.clone(),
+                )
+            }),
         }
     }
 }
 
-impl<'a> ParserIterator<'a> {
-    pub fn new(parser: &'a Parser) -> Self {
-        let context = ParserIteratorContext::from_parser(parser, true, None);
-        ParserIterator {
-            parser,
-            postorder_iteration: false,
-            context,
-        }
-    }
+fn read_root_key(
+    file_info: &FileInfo,
+    state: &mut State,
+    base_block: Option<&BaseBlock>,
+) -> Result<Option<CellKeyNode>, Error> {
+    match base_block {
+        Some(bb) => CellKeyNode::read(
+            file_info,
+            state,
+            CellKeyNodeReadOptions {
+                offset: bb.base.root_cell_offset_relative as usize + file_info.hbin_offset_absolute,
+                cur_path: "",
+                filter: None,
+                self_is_filter_match_or_descendent: true,
+                sequence_num: None,
+                get_deleted_and_modified: true,
+            },
+        ),
+        _ => Ok(None),
+    }
+}
+
+/// Sets `guid_name` on a key returned by a lookup, if the parser was built with `ParserBuilderFromPath::guid_names`
+fn annotate(
+    guid_names: Option<&GuidNames>,
+    key: Result<Option<CellKeyNode>, Error>,
+) -> Result<Option<CellKeyNode>, Error> {
+    let mut key = key?;
+    if let (Some(guid_names), Some(key)) = (guid_names, key.as_mut()) {
+        guid_names.annotate(key);
+    }
+    Ok(key)
+}
+
+/// See `Parser::get_key_with_options`; reads with `state` so `ParserReader` can share it
+fn find_key(
+    file_info: &FileInfo,
+    state: &mut State,
+    base_block: Option<&BaseBlock>,
+    key_path: &str,
+    key_path_has_root: bool,
+    case_sensitive: bool,
+) -> Result<Option<CellKeyNode>, Error> {
+    let key_path = match state.control_sets {
+        Some(control_sets) => control_sets.resolve_path(key_path),
+        None => Cow::Borrowed(key_path),
+    };
+    let mut key_path = key_path.as_ref();
+    match read_root_key(file_info, state, base_block) {
+        Ok(root) => {
+            if let Some(root) = root {
+                // if key_path starts with '\\', strip it
+                key_path = key_path.trim_start_matches('\\');
+                // if key_path_has_root, strip that before searching
+                if key_path_has_root {
+                    if let Some(slash_offset) = key_path.find('\\') {
+                        key_path = &key_path[slash_offset + 1..];
+                    } else {
+                        key_path = ""; // key_path _is_ root
+                    }
+                }
+                let key = root.find_sub_key_path(file_info, state, key_path, case_sensitive);
+                Ok(key)
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Looks up keys and values through a shared reference to a `Parser` (see `Parser::reader`). The state that lookups
+/// change (the cell cache and the resolved control set) belongs to the reader, so each thread can have its own.
+pub struct ParserReader<'a> {
+    parser: &'a Parser,
+    state: State,
+}
+
+impl ParserReader<'_> {
+    pub fn get_root_key(&mut self) -> Result<Option<CellKeyNode>, Error> {
+        annotate(
+            self.parser.guid_names.as_ref(),
+            read_root_key(
+                &self.parser.file_info,
+                self.state.for_lookup(),
+                self.parser.base_block.as_ref(),
+            ),
+        )
+    }
+
+    pub fn get_key(
+        &mut self,
+        key_path: &str,
+        key_path_has_root: bool,
+    ) -> Result<Option<CellKeyNode>, Error> {
+        self.get_key_with_options(key_path, key_path_has_root, false)
+    }
+
+    /// See `Parser::get_key_with_options`
+    pub fn get_key_with_options(
+        &mut self,
+        key_path: &str,
+        key_path_has_root: bool,
+        case_sensitive: bool,
+    ) -> Result<Option<CellKeyNode>, Error> {
+        annotate(
+            self.parser.guid_names.as_ref(),
+            find_key(
+                &self.parser.file_info,
+                self.state.for_lookup(),
+                self.parser.base_block.as_ref(),
+                key_path,
+                key_path_has_root,
+                case_sensitive,
+            ),
+        )
+    }
+
+    /// See `Parser::get_value`
+    pub fn get_value(
+        &mut self,
+        key_path: &str,
+        value_name: &str,
+    ) -> Result<Option<CellValue>, Error> {
+        Ok(self
+            .get_key(key_path, false)?
+            .and_then(|key| key.get_value(value_name))
+            .map(|value| value.get_content().0))
+    }
+
+    /// Returns the hit, miss, and eviction counts of this reader's cell cache
+    pub fn cell_cache_stats(&self) -> CellCacheStats {
+        self.state.cell_cache.stats()
+    }
+}
+
+// Parsers are shared between threads (see `Parser::reader`); this stops compiling if a field makes that impossible
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Parser>();
+};
+
+#[derive(Clone)]
+pub struct ParserIterator<'a> {
+    parser: &'a Parser,
+    order: TraversalOrder,
+    context: ParserIteratorContext,
+    report_progress: bool,
+    sort: Option<KeySort>,
+    sorted: Option<std::vec::IntoIter<CellKeyNode>>,
+    path_mapping: Option<PathMapping>,
+}
+
+impl Iterator for ParserIterator<'_> {
+    type Item = CellKeyNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut key = match self.sort {
+            Some(sort) => {
+                if self.sorted.is_none() {
+                    let mut keys: Vec<_> = std::iter::from_fn(|| self.next_unsorted()).collect();
+                    sort.sort(&mut keys);
+                    self.sorted = Some(keys.into_iter());
+                }
+                self.sorted.as_mut()?.next()?
+            }
+            None => self.next_unsorted()?,
+        };
+        if let Some(path_mapping) = &self.path_mapping {
+            path_mapping.apply(&mut key);
+        }
+        if let Some(guid_names) = &self.parser.guid_names {
+            guid_names.annotate(&mut key);
+        }
+        if self.report_progress && !self.context.state.values_only {
+            self.parser.progress.report_key(&key);
+        }
+        Some(key)
+    }
+}
+
+impl<'a> ParserIterator<'a> {
+    fn next_unsorted(&mut self) -> Option<CellKeyNode> {
+        let progress = &self.parser.progress;
+        if progress.is_cancelled() {
+            return None;
+        }
+        let key = match self.order {
+            TraversalOrder::PreOrder => self.parser.next_key_preorder(&mut self.context),
+            TraversalOrder::PostOrder => self.parser.next_key_postorder(&mut self.context),
+            TraversalOrder::BreadthFirst => self.parser.next_key_breadth_first(&mut self.context),
+        };
+        if self.report_progress {
+            match key {
+                Some(_) => {
+                    let percentage = self.context.track_progress();
+                    if self.context.keys_processed % ProgressReporter::TRAVERSAL_INTERVAL == 1 {
+                        progress.report(
+                            ProgressPhase::Traversing,
+                            percentage,
+                            self.context.keys_processed,
+                        );
+                    }
+                }
+                None => progress.report(
+                    ProgressPhase::Traversing,
+                    100.0,
+                    self.context.keys_processed,
+                ),
+            }
+        }
+        key
+    }
+
+    pub fn new(parser: &'a Parser) -> Self {
+        let context = ParserIteratorContext::from_parser(parser, true, None);
+        ParserIterator {
+            parser,
+            order: TraversalOrder::PreOrder,
+            context,
+            report_progress: true,
+            sort: None,
+            sorted: None,
+            path_mapping: None,
+        }
+    }
 
     pub fn with_filter(&mut self, filter: Filter) -> &mut Self {
         self.context.filter = filter;
@@ -673,7 +1656,30 @@ impl<'a> ParserIterator<'a> {
     }
 
     pub fn postorder_iteration(&mut self, value: bool) -> &mut Self {
-        self.postorder_iteration = value;
+        self.order = if value {
+            TraversalOrder::PostOrder
+        } else {
+            TraversalOrder::PreOrder
+        };
+        self
+    }
+
+    pub fn traversal_order(&mut self, order: TraversalOrder) -> &mut Self {
+        self.order = order;
+        self
+    }
+
+    /// Returns keys in a stable order (so exports of the same data can be diffed) rather than in traversal order.
+    /// All the matching keys are read (and held in memory) before the first one is returned.
+    pub fn sort_keys(&mut self, sort: KeySort) -> &mut Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// Returns keys with their full paths (ex: `HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft`) rather than their paths within the
+    /// hive. Filters still match the paths within the hive.
+    pub fn full_paths(&mut self, path_mapping: PathMapping) -> &mut Self {
+        self.path_mapping = Some(path_mapping);
         self
     }
 
@@ -682,18 +1688,142 @@ impl<'a> ParserIterator<'a> {
         self
     }
 
+    /// Doesn't read the sub keys of keys `max_depth` levels below the root key (which is at depth 0), as
+    /// `ParserBuilderFromPath::max_depth` does for every traversal. The lower of the two limits applies.
+    pub fn max_depth(&mut self, max_depth: usize) -> &mut Self {
+        let limits = &mut self.context.state.limits;
+        let max_depth = limits
+            .max_depth
+            .map_or(max_depth, |limit| limit.min(max_depth));
+        limits.max_depth = Some(max_depth);
+        self
+    }
+
     pub fn iter(&mut self) -> Self {
         self.clone()
     }
+
+    /// Returns an iterator over the values of the keys this iterator would return, paired with the path of their key.
+    /// Values are moved out of each key rather than copied, and keys without values are skipped. Keys are only read as far
+    /// as needed to get to their values and sub keys: they don't keep their full field info, slack, or hash, their names
+    /// aren't checked, and they aren't passed to the key handler.
+    pub fn values(&mut self) -> ParserValueIterator<'a> {
+        let mut keys = self.iter();
+        keys.context.state.values_only = true;
+        if keys.context.keys_processed == 0 {
+            // The root key was read up front with the parser's settings, so it's read again the same way as the rest
+            if let [root] = keys.context.stack_to_traverse.as_mut_slice() {
+                if let Ok(Some(light_root)) = CellKeyNode::read(
+                    &keys.parser.file_info,
+                    &mut keys.context.state,
+                    CellKeyNodeReadOptions {
+                        offset: root.file_offset_absolute,
+                        cur_path: "",
+                        filter: None,
+                        self_is_filter_match_or_descendent: true,
+                        sequence_num: None,
+                        get_deleted_and_modified: true,
+                    },
+                ) {
+                    *root = light_root;
+                }
+            }
+        }
+        ParserValueIterator {
+            keys,
+            key_path: Arc::from(""),
+            values: Vec::new().into_iter(),
+        }
+    }
+
+    /// Returns an iterator over the keys this iterator would return and their values, flattened into `Record`s: each key,
+    /// then its earlier versions, then its values (each followed by its earlier versions). Timestamps in value data are
+    /// written in `timestamp_format`.
+    pub fn as_records(
+        &mut self,
+        timestamp_format: TimestampFormat,
+    ) -> impl Iterator<Item = Record> + 'a {
+        self.iter()
+            .flat_map(move |key| Record::from_key(&key, timestamp_format))
+    }
+
+    /// Returns an iterator over the values of the keys this iterator would return whose data holds a payload
+    /// (see `CellKeyValue::detect_payload`), paired with the path of their key and the payload found.
+    pub fn payloads(&mut self) -> impl Iterator<Item = (Arc<str>, CellKeyValue, Payload)> + 'a {
+        self.values().filter_map(|(key_path, value)| {
+            let payload = value.detect_payload()?;
+            Some((key_path, value, payload))
+        })
+    }
+
+    /// Returns an iterator over the keys this iterator would return whose names look made to hide them
+    /// (see `CellKeyNode::suspicious_name`), paired with the reason.
+    pub fn suspicious_names(&mut self) -> impl Iterator<Item = (CellKeyNode, SuspiciousName)> + 'a {
+        self.iter().filter_map(|key| {
+            let suspicious_name = key.suspicious_name()?;
+            Some((key, suspicious_name))
+        })
+    }
+
+    /// Returns an iterator over the values of the keys this iterator would return whose data matches at least one of `rules`,
+    /// paired with the path of their key and the matching rules. `timeout` is the per value scan timeout in seconds (0 for none).
+    #[cfg(feature = "yara")]
+    pub fn yara_matches(
+        &mut self,
+        rules: &'a yara::Rules,
+        timeout: i32,
+    ) -> impl Iterator<Item = Result<(Arc<str>, CellKeyValue, Vec<YaraMatch>), Error>> + 'a {
+        self.values().filter_map(move |(key_path, value)| {
+            let matches = yara_scan::scan(rules, value.get_data(), timeout);
+            match matches {
+                Ok(matches) if matches.is_empty() => None,
+                Ok(matches) => Some(Ok((key_path, value, matches))),
+                Err(e) => Some(Err(e)),
+            }
+        })
+    }
+
+    /// Stops this iterator from sending traversal updates to the parser's progress handler, and its keys to the parser's key
+    /// handler (for internal passes over the keys)
+    pub(crate) fn without_progress(&mut self) -> &mut Self {
+        self.report_progress = false;
+        self
+    }
+}
+
+/// Iterator over `(key path, value)` pairs; see `ParserIterator::values`
+pub struct ParserValueIterator<'a> {
+    keys: ParserIterator<'a>,
+    key_path: Arc<str>,
+    values: std::vec::IntoIter<CellKeyValue>,
+}
+
+impl Iterator for ParserValueIterator<'_> {
+    type Item = (Arc<str>, CellKeyValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.values.next() {
+                return Some((self.key_path.clone(), value));
+            }
+            let mut key = self.keys.next()?;
+            if !key.sub_values.is_empty() {
+                self.values = key.take_values().into_iter();
+                self.key_path = key.path;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cell_key_node::CellKeyNodeDetailEnum;
     use crate::err::Error;
     use crate::filter::FilterBuilder;
     use crate::parser_builder::ParserBuilder;
     use md5;
+    use std::io::Cursor;
 
     #[test]
     fn test_parser_iter_postorder() {
@@ -710,7 +1840,7 @@ mod tests {
             .postorder_iteration(true)
             .iter()
         {
-            md5_context.consume(key.path);
+            md5_context.consume(key.path.as_bytes());
         }
         assert_eq!(
             "7e0d357766857c0524cc78d622709da9",
@@ -749,6 +1879,409 @@ mod tests {
         assert_eq!((2853, 5523), (keys, values));
     }
 
+    #[test]
+    fn test_parser_iterator_values() {
+        const ROOT: &str = r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}";
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let mut values = 0;
+        let mut data_size = 0;
+        for (key_path, value) in ParserIterator::new(&parser).values() {
+            values += 1;
+            data_size += value.get_data().len();
+            assert!(key_path.starts_with(ROOT));
+        }
+        assert_eq!(5523, values);
+
+        let mut expected_data_size = 0;
+        for key in ParserIterator::new(&parser).iter() {
+            expected_data_size += key.value_iter().map(|v| v.get_data().len()).sum::<usize>();
+        }
+        assert_eq!(expected_data_size, data_size);
+
+        let filter = FilterBuilder::new()
+            .add_key_path("Control Panel\\Accessibility\\Keyboard Response")
+            .build()
+            .unwrap();
+        let filtered: Vec<_> = ParserIterator::new(&parser)
+            .with_filter(filter)
+            .values()
+            .map(|(key_path, value)| (key_path.to_string(), value.detail.value_name()))
+            .collect();
+        assert_eq!(9, filtered.len());
+        assert_eq!(
+            (
+                format!("{}\\Control Panel\\Accessibility\\Keyboard Response", ROOT),
+                "Last Valid Wait".to_string()
+            ),
+            filtered[0]
+        );
+
+        // the keys are read without their full field info, even when the parser keeps it
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .get_full_field_info(true)
+            .build()
+            .unwrap();
+        let mut iter = ParserIterator::new(&parser);
+        let expected: Vec<_> = iter
+            .iter()
+            .flat_map(|key| {
+                let key_path = key.path.clone();
+                key.value_iter()
+                    .map(|value| {
+                        (
+                            key_path.clone(),
+                            value.detail.value_name(),
+                            value.get_data().to_vec(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let mut values = iter.values();
+        let key = values.keys.next().unwrap();
+        assert!(matches!(key.detail, CellKeyNodeDetailEnum::Light(_)));
+        assert!(key.hash.is_none());
+        let actual: Vec<_> = iter
+            .values()
+            .map(|(key_path, value)| {
+                (
+                    key_path,
+                    value.detail.value_name(),
+                    value.get_data().to_vec(),
+                )
+            })
+            .collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_parser_iterator_traversal_order() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let depth = |path: &str| path.matches('\\').count();
+
+        let preorder: Vec<String> = ParserIterator::new(&parser)
+            .map(|key| key.path.to_string())
+            .collect();
+        let postorder: Vec<String> = ParserIterator::new(&parser)
+            .traversal_order(TraversalOrder::PostOrder)
+            .iter()
+            .map(|key| key.path.to_string())
+            .collect();
+        let breadth_first: Vec<String> = ParserIterator::new(&parser)
+            .traversal_order(TraversalOrder::BreadthFirst)
+            .iter()
+            .map(|key| key.path.to_string())
+            .collect();
+        assert_eq!(2853, breadth_first.len());
+        assert_eq!(preorder.first(), postorder.last());
+        assert_eq!(preorder.first(), breadth_first.first());
+        assert!(breadth_first
+            .windows(2)
+            .all(|pair| depth(&pair[0]) <= depth(&pair[1])));
+        assert_eq!("\\AppEvents", &breadth_first[1][breadth_first[0].len()..]);
+
+        let mut sorted_preorder = preorder.clone();
+        sorted_preorder.sort();
+        let mut sorted_breadth_first = breadth_first;
+        sorted_breadth_first.sort();
+        assert_eq!(sorted_preorder, sorted_breadth_first);
+
+        let filter = FilterBuilder::new()
+            .add_key_path("Control Panel\\Accessibility")
+            .return_child_keys(true)
+            .build()
+            .unwrap();
+        let filtered: Vec<String> = ParserIterator::new(&parser)
+            .with_filter(filter)
+            .traversal_order(TraversalOrder::BreadthFirst)
+            .iter()
+            .map(|key| key.path.to_string())
+            .collect();
+        assert!(filtered[0].ends_with("\\Control Panel\\Accessibility"));
+        assert!(filtered[1..]
+            .iter()
+            .all(|path| path.starts_with(&filtered[0]) && depth(path) > depth(&filtered[0])));
+    }
+
+    #[test]
+    fn test_parser_iterator_sort_keys() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let segments =
+            |path: &str| -> Vec<String> { path.split('\\').map(str::to_lowercase).collect() };
+
+        let by_path: Vec<String> = ParserIterator::new(&parser)
+            .sort_keys(KeySort::Path)
+            .iter()
+            .map(|key| key.path.to_string())
+            .collect();
+        assert_eq!(2853, by_path.len());
+        assert!(by_path
+            .windows(2)
+            .all(|pair| segments(&pair[0]) <= segments(&pair[1])));
+        let mut preorder: Vec<String> = ParserIterator::new(&parser)
+            .map(|key| key.path.to_string())
+            .collect();
+        preorder.sort_by_key(|path| segments(path));
+        assert_eq!(preorder, by_path);
+
+        let by_timestamp: Vec<u64> = ParserIterator::new(&parser)
+            .sort_keys(KeySort::Timestamp)
+            .iter()
+            .map(|key| key.last_key_written_filetime())
+            .collect();
+        assert_eq!(2853, by_timestamp.len());
+        assert!(by_timestamp.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_subtree_hash() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let mut builder =
+            ParserBuilder::from_file(Cursor::new(std::fs::read("test_data/NTUSER.DAT").unwrap()));
+        builder.recover_deleted(true);
+        let other = builder.build().unwrap();
+
+        let hash = parser
+            .subtree_hash("Control Panel\\Accessibility", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Some(hash),
+            other
+                .subtree_hash("\\Control Panel\\Accessibility\\", false)
+                .unwrap()
+        );
+        assert_ne!(
+            Some(hash),
+            parser
+                .subtree_hash("Control Panel\\Accessibility", true)
+                .unwrap()
+        );
+        assert_ne!(
+            Some(hash),
+            parser.subtree_hash("Control Panel", false).unwrap()
+        );
+        assert_eq!(
+            parser.subtree_hash("", true).unwrap(),
+            other.subtree_hash("", true).unwrap()
+        );
+        assert_eq!(None, parser.subtree_hash("Nope", false).unwrap());
+    }
+
+    #[test]
+    fn test_value_dedup() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let report = parser.value_dedup(16).unwrap();
+        let stats = parser.subtree_stats("").unwrap().unwrap();
+        assert_eq!(
+            (stats.value_count, stats.data_size),
+            (report.value_count, report.data_size)
+        );
+        assert!(report.unique_data_size <= report.data_size);
+        assert!(!report.duplicates.is_empty());
+        for (duplicate, next) in report
+            .duplicates
+            .iter()
+            .zip(report.duplicates.iter().skip(1))
+        {
+            assert!(duplicate.size >= 16);
+            assert!(duplicate.values.len() > 1);
+            assert!(duplicate.redundant_size() >= next.redundant_size());
+        }
+        let redundant: u64 = report
+            .duplicates
+            .iter()
+            .map(|duplicate| duplicate.redundant_size())
+            .sum();
+        assert!(redundant <= report.redundant_size());
+    }
+
+    #[test]
+    fn test_subtree_stats() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let stats = parser.subtree_stats("").unwrap().unwrap();
+        assert_eq!((2853, 5523), (stats.key_count, stats.value_count));
+
+        let stats = parser
+            .subtree_stats("Control Panel\\Accessibility")
+            .unwrap()
+            .unwrap();
+        let (keys, values) = parser.count_all_keys_and_values(Some(
+            &FilterBuilder::new()
+                .add_key_path("Control Panel\\Accessibility")
+                .return_child_keys(true)
+                .build()
+                .unwrap(),
+        ));
+        assert_eq!((keys, values), (stats.key_count, stats.value_count));
+        assert_eq!(1, stats.max_depth);
+        assert!(stats
+            .deepest_path
+            .contains("\\Control Panel\\Accessibility\\"));
+        let accessibility = parser
+            .get_key("Control Panel\\Accessibility", false)
+            .unwrap()
+            .unwrap();
+        assert!(
+            stats.last_key_written_date_and_time.unwrap()
+                >= accessibility.last_key_written_date_and_time()
+        );
+        assert!(stats.data_size > 0);
+
+        assert_eq!(None, parser.subtree_stats("Does not exist").unwrap());
+    }
+
+    #[test]
+    fn test_get_value() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let key_path = "Control Panel\\Accessibility\\Keyboard Response";
+        let key = parser.get_key(key_path, false).unwrap().unwrap();
+        assert_eq!(
+            Some(
+                key.get_value("DelayBeforeAcceptance")
+                    .unwrap()
+                    .get_content()
+                    .0
+            ),
+            parser.get_value(key_path, "delaybeforeacceptance").unwrap()
+        );
+        assert_eq!(None, parser.get_value(key_path, "Nope").unwrap());
+        assert_eq!(
+            None,
+            parser.get_value("Nope", "DelayBeforeAcceptance").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_values_bulk() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let key_paths = [
+            "Control Panel\\Accessibility\\Keyboard Response",
+            "Control Panel\\Accessibility\\MouseKeys",
+            "\\control panel\\accessibility\\mousekeys",
+        ];
+        let mut names = Vec::new();
+        for key_path in &key_paths[..2] {
+            let key = parser.get_key(key_path, false).unwrap().unwrap();
+            names.extend(key.value_iter().map(|value| value.get_pretty_name()));
+        }
+        let mut lookups: Vec<(&str, &str)> = Vec::new();
+        for key_path in &key_paths {
+            lookups.extend(names.iter().map(|name| (*key_path, name.as_str())));
+        }
+        lookups.push(("Control Panel\\Nope", "DelayBeforeAcceptance"));
+        lookups.push((key_paths[0], "Nope"));
+
+        let results = parser.get_values_bulk(&lookups).unwrap();
+        assert!(!results.is_empty());
+        for (key_path, value_name) in lookups {
+            assert_eq!(
+                parser.get_value(key_path, value_name).unwrap().as_ref(),
+                results.get(&(key_path, value_name))
+            );
+        }
+    }
+
+    #[test]
+    fn test_reader() {
+        let parser = Arc::new(
+            ParserBuilder::from_path("test_data/NTUSER.DAT")
+                .build()
+                .unwrap(),
+        );
+        let key_path = "Control Panel\\Accessibility\\Keyboard Response";
+        let expected = parser
+            .reader()
+            .get_value(key_path, "DelayBeforeAcceptance")
+            .unwrap();
+        assert!(expected.is_some());
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let parser = Arc::clone(&parser);
+                std::thread::spawn(move || {
+                    let mut reader = parser.reader();
+                    let key = reader.get_key(key_path, false).unwrap().unwrap();
+                    let value = reader.get_value(key_path, "DelayBeforeAcceptance").unwrap();
+                    (key.path, value)
+                })
+            })
+            .collect();
+        for thread in threads {
+            let (path, value) = thread.join().unwrap();
+            assert!(path.ends_with(key_path));
+            assert_eq!(expected, value);
+        }
+    }
+
+    #[test]
+    fn test_resolve_current_control_set() {
+        let mut parser = ParserBuilder::from_path("test_data/system")
+            .build()
+            .unwrap();
+        let filter = FilterBuilder::new()
+            .add_key_path("CurrentControlSet\\Services\\Dhcp")
+            .build()
+            .unwrap();
+        assert_eq!(
+            None,
+            parser
+                .get_key("CurrentControlSet\\Services", false)
+                .unwrap()
+        );
+        assert_eq!(
+            0,
+            ParserIterator::new(&parser)
+                .with_filter(filter.clone())
+                .iter()
+                .count()
+        );
+
+        let control_sets = parser.resolve_current_control_set().unwrap().unwrap();
+        assert_eq!(
+            ControlSets {
+                current: 1,
+                default: Some(1),
+                failed: None,
+                last_known_good: Some(2)
+            },
+            control_sets
+        );
+        let services = parser
+            .get_key("currentcontrolset\\Services", false)
+            .unwrap()
+            .unwrap();
+        assert!(services.path.ends_with("\\ControlSet001\\Services"));
+        let keys: Vec<_> = ParserIterator::new(&parser)
+            .with_filter(filter)
+            .iter()
+            .map(|key| key.path)
+            .collect();
+        assert_eq!(1, keys.len());
+        assert!(keys[0].ends_with("\\ControlSet001\\Services\\Dhcp"));
+
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        assert_eq!(None, parser.resolve_current_control_set().unwrap());
+    }
+
     #[test]
     fn test_parser_iterator_postorder_next() {
         let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
@@ -777,7 +2310,7 @@ mod tests {
         let (keys, keys_versions, keys_deleted, values, values_versions, values_deleted) =
             parser._count_all_keys_and_values_with_modified(None);
         assert_eq!(
-            (45587, 289, 31, 108178, 139, 244),
+            (45587, 278, 135, 108245, 139, 267),
             (
                 keys,
                 keys_versions,
@@ -863,7 +2396,7 @@ mod tests {
             .postorder_iteration(true)
             .iter()
         {
-            md5_context.consume(key.path);
+            md5_context.consume(key.path.as_bytes());
         }
         assert_eq!(
             "c04d7a8f64b35f46bc93490701afbaf0",
@@ -921,43 +2454,43 @@ mod tests {
             .get_key("Control Panel\\Accessibility\\Keyboard Response", false)
             .unwrap()
             .unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", sub_key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", &*sub_key.path);
         assert_eq!(9, sub_key.sub_values.len());
 
         let sub_key = parser
             .get_key("\\Control Panel\\Accessibility\\Keyboard Response", false)
             .unwrap()
             .unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", sub_key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", &*sub_key.path);
         assert_eq!(9, sub_key.sub_values.len());
 
         let sub_key = parser
             .get_key("Control Panel\\Accessibility", false)
             .unwrap()
             .unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility", sub_key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility", &*sub_key.path);
         assert_eq!(2, sub_key.sub_values.len());
 
         let sub_key = parser.get_key("Control Panel\\Accessibility\\XYZ", false);
         assert_eq!(Ok(None), sub_key);
 
         let sub_key = parser.get_key("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", true).unwrap().unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", sub_key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", &*sub_key.path);
         assert_eq!(9, sub_key.sub_values.len());
 
         let sub_key = parser.get_key("CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", true).unwrap().unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", sub_key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", &*sub_key.path);
         assert_eq!(9, sub_key.sub_values.len());
 
         let mut key = parser.get_key("Control Panel", false).unwrap().unwrap();
         assert_eq!(
             "\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel",
-            key.path
+            &*key.path
         );
         let sub_key = key
             .get_sub_key_by_path(&mut parser, "Accessibility\\Keyboard Response")
             .unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", sub_key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", &*sub_key.path);
         assert_eq!(9, sub_key.sub_values.len());
     }
 
@@ -975,7 +2508,7 @@ mod tests {
             .next()
             .unwrap();
         let sub_key = parser.get_sub_key(&mut key, "Keyboard Response")?.unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", sub_key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", &*sub_key.path);
         assert_eq!(9, sub_key.sub_values.len());
 
         let sub_key = parser.get_sub_key(&mut key, "Nope").unwrap();
@@ -992,7 +2525,7 @@ mod tests {
             .next()
             .unwrap();
         let sub_key = parser.get_sub_key(&mut key, "Keyboard Response")?.unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", sub_key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", &*sub_key.path);
         assert_eq!(9, sub_key.sub_values.len());
 
         let sub_key = parser.get_sub_key(&mut key, "Nope").unwrap();
@@ -1015,11 +2548,59 @@ mod tests {
             .next()
             .unwrap();
         let parent_key = parser.get_parent_key(&mut key)?.unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility", parent_key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility", &*parent_key.path);
         assert_eq!(2, parent_key.sub_values.len());
         Ok(())
     }
 
+    #[test]
+    fn test_export_subtree() -> Result<(), Error> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("subtree.dat");
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT").build()?;
+        parser.export_subtree("Control Panel\\Accessibility", &path)?;
+        let mut subtree = ParserBuilder::from_path(path.clone()).build()?;
+
+        let original_root = parser
+            .get_key("Control Panel\\Accessibility", false)?
+            .unwrap();
+        let original: Vec<(String, usize)> = ParserIterator::new(&parser)
+            .iter()
+            .filter(|key| key.path.starts_with(&*original_root.path))
+            .map(|key| {
+                (
+                    key.path[original_root.path.len()..].to_string(),
+                    key.value_iter().count(),
+                )
+            })
+            .collect();
+        let root = subtree.get_root_key()?.unwrap();
+        assert_eq!("\\Accessibility", &*root.path);
+        let exported: Vec<(String, usize)> = ParserIterator::new(&subtree)
+            .iter()
+            .map(|key| {
+                (
+                    key.path[root.path.len()..].to_string(),
+                    key.value_iter().count(),
+                )
+            })
+            .collect();
+        assert_eq!(original, exported);
+        assert_eq!(14, exported.len());
+
+        assert_eq!(
+            Some("80".to_string()),
+            subtree
+                .get_key("MouseKeys", false)?
+                .unwrap()
+                .get_value("MaximumSpeed")
+                .map(|value| value.get_content().0.to_string())
+        );
+        assert!(parser.export_subtree("Does not exist", &path).is_err());
+        dir.close()?;
+        Ok(())
+    }
+
     #[test]
     fn test_get_root_key() {
         let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
@@ -1028,11 +2609,35 @@ mod tests {
         let root_key = parser.get_root_key().unwrap().unwrap();
         assert_eq!(
             "\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}",
-            root_key.path
+            &*root_key.path
         );
         assert_eq!(0, root_key.sub_values.len());
     }
 
+    #[test]
+    fn test_hive_capabilities() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let capabilities = parser.hive_capabilities();
+        assert_eq!(
+            (1, 5),
+            (capabilities.major_version, capabilities.minor_version)
+        );
+        assert!(capabilities.big_data && capabilities.hash_leaf_lists);
+        assert!(!capabilities.layered_keys);
+        assert_eq!(capabilities, parser.base_block().unwrap().capabilities());
+
+        let parser = ParserBuilder::from_path("test_data/win7_ntuser.dat")
+            .build()
+            .unwrap();
+        let capabilities = parser.hive_capabilities();
+        assert_eq!(3, capabilities.minor_version);
+        assert!(capabilities.fast_leaf_lists);
+        assert!(!capabilities.big_data && !capabilities.hash_leaf_lists);
+        assert!(parser.get_parse_logs().get().is_none());
+    }
+
     #[test]
     fn test_reg_query() -> Result<(), Error> {
         let filter = FilterBuilder::new()
@@ -1049,9 +2654,9 @@ mod tests {
             .postorder_iteration(true)
             .iter();
         let key = parser_iter.next().unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Preference", key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Preference", &*key.path);
         let key = parser_iter.next().unwrap();
-        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", key.path);
+        assert_eq!("\\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\\Control Panel\\Accessibility\\Keyboard Response", &*key.path);
         let kv = parser.count_all_keys_and_values(Some(&filter));
         assert_eq!((2, 10), kv, "if we get 12 values back then we are incorrectly returning values for the Control Panel\\Accessibility key");
 
@@ -1071,28 +2676,98 @@ mod tests {
         let key = parser_iter.next().unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\AppEvents\Schemes\Apps\Explorer\ActivatingDocument\.Current",
-            key.path
+            &*key.path
         );
         let key = parser_iter.next().unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\AppEvents\Schemes\Apps\Explorer\ActivatingDocument",
-            key.path
+            &*key.path
         );
         let key = parser_iter.next().unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\AppEvents\Schemes\Apps\Explorer\Navigating\.Current",
-            key.path
+            &*key.path
         );
         let key = parser_iter.next().unwrap();
         assert_eq!(
             r"\CsiTool-CreateHive-{00000000-0000-0000-0000-000000000000}\AppEvents\Schemes\Apps\Explorer\Navigating",
-            key.path
+            &*key.path
         );
         let kv = parser.count_all_keys_and_values(Some(&filter));
         assert_eq!((4, 4), kv);
         Ok(())
     }
 
+    #[test]
+    fn test_count() -> Result<(), Error> {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT").build()?;
+        assert_eq!((2853, 5523), parser.count(None)?);
+
+        let filters = [
+            FilterBuilder::new()
+                .add_key_path("Control Panel\\Accessibility")
+                .return_child_keys(true)
+                .build()?,
+            FilterBuilder::new()
+                .add_key_path("Control Panel\\Accessibility")
+                .build()?,
+            FilterBuilder::new()
+                .add_literal_segment("control Panel")
+                .add_regex_segment("access.*")
+                .add_regex_segment("keyboard.+")
+                .build()?,
+            FilterBuilder::new()
+                .add_key_path("no such key")
+                .return_child_keys(true)
+                .build()?,
+        ];
+        for filter in &filters {
+            assert_eq!(
+                parser.count_all_keys_and_values(Some(filter)),
+                parser.count(Some(filter))?
+            );
+        }
+        assert_eq!((0, 0), parser.count(Some(&filters[3]))?);
+
+        let parser = ParserBuilder::from_path("test_data/system")
+            .with_transaction_log("test_data/system.log1")
+            .with_transaction_log("test_data/system.log2")
+            .build()?;
+        assert_eq!(parser.count_all_keys_and_values(None), parser.count(None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_handler() -> Result<(), Error> {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT").build()?;
+        let paths = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let handler_paths = paths.clone();
+        parser.set_key_handler(Some(Arc::new(move |key: &CellKeyNode| {
+            handler_paths.lock().unwrap().push(key.path.clone())
+        })));
+        let filter = FilterBuilder::new()
+            .add_key_path("Control Panel\\Accessibility")
+            .return_child_keys(true)
+            .build()?;
+        let returned: Vec<_> = ParserIterator::new(&parser)
+            .with_filter(filter.clone())
+            .iter()
+            .map(|key| key.path)
+            .collect();
+        assert!(!returned.is_empty());
+        assert_eq!(returned, *paths.lock().unwrap());
+
+        // internal passes don't send their keys
+        paths.lock().unwrap().clear();
+        for _ in ParserIterator::new(&parser).without_progress().iter() {}
+        assert!(paths.lock().unwrap().is_empty());
+
+        parser.set_key_handler(None);
+        for _ in ParserIterator::new(&parser).with_filter(filter).iter() {}
+        assert!(paths.lock().unwrap().is_empty());
+        Ok(())
+    }
+
     #[test]
     fn test_push_stack_to_traverse() -> Result<(), Error> {
         let parser = ParserBuilder::from_path("test_data/NTUSER.DAT").build()?;
@@ -14,13 +14,22 @@
  * limitations under the License.
  */
 
+use crate::base_block::BaseBlockSource;
+use crate::cell_cache::CellCache;
+use crate::cell_extents::{CellExtents, OverlapCheck};
+use crate::cell_key_node::ParentOffsetCheck;
 use crate::err::Error;
-use crate::file_info::{FileInfo, ReadSeek};
+use crate::file_info::{FileInfo, ReadSeek, ReadSeekRange};
 use crate::filter::Filter;
-use crate::parser::Parser;
-use crate::state::State;
-use crate::transaction_log::TransactionLog;
+use crate::guid_names::GuidNames;
+use crate::parser::{KeySort, Parser};
+use crate::path_mapping::PathMapping;
+use crate::progress::{CancellationToken, ProgressHandler, ProgressReporter};
+use crate::state::{Limits, State};
+use crate::transaction_log::{LogEntryHashPolicy, ReplayScope, TransactionLog};
 use std::path::Path;
+#[cfg(feature = "tokio")]
+use tokio::io::AsyncRead;
 
 #[derive(Clone, Default)]
 pub struct ParserBuilderBase {
@@ -28,8 +37,208 @@ pub struct ParserBuilderBase {
     recover_deleted: bool,
     get_full_field_info: bool,
     update_console: bool,
+    strict: bool,
+    recover_base_block: bool,
+    replay_scope: Option<ReplayScope>,
+    log_entry_hash_policy: LogEntryHashPolicy,
+    parent_offset_check: Option<ParentOffsetCheck>,
+    overlap_check: Option<OverlapCheck>,
+    limits: Limits,
+    app_hive: bool,
+    check_data_types: bool,
+    detect_payloads: bool,
+    detect_suspicious_names: bool,
+    value_stats: bool,
+    guid_names: bool,
+    key_sort: Option<KeySort>,
+    path_mapping: Option<PathMapping>,
+    cell_cache_capacity: usize,
+    progress: ProgressReporter,
 }
 
+/// Implements the options stored in `ParserBuilderBase`, which the builders share
+macro_rules! impl_parser_builder_options {
+    ($builder: ident) => {
+        impl $builder {
+            pub fn recover_deleted(&mut self, recover: bool) -> &mut Self {
+                self.base.recover_deleted = recover;
+                self
+            }
+
+            pub fn get_full_field_info(&mut self, get_full_field_info: bool) -> &mut Self {
+                self.base.get_full_field_info = get_full_field_info;
+                self
+            }
+
+            /// If true, `build` fails when the hive has any structural inconsistency (see `Parser::validate`) or still needs
+            /// recovery after its transaction logs are applied. Unreferenced cells (`AnomalyType::UnreferencedCell`), which
+            /// are normal in healthy hives, aren't counted. Defaults to false: inconsistencies are logged and parsing continues.
+            pub fn strict(&mut self, strict: bool) -> &mut Self {
+                self.base.strict = strict;
+                self
+            }
+
+            /// If true, a primary file whose base block can't be parsed is read with the backup base block of its newest transaction
+            /// log (one with a valid checksum) rather than failing. A base block with a bad checksum is always replaced this way when
+            /// transaction logs are provided. See `Parser::base_block_source`. Defaults to false.
+            pub fn recover_base_block(&mut self, recover_base_block: bool) -> &mut Self {
+                self.base.recover_base_block = recover_base_block;
+                self
+            }
+
+            /// Only replays the transaction log entries with a dirty page in `replay_scope`, a hive offset range or a key subtree.
+            /// The result is an up-to-date view of what's in scope (the rest of the hive may be partially updated) and is faster to
+            /// produce than a full replay. Skipped entries are noted in the parse logs. Defaults to replaying every entry.
+            pub fn replay_only(&mut self, replay_scope: ReplayScope) -> &mut Self {
+                self.base.replay_scope = Some(replay_scope);
+                self
+            }
+
+            /// What to do with a log entry whose hashes don't match its contents: stop applying the log (the default, as Windows
+            /// does), skip the entry, or apply it with a warning. Partially damaged logs are common in acquired evidence.
+            /// Mismatches and a count of each log's valid and invalid entries are noted in the parse logs.
+            pub fn log_entry_hash_policy(
+                &mut self,
+                log_entry_hash_policy: LogEntryHashPolicy,
+            ) -> &mut Self {
+                self.base.log_entry_hash_policy = log_entry_hash_policy;
+                self
+            }
+
+            /// Checks that each sub key's parent offset points back to the key that lists it while sub keys are read.
+            /// Keys hidden by pointing them at a different parent are flagged (`ParentOffsetCheck::Flag`) or left out
+            /// (`ParentOffsetCheck::Skip`). Defaults to `Flag` in strict mode and `Off` otherwise.
+            pub fn verify_parent_offsets(&mut self, check: ParentOffsetCheck) -> &mut Self {
+                self.base.parent_offset_check = Some(check);
+                self
+            }
+
+            /// Checks that each key and value cell doesn't overlap a cell read before it (a sign of corruption, or of cells
+            /// crafted to show different data depending on how they're reached). The cell read first keeps its bytes: cells
+            /// overlapping it are flagged (`OverlapCheck::Flag`) or left out (`OverlapCheck::Skip`), and both offsets are logged
+            /// as `LogCode::WarningOverlap`. Defaults to `Flag` in strict mode and `Off` otherwise.
+            pub fn check_overlapping_cells(&mut self, check: OverlapCheck) -> &mut Self {
+                self.base.overlap_check = Some(check);
+                self
+            }
+
+            /// Stops traversals from reading sub keys more than `max_depth` levels below the root key. The keys at the limit
+            /// are returned with a `LogCode::WarningLimit` log. Defaults to unlimited.
+            pub fn max_depth(&mut self, max_depth: usize) -> &mut Self {
+                self.base.limits.max_depth = Some(max_depth);
+                self
+            }
+
+            /// Stops traversals from reading more sub keys once `max_keys` keys have been found (ex: in a hive whose sub key
+            /// lists were crafted to be huge). The key whose sub keys were cut short gets a `LogCode::WarningLimit` log.
+            /// Defaults to unlimited.
+            pub fn max_keys(&mut self, max_keys: usize) -> &mut Self {
+                self.base.limits.max_keys = Some(max_keys);
+                self
+            }
+
+            /// Skips reading value data larger than `max_value_size` bytes; such values are empty and have a
+            /// `LogCode::WarningLimit` log. Defaults to unlimited.
+            pub fn max_value_size(&mut self, max_value_size: usize) -> &mut Self {
+                self.base.limits.max_value_size = Some(max_value_size);
+                self
+            }
+
+            /// Parses the hive as an application hive (one loaded with `RegLoadAppKey`, such as a UWP app's settings.dat).
+            /// UWP values keep the modification timestamp stored after their data (see `CellKeyValue::uwp_timestamp`), and in
+            /// strict mode a primary file that wasn't fully flushed isn't an error, since app hives are often collected without
+            /// their transaction logs. Defaults to false.
+            pub fn app_hive(&mut self, app_hive: bool) -> &mut Self {
+                self.base.app_hive = app_hive;
+                self
+            }
+
+            /// Logs `LogCode::WarningDataType` for values whose data doesn't look like their declared data type
+            /// (see `CellKeyValue::data_type_mismatch`), and serializes the mismatch with the value. Defaults to false.
+            pub fn check_data_types(&mut self, check_data_types: bool) -> &mut Self {
+                self.base.check_data_types = check_data_types;
+                self
+            }
+
+            /// Logs `LogCode::WarningPayload` for values whose data holds a PE image (as is, embedded, or base64 encoded) or
+            /// large high entropy blobs (see `payload::detect`). Defaults to false.
+            pub fn detect_payloads(&mut self, detect_payloads: bool) -> &mut Self {
+                self.base.detect_payloads = detect_payloads;
+                self
+            }
+
+            /// Logs `LogCode::WarningSuspiciousName` for keys whose names look made to hide them: empty, with leading or
+            /// trailing whitespace or invisible characters, with lookalike letters, or ROT13 encoded (see `suspicious_name::detect`).
+            /// The tsv writer also adds a column with the reason. Defaults to false.
+            pub fn detect_suspicious_names(&mut self, detect_suspicious_names: bool) -> &mut Self {
+                self.base.detect_suspicious_names = detect_suspicious_names;
+                self
+            }
+
+            /// Makes the export writers write keys sorted by `sort` rather than in traversal order (which follows how the keys
+            /// are stored), so exports of the same data can be diffed. The writers then hold all the keys in memory.
+            pub fn sort_keys(&mut self, sort: KeySort) -> &mut Self {
+                self.base.key_sort = Some(sort);
+                self
+            }
+
+            /// Makes the export writers write the full paths of keys under `path_mapping`'s mount point
+            /// (ex: `HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft`) rather than their paths within the hive (`\ROOT\Microsoft`)
+            pub fn full_paths(&mut self, path_mapping: PathMapping) -> &mut Self {
+                self.base.path_mapping = Some(path_mapping);
+                self
+            }
+
+            /// Computes the entropy and identifies the file type (zlib, gzip, PNG, OLE, PE, etc.) of binary value data.
+            /// The results are kept in `CellKeyValue::value_stats` and serialized with the value. Defaults to false.
+            pub fn value_stats(&mut self, value_stats: bool) -> &mut Self {
+                self.base.value_stats = value_stats;
+                self
+            }
+
+            /// Sets `guid_name` on the keys and values returned by iterators and lookups (ex: `Parser::get_key`) that
+            /// are named after a GUID with a known name:
+            /// a class registered in the hive's Classes tree, or a known folder (see `GuidNames`). Defaults to false.
+            pub fn guid_names(&mut self, guid_names: bool) -> &mut Self {
+                self.base.guid_names = guid_names;
+                self
+            }
+
+            /// Sends progress updates (phase, estimated percentage, and keys processed) to `handler` while the hive is
+            /// parsed and while the resulting parser is iterated
+            pub fn progress_handler<H: ProgressHandler + 'static>(
+                &mut self,
+                handler: H,
+            ) -> &mut Self {
+                self.base.progress.handler = Some(std::sync::Arc::new(handler));
+                self
+            }
+
+            /// Checks `token` while recovering deleted items, applying transaction logs, and iterating keys
+            pub fn cancellation_token(&mut self, token: CancellationToken) -> &mut Self {
+                self.base.progress.cancellation_token = Some(token);
+                self
+            }
+
+            /// Caches up to `capacity` parsed key nodes (and as many key values) by offset, so keys that are read repeatedly
+            /// (ex: by `get_key`, `get_parent_key`, or `HiveDiff`) aren't parsed again. Defaults to 0 (no cache).
+            /// See `Parser::cell_cache_stats` to tune the capacity.
+            pub fn cell_cache_capacity(&mut self, capacity: usize) -> &mut Self {
+                self.base.cell_cache_capacity = capacity;
+                self
+            }
+
+            pub fn update_console(&mut self, update_console: bool) -> &mut Self {
+                self.base.update_console = update_console;
+                self
+            }
+        }
+    };
+}
+
+impl_parser_builder_options!(ParserBuilderFromPath);
+impl_parser_builder_options!(ParserBuilderFromFile);
+
 pub struct ParserBuilderFromPath {
     primary: Box<dyn AsRef<Path>>,
     transaction_logs: Vec<Box<dyn AsRef<Path>>>,
@@ -37,28 +246,13 @@ pub struct ParserBuilderFromPath {
 }
 
 impl ParserBuilderFromPath {
-    pub fn recover_deleted(&mut self, recover: bool) -> &mut Self {
-        self.base.recover_deleted = recover;
-        self
-    }
-
-    pub fn get_full_field_info(&mut self, get_full_field_info: bool) -> &mut Self {
-        self.base.get_full_field_info = get_full_field_info;
-        self
-    }
-
     pub fn with_transaction_log<T: AsRef<Path> + 'static>(&mut self, log: T) -> &mut Self {
         self.transaction_logs.push(Box::new(log));
         self
     }
 
-    pub fn update_console(&mut self, update_console: bool) -> &mut Self {
-        self.base.update_console = update_console;
-        self
-    }
-
     pub fn build(&self) -> Result<Parser, Error> {
-        let mut transaction_logs = vec![];
+        let mut transaction_logs: Vec<Box<dyn ReadSeek>> = vec![];
         for transaction_log in &self.transaction_logs {
             transaction_logs.push(Box::new(std::fs::File::open(transaction_log.as_ref())?))
         }
@@ -82,32 +276,58 @@ impl ParserBuilderFromFile {
         self
     }
 
-    pub fn recover_deleted(&mut self, recover: bool) -> &mut Self {
-        self.base.recover_deleted = recover;
-        self
-    }
-
-    pub fn get_full_field_info(&mut self, get_full_field_info: bool) -> &mut Self {
-        self.base.get_full_field_info = get_full_field_info;
-        self
-    }
-
     pub fn with_transaction_log<T: ReadSeek + 'static>(&mut self, log: T) -> &mut Self {
         self.transaction_logs.push(Box::new(log));
         self
     }
 
     pub fn build(self) -> Result<Parser, Error> {
-        let mut transaction_logs = vec![];
-        for transaction_log in self.transaction_logs {
-            transaction_logs.push(Box::new(transaction_log));
-        }
         ParserBuilder::build(
             FileInfo::from_read_seek(self.primary)?,
             self.base,
-            transaction_logs,
+            self.transaction_logs,
         )
     }
+
+    /// Reads a transaction log from an async source into memory (see `ParserBuilder::from_async_read_seek`)
+    #[cfg(feature = "tokio")]
+    pub async fn with_async_transaction_log<R: AsyncRead + Unpin>(
+        &mut self,
+        log: R,
+    ) -> Result<&mut Self, Error> {
+        let buffer = crate::async_util::read_to_end(log).await?;
+        Ok(self.with_transaction_log(std::io::Cursor::new(buffer)))
+    }
+
+    /// Like `build`, but parses on tokio's blocking thread pool so async callers don't stall their executor.
+    /// The primary file and transaction logs are read into memory on the calling task first, since they needn't be `Send`.
+    #[cfg(feature = "tokio")]
+    pub async fn build_async(self) -> Result<Parser, Error> {
+        let (file_info, base, transaction_logs) = self.into_buffers()?;
+        crate::async_util::spawn_blocking(move || {
+            ParserBuilder::build(
+                file_info,
+                base,
+                transaction_logs
+                    .into_iter()
+                    .map(|log| Box::new(std::io::Cursor::new(log)) as Box<dyn ReadSeek>)
+                    .collect(),
+            )
+        })
+        .await
+    }
+
+    #[cfg(feature = "tokio")]
+    fn into_buffers(self) -> Result<(FileInfo, ParserBuilderBase, Vec<Vec<u8>>), Error> {
+        let file_info = FileInfo::from_read_seek(self.primary)?;
+        let mut transaction_logs = Vec::with_capacity(self.transaction_logs.len());
+        for mut transaction_log in self.transaction_logs {
+            let mut buffer = Vec::new();
+            std::io::Read::read_to_end(&mut transaction_log, &mut buffer)?;
+            transaction_logs.push(buffer);
+        }
+        Ok((file_info, self.base, transaction_logs))
+    }
 }
 
 pub struct ParserBuilder {}
@@ -121,6 +341,9 @@ impl ParserBuilder {
         }
     }
 
+    /// Returns a builder reading the primary file and transaction logs from any `ReadSeek` source
+    /// (ex: a `File`, a `Cursor` over an in-memory buffer, or an archive member).
+    /// All the options available when building from a path are available here as well.
     pub fn from_file<R: ReadSeek + 'static>(primary: R) -> ParserBuilderFromFile {
         ParserBuilderFromFile {
             primary: Box::new(primary),
@@ -129,10 +352,52 @@ impl ParserBuilder {
         }
     }
 
-    fn build<T: ReadSeek + 'static>(
+    /// Returns a builder for a primary file read from an async source (ex: a `tokio::fs::File` or an upload stream). The
+    /// whole source is read into memory without blocking the executor, so it doesn't need to be seekable. Add transaction
+    /// logs with `ParserBuilderFromFile::with_async_transaction_log` and parse with `ParserBuilderFromFile::build_async`.
+    #[cfg(feature = "tokio")]
+    pub async fn from_async_read_seek<R: AsyncRead + Unpin>(
+        primary: R,
+    ) -> Result<ParserBuilderFromFile, Error> {
+        let buffer = crate::async_util::read_to_end(primary).await?;
+        Ok(Self::from_file(std::io::Cursor::new(buffer)))
+    }
+
+    /// Returns a builder for a hive stored at `start` within the file at `path`, such as a hive carved from a raw image.
+    /// Use `ReadSeekRange` with `from_file` to read a byte range from another `ReadSeek` source.
+    pub fn from_offset<P: AsRef<Path>>(
+        path: P,
+        start: u64,
+        len: u64,
+    ) -> Result<ParserBuilderFromFile, Error> {
+        Ok(Self::from_file(ReadSeekRange::new(
+            std::fs::File::open(path)?,
+            start,
+            len,
+        )?))
+    }
+
+    /// Returns a builder for the hive `member` of the zip archive at `archive` (ex: `C/Windows/System32/config/SYSTEM`
+    /// in a KAPE collection). The member's `.LOG1` and `.LOG2` transaction logs are applied if they are in the archive.
+    /// Members are decompressed into memory; nothing is extracted to disk.
+    #[cfg(feature = "archive")]
+    pub fn from_archive<P: AsRef<Path>>(
+        archive: P,
+        member: &str,
+    ) -> Result<ParserBuilderFromFile, Error> {
+        let (primary, transaction_logs) =
+            crate::archive::read_hive(std::fs::File::open(archive)?, member)?;
+        let mut builder = Self::from_file(std::io::Cursor::new(primary));
+        for transaction_log in transaction_logs {
+            builder.with_transaction_log(std::io::Cursor::new(transaction_log));
+        }
+        Ok(builder)
+    }
+
+    fn build(
         file_info: FileInfo,
         base: ParserBuilderBase,
-        transaction_logs: Vec<Box<T>>,
+        transaction_logs: Vec<Box<dyn ReadSeek>>,
     ) -> Result<Parser, Error> {
         let (parsed_transaction_logs, warning_logs) = TransactionLog::parse(transaction_logs)?;
 
@@ -140,19 +405,342 @@ impl ParserBuilder {
             file_info,
             state: State {
                 get_full_field_info: base.get_full_field_info,
+                parent_offset_check: base.parent_offset_check.unwrap_or(if base.strict {
+                    ParentOffsetCheck::Flag
+                } else {
+                    ParentOffsetCheck::Off
+                }),
+                overlap_check: base.overlap_check.unwrap_or(if base.strict {
+                    OverlapCheck::Flag
+                } else {
+                    OverlapCheck::Off
+                }),
+                limits: base.limits,
+                app_hive: base.app_hive,
+                check_data_types: base.check_data_types,
+                detect_payloads: base.detect_payloads,
+                detect_suspicious_names: base.detect_suspicious_names,
+                value_stats: base.value_stats,
                 ..State::default()
             },
             base_block: None,
+            base_block_source: BaseBlockSource::PrimaryFile,
             hive_bin_header: None,
             cell_key_node_root: None,
+            applied_transaction_logs: Vec::new(),
             recover_deleted: base.recover_deleted,
             update_console: base.update_console,
+            key_sort: base.key_sort,
+            path_mapping: base.path_mapping,
+            guid_names: None,
+            progress: base.progress,
         };
-        parser.init(base.recover_deleted, parsed_transaction_logs)?;
+        parser.init(
+            base.recover_deleted,
+            base.recover_base_block,
+            base.replay_scope.as_ref(),
+            base.log_entry_hash_policy,
+            parsed_transaction_logs,
+        )?;
+        if base.strict {
+            parser.check_strict()?;
+        }
+        // Enabled only now that the buffer won't change (transaction logs are applied while initializing)
+        parser.state.cell_cache = CellCache::new(base.cell_cache_capacity);
+        // Likewise, cells read while initializing may have been overwritten since
+        parser.state.cell_extents = CellExtents::default();
 
         if let Some(warning_logs) = warning_logs {
             parser.state.info.extend(warning_logs);
         }
+        if base.guid_names {
+            parser.guid_names = Some(GuidNames::from_parser(&mut parser)?);
+        }
         Ok(parser)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterBuilder;
+    use crate::parser::ParserIterator;
+    use crate::progress::{Progress, ProgressPhase};
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_from_file_matches_from_path() {
+        let from_path = ParserBuilder::from_path("test_data/system")
+            .with_transaction_log("test_data/system.log1")
+            .with_transaction_log("test_data/system.log2")
+            .build()
+            .unwrap();
+
+        let mut builder =
+            ParserBuilder::from_file(Cursor::new(std::fs::read("test_data/system").unwrap()));
+        builder
+            .with_transaction_log(Cursor::new(std::fs::read("test_data/system.log1").unwrap()))
+            .with_transaction_log(
+                Box::new(std::fs::File::open("test_data/system.log2").unwrap())
+                    as Box<dyn ReadSeek>,
+            );
+        let from_file = builder.build().unwrap();
+
+        assert_eq!(
+            from_path.count_all_keys_and_values(None),
+            from_file.count_all_keys_and_values(None)
+        );
+        assert_eq!(from_path.get_parse_logs(), from_file.get_parse_logs());
+    }
+
+    #[test]
+    fn test_from_offset() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let mut image = vec![0xff; 4096];
+        image.extend(&hive);
+        image.extend(vec![0xff; 4096]);
+        std::fs::write(file.path(), image).unwrap();
+
+        let parser = ParserBuilder::from_offset(file.path(), 4096, hive.len() as u64)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!((2853, 5523), parser.count_all_keys_and_values(None));
+    }
+
+    #[test]
+    fn test_recover_base_block() {
+        let build = |hive: Vec<u8>, recover_base_block: bool| {
+            let mut builder = ParserBuilder::from_file(Cursor::new(hive));
+            builder
+                .recover_base_block(recover_base_block)
+                .with_transaction_log(Cursor::new(std::fs::read("test_data/system.log1").unwrap()))
+                .with_transaction_log(Cursor::new(std::fs::read("test_data/system.log2").unwrap()));
+            builder.build()
+        };
+        let hive = std::fs::read("test_data/system").unwrap();
+        let expected = build(hive.clone(), false).unwrap();
+        assert_eq!(BaseBlockSource::PrimaryFile, expected.base_block_source());
+
+        // A bad checksum is always recovered from the transaction logs
+        let mut bad_checksum = hive.clone();
+        bad_checksum[0x30] ^= 0xff;
+        let recovered = build(bad_checksum, false).unwrap();
+        assert_eq!(
+            BaseBlockSource::TransactionLog,
+            recovered.base_block_source()
+        );
+
+        // An unparseable base block only with recover_base_block
+        let mut bad_signature = hive;
+        bad_signature[..4].copy_from_slice(b"xxxx");
+        assert!(build(bad_signature.clone(), false).is_err());
+        let parser = build(bad_signature, true).unwrap();
+        assert_eq!(BaseBlockSource::TransactionLog, parser.base_block_source());
+        assert_eq!(
+            recovered.count_all_keys_and_values(None),
+            parser.count_all_keys_and_values(None)
+        );
+    }
+
+    #[test]
+    fn test_replay_only() {
+        let build = |replay_scope: Option<ReplayScope>| {
+            let mut builder = ParserBuilder::from_path("test_data/system");
+            builder
+                .with_transaction_log("test_data/system.log1")
+                .with_transaction_log("test_data/system.log2");
+            if let Some(replay_scope) = replay_scope {
+                builder.replay_only(replay_scope);
+            }
+            builder.build().unwrap()
+        };
+        let skipped_entries = |parser: &Parser| -> Vec<String> {
+            parser
+                .get_parse_logs()
+                .get()
+                .unwrap()
+                .iter()
+                .filter(|log| log.text.ends_with("outside the replay scope"))
+                .map(|log| log.text.clone())
+                .collect()
+        };
+
+        let full = build(None);
+        assert!(skipped_entries(&full).is_empty());
+
+        // Every dirty page is in scope
+        let everything = build(Some(ReplayScope::OffsetRange(0..u32::MAX)));
+        assert!(skipped_entries(&everything).is_empty());
+        assert_eq!(
+            full.count_all_keys_and_values(None),
+            everything.count_all_keys_and_values(None)
+        );
+
+        // No dirty page is in scope; the sequence numbers are still updated
+        let nothing = build(Some(ReplayScope::OffsetRange(u32::MAX - 1..u32::MAX)));
+        assert_eq!(
+            vec![
+                "Skipped 3 log entries outside the replay scope",
+                "Skipped 7 log entries outside the replay scope"
+            ],
+            skipped_entries(&nothing)
+        );
+        assert_eq!(
+            full.get_base_block_info().0,
+            nothing.get_base_block_info().0
+        );
+
+        let subtree = build(Some(ReplayScope::KeySubtree("RegistryTest".to_string())));
+        assert_eq!(
+            vec![
+                "Skipped 3 log entries outside the replay scope",
+                "Skipped 3 log entries outside the replay scope"
+            ],
+            skipped_entries(&subtree)
+        );
+        // The keys and values in scope are the same as after a full replay
+        let registry_test = |parser: &Parser| {
+            let filter = FilterBuilder::new()
+                .add_key_path("RegistryTest")
+                .return_child_keys(true)
+                .build()
+                .unwrap();
+            ParserIterator::new(parser)
+                .with_filter(filter)
+                .iter()
+                .map(|key| {
+                    let values: Vec<_> = key
+                        .value_iter()
+                        .map(|value| (value.detail.value_name(), value.get_data().to_vec()))
+                        .collect();
+                    (
+                        key.path.to_string(),
+                        key.last_key_written_date_and_time(),
+                        values,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = registry_test(&full);
+        assert!(!expected.is_empty());
+        assert_eq!(expected, registry_test(&subtree));
+    }
+
+    #[test]
+    fn test_strict() {
+        let mut builder = ParserBuilder::from_path("test_data/NTUSER.DAT");
+        builder.strict(true);
+        assert!(builder.build().is_ok());
+
+        // A root key claiming one more sub key than its list holds
+        let mut hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let root = 4096 + crate::util::read_u32_le(&hive, 0x24).unwrap() as usize;
+        let sub_key_count = crate::util::read_u32_le(&hive, root + 24).unwrap();
+        hive[root + 24..root + 28].copy_from_slice(&(sub_key_count + 1).to_le_bytes());
+
+        assert!(ParserBuilder::from_file(Cursor::new(hive.clone()))
+            .build()
+            .is_ok());
+        let mut builder = ParserBuilder::from_file(Cursor::new(hive));
+        builder.strict(true);
+        match builder.build() {
+            Err(Error::Validation { detail }) => {
+                assert!(detail.contains("SubKeyCount"), "{}", detail)
+            }
+            _ => panic!("expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn test_progress_handler() {
+        let updates = Arc::new(Mutex::new(Vec::<Progress>::new()));
+        let handler_updates = updates.clone();
+        let parser = ParserBuilder::from_path("test_data/system")
+            .with_transaction_log("test_data/system.log1")
+            .with_transaction_log("test_data/system.log2")
+            .progress_handler(move |progress: &Progress| {
+                handler_updates.lock().unwrap().push(*progress)
+            })
+            .build()
+            .unwrap();
+        let replay = updates.lock().unwrap().split_off(0);
+        assert!(replay.len() > 1);
+        assert!(replay
+            .iter()
+            .all(|p| p.phase == ProgressPhase::ApplyingTransactionLogs));
+        assert_eq!(100.0, replay.last().unwrap().percentage);
+
+        let key_count = ParserIterator::new(&parser).iter().count();
+        let traversal = updates.lock().unwrap().split_off(0);
+        assert!(traversal
+            .iter()
+            .all(|p| p.phase == ProgressPhase::Traversing));
+        assert!(traversal.windows(2).all(
+            |w| w[0].percentage <= w[1].percentage && w[0].keys_processed < w[1].keys_processed
+        ));
+        let last = traversal.last().unwrap();
+        assert_eq!((100.0, key_count), (last.percentage, last.keys_processed));
+        assert_eq!(
+            key_count / ProgressReporter::TRAVERSAL_INTERVAL + 2,
+            traversal.len()
+        );
+    }
+
+    #[test]
+    fn test_progress_handler_recover_deleted() {
+        let updates = Arc::new(Mutex::new(Vec::<Progress>::new()));
+        let handler_updates = updates.clone();
+        ParserBuilder::from_path("test_data/system")
+            .recover_deleted(true)
+            .progress_handler(move |progress: &Progress| {
+                handler_updates.lock().unwrap().push(*progress)
+            })
+            .build()
+            .unwrap();
+        let recovery = updates.lock().unwrap().split_off(0);
+        assert!(recovery
+            .iter()
+            .all(|p| p.phase == ProgressPhase::RecoveringDeleted));
+        assert!(recovery
+            .windows(2)
+            .all(|w| w[0].percentage <= w[1].percentage));
+        assert_eq!(100.0, recovery.last().unwrap().percentage);
+        // hive bins are at least 4096 bytes, and updates are only sent every RECOVERY_INTERVAL of them
+        let hive_bins = std::fs::metadata("test_data/system").unwrap().len() as usize / 4096;
+        assert!(recovery.len() > 2);
+        assert!(recovery.len() <= hive_bins / ProgressReporter::RECOVERY_INTERVAL + 2);
+    }
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let mut builder = ParserBuilder::from_path("test_data/system");
+        builder
+            .with_transaction_log("test_data/system.log1")
+            .cancellation_token(token);
+        assert!(matches!(builder.build(), Err(Error::Cancelled { .. })));
+
+        // Cancel partway through an iteration
+        let token = CancellationToken::new();
+        let handler_token = token.clone();
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .cancellation_token(token)
+            .progress_handler(move |progress: &Progress| {
+                if progress.keys_processed > 1000 {
+                    handler_token.cancel();
+                }
+            })
+            .build()
+            .unwrap();
+        assert!(parser.check_cancelled().is_ok());
+        assert_eq!(1001, ParserIterator::new(&parser).iter().count());
+        assert!(matches!(
+            parser.check_cancelled(),
+            Err(Error::Cancelled { .. })
+        ));
+    }
+}
@@ -15,7 +15,7 @@
  */
 
 use crate::cell::{CellState, CellType};
-use crate::cell_key_node::{CellKeyNode, CellKeyNodeReadOptions};
+use crate::cell_key_node::{CellKeyNode, CellKeyNodeReadOptions, PathConfidence};
 use crate::cell_key_value::CellKeyValue;
 use crate::err::Error;
 use crate::file_info::FileInfo;
@@ -23,6 +23,8 @@ use crate::hive_bin_header::HiveBinHeader;
 use crate::log::LogCode;
 use crate::state::State;
 use nom::{branch::alt, bytes::complete::tag, combinator::map, number::complete::le_i32, IResult};
+use std::collections::HashMap;
+use std::convert::TryFrom;
 
 pub(crate) struct ParserRecoverDeleted<'a> {
     pub file_info: &'a FileInfo,
@@ -107,6 +109,57 @@ impl<'a> ParserRecoverDeleted<'a> {
         Ok(file_offset_absolute)
     }
 
+    /// Recovered keys are initially collected without a parent. This follows each one's parent offset: keys with an
+    /// allocated parent are attached to it, keys whose parent was recovered as well become that key's deleted sub keys,
+    /// and the rest stay attached to the root key. Each key's path is rebuilt along the way and marked with how much it can be trusted.
+    pub(crate) fn reconstruct_paths(&mut self) {
+        let recovered = match self.state.deleted_keys.map.remove("") {
+            Some(recovered) => recovered,
+            None => return,
+        };
+        let mut reconstructor = PathReconstructor {
+            file_info: self.file_info,
+            state: self.state,
+            index: HashMap::new(),
+            allocated_paths: HashMap::new(),
+            resolved: vec![None; recovered.len()],
+            in_progress: vec![false; recovered.len()],
+        };
+        for (i, key) in recovered.iter().enumerate() {
+            reconstructor
+                .index
+                .entry(key.file_offset_absolute)
+                .or_insert(i);
+        }
+        for i in 0..recovered.len() {
+            reconstructor.resolve(&recovered, i, 0);
+        }
+        let resolved: Vec<ResolvedPath> = reconstructor
+            .resolved
+            .into_iter()
+            .map(|r| r.expect("every key was resolved"))
+            .collect();
+
+        // attach the deepest keys first, so each parent still has its place in `keys` when its sub keys are moved into it
+        let mut order: Vec<usize> = (0..recovered.len()).collect();
+        order.sort_by_key(|i| std::cmp::Reverse(resolved[*i].depth));
+        let mut keys: Vec<Option<CellKeyNode>> = recovered.into_iter().map(Some).collect();
+        for i in order {
+            let resolved = &resolved[i];
+            let mut key = keys[i].take().expect("each key is attached once");
+            key.path = resolved.path.as_str().into();
+            key.path_confidence = Some(resolved.confidence);
+            match &resolved.parent {
+                Parent::Recovered(parent) => keys[*parent]
+                    .as_mut()
+                    .expect("parents are attached after their sub keys")
+                    .deleted_keys
+                    .push(key),
+                Parent::Allocated(parent_path) => self.state.deleted_keys.add(parent_path, key),
+            }
+        }
+    }
+
     // this method will ignore errors that are encountered
     fn find_cells_in_slack(&mut self, input_orig: &[u8], file_offset_absolute_start: usize) {
         let _ = self.find_cells_in_slack_internal(input_orig, file_offset_absolute_start);
@@ -238,6 +291,124 @@ impl<'a> ParserRecoverDeleted<'a> {
     }
 }
 
+/// Where a recovered key is attached, and its reconstructed path
+#[derive(Clone, Debug)]
+struct ResolvedPath {
+    path: String,
+    confidence: PathConfidence,
+    /// Number of recovered ancestors between the key and the allocated key it's attached under
+    depth: usize,
+    parent: Parent,
+}
+
+#[derive(Clone, Debug)]
+enum Parent {
+    /// Index of the recovered parent key
+    Recovered(usize),
+    /// Path of the allocated parent key, as used by `State::deleted_keys` ("" for the root key)
+    Allocated(String),
+}
+
+struct PathReconstructor<'a> {
+    file_info: &'a FileInfo,
+    state: &'a mut State,
+    /// Index of the recovered key at each file offset
+    index: HashMap<usize, usize>,
+    /// Path of the allocated key at each file offset (None if there isn't one, or its parents can't be followed to the root)
+    allocated_paths: HashMap<usize, Option<(String, bool)>>,
+    resolved: Vec<Option<ResolvedPath>>,
+    in_progress: Vec<bool>,
+}
+
+impl PathReconstructor<'_> {
+    /// Keys are nested at most 512 levels deep
+    const MAX_DEPTH: usize = 512;
+
+    /// Returns the path of the recovered key at index `i`, or None if its parent offsets form a loop
+    fn resolve(
+        &mut self,
+        recovered: &[CellKeyNode],
+        i: usize,
+        depth: usize,
+    ) -> Option<ResolvedPath> {
+        if let Some(resolved) = &self.resolved[i] {
+            return Some(resolved.clone());
+        }
+        if self.in_progress[i] || depth > Self::MAX_DEPTH {
+            return None;
+        }
+        self.in_progress[i] = true;
+        let key = &recovered[i];
+        let parent_offset = self.parent_offset(key);
+        let from_recovered_parent = match parent_offset.and_then(|o| self.index.get(&o)) {
+            Some(&parent) if parent != i => self
+                .resolve(recovered, parent, depth + 1)
+                .map(|r| (parent, r)),
+            _ => None,
+        };
+        let resolved = match from_recovered_parent {
+            Some((parent, parent_resolved)) => ResolvedPath {
+                path: format!("{}\\{}", parent_resolved.path, key.key_name),
+                confidence: match parent_resolved.confidence {
+                    PathConfidence::Orphan => PathConfidence::Orphan,
+                    _ => PathConfidence::Derived,
+                },
+                depth: parent_resolved.depth + 1,
+                parent: Parent::Recovered(parent),
+            },
+            None => match parent_offset.and_then(|o| self.allocated_path(o, 0)) {
+                Some((parent_path, parent_is_root)) => ResolvedPath {
+                    path: format!("{}\\{}", parent_path, key.key_name),
+                    confidence: PathConfidence::Exact,
+                    depth: 0,
+                    parent: Parent::Allocated(if parent_is_root {
+                        String::new()
+                    } else {
+                        parent_path
+                    }),
+                },
+                None => ResolvedPath {
+                    path: format!("\\{}", key.key_name),
+                    confidence: PathConfidence::Orphan,
+                    depth: 0,
+                    parent: Parent::Allocated(String::new()),
+                },
+            },
+        };
+        self.in_progress[i] = false;
+        self.resolved[i] = Some(resolved.clone());
+        Some(resolved)
+    }
+
+    fn parent_offset(&self, key: &CellKeyNode) -> Option<usize> {
+        usize::try_from(key.detail.parent_key_offset_relative())
+            .ok()
+            .map(|offset| offset + self.file_info.hbin_offset_absolute)
+    }
+
+    /// Returns the path of the allocated key at `offset`, and whether it's the root key
+    fn allocated_path(&mut self, offset: usize, depth: usize) -> Option<(String, bool)> {
+        if let Some(path) = self.allocated_paths.get(&offset) {
+            return path.clone();
+        }
+        let key = read_checked(&self.file_info.buffer, offset)
+            .and_then(|input| CellKeyNode::from_bytes(self.state, input, offset, "", None).ok())
+            .map(|(_, key)| key)
+            .filter(|key| !key.is_free());
+        let path = match key {
+            Some(key) if key.is_key_root() => Some((key.path.to_string(), true)),
+            Some(key) if depth < Self::MAX_DEPTH => self
+                .parent_offset(&key)
+                .filter(|parent_offset| *parent_offset != offset)
+                .and_then(|parent_offset| self.allocated_path(parent_offset, depth + 1))
+                .map(|(parent_path, _)| (format!("{}\\{}", parent_path, key.key_name), false)),
+            _ => None,
+        };
+        self.allocated_paths.insert(offset, path.clone());
+        path
+    }
+}
+
 fn read_range_checked(buffer: &[u8], file_offset_absolute: usize, size: usize) -> Option<&[u8]> {
     if file_offset_absolute < buffer.len() {
         Some(
@@ -265,6 +436,8 @@ fn read_checked(buffer: &[u8], file_offset_absolute: usize) -> Option<&[u8]> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
 
     #[test]
     fn test_read_checked() {
@@ -288,4 +461,55 @@ mod tests {
         assert_eq!(Some(ret1), read_range_checked(&buffer, 1, 3));
         assert_eq!(Some(ret4), read_range_checked(&buffer, 4, 1));
     }
+
+    #[test]
+    fn test_reconstruct_paths() {
+        let mut parser = ParserBuilder::from_path("test_data/system")
+            .recover_deleted(true)
+            .build()
+            .unwrap();
+        let mut exact = 0;
+        let mut derived = 0;
+        let mut orphan = 0;
+        let mut exact_parents = Vec::new();
+        for key in ParserIterator::new(&parser).iter() {
+            if !key.cell_state.is_deleted() {
+                assert_eq!(None, key.path_confidence);
+                assert!(!serde_json::to_string(&key)
+                    .unwrap()
+                    .contains("path_confidence"));
+                continue;
+            }
+            match key.path_confidence {
+                Some(PathConfidence::Exact) => {
+                    exact += 1;
+                    let (parent, _) = key.path.rsplit_once('\\').unwrap();
+                    exact_parents.push(parent.to_string());
+                }
+                Some(PathConfidence::Derived) => {
+                    derived += 1;
+                    assert!(key.path.starts_with("\\ROOT\\"), "{}", key.path);
+                }
+                Some(PathConfidence::Orphan) => {
+                    orphan += 1;
+                    assert!(!key.path.starts_with("\\ROOT\\"), "{}", key.path);
+                }
+                None => panic!("recovered key without a path confidence: {}", key.path),
+            }
+            assert!(serde_json::to_string(&key)
+                .unwrap()
+                .contains("\"path_confidence\""));
+        }
+        assert_eq!(192, exact + derived + orphan);
+        assert!(exact > 0 && derived > 0 && orphan > 0);
+
+        for parent in exact_parents {
+            let key = parser.get_key(&parent, true).unwrap();
+            assert!(
+                matches!(key, Some(k) if !k.cell_state.is_deleted()),
+                "{}",
+                parent
+            );
+        }
+    }
 }
@@ -0,0 +1,224 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Conversion between the paths of keys within a hive (`\<root key name>\Microsoft\Windows`) and the full paths the keys
+//! have once the hive is mounted in a live registry (`HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows`), as used by threat
+//! intel and other tools.
+
+use crate::cell_key_node::{CellKeyNode, PathConfidence};
+
+/// The abbreviations of the root keys of a live registry
+const ROOT_KEY_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("HKLM", "HKEY_LOCAL_MACHINE"),
+    ("HKCU", "HKEY_CURRENT_USER"),
+    ("HKU", "HKEY_USERS"),
+    ("HKCR", "HKEY_CLASSES_ROOT"),
+    ("HKCC", "HKEY_CURRENT_CONFIG"),
+];
+
+/// Where each well-known hive file is mounted, by file name
+const MOUNT_POINTS: &[(&str, &str)] = &[
+    ("system", "HKEY_LOCAL_MACHINE\\SYSTEM"),
+    ("software", "HKEY_LOCAL_MACHINE\\SOFTWARE"),
+    ("sam", "HKEY_LOCAL_MACHINE\\SAM"),
+    ("security", "HKEY_LOCAL_MACHINE\\SECURITY"),
+    ("components", "HKEY_LOCAL_MACHINE\\COMPONENTS"),
+    ("drivers", "HKEY_LOCAL_MACHINE\\DRIVERS"),
+    ("default", "HKEY_USERS\\.DEFAULT"),
+    ("ntuser.dat", "HKEY_CURRENT_USER"),
+    ("usrclass.dat", "HKEY_CURRENT_USER\\Software\\Classes"),
+];
+
+/// Maps the paths of a hive's keys to and from full paths under the key the hive is mounted at
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PathMapping {
+    mount_point: String,
+}
+
+impl PathMapping {
+    /// `mount_point` is the full path of the key the hive is mounted at (ex: `HKEY_USERS\S-1-5-21-...` for the NTUSER.DAT
+    /// of a user who isn't the current one). Root key abbreviations such as `HKLM` are expanded.
+    pub fn new(mount_point: &str) -> Self {
+        Self {
+            mount_point: expand_root_key(mount_point.trim_matches('\\')),
+        }
+    }
+
+    /// Returns the mapping for a well-known hive file, by its name (ex: `SOFTWARE` is mounted at
+    /// `HKEY_LOCAL_MACHINE\SOFTWARE`, and `NTUSER.DAT` at `HKEY_CURRENT_USER`). Returns None for other files.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        MOUNT_POINTS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(file_name))
+            .map(|(_, mount_point)| Self::new(mount_point))
+    }
+
+    pub fn mount_point(&self) -> &str {
+        &self.mount_point
+    }
+
+    /// Converts the path of a key within the hive, which starts with the root key's name (as in `CellKeyNode::path`), to
+    /// its full path (ex: `\CMI-CreateHive{...}\Microsoft` to `HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft`)
+    pub fn to_full_path(&self, hive_path: &str) -> String {
+        match hive_path.trim_start_matches('\\').split_once('\\') {
+            Some((_root, path)) if !path.is_empty() => format!("{}\\{}", self.mount_point, path),
+            _ => self.mount_point.clone(),
+        }
+    }
+
+    /// Converts a full path to the path of a key relative to the hive's root key, as taken by `Parser::get_key` and
+    /// `FilterBuilder::add_key_path` (ex: `HKLM\SOFTWARE\Microsoft` to `Microsoft`). The root key is an empty path.
+    /// Returns None if `full_path` isn't under the mount point.
+    pub fn to_hive_path(&self, full_path: &str) -> Option<String> {
+        let full_path = expand_root_key(full_path.trim_matches('\\'));
+        let prefix = full_path.get(..self.mount_point.len())?;
+        if !prefix.eq_ignore_ascii_case(&self.mount_point) {
+            return None;
+        }
+        let rest = &full_path[self.mount_point.len()..];
+        if rest.is_empty() {
+            Some(String::new())
+        } else {
+            rest.strip_prefix('\\').map(str::to_string)
+        }
+    }
+
+    /// Replaces the path of `key`, and of its earlier versions, with its full path. Recovered keys whose parents couldn't be
+    /// followed to the root key (`PathConfidence::Orphan`) keep their path, since it doesn't start with the root key.
+    pub fn apply(&self, key: &mut CellKeyNode) {
+        if key.path_confidence != Some(PathConfidence::Orphan) {
+            key.path = self.to_full_path(&key.path).into();
+        }
+        for version in &mut key.versions {
+            self.apply(version);
+        }
+    }
+}
+
+/// Returns `path` with an abbreviated root key (ex: `HKLM`) spelled out (`HKEY_LOCAL_MACHINE`)
+pub fn expand_root_key(path: &str) -> String {
+    let (root, rest) = match path.split_once('\\') {
+        Some((root, rest)) => (root, Some(rest)),
+        None => (path, None),
+    };
+    let root = ROOT_KEY_ABBREVIATIONS
+        .iter()
+        .find(|(abbreviation, _)| abbreviation.eq_ignore_ascii_case(root))
+        .map_or(root, |(_, name)| name);
+    match rest {
+        Some(rest) => format!("{}\\{}", root, rest),
+        None => root.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_path_mapping() {
+        let mapping = PathMapping::from_file_name("SOFTWARE").unwrap();
+        assert_eq!("HKEY_LOCAL_MACHINE\\SOFTWARE", mapping.mount_point());
+        assert_eq!(
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows",
+            mapping.to_full_path(
+                "\\CMI-CreateHive{199DAFC2-6F16-4946-BF90-5A3FC3A60902}\\Microsoft\\Windows"
+            )
+        );
+        assert_eq!(
+            "HKEY_LOCAL_MACHINE\\SOFTWARE",
+            mapping.to_full_path("\\ROOT")
+        );
+        assert_eq!(
+            Some("Microsoft\\Windows".to_string()),
+            mapping.to_hive_path("hklm\\software\\Microsoft\\Windows")
+        );
+        assert_eq!(
+            Some(String::new()),
+            mapping.to_hive_path("HKEY_LOCAL_MACHINE\\SOFTWARE\\")
+        );
+        assert_eq!(None, mapping.to_hive_path("HKLM\\SOFTWARE2\\Microsoft"));
+        assert_eq!(None, mapping.to_hive_path("HKCU\\Software"));
+        assert_eq!(None, PathMapping::from_file_name("amcache.hve"));
+
+        let mapping = PathMapping::new("HKU\\S-1-5-21-1004");
+        assert_eq!("HKEY_USERS\\S-1-5-21-1004", mapping.mount_point());
+        assert_eq!(
+            Some("Environment".to_string()),
+            mapping.to_hive_path(&mapping.to_full_path("\\ROOT\\Environment"))
+        );
+    }
+
+    #[test]
+    fn test_full_paths() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let mapping = PathMapping::from_file_name("NTUSER.DAT").unwrap();
+        let hive_paths: Vec<_> = ParserIterator::new(&parser)
+            .iter()
+            .map(|key| key.path.to_string())
+            .collect();
+        let full_paths: Vec<_> = ParserIterator::new(&parser)
+            .full_paths(mapping.clone())
+            .iter()
+            .map(|key| key.path.to_string())
+            .collect();
+        assert_eq!(hive_paths.len(), full_paths.len());
+        assert_eq!("HKEY_CURRENT_USER", full_paths[0]);
+        for (hive_path, full_path) in hive_paths.iter().zip(&full_paths) {
+            assert_eq!(&mapping.to_full_path(hive_path), full_path);
+        }
+        assert!(full_paths
+            .iter()
+            .any(|path| path == "HKEY_CURRENT_USER\\Control Panel\\Accessibility"));
+    }
+
+    #[test]
+    fn test_full_paths_recovered() {
+        let parser = ParserBuilder::from_path("test_data/system")
+            .recover_deleted(true)
+            .build()
+            .unwrap();
+        let mapping = PathMapping::from_file_name("SYSTEM").unwrap();
+        let keys: Vec<_> = ParserIterator::new(&parser).iter().collect();
+        let full_paths: Vec<_> = ParserIterator::new(&parser)
+            .full_paths(mapping)
+            .iter()
+            .map(|key| key.path.to_string())
+            .collect();
+        assert_eq!(keys.len(), full_paths.len());
+        let mut orphans = 0;
+        for (key, full_path) in keys.iter().zip(&full_paths) {
+            if key.path_confidence == Some(PathConfidence::Orphan) {
+                orphans += 1;
+                assert_eq!(key.path.to_string(), *full_path);
+            } else {
+                assert!(
+                    full_path.starts_with("HKEY_LOCAL_MACHINE\\SYSTEM\\")
+                        || full_path == "HKEY_LOCAL_MACHINE\\SYSTEM",
+                    "{}",
+                    full_path
+                );
+            }
+        }
+        assert!(orphans > 0);
+        assert!(keys.iter().any(|key| key.path_confidence.is_some()
+            && key.path_confidence != Some(PathConfidence::Orphan)));
+    }
+}
@@ -0,0 +1,293 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Detection of executables and other payloads hidden in value data, as used by fileless malware
+//! (see `CellKeyValue::detect_payload` and `ParserBuilder::detect_payloads`).
+
+use crate::util;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::fmt;
+
+/// Blobs smaller than this aren't checked for high entropy; short random-looking data (keys, hashes, GUIDs) is common
+pub const HIGH_ENTROPY_MIN_SIZE: usize = 4096;
+/// Bits per byte; compressed and encrypted data is close to 8
+pub const HIGH_ENTROPY_THRESHOLD: f64 = 7.2;
+/// Base64 text shorter than this isn't reported, since "TVq" and friends also show up in ordinary strings
+const BASE64_MIN_LEN: usize = 64;
+const PE_SIGNATURE: &[u8] = b"PE\0\0";
+const E_LFANEW_OFFSET: usize = 0x3C;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum PayloadKind {
+    /// The data starts with an MZ header
+    PeImage,
+    /// A PE image (an MZ header whose `e_lfanew` points to a PE signature) starts somewhere after the beginning of the data
+    EmbeddedPe,
+    /// Base64 text (ASCII or UTF-16LE) that decodes to an MZ header
+    Base64Pe,
+    /// Large data that looks compressed or encrypted (see `HIGH_ENTROPY_MIN_SIZE` and `HIGH_ENTROPY_THRESHOLD`)
+    HighEntropy,
+}
+
+impl fmt::Display for PayloadKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PayloadKind::PeImage => write!(f, "PE image"),
+            PayloadKind::EmbeddedPe => write!(f, "embedded PE image"),
+            PayloadKind::Base64Pe => write!(f, "base64 encoded PE image"),
+            PayloadKind::HighEntropy => write!(f, "high entropy data"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct Payload {
+    pub kind: PayloadKind,
+    /// Offset of the payload within the value's data
+    pub offset: usize,
+}
+
+impl fmt::Display for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Value data contains {} at offset {}",
+            self.kind, self.offset
+        )
+    }
+}
+
+/// Returns the first payload found in `data`, checking for (in order) a PE image, an embedded PE image,
+/// base64 encoded PE images, and high entropy data
+pub fn detect(data: &[u8]) -> Option<Payload> {
+    let payload = |kind, offset| Some(Payload { kind, offset });
+    if data.starts_with(b"MZ") {
+        return payload(PayloadKind::PeImage, 0);
+    }
+    if let Some(offset) = find_pe_image(data) {
+        return payload(PayloadKind::EmbeddedPe, offset);
+    }
+    if let Some(offset) = find_base64_pe(data) {
+        return payload(PayloadKind::Base64Pe, offset);
+    }
+    if data.len() >= HIGH_ENTROPY_MIN_SIZE && entropy(data) >= HIGH_ENTROPY_THRESHOLD {
+        return payload(PayloadKind::HighEntropy, 0);
+    }
+    None
+}
+
+/// Returns the Shannon entropy of `data`, in bits per byte
+pub fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for b in data {
+        counts[*b as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn find_pe_image(data: &[u8]) -> Option<usize> {
+    data.windows(2)
+        .enumerate()
+        .filter(|(_, window)| window == b"MZ")
+        .map(|(offset, _)| offset)
+        .find(|offset| {
+            let pe_offset = data
+                .get(offset + E_LFANEW_OFFSET..offset + E_LFANEW_OFFSET + 4)
+                .and_then(|e_lfanew| {
+                    offset.checked_add(u32::from_le_bytes(e_lfanew.try_into().ok()?) as usize)
+                });
+            pe_offset.and_then(|pe_offset| data.get(pe_offset..pe_offset + PE_SIGNATURE.len()))
+                == Some(PE_SIGNATURE)
+        })
+}
+
+/// Looks for a run of base64 text starting with "TVo", "TVp", "TVq", or "TVr" (the encodings of "MZ" followed by any
+/// byte) that decodes to an MZ header. Returns the offset within `data`, which is doubled for UTF-16LE text.
+fn find_base64_pe(data: &[u8]) -> Option<usize> {
+    let is_utf16 = data.len() >= 2 && data[0] != 0 && data[1] == 0;
+    let text: Vec<u8> = if is_utf16 {
+        data.iter().step_by(2).copied().collect()
+    } else {
+        data.to_vec()
+    };
+    let is_base64 = |b: &u8| b.is_ascii_alphanumeric() || *b == b'+' || *b == b'/';
+    let offset = text.windows(3).enumerate().find_map(|(offset, window)| {
+        if window[0] != b'T' || window[1] != b'V' || !(b'o'..=b'r').contains(&window[2]) {
+            return None;
+        }
+        let starts_run = offset == 0 || !is_base64(&text[offset - 1]);
+        let run_len = text[offset..].iter().take_while(|b| is_base64(b)).count();
+        if !starts_run || run_len < BASE64_MIN_LEN {
+            return None;
+        }
+        // whole groups of 4 characters only, since the padding (if any) follows the run
+        let run = std::str::from_utf8(&text[offset..offset + run_len - run_len % 4]).ok()?;
+        match util::from_base64(run) {
+            Some(decoded) if decoded.starts_with(b"MZ") => Some(offset),
+            _ => None,
+        }
+    })?;
+    Some(if is_utf16 { offset * 2 } else { offset })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+
+    fn pe_image() -> Vec<u8> {
+        let mut pe = vec![0; 0x100];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[E_LFANEW_OFFSET..E_LFANEW_OFFSET + 4].copy_from_slice(&0x80u32.to_le_bytes());
+        pe[0x80..0x84].copy_from_slice(PE_SIGNATURE);
+        pe
+    }
+
+    #[test]
+    fn test_detect() {
+        let pe = pe_image();
+        assert_eq!(
+            Some(Payload {
+                kind: PayloadKind::PeImage,
+                offset: 0
+            }),
+            detect(&pe)
+        );
+
+        let embedded = [b"header".as_ref(), &pe].concat();
+        assert_eq!(
+            Some(Payload {
+                kind: PayloadKind::EmbeddedPe,
+                offset: 6
+            }),
+            detect(&embedded)
+        );
+        // an MZ without a PE signature where e_lfanew points isn't reported
+        assert_eq!(None, detect(&[b"header".as_ref(), &pe[..0x80]].concat()));
+
+        let base64 = format!("powershell -enc TVqQAAMAAAAEAAAA//8AALgA{}", "A".repeat(64));
+        assert_eq!(
+            Some(Payload {
+                kind: PayloadKind::Base64Pe,
+                offset: 16
+            }),
+            detect(base64.as_bytes())
+        );
+        assert_eq!(
+            Some(Payload {
+                kind: PayloadKind::Base64Pe,
+                offset: 32
+            }),
+            detect(&crate::util::to_utf16_le_string(&base64))
+        );
+        // too short, or part of a longer run of base64 characters
+        assert_eq!(None, detect(b"TVqQAAMAAAAEAAAA"));
+        assert_eq!(None, detect(format!("ATVq{}", "A".repeat(64)).as_bytes()));
+
+        let random: Vec<u8> = (0..HIGH_ENTROPY_MIN_SIZE as u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        assert!(entropy(&random) > HIGH_ENTROPY_THRESHOLD);
+        assert_eq!(
+            Some(Payload {
+                kind: PayloadKind::HighEntropy,
+                offset: 0
+            }),
+            detect(&random)
+        );
+        assert_eq!(None, detect(&random[..HIGH_ENTROPY_MIN_SIZE - 1]));
+        assert_eq!(0.0, entropy(&[7; 100]));
+    }
+
+    #[test]
+    fn test_detect_payloads() {
+        use crate::cell_key_value::CellKeyValueDataTypes;
+        use crate::log::LogCode;
+        use crate::writer::{HiveWriter, WriterKey, WriterValue};
+
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("Software\\Classes")
+            .set_value(WriterValue::new(
+                "Payload",
+                CellKeyValueDataTypes::REG_SZ,
+                pe_image(),
+            ));
+        root.get_or_add_sub_key("Software")
+            .set_value(WriterValue::new(
+                "Benign",
+                CellKeyValueDataTypes::REG_SZ,
+                crate::util::to_utf16_le_string("notatin"),
+            ));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+
+        let mut builder = ParserBuilder::from_file(std::io::Cursor::new(hive));
+        builder.detect_payloads(true);
+        let parser = builder.build().unwrap();
+        let payloads: Vec<_> = ParserIterator::new(&parser)
+            .payloads()
+            .map(|(key_path, value, payload)| {
+                let logged = value.logs.has_code(LogCode::WarningPayload);
+                (
+                    key_path.to_string(),
+                    value.detail.value_name(),
+                    payload,
+                    logged,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![(
+                "\\ROOT\\Software\\Classes".to_string(),
+                "Payload".to_string(),
+                Payload {
+                    kind: PayloadKind::PeImage,
+                    offset: 0
+                },
+                true
+            )],
+            payloads
+        );
+
+        // encrypted tokens are reported as high entropy data
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let payloads: Vec<_> = ParserIterator::new(&parser)
+            .payloads()
+            .map(|(_, value, payload)| (value.detail.value_name(), payload.kind))
+            .collect();
+        assert_eq!(
+            vec![
+                ("UserTicket".to_string(), PayloadKind::HighEntropy),
+                ("UserTicket".to_string(), PayloadKind::HighEntropy)
+            ],
+            payloads
+        );
+    }
+}
@@ -0,0 +1,322 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell::CellState;
+use crate::cell_key_node::CellKeyNode;
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::plugins::{self, Plugin};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+
+/* Amcache.hve layout (Windows 10 and later), based upon:
+    https://github.com/EricZimmerman/AmcacheParser
+    https://www.ssi.gouv.fr/uploads/2019/01/anssi-coriin_2019-analysis_amcache.pdf
+*/
+
+/// An executable file recorded under `Root\InventoryApplicationFile`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct InventoryApplicationFile {
+    pub key_path: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    pub cell_state: CellState,
+    pub sha1: Option<String>,
+    pub path: Option<String>,
+    pub name: Option<String>,
+    pub publisher: Option<String>,
+    pub version: Option<String>,
+    pub product_name: Option<String>,
+    pub product_version: Option<String>,
+    pub binary_type: Option<String>,
+    pub size: Option<u64>,
+    pub link_date: Option<DateTime<Utc>>,
+    pub program_id: Option<String>,
+    pub is_os_component: Option<bool>,
+}
+
+/// A driver recorded under `Root\InventoryDriverBinary`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct InventoryDriverBinary {
+    pub key_path: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    pub cell_state: CellState,
+    pub sha1: Option<String>,
+    pub path: String,
+    pub name: Option<String>,
+    pub company: Option<String>,
+    pub product: Option<String>,
+    pub version: Option<String>,
+    pub size: Option<u64>,
+    pub link_date: Option<DateTime<Utc>>,
+    pub is_signed: Option<bool>,
+    pub is_kernel_mode: Option<bool>,
+    pub service: Option<String>,
+    pub inf: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "entry_type")]
+pub enum AmcacheEntry {
+    ApplicationFile(InventoryApplicationFile),
+    DriverBinary(InventoryDriverBinary),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Amcache {}
+
+impl Amcache {
+    const APPLICATION_FILE_PATH: &'static str = "Root\\InventoryApplicationFile";
+    const DRIVER_BINARY_PATH: &'static str = "Root\\InventoryDriverBinary";
+
+    /// Returns true if the hive contains the Amcache inventory keys
+    pub fn is_amcache(parser: &mut Parser) -> bool {
+        matches!(
+            parser.get_key(Self::APPLICATION_FILE_PATH, false),
+            Ok(Some(_))
+        ) || matches!(parser.get_key(Self::DRIVER_BINARY_PATH, false), Ok(Some(_)))
+    }
+
+    pub fn application_files(parser: &mut Parser) -> Result<Vec<InventoryApplicationFile>, Error> {
+        Ok(plugins::get_sub_keys(parser, Self::APPLICATION_FILE_PATH)?
+            .iter()
+            .map(Self::read_application_file)
+            .collect())
+    }
+
+    pub fn driver_binaries(parser: &mut Parser) -> Result<Vec<InventoryDriverBinary>, Error> {
+        Ok(plugins::get_sub_keys(parser, Self::DRIVER_BINARY_PATH)?
+            .iter()
+            .map(Self::read_driver_binary)
+            .collect())
+    }
+
+    fn read_application_file(key: &CellKeyNode) -> InventoryApplicationFile {
+        InventoryApplicationFile {
+            key_path: key.path.to_string(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            cell_state: key.cell_state,
+            sha1: plugins::get_value_string(key, "FileId").map(|id| Self::file_id_to_sha1(&id)),
+            path: plugins::get_value_string(key, "LowerCaseLongPath"),
+            name: plugins::get_value_string(key, "Name"),
+            publisher: plugins::get_value_string(key, "Publisher"),
+            version: plugins::get_value_string(key, "Version"),
+            product_name: plugins::get_value_string(key, "ProductName"),
+            product_version: plugins::get_value_string(key, "ProductVersion"),
+            binary_type: plugins::get_value_string(key, "BinaryType"),
+            size: plugins::get_value_u64(key, "Size"),
+            link_date: plugins::get_value_string(key, "LinkDate")
+                .and_then(|d| Self::parse_link_date(&d)),
+            program_id: plugins::get_value_string(key, "ProgramId"),
+            is_os_component: plugins::get_value_bool(key, "IsOsComponent"),
+        }
+    }
+
+    fn read_driver_binary(key: &CellKeyNode) -> InventoryDriverBinary {
+        InventoryDriverBinary {
+            key_path: key.path.to_string(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            cell_state: key.cell_state,
+            sha1: plugins::get_value_string(key, "DriverId").map(|id| Self::file_id_to_sha1(&id)),
+            path: key.key_name.clone(), // the key name is the driver's path
+            name: plugins::get_value_string(key, "DriverName"),
+            company: plugins::get_value_string(key, "DriverCompany"),
+            product: plugins::get_value_string(key, "Product"),
+            version: plugins::get_value_string(key, "DriverVersion"),
+            size: plugins::get_value_u64(key, "ImageSize"),
+            link_date: plugins::get_value_u64(key, "DriverTimeStamp")
+                .and_then(|t| Utc.timestamp_opt(t as i64, 0).single()),
+            is_signed: plugins::get_value_bool(key, "DriverSigned"),
+            is_kernel_mode: plugins::get_value_bool(key, "DriverIsKernelMode"),
+            service: plugins::get_value_string(key, "Service"),
+            inf: plugins::get_value_string(key, "Inf"),
+        }
+    }
+
+    /// FileId / DriverId values are the SHA1 of the file prefixed with four zeros
+    fn file_id_to_sha1(file_id: &str) -> String {
+        let file_id = file_id.trim();
+        match file_id.len() {
+            44 => file_id[4..].to_ascii_lowercase(),
+            _ => file_id.to_ascii_lowercase(),
+        }
+    }
+
+    /// LinkDate values are strings formatted as `MM/DD/YYYY HH:MM:SS` (UTC)
+    fn parse_link_date(link_date: &str) -> Option<DateTime<Utc>> {
+        NaiveDateTime::parse_from_str(link_date.trim(), "%m/%d/%Y %H:%M:%S")
+            .ok()
+            .map(|d| DateTime::<Utc>::from_utc(d, Utc))
+    }
+}
+
+impl Plugin for Amcache {
+    type Record = AmcacheEntry;
+
+    fn name(&self) -> &'static str {
+        "amcache"
+    }
+
+    fn parse(&self, parser: &mut Parser) -> Result<Vec<Self::Record>, Error> {
+        let mut entries: Vec<AmcacheEntry> = Self::application_files(parser)?
+            .into_iter()
+            .map(AmcacheEntry::ApplicationFile)
+            .collect();
+        entries.extend(
+            Self::driver_binaries(parser)?
+                .into_iter()
+                .map(AmcacheEntry::DriverBinary),
+        );
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::cell_value::CellValue;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_file_id_to_sha1() {
+        assert_eq!(
+            "a8a3bd1b9d6eb1bdee4d79e4f3d5c9e2d93a7e15",
+            Amcache::file_id_to_sha1("0000A8A3BD1B9D6EB1BDEE4D79E4F3D5C9E2D93A7E15")
+        );
+        assert_eq!("", Amcache::file_id_to_sha1(""));
+    }
+
+    #[test]
+    fn test_parse_link_date() {
+        assert_eq!(
+            "2019-06-21T20:13:58Z",
+            Amcache::parse_link_date("06/21/2019 20:13:58")
+                .unwrap()
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        );
+        assert_eq!(None, Amcache::parse_link_date("not a date"));
+    }
+
+    #[test]
+    fn test_not_amcache() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        assert!(!Amcache::is_amcache(&mut parser));
+        assert!(Amcache {}.parse(&mut parser).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        let string = |name: &str, content: &str| {
+            WriterValue::from_content(
+                name,
+                CellKeyValueDataTypes::REG_SZ,
+                &CellValue::String(content.to_string()),
+            )
+        };
+        let dword = |name: &str, content: u32| {
+            WriterValue::from_content(
+                name,
+                CellKeyValueDataTypes::REG_DWORD,
+                &CellValue::U32(content),
+            )
+        };
+        let mut root = WriterKey::new("{11517B7C-E79D-4e20-961B-75A811715ADD}");
+        root.get_or_add_sub_key("Root\\InventoryApplicationFile\\notepad.exe|c4d7a2ea0ee9e4a2")
+            .set_value(string(
+                "FileId",
+                "0000A8A3BD1B9D6EB1BDEE4D79E4F3D5C9E2D93A7E15",
+            ))
+            .set_value(string("LowerCaseLongPath", "c:\\windows\\notepad.exe"))
+            .set_value(string("Name", "notepad.exe"))
+            .set_value(string("LinkDate", "06/21/2019 20:13:58"))
+            .set_value(WriterValue::from_content(
+                "Size",
+                CellKeyValueDataTypes::REG_QWORD,
+                &CellValue::U64(201216),
+            ))
+            .set_value(dword("IsOsComponent", 1));
+        root.get_or_add_sub_key(
+            "Root\\InventoryDriverBinary\\c:/windows/system32/drivers/acpi.sys",
+        )
+        .set_value(string("DriverName", "acpi.sys"))
+        .set_value(string("DriverCompany", "Microsoft Corporation"))
+        .set_value(WriterValue::from_content(
+            "DriverTimeStamp",
+            CellKeyValueDataTypes::REG_QWORD,
+            &CellValue::U64(1_561_148_038),
+        ))
+        .set_value(dword("DriverSigned", 1))
+        .set_value(dword("DriverIsKernelMode", 1));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        let mut parser = ParserBuilder::from_file(Cursor::new(hive)).build().unwrap();
+        assert!(Amcache::is_amcache(&mut parser));
+
+        let link_date = Amcache::parse_link_date("06/21/2019 20:13:58");
+        let entries = Amcache {}.parse(&mut parser).unwrap();
+        match &entries[..] {
+            [AmcacheEntry::ApplicationFile(file), AmcacheEntry::DriverBinary(driver)] => {
+                assert_eq!(
+                    (
+                        Some("a8a3bd1b9d6eb1bdee4d79e4f3d5c9e2d93a7e15"),
+                        Some("c:\\windows\\notepad.exe"),
+                        Some("notepad.exe"),
+                        Some(201216),
+                        link_date,
+                        Some(true),
+                        None
+                    ),
+                    (
+                        file.sha1.as_deref(),
+                        file.path.as_deref(),
+                        file.name.as_deref(),
+                        file.size,
+                        file.link_date,
+                        file.is_os_component,
+                        file.publisher.as_deref()
+                    )
+                );
+                assert!(file
+                    .key_path
+                    .ends_with("\\Root\\InventoryApplicationFile\\notepad.exe|c4d7a2ea0ee9e4a2"));
+                assert_eq!(CellState::Allocated, file.cell_state);
+
+                assert_eq!(
+                    (
+                        "c:/windows/system32/drivers/acpi.sys",
+                        Some("acpi.sys"),
+                        Some("Microsoft Corporation"),
+                        link_date,
+                        Some(true),
+                        Some(true)
+                    ),
+                    (
+                        driver.path.as_str(),
+                        driver.name.as_deref(),
+                        driver.company.as_deref(),
+                        driver.link_date,
+                        driver.is_signed,
+                        driver.is_kernel_mode
+                    )
+                );
+            }
+            _ => panic!("unexpected entries: {:?}", entries),
+        }
+    }
+}
@@ -0,0 +1,531 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell::CellState;
+use crate::cell_key_node::CellKeyNode;
+use crate::cell_key_value::CellKeyValue;
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::plugins::services::{ServiceStartType, Services};
+use crate::plugins::{self, Plugin};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/* Persistence locations based upon:
+    https://learn.microsoft.com/en-us/sysinternals/downloads/autoruns
+    https://attack.mitre.org/tactics/TA0003/
+*/
+
+/// The hive a persistence location is in
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum HiveKind {
+    NtUser,
+    Software,
+    System,
+}
+
+/// A registry value (or key) that causes code to run automatically
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct PersistenceEntry {
+    pub category: &'static str,
+    pub hive: HiveKind,
+    /// Index of the hive among those passed to `Autoruns::sweep` (0 for `Autoruns::entries`)
+    pub hive_index: usize,
+    pub key_path: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    pub value_name: Option<String>,
+    pub data: Option<String>,
+    /// Deleted and modified states identify entries recovered from free space or transaction logs
+    pub cell_state: CellState,
+}
+
+/// What to report for a persistence location
+#[derive(Clone, Copy, Debug)]
+enum Check {
+    /// Every value of the key
+    AllValues,
+    /// Only the listed values of the key
+    Values(&'static [&'static str]),
+    /// The listed values of each sub key
+    SubKeyValues(&'static [&'static str]),
+    /// Each sub key (the key name is the entry, such as a CLSID)
+    SubKeys,
+}
+
+struct Location {
+    category: &'static str,
+    hive: HiveKind,
+    /// Relative to the root of the hive; locations which don't exist in a given hive are skipped
+    path: &'static str,
+    check: Check,
+}
+
+impl Location {
+    /// The path is relative to the current control set of a SYSTEM hive
+    fn in_control_set(&self) -> bool {
+        self.hive == HiveKind::System
+    }
+}
+
+const fn ntuser_location(category: &'static str, path: &'static str, check: Check) -> Location {
+    Location {
+        category,
+        hive: HiveKind::NtUser,
+        path,
+        check,
+    }
+}
+
+const fn software_location(category: &'static str, path: &'static str, check: Check) -> Location {
+    Location {
+        category,
+        hive: HiveKind::Software,
+        path,
+        check,
+    }
+}
+
+const fn control_set_location(
+    category: &'static str,
+    path: &'static str,
+    check: Check,
+) -> Location {
+    Location {
+        category,
+        hive: HiveKind::System,
+        path,
+        check,
+    }
+}
+
+const LOCATIONS: &[Location] = &[
+    // NTUSER.DAT
+    ntuser_location(
+        "Run",
+        "Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+        Check::AllValues,
+    ),
+    ntuser_location(
+        "RunOnce",
+        "Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+        Check::AllValues,
+    ),
+    ntuser_location(
+        "Run",
+        "Software\\Microsoft\\Windows\\CurrentVersion\\Policies\\Explorer\\Run",
+        Check::AllValues,
+    ),
+    ntuser_location(
+        "Run",
+        "Software\\Microsoft\\Windows NT\\CurrentVersion\\Windows",
+        Check::Values(&["Load", "Run"]),
+    ),
+    ntuser_location(
+        "Winlogon",
+        "Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon",
+        Check::Values(&["Shell"]),
+    ),
+    // SOFTWARE
+    software_location(
+        "Run",
+        "Microsoft\\Windows\\CurrentVersion\\Run",
+        Check::AllValues,
+    ),
+    software_location(
+        "RunOnce",
+        "Microsoft\\Windows\\CurrentVersion\\RunOnce",
+        Check::AllValues,
+    ),
+    software_location(
+        "RunOnce",
+        "Microsoft\\Windows\\CurrentVersion\\RunOnceEx",
+        Check::AllValues,
+    ),
+    software_location(
+        "Run",
+        "Microsoft\\Windows\\CurrentVersion\\Policies\\Explorer\\Run",
+        Check::AllValues,
+    ),
+    software_location(
+        "Run",
+        "Wow6432Node\\Microsoft\\Windows\\CurrentVersion\\Run",
+        Check::AllValues,
+    ),
+    software_location(
+        "RunOnce",
+        "Wow6432Node\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+        Check::AllValues,
+    ),
+    software_location(
+        "Winlogon",
+        "Microsoft\\Windows NT\\CurrentVersion\\Winlogon",
+        Check::Values(&["Shell", "Userinit", "Taskman", "AppSetup"]),
+    ),
+    software_location(
+        "Winlogon",
+        "Microsoft\\Windows NT\\CurrentVersion\\Winlogon\\Notify",
+        Check::SubKeyValues(&["DllName"]),
+    ),
+    software_location(
+        "AppInit_DLLs",
+        "Microsoft\\Windows NT\\CurrentVersion\\Windows",
+        Check::Values(&["AppInit_DLLs"]),
+    ),
+    software_location(
+        "AppInit_DLLs",
+        "Wow6432Node\\Microsoft\\Windows NT\\CurrentVersion\\Windows",
+        Check::Values(&["AppInit_DLLs"]),
+    ),
+    software_location(
+        "Image File Execution Options",
+        "Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options",
+        Check::SubKeyValues(&["Debugger"]),
+    ),
+    software_location(
+        "Image File Execution Options",
+        "Wow6432Node\\Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options",
+        Check::SubKeyValues(&["Debugger"]),
+    ),
+    software_location(
+        "Active Setup",
+        "Microsoft\\Active Setup\\Installed Components",
+        Check::SubKeyValues(&["StubPath"]),
+    ),
+    software_location(
+        "Browser Helper Objects",
+        "Microsoft\\Windows\\CurrentVersion\\Explorer\\Browser Helper Objects",
+        Check::SubKeys,
+    ),
+    // SYSTEM
+    control_set_location(
+        "LSA",
+        "Control\\Lsa",
+        Check::Values(&[
+            "Authentication Packages",
+            "Security Packages",
+            "Notification Packages",
+        ]),
+    ),
+    control_set_location(
+        "LSA",
+        "Control\\SecurityProviders",
+        Check::Values(&["SecurityProviders"]),
+    ),
+    control_set_location(
+        "BootExecute",
+        "Control\\Session Manager",
+        Check::Values(&["BootExecute", "SetupExecute"]),
+    ),
+    control_set_location(
+        "Print Monitors",
+        "Control\\Print\\Monitors",
+        Check::SubKeyValues(&["Driver"]),
+    ),
+];
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Autoruns {}
+
+impl Autoruns {
+    /// Checks each of the known persistence locations present in the hive. Auto-start services of a SYSTEM hive are included.
+    pub fn entries(parser: &mut Parser) -> Result<Vec<PersistenceEntry>, Error> {
+        let mut entries = Vec::new();
+        Self::read_hive(parser, 0, &mut entries)?;
+        Ok(entries)
+    }
+
+    /// Checks the known persistence locations across a set of hives (ex: a machine's SOFTWARE and SYSTEM hives and the
+    /// NTUSER.DAT of each user). Each entry records the index of its hive in `hives`.
+    pub fn sweep(hives: &mut [Parser]) -> Result<Vec<PersistenceEntry>, Error> {
+        let mut entries = Vec::new();
+        for (hive_index, parser) in hives.iter_mut().enumerate() {
+            Self::read_hive(parser, hive_index, &mut entries)?;
+        }
+        Ok(entries)
+    }
+
+    fn read_hive(
+        parser: &mut Parser,
+        hive_index: usize,
+        entries: &mut Vec<PersistenceEntry>,
+    ) -> Result<(), Error> {
+        let control_set = plugins::get_current_control_set(parser)?;
+        for location in LOCATIONS {
+            let path = if location.in_control_set() {
+                match &control_set {
+                    Some(control_set) => format!("{}\\{}", control_set, location.path),
+                    None => continue,
+                }
+            } else {
+                location.path.to_string()
+            };
+            let mut key = match parser.get_key(&path, false)? {
+                Some(key) => key,
+                None => continue,
+            };
+            let source = (location, hive_index);
+            match location.check {
+                Check::AllValues => Self::read_values(source, &key, None, entries),
+                Check::Values(names) => Self::read_values(source, &key, Some(names), entries),
+                Check::SubKeyValues(names) => {
+                    for sub_key in Self::read_sub_keys(parser, &mut key) {
+                        Self::read_values(source, &sub_key, Some(names), entries);
+                    }
+                }
+                Check::SubKeys => {
+                    for sub_key in Self::read_sub_keys(parser, &mut key) {
+                        entries.push(PersistenceEntry {
+                            category: location.category,
+                            hive: location.hive,
+                            hive_index,
+                            key_path: sub_key.path.to_string(),
+                            last_key_written_date_and_time: sub_key
+                                .last_key_written_date_and_time(),
+                            value_name: None,
+                            data: plugins::get_value_string(&sub_key, ""),
+                            cell_state: sub_key.cell_state,
+                        });
+                    }
+                }
+            }
+        }
+        Self::read_services(parser, hive_index, entries)
+    }
+
+    fn read_sub_keys(parser: &mut Parser, key: &mut CellKeyNode) -> Vec<CellKeyNode> {
+        let mut sub_keys = key.read_sub_keys(parser);
+        sub_keys.append(&mut key.deleted_keys);
+        sub_keys
+    }
+
+    /// Adds an entry for each matching value of `key`, including deleted values and prior versions of values
+    fn read_values(
+        source: (&Location, usize),
+        key: &CellKeyNode,
+        names: Option<&[&str]>,
+        entries: &mut Vec<PersistenceEntry>,
+    ) {
+        for value in key.value_iter() {
+            let value_name = value.detail.value_name();
+            if let Some(names) = names {
+                if !names.iter().any(|n| n.eq_ignore_ascii_case(&value_name)) {
+                    continue;
+                }
+            }
+            for value in std::iter::once(&value).chain(value.versions.iter()) {
+                if let Some(entry) = Self::read_value(source, key, value) {
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+
+    fn read_value(
+        (location, hive_index): (&Location, usize),
+        key: &CellKeyNode,
+        value: &CellKeyValue,
+    ) -> Option<PersistenceEntry> {
+        let data = plugins::value_content_string(value).filter(|d| !d.is_empty())?;
+        Some(PersistenceEntry {
+            category: location.category,
+            hive: location.hive,
+            hive_index,
+            key_path: key.path.to_string(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            value_name: Some(value.detail.value_name()),
+            data: Some(data),
+            cell_state: value.cell_state,
+        })
+    }
+
+    /// Adds the services and drivers which start without user interaction
+    fn read_services(
+        parser: &mut Parser,
+        hive_index: usize,
+        entries: &mut Vec<PersistenceEntry>,
+    ) -> Result<(), Error> {
+        for service in Services::services(parser)? {
+            if !matches!(
+                service.start_type,
+                Some(ServiceStartType::Boot)
+                    | Some(ServiceStartType::System)
+                    | Some(ServiceStartType::Automatic)
+            ) {
+                continue;
+            }
+            let values = [
+                ("ImagePath", service.image_path),
+                ("ServiceDll", service.service_dll),
+            ];
+            for (value_name, data) in values {
+                if let Some(data) = data {
+                    entries.push(PersistenceEntry {
+                        category: "Services",
+                        hive: HiveKind::System,
+                        hive_index,
+                        key_path: service.key_path.clone(),
+                        last_key_written_date_and_time: service.last_key_written_date_and_time,
+                        value_name: Some(value_name.to_string()),
+                        data: Some(data),
+                        cell_state: service.cell_state,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Plugin for Autoruns {
+    type Record = PersistenceEntry;
+
+    fn name(&self) -> &'static str {
+        "autoruns"
+    }
+
+    fn parse(&self, parser: &mut Parser) -> Result<Vec<Self::Record>, Error> {
+        Self::entries(parser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::cell_value::CellValue;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_autoruns_ntuser() {
+        let mut parser = ParserBuilder::from_path("test_data/win7_ntuser.dat")
+            .build()
+            .unwrap();
+        let entries = Autoruns {}.parse(&mut parser).unwrap();
+        assert_eq!(
+            vec![
+                ("Eraser", "C:\\Program Files\\Eraser\\eraser.exe -hide"),
+                (
+                    "MySQL Notifier",
+                    "C:\\Program Files (x86)\\MySQL\\MySQL Notifier 1.1.4\\MySqlNotifier.exe"
+                ),
+                (
+                    "Sidebar",
+                    "C:\\Program Files\\Windows Sidebar\\sidebar.exe /autoRun"
+                )
+            ],
+            entries
+                .iter()
+                .map(|e| (e.value_name.as_deref().unwrap(), e.data.as_deref().unwrap()))
+                .collect::<Vec<(&str, &str)>>()
+        );
+        assert!(entries.iter().all(|e| e.category == "Run"));
+    }
+
+    #[test]
+    fn test_autoruns_system() {
+        let mut parser = ParserBuilder::from_path("test_data/system")
+            .build()
+            .unwrap();
+        let entries = Autoruns {}.parse(&mut parser).unwrap();
+        let boot_execute = entries
+            .iter()
+            .find(|e| e.category == "BootExecute")
+            .unwrap();
+        assert_eq!(
+            Some("autocheck autochk /q /v *".to_string()),
+            boot_execute.data
+        );
+        assert_eq!(4, entries.iter().filter(|e| e.category == "LSA").count());
+        assert!(entries
+            .iter()
+            .any(|e| e.category == "Services" && e.key_path.ends_with("\\Dhcp")));
+    }
+
+    #[test]
+    fn test_autoruns_sweep() {
+        let string_value = |name: &str, data: &str| {
+            WriterValue::from_content(
+                name,
+                CellKeyValueDataTypes::REG_SZ,
+                &CellValue::String(data.to_string()),
+            )
+        };
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("Microsoft\\Windows\\CurrentVersion\\Run")
+            .set_value(string_value("Updater", "C:\\updater.exe"));
+        root.get_or_add_sub_key(
+            "Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options\\sethc.exe",
+        )
+        .set_value(string_value("Debugger", "C:\\Windows\\System32\\cmd.exe"));
+        let software = HiveWriter::new(root).to_bytes().unwrap();
+
+        let mut hives = vec![
+            ParserBuilder::from_path("test_data/win7_ntuser.dat")
+                .build()
+                .unwrap(),
+            ParserBuilder::from_file(Cursor::new(software))
+                .build()
+                .unwrap(),
+            ParserBuilder::from_path("test_data/system")
+                .build()
+                .unwrap(),
+        ];
+        let entries = Autoruns::sweep(&mut hives).unwrap();
+        let hive_entries = |hive_index: usize| {
+            entries
+                .iter()
+                .filter(move |e| e.hive_index == hive_index)
+                .collect::<Vec<_>>()
+        };
+
+        let ntuser = hive_entries(0);
+        assert_eq!(3, ntuser.len());
+        assert!(ntuser
+            .iter()
+            .all(|e| e.hive == HiveKind::NtUser && e.category == "Run"));
+
+        let software = hive_entries(1);
+        assert_eq!(
+            vec![
+                ("Run", "Updater", "C:\\updater.exe"),
+                (
+                    "Image File Execution Options",
+                    "Debugger",
+                    "C:\\Windows\\System32\\cmd.exe"
+                )
+            ],
+            software
+                .iter()
+                .map(|e| (
+                    e.category,
+                    e.value_name.as_deref().unwrap(),
+                    e.data.as_deref().unwrap()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert!(software.iter().all(|e| e.hive == HiveKind::Software));
+
+        let system = hive_entries(2);
+        assert!(system.iter().all(|e| e.hive == HiveKind::System));
+        assert_eq!(
+            Autoruns::entries(&mut hives[2]).unwrap().len(),
+            system.len()
+        );
+        assert_eq!(entries.len(), ntuser.len() + software.len() + system.len());
+    }
+}
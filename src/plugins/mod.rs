@@ -0,0 +1,156 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Decoders for registry artifacts with a well-known schema.
+//!
+//! Each plugin turns the raw keys and values of a hive into typed, serializable records.
+
+pub mod amcache;
+pub mod autoruns;
+pub mod mru;
+pub mod network;
+pub mod profiles;
+pub mod sam;
+pub mod services;
+pub mod shell_bags;
+pub mod user_assist;
+
+use crate::cell_key_node::CellKeyNode;
+use crate::cell_key_value::CellKeyValue;
+use crate::cell_value::CellValue;
+use crate::err::Error;
+use crate::parser::Parser;
+use serde::Serialize;
+use std::convert::TryFrom;
+
+pub trait Plugin {
+    type Record: Serialize;
+
+    /// Short name of the plugin (used to select it from `reg_dump --profile`)
+    fn name(&self) -> &'static str;
+
+    /// Returns the normalized records found in the hive
+    fn parse(&self, parser: &mut Parser) -> Result<Vec<Self::Record>, Error>;
+}
+
+/// Returns the sub keys of the key at `path`, including any deleted sub keys if the parser was built with `recover_deleted`.
+/// Returns an empty vector if the key doesn't exist.
+pub(crate) fn get_sub_keys(parser: &mut Parser, path: &str) -> Result<Vec<CellKeyNode>, Error> {
+    match parser.get_key(path, false)? {
+        Some(mut key) => {
+            let mut sub_keys = key.read_sub_keys(parser);
+            sub_keys.extend(key.deleted_keys);
+            Ok(sub_keys)
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Returns the content of the value `name` as a string. Numeric content is converted to its decimal representation.
+pub(crate) fn get_value_string(key: &CellKeyNode, name: &str) -> Option<String> {
+    value_content_string(&key.get_value(name)?)
+}
+
+/// Returns the content of `value` as a string (see `get_value_string`)
+pub(crate) fn value_content_string(value: &CellKeyValue) -> Option<String> {
+    match value.get_content().0 {
+        CellValue::String(s) => Some(s),
+        CellValue::MultiString(m) => Some(m.join(" ")),
+        CellValue::None | CellValue::Binary(_) | CellValue::Error => None,
+        content => Some(content.to_string()),
+    }
+}
+
+/// Returns the content of the value `name` as a list of strings. String content is split on commas and whitespace
+/// (as used by values such as `NameServer`). Returns an empty vector if the value doesn't exist.
+pub(crate) fn get_value_strings(key: &CellKeyNode, name: &str) -> Vec<String> {
+    match key.get_value(name).map(|value| value.get_content().0) {
+        Some(CellValue::MultiString(m)) => m.into_iter().filter(|s| !s.is_empty()).collect(),
+        Some(CellValue::String(s)) => s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the name of the control set in use (ex: `ControlSet001`) according to the `Select\Current` value of a SYSTEM hive.
+/// Returns None if the hive doesn't have a `Select` key.
+pub(crate) fn get_current_control_set(parser: &mut Parser) -> Result<Option<String>, Error> {
+    Ok(parser
+        .resolve_current_control_set()?
+        .map(|control_sets| control_sets.current_key_name()))
+}
+
+/// Returns the content of the value `name` as a u64. String content is parsed if it's a decimal or `0x` prefixed hex number.
+pub(crate) fn get_value_u64(key: &CellKeyNode, name: &str) -> Option<u64> {
+    let value = key.get_value(name)?;
+    match value.get_content().0 {
+        CellValue::U32(v) => Some(v as u64),
+        CellValue::U64(v) => Some(v),
+        CellValue::I32(v) => u64::try_from(v).ok(),
+        CellValue::I64(v) => u64::try_from(v).ok(),
+        CellValue::String(s) => {
+            let s = s.trim();
+            match s.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                None => s.parse().ok(),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns the content of the value `name` as a bool (any non-zero number is true)
+pub(crate) fn get_value_bool(key: &CellKeyNode, name: &str) -> Option<bool> {
+    get_value_u64(key, name).map(|v| v != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_get_value_helpers() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let key = parser
+            .get_key("Control Panel\\Accessibility\\MouseKeys", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Some("80".to_string()),
+            get_value_string(&key, "MaximumSpeed")
+        );
+        assert_eq!(Some(80), get_value_u64(&key, "MaximumSpeed"));
+        assert_eq!(None, get_value_u64(&key, "Does not exist"));
+
+        assert_eq!(
+            Vec::<String>::new(),
+            get_value_strings(&key, "Does not exist")
+        );
+        assert_eq!(None, get_current_control_set(&mut parser).unwrap());
+
+        let sub_keys = get_sub_keys(&mut parser, "Control Panel").unwrap();
+        assert_eq!(14, sub_keys.len());
+        assert!(get_sub_keys(&mut parser, "Does not exist")
+            .unwrap()
+            .is_empty());
+    }
+}
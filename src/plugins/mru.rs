@@ -0,0 +1,135 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell_key_node::CellKeyNode;
+use crate::cell_key_value::CellKeyValue;
+use crate::plugins;
+use std::convert::TryInto;
+
+/* MRU lists come in two flavors:
+    MRUList:   a string where each character is the name of a value (RunMRU, older RecentDocs)
+    MRUListEx: an array of u32s terminated by 0xFFFFFFFF where each number is the name of a value (RecentDocs, OpenSavePidlMRU, BagMRU)
+   In both cases the first entry is the most recently used.
+*/
+
+/// A value of an MRU key, along with its position in the MRU list (0 is the most recently used)
+#[derive(Clone, Debug)]
+pub struct MruItem {
+    pub position: usize,
+    pub value: CellKeyValue,
+}
+
+const MRU_LIST: &str = "MRUList";
+const MRU_LIST_EX: &str = "MRUListEx";
+const MRU_LIST_EX_TERMINATOR: u32 = 0xffffffff;
+
+/// Returns the values of `key` in MRU order, as listed by its `MRUListEx` or `MRUList` value.
+/// Entries in the list which don't have a matching value are skipped. Returns an empty vector if the key has no MRU list.
+pub fn get_mru_items(key: &CellKeyNode) -> Vec<MruItem> {
+    get_mru_order(key)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(position, value_name)| {
+            key.get_value(&value_name)
+                .map(|value| MruItem { position, value })
+        })
+        .collect()
+}
+
+/// Returns the value names listed by the key's `MRUListEx` or `MRUList` value, most recently used first
+pub fn get_mru_order(key: &CellKeyNode) -> Vec<String> {
+    if let Some(mru_list_ex) = key.get_value(MRU_LIST_EX) {
+        parse_mru_list_ex(mru_list_ex.get_data())
+    } else if let Some(mru_list) = plugins::get_value_string(key, MRU_LIST) {
+        mru_list.chars().map(String::from).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn parse_mru_list_ex(data: &[u8]) -> Vec<String> {
+    data.chunks_exact(4)
+        .map(|b| u32::from_le_bytes(b.try_into().expect("chunks_exact(4)")))
+        .take_while(|index| *index != MRU_LIST_EX_TERMINATOR)
+        .map(|index| index.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_value::CellValue;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_parse_mru_list_ex() {
+        assert_eq!(
+            vec!["2", "0", "1"],
+            parse_mru_list_ex(&[
+                2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 3, 0, 0, 0
+            ])
+        );
+        assert!(parse_mru_list_ex(&[0xff, 0xff, 0xff, 0xff]).is_empty());
+        assert!(parse_mru_list_ex(&[1, 0]).is_empty());
+    }
+
+    #[test]
+    fn test_get_mru_items() {
+        let mut parser = ParserBuilder::from_path("test_data/win7_ntuser.dat")
+            .build()
+            .unwrap();
+
+        // MRUList
+        let key = parser
+            .get_key(
+                "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\RunMRU",
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let items = get_mru_items(&key);
+        assert_eq!(
+            vec!["f", "e", "d", "a", "c", "b"],
+            items
+                .iter()
+                .map(|i| i.value.detail.value_name())
+                .collect::<Vec<String>>()
+        );
+        assert_eq!(0, items[0].position);
+        assert_eq!(
+            CellValue::String("C:\\tools\\1".to_string()),
+            items[0].value.get_content().0
+        );
+
+        // MRUListEx
+        let key = parser
+            .get_key(
+                "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\ComDlg32\\OpenSavePidlMRU\\iso",
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec!["4", "3", "2", "0", "1"], get_mru_order(&key));
+        assert_eq!(5, get_mru_items(&key).len());
+
+        // no MRU list
+        let key = parser
+            .get_key("Software\\Microsoft\\Windows\\CurrentVersion", false)
+            .unwrap()
+            .unwrap();
+        assert!(get_mru_items(&key).is_empty());
+    }
+}
@@ -0,0 +1,364 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell::CellState;
+use crate::cell_key_node::CellKeyNode;
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::plugins::{self, Plugin};
+use crate::util;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/* Network configuration is spread across two hives:
+    SYSTEM:   ControlSet00X\Services\Tcpip\Parameters\Interfaces\{GUID}
+    SOFTWARE: Microsoft\Windows NT\CurrentVersion\NetworkList\Profiles\{GUID}
+              Microsoft\Windows NT\CurrentVersion\NetworkList\Signatures\(Managed|Unmanaged)\{signature}
+*/
+
+/// TCP/IP configuration of an interface, including its most recent DHCP lease
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct NetworkInterface {
+    pub key_path: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    pub cell_state: CellState,
+    pub interface_guid: String,
+    pub enable_dhcp: Option<bool>,
+    pub ip_addresses: Vec<String>,
+    pub subnet_masks: Vec<String>,
+    pub default_gateways: Vec<String>,
+    pub name_servers: Vec<String>,
+    pub domain: Option<String>,
+    pub dhcp_ip_address: Option<String>,
+    pub dhcp_subnet_mask: Option<String>,
+    pub dhcp_server: Option<String>,
+    pub dhcp_default_gateways: Vec<String>,
+    pub dhcp_name_servers: Vec<String>,
+    pub dhcp_domain: Option<String>,
+    pub lease_obtained: Option<DateTime<Utc>>,
+    pub lease_terminates: Option<DateTime<Utc>>,
+}
+
+/// A network the machine has connected to, joined with the details of its signature key
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct NetworkProfile {
+    pub key_path: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    pub cell_state: CellState,
+    pub profile_guid: String,
+    pub profile_name: Option<String>,
+    pub description: Option<String>,
+    pub managed: Option<bool>,
+    pub category: Option<u32>,
+    pub name_type: Option<u32>,
+    /// In the machine's local time (stored as a SYSTEMTIME), so it has no time zone
+    pub date_created: Option<NaiveDateTime>,
+    /// In the machine's local time (stored as a SYSTEMTIME), so it has no time zone
+    pub date_last_connected: Option<NaiveDateTime>,
+    pub dns_suffix: Option<String>,
+    pub default_gateway_mac: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "entry_type")]
+pub enum NetworkEntry {
+    Interface(NetworkInterface),
+    Profile(NetworkProfile),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Network {}
+
+impl Network {
+    const INTERFACES_PATH: &'static str = "Services\\Tcpip\\Parameters\\Interfaces";
+    const PROFILES_PATH: &'static str =
+        "Microsoft\\Windows NT\\CurrentVersion\\NetworkList\\Profiles";
+    const SIGNATURES_PATHS: [&'static str; 2] = [
+        "Microsoft\\Windows NT\\CurrentVersion\\NetworkList\\Signatures\\Managed",
+        "Microsoft\\Windows NT\\CurrentVersion\\NetworkList\\Signatures\\Unmanaged",
+    ];
+
+    /// Returns the interfaces of the current control set of a SYSTEM hive
+    pub fn interfaces(parser: &mut Parser) -> Result<Vec<NetworkInterface>, Error> {
+        match plugins::get_current_control_set(parser)? {
+            Some(control_set) => Ok(plugins::get_sub_keys(
+                parser,
+                &format!("{}\\{}", control_set, Self::INTERFACES_PATH),
+            )?
+            .iter()
+            .map(Self::read_interface)
+            .collect()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the network profiles of a SOFTWARE hive
+    pub fn profiles(parser: &mut Parser) -> Result<Vec<NetworkProfile>, Error> {
+        // the signature keys are keyed by network rather than profile, but each one refers back to its profile
+        let mut signatures: HashMap<String, CellKeyNode> = HashMap::new();
+        for signatures_path in Self::SIGNATURES_PATHS {
+            for signature in plugins::get_sub_keys(parser, signatures_path)? {
+                if let Some(profile_guid) = plugins::get_value_string(&signature, "ProfileGuid") {
+                    signatures.insert(profile_guid.to_ascii_uppercase(), signature);
+                }
+            }
+        }
+        Ok(plugins::get_sub_keys(parser, Self::PROFILES_PATH)?
+            .iter()
+            .map(|profile| {
+                Self::read_profile(
+                    profile,
+                    signatures.get(&profile.key_name.to_ascii_uppercase()),
+                )
+            })
+            .collect())
+    }
+
+    fn read_interface(key: &CellKeyNode) -> NetworkInterface {
+        NetworkInterface {
+            key_path: key.path.to_string(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            cell_state: key.cell_state,
+            interface_guid: key.key_name.clone(),
+            enable_dhcp: plugins::get_value_bool(key, "EnableDHCP"),
+            ip_addresses: plugins::get_value_strings(key, "IPAddress"),
+            subnet_masks: plugins::get_value_strings(key, "SubnetMask"),
+            default_gateways: plugins::get_value_strings(key, "DefaultGateway"),
+            name_servers: plugins::get_value_strings(key, "NameServer"),
+            domain: plugins::get_value_string(key, "Domain").filter(|d| !d.is_empty()),
+            dhcp_ip_address: plugins::get_value_string(key, "DhcpIPAddress"),
+            dhcp_subnet_mask: plugins::get_value_string(key, "DhcpSubnetMask"),
+            dhcp_server: plugins::get_value_string(key, "DhcpServer"),
+            dhcp_default_gateways: plugins::get_value_strings(key, "DhcpDefaultGateway"),
+            dhcp_name_servers: plugins::get_value_strings(key, "DhcpNameServer"),
+            dhcp_domain: plugins::get_value_string(key, "DhcpDomain"),
+            lease_obtained: Self::get_value_epoch(key, "LeaseObtainedTime"),
+            lease_terminates: Self::get_value_epoch(key, "LeaseTerminatesTime"),
+        }
+    }
+
+    fn read_profile(key: &CellKeyNode, signature: Option<&CellKeyNode>) -> NetworkProfile {
+        NetworkProfile {
+            key_path: key.path.to_string(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            cell_state: key.cell_state,
+            profile_guid: key.key_name.clone(),
+            profile_name: plugins::get_value_string(key, "ProfileName"),
+            description: plugins::get_value_string(key, "Description"),
+            managed: plugins::get_value_bool(key, "Managed"),
+            category: plugins::get_value_u64(key, "Category").map(|c| c as u32),
+            name_type: plugins::get_value_u64(key, "NameType").map(|n| n as u32),
+            date_created: Self::get_value_systemtime(key, "DateCreated"),
+            date_last_connected: Self::get_value_systemtime(key, "DateLastConnected"),
+            dns_suffix: signature
+                .and_then(|s| plugins::get_value_string(s, "DnsSuffix"))
+                .filter(|d| d != "<none>"),
+            default_gateway_mac: signature
+                .and_then(|s| s.get_value("DefaultGatewayMac"))
+                .and_then(|v| v.detail.value_bytes())
+                .filter(|mac| !mac.is_empty())
+                .map(|mac| {
+                    mac.iter()
+                        .map(|b| format!("{:02X}", b))
+                        .collect::<Vec<String>>()
+                        .join(":")
+                }),
+        }
+    }
+
+    /// Lease times are stored as seconds since the Unix epoch
+    fn get_value_epoch(key: &CellKeyNode, name: &str) -> Option<DateTime<Utc>> {
+        plugins::get_value_u64(key, name).and_then(util::get_date_time_from_unix_epoch)
+    }
+
+    fn get_value_systemtime(key: &CellKeyNode, name: &str) -> Option<NaiveDateTime> {
+        key.get_value(name)
+            .and_then(|v| v.detail.value_bytes())
+            .and_then(|data| util::read_systemtime(&data, 0))
+    }
+}
+
+impl Plugin for Network {
+    type Record = NetworkEntry;
+
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn parse(&self, parser: &mut Parser) -> Result<Vec<Self::Record>, Error> {
+        let mut entries: Vec<NetworkEntry> = Self::interfaces(parser)?
+            .into_iter()
+            .map(NetworkEntry::Interface)
+            .collect();
+        entries.extend(
+            Self::profiles(parser)?
+                .into_iter()
+                .map(NetworkEntry::Profile),
+        );
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::cell_value::CellValue;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_interfaces() {
+        let mut parser = ParserBuilder::from_path("test_data/system")
+            .build()
+            .unwrap();
+        let entries = Network {}.parse(&mut parser).unwrap();
+        assert_eq!(4, entries.len());
+
+        let interface = match &entries[0] {
+            NetworkEntry::Interface(interface) => interface,
+            entry => panic!("Expected an interface: {:?}", entry),
+        };
+        assert_eq!(
+            "{54b31d7e-36bf-4bbe-9ab2-106a939cd78c}",
+            interface.interface_guid
+        );
+        assert_eq!(Some(true), interface.enable_dhcp);
+        assert_eq!(Some("172.31.74.169".to_string()), interface.dhcp_ip_address);
+        assert_eq!(vec!["172.31.64.1"], interface.dhcp_default_gateways);
+        assert_eq!(vec!["172.31.0.2"], interface.dhcp_name_servers);
+        assert!(interface.name_servers.is_empty());
+        assert_eq!(None, interface.domain);
+        assert_eq!(
+            "2021-08-06T21:41:53.0000000Z",
+            util::format_date_time(interface.lease_obtained.unwrap())
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let string = |name: &str, value: &str| {
+            WriterValue::from_content(
+                name,
+                CellKeyValueDataTypes::REG_SZ,
+                &CellValue::String(value.to_string()),
+            )
+        };
+        let dword = |name: &str, value: u32| {
+            WriterValue::from_content(
+                name,
+                CellKeyValueDataTypes::REG_DWORD,
+                &CellValue::U32(value),
+            )
+        };
+        let binary = |name: &str, value: Vec<u8>| {
+            WriterValue::new(name, CellKeyValueDataTypes::REG_BIN, value)
+        };
+        // year, month, day of week, day, hour, minute, second, milliseconds
+        let systemtime = |fields: [u16; 8]| fields.iter().flat_map(|f| f.to_le_bytes()).collect();
+
+        const HOME: &str = "{3F5A8E21-7B44-4C0D-9E1A-2B6C8D0F4A13}";
+        const OFFICE: &str = "{9C2D4E6F-1A3B-4C5D-8E7F-0A1B2C3D4E5F}";
+        const UNJOINED: &str = "{5E6F7A8B-9C0D-4E1F-A2B3-C4D5E6F7A8B9}";
+        let mut root = WriterKey::new("ROOT");
+        let profiles = root.get_or_add_sub_key(Network::PROFILES_PATH);
+        let home = profiles.get_or_add_sub_key(HOME);
+        home.set_value(string("ProfileName", "HomeWiFi"));
+        home.set_value(string("Description", "HomeWiFi"));
+        home.set_value(dword("Managed", 0));
+        home.set_value(dword("Category", 1));
+        home.set_value(dword("NameType", 71));
+        home.set_value(binary(
+            "DateCreated",
+            systemtime([2021, 3, 2, 16, 9, 30, 15, 250]),
+        ));
+        home.set_value(binary(
+            "DateLastConnected",
+            systemtime([2021, 8, 5, 6, 21, 41, 53, 0]),
+        ));
+        profiles
+            .get_or_add_sub_key(OFFICE)
+            .set_value(string("ProfileName", "corp.example.com"));
+        profiles
+            .get_or_add_sub_key(UNJOINED)
+            .set_value(string("ProfileName", "Guest"));
+
+        // Signatures refer to their profile by GUID, in any case
+        let unmanaged = root
+            .get_or_add_sub_key(Network::SIGNATURES_PATHS[1])
+            .get_or_add_sub_key("010103000F0000F0080000000F0000F0");
+        unmanaged.set_value(string("ProfileGuid", &HOME.to_ascii_lowercase()));
+        unmanaged.set_value(string("DnsSuffix", "<none>"));
+        unmanaged.set_value(binary(
+            "DefaultGatewayMac",
+            vec![0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e],
+        ));
+        let managed = root
+            .get_or_add_sub_key(Network::SIGNATURES_PATHS[0])
+            .get_or_add_sub_key("010103000F0000F0A00000000F0000F0");
+        managed.set_value(string("ProfileGuid", OFFICE));
+        managed.set_value(string("DnsSuffix", "corp.example.com"));
+        managed.set_value(binary("DefaultGatewayMac", Vec::new()));
+
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        let mut parser = ParserBuilder::from_file(std::io::Cursor::new(hive))
+            .build()
+            .unwrap();
+        let profiles = Network::profiles(&mut parser).unwrap();
+        assert_eq!(3, profiles.len());
+        let find = |guid: &str| {
+            profiles
+                .iter()
+                .find(|profile| profile.profile_guid == guid)
+                .unwrap()
+        };
+
+        let home = find(HOME);
+        assert_eq!(Some("HomeWiFi".to_string()), home.profile_name);
+        assert_eq!(Some(false), home.managed);
+        assert_eq!(Some(1), home.category);
+        assert_eq!(Some(71), home.name_type);
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2021, 3, 16)
+                .unwrap()
+                .and_hms_milli_opt(9, 30, 15, 250),
+            home.date_created
+        );
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2021, 8, 6)
+                .unwrap()
+                .and_hms_opt(21, 41, 53),
+            home.date_last_connected
+        );
+        assert_eq!(None, home.dns_suffix);
+        assert_eq!(
+            Some("00:1A:2B:3C:4D:5E".to_string()),
+            home.default_gateway_mac
+        );
+
+        let office = find(OFFICE);
+        assert_eq!(Some("corp.example.com".to_string()), office.dns_suffix);
+        assert_eq!(None, office.default_gateway_mac);
+        assert_eq!(None, office.date_created);
+
+        let unjoined = find(UNJOINED);
+        assert_eq!(Some("Guest".to_string()), unjoined.profile_name);
+        assert_eq!(None, unjoined.dns_suffix);
+        assert_eq!(None, unjoined.default_gateway_mac);
+    }
+}
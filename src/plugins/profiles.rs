@@ -0,0 +1,310 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell::CellState;
+use crate::cell_key_node::CellKeyNode;
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::plugins::{self, Plugin};
+use crate::util;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/* User profiles are listed in the SOFTWARE hive:
+    SOFTWARE: Microsoft\Windows NT\CurrentVersion\ProfileList\{SID}
+   Each user's NTUSER.DAT is in the profile directory, and UsrClass.dat is in AppData\Local\Microsoft\Windows.
+   A user hive is matched to its profile by the file name recorded in its base block (the last 31 characters of the
+   path the hive was loaded from) or, for UsrClass.dat, by its root key name ({SID}_Classes).
+*/
+
+/// A user profile from `Microsoft\Windows NT\CurrentVersion\ProfileList\{SID}`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct UserProfile {
+    pub key_path: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    pub cell_state: CellState,
+    pub sid: String,
+    /// The profile directory (`ProfileImagePath`), with environment variables left unexpanded
+    pub profile_path: Option<String>,
+    /// The last component of the profile directory, which is the user name unless the account was renamed
+    pub username: Option<String>,
+    pub flags: Option<u32>,
+    pub state: Option<u32>,
+    pub last_load_time: Option<DateTime<Utc>>,
+    pub last_unload_time: Option<DateTime<Utc>>,
+    /// Index of the user's NTUSER.DAT within the hives passed to `UserProfiles::discover`
+    pub ntuser_hive: Option<usize>,
+    /// Index of the user's UsrClass.dat within the hives passed to `UserProfiles::discover`
+    pub usrclass_hive: Option<usize>,
+}
+
+/// The kind of user hive, and the end of the profile directory (and SID, if known) it identifies
+#[derive(Debug, PartialEq)]
+enum UserHive {
+    NtUser {
+        profile_path: String,
+    },
+    UsrClass {
+        sid: Option<String>,
+        profile_path: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UserProfiles {}
+
+impl UserProfiles {
+    const PROFILE_LIST_PATH: &'static str = "Microsoft\\Windows NT\\CurrentVersion\\ProfileList";
+    const NTUSER_FILE: &'static str = "\\ntuser.dat";
+    const USRCLASS_FILE: &'static str = "\\appdata\\local\\microsoft\\windows\\usrclass.dat";
+    const CLASSES_ROOT_SUFFIX: &'static str = "_Classes";
+
+    /// Returns the user profiles of a SOFTWARE hive
+    pub fn profiles(software: &mut Parser) -> Result<Vec<UserProfile>, Error> {
+        Ok(plugins::get_sub_keys(software, Self::PROFILE_LIST_PATH)?
+            .iter()
+            .map(Self::read_profile)
+            .collect())
+    }
+
+    /// Returns the user profiles of a SOFTWARE hive, with each one associated with its NTUSER.DAT and UsrClass.dat
+    /// among `user_hives` (if present). Hives which can't be attributed to exactly one profile are left unassociated.
+    pub fn discover(
+        software: &mut Parser,
+        user_hives: &mut [Parser],
+    ) -> Result<Vec<UserProfile>, Error> {
+        let mut profiles = Self::profiles(software)?;
+        for (index, user_hive) in user_hives.iter_mut().enumerate() {
+            let user_hive = match Self::identify(user_hive)? {
+                Some(user_hive) => user_hive,
+                None => continue,
+            };
+            let matching = Self::find_profiles(&profiles, |profile| match &user_hive {
+                UserHive::NtUser { profile_path } => Self::is_profile_path(profile, profile_path),
+                UserHive::UsrClass { sid, profile_path } => match sid {
+                    Some(sid) => profile.sid.eq_ignore_ascii_case(sid),
+                    None => Self::is_profile_path(profile, profile_path),
+                },
+            });
+            if let [profile_index] = matching[..] {
+                let profile = &mut profiles[profile_index];
+                match user_hive {
+                    UserHive::NtUser { .. } => profile.ntuser_hive = Some(index),
+                    UserHive::UsrClass { .. } => profile.usrclass_hive = Some(index),
+                }
+            }
+        }
+        Ok(profiles)
+    }
+
+    fn read_profile(key: &CellKeyNode) -> UserProfile {
+        let profile_path = plugins::get_value_string(key, "ProfileImagePath");
+        UserProfile {
+            key_path: key.path.to_string(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            cell_state: key.cell_state,
+            sid: key.key_name.clone(),
+            username: profile_path
+                .as_deref()
+                .and_then(|path| path.trim_end_matches('\\').rsplit('\\').next())
+                .filter(|name| !name.is_empty())
+                .map(str::to_string),
+            profile_path,
+            flags: plugins::get_value_u64(key, "Flags").map(|f| f as u32),
+            state: plugins::get_value_u64(key, "State").map(|s| s as u32),
+            last_load_time: Self::get_value_filetime(key, "LocalProfileLoadTime"),
+            last_unload_time: Self::get_value_filetime(key, "LocalProfileUnloadTime"),
+            ntuser_hive: None,
+            usrclass_hive: None,
+        }
+    }
+
+    /// Timestamps are stored as the two halves of a FILETIME, in `{name}High` and `{name}Low`
+    fn get_value_filetime(key: &CellKeyNode, name: &str) -> Option<DateTime<Utc>> {
+        let high = plugins::get_value_u64(key, &format!("{}High", name))?;
+        let low = plugins::get_value_u64(key, &format!("{}Low", name))?;
+        match (high << 32) | low {
+            0 => None,
+            filetime => Some(util::get_date_time_from_filetime(filetime)),
+        }
+    }
+
+    /// Returns the kind of user hive `parser` is, based on its base block file name and root key name
+    fn identify(parser: &mut Parser) -> Result<Option<UserHive>, Error> {
+        let filename = match &parser.base_block {
+            Some(base_block) => base_block.base.filename.to_ascii_lowercase(),
+            None => return Ok(None),
+        };
+        // the name is usually prefixed with \??\ (which may be truncated), and '?' can't appear in a path
+        let filename = filename.rsplit('?').next().unwrap_or_default();
+        let filename = filename.strip_prefix('\\').unwrap_or(filename);
+        if let Some(profile_path) = filename.strip_suffix(Self::NTUSER_FILE) {
+            return Ok(Some(UserHive::NtUser {
+                profile_path: profile_path.to_string(),
+            }));
+        }
+        let sid = parser.get_root_key()?.and_then(|root| {
+            root.key_name
+                .strip_suffix(Self::CLASSES_ROOT_SUFFIX)
+                .map(str::to_string)
+        });
+        let profile_path = filename.strip_suffix(Self::USRCLASS_FILE);
+        if sid.is_some() || profile_path.is_some() {
+            return Ok(Some(UserHive::UsrClass {
+                sid,
+                profile_path: profile_path.unwrap_or_default().to_string(),
+            }));
+        }
+        Ok(None)
+    }
+
+    fn find_profiles(
+        profiles: &[UserProfile],
+        is_match: impl Fn(&UserProfile) -> bool,
+    ) -> Vec<usize> {
+        profiles
+            .iter()
+            .enumerate()
+            .filter(|(_, profile)| is_match(profile))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Returns true if the profile's directory ends with `profile_path` (lowercase, and possibly truncated at the start)
+    fn is_profile_path(profile: &UserProfile, profile_path: &str) -> bool {
+        match &profile.profile_path {
+            Some(path) if !profile_path.is_empty() => path
+                .trim_end_matches('\\')
+                .to_ascii_lowercase()
+                .ends_with(profile_path),
+            _ => false,
+        }
+    }
+}
+
+impl Plugin for UserProfiles {
+    type Record = UserProfile;
+
+    fn name(&self) -> &'static str {
+        "profiles"
+    }
+
+    fn parse(&self, parser: &mut Parser) -> Result<Vec<Self::Record>, Error> {
+        Self::profiles(parser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::cell_value::CellValue;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use std::io::Cursor;
+
+    fn profile_key(sid: &str, profile_path: &str) -> WriterKey {
+        let mut key = WriterKey::new(sid);
+        key.set_value(WriterValue::from_content(
+            "ProfileImagePath",
+            CellKeyValueDataTypes::REG_EXPAND_SZ,
+            &CellValue::String(profile_path.to_string()),
+        ));
+        key
+    }
+
+    #[test]
+    fn test_discover() {
+        let mut profile_list = WriterKey::new("ProfileList");
+        profile_list.add_sub_key(profile_key(
+            "S-1-5-18",
+            "%systemroot%\\system32\\config\\systemprofile",
+        ));
+        let mut patrick = profile_key("S-1-5-21-1-1001", "C:\\Users\\Patrick");
+        patrick.set_value(WriterValue::new(
+            "LocalProfileLoadTimeHigh",
+            CellKeyValueDataTypes::REG_DWORD,
+            0x01d7_8b5eu32.to_le_bytes().to_vec(),
+        ));
+        patrick.set_value(WriterValue::new(
+            "LocalProfileLoadTimeLow",
+            CellKeyValueDataTypes::REG_DWORD,
+            0x2d6d_c000u32.to_le_bytes().to_vec(),
+        ));
+        profile_list.add_sub_key(patrick);
+        profile_list.add_sub_key(profile_key("S-1-5-21-1-1002", "C:\\Users\\bob"));
+        profile_list.add_sub_key(profile_key("S-1-5-21-1-1003", "C:\\Users\\jmroberts"));
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("Microsoft\\Windows NT\\CurrentVersion")
+            .add_sub_key(profile_list);
+        let software = HiveWriter::new(root).to_bytes().unwrap();
+        let mut software = ParserBuilder::from_file(Cursor::new(software))
+            .build()
+            .unwrap();
+
+        let usrclass = HiveWriter::new(WriterKey::new("S-1-5-21-1-1002_Classes"))
+            .to_bytes()
+            .unwrap();
+        let mut user_hives = vec![
+            ParserBuilder::from_file(Cursor::new(usrclass))
+                .build()
+                .unwrap(),
+            ParserBuilder::from_path("test_data/NTUSER.DAT")
+                .build()
+                .unwrap(),
+            // the file name in the base block is truncated to ?\C:\Users\jmroberts\ntuser.dat
+            ParserBuilder::from_path("test_data/win7_ntuser.dat")
+                .build()
+                .unwrap(),
+        ];
+
+        let profiles = UserProfiles::discover(&mut software, &mut user_hives).unwrap();
+        assert_eq!(4, profiles.len());
+        assert_eq!("S-1-5-18", profiles[0].sid);
+        assert_eq!(Some("systemprofile".to_string()), profiles[0].username);
+        assert_eq!(
+            (None, None),
+            (profiles[0].ntuser_hive, profiles[0].usrclass_hive)
+        );
+
+        assert_eq!(Some("Patrick".to_string()), profiles[1].username);
+        assert_eq!(
+            (Some(1), None),
+            (profiles[1].ntuser_hive, profiles[1].usrclass_hive)
+        );
+        assert_eq!(
+            "2021-08-07T07:31:03.6072960Z",
+            util::format_date_time(profiles[1].last_load_time.unwrap())
+        );
+
+        assert_eq!(Some("bob".to_string()), profiles[2].username);
+        assert_eq!(
+            (None, Some(0)),
+            (profiles[2].ntuser_hive, profiles[2].usrclass_hive)
+        );
+        assert_eq!(None, profiles[2].last_load_time);
+
+        assert_eq!(
+            (Some(2), None),
+            (profiles[3].ntuser_hive, profiles[3].usrclass_hive)
+        );
+
+        assert_eq!(
+            profiles.len(),
+            UserProfiles {}.parse(&mut software).unwrap().len()
+        );
+    }
+}
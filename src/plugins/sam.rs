@@ -0,0 +1,520 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell::CellState;
+use crate::cell_key_node::CellKeyNode;
+use crate::err::Error;
+use crate::impl_serialize_for_bitflags;
+use crate::parser::Parser;
+use crate::plugins::{self, Plugin};
+use crate::util;
+use bitflags::bitflags;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/* SAM structures based upon:
+    https://github.com/keydet89/RegRipper3.0/blob/master/plugins/samparse.pl
+    https://github.com/libyal/winreg-kb/blob/main/docs/sources/security-accounts-manager/Security-accounts-manager.md
+*/
+
+bitflags! {
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+    pub struct AccountControlFlags: u16 {
+        const ACB_DISABLED   = 0x0001;
+        const ACB_HOMDIRREQ  = 0x0002;
+        const ACB_PWNOTREQ   = 0x0004;
+        const ACB_TEMPDUP    = 0x0008;
+        const ACB_NORMAL     = 0x0010;
+        const ACB_MNS        = 0x0020;
+        const ACB_DOMTRUST   = 0x0040;
+        const ACB_WSTRUST    = 0x0080;
+        const ACB_SVRTRUST   = 0x0100;
+        const ACB_PWNOEXP    = 0x0200;
+        const ACB_AUTOLOCK   = 0x0400;
+    }
+}
+impl_serialize_for_bitflags! { AccountControlFlags }
+
+/// A local user account from `SAM\Domains\Account\Users\{RID}`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct SamUser {
+    pub key_path: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    pub cell_state: CellState,
+    pub rid: u32,
+    /// The user's SID: the SID of the machine's account domain followed by the RID
+    pub sid: Option<String>,
+    pub username: Option<String>,
+    pub full_name: Option<String>,
+    pub comment: Option<String>,
+    pub home_directory: Option<String>,
+    pub profile_path: Option<String>,
+    pub last_login: Option<DateTime<Utc>>,
+    pub password_last_set: Option<DateTime<Utc>>,
+    pub account_expires: Option<DateTime<Utc>>,
+    pub last_failed_login: Option<DateTime<Utc>>,
+    pub login_count: u16,
+    pub failed_login_count: u16,
+    pub account_flags: AccountControlFlags,
+    /// Names of the local groups the user is a member of
+    pub groups: Vec<String>,
+}
+
+/// A local group from `SAM\Domains\(Builtin|Account)\Aliases\{RID}`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct SamGroup {
+    pub key_path: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    pub cell_state: CellState,
+    pub rid: Option<u32>,
+    pub name: Option<String>,
+    pub comment: Option<String>,
+    /// SIDs of the members
+    pub members: Vec<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "entry_type")]
+pub enum SamEntry {
+    User(SamUser),
+    Group(SamGroup),
+}
+
+/// The fixed-length `F` value of a user
+#[derive(Debug, Default, Eq, PartialEq)]
+struct UserF {
+    last_login: Option<DateTime<Utc>>,
+    password_last_set: Option<DateTime<Utc>>,
+    account_expires: Option<DateTime<Utc>>,
+    last_failed_login: Option<DateTime<Utc>>,
+    rid: u32,
+    account_flags: AccountControlFlags,
+    failed_login_count: u16,
+    login_count: u16,
+}
+
+/// The strings of the variable-length `V` value of a user
+#[derive(Debug, Default, Eq, PartialEq)]
+struct UserV {
+    username: Option<String>,
+    full_name: Option<String>,
+    comment: Option<String>,
+    home_directory: Option<String>,
+    profile_path: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sam {}
+
+impl Sam {
+    const ACCOUNT_PATH: &'static str = "SAM\\Domains\\Account";
+    const USERS_PATH: &'static str = "SAM\\Domains\\Account\\Users";
+    const ALIASES_PATHS: [&'static str; 2] = [
+        "SAM\\Domains\\Builtin\\Aliases",
+        "SAM\\Domains\\Account\\Aliases",
+    ];
+
+    const F_LEN: usize = 0x50;
+    // The V value starts with a table of (offset, length, unknown) entries; offsets are relative to the end of the table
+    const V_DATA_OFFSET: usize = 0xcc;
+    const V_USERNAME: usize = 0x0c;
+    const V_FULL_NAME: usize = 0x18;
+    const V_COMMENT: usize = 0x24;
+    const V_HOME_DIRECTORY: usize = 0x48;
+    const V_PROFILE_PATH: usize = 0x6c;
+    // The C value of a group is laid out the same way
+    const C_DATA_OFFSET: usize = 0x34;
+    const C_NAME: usize = 0x04;
+    const C_COMMENT: usize = 0x10;
+    const C_MEMBERS: usize = 0x1c;
+    const C_MEMBER_COUNT: usize = 0x24;
+    // The V value of the account domain ends with its SID (S-1-5-21-x-y-z)
+    const DOMAIN_SID_LEN: usize = 24;
+
+    pub fn users(parser: &mut Parser) -> Result<Vec<SamUser>, Error> {
+        let domain_sid = Self::domain_sid(parser)?;
+        let groups = Self::groups(parser)?;
+        let mut users = Vec::new();
+        for key in plugins::get_sub_keys(parser, Self::USERS_PATH)? {
+            if key.key_name.eq_ignore_ascii_case("Names") {
+                continue;
+            }
+            if let Some(user) = Self::read_user(&key, domain_sid.as_deref(), &groups) {
+                users.push(user);
+            }
+        }
+        Ok(users)
+    }
+
+    /// Returns the SID of the machine's account domain (the SID of its local users without the RID)
+    pub fn domain_sid(parser: &mut Parser) -> Result<Option<String>, Error> {
+        Ok(parser
+            .get_key(Self::ACCOUNT_PATH, false)?
+            .and_then(|key| key.get_value("V")?.detail.value_bytes())
+            .and_then(|v| Self::parse_domain_sid(&v)))
+    }
+
+    pub fn groups(parser: &mut Parser) -> Result<Vec<SamGroup>, Error> {
+        let mut groups = Vec::new();
+        for aliases_path in Self::ALIASES_PATHS {
+            for key in plugins::get_sub_keys(parser, aliases_path)? {
+                if key.key_name.eq_ignore_ascii_case("Members")
+                    || key.key_name.eq_ignore_ascii_case("Names")
+                {
+                    continue;
+                }
+                groups.push(Self::read_group(&key));
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Reads a user. Groups are matched on the user's full SID, so a member with the same RID in another domain
+    /// (ex: a domain account) isn't taken for the user; without `domain_sid` no groups are matched.
+    fn read_user(
+        key: &CellKeyNode,
+        domain_sid: Option<&str>,
+        groups: &[SamGroup],
+    ) -> Option<SamUser> {
+        let f = Self::parse_f(&key.get_value("F")?.detail.value_bytes()?)?;
+        let v = key
+            .get_value("V")
+            .and_then(|v| v.detail.value_bytes())
+            .map(|v| Self::parse_v(&v))
+            .unwrap_or_default();
+
+        let sid = domain_sid.map(|domain_sid| format!("{}-{}", domain_sid, f.rid));
+        let groups = match &sid {
+            Some(sid) => groups
+                .iter()
+                .filter(|group| group.members.contains(sid))
+                .filter_map(|group| group.name.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        Some(SamUser {
+            key_path: key.path.to_string(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            cell_state: key.cell_state,
+            rid: f.rid,
+            sid,
+            username: v.username,
+            full_name: v.full_name,
+            comment: v.comment,
+            home_directory: v.home_directory,
+            profile_path: v.profile_path,
+            last_login: f.last_login,
+            password_last_set: f.password_last_set,
+            account_expires: f.account_expires,
+            last_failed_login: f.last_failed_login,
+            login_count: f.login_count,
+            failed_login_count: f.failed_login_count,
+            account_flags: f.account_flags,
+            groups,
+        })
+    }
+
+    fn read_group(key: &CellKeyNode) -> SamGroup {
+        let mut group = SamGroup {
+            key_path: key.path.to_string(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            cell_state: key.cell_state,
+            rid: u32::from_str_radix(&key.key_name, 16).ok(),
+            ..Default::default()
+        };
+        if let Some(c) = key.get_value("C").and_then(|c| c.detail.value_bytes()) {
+            group.name = Self::read_table_string(&c, Self::C_DATA_OFFSET, Self::C_NAME);
+            group.comment = Self::read_table_string(&c, Self::C_DATA_OFFSET, Self::C_COMMENT);
+            group.members = Self::parse_members(&c);
+        }
+        group
+    }
+
+    fn parse_f(f: &[u8]) -> Option<UserF> {
+        if f.len() < Self::F_LEN {
+            return None;
+        }
+        Some(UserF {
+            last_login: util::read_filetime(f, 0x08),
+            password_last_set: util::read_filetime(f, 0x18),
+            account_expires: util::read_filetime(f, 0x20),
+            last_failed_login: util::read_filetime(f, 0x28),
+            rid: util::read_u32_le(f, 0x30)?,
+            account_flags: AccountControlFlags::from_bits_retain(util::read_u16_le(f, 0x38)?),
+            failed_login_count: util::read_u16_le(f, 0x40)?,
+            login_count: util::read_u16_le(f, 0x42)?,
+        })
+    }
+
+    fn parse_v(v: &[u8]) -> UserV {
+        UserV {
+            username: Self::read_table_string(v, Self::V_DATA_OFFSET, Self::V_USERNAME),
+            full_name: Self::read_table_string(v, Self::V_DATA_OFFSET, Self::V_FULL_NAME),
+            comment: Self::read_table_string(v, Self::V_DATA_OFFSET, Self::V_COMMENT),
+            home_directory: Self::read_table_string(v, Self::V_DATA_OFFSET, Self::V_HOME_DIRECTORY),
+            profile_path: Self::read_table_string(v, Self::V_DATA_OFFSET, Self::V_PROFILE_PATH),
+        }
+    }
+
+    /// Returns the SIDs listed in a group's C value
+    fn parse_members(c: &[u8]) -> Vec<String> {
+        let mut members = Vec::new();
+        if let (Some(offset), Some(count)) = (
+            util::read_u32_le(c, Self::C_MEMBERS),
+            util::read_u32_le(c, Self::C_MEMBER_COUNT),
+        ) {
+            let mut offset = Self::C_DATA_OFFSET + offset as usize;
+            for _ in 0..count {
+                match Self::read_sid(c, offset) {
+                    Some((sid, len)) => {
+                        members.push(sid);
+                        offset += len;
+                    }
+                    None => break,
+                }
+            }
+        }
+        members
+    }
+
+    /// Reads the SID at the end of the account domain's V value
+    fn parse_domain_sid(v: &[u8]) -> Option<String> {
+        let offset = v.len().checked_sub(Self::DOMAIN_SID_LEN)?;
+        let (sid, len) = Self::read_sid(v, offset)?;
+        Some(sid).filter(|sid| len == Self::DOMAIN_SID_LEN && sid.starts_with("S-1-5-21-"))
+    }
+
+    /// Reads a binary SID, returning its string form and its length
+    fn read_sid(data: &[u8], offset: usize) -> Option<(String, usize)> {
+        let revision = *data.get(offset)?;
+        let sub_authority_count = *data.get(offset + 1)? as usize;
+        let authority = data
+            .get(offset + 2..offset + 8)?
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        let mut sid = format!("S-{}-{}", revision, authority);
+        for i in 0..sub_authority_count {
+            sid.push_str(&format!(
+                "-{}",
+                util::read_u32_le(data, offset + 8 + i * 4)?
+            ));
+        }
+        Some((sid, 8 + sub_authority_count * 4))
+    }
+
+    /// Reads the UTF-16LE string described by the (offset, length) entry at `entry_offset` of a V or C value's table
+    fn read_table_string(data: &[u8], data_offset: usize, entry_offset: usize) -> Option<String> {
+        let offset = data_offset + util::read_u32_le(data, entry_offset)? as usize;
+        let len = util::read_u32_le(data, entry_offset + 4)? as usize;
+        let string = util::from_utf16_le_string(
+            data.get(offset..offset + len)?,
+            len,
+            &mut Default::default(),
+            "SAM string",
+        );
+        Some(string).filter(|s| !s.is_empty())
+    }
+}
+
+impl Plugin for Sam {
+    type Record = SamEntry;
+
+    fn name(&self) -> &'static str {
+        "sam"
+    }
+
+    fn parse(&self, parser: &mut Parser) -> Result<Vec<Self::Record>, Error> {
+        let mut entries: Vec<SamEntry> = Self::users(parser)?
+            .into_iter()
+            .map(SamEntry::User)
+            .collect();
+        entries.extend(Self::groups(parser)?.into_iter().map(SamEntry::Group));
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use std::io::Cursor;
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    /// Builds a value with a table of (offset, length) entries followed by the strings
+    fn make_table_value(table_len: usize, strings: &[(usize, &str)]) -> Vec<u8> {
+        let mut table = vec![0u8; table_len];
+        let mut data = Vec::new();
+        for (entry_offset, s) in strings {
+            let s = utf16(s);
+            table[*entry_offset..*entry_offset + 4]
+                .copy_from_slice(&(data.len() as u32).to_le_bytes());
+            table[*entry_offset + 4..*entry_offset + 8]
+                .copy_from_slice(&(s.len() as u32).to_le_bytes());
+            data.extend(s);
+        }
+        table.extend(data);
+        table
+    }
+
+    #[test]
+    fn test_parse_f() {
+        let mut f = vec![0u8; Sam::F_LEN];
+        f[0x08..0x10].copy_from_slice(&132_735_000_000_000_000u64.to_le_bytes());
+        f[0x20..0x28].copy_from_slice(&0x7fffffffffffffffu64.to_le_bytes());
+        f[0x30..0x34].copy_from_slice(&1001u32.to_le_bytes());
+        f[0x38..0x3a].copy_from_slice(&0x0211u16.to_le_bytes());
+        f[0x40..0x42].copy_from_slice(&2u16.to_le_bytes());
+        f[0x42..0x44].copy_from_slice(&37u16.to_le_bytes());
+
+        let f = Sam::parse_f(&f).unwrap();
+        assert_eq!(1001, f.rid);
+        assert_eq!(
+            "2021-08-15T11:20:00.0000000Z",
+            util::format_date_time(f.last_login.unwrap())
+        );
+        assert_eq!(None, f.password_last_set);
+        assert_eq!(None, f.account_expires);
+        assert_eq!(
+            AccountControlFlags::ACB_DISABLED
+                | AccountControlFlags::ACB_NORMAL
+                | AccountControlFlags::ACB_PWNOEXP,
+            f.account_flags
+        );
+        assert_eq!(2, f.failed_login_count);
+        assert_eq!(37, f.login_count);
+
+        assert_eq!(None, Sam::parse_f(&[0; 8]));
+    }
+
+    #[test]
+    fn test_parse_v() {
+        let v = make_table_value(
+            Sam::V_DATA_OFFSET,
+            &[
+                (Sam::V_USERNAME, "Patrick"),
+                (Sam::V_FULL_NAME, "Patrick Star"),
+                (Sam::V_PROFILE_PATH, "C:\\Users\\Patrick"),
+            ],
+        );
+        assert_eq!(
+            UserV {
+                username: Some("Patrick".to_string()),
+                full_name: Some("Patrick Star".to_string()),
+                comment: None,
+                home_directory: None,
+                profile_path: Some("C:\\Users\\Patrick".to_string()),
+            },
+            Sam::parse_v(&v)
+        );
+        assert_eq!(UserV::default(), Sam::parse_v(&[0; 4]));
+    }
+
+    #[test]
+    fn test_parse_members() {
+        let mut c = make_table_value(Sam::C_DATA_OFFSET, &[(Sam::C_NAME, "Administrators")]);
+        let members_offset = c.len() - Sam::C_DATA_OFFSET;
+        c[Sam::C_MEMBERS..Sam::C_MEMBERS + 4]
+            .copy_from_slice(&(members_offset as u32).to_le_bytes());
+        c[Sam::C_MEMBER_COUNT..Sam::C_MEMBER_COUNT + 4].copy_from_slice(&2u32.to_le_bytes());
+        // S-1-5-21-1-2-3-500
+        c.extend([1, 5, 0, 0, 0, 0, 0, 5]);
+        for sub_authority in [21u32, 1, 2, 3, 500] {
+            c.extend(sub_authority.to_le_bytes());
+        }
+        // S-1-5-32-544 (with a truncated second member)
+        c.extend([1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0]);
+
+        assert_eq!(
+            Some("Administrators".to_string()),
+            Sam::read_table_string(&c, Sam::C_DATA_OFFSET, Sam::C_NAME)
+        );
+        assert_eq!(vec!["S-1-5-21-1-2-3-500"], Sam::parse_members(&c));
+    }
+
+    fn binary_sid(sub_authorities: &[u32]) -> Vec<u8> {
+        let mut sid = vec![1, sub_authorities.len() as u8, 0, 0, 0, 0, 0, 5];
+        for sub_authority in sub_authorities {
+            sid.extend(sub_authority.to_le_bytes());
+        }
+        sid
+    }
+
+    /// Builds a group's C value listing `members`
+    fn make_c_value(name: &str, members: &[&[u32]]) -> Vec<u8> {
+        let mut c = make_table_value(Sam::C_DATA_OFFSET, &[(Sam::C_NAME, name)]);
+        let members_offset = c.len() - Sam::C_DATA_OFFSET;
+        c[Sam::C_MEMBERS..Sam::C_MEMBERS + 4]
+            .copy_from_slice(&(members_offset as u32).to_le_bytes());
+        c[Sam::C_MEMBER_COUNT..Sam::C_MEMBER_COUNT + 4]
+            .copy_from_slice(&(members.len() as u32).to_le_bytes());
+        for member in members {
+            c.extend(binary_sid(member));
+        }
+        c
+    }
+
+    #[test]
+    fn test_group_membership() {
+        let binary = |name: &str, data: Vec<u8>| {
+            WriterValue::new(name, CellKeyValueDataTypes::REG_BIN, data)
+        };
+        let mut f = vec![0u8; Sam::F_LEN];
+        f[0x30..0x34].copy_from_slice(&1001u32.to_le_bytes());
+        let mut account_v = vec![0u8; 0x40];
+        account_v.extend(binary_sid(&[21, 1, 2, 3]));
+
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("SAM\\Domains\\Account")
+            .set_value(binary("V", account_v));
+        root.get_or_add_sub_key("SAM\\Domains\\Account\\Users\\000003E9")
+            .set_value(binary("F", f));
+        root.get_or_add_sub_key("SAM\\Domains\\Builtin\\Aliases\\00000220")
+            .set_value(binary(
+                "C",
+                make_c_value("Administrators", &[&[21, 1, 2, 3, 1001]]),
+            ));
+        // a domain account with the same RID
+        root.get_or_add_sub_key("SAM\\Domains\\Builtin\\Aliases\\00000221")
+            .set_value(binary("C", make_c_value("Users", &[&[21, 7, 8, 9, 1001]])));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        let mut parser = ParserBuilder::from_file(Cursor::new(hive)).build().unwrap();
+
+        assert_eq!(
+            Some("S-1-5-21-1-2-3".to_string()),
+            Sam::domain_sid(&mut parser).unwrap()
+        );
+        let users = Sam::users(&mut parser).unwrap();
+        assert_eq!(1, users.len());
+        assert_eq!(Some("S-1-5-21-1-2-3-1001"), users[0].sid.as_deref());
+        assert_eq!(vec!["Administrators"], users[0].groups);
+
+        assert_eq!(None, Sam::parse_domain_sid(&binary_sid(&[32, 544])));
+    }
+
+    #[test]
+    fn test_not_sam() {
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        assert!(Sam {}.parse(&mut parser).unwrap().is_empty());
+    }
+}
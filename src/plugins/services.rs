@@ -0,0 +1,207 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell::CellState;
+use crate::cell_key_node::CellKeyNode;
+use crate::err::Error;
+use crate::impl_serialize_for_bitflags;
+use crate::parser::Parser;
+use crate::plugins::{self, Plugin};
+use bitflags::bitflags;
+use chrono::{DateTime, Utc};
+use enum_primitive_derive::Primitive;
+use num_traits::FromPrimitive;
+use serde::Serialize;
+
+/* Service values based upon:
+    https://learn.microsoft.com/en-us/windows-hardware/drivers/install/inf-addservice-directive
+*/
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Primitive, Serialize)]
+#[repr(u32)]
+pub enum ServiceStartType {
+    Boot = 0,
+    System = 1,
+    Automatic = 2,
+    Manual = 3,
+    Disabled = 4,
+    Unknown = 0x0fffffff,
+}
+
+bitflags! {
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+    pub struct ServiceTypeFlags: u32 {
+        const SERVICE_KERNEL_DRIVER        = 0x00000001;
+        const SERVICE_FILE_SYSTEM_DRIVER   = 0x00000002;
+        const SERVICE_ADAPTER              = 0x00000004;
+        const SERVICE_RECOGNIZER_DRIVER    = 0x00000008;
+        const SERVICE_WIN32_OWN_PROCESS    = 0x00000010;
+        const SERVICE_WIN32_SHARE_PROCESS  = 0x00000020;
+        const SERVICE_USER_SERVICE         = 0x00000040;
+        const SERVICE_USERSERVICE_INSTANCE = 0x00000080;
+        const SERVICE_INTERACTIVE_PROCESS  = 0x00000100;
+        const SERVICE_PKG_SERVICE          = 0x00000200;
+    }
+}
+impl_serialize_for_bitflags! { ServiceTypeFlags }
+
+impl ServiceTypeFlags {
+    pub fn is_driver(&self) -> bool {
+        self.intersects(
+            Self::SERVICE_KERNEL_DRIVER
+                | Self::SERVICE_FILE_SYSTEM_DRIVER
+                | Self::SERVICE_RECOGNIZER_DRIVER,
+        )
+    }
+}
+
+/// A service or driver from `ControlSet00X\Services`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Service {
+    pub key_path: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    /// Allocated for active services; deleted or modified states for services recovered from free space or transaction logs
+    pub cell_state: CellState,
+    pub control_set: String,
+    pub name: String,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub start_type: Option<ServiceStartType>,
+    pub service_type: Option<ServiceTypeFlags>,
+    pub image_path: Option<String>,
+    /// `Parameters\ServiceDll` (or `ServiceDll` directly under the service key) for svchost hosted services
+    pub service_dll: Option<String>,
+    /// The account the service runs as
+    pub object_name: Option<String>,
+    pub group: Option<String>,
+    pub depend_on_service: Vec<String>,
+    pub depend_on_group: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Services {}
+
+impl Services {
+    const SERVICES_PATH: &'static str = "Services";
+
+    /// Returns the services of the current control set of a SYSTEM hive (as selected by `Select\Current`).
+    /// Returns an empty vector if the hive isn't a SYSTEM hive.
+    pub fn services(parser: &mut Parser) -> Result<Vec<Service>, Error> {
+        match plugins::get_current_control_set(parser)? {
+            Some(control_set) => Self::services_for_control_set(parser, &control_set),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the services of `control_set` (ex: `ControlSet002`)
+    pub fn services_for_control_set(
+        parser: &mut Parser,
+        control_set: &str,
+    ) -> Result<Vec<Service>, Error> {
+        let mut services = Vec::new();
+        for mut key in
+            plugins::get_sub_keys(parser, &format!("{}\\{}", control_set, Self::SERVICES_PATH))?
+        {
+            let service_dll = key
+                .get_sub_key_by_path(parser, "Parameters")
+                .and_then(|parameters| plugins::get_value_string(&parameters, "ServiceDll"))
+                .or_else(|| plugins::get_value_string(&key, "ServiceDll"));
+            services.push(Self::read_service(&key, control_set, service_dll));
+        }
+        Ok(services)
+    }
+
+    fn read_service(key: &CellKeyNode, control_set: &str, service_dll: Option<String>) -> Service {
+        Service {
+            key_path: key.path.to_string(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            cell_state: key.cell_state,
+            control_set: control_set.to_string(),
+            name: key.key_name.clone(),
+            display_name: plugins::get_value_string(key, "DisplayName"),
+            description: plugins::get_value_string(key, "Description"),
+            start_type: plugins::get_value_u64(key, "Start").map(|start| {
+                ServiceStartType::from_u64(start).unwrap_or(ServiceStartType::Unknown)
+            }),
+            service_type: plugins::get_value_u64(key, "Type")
+                .map(|service_type| ServiceTypeFlags::from_bits_retain(service_type as u32)),
+            image_path: plugins::get_value_string(key, "ImagePath"),
+            service_dll,
+            object_name: plugins::get_value_string(key, "ObjectName"),
+            group: plugins::get_value_string(key, "Group"),
+            depend_on_service: plugins::get_value_strings(key, "DependOnService"),
+            depend_on_group: plugins::get_value_strings(key, "DependOnGroup"),
+        }
+    }
+}
+
+impl Plugin for Services {
+    type Record = Service;
+
+    fn name(&self) -> &'static str {
+        "services"
+    }
+
+    fn parse(&self, parser: &mut Parser) -> Result<Vec<Self::Record>, Error> {
+        Self::services(parser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_services() {
+        let mut parser = ParserBuilder::from_path("test_data/system")
+            .build()
+            .unwrap();
+        let services = Services {}.parse(&mut parser).unwrap();
+        assert_eq!(597, services.len());
+
+        let dhcp = services.iter().find(|s| s.name == "Dhcp").unwrap();
+        assert_eq!("ControlSet001", dhcp.control_set);
+        assert_eq!(Some(ServiceStartType::Automatic), dhcp.start_type);
+        assert_eq!(
+            Some(ServiceTypeFlags::SERVICE_WIN32_SHARE_PROCESS),
+            dhcp.service_type
+        );
+        assert!(!dhcp.service_type.unwrap().is_driver());
+        assert_eq!(
+            Some("%SystemRoot%\\system32\\dhcpcore.dll".to_string()),
+            dhcp.service_dll
+        );
+        assert_eq!(
+            Some("NT Authority\\LocalService".to_string()),
+            dhcp.object_name
+        );
+        assert_eq!(vec!["NSI", "Afd"], dhcp.depend_on_service);
+
+        assert_eq!(
+            services.len(),
+            Services::services_for_control_set(&mut parser, "ControlSet001")
+                .unwrap()
+                .len()
+        );
+
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        assert!(Services {}.parse(&mut parser).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell::CellState;
+use crate::cell_key_node::CellKeyNode;
+use crate::cell_key_value::CellKeyValue;
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::plugins::{self, Plugin};
+use crate::shell_items::{self, ShellItem};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/* ShellBag layout based upon:
+    https://github.com/EricZimmerman/RegistryPlugins/tree/master/RegistryPlugin.ShellBags
+    https://www.sans.org/white-papers/34545/
+*/
+
+/// A folder recorded under a `Shell\BagMRU` key
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ShellBag {
+    pub key_path: String,
+    pub value_name: String,
+    /// The full path of the folder, built from this item and its parents
+    pub path: String,
+    pub shell_item: ShellItem,
+    /// Index of the corresponding `Bags` key, which holds the folder's view settings
+    pub node_slot: Option<u32>,
+    /// Last written time of the item's BagMRU sub key; generally the time the folder was last interacted with
+    pub last_key_written_date_and_time: Option<DateTime<Utc>>,
+    pub cell_state: CellState,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShellBags {}
+
+impl ShellBags {
+    const BAG_MRU_PATHS: [&'static str; 3] = [
+        "Software\\Microsoft\\Windows\\Shell\\BagMRU", // NTUSER.DAT
+        "Software\\Microsoft\\Windows\\ShellNoRoam\\BagMRU", // NTUSER.DAT (Windows XP)
+        "Local Settings\\Software\\Microsoft\\Windows\\Shell\\BagMRU", // UsrClass.dat
+    ];
+
+    /// Walks the `BagMRU` keys. A sub key that was already walked (in a hive whose sub key lists lead back to a key's
+    /// ancestor) isn't walked again, and sub keys past `ParserBuilderFromPath::max_depth` aren't walked.
+    pub fn shell_bags(parser: &mut Parser) -> Result<Vec<ShellBag>, Error> {
+        let mut shell_bags = Vec::new();
+        let max_depth = parser.state.limits.max_depth;
+        for bag_mru_path in Self::BAG_MRU_PATHS {
+            if let Some(root) = parser.get_key(bag_mru_path, false)? {
+                let mut visited = HashSet::new();
+                visited.insert(root.file_offset_absolute);
+                // each numbered value is a shell item; the sub key with the same name holds the item's children
+                let mut stack = vec![(root, String::new(), bag_mru_path.split('\\').count())];
+                while let Some((mut key, parent_path, depth)) = stack.pop() {
+                    let values: Vec<CellKeyValue> = key.value_iter().collect();
+                    for value in values {
+                        let value_name = value.detail.value_name();
+                        if value_name.parse::<u32>().is_err() {
+                            continue; // MRUListEx, NodeSlot, NodeSlots
+                        }
+                        let id_list = value.get_data();
+                        let shell_item = match shell_items::parse_id_list(id_list).pop() {
+                            Some(shell_item) => shell_item,
+                            None => continue,
+                        };
+                        let path = match &shell_item {
+                            // network locations are full UNC paths
+                            ShellItem::NetworkLocation { location, .. } => location.clone(),
+                            _ => Self::join_path(&parent_path, &shell_item.name()),
+                        };
+                        let sub_key = key.get_sub_key_by_path(parser, &value_name);
+                        shell_bags.push(ShellBag {
+                            key_path: key.path.to_string(),
+                            value_name,
+                            path: path.clone(),
+                            shell_item,
+                            node_slot: sub_key
+                                .as_ref()
+                                .and_then(|k| plugins::get_value_u64(k, "NodeSlot"))
+                                .map(|slot| slot as u32),
+                            last_key_written_date_and_time: sub_key
+                                .as_ref()
+                                .map(CellKeyNode::last_key_written_date_and_time),
+                            cell_state: value.cell_state,
+                        });
+                        if let Some(sub_key) = sub_key {
+                            if max_depth.is_none_or(|max_depth| depth < max_depth)
+                                && visited.insert(sub_key.file_offset_absolute)
+                            {
+                                stack.push((sub_key, path, depth + 1));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(shell_bags)
+    }
+
+    fn join_path(parent_path: &str, name: &str) -> String {
+        if parent_path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}\\{}", parent_path, name)
+        }
+    }
+}
+
+impl Plugin for ShellBags {
+    type Record = ShellBag;
+
+    fn name(&self) -> &'static str {
+        "shellbags"
+    }
+
+    fn parse(&self, parser: &mut Parser) -> Result<Vec<Self::Record>, Error> {
+        Self::shell_bags(parser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_shell_bags() {
+        let mut parser = ParserBuilder::from_path("test_data/win7_ntuser.dat")
+            .build()
+            .unwrap();
+        let shell_bags = ShellBags {}.parse(&mut parser).unwrap();
+        assert_eq!(69, shell_bags.len());
+
+        let software = shell_bags
+            .iter()
+            .find(|s| s.key_path.ends_with("Shell\\BagMRU\\0\\2\\0") && s.value_name == "0")
+            .unwrap();
+        assert_eq!("\\\\seacelox01\\data\\_Software", software.path);
+        assert_eq!(Some(6), software.node_slot);
+        match &software.shell_item {
+            ShellItem::FileEntry { short_name, .. } => assert_eq!("_SOFTW~1", short_name),
+            item => panic!("Expected a file entry: {:?}", item),
+        }
+
+        let mut parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        assert!(ShellBags {}.parse(&mut parser).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_shell_bags_cycle() {
+        let patch = |hive: &mut Vec<u8>, offset: usize, value: u32| {
+            hive[offset..offset + 4].copy_from_slice(&value.to_le_bytes())
+        };
+        // the Network root folder
+        let id_list = vec![
+            0x14, 0x00, 0x1F, 0x58, 0x0D, 0x1A, 0x2C, 0xF0, 0x21, 0xBE, 0x50, 0x43, 0x88, 0xB0,
+            0x73, 0x67, 0xFC, 0x96, 0xEF, 0x3C, 0x00, 0x00,
+        ];
+        let mut root = WriterKey::new("ROOT");
+        let bag_mru = root.get_or_add_sub_key(ShellBags::BAG_MRU_PATHS[0]);
+        bag_mru.set_value(WriterValue::new(
+            "0",
+            CellKeyValueDataTypes::REG_BIN,
+            id_list.clone(),
+        ));
+        bag_mru.get_or_add_sub_key("0").set_value(WriterValue::new(
+            "0",
+            CellKeyValueDataTypes::REG_BIN,
+            id_list,
+        ));
+        bag_mru.get_or_add_sub_key("0\\0");
+        let mut hive = HiveWriter::new(root).to_bytes().unwrap();
+        let build = |hive: Vec<u8>, max_depth: Option<usize>| {
+            let mut builder = ParserBuilder::from_file(Cursor::new(hive));
+            if let Some(max_depth) = max_depth {
+                builder.max_depth(max_depth);
+            }
+            builder.build().unwrap()
+        };
+
+        let mut parser = build(hive.clone(), None);
+        assert_eq!(2, ShellBags::shell_bags(&mut parser).unwrap().len());
+        // BagMRU is 5 levels below the root key, so its sub keys aren't walked
+        let mut parser = build(hive.clone(), Some(5));
+        assert_eq!(1, ShellBags::shell_bags(&mut parser).unwrap().len());
+
+        // Give BagMRU\0 the sub key list of BagMRU, so its sub key 0 is itself
+        let bag_mru = parser
+            .get_key(ShellBags::BAG_MRU_PATHS[0], false)
+            .unwrap()
+            .unwrap();
+        let nk = parser
+            .get_key(&format!("{}\\0", ShellBags::BAG_MRU_PATHS[0]), false)
+            .unwrap()
+            .unwrap()
+            .file_offset_absolute
+            + 4;
+        patch(
+            &mut hive,
+            nk + CellKeyNode::SUB_KEYS_LIST_OFFSET_FIELD,
+            bag_mru.detail.sub_keys_list_offset_relative(),
+        );
+        let mut parser = build(hive, None);
+        let shell_bags = ShellBags::shell_bags(&mut parser).unwrap();
+        assert_eq!(2, shell_bags.len());
+        assert_eq!("Network\\Network", shell_bags[1].path);
+    }
+}
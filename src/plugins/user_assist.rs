@@ -0,0 +1,174 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell::CellState;
+use crate::cell_key_value::CellKeyValue;
+use crate::err::Error;
+use crate::parser::Parser;
+use crate::plugins::{self, Plugin};
+use crate::util;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/* UserAssist value layout based upon:
+    https://github.com/EricZimmerman/RegistryPlugins/tree/master/RegistryPlugin.UserAssist
+    https://www.aldeid.com/wiki/Windows-userassist-keys
+*/
+
+/// A program execution entry from an `Explorer\UserAssist\{GUID}\Count` value
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct UserAssistEntry {
+    pub key_path: String,
+    pub guid: String,
+    /// The value name with the ROT13 encoding removed
+    pub name: String,
+    pub value_name: String,
+    pub cell_state: CellState,
+    pub run_count: Option<u32>,
+    pub focus_count: Option<u32>,
+    pub focus_time_ms: Option<u32>,
+    pub last_executed: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UserAssist {}
+
+impl UserAssist {
+    const USER_ASSIST_PATH: &'static str =
+        "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\UserAssist";
+
+    // Windows XP / 2003 values are 16 bytes: session id, run count (offset by 5), last executed
+    const DATA_LEN_V3: usize = 16;
+    // Windows 7 and later values are 72 bytes: session id, run count, focus count, focus time, ..., last executed
+    const DATA_LEN_V5: usize = 72;
+    // Windows XP run counts start at 5
+    const RUN_COUNT_V3_OFFSET: u32 = 5;
+
+    pub fn entries(parser: &mut Parser) -> Result<Vec<UserAssistEntry>, Error> {
+        let mut entries = Vec::new();
+        for mut guid_key in plugins::get_sub_keys(parser, Self::USER_ASSIST_PATH)? {
+            if let Some(count_key) = guid_key.get_sub_key_by_path(parser, "Count") {
+                for value in count_key.value_iter() {
+                    entries.push(Self::read_entry(
+                        &count_key.path,
+                        &guid_key.key_name,
+                        &value,
+                    ));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn read_entry(key_path: &str, guid: &str, value: &CellKeyValue) -> UserAssistEntry {
+        let value_name = value.detail.value_name();
+        let mut entry = UserAssistEntry {
+            key_path: key_path.to_string(),
+            guid: guid.to_string(),
+            name: util::decode_rot13(&value_name),
+            value_name,
+            cell_state: value.cell_state,
+            ..Default::default()
+        };
+
+        let data = value.get_data();
+        match data.len() {
+            Self::DATA_LEN_V3 => {
+                entry.run_count = util::read_u32_le(data, 4)
+                    .map(|count| count.saturating_sub(Self::RUN_COUNT_V3_OFFSET));
+                entry.last_executed = util::read_filetime(data, 8);
+            }
+            len if len >= Self::DATA_LEN_V5 => {
+                entry.run_count = util::read_u32_le(data, 4);
+                entry.focus_count = util::read_u32_le(data, 8);
+                entry.focus_time_ms = util::read_u32_le(data, 12);
+                entry.last_executed = util::read_filetime(data, 60);
+            }
+            _ => (), // UEME_CTLSESSION and other bookkeeping values don't follow the entry layout
+        }
+        entry
+    }
+}
+
+impl Plugin for UserAssist {
+    type Record = UserAssistEntry;
+
+    fn name(&self) -> &'static str {
+        "userassist"
+    }
+
+    fn parse(&self, parser: &mut Parser) -> Result<Vec<Self::Record>, Error> {
+        Self::entries(parser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_user_assist() {
+        let mut parser = ParserBuilder::from_path("test_data/win7_ntuser.dat")
+            .build()
+            .unwrap();
+        let entries = UserAssist {}.parse(&mut parser).unwrap();
+        assert_eq!(338, entries.len());
+
+        let entry = entries
+            .iter()
+            .find(|e| e.name == "Microsoft.Windows.GettingStarted")
+            .unwrap();
+        assert_eq!("Zvpebfbsg.Jvaqbjf.TrggvatFgnegrq", entry.value_name);
+        assert_eq!("{CEBFF5CD-ACE2-4F4F-9178-9926F41749EA}", entry.guid);
+        assert_eq!(Some(0), entry.run_count);
+        assert_eq!(Some(0), entry.focus_count);
+        assert_eq!(
+            "2013-09-17T21:26:44.7880000Z",
+            util::format_date_time(entry.last_executed.unwrap())
+        );
+    }
+
+    #[test]
+    fn test_read_entry_v3() {
+        // session id, run count (offset by 5), last executed
+        let mut data = vec![1, 0, 0, 0, 8, 0, 0, 0];
+        data.extend_from_slice(&0x01CEB3EC9BCCFF40u64.to_le_bytes());
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("Count").set_value(WriterValue::new(
+            "HRZR_EHACNGU:P:\\Jvaqbjf\\abgrcnq.rkr",
+            CellKeyValueDataTypes::REG_BIN,
+            data,
+        ));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        let mut parser = ParserBuilder::from_file(Cursor::new(hive)).build().unwrap();
+        let count_key = parser.get_key("Count", false).unwrap().unwrap();
+        let value = count_key.value_iter().next().unwrap();
+
+        let entry = UserAssist::read_entry(&count_key.path, "{GUID}", &value);
+        assert_eq!("UEME_RUNPATH:C:\\Windows\\notepad.exe", entry.name);
+        assert_eq!(Some(3), entry.run_count);
+        assert_eq!(None, entry.focus_count);
+        assert_eq!(None, entry.focus_time_ms);
+        assert_eq!(
+            "2013-09-17T21:26:44.7880000Z",
+            util::format_date_time(entry.last_executed.unwrap())
+        );
+    }
+}
@@ -13,10 +13,15 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use crate::cell_key_node::CellKeyNode;
 use crate::err::Error;
+#[cfg(all(feature = "console", not(target_arch = "wasm32")))]
 use crossterm::{cursor, QueueableCommand};
+use std::fmt;
 use std::io;
 use std::io::{Stdout, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 pub fn new(update_console: bool) -> Box<dyn UpdateProgressTrait> {
     if update_console {
@@ -50,8 +55,10 @@ impl UpdateProgressTrait for UpdateConsole {
     }
 
     fn update(&mut self, msg: &str) -> Result<(), Error> {
+        #[cfg(all(feature = "console", not(target_arch = "wasm32")))]
         self.stdout.queue(cursor::SavePosition)?;
         self.stdout.write_all(msg.as_bytes())?;
+        #[cfg(all(feature = "console", not(target_arch = "wasm32")))]
         self.stdout.queue(cursor::RestorePosition)?;
         self.stdout.flush()?;
         self.need_final_newline = true;
@@ -89,3 +96,131 @@ impl UpdateProgressTrait for UpdateNull {
         Ok(())
     }
 }
+
+/// The stage of parsing a `Progress` update refers to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProgressPhase {
+    /// Scanning the hive bins for deleted keys and values (only when built with `recover_deleted`)
+    RecoveringDeleted,
+    /// Applying the dirty pages of the transaction logs
+    ApplyingTransactionLogs,
+    /// Iterating keys (ex: during an export)
+    Traversing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub phase: ProgressPhase,
+    /// Estimated completion of the current phase, from 0 to 100. During traversal this is based upon
+    /// how many of the root key's sub keys have been reached, so it advances unevenly for lopsided hives.
+    pub percentage: f64,
+    /// Number of keys returned so far (always 0 outside of traversal)
+    pub keys_processed: usize,
+}
+
+/// Receives progress updates while a hive is parsed and iterated. Register one with `ParserBuilder::progress_handler`.
+///
+/// Updates are sent from whichever thread is doing the work, so implementations should return quickly
+/// (ex: by forwarding to a channel). Any `Fn(&Progress)` closure is a `ProgressHandler`.
+pub trait ProgressHandler: Send + Sync {
+    fn on_progress(&self, progress: &Progress);
+}
+
+impl<F: Fn(&Progress) + Send + Sync> ProgressHandler for F {
+    fn on_progress(&self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// Receives each key the parser's iterators return, including those of the export writers, so a single traversal can
+/// feed other consumers (ex: collecting payloads while an export is written). Set one with `Parser::set_key_handler`.
+///
+/// Like `ProgressHandler`, it's called from whichever thread is iterating. Any `Fn(&CellKeyNode)` closure is a `KeyHandler`.
+pub trait KeyHandler: Send + Sync {
+    fn on_key(&self, key: &CellKeyNode);
+}
+
+impl<F: Fn(&CellKeyNode) + Send + Sync> KeyHandler for F {
+    fn on_key(&self, key: &CellKeyNode) {
+        self(key)
+    }
+}
+
+/// A cloneable flag used to abort parsing or iteration from another thread.
+/// Register one with `ParserBuilder::cancellation_token` and call `cancel` on any clone of it.
+///
+/// Once cancelled, building the parser fails with `Error::Cancelled`, iterators stop returning keys,
+/// and the export writers return `Error::Cancelled` rather than leaving a truncated file that looks complete.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// The progress handler and cancellation token a parser was built with, and its key handler
+#[derive(Clone, Default)]
+pub(crate) struct ProgressReporter {
+    pub(crate) handler: Option<Arc<dyn ProgressHandler>>,
+    pub(crate) key_handler: Option<Arc<dyn KeyHandler>>,
+    pub(crate) cancellation_token: Option<CancellationToken>,
+}
+
+impl ProgressReporter {
+    /// Number of keys between traversal updates
+    pub(crate) const TRAVERSAL_INTERVAL: usize = 1000;
+    /// Number of hive bins between updates while recovering deleted items (about 1 MB of the file at the usual bin size)
+    pub(crate) const RECOVERY_INTERVAL: usize = 256;
+
+    pub(crate) fn report(&self, phase: ProgressPhase, percentage: f64, keys_processed: usize) {
+        if let Some(handler) = &self.handler {
+            handler.on_progress(&Progress {
+                phase,
+                percentage: percentage.clamp(0.0, 100.0),
+                keys_processed,
+            });
+        }
+    }
+
+    pub(crate) fn report_key(&self, key: &CellKeyNode) {
+        if let Some(key_handler) = &self.key_handler {
+            key_handler.on_key(key);
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        matches!(&self.cancellation_token, Some(token) if token.is_cancelled())
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled {
+                detail: "the cancellation token was triggered".to_string(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("handler", &self.handler.is_some())
+            .field("key_handler", &self.key_handler.is_some())
+            .field("cancellation_token", &self.cancellation_token)
+            .finish()
+    }
+}
@@ -0,0 +1,198 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Keys and values flattened into records of a single shape (see `ParserIterator::as_records`), for tabular output and
+//! other consumers that shouldn't depend on the layout of `CellKeyNode` and `CellKeyValue`.
+
+use crate::cell::{Cell, CellState};
+use crate::cell_key_node::CellKeyNode;
+use crate::cell_key_value::CellKeyValue;
+use crate::log::Logs;
+use crate::timestamp_format::TimestampFormat;
+use bitflags::Flags;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Key,
+    Value,
+}
+
+/// A key or a value as one row. Fields that only apply to values are empty for keys, and the other way around.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Record {
+    pub kind: RecordKind,
+    /// The path of the key, or of the value's key (shared with the key and its other records)
+    pub key_path: Arc<str>,
+    /// The key's name, or the value's name (`(default)` for the default value)
+    pub name: String,
+    /// The value's data type (ex: `REG_SZ`)
+    pub data_type: String,
+    /// The value's data as text: binary data as hex bytes, and timestamps in the `TimestampFormat` passed to `from_key`
+    pub data: String,
+    /// The last written timestamp of the key, or of the value's key
+    pub last_written: DateTime<Utc>,
+    /// The number of sub keys read for the key
+    pub sub_key_count: Option<u32>,
+    /// The names of the key node flags (`KeyNodeFlags`) or value flags (`CellKeyValueFlags`) that are set, separated by
+    /// ` | `
+    pub flags: String,
+    /// The names of the key's access flags (`AccessFlags`) that are set, separated by ` | `
+    pub access_flags: String,
+    pub state: CellState,
+    /// True for anything deleted or modified by a transaction log, and for anything with earlier versions
+    pub recovered: bool,
+    pub sequence_num: Option<u32>,
+    pub updated_by_sequence_num: Option<u32>,
+    pub logs: String,
+    /// Why the key's name looks made to hide it (see `suspicious_name::detect`)
+    pub suspicious_name: String,
+}
+
+impl Record {
+    /// Returns the records for `key`: the key, its earlier versions, and its values, each followed by its earlier versions
+    pub fn from_key(key: &CellKeyNode, timestamp_format: TimestampFormat) -> Vec<Self> {
+        let mut records = vec![Self::key(key)];
+        records.extend(key.versions.iter().map(Self::key));
+        for value in key.value_iter() {
+            records.push(Self::value(key, &value, timestamp_format));
+            records.extend(
+                value
+                    .versions
+                    .iter()
+                    .map(|version| Self::value(key, version, timestamp_format)),
+            );
+        }
+        records
+    }
+
+    fn key(key: &CellKeyNode) -> Self {
+        Self {
+            kind: RecordKind::Key,
+            key_path: key.path.clone(),
+            name: key.key_name.clone(),
+            data_type: String::new(),
+            data: String::new(),
+            last_written: key.last_key_written_date_and_time(),
+            sub_key_count: Some(key.cell_sub_key_offsets_absolute.len() as u32),
+            flags: flag_names(&key.key_node_flags(&mut Logs::default())),
+            access_flags: flag_names(&key.access_flags(&mut Logs::default())),
+            state: key.cell_state,
+            recovered: key.has_or_is_recovered(),
+            sequence_num: key.sequence_num,
+            updated_by_sequence_num: key.updated_by_sequence_num,
+            logs: key.logs.to_string(),
+            suspicious_name: key
+                .suspicious_name()
+                .map(|suspicious_name| suspicious_name.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn value(key: &CellKeyNode, value: &CellKeyValue, timestamp_format: TimestampFormat) -> Self {
+        Self {
+            kind: RecordKind::Value,
+            key_path: key.path.clone(),
+            name: value.get_pretty_name(),
+            data_type: format!("{:?}", value.data_type),
+            data: timestamp_format.format_value(&value.get_content().0),
+            last_written: key.last_key_written_date_and_time(),
+            sub_key_count: None,
+            flags: flag_names(&value.flags),
+            access_flags: String::new(),
+            state: value.cell_state,
+            recovered: value.has_or_is_recovered(),
+            sequence_num: value.sequence_num,
+            updated_by_sequence_num: value.updated_by_sequence_num,
+            logs: value.logs.to_string(),
+            suspicious_name: String::new(),
+        }
+    }
+}
+
+fn flag_names(flags: &impl Flags) -> String {
+    flags
+        .iter_names()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterBuilder;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_as_records() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let filter = FilterBuilder::new()
+            .add_key_path("Control Panel\\Accessibility\\Keyboard Response")
+            .build()
+            .unwrap();
+        let key = ParserIterator::new(&parser)
+            .with_filter(filter.clone())
+            .iter()
+            .next()
+            .unwrap();
+        let records: Vec<_> = ParserIterator::new(&parser)
+            .with_filter(filter)
+            .as_records(TimestampFormat::default())
+            .collect();
+        assert_eq!(
+            1 + key.value_iter().count(),
+            records
+                .iter()
+                .filter(|record| record.key_path == key.path)
+                .count()
+        );
+
+        let key_record = &records[0];
+        assert_eq!(RecordKind::Key, key_record.kind);
+        assert_eq!("Keyboard Response", key_record.name);
+        assert_eq!(key.path, key_record.key_path);
+        assert!(key_record.flags.contains("KEY_COMP_NAME"));
+        assert_eq!(
+            Some(key.cell_sub_key_offsets_absolute.len() as u32),
+            key_record.sub_key_count
+        );
+        assert!(!key_record.recovered);
+        assert_eq!("", key_record.suspicious_name);
+
+        let value = key.get_value("DelayBeforeAcceptance").unwrap();
+        let value_record = records
+            .iter()
+            .find(|record| record.name == "DelayBeforeAcceptance")
+            .unwrap();
+        assert_eq!(RecordKind::Value, value_record.kind);
+        assert_eq!("REG_SZ", value_record.data_type);
+        assert_eq!(value.get_content().0.to_string(), value_record.data);
+        assert_eq!(key_record.last_written, value_record.last_written);
+        assert!(Arc::ptr_eq(&key_record.key_path, &value_record.key_path));
+        assert_eq!(None, value_record.sub_key_count);
+        assert_eq!("", value_record.access_flags);
+
+        let json = serde_json::to_value(value_record).unwrap();
+        assert_eq!("value", json["kind"]);
+    }
+}
@@ -15,17 +15,20 @@
  */
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 pub(crate) type RegItemMap = HashMap<RegItemMapKey, RegItemMapValue>;
 
 #[derive(Debug, Eq, Hash, PartialEq)]
 pub(crate) struct RegItemMapKey {
-    pub(crate) key_path: String,
+    /// Shared by the entry for a key and the entries for each of its values, and carried over from one
+    /// sequence number's map to the next, so each path is only allocated once no matter how many logs are applied
+    pub(crate) key_path: Arc<str>,
     pub(crate) value_name: Option<String>,
 }
 
 impl RegItemMapKey {
-    pub(crate) fn new(key_path: String, value_name: Option<String>) -> Self {
+    pub(crate) fn new(key_path: Arc<str>, value_name: Option<String>) -> Self {
         Self {
             key_path,
             value_name,
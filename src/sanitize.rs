@@ -0,0 +1,428 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Redacts personal information from a copy of a hive so it can be shared.
+//!
+//! `Sanitizer` copies a hive with `HiveWriter` and replaces the parts of value data matching its rules with
+//! placeholders. Key and value names, class names, security descriptors, and timestamps are copied unchanged.
+//!
+//! ```no_run
+//! use notatin::parser_builder::ParserBuilder;
+//! use notatin::sanitize::SanitizerBuilder;
+//!
+//! let mut parser = ParserBuilder::from_path("NTUSER.DAT").build().unwrap();
+//! let sanitizer = SanitizerBuilder::new()
+//!     .add_literal("jsmith", "USER")
+//!     .add_literal("WORKSTATION-7", "HOST")
+//!     .add_sids()
+//!     .build()
+//!     .unwrap();
+//! sanitizer.sanitize(&mut parser).unwrap().write("NTUSER.sanitized.DAT").unwrap();
+//! ```
+
+use crate::cell_key_value::CellKeyValueDataTypes;
+use crate::cell_value::CellValue;
+use crate::err::Error;
+use crate::log::Logs;
+use crate::parser::Parser;
+use crate::writer::{HiveWriter, WriterKey, WriterValue};
+use num_traits::FromPrimitive;
+use regex::{Captures, NoExpand, Regex};
+use std::borrow::Cow;
+
+#[derive(Clone, Debug)]
+struct SanitizeRule {
+    regex: Regex,
+    placeholder: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct SanitizerBuilder {
+    rules: Vec<SanitizeRule>,
+    redact_binary: bool,
+    regex_errors: Vec<String>,
+}
+
+impl Default for SanitizerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SanitizerBuilder {
+    pub fn new() -> Self {
+        SanitizerBuilder {
+            rules: vec![],
+            redact_binary: true,
+            regex_errors: vec![],
+        }
+    }
+
+    /// Replaces text matching `regex` with `placeholder`. `placeholder` is used as-is (`$` references aren't expanded).
+    pub fn add_pattern(mut self, regex: &str, placeholder: &str) -> Self {
+        match Regex::new(regex) {
+            Ok(regex) => self.rules.push(SanitizeRule {
+                regex,
+                placeholder: placeholder.to_string(),
+            }),
+            Err(e) => self.regex_errors.push(e.to_string()),
+        }
+        self
+    }
+
+    /// Replaces `text` (ex: a username or hostname), ignoring case, with `placeholder`
+    pub fn add_literal(self, text: &str, placeholder: &str) -> Self {
+        self.add_pattern(&format!("(?i){}", regex::escape(text)), placeholder)
+    }
+
+    /// Replaces the domain or machine identifier of account SIDs (`S-1-5-21-x-y-z`), keeping the relative ID
+    /// so that well-known accounts such as `-500` remain recognizable
+    pub fn add_sids(self) -> Self {
+        self.add_pattern(r"(?i)S-1-5-21-\d+-\d+-\d+", "S-1-5-21-0-0-0")
+    }
+
+    /// Determines if strings embedded in binary data (ex: MRU lists and shell items) are redacted.
+    /// Binary data keeps its length, so placeholders are truncated or padded with `*` to the length of each match.
+    /// Defaults to true.
+    pub fn redact_binary(mut self, redact_binary: bool) -> Self {
+        self.redact_binary = redact_binary;
+        self
+    }
+
+    pub fn build(self) -> Result<Sanitizer, Error> {
+        if self.regex_errors.is_empty() {
+            Ok(Sanitizer {
+                rules: self.rules,
+                redact_binary: self.redact_binary,
+            })
+        } else {
+            Err(Error::Any {
+                detail: format!("Regex errors encountered: {}", self.regex_errors.join(", ")),
+            })
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Sanitizer {
+    rules: Vec<SanitizeRule>,
+    redact_binary: bool,
+}
+
+impl Sanitizer {
+    /// Minimum number of consecutive printable characters treated as a string inside binary data
+    const MIN_EMBEDDED_STRING_LEN: usize = 4;
+    const PADDING: char = '*';
+
+    /// Returns a sanitized copy of the allocated keys and values of the hive opened by `parser`
+    pub fn sanitize(&self, parser: &mut Parser) -> Result<HiveWriter, Error> {
+        let mut writer = HiveWriter::from_parser(parser)?;
+        // The base block's file name is usually a path under the user's profile
+        writer.filename = self.replace(&writer.filename, false).into_owned();
+        self.sanitize_key(&mut writer.root);
+        Ok(writer)
+    }
+
+    /// Sanitizes the value data of `key` and its descendents. Returns the number of values changed.
+    pub fn sanitize_key(&self, key: &mut WriterKey) -> usize {
+        let mut changed = 0;
+        for value in &mut key.values {
+            if self.sanitize_value(value) {
+                changed += 1;
+            }
+        }
+        for sub_key in &mut key.sub_keys {
+            changed += self.sanitize_key(sub_key);
+        }
+        changed
+    }
+
+    /// Sanitizes the data of `value`. Returns true if it was changed.
+    pub fn sanitize_value(&self, value: &mut WriterValue) -> bool {
+        const DEVPROP_MASK_TYPE: u32 = 0x00000FFF;
+        let data_type = CellKeyValueDataTypes::from_u32(value.data_type_raw & DEVPROP_MASK_TYPE)
+            .unwrap_or(CellKeyValueDataTypes::REG_UNKNOWN);
+        let data = match data_type {
+            CellKeyValueDataTypes::REG_SZ
+            | CellKeyValueDataTypes::REG_EXPAND_SZ
+            | CellKeyValueDataTypes::REG_LINK
+            | CellKeyValueDataTypes::REG_MULTI_SZ => {
+                self.sanitize_string_data(data_type, &value.data)
+            }
+            _ if data_type.get_data_type_len().is_some() => None, // numbers and timestamps
+            _ if self.redact_binary => self.sanitize_binary_data(&value.data),
+            _ => None,
+        };
+        match data {
+            Some(data) => {
+                value.data = data;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn sanitize_string_data(
+        &self,
+        data_type: CellKeyValueDataTypes,
+        data: &[u8],
+    ) -> Option<Vec<u8>> {
+        let mut logs = Logs::default();
+        let content = data_type.get_value_content(Some(data), &mut logs).ok()?;
+        let sanitized = match content {
+            CellValue::String(s) => match self.replace(&s, false) {
+                Cow::Owned(replaced) => CellValue::String(replaced),
+                Cow::Borrowed(_) => return None,
+            },
+            CellValue::MultiString(m) => {
+                let replaced: Vec<String> = m
+                    .iter()
+                    .map(|s| self.replace(s, false).into_owned())
+                    .collect();
+                if replaced == m {
+                    return None;
+                }
+                CellValue::MultiString(replaced)
+            }
+            _ => return None,
+        };
+        data_type.get_value_bytes_from_content(&sanitized)
+    }
+
+    /// Redacts runs of printable UTF-16LE and ASCII characters in place
+    fn sanitize_binary_data(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let mut sanitized = data.to_vec();
+        self.sanitize_embedded_strings(&mut sanitized, 2);
+        self.sanitize_embedded_strings(&mut sanitized, 1);
+        if sanitized == data {
+            None
+        } else {
+            Some(sanitized)
+        }
+    }
+
+    fn sanitize_embedded_strings(&self, data: &mut [u8], char_len: usize) {
+        let read_char = |data: &[u8], offset: usize| -> Option<char> {
+            let c = match char_len {
+                1 => char::from(*data.get(offset)?),
+                _ => char::from_u32(
+                    u16::from_le_bytes([*data.get(offset)?, *data.get(offset + 1)?]).into(),
+                )?,
+            };
+            let printable = match char_len {
+                1 => c.is_ascii_graphic() || c == ' ',
+                _ => !c.is_control(),
+            };
+            printable.then_some(c)
+        };
+
+        // Strings may start at any offset, so each alignment is scanned separately
+        for alignment in 0..char_len {
+            let mut offset = alignment;
+            while offset < data.len() {
+                let mut run = String::new();
+                let mut end = offset;
+                while let Some(c) = read_char(data, end) {
+                    run.push(c);
+                    end += char_len;
+                }
+                if run.chars().count() < Self::MIN_EMBEDDED_STRING_LEN {
+                    offset += char_len;
+                    continue;
+                }
+                if let Cow::Owned(replaced) = self.replace(&run, true) {
+                    let bytes: Vec<u8> = match char_len {
+                        1 => replaced
+                            .chars()
+                            .map(|c| {
+                                if c.is_ascii() {
+                                    c as u8
+                                } else {
+                                    Self::PADDING as u8
+                                }
+                            })
+                            .collect(),
+                        _ => replaced.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+                    };
+                    data[offset..end].copy_from_slice(&bytes);
+                }
+                offset = end;
+            }
+        }
+    }
+
+    /// Applies each rule to `s`. If `keep_len` is true, each placeholder is truncated or padded to the length of its match.
+    fn replace<'a>(&self, s: &'a str, keep_len: bool) -> Cow<'a, str> {
+        let mut result = Cow::Borrowed(s);
+        for rule in &self.rules {
+            let replaced = if keep_len {
+                rule.regex.replace_all(&result, |caps: &Captures| {
+                    Self::fit(&rule.placeholder, caps[0].encode_utf16().count())
+                })
+            } else {
+                rule.regex.replace_all(&result, NoExpand(&rule.placeholder))
+            };
+            if let Cow::Owned(replaced) = replaced {
+                result = Cow::Owned(replaced);
+            }
+        }
+        result
+    }
+
+    /// Returns `placeholder` truncated or padded to `len` BMP characters
+    fn fit(placeholder: &str, len: usize) -> String {
+        placeholder
+            .chars()
+            .filter(|c| c.len_utf16() == 1)
+            .chain(std::iter::repeat(Self::PADDING))
+            .take(len)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+    use crate::util;
+    use std::io::Cursor;
+
+    fn sanitizer() -> Sanitizer {
+        SanitizerBuilder::new()
+            .add_literal("Patrick", "USER")
+            .add_sids()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sanitize_value() {
+        let sanitizer = sanitizer();
+
+        let mut value = WriterValue::from_content(
+            "ProfileImagePath",
+            CellKeyValueDataTypes::REG_EXPAND_SZ,
+            &CellValue::String("C:\\Users\\PATRICK\\AppData".to_string()),
+        );
+        assert!(sanitizer.sanitize_value(&mut value));
+        assert_eq!(
+            util::to_utf16_le_string("C:\\Users\\USER\\AppData"),
+            value.data
+        );
+
+        let mut value = WriterValue::from_content(
+            "Sids",
+            CellKeyValueDataTypes::REG_MULTI_SZ,
+            &CellValue::MultiString(vec![
+                "S-1-5-21-4282387053-314738432-104730921-1001".to_string(),
+                "S-1-5-18".to_string(),
+            ]),
+        );
+        assert!(sanitizer.sanitize_value(&mut value));
+        assert_eq!(
+            util::to_utf16_le_strings(&["S-1-5-21-0-0-0-1001".to_string(), "S-1-5-18".to_string()]),
+            value.data
+        );
+
+        let mut value = WriterValue::from_content(
+            "Unrelated",
+            CellKeyValueDataTypes::REG_SZ,
+            &CellValue::String("Nothing to see here".to_string()),
+        );
+        assert!(!sanitizer.sanitize_value(&mut value));
+
+        let mut value = WriterValue::new(
+            "Count",
+            CellKeyValueDataTypes::REG_DWORD,
+            0x1234u32.to_le_bytes().to_vec(),
+        );
+        assert!(!sanitizer.sanitize_value(&mut value));
+
+        // Embedded strings are redacted in place, at either alignment
+        let mut data = vec![0xff, 0x01];
+        data.extend(util::to_utf16_le_string("\\Users\\Patrick"));
+        data.push(0xfe);
+        data.extend(util::to_utf16_le_string("patrick.txt"));
+        data.extend(b"\x02ascii Patrick\x00");
+        let mut value = WriterValue::new("MRU", CellKeyValueDataTypes::REG_BIN, data.clone());
+        assert!(sanitizer.sanitize_value(&mut value));
+        let mut expected = vec![0xff, 0x01];
+        expected.extend(util::to_utf16_le_string("\\Users\\USER***"));
+        expected.push(0xfe);
+        expected.extend(util::to_utf16_le_string("USER***.txt"));
+        expected.extend(b"\x02ascii USER***\x00");
+        assert_eq!(expected, value.data);
+
+        let mut value = WriterValue::new("MRU", CellKeyValueDataTypes::REG_BIN, data);
+        let sanitizer = SanitizerBuilder::new()
+            .add_literal("Patrick", "USER")
+            .redact_binary(false)
+            .build()
+            .unwrap();
+        assert!(!sanitizer.sanitize_value(&mut value));
+    }
+
+    #[test]
+    fn test_sanitizer_builder_errors() {
+        assert!(SanitizerBuilder::new()
+            .add_pattern("(unclosed", "x")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_sanitize_hive() {
+        let mut original = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let writer = sanitizer().sanitize(&mut original).unwrap();
+
+        let mut sanitized = ParserBuilder::from_file(Cursor::new(writer.to_bytes().unwrap()))
+            .build()
+            .unwrap();
+        let mut keys = 0;
+        for key in ParserIterator::new(&sanitized).iter() {
+            keys += 1;
+            for value in key.value_iter() {
+                let content = value.get_content().0.to_string().to_lowercase();
+                assert!(!content.contains("patrick"), "{}", content);
+                assert!(!content.contains("4282387053"), "{}", content);
+            }
+        }
+        assert_eq!(2853, keys);
+
+        let path = "Control Panel\\Accessibility\\MouseKeys";
+        let original_key = original.get_key(path, false).unwrap().unwrap();
+        let sanitized_key = sanitized.get_key(path, false).unwrap().unwrap();
+        assert_eq!(
+            original_key.last_key_written_date_and_time(),
+            sanitized_key.last_key_written_date_and_time()
+        );
+        assert_eq!(
+            original_key
+                .get_value("MaximumSpeed")
+                .unwrap()
+                .get_content()
+                .0,
+            sanitized_key
+                .get_value("MaximumSpeed")
+                .unwrap()
+                .get_content()
+                .0
+        );
+    }
+}
@@ -0,0 +1,388 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::guid_names;
+use crate::util;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/* Shell item structures based upon:
+    https://github.com/libyal/libfwsi/blob/main/documentation/Windows%20Shell%20Item%20format.asciidoc
+*/
+
+/// A single item from a shell item ID list (as stored in ShellBags, MRU lists, LNK files, etc.)
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "item_type")]
+pub enum ShellItem {
+    RootFolder {
+        guid: String,
+        name: Option<String>,
+    },
+    Volume {
+        name: String,
+    },
+    FileEntry {
+        is_directory: bool,
+        short_name: String,
+        long_name: Option<String>,
+        file_size: u32,
+        modified: Option<DateTime<Utc>>,
+        created: Option<DateTime<Utc>>,
+        accessed: Option<DateTime<Utc>>,
+        mft_entry: Option<u64>,
+        mft_sequence: Option<u16>,
+    },
+    NetworkLocation {
+        location: String,
+        description: Option<String>,
+    },
+    ControlPanel {
+        guid: String,
+    },
+    /// Delegate items (search results, network servers, etc.) which store their data in a property store
+    PropertyView {
+        name: Option<String>,
+    },
+    Unknown {
+        class_type: u8,
+    },
+}
+
+impl ShellItem {
+    const CLASS_TYPE_ROOT_FOLDER: u8 = 0x1f;
+    const CLASS_TYPE_CONTROL_PANEL: u8 = 0x71;
+    const CLASS_TYPE_MASK: u8 = 0x70;
+    const CLASS_TYPE_VOLUME: u8 = 0x20;
+    const CLASS_TYPE_FILE_ENTRY: u8 = 0x30;
+    const CLASS_TYPE_NETWORK_LOCATION: u8 = 0x40;
+
+    // Known signatures found at offset 6 of property view items
+    const PROPERTY_VIEW_SIGNATURES: [u32; 6] = [
+        0x07192006, 0x10141981, 0x10312005, 0x23a3dfd5, 0x23febbee, 0x3b93afbb,
+    ];
+    const PROPERTY_STORAGE_SIGNATURE: &'static [u8] = b"1SPS";
+    // PKEY_ItemNameDisplay
+    const ITEM_NAME_DISPLAY_FORMAT_ID: &'static str = "B725F130-47EF-101A-A5F1-02608C9EEBAC";
+    const ITEM_NAME_DISPLAY_PROPERTY_ID: u32 = 10;
+    const VT_LPWSTR: u16 = 0x1f;
+
+    const FILE_ENTRY_FLAG_DIRECTORY: u8 = 0x01;
+    const FILE_ENTRY_FLAG_UNICODE: u8 = 0x04;
+    const FILE_ENTRY_EXTENSION_SIGNATURE: u32 = 0xbeef0004;
+
+    /// Parses a single shell item. `item` includes the leading u16 size field.
+    pub fn from_bytes(item: &[u8]) -> Self {
+        let class_type = item.get(2).copied().unwrap_or_default();
+        if let Some(signature) = util::read_u32_le(item, 6) {
+            if Self::PROPERTY_VIEW_SIGNATURES.contains(&signature) {
+                return ShellItem::PropertyView {
+                    name: Self::read_property_view_name(item),
+                };
+            }
+        }
+        let parsed = match class_type {
+            Self::CLASS_TYPE_ROOT_FOLDER => Self::read_root_folder(item),
+            Self::CLASS_TYPE_CONTROL_PANEL => {
+                util::read_guid_string(item, 14).map(|guid| ShellItem::ControlPanel { guid })
+            }
+            _ => match class_type & Self::CLASS_TYPE_MASK {
+                Self::CLASS_TYPE_VOLUME => Self::read_volume(item),
+                Self::CLASS_TYPE_FILE_ENTRY => Self::read_file_entry(item, class_type),
+                Self::CLASS_TYPE_NETWORK_LOCATION => Self::read_network_location(item),
+                _ => None,
+            },
+        };
+        parsed.unwrap_or(ShellItem::Unknown { class_type })
+    }
+
+    /// Returns the display name of the item, used when building a path from an ID list
+    pub fn name(&self) -> String {
+        match self {
+            ShellItem::RootFolder { guid, name } => match name {
+                Some(name) => name.clone(),
+                None => format!("{{{}}}", guid),
+            },
+            ShellItem::Volume { name } => name.trim_end_matches('\\').to_string(),
+            ShellItem::FileEntry {
+                short_name,
+                long_name,
+                ..
+            } => long_name.clone().unwrap_or_else(|| short_name.clone()),
+            ShellItem::NetworkLocation { location, .. } => location.clone(),
+            ShellItem::ControlPanel { guid } => format!("{{{}}}", guid),
+            ShellItem::PropertyView { name } => name
+                .clone()
+                .unwrap_or_else(|| "[Property view]".to_string()),
+            ShellItem::Unknown { class_type } => format!("[Unknown type 0x{:02X}]", class_type),
+        }
+    }
+
+    fn read_root_folder(item: &[u8]) -> Option<Self> {
+        let guid = util::read_guid_string(item, 4)?;
+        let name = guid_names::known_folder_name(&guid).map(str::to_string);
+        Some(ShellItem::RootFolder { guid, name })
+    }
+
+    /// Looks for the display name in the item's serialized property storages
+    fn read_property_view_name(item: &[u8]) -> Option<String> {
+        let mut offset = item
+            .windows(Self::PROPERTY_STORAGE_SIGNATURE.len())
+            .position(|w| w == Self::PROPERTY_STORAGE_SIGNATURE)?
+            .checked_sub(4)?;
+        // each storage is: size, signature, format id, then a list of values terminated by a zero size
+        while let Some(storage_size) = util::read_u32_le(item, offset).filter(|size| *size != 0) {
+            if util::read_guid_string(item, offset + 8).as_deref()
+                == Some(Self::ITEM_NAME_DISPLAY_FORMAT_ID)
+            {
+                let mut value_offset = offset + 24;
+                while let Some(value_size) =
+                    util::read_u32_le(item, value_offset).filter(|size| *size != 0)
+                {
+                    // value layout: size, id, reserved byte, type, padding, then the typed data
+                    if util::read_u32_le(item, value_offset + 4)
+                        == Some(Self::ITEM_NAME_DISPLAY_PROPERTY_ID)
+                        && util::read_u16_le(item, value_offset + 9) == Some(Self::VT_LPWSTR)
+                    {
+                        return util::read_utf16_le_string(item, value_offset + 17);
+                    }
+                    value_offset += value_size as usize;
+                }
+            }
+            offset += storage_size as usize;
+        }
+        None
+    }
+
+    fn read_volume(item: &[u8]) -> Option<Self> {
+        let name = read_ascii_string(item, 3)?;
+        match name.as_bytes() {
+            [letter, b':', ..] if letter.is_ascii_alphabetic() => Some(ShellItem::Volume { name }),
+            _ => {
+                // some volume class types hold a guid rather than a drive letter
+                let guid = util::read_guid_string(item, 4)?;
+                let name = guid_names::known_folder_name(&guid).map(str::to_string);
+                Some(ShellItem::RootFolder { guid, name })
+            }
+        }
+    }
+
+    fn read_network_location(item: &[u8]) -> Option<Self> {
+        let location = read_ascii_string(item, 5)?;
+        let description = read_ascii_string(item, 5 + location.len() + 1).filter(|d| !d.is_empty());
+        Some(ShellItem::NetworkLocation {
+            location,
+            description,
+        })
+    }
+
+    fn read_file_entry(item: &[u8], class_type: u8) -> Option<Self> {
+        const SHORT_NAME_OFFSET: usize = 14;
+        let file_size = util::read_u32_le(item, 4)?;
+        let modified = util::read_dos_date_time(item, 8);
+        let (short_name, short_name_len) = if class_type & Self::FILE_ENTRY_FLAG_UNICODE != 0 {
+            let name = util::read_utf16_le_string(item, SHORT_NAME_OFFSET)?;
+            let len = (name.encode_utf16().count() + 1) * 2;
+            (name, len)
+        } else {
+            let name = read_ascii_string(item, SHORT_NAME_OFFSET)?;
+            let len = name.len() + 1;
+            (name, len)
+        };
+        let mut file_entry = ShellItem::FileEntry {
+            is_directory: class_type & Self::FILE_ENTRY_FLAG_DIRECTORY != 0,
+            short_name,
+            long_name: None,
+            file_size,
+            modified,
+            created: None,
+            accessed: None,
+            mft_entry: None,
+            mft_sequence: None,
+        };
+
+        // the extension block starts at the next 2-byte boundary after the short name
+        let extension_offset = SHORT_NAME_OFFSET + short_name_len + short_name_len % 2;
+        if let Some(extension) = item.get(extension_offset..) {
+            Self::read_file_entry_extension(extension, &mut file_entry);
+        }
+        Some(file_entry)
+    }
+
+    /// Reads the 0xbeef0004 extension block, which holds the long name and additional timestamps
+    fn read_file_entry_extension(extension: &[u8], file_entry: &mut Self) {
+        if util::read_u32_le(extension, 4) != Some(Self::FILE_ENTRY_EXTENSION_SIGNATURE) {
+            return;
+        }
+        let version = util::read_u16_le(extension, 2).unwrap_or_default();
+        if let ShellItem::FileEntry {
+            long_name,
+            created,
+            accessed,
+            mft_entry,
+            mft_sequence,
+            ..
+        } = file_entry
+        {
+            *created = util::read_dos_date_time(extension, 8);
+            *accessed = util::read_dos_date_time(extension, 12);
+
+            let mut long_name_offset = 18;
+            if version >= 7 {
+                if let Some(file_reference) = util::read_u64_le(extension, 20) {
+                    *mft_entry = Some(file_reference & 0xffff_ffff_ffff);
+                    *mft_sequence = Some((file_reference >> 48) as u16);
+                }
+                long_name_offset = 36;
+            }
+            if version >= 3 {
+                long_name_offset += 2; // long string size
+            }
+            if version >= 9 {
+                long_name_offset += 4;
+            }
+            if version >= 8 {
+                long_name_offset += 4;
+            }
+            *long_name =
+                util::read_utf16_le_string(extension, long_name_offset).filter(|n| !n.is_empty());
+        }
+    }
+}
+
+/// Parses a shell item ID list (a sequence of size-prefixed items terminated by a zero size)
+pub fn parse_id_list(id_list: &[u8]) -> Vec<ShellItem> {
+    let mut items = Vec::new();
+    let mut offset = 0;
+    while let Some(size) = util::read_u16_le(id_list, offset) {
+        let size = size as usize;
+        if size == 0 {
+            break;
+        }
+        match id_list.get(offset..offset + size) {
+            Some(item) => items.push(ShellItem::from_bytes(item)),
+            None => break,
+        }
+        offset += size;
+    }
+    items
+}
+
+/// Reads a null-terminated ASCII string
+fn read_ascii_string(data: &[u8], offset: usize) -> Option<String> {
+    let data = data.get(offset..)?;
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    Some(String::from_utf8_lossy(&data[..end]).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex_to_bytes(hex: &str) -> Vec<u8> {
+        hex.split_whitespace()
+            .map(|b| u8::from_str_radix(b, 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_root_folder() {
+        let items = parse_id_list(&hex_to_bytes(
+            "14 00 1F 58 0D 1A 2C F0 21 BE 50 43 88 B0 73 67 FC 96 EF 3C 00 00",
+        ));
+        assert_eq!(
+            vec![ShellItem::RootFolder {
+                guid: "F02C1A0D-BE21-4350-88B0-7367FC96EF3C".to_string(),
+                name: Some("Network".to_string())
+            }],
+            items
+        );
+    }
+
+    #[test]
+    fn test_network_location() {
+        let item = ShellItem::from_bytes(&hex_to_bytes(
+            "2B 00 C3 01 C5 5C 5C 6E 79 63 65 6C 6F 78 30 32 5C 64 61 74 61 00 4D 69 63 72 6F 73 6F 66 74 20 4E 65 74 77 6F 72 6B 00 00",
+        ));
+        assert_eq!(
+            ShellItem::NetworkLocation {
+                location: "\\\\nycelox02\\data".to_string(),
+                description: Some("Microsoft Network".to_string())
+            },
+            item
+        );
+    }
+
+    #[test]
+    fn test_file_entry() {
+        let item = ShellItem::from_bytes(&hex_to_bytes(
+            "58 00 31 00 00 00 00 00 23 43 D7 9C 10 00 5F 53 4F 46 54 57 7E 31 00 00 40 00 08 00 04 00 EF BE 5B 3F B0 94 23 43 D7 9C 2A 00 00 00 DE 00 00 00 00 00 01 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 5F 00 53 00 6F 00 66 00 74 00 77 00 61 00 72 00 65 00 00 00 18 00",
+        ));
+        match &item {
+            ShellItem::FileEntry {
+                is_directory,
+                short_name,
+                long_name,
+                modified,
+                created,
+                mft_entry,
+                mft_sequence,
+                ..
+            } => {
+                assert!(is_directory);
+                assert_eq!("_SOFTW~1", short_name);
+                assert_eq!(&Some("_Software".to_string()), long_name);
+                assert_eq!(
+                    "2013-09-03T19:38:46.0000000Z",
+                    util::format_date_time(modified.unwrap())
+                );
+                assert_eq!(
+                    "2011-10-27T18:37:32.0000000Z",
+                    util::format_date_time(created.unwrap())
+                );
+                assert_eq!(&Some(0xde), mft_entry);
+                assert_eq!(&Some(1), mft_sequence);
+            }
+            _ => panic!("Expected a file entry: {:?}", item),
+        }
+        assert_eq!("_Software", item.name());
+    }
+
+    #[test]
+    fn test_property_view() {
+        let item = ShellItem::from_bytes(&hex_to_bytes(
+            "B3 00 00 00 AD 00 BB AF 93 3B 9F 00 04 00 00 00 00 00 41 00 00 00 31 53 50 53 30 F1 25 B7 EF 47 1A 10 A5 F1 02 60 8C 9E EB AC 25 00 00 00 0A 00 00 00 00 1F 00 00 00 0A 00 00 00 6E 00 79 00 63 00 65 00 6C 00 6F 00 78 00 30 00 32 00 00 00 00 00 00 00 2D 00 00 00 31 53 50 53 3A A4 BD DE B3 37 83 43 91 E7 44 98 DA 29 95 AB 11 00 00 00 03 00 00 00 00 13 00 00 00 00 00 00 00 00 00 00 00 2D 00 00 00 31 53 50 53 73 43 E5 0A BE 43 AD 4F 85 E4 69 DC 86 33 98 6E 11 00 00 00 0B 00 00 00 00 0B 00 00 00 FF FF 00 00 00 00 00 00 00 00 00 00 00 00 00 00",
+        ));
+        assert_eq!(
+            ShellItem::PropertyView {
+                name: Some("nycelox02".to_string())
+            },
+            item
+        );
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert_eq!(
+            ShellItem::Unknown { class_type: 0x31 },
+            ShellItem::from_bytes(&[0x04, 0x00, 0x31])
+        );
+        assert_eq!(
+            ShellItem::Unknown { class_type: 0 },
+            ShellItem::from_bytes(&[])
+        );
+        assert!(parse_id_list(&[0xff, 0x00, 0x1f]).is_empty());
+    }
+}
@@ -14,13 +14,29 @@
  * limitations under the License.
  */
 
-use crate::cell_key_node::CellKeyNode;
+use crate::base_block::HiveCapabilities;
+use crate::cell_cache::CellCache;
+use crate::cell_extents::{CellExtents, OverlapCheck};
+use crate::cell_key_node::{CellKeyNode, ParentOffsetCheck};
 use crate::cell_key_value::CellKeyValue;
+use crate::control_set::ControlSets;
 use crate::log::Logs;
 use crate::util;
 use blake3::{Hash, Hasher};
 use std::collections::HashMap;
 
+/// Guards against hives crafted to exhaust the stack or memory (see `ParserBuilderFromPath::max_depth`,
+/// `max_keys`, and `max_value_size`). `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Limits {
+    /// Sub keys deeper than this many levels below the root key aren't read
+    pub max_depth: Option<usize>,
+    /// A traversal stops reading sub keys once it has found this many keys
+    pub max_keys: Option<usize>,
+    /// Value data larger than this many bytes isn't read
+    pub max_value_size: Option<usize>,
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct ModifiedValueMap {
     pub map: HashMap<(String, String), Vec<CellKeyValue>>,
@@ -132,7 +148,7 @@ impl ModifiedDeletedKeyMap {
         if let Some(keys) = self.map.get_mut(parent_path) {
             for (index, key) in keys.iter().enumerate() {
                 if let Some(key_hash) = key.hash {
-                    if hash == &key_hash && path == key.path {
+                    if hash == &key_hash && path == &*key.path {
                         keys.remove(index);
                         if keys.is_empty() {
                             self.map.remove(path);
@@ -163,6 +179,44 @@ pub(crate) struct State {
     pub modified_keys: ModifiedDeletedKeyMap,
     pub deleted_values: DeletedValueMap,
     pub modified_values: ModifiedValueMap,
+
+    pub cell_cache: CellCache,
+
+    /// Set by `Parser::resolve_current_control_set`; a `CurrentControlSet` segment in a path refers to `current`
+    pub control_sets: Option<ControlSets>,
+
+    /// Whether sub keys are checked to point back to the key that lists them while reading sub keys
+    pub parent_offset_check: ParentOffsetCheck,
+
+    /// Whether key and value cells are checked to not overlap the cells read before them
+    pub overlap_check: OverlapCheck,
+
+    /// The cells read so far, when `overlap_check` isn't `Off`
+    pub cell_extents: CellExtents,
+
+    pub limits: Limits,
+
+    /// Set from the base block; decides which version-specific structures are looked for
+    pub capabilities: HiveCapabilities,
+
+    /// Set by `ParserBuilder::app_hive`
+    pub app_hive: bool,
+
+    /// Set by `ParserBuilder::check_data_types`
+    pub check_data_types: bool,
+
+    /// Set by `ParserBuilder::detect_payloads`
+    pub detect_payloads: bool,
+
+    /// Set by `ParserBuilder::detect_suspicious_names`
+    pub detect_suspicious_names: bool,
+
+    /// Set by `ParserBuilder::value_stats`
+    pub value_stats: bool,
+
+    /// Set by `ParserIterator::values`: keys are only read to get to their values, so their full field info, slack, and
+    /// hash aren't kept, and their names aren't checked
+    pub values_only: bool,
 }
 
 impl State {
@@ -172,6 +226,41 @@ impl State {
         }
         self.root_key_path_offset
     }
+
+    /// Forgets the cells read before, so each top-level lookup (ex: `Parser::get_key`) resolves overlapping cells the same
+    /// way regardless of what was looked up before it
+    pub(crate) fn for_lookup(&mut self) -> &mut Self {
+        self.cell_extents = CellExtents::default();
+        self
+    }
+
+    /// Returns a copy of the state for a traversal. A traversal visits each key once, so the copy doesn't use the cell cache.
+    /// The copy starts with no cells read, so each traversal resolves overlapping cells the same way.
+    pub(crate) fn for_traversal(&self) -> Self {
+        Self {
+            root_key_path_offset: self.root_key_path_offset,
+            get_full_field_info: self.get_full_field_info,
+            info: self.info.clone(),
+            hasher: self.hasher.clone(),
+            deleted_keys: self.deleted_keys.clone(),
+            modified_keys: self.modified_keys.clone(),
+            deleted_values: self.deleted_values.clone(),
+            modified_values: self.modified_values.clone(),
+            cell_cache: CellCache::default(),
+            control_sets: self.control_sets,
+            parent_offset_check: self.parent_offset_check,
+            overlap_check: self.overlap_check,
+            cell_extents: CellExtents::default(),
+            limits: self.limits,
+            capabilities: self.capabilities,
+            app_hive: self.app_hive,
+            check_data_types: self.check_data_types,
+            detect_payloads: self.detect_payloads,
+            detect_suspicious_names: self.detect_suspicious_names,
+            value_stats: self.value_stats,
+            values_only: false,
+        }
+    }
 }
 
 impl Default for State {
@@ -185,6 +274,70 @@ impl Default for State {
             modified_keys: ModifiedDeletedKeyMap::new(),
             deleted_values: DeletedValueMap::new(),
             modified_values: ModifiedValueMap::new(),
+            cell_cache: CellCache::default(),
+            control_sets: None,
+            parent_offset_check: ParentOffsetCheck::Off,
+            overlap_check: OverlapCheck::Off,
+            cell_extents: CellExtents::default(),
+            limits: Limits::default(),
+            capabilities: HiveCapabilities::default(),
+            app_hive: false,
+            check_data_types: false,
+            detect_payloads: false,
+            detect_suspicious_names: false,
+            value_stats: false,
+            values_only: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::log::LogCode;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_limits() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .max_depth(1)
+            .build()
+            .unwrap();
+        let keys: Vec<_> = ParserIterator::new(&parser).iter().collect();
+        assert_eq!(11, keys.len());
+        assert!(keys.iter().all(|key| key.iteration_state.depth <= 1));
+        assert!(keys
+            .iter()
+            .filter(|key| key.iteration_state.depth == 1 && key.detail.number_of_sub_keys() > 0)
+            .all(|key| key.logs.has_code(LogCode::WarningLimit)));
+
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .max_keys(100)
+            .build()
+            .unwrap();
+        assert_eq!((100, 223), parser.count_all_keys_and_values(None));
+        assert!(ParserIterator::new(&parser)
+            .iter()
+            .any(|key| key.logs.has_code(LogCode::WarningLimit)));
+
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .max_value_size(4)
+            .build()
+            .unwrap();
+        let values: Vec<_> = ParserIterator::new(&parser)
+            .iter()
+            .flat_map(|mut key| key.take_values())
+            .collect();
+        assert_eq!(5523, values.len());
+        let skipped: Vec<_> = values
+            .iter()
+            .filter(|value| value.logs.has_code(LogCode::WarningLimit))
+            .collect();
+        assert_eq!(3082, skipped.len());
+        assert!(skipped.iter().all(|value| value
+            .detail
+            .value_bytes()
+            .unwrap_or_default()
+            .is_empty()));
+    }
+}
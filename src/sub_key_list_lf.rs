@@ -40,7 +40,10 @@ impl hive_bin_cell::CellSubKeyList for SubKeyListLf {
     fn get_offset_list(&self, hbin_offset_absolute: u32) -> Vec<u32> {
         self.items
             .iter()
-            .map(|x| x.named_key_offset_relative + hbin_offset_absolute)
+            .filter_map(|x| {
+                x.named_key_offset_relative
+                    .checked_add(hbin_offset_absolute)
+            })
             .collect()
     }
 }
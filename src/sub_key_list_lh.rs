@@ -21,6 +21,7 @@ use nom::{
     IResult,
 };
 use serde::Serialize;
+use std::convert::TryFrom;
 
 // Subkeys list with name hints
 #[derive(Debug, Eq, PartialEq, Serialize)]
@@ -71,6 +72,36 @@ impl hive_bin_cell::CellSubKeyList for SubKeyListLh {
             })
             .collect()
     }
+
+    fn get_offset_and_name_hash_list(&self, hbin_offset_absolute: u32) -> Vec<(u32, Option<u32>)> {
+        self.items
+            .iter()
+            .filter_map(|x| {
+                x.named_key_offset_relative
+                    .checked_add(hbin_offset_absolute)
+                    .map(|offset| (offset, Some(x.name_hash)))
+            })
+            .collect()
+    }
+}
+
+/// Returns the hash of a key name as stored in lh lists: each UTF-16 code unit is uppercased and added to the hash times 37
+pub(crate) fn name_hash(name: &str) -> u32 {
+    name.encode_utf16()
+        .map(upcase)
+        .fold(0u32, |hash, c| hash.wrapping_mul(37).wrapping_add(c as u32))
+}
+
+/// Uppercases a single UTF-16 code unit; characters whose uppercase form isn't a single code unit are left as they are
+fn upcase(c: u16) -> u16 {
+    let mut upper = match char::from_u32(c as u32) {
+        Some(ch) => ch.to_uppercase(),
+        None => return c,
+    };
+    match (upper.next(), upper.next()) {
+        (Some(u), None) => u16::try_from(u as u32).unwrap_or(c),
+        _ => c,
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Serialize)]
@@ -118,6 +149,24 @@ mod tests {
         };
         assert_eq!(lh.size, lh.size());
         assert_eq!(vec![16441, 58417], lh.get_offset_list(4096));
+        assert_eq!(
+            vec![(16441, Some(1111)), (58417, Some(2222))],
+            lh.get_offset_and_name_hash_list(4096)
+        );
+    }
+
+    #[test]
+    fn test_name_hash() {
+        assert_eq!(name_hash("SOFTWARE"), name_hash("Software"));
+        assert_eq!(
+            ('A' as u32).wrapping_mul(37).wrapping_add('B' as u32),
+            name_hash("ab")
+        );
+        // ß uppercases to "SS", so it's left as it is
+        assert_eq!(
+            ('S' as u32).wrapping_mul(37).wrapping_add('ß' as u32),
+            name_hash("sß")
+        );
     }
 
     #[test]
@@ -25,6 +25,7 @@ use nom::{
     IResult,
 };
 use serde::Serialize;
+use std::collections::HashSet;
 
 // List of subkeys lists (used to subdivide subkeys lists)
 #[derive(Debug, Eq, PartialEq, Serialize)]
@@ -42,7 +43,10 @@ impl hive_bin_cell::CellSubKeyList for SubKeyListRi {
     fn get_offset_list(&self, hbin_offset_absolute: u32) -> Vec<u32> {
         self.items
             .iter()
-            .map(|x| x.sub_key_list_offset_relative + hbin_offset_absolute)
+            .filter_map(|x| {
+                x.sub_key_list_offset_relative
+                    .checked_add(hbin_offset_absolute)
+            })
             .collect()
     }
 }
@@ -66,17 +70,22 @@ impl SubKeyListRi {
         ))
     }
 
-    pub(crate) fn parse_offsets(
+    /// Returns the sub key offsets and name hashes of each of the lists this list points to (see `CellKeyNode::parse_sub_key_list_items`)
+    pub(crate) fn parse_items(
         &self,
         file_info: &FileInfo,
         state: &mut State,
-    ) -> Result<Vec<u32>, Error> {
-        let mut list: Vec<u32> = Vec::new();
+        depth: usize,
+        visited: &mut HashSet<u32>,
+    ) -> Result<Vec<(u32, Option<u32>)>, Error> {
+        let mut list = Vec::new();
         for item in self.items.iter() {
-            let mut sub_list = CellKeyNode::parse_sub_key_list(
+            let mut sub_list = CellKeyNode::parse_sub_key_list_items(
                 file_info,
                 state,
                 item.sub_key_list_offset_relative,
+                depth,
+                visited,
             )?;
             list.append(&mut sub_list);
         }
@@ -0,0 +1,68 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::cell_key_node::CellKeyNode;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Summary of a key and its descendents, as returned by `Parser::subtree_stats`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct SubtreeStats {
+    /// Number of keys, including the key at the top of the subtree
+    pub key_count: usize,
+    pub value_count: usize,
+    /// Total size of the values' data, in bytes
+    pub data_size: u64,
+    /// Number of levels below the key at the top of the subtree (0 if it doesn't have any sub keys)
+    pub max_depth: usize,
+    /// Path of the first key found at `max_depth`
+    pub deepest_path: String,
+    pub last_key_written_date_and_time: Option<DateTime<Utc>>,
+    /// Path of the key that was most recently written
+    pub last_key_written_path: String,
+}
+
+impl SubtreeStats {
+    pub(crate) fn add_key(&mut self, key: &CellKeyNode, top_depth: usize) {
+        self.key_count += 1;
+        self.value_count += key.sub_values.len();
+        self.data_size += key
+            .sub_values
+            .iter()
+            .map(|value| value.get_data().len() as u64)
+            .sum::<u64>();
+
+        let depth = Self::depth(&key.path).saturating_sub(top_depth);
+        if depth > self.max_depth || self.deepest_path.is_empty() {
+            self.max_depth = depth;
+            self.deepest_path = key.path.to_string();
+        }
+
+        let last_written = key.last_key_written_date_and_time();
+        let is_latest = match self.last_key_written_date_and_time {
+            Some(latest) => last_written > latest,
+            None => true,
+        };
+        if is_latest {
+            self.last_key_written_date_and_time = Some(last_written);
+            self.last_key_written_path = key.path.to_string();
+        }
+    }
+
+    pub(crate) fn depth(path: &str) -> usize {
+        path.matches('\\').count()
+    }
+}
@@ -0,0 +1,250 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Detection of key names made to hide persistence from regedit and from analysts: empty names, names with leading or
+//! trailing whitespace or invisible characters, names that mix lookalike letters into ASCII text, and ROT13 encoded
+//! names (see `CellKeyNode::suspicious_name` and `ParserBuilder::detect_suspicious_names`).
+
+use crate::util;
+use serde::Serialize;
+use std::fmt;
+
+/// Words that show up in the ROT13 decoding of names that hide what they're about; a name that holds one of them
+/// once decoded (and not as is) is reported
+const ROT13_WORDS: &[&str] = &[
+    "microsoft",
+    "windows",
+    "software",
+    "currentversion",
+    "explorer",
+    "system32",
+    "update",
+    "services",
+    "policies",
+    "startup",
+    "winlogon",
+    "svchost",
+    "powershell",
+    "rundll32",
+    ".exe",
+    ".dll",
+];
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum SuspiciousName {
+    /// The name is empty
+    Empty,
+    /// The name starts or ends with whitespace, which is easy to miss when the name is displayed
+    Whitespace,
+    /// The name holds a control character (such as the null that hides keys created through the native API from
+    /// regedit), or a zero-width, soft hyphen, or text direction character. `offset` is in characters.
+    InvisibleCharacter { offset: usize, character: char },
+    /// The name mixes ASCII letters with lookalike letters from other scripts (ex: a Cyrillic 'о' in "Micrоsoft").
+    /// `offset` is in characters.
+    Homoglyph { offset: usize, character: char },
+    /// The name decodes to something meaningful with ROT13 (ex: "Zvpebfbsg" for "Microsoft")
+    Rot13 { decoded: String },
+}
+
+impl fmt::Display for SuspiciousName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SuspiciousName::Empty => write!(f, "Key name is empty"),
+            SuspiciousName::Whitespace => write!(f, "Key name has leading or trailing whitespace"),
+            SuspiciousName::InvisibleCharacter { offset, character } => write!(
+                f,
+                "Key name contains invisible character U+{:04X} at offset {}",
+                *character as u32, offset
+            ),
+            SuspiciousName::Homoglyph { offset, character } => write!(
+                f,
+                "Key name contains lookalike character '{}' (U+{:04X}) at offset {}",
+                character, *character as u32, offset
+            ),
+            SuspiciousName::Rot13 { decoded } => {
+                write!(
+                    f,
+                    "Key name looks ROT13 encoded: {}",
+                    decoded.escape_debug()
+                )
+            }
+        }
+    }
+}
+
+/// Returns the first reason `name` looks made to hide its key, checking for (in order) an empty name, leading or
+/// trailing whitespace, invisible characters, lookalike characters, and ROT13 encoding
+pub fn detect(name: &str) -> Option<SuspiciousName> {
+    if name.is_empty() {
+        return Some(SuspiciousName::Empty);
+    }
+    if name.starts_with(char::is_whitespace) || name.ends_with(char::is_whitespace) {
+        return Some(SuspiciousName::Whitespace);
+    }
+    if let Some((offset, character)) = name.chars().enumerate().find(|(_, c)| is_invisible(*c)) {
+        return Some(SuspiciousName::InvisibleCharacter { offset, character });
+    }
+    if name.chars().any(|c| c.is_ascii_alphabetic()) {
+        if let Some((offset, character)) = name.chars().enumerate().find(|(_, c)| is_homoglyph(*c))
+        {
+            return Some(SuspiciousName::Homoglyph { offset, character });
+        }
+    }
+    let decoded = util::decode_rot13(name);
+    let (name_lower, decoded_lower) = (name.to_lowercase(), decoded.to_lowercase());
+    if ROT13_WORDS
+        .iter()
+        .any(|word| decoded_lower.contains(word) && !name_lower.contains(word))
+    {
+        return Some(SuspiciousName::Rot13 { decoded });
+    }
+    None
+}
+
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{2064}'
+                | '\u{FEFF}'
+        )
+}
+
+/// Returns true for the Cyrillic, Greek, and fullwidth letters that look like ASCII letters
+fn is_homoglyph(c: char) -> bool {
+    matches!(
+        c,
+        // Cyrillic
+        'а' | 'е' | 'о' | 'р' | 'с' | 'у' | 'х' | 'ѕ' | 'і' | 'ј' | 'һ' | 'ԁ' | 'ԛ' | 'ԝ' | 'ӏ'
+            | 'А' | 'В' | 'Е' | 'К' | 'М' | 'Н' | 'О' | 'Р' | 'С' | 'Т' | 'Х' | 'Ѕ' | 'І' | 'Ј'
+            // Greek
+            | 'Α' | 'Β' | 'Ε' | 'Ζ' | 'Η' | 'Ι' | 'Κ' | 'Μ' | 'Ν' | 'Ο' | 'Ρ' | 'Τ' | 'Υ' | 'Χ'
+            | 'ο' | 'ν'
+            // fullwidth
+            | 'Ａ'..='Ｚ' | 'ａ'..='ｚ'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Some(SuspiciousName::Empty), detect(""));
+        assert_eq!(Some(SuspiciousName::Whitespace), detect("Run "));
+        assert_eq!(Some(SuspiciousName::Whitespace), detect("\u{00A0}Run"));
+        assert_eq!(
+            Some(SuspiciousName::InvisibleCharacter {
+                offset: 3,
+                character: '\0'
+            }),
+            detect("Run\0Hidden")
+        );
+        assert_eq!(
+            Some(SuspiciousName::InvisibleCharacter {
+                offset: 0,
+                character: '\u{202E}'
+            }),
+            detect("\u{202E}exe.txt")
+        );
+        assert_eq!(
+            Some(SuspiciousName::Homoglyph {
+                offset: 4,
+                character: 'о'
+            }),
+            detect("Micrоsoft")
+        );
+        // names written entirely in another script are fine
+        assert_eq!(None, detect("Настройки"));
+        assert_eq!(
+            Some(SuspiciousName::Rot13 {
+                decoded: "Microsoft Update".to_string()
+            }),
+            detect("Zvpebfbsg Hcqngr")
+        );
+        assert_eq!(
+            "Key name contains lookalike character 'о' (U+043E) at offset 4",
+            detect("Micrоsoft").unwrap().to_string()
+        );
+
+        for name in [
+            "Microsoft",
+            "CurrentVersion",
+            "Run",
+            "{8E5E1C0E-0F2F-4B1B-9F3A-6C4D3E2A1B0C}",
+            "Control Panel",
+            ".DEFAULT",
+            "S-1-5-21-1004",
+        ]
+        .iter()
+        {
+            assert_eq!(None, detect(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_detect_suspicious_names() {
+        use crate::log::LogCode;
+        use crate::writer::{HiveWriter, WriterKey};
+
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("Software\\Zvpebfbsg");
+        root.get_or_add_sub_key("Software\\Run ");
+        root.get_or_add_sub_key("Software\\Microsoft");
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+
+        let mut builder = ParserBuilder::from_file(std::io::Cursor::new(hive));
+        builder.detect_suspicious_names(true);
+        let parser = builder.build().unwrap();
+        let mut suspicious: Vec<_> = ParserIterator::new(&parser)
+            .suspicious_names()
+            .map(|(key, suspicious_name)| {
+                let logged = key.logs.has_code(LogCode::WarningSuspiciousName);
+                (key.path.to_string(), suspicious_name, logged)
+            })
+            .collect();
+        suspicious.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            vec![
+                (
+                    "\\ROOT\\Software\\Run ".to_string(),
+                    SuspiciousName::Whitespace,
+                    true
+                ),
+                (
+                    "\\ROOT\\Software\\Zvpebfbsg".to_string(),
+                    SuspiciousName::Rot13 {
+                        decoded: "Microsoft".to_string()
+                    },
+                    true
+                )
+            ],
+            suspicious
+        );
+
+        // the test hives don't hide anything
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        assert_eq!(0, ParserIterator::new(&parser).suspicious_names().count());
+    }
+}
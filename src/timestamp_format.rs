@@ -0,0 +1,166 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! How timestamps are written by the tsv, xlsx, and graph export writers (see `WriteTsv::timestamp_format`). Timezones are
+//! fixed UTC offsets: there are no daylight saving rules. The json, jsonl, and common export formats always use UTC
+//! ISO-8601 so their output can be compared with other tools.
+
+use crate::cell_value::CellValue;
+use crate::util;
+use chrono::{DateTime, FixedOffset, Utc};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TimestampStyle {
+    /// ISO-8601 with 100ns precision, ending in `Z` for UTC or the timezone's offset (ex: `+02:00`)
+    #[default]
+    Iso8601,
+    /// Whole seconds since 1970-01-01 UTC
+    EpochSeconds,
+    /// The Windows FILETIME integer (100ns intervals since 1601-01-01 UTC)
+    FileTime,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TimestampFormat {
+    pub style: TimestampStyle,
+    /// Only applies to `TimestampStyle::Iso8601`; `None` is UTC
+    pub timezone: Option<FixedOffset>,
+}
+
+impl TimestampFormat {
+    pub fn new(style: TimestampStyle, timezone: Option<FixedOffset>) -> Self {
+        Self { style, timezone }
+    }
+
+    pub fn format(&self, date_time: DateTime<Utc>) -> String {
+        match self.style {
+            TimestampStyle::Iso8601 => match self.timezone {
+                Some(timezone) => {
+                    let local = date_time.with_timezone(&timezone);
+                    format!(
+                        "{}.{:07}{}",
+                        local.format("%Y-%m-%dT%H:%M:%S"),
+                        local.timestamp_subsec_nanos() / 100,
+                        local.format("%:z")
+                    )
+                }
+                None => util::format_date_time(date_time),
+            },
+            TimestampStyle::EpochSeconds => date_time.timestamp().to_string(),
+            TimestampStyle::FileTime => util::get_filetime_from_date_time(date_time).to_string(),
+        }
+    }
+
+    /// Returns the value's content as text, formatting `CellValue::DateTime` content with this format
+    pub fn format_value(&self, value: &CellValue) -> String {
+        match value {
+            CellValue::DateTime(date_time) => self.format(*date_time),
+            _ => value.to_string(),
+        }
+    }
+
+    /// Parses a UTC offset such as `+02:00`, `-0530`, or `+9`; `Z` and `UTC` are accepted for UTC
+    pub fn parse_timezone(timezone: &str) -> Option<FixedOffset> {
+        let timezone = timezone.trim();
+        if timezone.eq_ignore_ascii_case("z") || timezone.eq_ignore_ascii_case("utc") {
+            return FixedOffset::east_opt(0);
+        }
+        let sign = match timezone.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let digits = timezone[1..].replace(':', "");
+        if digits.is_empty() || digits.len() > 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let (hours, minutes) = if digits.len() > 2 {
+            digits.split_at(digits.len() - 2)
+        } else {
+            (digits.as_str(), "0")
+        };
+        let hours: i32 = hours.parse().ok()?;
+        let minutes: i32 = minutes.parse().ok()?;
+        if minutes >= 60 {
+            return None;
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let date_time = util::get_date_time_from_filetime(129782011451468083);
+        assert_eq!(
+            "2012-04-06T15:52:25.1468083Z",
+            TimestampFormat::default().format(date_time)
+        );
+        assert_eq!(
+            "2012-04-06T11:22:25.1468083-04:30",
+            TimestampFormat::new(
+                TimestampStyle::Iso8601,
+                TimestampFormat::parse_timezone("-04:30")
+            )
+            .format(date_time)
+        );
+        assert_eq!(
+            "1333727545",
+            TimestampFormat::new(TimestampStyle::EpochSeconds, None).format(date_time)
+        );
+        assert_eq!(
+            "129782011451468083",
+            TimestampFormat::new(TimestampStyle::FileTime, None).format(date_time)
+        );
+        assert_eq!(
+            "129782011451468083",
+            TimestampFormat::new(TimestampStyle::FileTime, None)
+                .format_value(&CellValue::DateTime(date_time))
+        );
+        assert_eq!(
+            "notatin",
+            TimestampFormat::new(TimestampStyle::FileTime, None)
+                .format_value(&CellValue::String("notatin".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(
+            FixedOffset::east_opt(2 * 3600),
+            TimestampFormat::parse_timezone("+02:00")
+        );
+        assert_eq!(
+            FixedOffset::west_opt(5 * 3600 + 30 * 60),
+            TimestampFormat::parse_timezone("-0530")
+        );
+        assert_eq!(
+            FixedOffset::east_opt(9 * 3600),
+            TimestampFormat::parse_timezone("+9")
+        );
+        assert_eq!(
+            FixedOffset::east_opt(0),
+            TimestampFormat::parse_timezone("UTC")
+        );
+        assert_eq!(None, TimestampFormat::parse_timezone("02:00"));
+        assert_eq!(None, TimestampFormat::parse_timezone("+02:60"));
+        assert_eq!(None, TimestampFormat::parse_timezone("+25:00"));
+        assert_eq!(None, TimestampFormat::parse_timezone("+"));
+    }
+}
@@ -23,7 +23,7 @@ use crate::file_info::{FileInfo, ReadSeek};
 use crate::log::{LogCode, Logs};
 use crate::marvin32::marvin32;
 use crate::parser::{Parser, ParserIterator};
-use crate::progress;
+use crate::progress::{self, ProgressPhase};
 use crate::reg_item_map::{RegItemMap, RegItemMapKey, RegItemMapValue};
 use crate::state::State;
 use crate::util;
@@ -34,6 +34,11 @@ use nom::{
 };
 use serde::Serialize;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
 
 // Transaction log structures based off https://github.com/msuhanov/regf/blob/master/Windows%20registry%20file%20format%20specification.md#format-of-transaction-log-files
 
@@ -45,6 +50,8 @@ struct DirtyPageRef {
 }
 
 impl DirtyPageRef {
+    const SIZE: usize = 8;
+
     fn from_bytes() -> impl Fn(&[u8]) -> IResult<&[u8], Self> {
         |input: &[u8]| {
             let (input, offset) = le_u32(input)?;
@@ -104,6 +111,13 @@ impl LogEntry {
         let (input, dirty_pages_count) = le_u32(input)?;
         let (input, hash1) = le_u64(input)?;
         let (input, hash2) = le_u64(input)?;
+        // Don't let a corrupt count allocate more refs than the input could hold
+        if dirty_pages_count as usize > input.len() / DirtyPageRef::SIZE {
+            return Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::Count,
+            )));
+        }
         let (mut input, dirty_page_refs) =
             nom::multi::count(DirtyPageRef::from_bytes(), dirty_pages_count as usize)(input)?;
 
@@ -121,8 +135,8 @@ impl LogEntry {
             size,
             input.as_ptr() as usize - start_pos - file_offset_absolute,
         )?;
-        let has_valid_hashes =
-            hash1 == Self::calc_hash1(start, size as usize) && hash2 == Self::calc_hash2(start);
+        let has_valid_hashes = Some(hash1) == Self::calc_hash1(start, size as usize)
+            && hash2 == Self::calc_hash2(start);
 
         let hbh = Self {
             file_offset_absolute,
@@ -144,13 +158,22 @@ impl LogEntry {
         self.hive_bins_data_size % 4096 == 0
     }
 
-    fn calc_hash1(raw_bytes: &[u8], len: usize) -> u64 {
+    /// Returns true if any of the entry's dirty pages overlaps one of `ranges` (offsets relative from the start of the hive bins data)
+    fn touches_any(&self, ranges: &[Range<usize>]) -> bool {
+        self.dirty_pages.iter().any(|dirty_page| {
+            let start = dirty_page.dirty_page_ref_offset as usize;
+            let end = start + dirty_page.page_bytes.len();
+            ranges
+                .iter()
+                .any(|range| range.start < end && start < range.end)
+        })
+    }
+
+    /// Returns None if the entry's size doesn't cover its header or runs past `raw_bytes`
+    fn calc_hash1(raw_bytes: &[u8], len: usize) -> Option<u64> {
         const OFFSET: usize = 40;
-        let mut b = vec![0; len - OFFSET];
-        let dst = &mut b[0..len - OFFSET];
-        let src = &raw_bytes[OFFSET..len];
-        dst.copy_from_slice(src);
-        marvin32(DEFAULT_SEED, dst, len - OFFSET)
+        let src = raw_bytes.get(OFFSET..len)?;
+        Some(marvin32(DEFAULT_SEED, src, src.len()))
     }
 
     fn calc_hash2(raw_bytes: &[u8]) -> u64 {
@@ -163,6 +186,29 @@ impl LogEntry {
     }
 }
 
+/// Limits which log entries are replayed (see `ParserBuilderFromPath::replay_only`). Entries without a dirty page in scope
+/// are skipped, which is faster than a full replay but only gives an up-to-date view of what's in scope.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReplayScope {
+    /// Offsets relative from the start of the hive bins data, as used by dirty pages and cell offsets
+    OffsetRange(Range<u32>),
+    /// A key path (ex: `Control Panel\Desktop`); the cells of the key and its descendants in the primary file are in scope
+    KeySubtree(String),
+}
+
+/// What to do with a log entry whose Marvin32 hashes don't match its contents (see `ParserBuilderFromPath::log_entry_hash_policy`).
+/// Every mismatch is noted in the parse logs, along with a count of the valid and invalid entries of the log.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LogEntryHashPolicy {
+    /// Stop applying the log at the entry, as Windows does
+    #[default]
+    StopReplay,
+    /// Leave the entry out and keep applying the ones after it
+    SkipEntry,
+    /// Apply the entry anyway
+    ApplyWithWarning,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 pub(crate) struct TransactionLog {
     pub(crate) base_block: BaseBlockBase,
@@ -217,106 +263,170 @@ impl TransactionLog {
         Ok((transaction_logs, error_logs.get_option()))
     }
 
-    /// Updates the primary registry with the dirty pages in the passed-in log. Returns the last sequence number applied and the updated RegItemMap
+    /// Updates the primary registry with the dirty pages in the passed-in log. Returns the last sequence number applied and the updated RegItemMap.
+    /// `(log_index, log_count)` places this log among the ones being applied, for progress reporting.
+    /// If `replay_ranges` is set, entries without a dirty page in one of the ranges are skipped (see `ReplayScope`).
+    /// Entries whose hashes don't match are handled according to `hash_policy`.
     pub(crate) fn update_parser(
         &self,
         parser: &mut Parser,
         mut prior_items: RegItemMap,
+        (log_index, log_count): (usize, usize),
+        replay_ranges: Option<&[Range<usize>]>,
+        hash_policy: LogEntryHashPolicy,
     ) -> Result<(u32, RegItemMap), Error> {
         let mut new_sequence_number = 0;
+        let mut skipped_entries = 0;
+        let (mut valid_hashes, mut invalid_hashes) = (0, 0);
         let (primary_secondary_seq_num, primary_hive_bins_data_size) = parser.get_base_block_info();
         let mut console = progress::new(parser.update_console);
         for (index, log_entry) in self.log_entries.iter().enumerate() {
+            parser.progress.check_cancelled()?;
+            parser.progress.report(
+                ProgressPhase::ApplyingTransactionLogs,
+                100.0 * (log_index as f64 + index as f64 / self.log_entries.len() as f64)
+                    / log_count as f64,
+                0,
+            );
             console.update(&format!(
                 "Processing transaction log entry {} of {}",
                 index + 1,
                 self.log_entries.len()
             ))?;
             if log_entry.has_valid_hashes {
-                if log_entry.sequence_number < primary_secondary_seq_num {
-                    parser.state.info.add(
-                        LogCode::WarningTransactionLog,
-                        &format!("Skipping log entry; the log entry sequence number ({}) is less than to the primary file's secondary sequence number ({})", log_entry.sequence_number, primary_secondary_seq_num)
-                    );
-                } else if !log_entry.is_valid_hive_bin_data_size() {
-                    parser.state.info.add(
-                        LogCode::WarningTransactionLog,
-                        &format!("Stopping log entry processing; the hive_bin_data_size ({}) is not a multiple of 4096)", log_entry.hive_bins_data_size)
-                    );
-                    break;
-                } else if new_sequence_number != 0
-                    && log_entry.sequence_number != new_sequence_number + 1
-                {
-                    parser.state.info.add(
-                        LogCode::WarningTransactionLog,
-                        &format!("Stopping log entry processing; the sequence number ({}) does not follow the previous log entry's sequence number ({})", log_entry.sequence_number, new_sequence_number)
-                    );
-                    break;
-                } else {
-                    if primary_hive_bins_data_size < log_entry.hive_bins_data_size {
-                        parser.file_info.buffer.resize(
-                            parser.file_info.buffer.len()
-                                + (log_entry.hive_bins_data_size - primary_hive_bins_data_size)
-                                    as usize,
-                            0,
+                valid_hashes += 1;
+            } else {
+                invalid_hashes += 1;
+                match hash_policy {
+                    LogEntryHashPolicy::StopReplay => {
+                        parser.state.info.add(
+                            LogCode::WarningTransactionLog,
+                            &format!("Stopping log entry processing; hash mismatch at log entry with sequence number {}", log_entry.sequence_number)
                         );
+                        break;
                     }
-                    new_sequence_number = log_entry.sequence_number;
-
-                    // save the prior buffer for use
-                    let prior_file_info = if parser.recover_deleted {
-                        Some(parser.file_info.clone())
-                    } else {
-                        None
-                    };
-
-                    // apply the updated bytes to the main file buffer for each dirty page
-                    for dirty_page in &log_entry.dirty_pages {
-                        let dst_offset = dirty_page.dirty_page_ref_offset as usize
-                            + parser.file_info.hbin_offset_absolute;
-                        let dst_offset_end = dst_offset + dirty_page.page_bytes.len();
-                        let dst = parser
-                            .file_info
-                            .buffer
-                            .get_mut(dst_offset..dst_offset_end)
-                            .ok_or_else(|| Error::buffer("update_parser"))?;
-                        let src = &dirty_page.page_bytes;
-                        dst.copy_from_slice(src);
-                    }
-
-                    if parser.recover_deleted {
-                        let mut logs = Logs::default();
-                        let transaction_analyzer = TransactionAnalyzer {
-                            prior_file_info: &prior_file_info.unwrap(),
-                            new_sequence_number,
-                        };
-                        match transaction_analyzer.get_latest_reg_items(
-                            parser,
-                            &mut prior_items,
-                            &mut logs,
-                        ) {
-                            Ok(updated_items_ret) => {
-                                prior_items = updated_items_ret;
-                            }
-                            Err(e) => parser.state.info.add(
-                                LogCode::WarningTransactionLog,
-                                &format!(
-                                    "Unable to read cell tree for new sequence number {}, {:?}",
-                                    new_sequence_number, e
-                                ),
-                            ),
+                    LogEntryHashPolicy::SkipEntry => {
+                        parser.state.info.add(
+                            LogCode::WarningTransactionLog,
+                            &format!("Skipping log entry; hash mismatch at log entry with sequence number {}", log_entry.sequence_number)
+                        );
+                        // The entry still takes its place in the sequence so the entries after it can be applied
+                        if log_entry.sequence_number >= primary_secondary_seq_num
+                            && (new_sequence_number == 0
+                                || log_entry.sequence_number == new_sequence_number + 1)
+                        {
+                            new_sequence_number = log_entry.sequence_number;
                         }
-                        parser.state.info.extend(logs);
+                        continue;
                     }
+                    LogEntryHashPolicy::ApplyWithWarning => parser.state.info.add(
+                        LogCode::WarningTransactionLog,
+                        &format!("Applying log entry despite a hash mismatch at log entry with sequence number {}", log_entry.sequence_number)
+                    ),
                 }
-            } else {
+            }
+            if log_entry.sequence_number < primary_secondary_seq_num {
+                parser.state.info.add(
+                    LogCode::WarningTransactionLog,
+                    &format!("Skipping log entry; the log entry sequence number ({}) is less than to the primary file's secondary sequence number ({})", log_entry.sequence_number, primary_secondary_seq_num)
+                );
+            } else if !log_entry.is_valid_hive_bin_data_size() {
+                parser.state.info.add(
+                    LogCode::WarningTransactionLog,
+                    &format!("Stopping log entry processing; the hive_bin_data_size ({}) is not a multiple of 4096)", log_entry.hive_bins_data_size)
+                );
+                break;
+            } else if new_sequence_number != 0
+                && log_entry.sequence_number != new_sequence_number + 1
+            {
                 parser.state.info.add(
                     LogCode::WarningTransactionLog,
-                    &format!("Stopping log entry processing; hash mismatch at log entry with sequence number {}", log_entry.sequence_number)
+                    &format!("Stopping log entry processing; the sequence number ({}) does not follow the previous log entry's sequence number ({})", log_entry.sequence_number, new_sequence_number)
                 );
                 break;
+            } else {
+                if primary_hive_bins_data_size < log_entry.hive_bins_data_size {
+                    parser.file_info.buffer.resize(
+                        parser.file_info.buffer.len()
+                            + (log_entry.hive_bins_data_size - primary_hive_bins_data_size)
+                                as usize,
+                        0,
+                    );
+                }
+                new_sequence_number = log_entry.sequence_number;
+
+                if let Some(ranges) = replay_ranges {
+                    if !log_entry.touches_any(ranges) {
+                        skipped_entries += 1;
+                        continue;
+                    }
+                }
+
+                // save the prior buffer for use
+                let prior_file_info = if parser.recover_deleted {
+                    Some(parser.file_info.clone())
+                } else {
+                    None
+                };
+
+                // apply the updated bytes to the main file buffer for each dirty page
+                for dirty_page in &log_entry.dirty_pages {
+                    let dst_offset = dirty_page.dirty_page_ref_offset as usize
+                        + parser.file_info.hbin_offset_absolute;
+                    let dst_offset_end = dst_offset + dirty_page.page_bytes.len();
+                    let dst = parser
+                        .file_info
+                        .buffer
+                        .get_mut(dst_offset..dst_offset_end)
+                        .ok_or_else(|| Error::buffer("update_parser"))?;
+                    let src = &dirty_page.page_bytes;
+                    dst.copy_from_slice(src);
+                }
+
+                if parser.recover_deleted {
+                    let mut logs = Logs::default();
+                    let transaction_analyzer = TransactionAnalyzer {
+                        prior_file_info: &prior_file_info.unwrap(),
+                        new_sequence_number,
+                    };
+                    match transaction_analyzer.get_latest_reg_items(
+                        parser,
+                        &mut prior_items,
+                        &mut logs,
+                    ) {
+                        Ok(updated_items_ret) => {
+                            prior_items = updated_items_ret;
+                        }
+                        Err(e) => parser.state.info.add(
+                            LogCode::WarningTransactionLog,
+                            &format!(
+                                "Unable to read cell tree for new sequence number {}, {:?}",
+                                new_sequence_number, e
+                            ),
+                        ),
+                    }
+                    parser.state.info.extend(logs);
+                }
             }
         }
+        if invalid_hashes > 0 {
+            parser.state.info.add(
+                LogCode::WarningTransactionLog,
+                &format!(
+                    "Log entry hashes: {} valid, {} invalid (policy: {:?})",
+                    valid_hashes, invalid_hashes, hash_policy
+                ),
+            );
+        }
+        if skipped_entries > 0 {
+            parser.state.info.add(
+                LogCode::Info,
+                &format!(
+                    "Skipped {} log entries outside the replay scope",
+                    skipped_entries
+                ),
+            );
+        }
         Ok((new_sequence_number, prior_items))
     }
 
@@ -327,18 +437,24 @@ impl TransactionLog {
         let mut reg_items: RegItemMap = HashMap::new();
         if parser.recover_deleted {
             parser.init_root()?;
-            for key in ParserIterator::new(parser).iter() {
+            // Recovered keys carry reconstructed paths which may collide with allocated keys, so only the allocated tree is tracked
+            for key in ParserIterator::new(parser)
+                .get_modified_items(false)
+                .without_progress()
+                .iter()
+            {
+                let key_path = key.path.clone();
                 reg_items.insert(
-                    RegItemMapKey::new(key.path.clone(), None),
+                    RegItemMapKey::new(key_path.clone(), None),
                     RegItemMapValue::new(
                         key.hash.expect("Must have a hash here"),
                         key.file_offset_absolute,
                         sequence_num,
                     ),
                 );
-                for value in key.sub_values {
+                for value in key.sub_values.iter() {
                     reg_items.insert(
-                        RegItemMapKey::new(key.path.clone(), Some(value.detail.value_name())),
+                        RegItemMapKey::new(key_path.clone(), Some(value.detail.value_name())),
                         RegItemMapValue::new(
                             value.hash.expect("Must have a hash here"),
                             value.file_offset_absolute,
@@ -347,19 +463,20 @@ impl TransactionLog {
                     );
                 }
             }
+            parser.progress.check_cancelled()?;
         }
         Ok(reg_items)
     }
 }
 
 struct RegItemNeeded {
-    key_path: String,
+    key_path: Arc<str>,
     file_offset_absolute: usize,
     sequence_num: u32,
 }
 
 impl RegItemNeeded {
-    fn new(key_path: String, file_offset_absolute: usize, sequence_num: u32) -> Self {
+    fn new(key_path: Arc<str>, file_offset_absolute: usize, sequence_num: u32) -> Self {
         Self {
             key_path,
             file_offset_absolute,
@@ -369,7 +486,7 @@ impl RegItemNeeded {
 }
 
 struct NewItemInfo<'a> {
-    key_path: &'a str,
+    key_path: &'a Arc<str>,
     value_path: Option<String>,
     updated_item: &'a dyn Cell,
 }
@@ -395,29 +512,31 @@ impl TransactionAnalyzer<'_> {
 
         for updated_key in ParserIterator::new(updated_parser)
             .get_modified_items(false)
+            .without_progress()
             .iter()
         {
+            let key_path = updated_key.path.clone();
             self.handle_new_item(
                 prior_reg_items,
                 &mut latest_reg_items,
                 &mut prior_keys_needed,
                 logs,
                 NewItemInfo {
-                    key_path: &updated_key.path,
+                    key_path: &key_path,
                     value_path: None,
                     updated_item: &updated_key,
                 },
             );
-            for updated_value in updated_key.sub_values {
+            for updated_value in updated_key.sub_values.iter() {
                 self.handle_new_item(
                     prior_reg_items,
                     &mut latest_reg_items,
                     &mut prior_values_needed,
                     logs,
                     NewItemInfo {
-                        key_path: &updated_key.path,
+                        key_path: &key_path,
                         value_path: Some(updated_value.detail.value_name().clone()),
-                        updated_item: &updated_value,
+                        updated_item: updated_value,
                     },
                 );
             }
@@ -541,10 +660,11 @@ impl TransactionAnalyzer<'_> {
             .updated_item
             .get_hash()
             .expect("must have hash if here");
-        if let Some(prior_item) = prior_reg_items.remove(&RegItemMapKey::new(
-            new_item_info.key_path.to_string(),
-            new_item_info.value_path.clone(),
-        )) {
+        let mut reg_item_map_key =
+            RegItemMapKey::new(new_item_info.key_path.clone(), new_item_info.value_path);
+        if let Some((prior_key, prior_item)) = prior_reg_items.remove_entry(&reg_item_map_key) {
+            // Keep the prior map's copy of the path (rather than the one just read) so unchanged paths are shared across sequence numbers
+            reg_item_map_key.key_path = prior_key.key_path;
             // We found our item in prior_reg_items. Now we will check the hash.
             // If the hash is the same then we will just return the sequence number from the original item.
             // If the hash differs, we have a modified item and we'll add it to the reg_items_needed map
@@ -553,7 +673,7 @@ impl TransactionAnalyzer<'_> {
             } else {
                 sequence_num = self.new_sequence_number;
                 reg_items_needed.push(RegItemNeeded::new(
-                    new_item_info.key_path.to_string(),
+                    reg_item_map_key.key_path.clone(),
                     prior_item.file_offset_absolute,
                     prior_item.sequence_num,
                 ));
@@ -563,7 +683,7 @@ impl TransactionAnalyzer<'_> {
         }
 
         latest_reg_items.insert(
-            RegItemMapKey::new(new_item_info.key_path.to_string(), new_item_info.value_path),
+            reg_item_map_key,
             RegItemMapValue::new(
                 updated_item_hash,
                 new_item_info.updated_item.get_file_offset_absolute(),
@@ -600,7 +720,7 @@ impl TransactionAnalyzer<'_> {
         if let Some(mut full_key) = full_key {
             full_key.updated_by_sequence_num = Some(self.new_sequence_number);
             // remove values; they'll be captured elsewhere
-            full_key.sub_values = vec![];
+            full_key.sub_values = Arc::default();
             match modified_list_type {
                 ModifiedListType::Updated => {
                     full_key.cell_state = CellState::ModifiedTransactionLog;
@@ -651,6 +771,91 @@ impl TransactionAnalyzer<'_> {
     }
 }
 
+/// A page a log entry writes to the primary file
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct DirtyPageInfo {
+    /// Offset of the page in bytes, relative from the start of the hive bins data
+    pub offset: u32,
+    /// Size of the page in bytes
+    pub size: u32,
+}
+
+/// The header of a log entry and the pages it writes (without their data)
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct LogEntryInfo {
+    pub file_offset_absolute: usize,
+    pub size: u32,
+    pub flags: u32,
+    pub sequence_number: u32,
+    pub hive_bins_data_size: u32,
+    pub dirty_pages_count: u32,
+    /// Entries with invalid hashes aren't applied
+    pub has_valid_hashes: bool,
+    pub dirty_pages: Vec<DirtyPageInfo>,
+}
+
+/// A transaction log that was applied while building a parser (see `Parser::applied_transaction_logs`)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct AppliedTransactionLog {
+    /// The primary sequence number from the log's base block, which identifies the log
+    pub primary_sequence_number: u32,
+    /// The sequence number of the last log entry applied (from this log or an earlier one)
+    pub last_sequence_number: u32,
+}
+
+/// The structure of a transaction log (.LOG1 or .LOG2) file, for deciding whether to apply it. Log entries have no timestamp
+/// of their own; the base block's `last_modification_date_and_time` is the only one in the file.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct TransactionLogInfo {
+    pub base_block: BaseBlockBase,
+    pub base_block_checksum_is_valid: bool,
+    pub entries: Vec<LogEntryInfo>,
+}
+
+impl TransactionLogInfo {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        let (_, log) = TransactionLog::from_bytes(&buffer)?;
+        Ok(Self::from(&log))
+    }
+}
+
+impl From<&TransactionLog> for TransactionLogInfo {
+    fn from(log: &TransactionLog) -> Self {
+        Self {
+            base_block: log.base_block.clone(),
+            base_block_checksum_is_valid: BaseBlockBase::verify_checksum(&log.base_block_bytes)
+                .unwrap_or_default(),
+            entries: log
+                .log_entries
+                .iter()
+                .map(|entry| LogEntryInfo {
+                    file_offset_absolute: entry.file_offset_absolute,
+                    size: entry.size,
+                    flags: entry.flags,
+                    sequence_number: entry.sequence_number,
+                    hive_bins_data_size: entry.hive_bins_data_size,
+                    dirty_pages_count: entry.dirty_pages_count,
+                    has_valid_hashes: entry.has_valid_hashes,
+                    dirty_pages: entry
+                        .dirty_pages
+                        .iter()
+                        .map(|page| DirtyPageInfo {
+                            offset: page.dirty_page_ref_offset,
+                            size: page.page_bytes.len() as u32,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -677,6 +882,7 @@ mod tests {
             primary_sequence_number: 4064,
             secondary_sequence_number: 4064,
             last_modification_date_and_time: util::get_date_time_from_filetime(0),
+            last_modification_filetime: 0,
             major_version: 1,
             minor_version: 5,
             file_type: FileType::TransactionLogNewFormat,
@@ -696,6 +902,37 @@ mod tests {
         assert_eq!(114, log.log_entries[2].dirty_pages[1].page_bytes[1000]);
     }
 
+    #[test]
+    fn test_transaction_log_info() {
+        let info = TransactionLogInfo::from_path("test_data/system.log1").unwrap();
+        assert!(info.base_block_checksum_is_valid);
+        assert_eq!(4064, info.base_block.primary_sequence_number);
+        assert_eq!(3, info.entries.len());
+        for entry in &info.entries {
+            assert_eq!(entry.dirty_pages_count as usize, entry.dirty_pages.len());
+        }
+        let entry = &info.entries[2];
+        assert_eq!((32768, 16384), (entry.file_offset_absolute, entry.size));
+        assert_eq!(
+            vec![
+                DirtyPageInfo {
+                    offset: 0,
+                    size: 4096
+                },
+                DirtyPageInfo {
+                    offset: 4427776,
+                    size: 8192
+                }
+            ],
+            entry.dirty_pages
+        );
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(3, json["entries"].as_array().unwrap().len());
+
+        assert!(TransactionLogInfo::from_reader(&b"HvLE"[..]).is_err());
+    }
+
     #[test]
     fn test_parse_log_entry() -> Result<(), Error> {
         let mut parser = ParserBuilder::from_path("test_data/system")
@@ -718,7 +955,13 @@ mod tests {
         log.log_entries[1].sequence_number = 4068;
 
         let original_items = TransactionLog::get_reg_items(&mut parser, 0)?;
-        let (last_sequence_num, _) = log.update_parser(&mut parser, original_items)?;
+        let (last_sequence_num, _) = log.update_parser(
+            &mut parser,
+            original_items,
+            (0, 1),
+            None,
+            LogEntryHashPolicy::default(),
+        )?;
         assert_eq!(4066, last_sequence_num);
         let mut expected_warning_logs = Logs::default();
         expected_warning_logs.add(LogCode::WarningBaseBlock, &"Hive requires recovery: primary and secondary sequence numbers do not match. 4019, 4018");
@@ -727,13 +970,97 @@ mod tests {
 
         let original_items = TransactionLog::get_reg_items(&mut parser, 0)?;
         log.log_entries[0].has_valid_hashes = false;
-        let (last_sequence_num, _) = log.update_parser(&mut parser, original_items)?;
+        let (last_sequence_num, _) = log.update_parser(
+            &mut parser,
+            original_items,
+            (0, 1),
+            None,
+            LogEntryHashPolicy::default(),
+        )?;
         assert_eq!(0, last_sequence_num);
         expected_warning_logs.add(
             LogCode::WarningTransactionLog,
             &"Stopping log entry processing; hash mismatch at log entry with sequence number 4066",
         );
+        expected_warning_logs.add(
+            LogCode::WarningTransactionLog,
+            &"Log entry hashes: 0 valid, 1 invalid (policy: StopReplay)",
+        );
+        assert_eq!(expected_warning_logs, parser.state.info);
+
+        log.log_entries[1].sequence_number = 4067;
+        let original_items = TransactionLog::get_reg_items(&mut parser, 0)?;
+        let (last_sequence_num, _) = log.update_parser(
+            &mut parser,
+            original_items,
+            (0, 1),
+            None,
+            LogEntryHashPolicy::SkipEntry,
+        )?;
+        assert_eq!(4067, last_sequence_num);
+        expected_warning_logs.add(
+            LogCode::WarningTransactionLog,
+            &"Skipping log entry; hash mismatch at log entry with sequence number 4066",
+        );
+        expected_warning_logs.add(LogCode::WarningTransactionLog, &"Stopping log entry processing; the sequence number (4066) does not follow the previous log entry's sequence number (4067)");
+        expected_warning_logs.add(
+            LogCode::WarningTransactionLog,
+            &"Log entry hashes: 2 valid, 1 invalid (policy: SkipEntry)",
+        );
         assert_eq!(expected_warning_logs, parser.state.info);
+
+        let original_items = TransactionLog::get_reg_items(&mut parser, 0)?;
+        let (last_sequence_num, _) = log.update_parser(
+            &mut parser,
+            original_items,
+            (0, 1),
+            None,
+            LogEntryHashPolicy::ApplyWithWarning,
+        )?;
+        assert_eq!(4067, last_sequence_num);
+        expected_warning_logs.add(
+            LogCode::WarningTransactionLog,
+            &"Applying log entry despite a hash mismatch at log entry with sequence number 4066",
+        );
+        expected_warning_logs.add(LogCode::WarningTransactionLog, &"Stopping log entry processing; the sequence number (4066) does not follow the previous log entry's sequence number (4067)");
+        expected_warning_logs.add(
+            LogCode::WarningTransactionLog,
+            &"Log entry hashes: 2 valid, 1 invalid (policy: ApplyWithWarning)",
+        );
+        assert_eq!(expected_warning_logs, parser.state.info);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reg_item_paths_are_shared() -> Result<(), Error> {
+        let mut parser = ParserBuilder::from_path("test_data/system")
+            .recover_deleted(true)
+            .build()?;
+        let original_items = TransactionLog::get_reg_items(&mut parser, 0)?;
+        let key_path = original_items
+            .keys()
+            .find(|item| item.value_name.is_some())
+            .map(|item| item.key_path.clone())
+            .unwrap();
+        let (key_item, _) = original_items
+            .get_key_value(&RegItemMapKey::new(key_path.clone(), None))
+            .unwrap();
+        assert!(Arc::ptr_eq(&key_path, &key_item.key_path));
+
+        let mut file_info = FileInfo::from_path("test_data/system.log1")?;
+        file_info.hbin_offset_absolute = 4096;
+        let (_, log) = TransactionLog::from_bytes(&file_info.buffer[0..]).unwrap();
+        let (_, latest_items) = log.update_parser(
+            &mut parser,
+            original_items,
+            (0, 1),
+            None,
+            LogEntryHashPolicy::default(),
+        )?;
+        let (latest_key_item, _) = latest_items
+            .get_key_value(&RegItemMapKey::new(key_path.clone(), None))
+            .unwrap();
+        assert!(Arc::ptr_eq(&key_path, &latest_key_item.key_path));
         Ok(())
     }
 
@@ -19,11 +19,17 @@ use crate::log::{LogCode, Logs};
 use chrono::{DateTime, Utc};
 use nom::{bytes::complete::take, IResult};
 use std::{
-    borrow::Cow, char::REPLACEMENT_CHARACTER, convert::TryInto, fmt::Write as FmtWrite, mem, str,
+    borrow::Cow,
+    char::REPLACEMENT_CHARACTER,
+    convert::{TryFrom, TryInto},
+    fmt::Write as FmtWrite,
+    mem, str,
 };
 use winstructs::guid::Guid;
 
 const SIZE_OF_UTF16_CHAR: usize = mem::size_of::<u16>();
+/// The value of an offset field that doesn't reference a cell (ex: a key without a class name)
+pub(crate) const NO_OFFSET: u32 = u32::MAX;
 
 fn from_utf16_le_string_single(
     slice: &[u8],
@@ -117,6 +123,7 @@ pub(crate) fn from_ascii(slice: &[u8], logs: &mut Logs, err_detail: &str) -> Str
     result
 }
 
+/// Reads a name of `count` bytes. Names have an explicit length, so unlike string data they aren't truncated at a null.
 pub(crate) fn string_from_bytes(
     is_ascii: bool,
     slice: &[u8],
@@ -127,7 +134,56 @@ pub(crate) fn string_from_bytes(
     if is_ascii {
         from_ascii(slice, logs, err_detail)
     } else {
-        from_utf16_le_string(slice, count.into(), logs, err_detail)
+        let iter = slice[..count as usize]
+            .chunks_exact(SIZE_OF_UTF16_CHAR)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]));
+        std::char::decode_utf16(iter)
+            .map(|r| {
+                r.unwrap_or_else(|err| {
+                    logs.add(
+                        LogCode::WarningConversion,
+                        &format!("{}: {}", err_detail, err),
+                    );
+                    REPLACEMENT_CHARACTER
+                })
+            })
+            .collect()
+    }
+}
+
+/// Reads a key or value name (see `string_from_bytes`). Embedded nulls and other control characters, which regedit
+/// can't display, are kept and flagged in `logs`. Returns the name, and the raw bytes if they can't be recreated from it
+/// (the name isn't valid UTF-16).
+pub(crate) fn name_from_bytes(
+    is_ascii: bool,
+    slice: &[u8],
+    logs: &mut Logs,
+    err_detail: &str,
+) -> (String, Option<Vec<u8>>) {
+    let name = string_from_bytes(is_ascii, slice, slice.len() as u16, logs, err_detail);
+    if name.contains(char::is_control) {
+        logs.add(
+            LogCode::WarningName,
+            &format!(
+                "{}: name contains null or control characters: {}",
+                err_detail,
+                name.escape_debug()
+            ),
+        );
+    }
+    if !is_ascii && name.contains(REPLACEMENT_CHARACTER) {
+        (name, Some(slice.to_vec()))
+    } else {
+        (name, None)
+    }
+}
+
+/// Encodes `name` as it's stored in a hive: Latin-1 for compressed (ASCII) names, otherwise UTF-16LE
+pub(crate) fn name_to_bytes(name: &str, is_ascii: bool) -> Vec<u8> {
+    if is_ascii {
+        name.chars().map(|c| c as u8).collect()
+    } else {
+        name.encode_utf16().flat_map(u16::to_le_bytes).collect()
     }
 }
 
@@ -145,6 +201,28 @@ pub(crate) fn parser_eat_remaining(
     }
 }
 
+/// Absolute offset and bytes of the slack at the end of a cell
+pub(crate) type CellSlack = (usize, Vec<u8>);
+
+/// Returns the absolute offset and the bytes between the end of `data_len` bytes of data and the end of the cell at `cell_offset_absolute`.
+/// Returns None if the cell can't be read or the data fills it.
+pub(crate) fn read_cell_slack(
+    buffer: &[u8],
+    cell_offset_absolute: usize,
+    data_len: usize,
+) -> Option<CellSlack> {
+    let size_bytes =
+        buffer.get(cell_offset_absolute..cell_offset_absolute + mem::size_of::<i32>())?;
+    let cell_size = i32::from_le_bytes(size_bytes.try_into().ok()?).unsigned_abs() as usize;
+    let slack_offset = cell_offset_absolute + mem::size_of::<i32>() + data_len;
+    let slack = buffer.get(slack_offset..cell_offset_absolute + cell_size)?;
+    if slack.is_empty() {
+        None
+    } else {
+        Some((slack_offset, slack.to_vec()))
+    }
+}
+
 /// Converts a u64 filetime to a DateTime<Utc>
 pub fn get_date_time_from_filetime(filetime: u64) -> DateTime<Utc> {
     const UNIX_EPOCH_SECONDS_SINCE_WINDOWS_EPOCH: i128 = 11644473600;
@@ -162,6 +240,14 @@ pub fn get_date_time_from_filetime(filetime: u64) -> DateTime<Utc> {
     )
 }
 
+/// Converts a DateTime<Utc> to a u64 filetime (the inverse of `get_date_time_from_filetime`). Dates before 1601 are returned as 0.
+pub fn get_filetime_from_date_time(date_time: DateTime<Utc>) -> u64 {
+    const UNIX_EPOCH_FILETIME: i128 = 11644473600 * 10_000_000;
+    let nanos =
+        date_time.timestamp() as i128 * 1_000_000_000 + date_time.timestamp_subsec_nanos() as i128;
+    u64::try_from(nanos / 100 + UNIX_EPOCH_FILETIME).unwrap_or_default()
+}
+
 /// Converts a DateTime<Utc> to ISO-8601/RFC-3339 format `%Y-%m-%dT%H:%M:%S%.7f` (manually, since Rust doesn't support `%.7f`)
 pub fn format_date_time(date_time: DateTime<Utc>) -> String {
     let fractional_seconds = date_time.format("%9f").to_string();
@@ -194,6 +280,22 @@ pub(crate) fn get_guid_from_buffer(buffer: &[u8], logs: &mut Logs) -> Guid {
         .expect("Error handled in or_else")
 }
 
+/// Parses a guid in the form it is displayed (`54849625-5478-4994-A5BA-3E3B0328C30D`). Returns None if `s` isn't a guid.
+pub(crate) fn get_guid_from_string(s: &str) -> Option<Guid> {
+    let hex: String = s.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 || s.len() != 36 {
+        return None;
+    }
+    let d1 = u32::from_str_radix(hex.get(0..8)?, 16).ok()?;
+    let d2 = u16::from_str_radix(hex.get(8..12)?, 16).ok()?;
+    let d3 = u16::from_str_radix(hex.get(12..16)?, 16).ok()?;
+    let mut d4 = [0; 8];
+    for (i, b) in d4.iter_mut().enumerate() {
+        *b = u8::from_str_radix(hex.get(16 + i * 2..18 + i * 2)?, 16).ok()?;
+    }
+    Some(Guid::new(d1, d2, d3, d4))
+}
+
 pub(crate) fn get_pretty_name(name: &str) -> String {
     if name.is_empty() {
         "(default)".to_string()
@@ -213,10 +315,248 @@ pub fn to_hex_string(bytes: &[u8]) -> String {
     s.trim_end().to_string()
 }
 
+/// The inverse of `to_hex_string`. Returns None if `s` contains anything other than space separated hex bytes.
+pub fn from_hex_string(s: &str) -> Option<Vec<u8>> {
+    s.split_whitespace()
+        .map(|byte| match byte.len() {
+            2 => u8::from_str_radix(byte, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Encodes `s` as a null-terminated UTF-16 string (REG_SZ)
+pub(crate) fn to_utf16_le_string(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// Encodes `strings` as a sequence of null-terminated UTF-16 strings, terminated by an empty string (REG_MULTI_SZ)
+pub(crate) fn to_utf16_le_strings(strings: &[String]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = strings.iter().flat_map(|s| to_utf16_le_string(s)).collect();
+    bytes.extend_from_slice(&[0, 0]);
+    bytes
+}
+
+/// Adds `bytes` to `hasher` prefixed with their length, so adjacent fields can't run together
+pub(crate) fn hash_field(hasher: &mut blake3::Hasher, bytes: &[u8]) {
+    hasher.update(&(bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` as standard (RFC 4648) base64 with padding
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or_default(),
+            chunk.get(2).copied().unwrap_or_default(),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+/// The inverse of `to_base64`. Whitespace is ignored and padding is optional. Returns None if `s` isn't valid base64.
+pub fn from_base64(s: &str) -> Option<Vec<u8>> {
+    let s: Vec<u8> = s.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    let unpadded = s
+        .strip_suffix(b"==")
+        .or_else(|| s.strip_suffix(b"="))
+        .unwrap_or(&s);
+    if unpadded.len() != s.len() && !s.len().is_multiple_of(4) {
+        return None;
+    }
+    let digits = unpadded
+        .iter()
+        .map(|&c| {
+            BASE64_ALPHABET
+                .iter()
+                .position(|&a| a == c)
+                .map(|d| d as u32)
+        })
+        .collect::<Option<Vec<u32>>>()?;
+    if digits.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, digit)| n | digit << (18 - 6 * i));
+        bytes.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(bytes)
+}
+
+/// Formats `data` as lines of 16 bytes: the offset, the bytes in hex, and the printable ASCII characters
+pub fn hex_dump(data: &[u8]) -> Vec<String> {
+    hex_dump_with_text(data, |chunk| {
+        chunk.iter().map(|&b| printable_or_dot(b as char)).collect()
+    })
+}
+
+/// Like `hex_dump`, but the text column shows the bytes as UTF-16LE characters (as most registry strings are stored)
+pub fn hex_dump_utf16_le(data: &[u8]) -> Vec<String> {
+    hex_dump_with_text(data, |chunk| {
+        std::char::decode_utf16(
+            chunk
+                .chunks(SIZE_OF_UTF16_CHAR)
+                .map(|c| u16::from_le_bytes([c[0], c.get(1).copied().unwrap_or_default()])),
+        )
+        .map(|c| printable_or_dot(c.unwrap_or('.')))
+        .collect()
+    })
+}
+
+fn hex_dump_with_text(data: &[u8], text: impl Fn(&[u8]) -> String) -> Vec<String> {
+    data.chunks(16)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("{:08x}  {:<47}  {}", index * 16, hex.join(" "), text(chunk))
+        })
+        .collect()
+}
+
+fn printable_or_dot(c: char) -> char {
+    if c == ' ' || (!c.is_whitespace() && !c.is_control()) {
+        c
+    } else {
+        '.'
+    }
+}
+
+/// Reads a little-endian u16 at `offset`. Returns None if `data` is too short.
+pub fn read_u16_le(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset.checked_add(2)?)
+        .and_then(|b| b.try_into().ok())
+        .map(u16::from_le_bytes)
+}
+
+/// Reads a little-endian u32 at `offset`. Returns None if `data` is too short.
+pub fn read_u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset.checked_add(4)?)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_le_bytes)
+}
+
+/// Reads a little-endian u64 at `offset`. Returns None if `data` is too short.
+pub fn read_u64_le(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset.checked_add(8)?)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_le_bytes)
+}
+
+/// Reads a FILETIME at `offset`. Returns None if `data` is too short, or if the FILETIME is zero or 0x7fffffffffffffff (both of which mean "never").
+pub fn read_filetime(data: &[u8], offset: usize) -> Option<DateTime<Utc>> {
+    const FILETIME_NEVER: u64 = 0x7fffffffffffffff;
+    read_u64_le(data, offset)
+        .filter(|filetime| *filetime != 0 && *filetime != FILETIME_NEVER)
+        .map(get_date_time_from_filetime)
+}
+
+/// Converts seconds since the Unix epoch to a DateTime<Utc>. Returns None for zero (never set) or out of range values.
+pub fn get_date_time_from_unix_epoch(seconds: u64) -> Option<DateTime<Utc>> {
+    if seconds == 0 {
+        return None;
+    }
+    chrono::NaiveDateTime::from_timestamp_opt(seconds.try_into().ok()?, 0)
+        .map(|d| DateTime::<Utc>::from_utc(d, Utc))
+}
+
+/// Reads a u32 count of seconds since the Unix epoch at `offset` (see `get_date_time_from_unix_epoch`)
+pub fn read_unix_epoch(data: &[u8], offset: usize) -> Option<DateTime<Utc>> {
+    read_u32_le(data, offset).and_then(|seconds| get_date_time_from_unix_epoch(seconds as u64))
+}
+
+/// Converts an MS-DOS (FAT) date and time to a DateTime<Utc>. Returns None if either is invalid (including a zero date).
+pub fn get_date_time_from_dos_date_time(date: u16, time: u16) -> Option<DateTime<Utc>> {
+    chrono::NaiveDate::from_ymd_opt(
+        1980 + (date >> 9) as i32,
+        ((date >> 5) & 0x0f) as u32,
+        (date & 0x1f) as u32,
+    )?
+    .and_hms_opt(
+        (time >> 11) as u32,
+        ((time >> 5) & 0x3f) as u32,
+        ((time & 0x1f) * 2) as u32,
+    )
+    .map(|d| DateTime::<Utc>::from_utc(d, Utc))
+}
+
+/// Reads an MS-DOS date (u16) followed by an MS-DOS time (u16) at `offset` (see `get_date_time_from_dos_date_time`)
+pub fn read_dos_date_time(data: &[u8], offset: usize) -> Option<DateTime<Utc>> {
+    get_date_time_from_dos_date_time(
+        read_u16_le(data, offset)?,
+        read_u16_le(data, offset.checked_add(2)?)?,
+    )
+}
+
+/// Reads a 16 byte SYSTEMTIME structure (year, month, day of week, day, hour, minute, second, milliseconds) at `offset`.
+/// SYSTEMTIMEs are frequently stored in local time, so no time zone is assumed.
+pub fn read_systemtime(data: &[u8], offset: usize) -> Option<chrono::NaiveDateTime> {
+    let systemtime = data.get(offset..offset.checked_add(16)?)?;
+    let field = |i: usize| read_u16_le(systemtime, i * 2).map(u32::from);
+    let date = chrono::NaiveDate::from_ymd_opt(field(0)? as i32, field(1)?, field(3)?)?;
+    date.and_hms_milli_opt(field(4)?, field(5)?, field(6)?, field(7)?)
+}
+
+/// Reads a 16 byte GUID at `offset` and formats it as `XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX`
+pub fn read_guid_string(data: &[u8], offset: usize) -> Option<String> {
+    let buffer = data.get(offset..offset.checked_add(16)?)?;
+    Guid::from_buffer(buffer).ok().map(|guid| guid.to_string())
+}
+
+/// Reads a null-terminated UTF-16LE string at `offset`. If there is no terminator the rest of `data` is read.
+pub fn read_utf16_le_string(data: &[u8], offset: usize) -> Option<String> {
+    let chars: Vec<u16> = data
+        .get(offset..)?
+        .chunks_exact(SIZE_OF_UTF16_CHAR)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|c| *c != 0)
+        .collect();
+    Some(String::from_utf16_lossy(&chars))
+}
+
 pub fn escape_string(orig: &str) -> Cow<str> {
+    let orig = escape_control_chars(orig);
     if orig.contains(&['\t', '\r', '\n', ',', '\"'][..]) {
-        let escaped = &str::replace(orig, "\"", "\"\"");
+        let escaped = &str::replace(&orig, "\"", "\"\"");
         Cow::Owned(format!("\"{}\"", escaped))
+    } else {
+        orig
+    }
+}
+
+/// Replaces control characters other than tab, CR, and LF (such as the embedded nulls found in some malicious key names)
+/// with `\xNN` escapes, so they can't truncate or corrupt the output of text based writers
+pub fn escape_control_chars(orig: &str) -> Cow<'_, str> {
+    let is_unsafe = |c: char| c.is_control() && !matches!(c, '\t' | '\r' | '\n');
+    if orig.contains(is_unsafe) {
+        let mut escaped = String::with_capacity(orig.len() + 8);
+        for c in orig.chars() {
+            if is_unsafe(c) {
+                escaped += &format!("\\x{:02x}", c as u32);
+            } else {
+                escaped.push(c);
+            }
+        }
+        Cow::Owned(escaped)
     } else {
         Cow::Borrowed(orig)
     }
@@ -240,7 +580,8 @@ const fn calc_compression_bits() -> [u8; 4096] {
 }
 
 // https://github.com/marekventur/rust-rot13
-pub(crate) fn decode_rot13(text: &str) -> String {
+/// Decodes (or encodes) ROT13 text, as used by UserAssist value names
+pub fn decode_rot13(text: &str) -> String {
     text.chars()
         .map(|c| match c {
             'A'..='M' | 'a'..='m' => ((c as u8) + 13) as char,
@@ -260,17 +601,23 @@ pub(crate) fn decode_lznt1(
     const SUB_BLOCK_SIZE_MASK: u16 = 0x0fff;
     const COMPRESSION_BITS: [u8; 4096] = calc_compression_bits();
 
-    let mut decompressed = vec![]; //vec![0; source.len() * 10];
-    let decompressed_offset: usize = 0;
+    // Malformed input is common in recovered data, so every access is checked
+    let byte_at = |index: usize| {
+        source
+            .get(source_offset + index)
+            .copied()
+            .ok_or_else(|| Error::buffer("decode_lznt1"))
+    };
+    let u16_at = |index: usize| {
+        read_u16_le(source, source_offset + index).ok_or_else(|| Error::buffer("decode_lznt1"))
+    };
+
+    let mut decompressed = vec![];
     let mut source_index: usize = 0;
     let mut dest_index: usize = 0;
 
     while source_index + source_offset < source_length {
-        let header = u16::from_le_bytes(
-            source[source_index + source_offset
-                ..source_index + source_offset + mem::size_of::<u16>()]
-                .try_into()?,
-        ); //Utilities.ToUInt16LittleEndian(source, source_offset + source_index);
+        let header = u16_at(source_index)?;
         source_index += 2;
 
         // Look for null-terminating sub-block header
@@ -280,9 +627,10 @@ pub(crate) fn decode_lznt1(
 
         if (header & SUB_BLOCK_IS_COMPRESSED_FLAG) == 0 {
             let block_size: usize = (header & SUB_BLOCK_SIZE_MASK) as usize + 1;
-            decompressed.extend(
-                source[source_offset + source_index..source_offset + source_index + block_size]
-                    .to_vec(),
+            decompressed.extend_from_slice(
+                source
+                    .get(source_offset + source_index..source_offset + source_index + block_size)
+                    .ok_or_else(|| Error::buffer("decode_lznt1"))?,
             );
             source_index += block_size;
             dest_index += block_size;
@@ -291,7 +639,7 @@ pub(crate) fn decode_lznt1(
             let dest_sub_block_start = dest_index;
             let src_sub_block_end = source_index + (header & SUB_BLOCK_SIZE_MASK) as usize + 1;
             while source_index < src_sub_block_end {
-                let mut tag: u8 = source[source_offset + source_index];
+                let mut tag: u8 = byte_at(source_index)?;
                 source_index += 1;
 
                 for _token in 0..8 {
@@ -300,27 +648,30 @@ pub(crate) fn decode_lznt1(
                     }
 
                     if (tag & 1) == 0 {
-                        decompressed.push(source[source_offset + source_index]);
+                        decompressed.push(byte_at(source_index)?);
                         dest_index += 1;
                         source_index += 1;
                     } else {
-                        let length_bits: u16 =
-                            (16 - COMPRESSION_BITS[dest_index - dest_sub_block_start]) as u16;
+                        let compression_bits = *COMPRESSION_BITS
+                            .get(dest_index - dest_sub_block_start)
+                            .ok_or_else(|| Error::buffer("decode_lznt1: sub-block too large"))?;
+                        let length_bits: u16 = (16 - compression_bits) as u16;
                         let length_mask: u16 = ((1 << length_bits) - 1) as u16;
 
-                        let phrase_token: u16 = u16::from_le_bytes(
-                            source[source_index + source_offset
-                                ..source_index + source_offset + mem::size_of::<u16>()]
-                                .try_into()?,
-                        );
+                        let phrase_token: u16 = u16_at(source_index)?;
                         source_index += 2;
 
-                        let mut dest_back_addr =
-                            dest_index - (phrase_token >> length_bits) as usize - 1;
+                        let mut dest_back_addr = dest_index
+                            .checked_sub((phrase_token >> length_bits) as usize + 1)
+                            .ok_or_else(|| Error::buffer("decode_lznt1: back reference"))?;
                         let length = (phrase_token & length_mask) + 3;
 
                         for _i in 0..length {
-                            decompressed.push(decompressed[decompressed_offset + dest_back_addr]);
+                            decompressed.push(
+                                *decompressed
+                                    .get(dest_back_addr)
+                                    .ok_or_else(|| Error::buffer("decode_lznt1: back reference"))?,
+                            );
                             dest_index += 1;
                             dest_back_addr += 1;
                         }
@@ -344,11 +695,59 @@ pub(crate) fn get_root_path_offset(path: &str) -> usize {
     }
 }
 
+/// Escapes the backslashes in a key name (by doubling them) so that the name can be used as a segment of a key path
+/// passed to `Parser::get_key` or `CellKeyNode::get_sub_key`
+pub fn escape_key_name(name: &str) -> Cow<'_, str> {
+    if name.contains('\\') {
+        Cow::Owned(name.replace('\\', "\\\\"))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Splits a key path into key names. Names are separated by a backslash, and two consecutive backslashes are a
+/// backslash within a name (see `escape_key_name`). Empty names (from leading or trailing separators) are skipped.
+pub(crate) fn split_key_path(path: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut name = String::new();
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if chars.peek() == Some(&'\\') {
+                chars.next();
+                name.push('\\');
+            } else if !name.is_empty() {
+                names.push(std::mem::take(&mut name));
+            }
+        } else {
+            name.push(c);
+        }
+    }
+    if !name.is_empty() {
+        names.push(name);
+    }
+    names
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::log::Log;
 
+    #[test]
+    fn test_split_key_path() {
+        assert_eq!(
+            vec!["Control Panel", "Accessibility"],
+            split_key_path("\\Control Panel\\Accessibility\\")
+        );
+        assert_eq!(
+            vec!["Software", "a\\b", "c"],
+            split_key_path(&format!("Software\\{}\\c", escape_key_name("a\\b")))
+        );
+        assert_eq!(Vec::<String>::new(), split_key_path(""));
+        assert_eq!("no backslash", escape_key_name("no backslash"));
+    }
+
     #[test]
     fn test_get_date_time_from_filetime() {
         assert_eq!(
@@ -357,6 +756,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_filetime_from_date_time() {
+        let filetime = 130679916674978318;
+        assert_eq!(
+            filetime,
+            get_filetime_from_date_time(get_date_time_from_filetime(filetime))
+        );
+        assert_eq!(
+            116444736000000000,
+            get_filetime_from_date_time(DateTime::<Utc>::from(std::time::UNIX_EPOCH))
+        );
+    }
+
     #[test]
     fn test_format_date_time() {
         assert_eq!(
@@ -391,6 +803,17 @@ mod tests {
         assert_eq!(&vec![expected_warning], logs.get().unwrap());
     }
 
+    #[test]
+    fn test_get_guid_from_string() {
+        let guid = get_guid_from_string("54849625-5478-4994-A5BA-3E3B0328C30D").unwrap();
+        assert_eq!(format!("{}", guid), "54849625-5478-4994-A5BA-3E3B0328C30D");
+        assert_eq!(
+            None,
+            get_guid_from_string("54849625-5478-4994-A5BA-3E3B0328C30")
+        );
+        assert_eq!(None, get_guid_from_string("not a guid"));
+    }
+
     #[test]
     fn test_from_ascii() {
         let mut logs = Logs::default();
@@ -485,6 +908,30 @@ mod tests {
         assert_eq!(expected_strings, strings);
     }
 
+    #[test]
+    fn test_name_from_bytes() {
+        let mut logs = Logs::default();
+        let (name, raw) = name_from_bytes(false, &[0x61, 0, 0, 0, 0x62, 0], &mut logs, "Unit test");
+        assert_eq!("a\0b", name, "Embedded null is kept");
+        assert_eq!(None, raw);
+        assert_eq!(LogCode::WarningName, logs.get().unwrap()[0].code);
+
+        let mut logs = Logs::default();
+        let bytes = [0x61, 0, 0xFF, 0xDB];
+        let (name, raw) = name_from_bytes(false, &bytes, &mut logs, "Unit test");
+        assert_eq!(format!("a{}", std::char::REPLACEMENT_CHARACTER), name);
+        assert_eq!(
+            Some(bytes.to_vec()),
+            raw,
+            "Raw bytes kept for invalid UTF-16"
+        );
+        assert_eq!(LogCode::WarningConversion, logs.get().unwrap()[0].code);
+
+        assert_eq!(vec![0x61, 0, 0x62], name_to_bytes("a\0b", true));
+        assert_eq!(vec![0x61, 0, 0, 0], name_to_bytes("a\0", false));
+        assert_eq!("\"a\\x01,b\"", escape_string("a\u{1},b"));
+    }
+
     #[test]
     fn test_string_from_bytes() {
         let test_str_ascii = "test string";
@@ -568,4 +1015,155 @@ mod tests {
             to_hex_string(&[0, 1, 2, 3, 4, 5, 0xff])
         );
     }
+
+    #[test]
+    fn test_from_hex_string() {
+        assert_eq!(
+            Some(vec![0, 1, 2, 3, 4, 5, 0xff]),
+            from_hex_string("00 01 02 03 04 05 FF")
+        );
+        assert_eq!(Some(Vec::new()), from_hex_string(""));
+        assert_eq!(None, from_hex_string("00 1"));
+        assert_eq!(None, from_hex_string("GG"));
+    }
+
+    #[test]
+    fn test_to_utf16_le_strings() {
+        let mut logs = Logs::default();
+        let bytes = to_utf16_le_string("abc");
+        assert_eq!(vec![0x61, 0, 0x62, 0, 0x63, 0, 0, 0], bytes);
+        assert_eq!(
+            "abc",
+            from_utf16_le_string(&bytes, bytes.len(), &mut logs, "")
+        );
+
+        let strings = vec!["abc".to_string(), "de".to_string()];
+        let bytes = to_utf16_le_strings(&strings);
+        assert_eq!(
+            strings,
+            from_utf16_le_strings(&bytes, bytes.len(), &mut logs, "")
+        );
+        assert_eq!(None, logs.get());
+    }
+
+    #[test]
+    fn test_to_base64() {
+        assert_eq!("", to_base64(b""));
+        assert_eq!("Zg==", to_base64(b"f"));
+        assert_eq!("Zm8=", to_base64(b"fo"));
+        assert_eq!("Zm9v", to_base64(b"foo"));
+        assert_eq!("Zm9vYmFy", to_base64(b"foobar"));
+        assert_eq!("AP8=", to_base64(&[0, 0xff]));
+    }
+
+    #[test]
+    fn test_from_base64() {
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foobar", &[0, 0xff]].iter() {
+            assert_eq!(Some(bytes.to_vec()), from_base64(&to_base64(bytes)));
+        }
+        assert_eq!(Some(b"fo".to_vec()), from_base64("Zm8"));
+        assert_eq!(Some(b"foobar".to_vec()), from_base64("Zm9v\r\nYmFy"));
+        assert_eq!(None, from_base64("Zm9v!"));
+        assert_eq!(None, from_base64("Z"));
+        assert_eq!(None, from_base64("Zm8=="));
+        assert_eq!(None, from_base64("Zg==Zg=="));
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let data: Vec<u8> = (0..18).map(|b| b + 0x40).collect();
+        assert_eq!(
+            vec![
+                "00000000  40 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f  @ABCDEFGHIJKLMNO",
+                "00000010  50 51                                            PQ"
+            ],
+            hex_dump(&data)
+        );
+        assert_eq!(
+            vec!["00000000  41 00 0a 00 e9 00 3d d8                          A.é."],
+            hex_dump_utf16_le(&[0x41, 0, 0x0a, 0, 0xe9, 0, 0x3d, 0xd8])
+        );
+        assert!(hex_dump(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_read_le() {
+        let data = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        assert_eq!(Some(0x0302), read_u16_le(&data, 1));
+        assert_eq!(Some(0x04030201), read_u32_le(&data, 0));
+        assert_eq!(Some(0x0908070605040302), read_u64_le(&data, 1));
+        assert_eq!(None, read_u32_le(&data, 6));
+        assert_eq!(None, read_u16_le(&data, usize::MAX));
+    }
+
+    #[test]
+    fn test_decode_lznt1() {
+        let data = [0x03, 0x30, b'a', b'b', b'c', b'd'];
+        assert_eq!(Ok(b"abcd".to_vec()), decode_lznt1(&data, 0, data.len()));
+        // Malformed input is an error rather than a panic
+        assert!(decode_lznt1(&[0x05], 0, 1).is_err());
+        assert!(decode_lznt1(&data[..4], 0, 4).is_err());
+        // A back reference before the start of the output
+        let data = [0x02, 0xB0, 0x01, 0x00, 0x00];
+        assert!(decode_lznt1(&data, 0, data.len()).is_err());
+    }
+
+    #[test]
+    fn test_read_date_times() {
+        let filetime = 129782011451468083u64.to_le_bytes();
+        assert_eq!(
+            "2012-04-06T15:52:25.1468083Z",
+            format_date_time(read_filetime(&filetime, 0).unwrap())
+        );
+        assert_eq!(None, read_filetime(&[0; 8], 0));
+        assert_eq!(None, read_filetime(&0x7fffffffffffffffu64.to_le_bytes(), 0));
+
+        assert_eq!(
+            "2021-08-06T21:41:53.0000000Z",
+            format_date_time(read_unix_epoch(&1628286113u32.to_le_bytes(), 0).unwrap())
+        );
+        assert_eq!(None, get_date_time_from_unix_epoch(0));
+
+        // 2013-09-03 19:38:46
+        assert_eq!(
+            "2013-09-03T19:38:46.0000000Z",
+            format_date_time(read_dos_date_time(&[0x23, 0x43, 0xd7, 0x9c], 0).unwrap())
+        );
+        assert_eq!(None, read_dos_date_time(&[0; 4], 0));
+
+        let systemtime = [
+            0xe5, 0x07, 0x08, 0x00, 0x05, 0x00, 0x06, 0x00, 0x0e, 0x00, 0x29, 0x00, 0x35, 0x00,
+            0x7b, 0x00,
+        ];
+        assert_eq!(
+            "2021-08-06T14:41:53.123",
+            read_systemtime(&systemtime, 0)
+                .unwrap()
+                .format("%Y-%m-%dT%H:%M:%S%.3f")
+                .to_string()
+        );
+        assert_eq!(None, read_systemtime(&systemtime[..8], 0));
+        assert_eq!(None, read_systemtime(&systemtime, usize::MAX));
+        assert_eq!(None, read_systemtime(&[0; 16], 0));
+    }
+
+    #[test]
+    fn test_read_strings() {
+        let raw_guid: &[u8] = &[
+            0xff, 0x25, 0x96, 0x84, 0x54, 0x78, 0x54, 0x94, 0x49, 0xa5, 0xba, 0x3e, 0x3b, 0x3,
+            0x28, 0xc3, 0xd,
+        ];
+        assert_eq!(
+            Some("54849625-5478-4994-A5BA-3E3B0328C30D".to_string()),
+            read_guid_string(raw_guid, 1)
+        );
+        assert_eq!(None, read_guid_string(raw_guid, 2));
+
+        let utf16 = [0x41, 0, 0x42, 0, 0, 0, 0x43, 0];
+        assert_eq!(Some("AB".to_string()), read_utf16_le_string(&utf16, 0));
+        assert_eq!(Some("C".to_string()), read_utf16_le_string(&utf16, 6));
+        assert_eq!(None, read_utf16_le_string(&utf16, 9));
+
+        assert_eq!("HRZR_PGYFRFFVBA", decode_rot13("UEME_CTLSESSION"));
+    }
 }
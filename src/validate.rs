@@ -0,0 +1,843 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Structural validation of a hive's hive bins and the cells reachable from its root key.
+//!
+//! Validation reads the raw cells rather than the parsed keys and values, so it can report anomalies that parsing
+//! tolerates (or that are deliberately crafted to hide data) along with the offsets where they were found.
+
+use crate::cell_key_node::{CellKeyNode, KeyNodeFlags};
+use crate::cell_key_value::CellKeyValue;
+use crate::hive_bin_header::{HiveBinCell, HiveBinHeader};
+use crate::util::{self, NO_OFFSET};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub enum AnomalyType {
+    /// A hive bin header is missing or inconsistent with its position
+    HiveBin,
+    /// The cells of a hive bin don't add up to the hive bin's size
+    CellLayout,
+    /// A cell's size is unaligned, too small, or extends past its hive bin
+    CellSize,
+    /// An offset points outside of the hive bins
+    OutOfBounds,
+    /// A referenced cell is marked as free
+    FreeCellReferenced,
+    /// A cell doesn't have the signature expected for how it's referenced
+    Signature,
+    /// A key's parent offset doesn't point to the key that references it
+    Parent,
+    /// A key's sub key count doesn't match its sub key list
+    SubKeyCount,
+    /// A key's value count doesn't match its value list
+    ValueCount,
+    /// A value's data (or a class name) is larger than the cell holding it
+    DataSize,
+    /// A security cell's reference count doesn't match the number of keys using it
+    SecurityReferenceCount,
+    /// The linked list of security cells is broken
+    SecurityList,
+    /// A key is reachable more than once (which would make it its own ancestor or shared between parents)
+    KeyReferencedMultipleTimes,
+    /// An allocated cell isn't referenced by anything reachable from the root key
+    UnreferencedCell,
+    /// A referenced cell starts inside another allocated cell (see `ParserBuilderFromPath::check_overlapping_cells`)
+    OverlappingCell,
+}
+
+/// An anomaly found by `Parser::validate`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Anomaly {
+    pub anomaly_type: AnomalyType,
+    /// Absolute file offset of the hive bin or cell
+    pub offset: usize,
+    /// Path of the key being checked, if any
+    pub key_path: String,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ValidationReport {
+    pub hive_bins_checked: usize,
+    pub keys_checked: usize,
+    pub values_checked: usize,
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.anomalies.is_empty()
+    }
+}
+
+pub(crate) struct Validator<'a> {
+    /// The hive bins (the file starting at the first hive bin, truncated to the size in the base block)
+    hive_bins: &'a [u8],
+    hbin_offset_absolute: usize,
+    minor_version: u32,
+    /// (start, end) of each hive bin, relative to the first
+    hive_bin_ranges: Vec<(usize, usize)>,
+    allocated_cells: Vec<usize>,
+    referenced_cells: HashSet<usize>,
+    /// offset -> (end, description) of the allocated cells in the hive bins and of the cells referenced as something
+    cell_extents: BTreeMap<usize, (usize, &'static str)>,
+    security_users: HashMap<u32, u32>,
+    report: ValidationReport,
+}
+
+impl<'a> Validator<'a> {
+    pub(crate) fn new(
+        hive_bins: &'a [u8],
+        hbin_offset_absolute: usize,
+        minor_version: u32,
+    ) -> Self {
+        Self {
+            hive_bins,
+            hbin_offset_absolute,
+            minor_version,
+            hive_bin_ranges: Vec::new(),
+            allocated_cells: Vec::new(),
+            referenced_cells: HashSet::new(),
+            cell_extents: BTreeMap::new(),
+            security_users: HashMap::new(),
+            report: ValidationReport::default(),
+        }
+    }
+
+    pub(crate) fn validate(mut self, root_offset: u32) -> ValidationReport {
+        self.check_hive_bins();
+        self.check_keys(root_offset);
+        self.check_security();
+        self.check_overlapping_cells();
+        for offset in std::mem::take(&mut self.allocated_cells) {
+            if !self.referenced_cells.contains(&offset) {
+                let size = self.cell_size(offset).unwrap_or_default().unsigned_abs();
+                self.add(
+                    AnomalyType::UnreferencedCell,
+                    offset,
+                    "",
+                    format!("{} byte cell", size),
+                );
+            }
+        }
+        self.report.anomalies.sort_by_key(|anomaly| anomaly.offset);
+        self.report
+    }
+
+    fn add(&mut self, anomaly_type: AnomalyType, offset: usize, key_path: &str, detail: String) {
+        self.report.anomalies.push(Anomaly {
+            anomaly_type,
+            offset: offset + self.hbin_offset_absolute,
+            key_path: key_path.to_string(),
+            detail,
+        });
+    }
+
+    fn cell_size(&self, offset: usize) -> Option<i32> {
+        util::read_u32_le(self.hive_bins, offset).map(|size| size as i32)
+    }
+
+    /// Walks each hive bin and its cells, recording the allocated cells
+    fn check_hive_bins(&mut self) {
+        let mut offset = 0;
+        while offset < self.hive_bins.len() {
+            if self.hive_bins.get(offset..offset + 4) != Some(b"hbin") {
+                self.add(
+                    AnomalyType::HiveBin,
+                    offset,
+                    "",
+                    "Missing hbin signature; skipping the rest of the hive bins".to_string(),
+                );
+                break;
+            }
+            let stated_offset = util::read_u32_le(self.hive_bins, offset + 4).unwrap_or_default();
+            if stated_offset as usize != offset {
+                self.add(
+                    AnomalyType::HiveBin,
+                    offset,
+                    "",
+                    format!("Hive bin header has offset {:#x}", stated_offset),
+                );
+            }
+            let size = util::read_u32_le(self.hive_bins, offset + 8).unwrap_or_default() as usize;
+            if size == 0
+                || size & (HiveBinHeader::ALIGNMENT - 1) != 0
+                || offset + size > self.hive_bins.len()
+            {
+                self.add(
+                    AnomalyType::HiveBin,
+                    offset,
+                    "",
+                    format!(
+                        "Invalid hive bin size {:#x}; skipping the rest of the hive bins",
+                        size
+                    ),
+                );
+                break;
+            }
+            self.report.hive_bins_checked += 1;
+            self.hive_bin_ranges.push((offset, offset + size));
+            self.check_cell_layout(offset + HiveBinHeader::SIZE, offset + size);
+            offset += size;
+        }
+    }
+
+    fn check_cell_layout(&mut self, mut offset: usize, hive_bin_end: usize) {
+        while offset < hive_bin_end {
+            let size = match self.cell_size(offset) {
+                Some(size) => size,
+                None => break,
+            };
+            let size_abs = size.unsigned_abs() as usize;
+            if size_abs < HiveBinCell::ALIGNMENT
+                || size_abs & (HiveBinCell::ALIGNMENT - 1) != 0
+                || offset + size_abs > hive_bin_end
+            {
+                self.add(
+                    AnomalyType::CellLayout,
+                    offset,
+                    "",
+                    format!(
+                        "Invalid cell size {}; skipping the rest of the hive bin ({} bytes)",
+                        size,
+                        hive_bin_end - offset
+                    ),
+                );
+                return;
+            }
+            if size < 0 {
+                self.allocated_cells.push(offset);
+                self.cell_extents
+                    .insert(offset, (offset + size_abs, "Allocated"));
+            }
+            offset += size_abs;
+        }
+    }
+
+    /// Returns the data (following the size) of the allocated cell at `offset` if it is valid; adds an anomaly otherwise.
+    /// `signature` is checked if it isn't empty.
+    fn cell(
+        &mut self,
+        offset: u32,
+        key_path: &str,
+        description: &'static str,
+        signature: &[u8],
+    ) -> Option<&'a [u8]> {
+        let hive_bins = self.hive_bins;
+        let offset_usize = offset as usize;
+        let hive_bin_end = match self
+            .hive_bin_ranges
+            .iter()
+            .find(|(start, end)| (*start..*end).contains(&offset_usize))
+        {
+            Some((start, end)) if offset_usize >= start + HiveBinHeader::SIZE => *end,
+            _ => {
+                self.add(
+                    AnomalyType::OutOfBounds,
+                    offset_usize,
+                    key_path,
+                    format!("{} offset {:#x} isn't in a hive bin", description, offset),
+                );
+                return None;
+            }
+        };
+        self.referenced_cells.insert(offset_usize);
+        let size = self.cell_size(offset_usize)?;
+        let size_abs = size.unsigned_abs() as usize;
+        if size_abs < HiveBinCell::ALIGNMENT || offset_usize + size_abs > hive_bin_end {
+            self.add(
+                AnomalyType::CellSize,
+                offset_usize,
+                key_path,
+                format!("{} cell has invalid size {}", description, size),
+            );
+            return None;
+        }
+        if size > 0 {
+            self.add(
+                AnomalyType::FreeCellReferenced,
+                offset_usize,
+                key_path,
+                format!("{} cell is free", description),
+            );
+            return None;
+        }
+        self.cell_extents
+            .insert(offset_usize, (offset_usize + size_abs, description));
+        let data = &hive_bins[offset_usize + 4..offset_usize + size_abs];
+        if !data.starts_with(signature) {
+            self.add(
+                AnomalyType::Signature,
+                offset_usize,
+                key_path,
+                format!(
+                    "{} cell has signature {:?}; expected {:?}",
+                    description,
+                    String::from_utf8_lossy(data.get(..2).unwrap_or_default()),
+                    String::from_utf8_lossy(signature)
+                ),
+            );
+            return None;
+        }
+        Some(data)
+    }
+
+    /// Walks the keys reachable from the root key
+    fn check_keys(&mut self, root_offset: u32) {
+        let mut visited = HashSet::new();
+        // (offset, expected parent offset, parent path)
+        let mut stack = vec![(root_offset, None, String::new())];
+        while let Some((offset, parent_offset, parent_path)) = stack.pop() {
+            if !visited.insert(offset) {
+                self.add(
+                    AnomalyType::KeyReferencedMultipleTimes,
+                    offset as usize,
+                    &parent_path,
+                    "Key was already visited".to_string(),
+                );
+                continue;
+            }
+            let nk = match self.cell(offset, &parent_path, "Key", b"nk") {
+                Some(nk) => nk,
+                None => continue,
+            };
+            if nk.len() < CellKeyNode::HEADER_SIZE {
+                self.add(
+                    AnomalyType::CellSize,
+                    offset as usize,
+                    &parent_path,
+                    format!("Key cell is too small ({} bytes)", nk.len()),
+                );
+                continue;
+            }
+            self.report.keys_checked += 1;
+            let read_u32 = |field_offset| util::read_u32_le(nk, field_offset).unwrap_or_default();
+            let flags = util::read_u16_le(nk, CellKeyNode::FLAGS_FIELD).unwrap_or_default();
+            let name_len = util::read_u16_le(nk, CellKeyNode::KEY_NAME_SIZE_FIELD)
+                .unwrap_or_default() as usize;
+            let name_bytes = nk
+                .get(CellKeyNode::HEADER_SIZE..CellKeyNode::HEADER_SIZE + name_len)
+                .unwrap_or_default();
+            let name = if flags & KeyNodeFlags::KEY_COMP_NAME.bits() != 0 {
+                String::from_utf8_lossy(name_bytes).into_owned()
+            } else {
+                let units: Vec<u16> = name_bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            };
+            let path = format!("{}\\{}", parent_path, name);
+            if name_bytes.len() != name_len {
+                self.add(
+                    AnomalyType::DataSize,
+                    offset as usize,
+                    &path,
+                    format!("Key name length {} exceeds the cell", name_len),
+                );
+            }
+
+            if let Some(parent_offset) = parent_offset {
+                let key_parent_offset = read_u32(CellKeyNode::PARENT_KEY_OFFSET_FIELD);
+                if key_parent_offset != parent_offset {
+                    self.add(
+                        AnomalyType::Parent,
+                        offset as usize,
+                        &path,
+                        format!(
+                            "Parent offset is {:#x}; expected {:#x}",
+                            key_parent_offset, parent_offset
+                        ),
+                    );
+                }
+            }
+
+            let sub_key_count = read_u32(CellKeyNode::NUMBER_OF_SUB_KEYS_FIELD);
+            let sub_keys = match read_u32(CellKeyNode::SUB_KEYS_LIST_OFFSET_FIELD) {
+                NO_OFFSET => Vec::new(),
+                list_offset => self.read_sub_key_list(list_offset, &path, true),
+            };
+            if sub_keys.len() != sub_key_count as usize {
+                self.add(
+                    AnomalyType::SubKeyCount,
+                    offset as usize,
+                    &path,
+                    format!(
+                        "Sub key count is {}; the sub key list has {}",
+                        sub_key_count,
+                        sub_keys.len()
+                    ),
+                );
+            }
+            for sub_key in sub_keys.into_iter().rev() {
+                stack.push((sub_key, Some(offset), path.clone()));
+            }
+
+            self.check_values(
+                offset,
+                read_u32(CellKeyNode::NUMBER_OF_KEY_VALUES_FIELD),
+                read_u32(CellKeyNode::KEY_VALUES_LIST_OFFSET_FIELD),
+                &path,
+            );
+
+            let security_offset = read_u32(CellKeyNode::SECURITY_KEY_OFFSET_FIELD);
+            *self.security_users.entry(security_offset).or_default() += 1;
+
+            let class_name_offset = read_u32(CellKeyNode::CLASS_NAME_OFFSET_FIELD);
+            if class_name_offset != NO_OFFSET {
+                let class_name_len = util::read_u16_le(nk, CellKeyNode::CLASS_NAME_SIZE_FIELD)
+                    .unwrap_or_default() as usize;
+                if let Some(class_name) = self.cell(class_name_offset, &path, "Class name", b"") {
+                    if class_name.len() < class_name_len {
+                        self.add(
+                            AnomalyType::DataSize,
+                            class_name_offset as usize,
+                            &path,
+                            format!(
+                                "Class name length {} exceeds the cell ({} bytes)",
+                                class_name_len,
+                                class_name.len()
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the key offsets in the sub key list at `offset`. Index roots (ri) are expanded if `allow_index_root` is true.
+    fn read_sub_key_list(
+        &mut self,
+        offset: u32,
+        key_path: &str,
+        allow_index_root: bool,
+    ) -> Vec<u32> {
+        let list = match self.cell(offset, key_path, "Sub key list", b"") {
+            Some(list) => list,
+            None => return Vec::new(),
+        };
+        let signature = list.get(..2).unwrap_or_default();
+        let count = util::read_u16_le(list, 2).unwrap_or_default() as usize;
+        let entry_size = match signature {
+            b"lf" | b"lh" => 8,
+            b"li" => 4,
+            b"ri" if allow_index_root => 4,
+            _ => {
+                self.add(
+                    AnomalyType::Signature,
+                    offset as usize,
+                    key_path,
+                    format!(
+                        "Sub key list has signature {:?}",
+                        String::from_utf8_lossy(signature)
+                    ),
+                );
+                return Vec::new();
+            }
+        };
+        if 4 + count * entry_size > list.len() {
+            self.add(
+                AnomalyType::DataSize,
+                offset as usize,
+                key_path,
+                format!(
+                    "Sub key list of {} entries exceeds the cell ({} bytes)",
+                    count,
+                    list.len()
+                ),
+            );
+        }
+        let entries: Vec<u32> = (0..count)
+            .map_while(|index| util::read_u32_le(list, 4 + index * entry_size))
+            .collect();
+        if signature == b"ri" {
+            entries
+                .into_iter()
+                .flat_map(|entry| self.read_sub_key_list(entry, key_path, false))
+                .collect()
+        } else {
+            entries
+        }
+    }
+
+    fn check_values(
+        &mut self,
+        key_offset: u32,
+        value_count: u32,
+        list_offset: u32,
+        key_path: &str,
+    ) {
+        if value_count == 0 {
+            return;
+        }
+        let list = match self.cell(list_offset, key_path, "Value list", b"") {
+            Some(list) => list,
+            None => return,
+        };
+        if value_count as usize * 4 > list.len() {
+            self.add(
+                AnomalyType::ValueCount,
+                key_offset as usize,
+                key_path,
+                format!(
+                    "Value count is {}; the value list only has room for {}",
+                    value_count,
+                    list.len() / 4
+                ),
+            );
+        }
+        let values: Vec<u32> = (0..value_count as usize)
+            .map_while(|index| util::read_u32_le(list, index * 4))
+            .collect();
+        for value_offset in values {
+            self.check_value(value_offset, key_path);
+        }
+    }
+
+    fn check_value(&mut self, offset: u32, key_path: &str) {
+        let vk = match self.cell(offset, key_path, "Value", b"vk") {
+            Some(vk) => vk,
+            None => return,
+        };
+        self.report.values_checked += 1;
+        const VK_HEADER_SIZE: usize = 20;
+        let name_len = util::read_u16_le(vk, 2).unwrap_or_default() as usize;
+        if vk.len() < VK_HEADER_SIZE + name_len {
+            self.add(
+                AnomalyType::DataSize,
+                offset as usize,
+                key_path,
+                format!(
+                    "Value name length {} exceeds the cell ({} bytes)",
+                    name_len,
+                    vk.len()
+                ),
+            );
+            return;
+        }
+        let data_size = util::read_u32_le(vk, 4).unwrap_or_default();
+        let data_offset = util::read_u32_le(vk, 8).unwrap_or_default();
+        if data_size & CellKeyValue::DATA_IS_RESIDENT_MASK != 0 || data_size == 0 {
+            return;
+        }
+        let data = match self.cell(data_offset, key_path, "Value data", b"") {
+            Some(data) => data,
+            None => return,
+        };
+        if data_size > CellKeyValue::BIG_DATA_SIZE_THRESHOLD
+            && self.minor_version >= 4
+            && data.starts_with(b"db")
+        {
+            self.check_big_data(data_offset, data, data_size, key_path);
+        } else if (data.len() as u32) < data_size {
+            self.add(
+                AnomalyType::DataSize,
+                data_offset as usize,
+                key_path,
+                format!(
+                    "Value data size {} exceeds the cell ({} bytes)",
+                    data_size,
+                    data.len()
+                ),
+            );
+        }
+    }
+
+    fn check_big_data(&mut self, offset: u32, db: &[u8], data_size: u32, key_path: &str) {
+        let segment_count = util::read_u16_le(db, 2).unwrap_or_default() as usize;
+        let segments_offset = util::read_u32_le(db, 4).unwrap_or(NO_OFFSET);
+        let segments = match self.cell(segments_offset, key_path, "Big data segment list", b"") {
+            Some(segments) => segments,
+            None => return,
+        };
+        let mut total = 0;
+        for index in 0..segment_count {
+            let segment_offset = match util::read_u32_le(segments, index * 4) {
+                Some(segment_offset) => segment_offset,
+                None => {
+                    self.add(
+                        AnomalyType::DataSize,
+                        segments_offset as usize,
+                        key_path,
+                        format!(
+                            "Big data segment list of {} entries exceeds the cell ({} bytes)",
+                            segment_count,
+                            segments.len()
+                        ),
+                    );
+                    return;
+                }
+            };
+            if let Some(segment) = self.cell(segment_offset, key_path, "Big data segment", b"") {
+                total += segment.len();
+            }
+        }
+        if total < data_size as usize {
+            self.add(
+                AnomalyType::DataSize,
+                offset as usize,
+                key_path,
+                format!(
+                    "Value data size {} exceeds its big data segments ({} bytes)",
+                    data_size, total
+                ),
+            );
+        }
+    }
+
+    /// Reports the cells that start inside an earlier cell, along with the earlier cell that extends the furthest
+    fn check_overlapping_cells(&mut self) {
+        // (offset, end, description)
+        let mut furthest: Option<(usize, usize, &str)> = None;
+        for (offset, (end, description)) in std::mem::take(&mut self.cell_extents) {
+            if let Some((other_offset, other_end, other_description)) = furthest {
+                if offset < other_end {
+                    self.add(
+                        AnomalyType::OverlappingCell,
+                        offset,
+                        "",
+                        format!(
+                            "{} cell ({} bytes) overlaps the {} cell at offset {} ({} bytes)",
+                            description,
+                            end - offset,
+                            other_description.to_lowercase(),
+                            other_offset + self.hbin_offset_absolute,
+                            other_end - other_offset
+                        ),
+                    );
+                }
+                if end <= other_end {
+                    continue;
+                }
+            }
+            furthest = Some((offset, end, description));
+        }
+    }
+
+    /// Checks the security cells used by the keys (and those linked to them): their reference counts and the
+    /// doubly linked list joining them
+    fn check_security(&mut self) {
+        let mut to_visit: Vec<u32> = self.security_users.keys().copied().collect();
+        to_visit.sort_unstable_by(|a, b| b.cmp(a));
+        let mut visited = HashSet::new();
+        // offset -> (flink, blink)
+        let mut links = HashMap::new();
+        while let Some(offset) = to_visit.pop() {
+            if !visited.insert(offset) {
+                continue;
+            }
+            let sk = match self.cell(offset, "", "Security", b"sk") {
+                Some(sk) => sk,
+                None => continue,
+            };
+            let read_u32 = |field_offset| util::read_u32_le(sk, field_offset).unwrap_or(NO_OFFSET);
+            let (flink, blink, reference_count) = (read_u32(4), read_u32(8), read_u32(12));
+            let users = self
+                .security_users
+                .get(&offset)
+                .copied()
+                .unwrap_or_default();
+            if reference_count != users {
+                self.add(
+                    AnomalyType::SecurityReferenceCount,
+                    offset as usize,
+                    "",
+                    format!(
+                        "Reference count is {}; used by {} keys",
+                        reference_count, users
+                    ),
+                );
+            }
+            links.insert(offset, (flink, blink));
+            to_visit.push(blink);
+            to_visit.push(flink);
+        }
+
+        let mut links: Vec<(u32, (u32, u32))> = links.into_iter().collect();
+        links.sort_unstable();
+        let back_links: HashMap<u32, (u32, u32)> = links.iter().copied().collect();
+        for (offset, (flink, blink)) in links {
+            // Links to invalid cells were already reported by `cell()`
+            if matches!(back_links.get(&flink), Some((_, flink_blink)) if *flink_blink != offset) {
+                self.add(
+                    AnomalyType::SecurityList,
+                    offset as usize,
+                    "",
+                    format!(
+                        "The blink of the next security cell ({:#x}) doesn't point back",
+                        flink
+                    ),
+                );
+            }
+            if matches!(back_links.get(&blink), Some((blink_flink, _)) if *blink_flink != offset) {
+                self.add(
+                    AnomalyType::SecurityList,
+                    offset as usize,
+                    "",
+                    format!(
+                        "The flink of the previous security cell ({:#x}) doesn't point back",
+                        blink
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::parser::Parser;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use std::io::Cursor;
+
+    fn parse(hive: Vec<u8>) -> Parser {
+        ParserBuilder::from_file(Cursor::new(hive)).build().unwrap()
+    }
+
+    fn anomaly_types(report: &ValidationReport) -> Vec<AnomalyType> {
+        report
+            .anomalies
+            .iter()
+            .map(|anomaly| anomaly.anomaly_type)
+            .collect()
+    }
+
+    #[test]
+    fn test_validate() {
+        let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let report = parser.validate();
+        assert!(report.is_valid(), "{:?}", report.anomalies);
+        assert_eq!(
+            (244, 2853, 5523),
+            (
+                report.hive_bins_checked,
+                report.keys_checked,
+                report.values_checked
+            )
+        );
+    }
+
+    #[test]
+    fn test_validate_written_hive() {
+        let mut root = WriterKey::new("ROOT");
+        for i in 0..1100 {
+            root.get_or_add_sub_key(&format!("Many\\{}", i));
+        }
+        root.get_or_add_sub_key("Big").set_value(WriterValue::new(
+            "Data",
+            CellKeyValueDataTypes::REG_BIN,
+            vec![0x5a; 40000],
+        ));
+        let report = parse(HiveWriter::new(root).to_bytes().unwrap()).validate();
+        assert!(report.is_valid(), "{:?}", report.anomalies);
+        assert_eq!(1103, report.keys_checked);
+    }
+
+    #[test]
+    fn test_validate_corrupted() {
+        let mut hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let key = parse(hive.clone())
+            .get_key("Control Panel", false)
+            .unwrap()
+            .unwrap();
+        let nk = key.file_offset_absolute + 4;
+        let patch = |hive: &mut Vec<u8>, offset: usize, value: u32| {
+            hive[offset..offset + 4].copy_from_slice(&value.to_le_bytes())
+        };
+        patch(&mut hive, nk + 16, 0x1230); // parent offset
+        patch(&mut hive, nk + 20, key.detail.number_of_sub_keys() + 1);
+        let security = 4096 + key.detail.security_key_offset_relative() as usize + 4;
+        let reference_count = util::read_u32_le(&hive, security + 12).unwrap();
+        patch(&mut hive, security + 12, reference_count + 5);
+
+        let report = parse(hive).validate();
+        assert_eq!(
+            vec![
+                AnomalyType::Parent,
+                AnomalyType::SubKeyCount,
+                AnomalyType::SecurityReferenceCount
+            ],
+            anomaly_types(&report)
+        );
+        let parent = &report.anomalies[0];
+        assert_eq!(key.file_offset_absolute, parent.offset);
+        assert!(parent.key_path.ends_with("\\Control Panel"));
+    }
+
+    #[test]
+    fn test_validate_hidden_cell() {
+        // Allocating a free cell without referencing it is a way to hide data in a hive
+        let mut hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let mut offset = 4096 + 32;
+        let free_cell = loop {
+            if hive[offset..offset + 4] == *b"hbin" {
+                offset += 32;
+            }
+            let size = util::read_u32_le(&hive, offset).unwrap() as i32;
+            if size > 0 {
+                break offset;
+            }
+            offset += size.unsigned_abs() as usize;
+        };
+        let size = util::read_u32_le(&hive, free_cell).unwrap() as i32;
+        hive[free_cell..free_cell + 4].copy_from_slice(&(-size).to_le_bytes());
+        let report = parse(hive.clone()).validate();
+        assert_eq!(vec![AnomalyType::UnreferencedCell], anomaly_types(&report));
+        assert_eq!(free_cell, report.anomalies[0].offset);
+
+        // Unreferenced cells are common in healthy hives, so they don't fail strict mode
+        let mut builder = ParserBuilder::from_file(Cursor::new(hive));
+        builder.strict(true);
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn test_validate_overlapping_cell() {
+        // Grow "Control Panel" over the cell of the key that follows it, "Console"
+        let mut hive = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        let mut parser = parse(hive.clone());
+        let control_panel = parser.get_key("Control Panel", false).unwrap().unwrap();
+        let console = parser.get_key("Console", false).unwrap().unwrap();
+        let offset = control_panel.file_offset_absolute;
+        assert_eq!(
+            offset + control_panel.get_cell_size(),
+            console.file_offset_absolute
+        );
+        let size = control_panel.detail.size() + console.detail.size();
+        hive[offset..offset + 4].copy_from_slice(&size.to_le_bytes());
+
+        let report = parse(hive).validate();
+        assert_eq!(vec![AnomalyType::OverlappingCell], anomaly_types(&report));
+        let overlap = &report.anomalies[0];
+        assert_eq!(console.file_offset_absolute, overlap.offset);
+        assert_eq!(
+            format!(
+                "Key cell ({} bytes) overlaps the key cell at offset {} ({} bytes)",
+                console.get_cell_size(),
+                offset,
+                size.unsigned_abs()
+            ),
+            overlap.detail
+        );
+    }
+}
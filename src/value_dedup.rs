@@ -0,0 +1,113 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Duplicate value data statistics (see `Parser::value_dedup`). The same blob stored under several values can be an
+//! interesting artifact (ex: one payload planted in several keys), and the totals show how much smaller an export that
+//! stores each distinct blob once would be.
+
+use crate::cell_key_value::CellKeyValue;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Where a value is: the path of its key and its name
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ValueLocation {
+    pub key_path: String,
+    pub value_name: String,
+}
+
+/// Data held by more than one value
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct DuplicateData {
+    /// BLAKE3 hash of the data, in hex
+    pub hash: String,
+    /// Size of the data, in bytes
+    pub size: usize,
+    /// The values holding the data, in the order they were found
+    pub values: Vec<ValueLocation>,
+}
+
+impl DuplicateData {
+    /// Bytes that would be saved by storing the data once
+    pub fn redundant_size(&self) -> u64 {
+        self.size as u64 * (self.values.len() as u64 - 1)
+    }
+}
+
+/// Duplicate value data across a hive, as returned by `Parser::value_dedup`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct DedupReport {
+    pub value_count: usize,
+    /// Total size of the values' data, in bytes
+    pub data_size: u64,
+    /// Number of distinct blobs of data (values without data aren't counted)
+    pub unique_data_count: usize,
+    /// Total size of the distinct blobs; the difference from `data_size` is what deduplication would save
+    pub unique_data_size: u64,
+    /// Data of at least the minimum size held by more than one value, the most redundant bytes first
+    pub duplicates: Vec<DuplicateData>,
+}
+
+impl DedupReport {
+    pub(crate) fn new(
+        values: impl Iterator<Item = (Arc<str>, CellKeyValue)>,
+        min_size: usize,
+    ) -> Self {
+        let mut report = Self::default();
+        let mut blobs: HashMap<blake3::Hash, (usize, Vec<ValueLocation>)> = HashMap::new();
+        for (key_path, value) in values {
+            let data = value.get_data();
+            report.value_count += 1;
+            report.data_size += data.len() as u64;
+            if data.is_empty() {
+                continue;
+            }
+            let (size, locations) = blobs
+                .entry(blake3::hash(data))
+                .or_insert_with(|| (data.len(), Vec::new()));
+            if *size >= min_size {
+                locations.push(ValueLocation {
+                    key_path: key_path.to_string(),
+                    value_name: value.get_pretty_name(),
+                });
+            }
+        }
+
+        report.unique_data_count = blobs.len();
+        for (hash, (size, values)) in blobs {
+            report.unique_data_size += size as u64;
+            if values.len() > 1 {
+                report.duplicates.push(DuplicateData {
+                    hash: hash.to_hex().to_string(),
+                    size,
+                    values,
+                });
+            }
+        }
+        report.duplicates.sort_by(|a, b| {
+            b.redundant_size()
+                .cmp(&a.redundant_size())
+                .then_with(|| a.hash.cmp(&b.hash))
+        });
+        report
+    }
+
+    /// Bytes an export that stores each distinct blob of data once would save
+    pub fn redundant_size(&self) -> u64 {
+        self.data_size - self.unique_data_size
+    }
+}
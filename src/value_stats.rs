@@ -0,0 +1,185 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Entropy and file type statistics for binary value data (see `ParserBuilder::value_stats`), to help prioritize
+//! values in large exports.
+
+use crate::cell_key_value::CellKeyValueDataTypes;
+use crate::payload;
+use serde::{Deserialize, Serialize};
+
+/// File types identified by the magic bytes at the start of value data
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum MagicType {
+    Zlib,
+    Gzip,
+    Png,
+    Jpeg,
+    Zip,
+    /// OLE compound file (ex: legacy Office documents)
+    Ole,
+    Pe,
+}
+
+impl MagicType {
+    const SIGNATURES: [(&'static [u8], MagicType); 6] = [
+        (&[0x1F, 0x8B], MagicType::Gzip),
+        (
+            &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A],
+            MagicType::Png,
+        ),
+        (&[0xFF, 0xD8, 0xFF], MagicType::Jpeg),
+        (b"PK\x03\x04", MagicType::Zip),
+        (
+            &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1],
+            MagicType::Ole,
+        ),
+        (b"MZ", MagicType::Pe),
+    ];
+
+    /// Returns the type of `data` according to its first bytes
+    pub fn identify(data: &[u8]) -> Option<Self> {
+        if let Some(magic_type) = Self::SIGNATURES
+            .iter()
+            .find(|(signature, _)| data.starts_with(signature))
+            .map(|(_, magic_type)| *magic_type)
+        {
+            return Some(magic_type);
+        }
+        // a deflate CMF byte followed by the FLG byte of one of the four compression levels
+        match data {
+            [0x78, 0x01 | 0x5E | 0x9C | 0xDA, ..] => Some(MagicType::Zlib),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ValueStats {
+    /// Shannon entropy in bits per byte (0 to 8)
+    pub entropy: f64,
+    pub magic_type: Option<MagicType>,
+}
+
+// entropy is never NaN
+impl Eq for ValueStats {}
+
+impl ValueStats {
+    pub fn from_data(data: &[u8]) -> Self {
+        Self {
+            entropy: payload::entropy(data),
+            magic_type: MagicType::identify(data),
+        }
+    }
+
+    /// Returns true for the data types whose content is kept as binary (strings and numbers don't get stats)
+    pub(crate) fn applies_to(data_type: CellKeyValueDataTypes) -> bool {
+        !matches!(
+            data_type,
+            CellKeyValueDataTypes::REG_SZ
+                | CellKeyValueDataTypes::REG_EXPAND_SZ
+                | CellKeyValueDataTypes::REG_LINK
+                | CellKeyValueDataTypes::REG_MULTI_SZ
+        ) && data_type.get_data_type_len().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+
+    #[test]
+    fn test_identify() {
+        assert_eq!(Some(MagicType::Zlib), MagicType::identify(&[0x78, 0x9C, 1]));
+        assert_eq!(Some(MagicType::Zlib), MagicType::identify(&[0x78, 0xDA]));
+        assert_eq!(None, MagicType::identify(&[0x78, 0x9D]));
+        assert_eq!(Some(MagicType::Gzip), MagicType::identify(&[0x1F, 0x8B, 8]));
+        assert_eq!(
+            Some(MagicType::Png),
+            MagicType::identify(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")
+        );
+        assert_eq!(
+            Some(MagicType::Ole),
+            MagicType::identify(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1, 0])
+        );
+        assert_eq!(Some(MagicType::Pe), MagicType::identify(b"MZ\x90\0"));
+        assert_eq!(None, MagicType::identify(b"M"));
+        assert_eq!(None, MagicType::identify(&[]));
+    }
+
+    #[test]
+    fn test_value_stats() {
+        use crate::cell_key_value::CellKeyValue;
+        use crate::writer::{HiveWriter, WriterKey, WriterValue};
+
+        let mut root = WriterKey::new("ROOT");
+        root.set_value(WriterValue::new(
+            "Image",
+            CellKeyValueDataTypes::REG_BIN,
+            b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec(),
+        ));
+        root.set_value(WriterValue::new(
+            "Zeros",
+            CellKeyValueDataTypes::REG_NONE,
+            vec![0; 32],
+        ));
+        root.set_value(WriterValue::new(
+            "Text",
+            CellKeyValueDataTypes::REG_SZ,
+            crate::util::to_utf16_le_string("MZ"),
+        ));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        let mut builder = ParserBuilder::from_file(std::io::Cursor::new(hive));
+        builder.value_stats(true);
+        let mut parser = builder.build().unwrap();
+        let root = parser.get_root_key().unwrap().unwrap();
+
+        let image = root.get_value("Image").unwrap();
+        let stats = image.value_stats.unwrap();
+        assert_eq!(Some(MagicType::Png), stats.magic_type);
+        assert!((stats.entropy - 3.453).abs() < 0.001);
+        assert_eq!(
+            Some(ValueStats {
+                entropy: 0.0,
+                magic_type: None
+            }),
+            root.get_value("Zeros").unwrap().value_stats
+        );
+        assert_eq!(None, root.get_value("Text").unwrap().value_stats);
+
+        let json = serde_json::to_value(&image).unwrap();
+        assert_eq!("Png", json["value_stats"]["magic_type"]);
+        let deserialized: CellKeyValue = serde_json::from_value(json).unwrap();
+        assert_eq!(image.value_stats, deserialized.value_stats);
+        let json = serde_json::to_value(root.get_value("Text").unwrap()).unwrap();
+        assert!(json.get("value_stats").is_none());
+
+        let count_values_with_stats = |value_stats: bool| {
+            let parser = ParserBuilder::from_path("test_data/NTUSER.DAT")
+                .value_stats(value_stats)
+                .build()
+                .unwrap();
+            ParserIterator::new(&parser)
+                .values()
+                .filter(|(_, value)| value.value_stats.is_some())
+                .count()
+        };
+        assert_eq!(1244, count_values_with_stats(true));
+        assert_eq!(0, count_values_with_stats(false));
+    }
+}
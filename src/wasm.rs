@@ -0,0 +1,208 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! JavaScript bindings, enabled with the `wasm` feature.
+//!
+//! Hives are passed in as bytes (ex: `new Uint8Array(await file.arrayBuffer())`), so no file I/O is needed.
+//! Keys and values are returned as JSON strings using the same serialization as `reg_dump`'s JSONL output.
+
+use crate::cell_key_node::CellKeyNode;
+use crate::cell_key_value::CellKeyValue;
+use crate::err::Error;
+use crate::parser::{Parser, ParserIterator};
+use crate::parser_builder::ParserBuilder;
+use std::io::Cursor;
+use wasm_bindgen::prelude::*;
+
+/// Builds a `NotatinParser` from the bytes of a hive and its transaction logs
+#[wasm_bindgen(js_name = NotatinParserBuilder)]
+#[derive(Default)]
+pub struct WasmParserBuilder {
+    primary: Vec<u8>,
+    transaction_logs: Vec<Vec<u8>>,
+    recover_deleted: bool,
+}
+
+#[wasm_bindgen(js_class = NotatinParserBuilder)]
+impl WasmParserBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(primary: Vec<u8>) -> Self {
+        Self {
+            primary,
+            ..Default::default()
+        }
+    }
+
+    #[wasm_bindgen(js_name = withTransactionLog)]
+    pub fn with_transaction_log(mut self, log: Vec<u8>) -> Self {
+        self.transaction_logs.push(log);
+        self
+    }
+
+    #[wasm_bindgen(js_name = recoverDeleted)]
+    pub fn recover_deleted(mut self, recover: bool) -> Self {
+        self.recover_deleted = recover;
+        self
+    }
+
+    pub fn build(self) -> Result<WasmParser, JsError> {
+        Ok(WasmParser {
+            inner: self.build_parser()?,
+        })
+    }
+}
+
+impl WasmParserBuilder {
+    fn build_parser(self) -> Result<Parser, Error> {
+        let mut builder = ParserBuilder::from_file(Cursor::new(self.primary));
+        for log in self.transaction_logs {
+            builder.with_transaction_log(Cursor::new(log));
+        }
+        builder.recover_deleted(self.recover_deleted);
+        builder.build()
+    }
+}
+
+/// A parsed hive. Key paths are relative to the root key (ex: `Control Panel\Accessibility`); use "" for the root key.
+#[wasm_bindgen(js_name = NotatinParser)]
+pub struct WasmParser {
+    inner: Parser,
+}
+
+#[wasm_bindgen(js_class = NotatinParser)]
+impl WasmParser {
+    /// Returns a JSON array of the paths of every key in the hive
+    #[wasm_bindgen(js_name = keyPaths)]
+    pub fn key_paths(&self) -> Result<String, JsError> {
+        let paths: Vec<String> = ParserIterator::new(&self.inner)
+            .iter()
+            .map(|key| key.path.to_string())
+            .collect();
+        Ok(serde_json::to_string(&paths)?)
+    }
+
+    /// Returns the key at `path` as JSON (including its values), or undefined if there is no such key
+    pub fn key(&mut self, path: &str) -> Result<Option<String>, JsError> {
+        Ok(self.key_json(path)?)
+    }
+
+    /// Returns a JSON array of the names of the sub keys of the key at `path`, or undefined if there is no such key
+    #[wasm_bindgen(js_name = subKeyNames)]
+    pub fn sub_key_names(&mut self, path: &str) -> Result<Option<String>, JsError> {
+        Ok(self.sub_key_names_json(path)?)
+    }
+
+    /// Returns a JSON array of the values of the key at `path`, or undefined if there is no such key
+    pub fn values(&mut self, path: &str) -> Result<Option<String>, JsError> {
+        Ok(self.values_json(path)?)
+    }
+}
+
+impl WasmParser {
+    fn get_key(&mut self, path: &str) -> Result<Option<CellKeyNode>, Error> {
+        if path.is_empty() {
+            self.inner.get_root_key()
+        } else {
+            self.inner.get_key(path, false)
+        }
+    }
+
+    fn key_json(&mut self, path: &str) -> Result<Option<String>, Error> {
+        self.get_key(path)?.map(|key| to_json(&key)).transpose()
+    }
+
+    fn sub_key_names_json(&mut self, path: &str) -> Result<Option<String>, Error> {
+        match self.get_key(path)? {
+            Some(mut key) => {
+                let names: Vec<String> = key
+                    .read_sub_keys(&mut self.inner)
+                    .into_iter()
+                    .map(|sub_key| sub_key.key_name)
+                    .collect();
+                to_json(&names).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn values_json(&mut self, path: &str) -> Result<Option<String>, Error> {
+        self.get_key(path)?
+            .map(|key| to_json(&key.value_iter().collect::<Vec<CellKeyValue>>()))
+            .transpose()
+    }
+}
+
+fn to_json<T: serde::Serialize + ?Sized>(item: &T) -> Result<String, Error> {
+    serde_json::to_string(item).map_err(|e| Error::Any {
+        detail: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser() -> WasmParser {
+        let primary = std::fs::read("test_data/NTUSER.DAT").unwrap();
+        WasmParser {
+            inner: WasmParserBuilder::new(primary).build_parser().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_key_paths() {
+        let paths: Vec<String> = serde_json::from_str(&parser().key_paths().unwrap()).unwrap();
+        assert_eq!(2853, paths.len());
+    }
+
+    #[test]
+    fn test_key_and_values() {
+        let mut parser = parser();
+        let key: serde_json::Value = serde_json::from_str(
+            &parser
+                .key_json("Control Panel\\Accessibility\\MouseKeys")
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!("MouseKeys", key["key_name"]);
+
+        let values: serde_json::Value = serde_json::from_str(
+            &parser
+                .values_json("Control Panel\\Accessibility\\MouseKeys")
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(3, values.as_array().unwrap().len());
+        assert_eq!(None, parser.values_json("Does not exist").unwrap());
+
+        let names: Vec<String> = serde_json::from_str(
+            &parser
+                .sub_key_names_json("Control Panel\\Accessibility")
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(13, names.len());
+        assert_eq!(
+            10,
+            serde_json::from_str::<Vec<String>>(&parser.sub_key_names_json("").unwrap().unwrap())
+                .unwrap()
+                .len()
+        );
+    }
+}
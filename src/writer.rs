@@ -0,0 +1,883 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Builds new hive files from a tree of keys and values.
+//!
+//! The output is a clean primary file: hive bins contain only the cells for the keys, values, lists, class names,
+//! and security descriptors in the tree (plus a trailing free cell per hive bin), and the base block has matching
+//! sequence numbers and a valid checksum.
+//!
+//! ```no_run
+//! use notatin::cell_key_value::CellKeyValueDataTypes;
+//! use notatin::writer::{HiveWriter, WriterKey, WriterValue};
+//!
+//! let mut root = WriterKey::new("ROOT");
+//! root.get_or_add_sub_key("Software\\Vendor")
+//!     .set_value(WriterValue::new("Version", CellKeyValueDataTypes::REG_DWORD, 3u32.to_le_bytes().to_vec()));
+//! HiveWriter::new(root).write("minimal.dat").unwrap();
+//! ```
+
+use crate::base_block::BaseBlockBase;
+use crate::cell_key_node::{CellKeyNode, KeyNodeFlags};
+use crate::cell_key_value::{CellKeyValue, CellKeyValueDataTypes, CellKeyValueFlags};
+use crate::cell_value::CellValue;
+use crate::err::Error;
+use crate::hive_bin_header::{HiveBinCell, HiveBinHeader};
+use crate::parser::Parser;
+use crate::sub_key_list_lh;
+use crate::util::{self, NO_OFFSET};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::path::Path;
+
+/// A key to be written by `HiveWriter`
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WriterKey {
+    pub name: String,
+    pub last_key_written_date_and_time: DateTime<Utc>,
+    /// Flags to set in addition to those the writer manages (`KEY_HIVE_ENTRY`, `KEY_NO_DELETE`, and `KEY_COMP_NAME`)
+    pub flags: KeyNodeFlags,
+    pub class_name: Option<String>,
+    /// A self-relative security descriptor. Keys without one share their parent's (the root key gets a default descriptor
+    /// granting full control to Administrators and SYSTEM).
+    pub security_descriptor: Option<Vec<u8>>,
+    pub values: Vec<WriterValue>,
+    pub sub_keys: Vec<WriterKey>,
+}
+
+impl WriterKey {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            last_key_written_date_and_time: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    /// Copies `key` and its allocated descendents and values from `parser`, including class names and security descriptors
+    pub fn from_parser_key(parser: &mut Parser, key: &mut CellKeyNode) -> Result<Self, Error> {
+        Self::from_parser_key_internal(parser, key, &mut HashSet::new())
+    }
+
+    /// `ancestors` holds the offsets of the keys being copied above `key`, so a sub key list that refers back to one
+    /// of them is an error rather than endless recursion
+    fn from_parser_key_internal(
+        parser: &mut Parser,
+        key: &mut CellKeyNode,
+        ancestors: &mut HashSet<usize>,
+    ) -> Result<Self, Error> {
+        if !ancestors.insert(key.file_offset_absolute) {
+            return Err(Error::Any {
+                detail: format!(
+                    "{}: key at offset {} is its own ancestor (circular reference)",
+                    key.path, key.file_offset_absolute
+                ),
+            });
+        }
+        let mut sub_keys = Vec::new();
+        for mut sub_key in key.read_sub_keys(parser) {
+            sub_keys.push(Self::from_parser_key_internal(
+                parser,
+                &mut sub_key,
+                ancestors,
+            )?);
+        }
+        ancestors.remove(&key.file_offset_absolute);
+        Ok(Self {
+            sub_keys,
+            ..Self::from_parser_key_without_sub_keys(parser, key)
+        })
+    }
+
+    /// Copies `key` and its allocated values from `parser`, but none of its sub keys
+    pub(crate) fn from_parser_key_without_sub_keys(parser: &Parser, key: &CellKeyNode) -> Self {
+        let mut logs = crate::log::Logs::default();
+        let flags = key.key_node_flags(&mut logs)
+            - (KeyNodeFlags::KEY_HIVE_ENTRY
+                | KeyNodeFlags::KEY_NO_DELETE
+                | KeyNodeFlags::KEY_COMP_NAME);
+        Self {
+            name: key.key_name.clone(),
+            last_key_written_date_and_time: key.last_key_written_date_and_time(),
+            flags,
+            class_name: key.get_class_name(parser),
+            security_descriptor: key.get_security_descriptor_bytes(parser).ok(),
+            values: key
+                .value_iter()
+                .filter(|value| !value.cell_state.is_deleted())
+                .map(|value| WriterValue::from(&value))
+                .collect(),
+            sub_keys: Vec::new(),
+        }
+    }
+
+    /// Returns the sub key at `path` (relative to this key), adding any missing keys along the way
+    pub fn get_or_add_sub_key(&mut self, path: &str) -> &mut Self {
+        let mut key = self;
+        for name in path.split('\\').filter(|name| !name.is_empty()) {
+            let index = match key.sub_key_index(name) {
+                Some(index) => index,
+                None => {
+                    key.sub_keys.push(Self::new(name));
+                    key.sub_keys.len() - 1
+                }
+            };
+            key = &mut key.sub_keys[index];
+        }
+        key
+    }
+
+    /// Returns the sub key at `path` (relative to this key)
+    pub fn get_sub_key(&self, path: &str) -> Option<&Self> {
+        let mut key = self;
+        for name in path.split('\\').filter(|name| !name.is_empty()) {
+            key = &key.sub_keys[key.sub_key_index(name)?];
+        }
+        Some(key)
+    }
+
+    /// Returns the sub key at `path` (relative to this key)
+    pub fn get_sub_key_mut(&mut self, path: &str) -> Option<&mut Self> {
+        let mut key = self;
+        for name in path.split('\\').filter(|name| !name.is_empty()) {
+            let index = key.sub_key_index(name)?;
+            key = &mut key.sub_keys[index];
+        }
+        Some(key)
+    }
+
+    /// Returns the value named `name`
+    pub fn get_value(&self, name: &str) -> Option<&WriterValue> {
+        self.value_index(name).map(|index| &self.values[index])
+    }
+
+    /// Adds `sub_key`, replacing any existing sub key with the same name
+    pub fn add_sub_key(&mut self, sub_key: Self) -> &mut Self {
+        let index = match self.sub_key_index(&sub_key.name) {
+            Some(index) => {
+                self.sub_keys[index] = sub_key;
+                index
+            }
+            None => {
+                self.sub_keys.push(sub_key);
+                self.sub_keys.len() - 1
+            }
+        };
+        &mut self.sub_keys[index]
+    }
+
+    /// Removes the sub key named `name`. Returns the removed key, if there was one.
+    pub fn remove_sub_key(&mut self, name: &str) -> Option<Self> {
+        self.sub_key_index(name)
+            .map(|index| self.sub_keys.remove(index))
+    }
+
+    /// Sets `value`, replacing any existing value with the same name
+    pub fn set_value(&mut self, value: WriterValue) -> &mut Self {
+        match self.value_index(&value.name) {
+            Some(index) => self.values[index] = value,
+            None => self.values.push(value),
+        }
+        self
+    }
+
+    /// Removes the value named `name`. Returns the removed value, if there was one.
+    pub fn remove_value(&mut self, name: &str) -> Option<WriterValue> {
+        self.value_index(name)
+            .map(|index| self.values.remove(index))
+    }
+
+    // Key and value names are case insensitive
+    fn sub_key_index(&self, name: &str) -> Option<usize> {
+        let name = name.to_uppercase();
+        self.sub_keys
+            .iter()
+            .position(|sub_key| sub_key.name.to_uppercase() == name)
+    }
+
+    fn value_index(&self, name: &str) -> Option<usize> {
+        let name = name.to_uppercase();
+        self.values
+            .iter()
+            .position(|value| value.name.to_uppercase() == name)
+    }
+}
+
+/// A value to be written by `HiveWriter`
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WriterValue {
+    /// An empty name is the key's default value
+    pub name: String,
+    /// The raw data type (see `CellKeyValueDataTypes`); kept as a u32 so unrecognized types are copied as-is
+    pub data_type_raw: u32,
+    pub data: Vec<u8>,
+}
+
+impl WriterValue {
+    pub fn new(name: &str, data_type: CellKeyValueDataTypes, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            data_type_raw: data_type as u32,
+            data,
+        }
+    }
+
+    /// Encodes `content` as `data_type` (ex: a `CellValue::String` as a null-terminated UTF-16LE string)
+    pub fn from_content(name: &str, data_type: CellKeyValueDataTypes, content: &CellValue) -> Self {
+        Self::new(
+            name,
+            data_type,
+            data_type
+                .get_value_bytes_from_content(content)
+                .unwrap_or_default(),
+        )
+    }
+}
+
+impl From<&CellKeyValue> for WriterValue {
+    fn from(value: &CellKeyValue) -> Self {
+        Self {
+            name: value.detail.value_name(),
+            data_type_raw: value.detail.data_type_raw(),
+            data: value.get_data().to_vec(),
+        }
+    }
+}
+
+/// Writes a tree of `WriterKey`s as a new primary hive file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HiveWriter {
+    pub root: WriterKey,
+    /// Stored in the base block; Windows uses a partial path to the primary file here
+    pub filename: String,
+    pub last_modification_date_and_time: DateTime<Utc>,
+    /// Written as both the primary and secondary sequence numbers, so the hive doesn't need its transaction logs
+    pub sequence_number: u32,
+}
+
+impl HiveWriter {
+    pub fn new(root: WriterKey) -> Self {
+        Self {
+            root,
+            filename: String::new(),
+            last_modification_date_and_time: Utc::now(),
+            sequence_number: 1,
+        }
+    }
+
+    /// Copies the allocated keys and values of the hive opened by `parser`
+    pub fn from_parser(parser: &mut Parser) -> Result<Self, Error> {
+        let mut root = parser
+            .get_root_key()?
+            .ok_or_else(|| Error::buffer("HiveWriter::from_parser: no root key"))?;
+        Self::from_parser_key(parser, &mut root)
+    }
+
+    /// Copies `key` and its allocated descendents and values; `key` becomes the root key of the new hive.
+    /// The file name and last modification time are taken from the base block of the hive opened by `parser`.
+    pub fn from_parser_key(parser: &mut Parser, key: &mut CellKeyNode) -> Result<Self, Error> {
+        let base_block = &parser
+            .base_block
+            .as_ref()
+            .expect("Shouldn't be here unless we've parsed the base block")
+            .base;
+        let filename = base_block.filename.clone();
+        let last_modification_date_and_time = base_block.last_modification_date_and_time;
+        let sequence_number = base_block.primary_sequence_number;
+        Ok(Self {
+            root: WriterKey::from_parser_key(parser, key)?,
+            filename,
+            last_modification_date_and_time,
+            sequence_number,
+        })
+    }
+
+    /// Writes the hive to `out_path`
+    pub fn write(&self, out_path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(out_path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Returns the contents of the hive file
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let timestamp = util::get_filetime_from_date_time(self.last_modification_date_and_time);
+        let mut cells = CellWriter::new(timestamp);
+        let mut security = SecurityCells::default();
+        // Notatin (like Windows) expects the root key to be the first cell, so it's written first
+        let root_offset = cells.write_key(&mut security, &self.root, NO_OFFSET, NO_OFFSET, true)?;
+        security.write(&mut cells);
+        let hive_bins = cells.finish();
+
+        let mut hive = self.base_block(root_offset, u32::try_from(hive_bins.len())?, timestamp);
+        hive.extend(hive_bins);
+        Ok(hive)
+    }
+
+    fn base_block(&self, root_offset: u32, hive_bins_data_size: u32, timestamp: u64) -> Vec<u8> {
+        const MAJOR_VERSION: u32 = 1;
+        const MINOR_VERSION: u32 = 5;
+        const FILE_TYPE_PRIMARY: u32 = 0;
+        const FILE_FORMAT_DIRECT_MEMORY_LOAD: u32 = 1;
+        const CLUSTERING_FACTOR: u32 = 1;
+        const FILENAME_LEN: usize = 64;
+
+        let mut base_block = Vec::with_capacity(BASE_BLOCK_SIZE);
+        base_block.extend_from_slice(b"regf");
+        base_block.extend_from_slice(&self.sequence_number.to_le_bytes());
+        base_block.extend_from_slice(&self.sequence_number.to_le_bytes());
+        base_block.extend_from_slice(&timestamp.to_le_bytes());
+        base_block.extend_from_slice(&MAJOR_VERSION.to_le_bytes());
+        base_block.extend_from_slice(&MINOR_VERSION.to_le_bytes());
+        base_block.extend_from_slice(&FILE_TYPE_PRIMARY.to_le_bytes());
+        base_block.extend_from_slice(&FILE_FORMAT_DIRECT_MEMORY_LOAD.to_le_bytes());
+        base_block.extend_from_slice(&root_offset.to_le_bytes());
+        base_block.extend_from_slice(&hive_bins_data_size.to_le_bytes());
+        base_block.extend_from_slice(&CLUSTERING_FACTOR.to_le_bytes());
+        let mut filename = util::to_utf16_le_string(&self.filename);
+        filename.resize(FILENAME_LEN, 0);
+        filename[FILENAME_LEN - 2..].fill(0); // keep the null terminator if the name is truncated
+        base_block.extend_from_slice(&filename);
+        base_block.resize(BASE_BLOCK_SIZE, 0);
+        BaseBlockBase::update_checksum(&mut base_block)
+            .expect("base_block is BASE_BLOCK_SIZE bytes");
+        base_block
+    }
+}
+
+const BASE_BLOCK_SIZE: usize = 4096;
+/// Sub key lists longer than this are split up and referenced from an ri list
+const MAX_SUB_KEY_LIST_LEN: usize = 1012;
+
+/// Lays out cells in hive bins. Offsets are relative to the start of the first hive bin, as they are in the file.
+struct CellWriter {
+    hive_bins: Vec<u8>,
+    /// Hive bins with room left, as (offset of the unused space, end of the hive bin)
+    open_hive_bins: Vec<(usize, usize)>,
+    timestamp: u64,
+}
+
+impl CellWriter {
+    /// Cells are placed in the first of this many hive bins with room for them, so a large cell doesn't waste the rest
+    /// of the hive bin before it
+    const MAX_OPEN_HIVE_BINS: usize = 8;
+
+    fn new(timestamp: u64) -> Self {
+        Self {
+            hive_bins: Vec::new(),
+            open_hive_bins: Vec::new(),
+            timestamp,
+        }
+    }
+
+    /// Adds an allocated cell containing `data` and returns its offset
+    fn add_cell(&mut self, data: &[u8]) -> u32 {
+        let offset = self.allocate(data.len());
+        self.hive_bins[offset + 4..offset + 4 + data.len()].copy_from_slice(data);
+        offset as u32
+    }
+
+    /// Reserves an allocated cell large enough for `data_len` bytes and returns its offset
+    fn allocate(&mut self, data_len: usize) -> usize {
+        let cell_size = align(data_len + 4, HiveBinCell::ALIGNMENT);
+        let index = match self
+            .open_hive_bins
+            .iter()
+            .position(|(unused, end)| end - unused >= cell_size)
+        {
+            Some(index) => index,
+            None => self.add_hive_bin(cell_size),
+        };
+        let (offset, end) = self.open_hive_bins[index];
+        if offset + cell_size == end {
+            self.open_hive_bins.remove(index);
+        } else {
+            self.open_hive_bins[index].0 += cell_size;
+        }
+        self.hive_bins[offset..offset + 4].copy_from_slice(&(-(cell_size as i32)).to_le_bytes());
+        offset
+    }
+
+    fn write_at(&mut self, offset: usize, data: &[u8]) {
+        self.hive_bins[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    /// Appends a hive bin big enough to hold a cell of `cell_size` bytes. Returns its index in `open_hive_bins`.
+    fn add_hive_bin(&mut self, cell_size: usize) -> usize {
+        if self.open_hive_bins.len() == Self::MAX_OPEN_HIVE_BINS {
+            let fullest = (0..self.open_hive_bins.len())
+                .min_by_key(|&index| self.open_hive_bins[index].1 - self.open_hive_bins[index].0)
+                .expect("open_hive_bins isn't empty");
+            let (unused, end) = self.open_hive_bins.remove(fullest);
+            self.add_free_cell(unused, end);
+        }
+
+        let start = self.hive_bins.len();
+        let size = align(HiveBinHeader::SIZE + cell_size, HiveBinHeader::ALIGNMENT);
+        self.hive_bins.extend_from_slice(b"hbin");
+        self.hive_bins
+            .extend_from_slice(&(start as u32).to_le_bytes());
+        self.hive_bins
+            .extend_from_slice(&(size as u32).to_le_bytes());
+        self.hive_bins.extend_from_slice(&[0; 8]); // unknown1, unknown2
+
+        // Only the first hive bin's timestamp is meaningful (a backup of the base block's)
+        let timestamp = if start == 0 { self.timestamp } else { 0 };
+        self.hive_bins.extend_from_slice(&timestamp.to_le_bytes());
+        self.hive_bins.extend_from_slice(&[0; 4]); // spare
+        self.hive_bins.resize(start + size, 0);
+        self.open_hive_bins
+            .push((start + HiveBinHeader::SIZE, start + size));
+        self.open_hive_bins.len() - 1
+    }
+
+    /// Marks the unused space from `offset` to `end` as a free cell
+    fn add_free_cell(&mut self, offset: usize, end: usize) {
+        self.hive_bins[offset..offset + 4].copy_from_slice(&((end - offset) as i32).to_le_bytes());
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.hive_bins.is_empty() {
+            self.add_hive_bin(0);
+        }
+        for (unused, end) in std::mem::take(&mut self.open_hive_bins) {
+            self.add_free_cell(unused, end);
+        }
+        self.hive_bins
+    }
+
+    /// Writes `key` and everything beneath it. Returns the offset of the key's nk cell.
+    fn write_key(
+        &mut self,
+        security: &mut SecurityCells,
+        key: &WriterKey,
+        parent_offset: u32,
+        parent_sk_offset: u32,
+        is_root: bool,
+    ) -> Result<u32, Error> {
+        let (name, is_ascii_name) = encode_name(&key.name);
+        let nk_offset = self.allocate(CellKeyNode::HEADER_SIZE + name.len());
+
+        let sk_offset = match &key.security_descriptor {
+            Some(descriptor) => security.get_or_add(self, descriptor),
+            None if is_root => security.get_or_add(self, &default_security_descriptor()),
+            None => parent_sk_offset,
+        };
+        security.add_reference(sk_offset);
+
+        // Unlike other strings, the class name isn't null terminated
+        let class_name: Option<Vec<u8>> = key
+            .class_name
+            .as_ref()
+            .map(|c| c.encode_utf16().flat_map(u16::to_le_bytes).collect());
+        let class_name_offset = class_name.as_ref().map_or(NO_OFFSET, |c| self.add_cell(c));
+
+        let mut value_offsets = Vec::with_capacity(key.values.len());
+        for value in &key.values {
+            value_offsets.push(self.write_value(value)?);
+        }
+        let values_list_offset = match value_offsets.is_empty() {
+            true => NO_OFFSET,
+            false => self.add_cell(&offsets_to_bytes(&value_offsets)),
+        };
+
+        let mut sub_keys: Vec<&WriterKey> = key.sub_keys.iter().collect();
+        sub_keys.sort_by_cached_key(|sub_key| sub_key.name.to_uppercase());
+        let mut sub_key_items = Vec::with_capacity(sub_keys.len());
+        for sub_key in &sub_keys {
+            let offset = self.write_key(security, sub_key, nk_offset as u32, sk_offset, false)?;
+            sub_key_items.push((offset, sub_key_list_lh::name_hash(&sub_key.name)));
+        }
+        let sub_keys_list_offset = self.write_sub_key_list(&sub_key_items)?;
+
+        let mut flags = key.flags
+            - (KeyNodeFlags::KEY_HIVE_ENTRY
+                | KeyNodeFlags::KEY_NO_DELETE
+                | KeyNodeFlags::KEY_COMP_NAME);
+        if is_root {
+            flags |= KeyNodeFlags::KEY_HIVE_ENTRY | KeyNodeFlags::KEY_NO_DELETE;
+        }
+        if is_ascii_name {
+            flags |= KeyNodeFlags::KEY_COMP_NAME;
+        }
+        let largest =
+            |sizes: &mut dyn Iterator<Item = usize>| sizes.max().unwrap_or_default() as u32;
+
+        let mut nk = Vec::with_capacity(CellKeyNode::HEADER_SIZE + name.len());
+        nk.extend_from_slice(b"nk");
+        nk.extend_from_slice(&flags.bits().to_le_bytes());
+        nk.extend_from_slice(
+            &util::get_filetime_from_date_time(key.last_key_written_date_and_time).to_le_bytes(),
+        );
+        nk.extend_from_slice(&0u32.to_le_bytes()); // access bits
+        nk.extend_from_slice(&parent_offset.to_le_bytes());
+        nk.extend_from_slice(&u32::try_from(sub_keys.len())?.to_le_bytes());
+        nk.extend_from_slice(&0u32.to_le_bytes()); // volatile sub keys are never written to disk
+        nk.extend_from_slice(&sub_keys_list_offset.to_le_bytes());
+        nk.extend_from_slice(&NO_OFFSET.to_le_bytes());
+        nk.extend_from_slice(&u32::try_from(value_offsets.len())?.to_le_bytes());
+        nk.extend_from_slice(&values_list_offset.to_le_bytes());
+        nk.extend_from_slice(&sk_offset.to_le_bytes());
+        nk.extend_from_slice(&class_name_offset.to_le_bytes());
+        // The largest name sizes are in bytes, treating the names as UTF-16LE strings
+        nk.extend_from_slice(
+            &largest(&mut sub_keys.iter().map(|k| k.name.encode_utf16().count() * 2)).to_le_bytes(),
+        );
+        nk.extend_from_slice(
+            &largest(&mut sub_keys.iter().map(|k| {
+                k.class_name
+                    .as_ref()
+                    .map_or(0, |c| c.encode_utf16().count() * 2)
+            }))
+            .to_le_bytes(),
+        );
+        nk.extend_from_slice(
+            &largest(&mut key.values.iter().map(|v| v.name.encode_utf16().count() * 2))
+                .to_le_bytes(),
+        );
+        nk.extend_from_slice(&largest(&mut key.values.iter().map(|v| v.data.len())).to_le_bytes());
+        nk.extend_from_slice(&0u32.to_le_bytes()); // work var
+        nk.extend_from_slice(&u16::try_from(name.len())?.to_le_bytes());
+        nk.extend_from_slice(&u16::try_from(class_name.map_or(0, |c| c.len()))?.to_le_bytes());
+        nk.extend_from_slice(&name);
+        self.write_at(nk_offset + 4, &nk);
+        Ok(nk_offset as u32)
+    }
+
+    /// Writes an lh list for `items` (tuples of nk offset and name hash), or an ri list of lh lists if there are too many
+    /// items for one list. Returns the offset of the list.
+    fn write_sub_key_list(&mut self, items: &[(u32, u32)]) -> Result<u32, Error> {
+        if items.is_empty() {
+            return Ok(NO_OFFSET);
+        }
+        let mut lists = Vec::new();
+        for chunk in items.chunks(MAX_SUB_KEY_LIST_LEN) {
+            let mut lh = Vec::with_capacity(4 + chunk.len() * 8);
+            lh.extend_from_slice(b"lh");
+            lh.extend_from_slice(&u16::try_from(chunk.len())?.to_le_bytes());
+            for (offset, hash) in chunk {
+                lh.extend_from_slice(&offset.to_le_bytes());
+                lh.extend_from_slice(&hash.to_le_bytes());
+            }
+            lists.push(self.add_cell(&lh));
+        }
+        if lists.len() == 1 {
+            return Ok(lists[0]);
+        }
+        let mut ri = Vec::with_capacity(4 + lists.len() * 4);
+        ri.extend_from_slice(b"ri");
+        ri.extend_from_slice(&u16::try_from(lists.len())?.to_le_bytes());
+        ri.extend(offsets_to_bytes(&lists));
+        Ok(self.add_cell(&ri))
+    }
+
+    /// Writes a vk cell and its data. Returns the offset of the vk cell.
+    fn write_value(&mut self, value: &WriterValue) -> Result<u32, Error> {
+        const MAX_RESIDENT_DATA_SIZE: usize = 4;
+
+        let (name, is_ascii_name) = encode_name(&value.name);
+        let data_size = u32::try_from(value.data.len())?;
+        if data_size & CellKeyValue::DATA_IS_RESIDENT_MASK != 0 {
+            return Err(Error::buffer("write_value: value data is too large"));
+        }
+        let (data_size_raw, data_offset) = if value.data.len() <= MAX_RESIDENT_DATA_SIZE {
+            let mut resident = [0; MAX_RESIDENT_DATA_SIZE];
+            resident[..value.data.len()].copy_from_slice(&value.data);
+            (
+                data_size | CellKeyValue::DATA_IS_RESIDENT_MASK,
+                u32::from_le_bytes(resident),
+            )
+        } else if data_size > CellKeyValue::BIG_DATA_SIZE_THRESHOLD {
+            (data_size, self.write_big_data(&value.data)?)
+        } else {
+            (data_size, self.add_cell(&value.data))
+        };
+
+        let flags = match is_ascii_name {
+            true => CellKeyValueFlags::VALUE_COMP_NAME_ASCII,
+            false => CellKeyValueFlags::empty(),
+        };
+        let mut vk = Vec::with_capacity(20 + name.len());
+        vk.extend_from_slice(b"vk");
+        vk.extend_from_slice(&u16::try_from(name.len())?.to_le_bytes());
+        vk.extend_from_slice(&data_size_raw.to_le_bytes());
+        vk.extend_from_slice(&data_offset.to_le_bytes());
+        vk.extend_from_slice(&value.data_type_raw.to_le_bytes());
+        vk.extend_from_slice(&flags.bits().to_le_bytes());
+        vk.extend_from_slice(&0u16.to_le_bytes()); // spare
+        vk.extend_from_slice(&name);
+        Ok(self.add_cell(&vk))
+    }
+
+    /// Writes data larger than `BIG_DATA_SIZE_THRESHOLD` as a db cell referencing a list of data segments
+    fn write_big_data(&mut self, data: &[u8]) -> Result<u32, Error> {
+        let segments: Vec<u32> = data
+            .chunks(CellKeyValue::BIG_DATA_SIZE_THRESHOLD as usize)
+            .map(|segment| self.add_cell(segment))
+            .collect();
+        let segment_list_offset = self.add_cell(&offsets_to_bytes(&segments));
+        let mut db = Vec::with_capacity(8);
+        db.extend_from_slice(b"db");
+        db.extend_from_slice(&u16::try_from(segments.len())?.to_le_bytes());
+        db.extend_from_slice(&segment_list_offset.to_le_bytes());
+        Ok(self.add_cell(&db))
+    }
+}
+
+/// Tracks the sk cells written so far. Identical descriptors share a cell; the cells are linked into a list
+/// (and their reference counts set) once all keys have been written.
+#[derive(Default)]
+struct SecurityCells {
+    offsets: HashMap<Vec<u8>, u32>,
+    cells: Vec<(u32, Vec<u8>, u32)>, // (offset, descriptor, reference count)
+}
+
+impl SecurityCells {
+    const SK_HEADER_SIZE: usize = 20;
+
+    fn get_or_add(&mut self, cells: &mut CellWriter, descriptor: &[u8]) -> u32 {
+        if let Some(offset) = self.offsets.get(descriptor) {
+            return *offset;
+        }
+        let offset = cells.allocate(Self::SK_HEADER_SIZE + descriptor.len()) as u32;
+        self.offsets.insert(descriptor.to_vec(), offset);
+        self.cells.push((offset, descriptor.to_vec(), 0));
+        offset
+    }
+
+    fn add_reference(&mut self, offset: u32) {
+        if let Some(cell) = self.cells.iter_mut().find(|cell| cell.0 == offset) {
+            cell.2 += 1;
+        }
+    }
+
+    fn write(&self, cells: &mut CellWriter) {
+        for (index, (offset, descriptor, reference_count)) in self.cells.iter().enumerate() {
+            let flink = self.cells[(index + 1) % self.cells.len()].0;
+            let blink = self.cells[(index + self.cells.len() - 1) % self.cells.len()].0;
+            let mut sk = Vec::with_capacity(Self::SK_HEADER_SIZE + descriptor.len());
+            sk.extend_from_slice(b"sk");
+            sk.extend_from_slice(&0u16.to_le_bytes()); // reserved
+            sk.extend_from_slice(&flink.to_le_bytes());
+            sk.extend_from_slice(&blink.to_le_bytes());
+            sk.extend_from_slice(&reference_count.to_le_bytes());
+            sk.extend_from_slice(&(descriptor.len() as u32).to_le_bytes());
+            sk.extend_from_slice(descriptor);
+            cells.write_at(*offset as usize + 4, &sk);
+        }
+    }
+}
+
+fn align(len: usize, alignment: usize) -> usize {
+    len.div_ceil(alignment) * alignment
+}
+
+fn offsets_to_bytes(offsets: &[u32]) -> Vec<u8> {
+    offsets
+        .iter()
+        .flat_map(|offset| offset.to_le_bytes())
+        .collect()
+}
+
+/// Returns the bytes to store for a key or value name, and whether they are ASCII (otherwise they are UTF-16LE)
+fn encode_name(name: &str) -> (Vec<u8>, bool) {
+    if name.is_ascii() {
+        (name.as_bytes().to_vec(), true)
+    } else {
+        (
+            name.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            false,
+        )
+    }
+}
+
+/// A self-relative security descriptor with a DACL granting full control to Administrators and SYSTEM
+/// (O:BAG:SYD:(A;CI;KA;;;BA)(A;CI;KA;;;SY))
+fn default_security_descriptor() -> Vec<u8> {
+    const SE_DACL_PRESENT: u16 = 0x0004;
+    const SE_SELF_RELATIVE: u16 = 0x8000;
+    const CONTAINER_INHERIT_ACE: u8 = 0x02;
+    const KEY_ALL_ACCESS: u32 = 0x000F003F;
+    const ADMINISTRATORS: [u8; 16] = [1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 0x20, 2, 0, 0];
+    const SYSTEM: [u8; 12] = [1, 1, 0, 0, 0, 0, 0, 5, 18, 0, 0, 0];
+
+    let ace = |sid: &[u8]| -> Vec<u8> {
+        let mut ace = vec![0, CONTAINER_INHERIT_ACE]; // ACCESS_ALLOWED_ACE_TYPE
+        ace.extend_from_slice(&((8 + sid.len()) as u16).to_le_bytes());
+        ace.extend_from_slice(&KEY_ALL_ACCESS.to_le_bytes());
+        ace.extend_from_slice(sid);
+        ace
+    };
+    let aces = [ace(&ADMINISTRATORS), ace(&SYSTEM)].concat();
+    let mut dacl = vec![2, 0]; // ACL_REVISION
+    dacl.extend_from_slice(&((8 + aces.len()) as u16).to_le_bytes());
+    dacl.extend_from_slice(&2u16.to_le_bytes());
+    dacl.extend_from_slice(&0u16.to_le_bytes());
+    dacl.extend(aces);
+
+    const HEADER_SIZE: usize = 20;
+    let dacl_offset = HEADER_SIZE;
+    let owner_offset = dacl_offset + dacl.len();
+    let group_offset = owner_offset + ADMINISTRATORS.len();
+    let mut descriptor = vec![1, 0]; // revision
+    descriptor.extend_from_slice(&(SE_SELF_RELATIVE | SE_DACL_PRESENT).to_le_bytes());
+    descriptor.extend_from_slice(&(owner_offset as u32).to_le_bytes());
+    descriptor.extend_from_slice(&(group_offset as u32).to_le_bytes());
+    descriptor.extend_from_slice(&0u32.to_le_bytes()); // no SACL
+    descriptor.extend_from_slice(&(dacl_offset as u32).to_le_bytes());
+    descriptor.extend(dacl);
+    descriptor.extend_from_slice(&ADMINISTRATORS);
+    descriptor.extend_from_slice(&SYSTEM);
+    descriptor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    fn parse(hive: Vec<u8>) -> Parser {
+        ParserBuilder::from_file(Cursor::new(hive)).build().unwrap()
+    }
+
+    #[test]
+    fn test_write_hive() {
+        let mut root = WriterKey::new("ROOT");
+        let key = root.get_or_add_sub_key("Software\\Vendor");
+        key.class_name = Some("VendorClass".to_string());
+        key.set_value(WriterValue::from_content(
+            "Name",
+            CellKeyValueDataTypes::REG_SZ,
+            &CellValue::String("Notatin".to_string()),
+        ))
+        .set_value(WriterValue::from_content(
+            "Count",
+            CellKeyValueDataTypes::REG_DWORD,
+            &CellValue::U32(42),
+        ))
+        .set_value(WriterValue::from_content(
+            "",
+            CellKeyValueDataTypes::REG_QWORD,
+            &CellValue::U64(u64::MAX),
+        ))
+        .set_value(WriterValue::new(
+            "Empty",
+            CellKeyValueDataTypes::REG_NONE,
+            vec![],
+        ))
+        .set_value(WriterValue::new(
+            "Big",
+            CellKeyValueDataTypes::REG_BIN,
+            (0..40000).map(|i| i as u8).collect(),
+        ));
+        root.get_or_add_sub_key("Software\\Ünïcödé")
+            .set_value(WriterValue::from_content(
+                "Multi",
+                CellKeyValueDataTypes::REG_MULTI_SZ,
+                &CellValue::MultiString(vec!["a".to_string(), "β".to_string()]),
+            ));
+        let many = root.get_or_add_sub_key("Many");
+        for i in 0..1500 {
+            many.get_or_add_sub_key(&format!("Key{}", i));
+        }
+
+        let mut parser = parse(HiveWriter::new(root).to_bytes().unwrap());
+        assert_eq!(None, parser.get_parse_logs().get());
+        assert_eq!((1505, 6), parser.count_all_keys_and_values(None));
+
+        let mut key = parser.get_key("Software\\Vendor", false).unwrap().unwrap();
+        assert_eq!(Some("VendorClass".to_string()), key.get_class_name(&parser));
+        let content = |name: &str| key.get_value(name).unwrap().get_content();
+        assert_eq!(
+            (CellValue::String("Notatin".to_string()), None),
+            content("Name")
+        );
+        assert_eq!((CellValue::U32(42), None), content("Count"));
+        assert_eq!((CellValue::U64(u64::MAX), None), content(""));
+        assert_eq!((CellValue::Binary(vec![]), None), content("Empty"));
+        assert_eq!(
+            (
+                CellValue::Binary((0..40000).map(|i| i as u8).collect()),
+                None
+            ),
+            content("Big")
+        );
+        assert_eq!(
+            default_security_descriptor(),
+            key.get_security_descriptor_bytes(&parser).unwrap()
+        );
+        let parent = parser.get_parent_key(&mut key).unwrap().unwrap();
+        assert_eq!("Software", parent.key_name);
+
+        let key = parser.get_key("software\\Ünïcödé", false).unwrap().unwrap();
+        assert_eq!(
+            (
+                CellValue::MultiString(vec!["a".to_string(), "β".to_string()]),
+                None
+            ),
+            key.get_value("Multi").unwrap().get_content()
+        );
+        assert!(parser.get_key("Many\\Key1499", false).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_copy_hive() {
+        let mut original = ParserBuilder::from_path("test_data/NTUSER.DAT")
+            .build()
+            .unwrap();
+        let writer = HiveWriter::from_parser(&mut original).unwrap();
+        let mut copy = parse(writer.to_bytes().unwrap());
+        assert_eq!(None, copy.get_parse_logs().get());
+
+        let contents = |parser: &Parser| {
+            let mut keys = BTreeMap::new();
+            for key in ParserIterator::new(parser).iter() {
+                let values: Vec<_> = key
+                    .value_iter()
+                    .map(|value| (value.detail.value_name(), value.get_content()))
+                    .collect();
+                keys.insert(
+                    key.path.clone(),
+                    (key.last_key_written_date_and_time(), values),
+                );
+            }
+            keys
+        };
+        let copied = contents(&copy);
+        assert_eq!(2853, copied.len());
+        assert_eq!(contents(&original), copied);
+
+        let mut original_key = original
+            .get_key("Control Panel\\Accessibility\\MouseKeys", false)
+            .unwrap()
+            .unwrap();
+        let mut copy_key = copy
+            .get_key("Control Panel\\Accessibility\\MouseKeys", false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            original_key.get_security_descriptor_bytes(&original),
+            copy_key.get_security_descriptor_bytes(&copy)
+        );
+        assert_eq!(
+            WriterKey::from_parser_key(&mut original, &mut original_key).unwrap(),
+            WriterKey::from_parser_key(&mut copy, &mut copy_key).unwrap()
+        );
+    }
+}
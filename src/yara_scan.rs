@@ -0,0 +1,166 @@
+/*
+ * Copyright 2023 Aon Cyber Solutions
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Applies compiled Yara rules to value data during iteration (see `ParserIterator::yara_matches`), so registry contents
+//! can be scanned without exporting them to disk first. Enabled with the `yara` feature.
+
+use crate::err::Error;
+use serde::Serialize;
+use yara::Rules;
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct YaraStringMatch {
+    /// The string's identifier in the rule (ex: `$mz`)
+    pub identifier: String,
+    /// Offset of the match within the value's data
+    pub offset: usize,
+    pub length: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct YaraMatch {
+    pub rule: String,
+    pub namespace: String,
+    pub tags: Vec<String>,
+    pub strings: Vec<YaraStringMatch>,
+}
+
+/// Returns the rules in `rules` that match `data`. `timeout` is in seconds (0 for no timeout).
+pub fn scan(rules: &Rules, data: &[u8], timeout: i32) -> Result<Vec<YaraMatch>, Error> {
+    Ok(rules
+        .scan_mem(data, timeout)?
+        .into_iter()
+        .map(|rule| YaraMatch {
+            rule: rule.identifier.to_string(),
+            namespace: rule.namespace.to_string(),
+            tags: rule.tags.iter().map(|tag| tag.to_string()).collect(),
+            strings: rule
+                .strings
+                .iter()
+                .flat_map(|string| {
+                    string.matches.iter().map(move |m| YaraStringMatch {
+                        identifier: string.identifier.to_string(),
+                        offset: m.offset,
+                        length: m.length,
+                    })
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_key_value::CellKeyValueDataTypes;
+    use crate::parser::ParserIterator;
+    use crate::parser_builder::ParserBuilder;
+    use crate::writer::{HiveWriter, WriterKey, WriterValue};
+    use yara::Compiler;
+
+    const RULES: &str = r#"
+        rule powershell_encoded : lolbin {
+            strings:
+                $enc = "-enc" wide ascii nocase
+            condition:
+                $enc
+        }
+        rule mz_header {
+            strings:
+                $mz = "MZ"
+            condition:
+                $mz at 0
+        }
+    "#;
+
+    fn compile_rules() -> Rules {
+        Compiler::new()
+            .unwrap()
+            .add_rules_str(RULES)
+            .unwrap()
+            .compile_rules()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_scan() {
+        let rules = compile_rules();
+        assert_eq!(
+            vec![YaraMatch {
+                rule: "mz_header".to_string(),
+                namespace: "default".to_string(),
+                tags: vec![],
+                strings: vec![YaraStringMatch {
+                    identifier: "$mz".to_string(),
+                    offset: 0,
+                    length: 2
+                }]
+            }],
+            scan(&rules, b"MZ\x90\0", 0).unwrap()
+        );
+        assert!(scan(&rules, b"xMZ", 0).unwrap().is_empty());
+        assert!(scan(&rules, &[], 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_yara_matches() {
+        let mut root = WriterKey::new("ROOT");
+        root.get_or_add_sub_key("Software\\Microsoft\\Windows\\CurrentVersion\\Run")
+            .set_value(WriterValue::new(
+                "Updater",
+                CellKeyValueDataTypes::REG_SZ,
+                crate::util::to_utf16_le_string("powershell.exe -ENC SQBFAFgA"),
+            ));
+        root.get_or_add_sub_key("Software")
+            .set_value(WriterValue::new(
+                "Benign",
+                CellKeyValueDataTypes::REG_SZ,
+                crate::util::to_utf16_le_string("notatin"),
+            ));
+        let hive = HiveWriter::new(root).to_bytes().unwrap();
+        let parser = ParserBuilder::from_file(std::io::Cursor::new(hive))
+            .build()
+            .unwrap();
+
+        let rules = compile_rules();
+        let matches: Vec<_> = ParserIterator::new(&parser)
+            .yara_matches(&rules, 0)
+            .map(|result| {
+                let (key_path, value, matches) = result.unwrap();
+                (
+                    key_path.to_string(),
+                    value.detail.value_name(),
+                    matches
+                        .into_iter()
+                        .map(|m| (m.rule, m.tags, m.strings[0].offset))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![(
+                "\\ROOT\\Software\\Microsoft\\Windows\\CurrentVersion\\Run".to_string(),
+                "Updater".to_string(),
+                vec![(
+                    "powershell_encoded".to_string(),
+                    vec!["lolbin".to_string()],
+                    30
+                )]
+            )],
+            matches
+        );
+    }
+}